@@ -10,7 +10,7 @@ fn main() {
 
     println!("cargo::rerun-if-changed=build.rs");
     
-	let mut registry = Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, &["GL_ARB_parallel_shader_compile"]);
+	let mut registry = Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, &["GL_ARB_parallel_shader_compile", "GL_ARB_sparse_texture"]);
 
 	registry.cmds.retain(should_keep_cmd);
 
@@ -18,8 +18,10 @@ fn main() {
 }
 
 static ALLOWED_GET_FUNCTIONS: &[&str] = &[
+	"GetFloatv",
 	"GetIntegerv",
 	"GetInternalformativ",
+	"GetNamedBufferSubData",
 	"GetObjectLabel",
 	"GetProgramBinary",
 	"GetProgramInfoLog",
@@ -43,9 +45,12 @@ static ALLOWED_GET_FUNCTIONS: &[&str] = &[
 	"GetQueryObjectiv",
 	"GetQueryObjectui64v",
 	"GetQueryObjectuiv",
+	"GetShaderInfoLog",
+	"GetShaderiv",
 	"GetString",
 	"GetStringi",
 	"GetSynciv",
+	"GetTextureParameteriv",
 	"GetTextureSubImage",
 ];
 
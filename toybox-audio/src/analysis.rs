@@ -0,0 +1,243 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+use super::{Configuration, Provider};
+
+
+/// Number of samples per spectrum analysis. Results have half as many bins.
+pub const SPECTRUM_SIZE: usize = 1024;
+pub const SPECTRUM_BINS: usize = SPECTRUM_SIZE / 2;
+
+
+/// Wraps a provider, measuring levels and spectrum of everything it produces.
+/// Results are published to any number of [`AnalysisReader`]s without locking, so are safe to read from the main thread.
+pub struct AnalysisTap<P> {
+	inner: P,
+	analyser: Analyser,
+}
+
+impl<P: Provider> AnalysisTap<P> {
+	pub fn new(inner: P) -> (Self, AnalysisReader) {
+		let analyser = Analyser::new();
+		let reader = analyser.reader();
+		(AnalysisTap { inner, analyser }, reader)
+	}
+
+	pub fn inner(&self) -> &P { &self.inner }
+	pub fn inner_mut(&mut self) -> &mut P { &mut self.inner }
+}
+
+impl<P: Provider> Provider for AnalysisTap<P> {
+	fn on_configuration_changed(&mut self, configuration: Option<Configuration>) {
+		self.analyser.set_configuration(configuration);
+		self.inner.on_configuration_changed(configuration);
+	}
+
+	fn fill_buffer(&mut self, buffer: &mut [f32]) {
+		self.inner.fill_buffer(buffer);
+		self.analyser.process(buffer);
+	}
+}
+
+
+
+/// Main thread view of analysis results. Values may be up to a buffer out of date, and the spectrum may tear
+/// between bins while being updated, which is fine for visualisation.
+#[derive(Clone)]
+pub struct AnalysisReader {
+	shared: Arc<SharedAnalysis>,
+}
+
+impl AnalysisReader {
+	/// Peak absolute sample value of the most recent buffer, across all channels.
+	pub fn peak(&self) -> f32 {
+		f32::from_bits(self.shared.peak.load(Ordering::Relaxed))
+	}
+
+	/// RMS level of the most recent buffer, across all channels.
+	pub fn rms(&self) -> f32 {
+		f32::from_bits(self.shared.rms.load(Ordering::Relaxed))
+	}
+
+	pub fn peak_db(&self) -> f32 { amplitude_to_db(self.peak()) }
+	pub fn rms_db(&self) -> f32 { amplitude_to_db(self.rms()) }
+
+	/// Copies magnitudes of the most recent spectrum into `bins`, which will be resized to [`SPECTRUM_BINS`].
+	/// Bin `i` is centered on `i * sample_rate / SPECTRUM_SIZE` Hz.
+	pub fn read_spectrum(&self, bins: &mut Vec<f32>) {
+		bins.clear();
+		bins.extend(self.shared.spectrum.iter().map(|bin| f32::from_bits(bin.load(Ordering::Relaxed))));
+	}
+
+	/// Sample rate that the spectrum was last computed at, or 0 if no stream is active.
+	pub fn sample_rate(&self) -> u32 {
+		self.shared.sample_rate.load(Ordering::Relaxed)
+	}
+
+	/// Incremented every time a new spectrum is published.
+	pub fn spectrum_generation(&self) -> usize {
+		self.shared.spectrum_generation.load(Ordering::Relaxed)
+	}
+}
+
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+	20.0 * amplitude.max(1.0e-5).log10()
+}
+
+
+
+struct SharedAnalysis {
+	peak: AtomicU32,
+	rms: AtomicU32,
+	spectrum: Box<[AtomicU32]>,
+	spectrum_generation: AtomicUsize,
+	sample_rate: AtomicU32,
+}
+
+
+/// Audio thread side of analysis.
+pub(crate) struct Analyser {
+	shared: Arc<SharedAnalysis>,
+	channels: usize,
+
+	window: Vec<f32>,
+	samples: Vec<f32>,
+	fft_real: Vec<f32>,
+	fft_imag: Vec<f32>,
+}
+
+impl Analyser {
+	pub fn new() -> Analyser {
+		let shared = Arc::new(SharedAnalysis {
+			peak: AtomicU32::new(0),
+			rms: AtomicU32::new(0),
+			spectrum: (0..SPECTRUM_BINS).map(|_| AtomicU32::new(0)).collect(),
+			spectrum_generation: AtomicUsize::new(0),
+			sample_rate: AtomicU32::new(0),
+		});
+
+		// Hann window
+		let window = (0..SPECTRUM_SIZE)
+			.map(|i| {
+				let phase = i as f32 / (SPECTRUM_SIZE - 1) as f32;
+				0.5 - 0.5 * (std::f32::consts::TAU * phase).cos()
+			})
+			.collect();
+
+		Analyser {
+			shared,
+			channels: 1,
+
+			window,
+			samples: Vec::with_capacity(SPECTRUM_SIZE),
+			fft_real: vec![0.0; SPECTRUM_SIZE],
+			fft_imag: vec![0.0; SPECTRUM_SIZE],
+		}
+	}
+
+	pub fn reader(&self) -> AnalysisReader {
+		AnalysisReader { shared: self.shared.clone() }
+	}
+
+	pub fn set_configuration(&mut self, configuration: Option<Configuration>) {
+		self.channels = configuration.map_or(1, |config| config.device_channels.max(1));
+		self.samples.clear();
+
+		let sample_rate = configuration.map_or(0, |config| config.sample_rate);
+		self.shared.sample_rate.store(sample_rate, Ordering::Relaxed);
+	}
+
+	pub fn process(&mut self, buffer: &[f32]) {
+		if buffer.is_empty() {
+			return
+		}
+
+		let peak = buffer.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+		let mean_square = buffer.iter().map(|sample| sample * sample).sum::<f32>() / buffer.len() as f32;
+
+		self.shared.peak.store(peak.to_bits(), Ordering::Relaxed);
+		self.shared.rms.store(mean_square.sqrt().to_bits(), Ordering::Relaxed);
+
+		for frame in buffer.chunks_exact(self.channels) {
+			let mono = frame.iter().sum::<f32>() / self.channels as f32;
+			self.samples.push(mono);
+
+			if self.samples.len() == SPECTRUM_SIZE {
+				self.publish_spectrum();
+				self.samples.clear();
+			}
+		}
+	}
+
+	fn publish_spectrum(&mut self) {
+		for (i, (&sample, &window)) in self.samples.iter().zip(&self.window).enumerate() {
+			self.fft_real[i] = sample * window;
+			self.fft_imag[i] = 0.0;
+		}
+
+		fft_in_place(&mut self.fft_real, &mut self.fft_imag);
+
+		// Normalise so that a full scale sine gives roughly 1.0 in its bin - the hann window halves the coherent gain.
+		let scale = 4.0 / SPECTRUM_SIZE as f32;
+
+		for (bin, (re, im)) in self.shared.spectrum.iter().zip(self.fft_real.iter().zip(&self.fft_imag)) {
+			let magnitude = (re*re + im*im).sqrt() * scale;
+			bin.store(magnitude.to_bits(), Ordering::Relaxed);
+		}
+
+		self.shared.spectrum_generation.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+
+/// Iterative radix-2 Cooley-Tukey. Length must be a power of two.
+fn fft_in_place(real: &mut [f32], imag: &mut [f32]) {
+	let n = real.len();
+	debug_assert!(n.is_power_of_two());
+	debug_assert_eq!(n, imag.len());
+
+	// Bit reversal permutation
+	let mut j = 0;
+	for i in 1..n {
+		let mut bit = n >> 1;
+		while j & bit != 0 {
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+
+		if i < j {
+			real.swap(i, j);
+			imag.swap(i, j);
+		}
+	}
+
+	let mut length = 2;
+	while length <= n {
+		let angle = -std::f32::consts::TAU / length as f32;
+		let (w_imag, w_real) = angle.sin_cos();
+
+		for start in (0..n).step_by(length) {
+			let (mut cur_real, mut cur_imag) = (1.0f32, 0.0f32);
+
+			for k in 0..length/2 {
+				let a = start + k;
+				let b = a + length/2;
+
+				let t_real = real[b] * cur_real - imag[b] * cur_imag;
+				let t_imag = real[b] * cur_imag + imag[b] * cur_real;
+
+				real[b] = real[a] - t_real;
+				imag[b] = imag[a] - t_imag;
+				real[a] += t_real;
+				imag[a] += t_imag;
+
+				let next_real = cur_real * w_real - cur_imag * w_imag;
+				cur_imag = cur_real * w_imag + cur_imag * w_real;
+				cur_real = next_real;
+			}
+		}
+
+		length <<= 1;
+	}
+}
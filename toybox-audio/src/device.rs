@@ -3,19 +3,37 @@ use anyhow::Context as AnyhowContext;
 use tracing::instrument;
 
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::thread::{JoinHandle};
 
-use super::{Configuration, Provider};
+use super::{Configuration, Provider, StreamSettings, ChannelLayout, ChannelMapper, VariableResampler};
 
 
 // should be able to close and reopen streams dynamically, potentially on different devices
 //	any non-device state should be maintained
 // 	should be able to cope with different sample rates
 
+/// Used to size buffers used by the callback when the device doesn't have a fixed buffer size.
+pub(crate) const DEFAULT_MAX_FRAMES_PER_CALLBACK: usize = 4096;
+
+
 pub struct SharedStreamState {
 	pub provider: Mutex<Option<Box<dyn Provider>>>,
 	pub device_lost: AtomicBool,
+
+	/// f32 bits
+	pub master_volume: AtomicU32,
+
+	/// f32 bits. Output is resampled by this factor unless `preserve_pitch` is set. Zero pauses the provider.
+	pub time_scale: AtomicU32,
+	pub preserve_pitch: AtomicBool,
+
+	/// Applies mute and meters the final output. Only ever locked by the main thread while reconfiguring, so
+	/// contention is negligible.
+	pub master: Mutex<super::graph::NodeTap>,
+
+	pub last_callback_frames: AtomicU32,
+	pub last_callback_latency_us: AtomicU64,
 }
 
 
@@ -47,16 +65,16 @@ impl StreamState {
 }
 
 
-pub fn start_stream_build(stream_shared: Arc<SharedStreamState>) -> JoinHandle<anyhow::Result<ActiveStream>> {
+pub fn start_stream_build(stream_shared: Arc<SharedStreamState>, settings: StreamSettings) -> JoinHandle<anyhow::Result<ActiveStream>> {
 	std::thread::spawn(move || {
 		let host = cpal::default_host();
-		build_output_stream(&host, stream_shared.clone())
+		build_output_stream(&host, stream_shared.clone(), settings)
 	})
 }
 
 
 #[instrument(skip_all, name="audio build_output_stream")]
-fn build_output_stream(host: &cpal::Host, stream_shared: Arc<SharedStreamState>) -> anyhow::Result<ActiveStream> {
+fn build_output_stream(host: &cpal::Host, stream_shared: Arc<SharedStreamState>, settings: StreamSettings) -> anyhow::Result<ActiveStream> {
 	let device = host.default_output_device().context("no output device available")?;
 
 	log::info!("Selected audio device: {}", device.name().unwrap_or_else(|_| String::from("<no name>")));
@@ -64,17 +82,59 @@ fn build_output_stream(host: &cpal::Host, stream_shared: Arc<SharedStreamState>)
 	let supported_configs_range = device.supported_output_configs()
 		.context("error while querying configs")?;
 
+	let channel_layout = settings.channel_layout.unwrap_or_default();
+	let desired_channels = channel_layout.channel_count();
+
+	// Prefer an exact channel match, then the smallest config that can fit every channel, then the largest config
+	// that can't - which will need downmixing.
+	let channel_preference = |config: &cpal::SupportedStreamConfigRange| {
+		let channels = config.channels() as usize;
+		match channels.cmp(&desired_channels) {
+			std::cmp::Ordering::Equal => (2, 0),
+			std::cmp::Ordering::Greater => (1, usize::MAX - channels),
+			std::cmp::Ordering::Less => (0, channels),
+		}
+	};
+
 	// TODO(pat.m): support different sample formats
 	let supported_config = supported_configs_range
 		.filter(|config| config.sample_format().is_float())
-		.max_by(cpal::SupportedStreamConfigRange::cmp_default_heuristics)
+		.max_by(|a, b| channel_preference(a).cmp(&channel_preference(b))
+			.then_with(|| a.cmp_default_heuristics(b)))
 		.context("couldn't find a supported configuration")?;
 
-	let desired_sample_rate = 48000.clamp(supported_config.min_sample_rate().0, supported_config.max_sample_rate().0);
+	if supported_config.channels() as usize != desired_channels {
+		log::warn!("Audio device doesn't support {} output - mapping to {} channels",
+			channel_layout.name(), supported_config.channels());
+	}
+
+	let desired_sample_rate = settings.sample_rate.unwrap_or(48000)
+		.clamp(supported_config.min_sample_rate().0, supported_config.max_sample_rate().0);
 	let supported_config = supported_config
 		.with_sample_rate(cpal::SampleRate(desired_sample_rate));
 
-	let config = supported_config.into();
+	let desired_frames_per_buffer = settings.frames_per_buffer
+		.or_else(|| settings.target_latency.map(|latency| (latency.as_secs_f64() * desired_sample_rate as f64).ceil() as u32));
+
+	let frames_per_buffer = match (desired_frames_per_buffer, supported_config.buffer_size()) {
+		(Some(frames), &cpal::SupportedBufferSize::Range{min, max}) => Some(frames.clamp(min, max)),
+		(Some(_), cpal::SupportedBufferSize::Unknown) => {
+			log::warn!("Audio device doesn't report supported buffer sizes - using default buffer size");
+			None
+		}
+		(None, _) => None,
+	};
+
+	// Periods longer than this are processed in chunks, so that the callback never has to grow its buffers.
+	let max_frames_per_callback = match frames_per_buffer {
+		Some(frames) => frames as usize,
+		None => DEFAULT_MAX_FRAMES_PER_CALLBACK,
+	};
+
+	let mut config: cpal::StreamConfig = supported_config.into();
+	if let Some(frames) = frames_per_buffer {
+		config.buffer_size = cpal::BufferSize::Fixed(frames);
+	}
 
 	log::info!("Selected audio device config: {config:#?}");
 
@@ -83,15 +143,41 @@ fn build_output_stream(host: &cpal::Host, stream_shared: Arc<SharedStreamState>)
 		{
 			let stream_shared = Arc::clone(&stream_shared);
 
-			move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
+			let device_channels = config.channels as usize;
+			let provider_channels = channel_layout.channel_count();
+
+			let mapper = ChannelMapper::new(channel_layout, device_channels);
+			let mut provider_buffer = vec![0.0; max_frames_per_callback * provider_channels];
+			let mut varispeed = VariableResampler::with_channels(device_channels.max(provider_channels));
+
+			move |data: &mut [f32], info: &cpal::OutputCallbackInfo| {
 				let _span = tracing::trace_span!("audio provider callback").entered();
 
-				let mut provider_maybe = stream_shared.provider.lock().unwrap();
-				if let Some(provider) = &mut *provider_maybe {
-					provider.fill_buffer(data);
+				let frames = data.len() / device_channels;
+
+				let timestamp = info.timestamp();
+				let latency = timestamp.playback.duration_since(&timestamp.callback).unwrap_or_default();
+				stream_shared.last_callback_latency_us.store(latency.as_micros() as u64, Ordering::Relaxed);
+				stream_shared.last_callback_frames.store(frames as u32, Ordering::Relaxed);
+
+				if mapper.is_passthrough() {
+					fill_from_provider(&stream_shared, &mut varispeed, data, device_channels);
 				} else {
-					data.fill(0.0);
+					for chunk in data.chunks_mut(max_frames_per_callback * device_channels) {
+						let chunk_frames = chunk.len() / device_channels;
+						let provider_chunk = &mut provider_buffer[..chunk_frames * provider_channels];
+
+						fill_from_provider(&stream_shared, &mut varispeed, provider_chunk, provider_channels);
+						mapper.process(provider_chunk, chunk);
+					}
 				}
+
+				let master_volume = f32::from_bits(stream_shared.master_volume.load(Ordering::Relaxed));
+				if master_volume != 1.0 {
+					data.iter_mut().for_each(|sample| *sample *= master_volume);
+				}
+
+				stream_shared.master.lock().unwrap().process(data);
 			}
 		},
 		{
@@ -108,7 +194,10 @@ fn build_output_stream(host: &cpal::Host, stream_shared: Arc<SharedStreamState>)
 
 	let configuration = Configuration {
 		sample_rate: config.sample_rate.0 as u32,
-		channels: config.channels as usize,
+		channels: channel_layout.channel_count(),
+		channel_layout: Some(channel_layout),
+		device_channels: config.channels as usize,
+		frames_per_buffer,
 	};
 
 	Ok(ActiveStream {
@@ -120,6 +209,19 @@ fn build_output_stream(host: &cpal::Host, stream_shared: Arc<SharedStreamState>)
 	})
 }
 
+fn fill_from_provider(stream_shared: &SharedStreamState, varispeed: &mut VariableResampler, buffer: &mut [f32], channels: usize) {
+	let time_scale = f32::from_bits(stream_shared.time_scale.load(Ordering::Relaxed));
+	let preserve_pitch = stream_shared.preserve_pitch.load(Ordering::Relaxed);
+
+	let mut provider_maybe = stream_shared.provider.lock().unwrap();
+	match &mut *provider_maybe {
+		Some(_) if time_scale <= 0.0 => buffer.fill(0.0),
+		Some(provider) if time_scale != 1.0 && !preserve_pitch => varispeed.process(buffer, channels, time_scale, |source| provider.fill_buffer(source)),
+		Some(provider) => provider.fill_buffer(buffer),
+		None => buffer.fill(0.0),
+	}
+}
+
 #[instrument]
 pub fn enumerate_audio_devices() -> anyhow::Result<()> {
 	let host = cpal::default_host();
@@ -139,4 +241,4 @@ pub fn enumerate_audio_devices() -> anyhow::Result<()> {
 	log::trace!("^^^^^^ Available audio devices ^^^^^^^");
 
 	Ok(())
-}
\ No newline at end of file
+}
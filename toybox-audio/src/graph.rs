@@ -0,0 +1,196 @@
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use super::Configuration;
+use super::analysis::{Analyser, AnalysisReader};
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+
+/// Topology of everything feeding the output, for debugging and runtime mute/solo.
+/// The output itself is always [`NodeGraph::MASTER`], and nodes are added under it by [`Mixer`](super::Mixer)s.
+#[derive(Clone)]
+pub struct NodeGraph {
+	shared: Arc<Mutex<SharedGraph>>,
+}
+
+struct SharedGraph {
+	nodes: Vec<NodeView>,
+	next_id: usize,
+}
+
+impl NodeGraph {
+	pub const MASTER: NodeId = NodeId(0);
+
+	pub(crate) fn new() -> (NodeGraph, NodeTap) {
+		let graph = NodeGraph {
+			shared: Arc::new(Mutex::new(SharedGraph {
+				nodes: Vec::new(),
+				next_id: 0,
+			})),
+		};
+
+		let (_, master_tap) = graph.insert_node("master", None);
+		(graph, master_tap)
+	}
+
+	/// Registers a node feeding into `parent`. Everything the node produces should be passed through the returned tap,
+	/// which meters it and applies mute/solo. The node is removed once the tap is dropped.
+	pub fn add_node(&self, name: impl Into<String>, parent: NodeId) -> (NodeId, NodeTap) {
+		self.insert_node(name.into(), Some(parent))
+	}
+
+	/// Every live node, ordered so that parents always come before their children.
+	pub fn nodes(&self) -> Vec<NodeView> {
+		let mut shared = self.shared.lock().unwrap();
+		shared.nodes.retain(|node| node.state.attached.load(Ordering::Relaxed));
+		shared.nodes.clone()
+	}
+
+	pub fn node(&self, id: NodeId) -> Option<NodeView> {
+		self.shared.lock().unwrap().nodes.iter()
+			.find(|node| node.id == id && node.state.attached.load(Ordering::Relaxed))
+			.cloned()
+	}
+
+	fn insert_node(&self, name: impl Into<String>, parent: Option<NodeId>) -> (NodeId, NodeTap) {
+		let mut shared = self.shared.lock().unwrap();
+
+		let id = NodeId(shared.next_id);
+		shared.next_id += 1;
+
+		let parent_state = parent.and_then(|parent| shared.nodes.iter().find(|node| node.id == parent))
+			.map(|parent| parent.state.clone());
+
+		if parent.is_some() && parent_state.is_none() {
+			log::warn!("Adding audio node under unknown parent {parent:?} - it won't be affected by solo");
+		}
+
+		let state = Arc::new(NodeState {
+			muted: AtomicBool::new(false),
+			soloed: AtomicBool::new(false),
+			attached: AtomicBool::new(true),
+			soloed_children: AtomicUsize::new(0),
+		});
+
+		let analyser = Analyser::new();
+
+		shared.nodes.push(NodeView {
+			id,
+			parent,
+			name: name.into().into(),
+			analysis: analyser.reader(),
+			state: state.clone(),
+			parent_state: parent_state.clone(),
+		});
+
+		(id, NodeTap { state, parent_state, analyser })
+	}
+}
+
+
+struct NodeState {
+	muted: AtomicBool,
+	soloed: AtomicBool,
+	attached: AtomicBool,
+
+	/// While any children are soloed, only soloed children are audible.
+	soloed_children: AtomicUsize,
+}
+
+
+/// Main thread view of a node in a [`NodeGraph`].
+#[derive(Clone)]
+pub struct NodeView {
+	id: NodeId,
+	parent: Option<NodeId>,
+	name: Arc<str>,
+	analysis: AnalysisReader,
+
+	state: Arc<NodeState>,
+	parent_state: Option<Arc<NodeState>>,
+}
+
+impl NodeView {
+	pub fn id(&self) -> NodeId { self.id }
+	pub fn parent(&self) -> Option<NodeId> { self.parent }
+	pub fn name(&self) -> &str { &self.name }
+
+	/// Levels and spectrum of this node's output, after mute and solo.
+	pub fn analysis(&self) -> &AnalysisReader { &self.analysis }
+
+	pub fn set_muted(&self, muted: bool) {
+		self.state.muted.store(muted, Ordering::Relaxed);
+	}
+
+	pub fn is_muted(&self) -> bool {
+		self.state.muted.load(Ordering::Relaxed)
+	}
+
+	/// Silences every sibling that isn't also soloed.
+	pub fn set_soloed(&self, soloed: bool) {
+		set_soloed(&self.state, self.parent_state.as_deref(), soloed);
+	}
+
+	pub fn is_soloed(&self) -> bool {
+		self.state.soloed.load(Ordering::Relaxed)
+	}
+}
+
+
+/// Audio thread side of a node in a [`NodeGraph`].
+pub struct NodeTap {
+	state: Arc<NodeState>,
+	parent_state: Option<Arc<NodeState>>,
+	analyser: Analyser,
+}
+
+impl NodeTap {
+	pub fn set_configuration(&mut self, configuration: Option<Configuration>) {
+		// Only the master node sees output after it's mapped to device channels.
+		let configuration = match self.parent_state {
+			Some(_) => configuration.map(|config| Configuration { device_channels: config.channels, .. config }),
+			None => configuration,
+		};
+
+		self.analyser.set_configuration(configuration);
+	}
+
+	pub fn is_audible(&self) -> bool {
+		let soloed_out = self.parent_state.as_ref()
+			.is_some_and(|parent| parent.soloed_children.load(Ordering::Relaxed) > 0 && !self.state.soloed.load(Ordering::Relaxed));
+
+		!self.state.muted.load(Ordering::Relaxed) && !soloed_out
+	}
+
+	/// Silences `buffer` if this node is muted or soloed out, and then meters it.
+	pub fn process(&mut self, buffer: &mut [f32]) {
+		if !self.is_audible() {
+			buffer.fill(0.0);
+		}
+
+		self.analyser.process(buffer);
+	}
+}
+
+impl Drop for NodeTap {
+	fn drop(&mut self) {
+		set_soloed(&self.state, self.parent_state.as_deref(), false);
+		self.state.attached.store(false, Ordering::Relaxed);
+	}
+}
+
+
+fn set_soloed(state: &NodeState, parent_state: Option<&NodeState>, soloed: bool) {
+	let was_soloed = state.soloed.swap(soloed, Ordering::Relaxed);
+
+	if let Some(parent_state) = parent_state {
+		match (was_soloed, soloed) {
+			(false, true) => { parent_state.soloed_children.fetch_add(1, Ordering::Relaxed); }
+			(true, false) => { parent_state.soloed_children.fetch_sub(1, Ordering::Relaxed); }
+			_ => {}
+		}
+	}
+}
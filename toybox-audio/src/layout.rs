@@ -0,0 +1,197 @@
+//! Speaker layouts, and mapping between the layout providers render in and whatever the device ends up supporting.
+
+use std::f32::consts::FRAC_1_SQRT_2;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Speaker {
+	FrontLeft,
+	FrontRight,
+	FrontCenter,
+	LowFrequency,
+	BackLeft,
+	BackRight,
+	SideLeft,
+	SideRight,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ChannelLayout {
+	Mono,
+	#[default]
+	Stereo,
+	Quad,
+	Surround51,
+	Surround71,
+}
+
+impl ChannelLayout {
+	/// In interleaving order - matches WAVE and the order most backends expect.
+	pub fn speakers(self) -> &'static [Speaker] {
+		use Speaker::*;
+
+		match self {
+			ChannelLayout::Mono => &[FrontCenter],
+			ChannelLayout::Stereo => &[FrontLeft, FrontRight],
+			ChannelLayout::Quad => &[FrontLeft, FrontRight, BackLeft, BackRight],
+			ChannelLayout::Surround51 => &[FrontLeft, FrontRight, FrontCenter, LowFrequency, BackLeft, BackRight],
+			ChannelLayout::Surround71 => &[FrontLeft, FrontRight, FrontCenter, LowFrequency, BackLeft, BackRight, SideLeft, SideRight],
+		}
+	}
+
+	pub fn channel_count(self) -> usize {
+		self.speakers().len()
+	}
+
+	pub fn from_channel_count(channels: usize) -> Option<ChannelLayout> {
+		match channels {
+			1 => Some(ChannelLayout::Mono),
+			2 => Some(ChannelLayout::Stereo),
+			4 => Some(ChannelLayout::Quad),
+			6 => Some(ChannelLayout::Surround51),
+			8 => Some(ChannelLayout::Surround71),
+			_ => None,
+		}
+	}
+
+	/// Largest layout that fits within `channels`, for devices with channel counts that don't match a known layout.
+	/// Any channels beyond the layout are left silent.
+	pub fn fitting_channel_count(channels: usize) -> ChannelLayout {
+		(1..=channels.max(1)).rev()
+			.find_map(ChannelLayout::from_channel_count)
+			.unwrap_or(ChannelLayout::Mono)
+	}
+
+	/// Parses names as used by the `audio.channel_layout` setting - "mono", "stereo", "quad", "5.1" or "7.1".
+	pub fn from_name(name: &str) -> Option<ChannelLayout> {
+		match name.trim().to_ascii_lowercase().as_str() {
+			"mono" => Some(ChannelLayout::Mono),
+			"stereo" => Some(ChannelLayout::Stereo),
+			"quad" => Some(ChannelLayout::Quad),
+			"5.1" | "surround51" => Some(ChannelLayout::Surround51),
+			"7.1" | "surround71" => Some(ChannelLayout::Surround71),
+			_ => None,
+		}
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ChannelLayout::Mono => "mono",
+			ChannelLayout::Stereo => "stereo",
+			ChannelLayout::Quad => "quad",
+			ChannelLayout::Surround51 => "5.1",
+			ChannelLayout::Surround71 => "7.1",
+		}
+	}
+
+	pub fn contains(self, speaker: Speaker) -> bool {
+		self.speakers().contains(&speaker)
+	}
+}
+
+
+/// Converts interleaved audio from one layout to a device's channels - downmixing missing speakers into their nearest
+/// neighbours, and leaving extra speakers silent when upmixing.
+#[derive(Debug, Clone)]
+pub struct ChannelMapper {
+	source: ChannelLayout,
+	target_channels: usize,
+
+	/// Gain from each source channel to each target channel, `target_channels` rows of `source.channel_count()`.
+	matrix: Vec<f32>,
+}
+
+impl ChannelMapper {
+	pub fn new(source: ChannelLayout, target_channels: usize) -> ChannelMapper {
+		let target_channels = target_channels.max(1);
+		let target = ChannelLayout::fitting_channel_count(target_channels);
+		let source_channels = source.channel_count();
+
+		let mut matrix = vec![0.0; target_channels * source_channels];
+
+		for (source_index, &speaker) in source.speakers().iter().enumerate() {
+			fold_speaker(target, speaker, 1.0, &mut |target_speaker, gain| {
+				let target_index = target.speakers().iter().position(|&s| s == target_speaker).unwrap();
+				matrix[target_index * source_channels + source_index] += gain;
+			});
+		}
+
+		ChannelMapper { source, target_channels, matrix }
+	}
+
+	pub fn source_layout(&self) -> ChannelLayout {
+		self.source
+	}
+
+	pub fn target_channels(&self) -> usize {
+		self.target_channels
+	}
+
+	/// Whether [`ChannelMapper::process`] would just copy.
+	pub fn is_passthrough(&self) -> bool {
+		self.source.channel_count() == self.target_channels
+	}
+
+	/// Gain from `source_channel` to `target_channel`.
+	pub fn gain(&self, source_channel: usize, target_channel: usize) -> f32 {
+		self.matrix[target_channel * self.source.channel_count() + source_channel]
+	}
+
+	/// `source` and `target` must contain the same number of frames.
+	pub fn process(&self, source: &[f32], target: &mut [f32]) {
+		let source_channels = self.source.channel_count();
+
+		debug_assert_eq!(source.len() / source_channels, target.len() / self.target_channels,
+			"ChannelMapper source and target frame counts differ");
+
+		if self.is_passthrough() {
+			target.copy_from_slice(source);
+			return
+		}
+
+		let frames = source.chunks_exact(source_channels)
+			.zip(target.chunks_exact_mut(self.target_channels));
+
+		for (source_frame, target_frame) in frames {
+			for (target_sample, gains) in target_frame.iter_mut().zip(self.matrix.chunks_exact(source_channels)) {
+				*target_sample = source_frame.iter().zip(gains)
+					.map(|(sample, gain)| sample * gain)
+					.sum();
+			}
+		}
+	}
+}
+
+
+/// Calls `emit` for each speaker in `target` that `speaker` should be heard from, folding speakers the layout doesn't
+/// have into their nearest neighbours at -3dB per step. The LFE channel is dropped rather than folded, as is usual.
+fn fold_speaker(target: ChannelLayout, speaker: Speaker, gain: f32, emit: &mut impl FnMut(Speaker, f32)) {
+	use Speaker::*;
+
+	if target.contains(speaker) {
+		emit(speaker, gain);
+		return
+	}
+
+	let folded_gain = gain * FRAC_1_SQRT_2;
+
+	match speaker {
+		FrontLeft | FrontRight => fold_speaker(target, FrontCenter, folded_gain, emit),
+
+		FrontCenter => {
+			fold_speaker(target, FrontLeft, folded_gain, emit);
+			fold_speaker(target, FrontRight, folded_gain, emit);
+		}
+
+		LowFrequency => {}
+
+		BackLeft if target.contains(SideLeft) => emit(SideLeft, gain),
+		BackRight if target.contains(SideRight) => emit(SideRight, gain),
+		SideLeft if target.contains(BackLeft) => emit(BackLeft, gain),
+		SideRight if target.contains(BackRight) => emit(BackRight, gain),
+
+		BackLeft | SideLeft => fold_speaker(target, FrontLeft, folded_gain, emit),
+		BackRight | SideRight => fold_speaker(target, FrontRight, folded_gain, emit),
+	}
+}
@@ -6,12 +6,37 @@ use anyhow::Context as AnyhowContext;
 use tracing::instrument;
 
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
 use std::thread::{JoinHandle};
+use std::time::Duration;
 
 mod device;
 use device::*;
 
+pub mod analysis;
+pub use analysis::{AnalysisTap, AnalysisReader};
+
+pub mod graph;
+pub use graph::{NodeGraph, NodeId, NodeView, NodeTap};
+
+pub mod mixer;
+pub use mixer::Mixer;
+
+pub mod music;
+pub use music::{MusicPlayer, MusicSource, MusicTrack, MusicStem};
+
+pub mod offline;
+pub use offline::{OfflineRenderer, OfflineBuffer};
+
+pub mod layout;
+pub use layout::{ChannelLayout, ChannelMapper, Speaker};
+
+pub mod resample;
+pub use resample::{VariableResampler, Resampled, RateControl, MAX_RESAMPLE_RATE};
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod prelude {
 	pub use super::Provider;
 }
@@ -20,35 +45,230 @@ pub mod prelude {
 #[derive(Debug, Copy, Clone)]
 pub struct Configuration {
 	pub sample_rate: u32,
+
+	/// Channels providers should interleave. Matches `channel_layout` if there is one.
 	pub channels: usize,
+
+	/// Layout providers render in. None for channel counts with no known layout, which can only come from an
+	/// [`OfflineRenderer`].
+	pub channel_layout: Option<ChannelLayout>,
+
+	/// Channels actually output by the device. Provider output is mapped from `channel_layout` to this if they differ.
+	pub device_channels: usize,
+
+	/// The buffer size requested from the device, if one was requested and supported.
+	/// Otherwise the device default is used, which may vary between callbacks.
+	pub frames_per_buffer: Option<u32>,
+}
+
+impl Configuration {
+	/// Latency introduced by buffering alone, if a fixed buffer size is in use.
+	pub fn buffer_latency(&self) -> Option<Duration> {
+		self.frames_per_buffer
+			.map(|frames| Duration::from_secs_f64(frames as f64 / self.sample_rate as f64))
+	}
+}
+
+
+/// Requested output stream parameters. These are requests only - see [`System::current_configuration`]
+/// and [`System::achieved_latency`] for what was actually achieved.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct StreamSettings {
+	/// Defaults to 48kHz if supported.
+	pub sample_rate: Option<u32>,
+
+	/// Explicit buffer size. Smaller buffers reduce latency but risk underruns. Takes precedence over `target_latency`.
+	pub frames_per_buffer: Option<u32>,
+
+	/// Target buffer latency, converted to a buffer size once the sample rate is known.
+	pub target_latency: Option<Duration>,
+
+	/// Layout providers render in. Defaults to stereo. The device config closest to this is selected, and output is
+	/// downmixed if the device has fewer channels.
+	pub channel_layout: Option<ChannelLayout>,
+}
+
+impl StreamSettings {
+	pub fn sample_rate(self, sample_rate: u32) -> Self {
+		Self { sample_rate: Some(sample_rate), .. self }
+	}
+
+	pub fn frames_per_buffer(self, frames_per_buffer: u32) -> Self {
+		Self { frames_per_buffer: Some(frames_per_buffer), .. self }
+	}
+
+	pub fn target_latency(self, target_latency: Duration) -> Self {
+		Self { target_latency: Some(target_latency), .. self }
+	}
+
+	pub fn channel_layout(self, channel_layout: ChannelLayout) -> Self {
+		Self { channel_layout: Some(channel_layout), .. self }
+	}
 }
 
 pub trait Provider : Send + 'static {
 	fn on_configuration_changed(&mut self, _: Option<Configuration>);
 	fn fill_buffer(&mut self, buffer: &mut [f32]);
+
+	/// See [`System::set_time_scale`].
+	fn on_time_scale_changed(&mut self, _time_scale: f32, _preserve_pitch: bool) {}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamStatus {
+	Pending,
+	Active,
+	Failed,
 }
 
 
 pub struct System {
 	stream_shared: Arc<SharedStreamState>,
 	stream_state: StreamState,
+	stream_settings: StreamSettings,
+	node_graph: NodeGraph,
+	master: NodeView,
 }
 
 impl System {
 	#[instrument(skip_all, name="audio init")]
-	pub fn init() -> System {
+	pub fn init(stream_settings: StreamSettings) -> System {
 		if false {
 			std::thread::spawn(enumerate_audio_devices);
 		}
 
+		let (node_graph, master_tap) = NodeGraph::new();
+		let master = node_graph.node(NodeGraph::MASTER).unwrap();
+
 		let stream_shared = Arc::new(SharedStreamState {
 			provider: Mutex::new(None),
 			device_lost: AtomicBool::new(false),
+			master_volume: AtomicU32::new(1.0f32.to_bits()),
+			time_scale: AtomicU32::new(1.0f32.to_bits()),
+			preserve_pitch: AtomicBool::new(false),
+			master: Mutex::new(master_tap),
+			last_callback_frames: AtomicU32::new(0),
+			last_callback_latency_us: AtomicU64::new(0),
 		});
 
 		System {
-			stream_state: StreamState::Pending(Some(start_stream_build(stream_shared.clone()))),
+			stream_state: StreamState::Pending(Some(start_stream_build(stream_shared.clone(), stream_settings))),
+			node_graph,
+			master,
 			stream_shared,
+			stream_settings,
+		}
+	}
+
+	/// Rebuilds the output stream with new settings. Audio will drop out briefly.
+	pub fn set_stream_settings(&mut self, stream_settings: StreamSettings) {
+		if self.stream_settings == stream_settings {
+			return
+		}
+
+		self.stream_settings = stream_settings;
+
+		// Wait for any in flight builds so we don't end up with two streams.
+		if let StreamState::Pending(handle) = &mut self.stream_state
+			&& let Some(handle) = handle.take()
+		{
+			let _ = handle.join();
+		}
+
+		// Make sure the old stream is dropped before building the new one.
+		self.stream_state = StreamState::InitFailure;
+		self.stream_state = StreamState::Pending(Some(start_stream_build(self.stream_shared.clone(), stream_settings)));
+	}
+
+	/// Silences output without disturbing the provider. The provider is still called, so state continues to advance.
+	/// Same as muting the [`NodeGraph::MASTER`] node.
+	pub fn set_muted(&self, muted: bool) {
+		self.master.set_muted(muted);
+	}
+
+	pub fn is_muted(&self) -> bool {
+		self.master.is_muted()
+	}
+
+	/// Scales final output after the provider is called. Clamped to [0, 1].
+	pub fn set_master_volume(&self, volume: f32) {
+		self.stream_shared.master_volume.store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+	}
+
+	pub fn master_volume(&self) -> f32 {
+		f32::from_bits(self.stream_shared.master_volume.load(Ordering::Relaxed))
+	}
+
+	/// Speed up or slow down playback, e.g., for slow motion. A scale of zero pauses the provider entirely.
+	/// If `preserve_pitch` is set, the provider is called as normal and is expected to handle the scale itself
+	/// via [`Provider::on_time_scale_changed`]. Otherwise output is resampled, shifting pitch along with speed.
+	pub fn set_time_scale(&self, time_scale: f32, preserve_pitch: bool) {
+		let time_scale = time_scale.max(0.0);
+		if time_scale == self.time_scale() && preserve_pitch == self.stream_shared.preserve_pitch.load(Ordering::Relaxed) {
+			return
+		}
+
+		self.stream_shared.time_scale.store(time_scale.to_bits(), Ordering::Relaxed);
+		self.stream_shared.preserve_pitch.store(preserve_pitch, Ordering::Relaxed);
+
+		if let Some(provider) = &mut *self.stream_shared.provider.lock().unwrap() {
+			provider.on_time_scale_changed(time_scale, preserve_pitch);
+		}
+	}
+
+	pub fn time_scale(&self) -> f32 {
+		f32::from_bits(self.stream_shared.time_scale.load(Ordering::Relaxed))
+	}
+
+	/// Levels and spectrum of the final output, after muting and master volume.
+	pub fn output_analysis(&self) -> &AnalysisReader {
+		self.master.analysis()
+	}
+
+	/// Everything feeding the output. Providers only show up here if they're mixed with a [`Mixer`].
+	pub fn node_graph(&self) -> &NodeGraph {
+		&self.node_graph
+	}
+
+	pub fn has_provider(&self) -> bool {
+		self.stream_shared.provider.lock()
+			.map_or(false, |provider| provider.is_some())
+	}
+
+	pub fn stream_status(&self) -> StreamStatus {
+		match self.stream_state {
+			StreamState::Pending(_) => StreamStatus::Pending,
+			StreamState::Active(_) => StreamStatus::Active,
+			StreamState::InitFailure => StreamStatus::Failed,
+		}
+	}
+
+	pub fn stream_settings(&self) -> StreamSettings {
+		self.stream_settings
+	}
+
+	pub fn current_configuration(&self) -> Option<Configuration> {
+		self.stream_state.current_configuration()
+	}
+
+	/// Number of frames requested in the most recent callback. May differ from the requested buffer size.
+	pub fn last_buffer_frames(&self) -> Option<u32> {
+		self.stream_state.current_configuration()?;
+
+		match self.stream_shared.last_callback_frames.load(Ordering::Relaxed) {
+			0 => None,
+			frames => Some(frames),
+		}
+	}
+
+	/// Time between the most recent callback and when its output is expected to be played, as reported by the backend.
+	pub fn achieved_latency(&self) -> Option<Duration> {
+		self.stream_state.current_configuration()?;
+
+		match self.stream_shared.last_callback_latency_us.load(Ordering::Relaxed) {
+			0 => None,
+			micros => Some(Duration::from_micros(micros)),
 		}
 	}
 
@@ -56,7 +276,7 @@ impl System {
 		match &mut self.stream_state {
 			StreamState::Active(_) => {
 				if self.stream_shared.device_lost.load(Ordering::Relaxed) {
-					self.stream_state = StreamState::Pending(Some(start_stream_build(self.stream_shared.clone())));
+					self.stream_state = StreamState::Pending(Some(start_stream_build(self.stream_shared.clone(), self.stream_settings)));
 				}
 			}
 
@@ -106,6 +326,7 @@ impl System {
 
 			log::info!("Setting initial provider configuration: {configuration:?}");
 			provider.on_configuration_changed(configuration);
+			provider.on_time_scale_changed(self.time_scale(), self.stream_shared.preserve_pitch.load(Ordering::Relaxed));
 
 			*shared_provider = Some(Box::new(provider));
 
@@ -119,6 +340,10 @@ impl System {
 	fn try_update_provider_config(&mut self) {
 		let configuration = self.stream_state.current_configuration();
 
+		if let Ok(mut master) = self.stream_shared.master.lock() {
+			master.set_configuration(configuration);
+		}
+
 		if let Ok(mut guard) = self.stream_shared.provider.lock()
 			&& let Some(provider) = &mut *guard
 		{
@@ -0,0 +1,115 @@
+use super::{Configuration, Provider};
+use super::graph::{NodeGraph, NodeId, NodeTap};
+
+
+/// Sums any number of providers, each of which is metered and can be muted or soloed as its own node in the
+/// [`NodeGraph`]. Inputs should be added before the mixer is handed to the audio thread.
+///
+/// ```ignore
+/// let graph = ctx.audio.node_graph();
+/// let mut mixer = audio::Mixer::new(graph, audio::NodeGraph::MASTER);
+/// mixer.add_input("music", ctx.music.take_source().unwrap());
+/// mixer.add_input_with("sfx", |sfx_node| {
+/// 	let mut sfx = audio::Mixer::new(graph, sfx_node);
+/// 	sfx.add_input("footsteps", footsteps);
+/// 	sfx
+/// });
+///
+/// ctx.audio.set_provider(Some(mixer))?;
+/// ```
+pub struct Mixer {
+	graph: NodeGraph,
+	parent: NodeId,
+
+	inputs: Vec<MixerInput>,
+	configuration: Option<Configuration>,
+	time_scale: (f32, bool),
+
+	/// Each input is rendered here before being summed. Sized on configuration change so that mixing never allocates.
+	scratch: Vec<f32>,
+}
+
+struct MixerInput {
+	provider: Box<dyn Provider>,
+	tap: NodeTap,
+}
+
+impl Mixer {
+	/// Inputs are added to `graph` as children of `parent` - [`NodeGraph::MASTER`] if the mixer is set as the provider
+	/// directly, or the mixer's own node if it's an input of another mixer.
+	pub fn new(graph: &NodeGraph, parent: NodeId) -> Mixer {
+		Mixer {
+			graph: graph.clone(),
+			parent,
+
+			inputs: Vec::new(),
+			configuration: None,
+			time_scale: (1.0, false),
+
+			scratch: Vec::new(),
+		}
+	}
+
+	pub fn add_input(&mut self, name: impl Into<String>, provider: impl Provider) -> NodeId {
+		self.add_input_with(name, move |_| provider)
+	}
+
+	/// Like [`Mixer::add_input`], but the provider is built knowing its node - e.g., for nesting mixers.
+	pub fn add_input_with<P: Provider>(&mut self, name: impl Into<String>, build: impl FnOnce(NodeId) -> P) -> NodeId {
+		let (id, mut tap) = self.graph.add_node(name, self.parent);
+		let mut provider = build(id);
+
+		let (time_scale, preserve_pitch) = self.time_scale;
+		tap.set_configuration(self.configuration);
+		provider.on_configuration_changed(self.configuration);
+		provider.on_time_scale_changed(time_scale, preserve_pitch);
+
+		self.inputs.push(MixerInput { provider: Box::new(provider), tap });
+		id
+	}
+}
+
+impl Provider for Mixer {
+	fn on_configuration_changed(&mut self, configuration: Option<Configuration>) {
+		self.configuration = configuration;
+
+		let scratch_frames = configuration
+			.map_or(0, |config| config.frames_per_buffer.map_or(crate::device::DEFAULT_MAX_FRAMES_PER_CALLBACK, |frames| frames as usize));
+		let channels = configuration.map_or(0, |config| config.channels);
+		self.scratch = vec![0.0; scratch_frames * channels];
+
+		for MixerInput{provider, tap} in self.inputs.iter_mut() {
+			tap.set_configuration(configuration);
+			provider.on_configuration_changed(configuration);
+		}
+	}
+
+	fn fill_buffer(&mut self, buffer: &mut [f32]) {
+		buffer.fill(0.0);
+
+		if self.scratch.is_empty() {
+			return
+		}
+
+		for chunk in buffer.chunks_mut(self.scratch.len()) {
+			let scratch = &mut self.scratch[..chunk.len()];
+
+			for MixerInput{provider, tap} in self.inputs.iter_mut() {
+				provider.fill_buffer(scratch);
+				tap.process(scratch);
+
+				for (sample, input) in chunk.iter_mut().zip(scratch.iter()) {
+					*sample += input;
+				}
+			}
+		}
+	}
+
+	fn on_time_scale_changed(&mut self, time_scale: f32, preserve_pitch: bool) {
+		self.time_scale = (time_scale, preserve_pitch);
+
+		for input in self.inputs.iter_mut() {
+			input.provider.on_time_scale_changed(time_scale, preserve_pitch);
+		}
+	}
+}
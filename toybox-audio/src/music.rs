@@ -0,0 +1,513 @@
+use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use super::{Configuration, Provider, ChannelLayout, ChannelMapper};
+
+
+/// Decoded, interleaved samples for one stem of a [`MusicTrack`].
+#[derive(Debug, Clone)]
+pub struct MusicStem {
+	pub samples: Arc<[f32]>,
+	pub channels: usize,
+	pub sample_rate: u32,
+}
+
+impl MusicStem {
+	pub fn new(samples: impl Into<Arc<[f32]>>, channels: usize, sample_rate: u32) -> MusicStem {
+		assert!(channels > 0, "MusicStem must have at least one channel");
+		MusicStem { samples: samples.into(), channels, sample_rate }
+	}
+
+	pub fn num_frames(&self) -> usize {
+		self.samples.len() / self.channels
+	}
+}
+
+
+/// One or more stems that play in lockstep. Stems are all read from the same play position, so stay sample-accurately
+/// in sync regardless of their individual gains - e.g., for bringing in percussion as tension rises.
+/// Stems can have different lengths, shorter stems are silent once they run out.
+#[derive(Debug, Clone)]
+pub struct MusicTrack {
+	pub stems: Vec<MusicStem>,
+	pub looping: bool,
+
+	/// Initial gain for each stem. Missing entries default to 1.
+	pub stem_gains: Vec<f32>,
+}
+
+impl MusicTrack {
+	pub fn new(stem: MusicStem) -> MusicTrack {
+		MusicTrack::from_stems(vec![stem])
+	}
+
+	pub fn from_stems(stems: Vec<MusicStem>) -> MusicTrack {
+		assert!(!stems.is_empty(), "MusicTrack must have at least one stem");
+		MusicTrack { stems, looping: true, stem_gains: Vec::new() }
+	}
+
+	pub fn looping(self, looping: bool) -> Self {
+		Self { looping, .. self }
+	}
+
+	pub fn stem_gains(self, stem_gains: impl Into<Vec<f32>>) -> Self {
+		Self { stem_gains: stem_gains.into(), .. self }
+	}
+
+	/// Play position is measured in frames of the first stem.
+	fn sample_rate(&self) -> u32 {
+		self.stems[0].sample_rate
+	}
+
+	fn num_frames(&self) -> usize {
+		self.stems.iter().map(MusicStem::num_frames).max().unwrap_or(0)
+	}
+}
+
+
+/// Commands are queued in fixed size channels, so that neither sending nor receiving them allocates on the audio thread.
+const COMMAND_QUEUE_SIZE: usize = 32;
+const RETIRED_QUEUE_SIZE: usize = 16;
+
+/// Fading out any more voices than this cuts off the oldest.
+const MAX_FADING_VOICES: usize = 8;
+
+
+/// Voices are built on the main thread and sent back once finished, so that the audio thread never allocates or frees
+/// track data.
+enum MusicCommand {
+	Play { voice: Voice, fade_in: Duration },
+	CrossfadeTo { voice: Voice, duration: Duration },
+	Stop { fade_out: Duration },
+	SetStemGain { stem: usize, gain: f32, duration: Duration },
+}
+
+
+/// Main thread control of music playback. Commands are sent to the paired [`MusicSource`], which does the actual mixing
+/// on the audio thread, so take effect within a buffer.
+///
+/// The source either needs to be set as the audio provider directly, or mixed into the app's own provider with
+/// [`MusicSource::mix_into`].
+pub struct MusicPlayer {
+	command_tx: mpsc::SyncSender<MusicCommand>,
+	retired_rx: mpsc::Receiver<Voice>,
+	volume: Arc<AtomicU32>,
+
+	/// Channels of the output layout, or zero if unknown. Lets voices map their stems before they're sent.
+	output_channels: Arc<AtomicUsize>,
+
+	source: Option<MusicSource>,
+}
+
+impl MusicPlayer {
+	pub fn new() -> MusicPlayer {
+		let (command_tx, command_rx) = mpsc::sync_channel(COMMAND_QUEUE_SIZE);
+		let (retired_tx, retired_rx) = mpsc::sync_channel(RETIRED_QUEUE_SIZE);
+		let volume = Arc::new(AtomicU32::new(1.0f32.to_bits()));
+		let output_channels = Arc::new(AtomicUsize::new(0));
+
+		let source = MusicSource {
+			command_rx,
+			retired_tx,
+			retired: Vec::with_capacity(RETIRED_QUEUE_SIZE),
+			volume: Arc::clone(&volume),
+			output_channels: Arc::clone(&output_channels),
+			configuration: None,
+			current: None,
+			fading_out: Vec::with_capacity(MAX_FADING_VOICES),
+		};
+
+		MusicPlayer {
+			command_tx,
+			retired_rx,
+			volume,
+			output_channels,
+			source: Some(source),
+		}
+	}
+
+	/// Frees tracks that the audio thread has finished with. Should be called regularly - toybox does so every frame.
+	pub fn update(&self) {
+		while self.retired_rx.try_recv().is_ok() {}
+	}
+
+	/// The audio thread side of this player. Can only be taken once.
+	pub fn take_source(&mut self) -> Option<MusicSource> {
+		self.source.take()
+	}
+
+	/// Cuts off anything currently playing and starts `track` from the beginning.
+	pub fn play(&self, track: MusicTrack, fade_in: Duration) {
+		self.send(MusicCommand::Play { voice: self.new_voice(track), fade_in });
+	}
+
+	/// Fades out whatever is currently playing while fading in `track` over the same period.
+	pub fn crossfade_to(&self, track: MusicTrack, duration: Duration) {
+		self.send(MusicCommand::CrossfadeTo { voice: self.new_voice(track), duration });
+	}
+
+	pub fn stop(&self, fade_out: Duration) {
+		self.send(MusicCommand::Stop { fade_out });
+	}
+
+	/// Ramp the gain of one stem of the current track. Ignored if the current track has no such stem.
+	pub fn set_stem_gain(&self, stem: usize, gain: f32, duration: Duration) {
+		self.send(MusicCommand::SetStemGain { stem, gain: gain.max(0.0), duration });
+	}
+
+	/// Scales all music, independent of master volume. Clamped to [0, 1].
+	pub fn set_volume(&self, volume: f32) {
+		self.volume.store(volume.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+	}
+
+	pub fn volume(&self) -> f32 {
+		f32::from_bits(self.volume.load(Ordering::Relaxed))
+	}
+
+	fn new_voice(&self, track: MusicTrack) -> Voice {
+		let output_channels = match self.output_channels.load(Ordering::Relaxed) {
+			0 => None,
+			channels => Some(channels),
+		};
+
+		Voice::new(track, output_channels)
+	}
+
+	fn send(&self, command: MusicCommand) {
+		self.update();
+
+		match self.command_tx.try_send(command) {
+			Ok(()) => {}
+			Err(mpsc::TrySendError::Full(_)) => log::warn!("Music command queue full - ignoring command"),
+			Err(mpsc::TrySendError::Disconnected(_)) => log::warn!("Music source has been dropped - ignoring command"),
+		}
+	}
+}
+
+impl Default for MusicPlayer {
+	fn default() -> MusicPlayer {
+		MusicPlayer::new()
+	}
+}
+
+
+/// Audio thread side of a [`MusicPlayer`].
+pub struct MusicSource {
+	command_rx: mpsc::Receiver<MusicCommand>,
+	retired_tx: mpsc::SyncSender<Voice>,
+
+	/// Voices that couldn't be sent back yet because the queue was full.
+	retired: Vec<Voice>,
+
+	volume: Arc<AtomicU32>,
+	output_channels: Arc<AtomicUsize>,
+	configuration: Option<Configuration>,
+
+	current: Option<Voice>,
+	fading_out: Vec<Voice>,
+}
+
+impl MusicSource {
+	/// Adds music to `buffer` rather than overwriting it.
+	pub fn mix_into(&mut self, buffer: &mut [f32]) {
+		self.process_commands();
+		self.flush_retired();
+
+		let Some(configuration) = self.configuration else { return };
+		let volume = f32::from_bits(self.volume.load(Ordering::Relaxed));
+
+		if let Some(voice) = &mut self.current {
+			voice.mix_into(buffer, configuration, volume);
+		}
+
+		if let Some(voice) = self.current.take_if(|voice| voice.finished) {
+			self.retire(voice);
+		}
+
+		for voice in self.fading_out.iter_mut() {
+			voice.mix_into(buffer, configuration, volume);
+		}
+
+		for index in (0..self.fading_out.len()).rev() {
+			if self.fading_out[index].finished {
+				let voice = self.fading_out.swap_remove(index);
+				self.retire(voice);
+			}
+		}
+	}
+
+	fn process_commands(&mut self) {
+		while let Ok(command) = self.command_rx.try_recv() {
+			let sample_rate = self.configuration.map_or(48000, |c| c.sample_rate);
+
+			match command {
+				MusicCommand::Play { mut voice, fade_in } => {
+					while let Some(voice) = self.fading_out.pop() {
+						self.retire(voice);
+					}
+
+					voice.gain.ramp_to(1.0, fade_in, sample_rate);
+					if let Some(previous) = self.current.replace(voice) {
+						self.retire(previous);
+					}
+				}
+
+				MusicCommand::CrossfadeTo { mut voice, duration } => {
+					self.fade_out_current(duration, sample_rate);
+
+					voice.gain.ramp_to(1.0, duration, sample_rate);
+					self.current = Some(voice);
+				}
+
+				MusicCommand::Stop { fade_out } => {
+					self.fade_out_current(fade_out, sample_rate);
+				}
+
+				MusicCommand::SetStemGain { stem, gain, duration } => {
+					if let Some(voice) = &mut self.current
+						&& let Some(stem_gain) = voice.stem_gains.get_mut(stem)
+					{
+						stem_gain.ramp_to(gain, duration, sample_rate);
+					}
+				}
+			}
+		}
+	}
+
+	fn fade_out_current(&mut self, duration: Duration, sample_rate: u32) {
+		let Some(mut voice) = self.current.take() else { return };
+
+		// Keeps within the preallocated capacity.
+		if self.fading_out.len() >= MAX_FADING_VOICES {
+			let oldest = self.fading_out.remove(0);
+			self.retire(oldest);
+		}
+
+		voice.gain.ramp_to(0.0, duration, sample_rate);
+		voice.stop_when_silent = true;
+		self.fading_out.push(voice);
+	}
+
+	/// Send `voice` back to the main thread to be freed.
+	fn retire(&mut self, voice: Voice) {
+		match self.retired_tx.try_send(voice) {
+			Err(mpsc::TrySendError::Full(voice)) if self.retired.len() < self.retired.capacity() => self.retired.push(voice),
+
+			// Either the player is gone, in which case there's nowhere else to free it, or things are badly backed up.
+			_ => {}
+		}
+	}
+
+	fn flush_retired(&mut self) {
+		while let Some(voice) = self.retired.pop() {
+			if let Err(mpsc::TrySendError::Full(voice)) = self.retired_tx.try_send(voice) {
+				self.retired.push(voice);
+				break
+			}
+		}
+	}
+}
+
+impl Provider for MusicSource {
+	// Called on the main thread, so voices can be remapped here rather than while mixing.
+	fn on_configuration_changed(&mut self, configuration: Option<Configuration>) {
+		self.configuration = configuration;
+
+		let output_channels = output_channels(configuration);
+		self.output_channels.store(output_channels.unwrap_or(0), Ordering::Relaxed);
+
+		for voice in self.current.iter_mut().chain(self.fading_out.iter_mut()) {
+			voice.map_stems(output_channels);
+		}
+	}
+
+	fn fill_buffer(&mut self, buffer: &mut [f32]) {
+		buffer.fill(0.0);
+		self.mix_into(buffer);
+	}
+}
+
+
+struct Voice {
+	track: MusicTrack,
+
+	/// In frames of the track's sample rate. Fractional so tracks can be resampled to the output rate.
+	position: f64,
+
+	gain: Ramp,
+	stem_gains: Vec<Ramp>,
+
+	/// Maps each stem to the output layout, for stems with known layouts. Rebuilt when the output layout changes.
+	stem_mappers: Vec<Option<ChannelMapper>>,
+	mapped_channels: Option<usize>,
+
+	stop_when_silent: bool,
+	finished: bool,
+}
+
+impl Voice {
+	/// Starts silent - the audio thread ramps it in once the output sample rate is known.
+	fn new(track: MusicTrack, output_channels: Option<usize>) -> Voice {
+		let stem_gains = (0..track.stems.len())
+			.map(|index| Ramp::new(track.stem_gains.get(index).copied().unwrap_or(1.0)))
+			.collect();
+
+		let mut voice = Voice {
+			track,
+			position: 0.0,
+			gain: Ramp::new(0.0),
+			stem_gains,
+			stem_mappers: Vec::new(),
+			mapped_channels: None,
+			stop_when_silent: false,
+			finished: false,
+		};
+
+		voice.map_stems(output_channels);
+		voice
+	}
+
+	/// Allocates, so should only be called from the main thread.
+	fn map_stems(&mut self, output_channels: Option<usize>) {
+		self.mapped_channels = output_channels;
+		self.stem_mappers = self.track.stems.iter()
+			.map(|stem| {
+				let stem_layout = ChannelLayout::from_channel_count(stem.channels)?;
+				Some(ChannelMapper::new(stem_layout, output_channels?))
+			})
+			.collect();
+	}
+
+	fn mix_into(&mut self, buffer: &mut [f32], configuration: Configuration, volume: f32) {
+		let channels = configuration.channels;
+		let step = self.track.sample_rate() as f64 / configuration.sample_rate as f64;
+		let num_frames = self.track.num_frames() as f64;
+
+		// Only happens if the voice was created while the output was being reconfigured.
+		let output_channels = output_channels(Some(configuration));
+		if self.mapped_channels != output_channels {
+			self.map_stems(output_channels);
+		}
+
+		for frame in buffer.chunks_exact_mut(channels) {
+			if self.position >= num_frames {
+				if !self.track.looping || num_frames == 0.0 {
+					self.finished = true;
+					return
+				}
+
+				self.position -= num_frames;
+			}
+
+			let gain = self.gain.next() * volume;
+
+			let stems = self.track.stems.iter().zip(self.stem_gains.iter_mut()).zip(&self.stem_mappers);
+
+			for ((stem, stem_gain), mapper) in stems {
+				let stem_gain = stem_gain.next() * gain;
+				if stem_gain <= 0.0 {
+					continue
+				}
+
+				// Stems at other rates are scaled to the same position in time, keeping them in sync.
+				let stem_position = self.position * stem.sample_rate as f64 / self.track.sample_rate() as f64;
+
+				match mapper {
+					Some(mapper) if !mapper.is_passthrough() => {
+						for (channel, sample) in frame.iter_mut().enumerate() {
+							for stem_channel in 0..stem.channels {
+								let mapped_gain = mapper.gain(stem_channel, channel);
+								if mapped_gain != 0.0 {
+									*sample += stem_gain * mapped_gain * stem.sample(stem_position, stem_channel, self.track.looping);
+								}
+							}
+						}
+					}
+
+					_ => {
+						for (channel, sample) in frame.iter_mut().enumerate() {
+							*sample += stem_gain * stem.sample(stem_position, channel % stem.channels, self.track.looping);
+						}
+					}
+				}
+			}
+
+			self.position += step;
+
+			if self.stop_when_silent && self.gain.is_settled() && self.gain.value <= 0.0 {
+				self.finished = true;
+				return
+			}
+		}
+	}
+}
+
+fn output_channels(configuration: Option<Configuration>) -> Option<usize> {
+	Some(configuration?.channel_layout?.channel_count())
+}
+
+impl MusicStem {
+	/// Linearly interpolated sample at fractional frame `position`.
+	fn sample(&self, position: f64, channel: usize, looping: bool) -> f32 {
+		let num_frames = self.num_frames();
+		let frame = position as usize;
+		if frame >= num_frames {
+			return 0.0
+		}
+
+		let next_frame = match frame + 1 {
+			next if next < num_frames => next,
+			_ if looping => 0,
+			_ => frame,
+		};
+
+		let t = position.fract() as f32;
+		let a = self.samples[frame * self.channels + channel];
+		let b = self.samples[next_frame * self.channels + channel];
+		a + (b - a) * t
+	}
+}
+
+
+/// Linear per-frame gain ramp.
+struct Ramp {
+	value: f32,
+	target: f32,
+	step: f32,
+}
+
+impl Ramp {
+	fn new(value: f32) -> Ramp {
+		Ramp { value, target: value, step: 0.0 }
+	}
+
+	fn ramp_to(&mut self, target: f32, duration: Duration, sample_rate: u32) {
+		let frames = (duration.as_secs_f32() * sample_rate as f32).max(1.0);
+		self.target = target;
+		self.step = (target - self.value) / frames;
+	}
+
+	fn is_settled(&self) -> bool {
+		self.value == self.target
+	}
+
+	/// Returns the current value and advances by a frame.
+	fn next(&mut self) -> f32 {
+		let value = self.value;
+
+		if !self.is_settled() {
+			self.value += self.step;
+
+			let overshot = (self.step > 0.0 && self.value >= self.target)
+				|| (self.step < 0.0 && self.value <= self.target)
+				|| self.step == 0.0;
+
+			if overshot {
+				self.value = self.target;
+			}
+		}
+
+		value
+	}
+}
@@ -0,0 +1,161 @@
+use std::io::Write;
+use std::time::Duration;
+
+use super::{Configuration, Provider, ChannelLayout};
+
+
+/// Runs a provider without an output device, as fast as possible - for testing DSP code and exporting audio.
+/// Providers are always called with buffers of exactly `block_frames` frames (except for a possibly shorter final
+/// block), so output is deterministic regardless of hardware.
+///
+/// ```ignore
+/// let renderer = OfflineRenderer::new(48000, 2);
+/// let buffer = renderer.render_duration(&mut music_source, Duration::from_secs(30));
+/// vfs.save_data(PathKind::UserData, "export/song.wav", buffer.to_wav_bytes())?;
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct OfflineRenderer {
+	pub sample_rate: u32,
+	pub channels: usize,
+
+	/// Frames per call to [`Provider::fill_buffer`].
+	pub block_frames: usize,
+}
+
+impl OfflineRenderer {
+	pub fn new(sample_rate: u32, channels: usize) -> Self {
+		OfflineRenderer {
+			sample_rate,
+			channels,
+			block_frames: 512,
+		}
+	}
+
+	pub fn block_frames(self, block_frames: usize) -> Self {
+		Self { block_frames: block_frames.max(1), .. self }
+	}
+
+	/// The configuration providers are given, as if a device with a fixed buffer size was active.
+	pub fn configuration(&self) -> Configuration {
+		Configuration {
+			sample_rate: self.sample_rate,
+			channels: self.channels,
+			channel_layout: ChannelLayout::from_channel_count(self.channels),
+			device_channels: self.channels,
+			frames_per_buffer: Some(self.block_frames as u32),
+		}
+	}
+
+	pub fn duration_to_frames(&self, duration: Duration) -> usize {
+		(duration.as_secs_f64() * self.sample_rate as f64).round() as usize
+	}
+
+	/// Reconfigures `provider` for offline rendering. Called by the render functions, but only needs calling once
+	/// if rendering in several parts with [`OfflineRenderer::render_into`].
+	pub fn prepare(&self, provider: &mut impl Provider) {
+		provider.on_configuration_changed(Some(self.configuration()));
+		provider.on_time_scale_changed(1.0, false);
+	}
+
+	/// Render `frames` frames of interleaved output.
+	pub fn render(&self, provider: &mut impl Provider, frames: usize) -> OfflineBuffer {
+		self.prepare(provider);
+
+		let mut samples = vec![0.0; frames * self.channels];
+		self.render_into(provider, &mut samples);
+
+		OfflineBuffer {
+			sample_rate: self.sample_rate,
+			channels: self.channels,
+			samples,
+		}
+	}
+
+	pub fn render_duration(&self, provider: &mut impl Provider, duration: Duration) -> OfflineBuffer {
+		self.render(provider, self.duration_to_frames(duration))
+	}
+
+	/// Fill `buffer` with interleaved output, block by block. Doesn't call [`OfflineRenderer::prepare`].
+	pub fn render_into(&self, provider: &mut impl Provider, buffer: &mut [f32]) {
+		assert!(buffer.len() % self.channels == 0, "Buffer must contain a whole number of frames");
+
+		for block in buffer.chunks_mut(self.block_frames * self.channels) {
+			provider.fill_buffer(block);
+		}
+	}
+}
+
+
+/// Interleaved output from an [`OfflineRenderer`].
+#[derive(Debug, Clone, Default)]
+pub struct OfflineBuffer {
+	pub sample_rate: u32,
+	pub channels: usize,
+	pub samples: Vec<f32>,
+}
+
+impl OfflineBuffer {
+	pub fn frames(&self) -> usize {
+		self.samples.len() / self.channels.max(1)
+	}
+
+	pub fn duration(&self) -> Duration {
+		Duration::from_secs_f64(self.frames() as f64 / self.sample_rate as f64)
+	}
+
+	pub fn channel(&self, channel: usize) -> impl Iterator<Item=f32> + '_ {
+		self.samples.iter().copied()
+			.skip(channel)
+			.step_by(self.channels)
+	}
+
+	pub fn peak(&self) -> f32 {
+		self.samples.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+	}
+
+	pub fn rms(&self) -> f32 {
+		if self.samples.is_empty() {
+			return 0.0
+		}
+
+		let sum_squares: f32 = self.samples.iter().map(|sample| sample * sample).sum();
+		(sum_squares / self.samples.len() as f32).sqrt()
+	}
+
+	/// Encode as a 32-bit float WAV file.
+	pub fn write_wav(&self, mut writer: impl Write) -> std::io::Result<()> {
+		const FORMAT_IEEE_FLOAT: u16 = 3;
+		const BYTES_PER_SAMPLE: u32 = 4;
+
+		let channels = self.channels as u32;
+		let data_size = self.samples.len() as u32 * BYTES_PER_SAMPLE;
+
+		writer.write_all(b"RIFF")?;
+		writer.write_all(&(36 + data_size).to_le_bytes())?;
+		writer.write_all(b"WAVE")?;
+
+		writer.write_all(b"fmt ")?;
+		writer.write_all(&16u32.to_le_bytes())?;
+		writer.write_all(&FORMAT_IEEE_FLOAT.to_le_bytes())?;
+		writer.write_all(&(channels as u16).to_le_bytes())?;
+		writer.write_all(&self.sample_rate.to_le_bytes())?;
+		writer.write_all(&(self.sample_rate * channels * BYTES_PER_SAMPLE).to_le_bytes())?;
+		writer.write_all(&((channels * BYTES_PER_SAMPLE) as u16).to_le_bytes())?;
+		writer.write_all(&((BYTES_PER_SAMPLE * 8) as u16).to_le_bytes())?;
+
+		writer.write_all(b"data")?;
+		writer.write_all(&data_size.to_le_bytes())?;
+
+		for sample in self.samples.iter() {
+			writer.write_all(&sample.to_le_bytes())?;
+		}
+
+		Ok(())
+	}
+
+	pub fn to_wav_bytes(&self) -> Vec<u8> {
+		let mut bytes = Vec::with_capacity(44 + self.samples.len() * 4);
+		self.write_wav(&mut bytes).expect("Writing to a Vec can't fail");
+		bytes
+	}
+}
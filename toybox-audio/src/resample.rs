@@ -0,0 +1,218 @@
+//! Variable rate resampling, for shifting pitch along with speed - e.g., for doppler, or time scaling without
+//! preserving pitch.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{Configuration, Provider};
+
+
+/// Rates passed to [`VariableResampler::process`] are clamped to this, which bounds how much source it has to buffer.
+pub const MAX_RESAMPLE_RATE: f32 = 8.0;
+
+/// Longer buffers are processed in chunks of this many output frames, again to bound source storage.
+const MAX_CHUNK_FRAMES: usize = 1024;
+
+
+/// Linearly interpolating resampler that can change rate every buffer. Pulls as many frames from its source as it
+/// needs to produce each buffer. Changes in rate are ramped across a buffer, so continuously varying rates
+/// (e.g., doppler on a passing vehicle) don't produce zipper noise.
+///
+/// Source storage is fixed size once reserved with [`VariableResampler::with_channels`] or
+/// [`VariableResampler::reserve`], so that processing doesn't allocate on the audio thread.
+#[derive(Debug, Clone, Default)]
+pub struct VariableResampler {
+	/// Interleaved source frames that haven't been fully consumed yet. Never grows beyond its reserved capacity.
+	source: Vec<f32>,
+
+	/// Fractional frame position into `source`.
+	position: f64,
+
+	/// Rate at the end of the last buffer, for ramping from. None until the first buffer.
+	last_rate: Option<f32>,
+}
+
+impl VariableResampler {
+	/// Storage is allocated on first use - prefer [`VariableResampler::with_channels`] for use on the audio thread.
+	pub fn new() -> VariableResampler {
+		VariableResampler::default()
+	}
+
+	pub fn with_channels(channels: usize) -> VariableResampler {
+		let mut resampler = VariableResampler::new();
+		resampler.reserve(channels);
+		resampler
+	}
+
+	/// Allocate enough source storage for `channels` interleaved channels at any rate.
+	pub fn reserve(&mut self, channels: usize) {
+		// +3 for the fractional position and the frames interpolated towards.
+		let max_source_frames = (MAX_RESAMPLE_RATE as usize * MAX_CHUNK_FRAMES) + 3;
+		let capacity = max_source_frames * channels;
+		self.source.reserve_exact(capacity.saturating_sub(self.source.len()));
+	}
+
+	/// Drop any buffered source frames, e.g., after the source is seeked or replaced.
+	pub fn reset(&mut self) {
+		self.source.clear();
+		self.position = 0.0;
+		self.last_rate = None;
+	}
+
+	/// Fill `data` with `channels` interleaved channels, consuming `rate` source frames per output frame - so rates
+	/// above 1 raise pitch. `fill_source` is called with buffers to be completely filled with source frames.
+	pub fn process(&mut self, data: &mut [f32], channels: usize, rate: f32, mut fill_source: impl FnMut(&mut [f32])) {
+		let output_frames = data.len() / channels;
+		if output_frames == 0 {
+			return
+		}
+
+		// Only allocates if not already reserved for this many channels.
+		self.reserve(channels);
+
+		let end_rate = rate.clamp(0.0, MAX_RESAMPLE_RATE);
+		let start_rate = self.last_rate.unwrap_or(end_rate);
+
+		// Ramp across the whole buffer, even if it is split into chunks.
+		let mut chunk_end_frame = 0;
+		for chunk in data[..output_frames * channels].chunks_mut(MAX_CHUNK_FRAMES * channels) {
+			chunk_end_frame += chunk.len() / channels;
+
+			let t = chunk_end_frame as f32 / output_frames as f32;
+			let chunk_end_rate = start_rate + (end_rate - start_rate) * t;
+
+			self.process_chunk(chunk, channels, chunk_end_rate, &mut fill_source);
+		}
+	}
+
+	fn process_chunk(&mut self, data: &mut [f32], channels: usize, rate: f32, fill_source: &mut impl FnMut(&mut [f32])) {
+		let output_frames = data.len() / channels;
+
+		let end_step = rate as f64;
+		let start_step = self.last_rate.map_or(end_step, |rate| rate as f64);
+		let step_delta = (end_step - start_step) / output_frames as f64;
+
+		// Sum of the per frame steps below.
+		let total_step = start_step * output_frames as f64 + step_delta * (output_frames * (output_frames - 1)) as f64 / 2.0;
+
+		// +2 so there is always a frame to interpolate towards.
+		let required_frames = (self.position + total_step).floor() as usize + 2;
+		let available_frames = self.source.len() / channels;
+
+		if required_frames > available_frames {
+			let start = self.source.len();
+			debug_assert!(required_frames * channels <= self.source.capacity());
+
+			self.source.resize(required_frames * channels, 0.0);
+			fill_source(&mut self.source[start..]);
+		}
+
+		let mut position = self.position;
+
+		for (frame_index, frame) in data.chunks_exact_mut(channels).enumerate() {
+			let index = position.floor() as usize;
+			let t = position.fract() as f32;
+
+			let a = &self.source[index * channels..(index + 1) * channels];
+			let b = &self.source[(index + 1) * channels..(index + 2) * channels];
+
+			for ((out, a), b) in frame.iter_mut().zip(a).zip(b) {
+				*out = a + (b - a) * t;
+			}
+
+			position += start_step + step_delta * frame_index as f64;
+		}
+
+		self.position = position;
+		self.last_rate = Some(end_step as f32);
+
+		// Shift what's left to the front rather than draining, which would reallocate.
+		let consumed_samples = (self.position.floor() as usize * channels).min(self.source.len());
+		self.source.copy_within(consumed_samples.., 0);
+		self.source.truncate(self.source.len() - consumed_samples);
+		self.position -= (consumed_samples / channels) as f64;
+	}
+}
+
+
+/// Shared rate for a [`Resampled`] provider, settable from any thread.
+#[derive(Debug, Clone)]
+pub struct RateControl(Arc<AtomicU32>);
+
+impl RateControl {
+	pub fn new(rate: f32) -> RateControl {
+		RateControl(Arc::new(AtomicU32::new(rate.to_bits())))
+	}
+
+	/// Source frames consumed per output frame. Clamped to [0, MAX_RESAMPLE_RATE], zero pauses the source.
+	pub fn set(&self, rate: f32) {
+		self.0.store(rate.clamp(0.0, MAX_RESAMPLE_RATE).to_bits(), Ordering::Relaxed);
+	}
+
+	pub fn get(&self) -> f32 {
+		f32::from_bits(self.0.load(Ordering::Relaxed))
+	}
+}
+
+
+/// Plays another provider at a variable rate, shifting its pitch. E.g., for doppler on a looping engine sound:
+/// ```ignore
+/// let engine = audio::Resampled::new(engine_source);
+/// let engine_rate = engine.rate_control();
+/// // ... each frame
+/// engine_rate.set(spatial.doppler_factor(emitter, listener));
+/// ```
+pub struct Resampled<P> {
+	inner: P,
+	resampler: VariableResampler,
+	rate: RateControl,
+	channels: usize,
+}
+
+impl<P: Provider> Resampled<P> {
+	pub fn new(inner: P) -> Resampled<P> {
+		Resampled {
+			inner,
+			resampler: VariableResampler::with_channels(2),
+			rate: RateControl::new(1.0),
+			channels: 2,
+		}
+	}
+
+	pub fn rate_control(&self) -> RateControl {
+		self.rate.clone()
+	}
+
+	pub fn inner(&self) -> &P {
+		&self.inner
+	}
+
+	pub fn inner_mut(&mut self) -> &mut P {
+		&mut self.inner
+	}
+}
+
+impl<P: Provider> Provider for Resampled<P> {
+	fn on_configuration_changed(&mut self, configuration: Option<Configuration>) {
+		if let Some(configuration) = configuration {
+			self.channels = configuration.channels;
+		}
+
+		self.resampler.reset();
+		self.resampler.reserve(self.channels);
+		self.inner.on_configuration_changed(configuration);
+	}
+
+	fn fill_buffer(&mut self, buffer: &mut [f32]) {
+		let Resampled { inner, resampler, rate, channels } = self;
+
+		match rate.get() {
+			rate if rate <= 0.0 => buffer.fill(0.0),
+			rate => resampler.process(buffer, *channels, rate, |source| inner.fill_buffer(source)),
+		}
+	}
+
+	fn on_time_scale_changed(&mut self, time_scale: f32, preserve_pitch: bool) {
+		self.inner.on_time_scale_changed(time_scale, preserve_pitch);
+	}
+}
@@ -7,6 +7,9 @@ use std::marker::PhantomData;
 #[cfg(test)]
 mod test;
 
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 
 #[derive(Clone)]
 pub struct MessageBus {
@@ -0,0 +1,120 @@
+use toml::{Table, Value};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Config, table};
+
+
+/// A typed view of a section of [`Config`]. See [`Config::bind`].
+/// Fields missing from config take their values from [`Default`].
+pub trait ConfigSection: Serialize + DeserializeOwned + Default {
+	/// Report any invalid values in `errors`. See [`check_range`].
+	fn validate(&self, _errors: &mut Vec<String>) {}
+}
+
+
+/// Helper for [`ConfigSection::validate`].
+pub fn check_range<T>(errors: &mut Vec<String>, key: &str, value: T, range: impl std::ops::RangeBounds<T> + std::fmt::Debug)
+	where T: PartialOrd + std::fmt::Debug
+{
+	if !range.contains(&value) {
+		errors.push(format!("'{key}' = {value:?} is out of range {range:?}"));
+	}
+}
+
+
+impl Config {
+	/// Deserialize the section at `key` into `T`, with arguments and preview values applied.
+	/// Fails if the section contains keys `T` doesn't know about, values of the wrong type, or values that fail
+	/// [`ConfigSection::validate`].
+	pub fn bind<T: ConfigSection>(&self, key: &str) -> anyhow::Result<T> {
+		let mut merged = to_table(&T::default())?;
+		let mut errors = Vec::new();
+
+		for (layer, is_arguments) in [(&self.base, false), (&self.arguments, true), (&self.preview, false)] {
+			let Some(section) = table::get_value(layer, key) else { continue };
+			let Some(section) = section.as_table() else {
+				errors.push(format!("'{key}' is not a table"));
+				continue
+			};
+
+			merge_into(&mut merged, section, is_arguments);
+		}
+
+		// Known keys come from T's Deserialize impl rather than its defaults, so that fields which don't serialize
+		// when default (e.g., Options) are still allowed.
+		let mut unknown_keys = Vec::new();
+		let result = serde_ignored::deserialize(Value::Table(merged), |path| unknown_keys.push(path.to_string()));
+
+		errors.extend(unknown_keys.into_iter().map(|path| format!("Unknown key '{key}.{path}'")));
+
+		let value: T = match result {
+			Ok(value) => value,
+			Err(error) => {
+				errors.push(error.message().to_owned());
+				T::default()
+			}
+		};
+
+		value.validate(&mut errors);
+
+		if !errors.is_empty() {
+			anyhow::bail!("Invalid config section '{key}':\n\t{}", errors.join("\n\t"));
+		}
+
+		Ok(value)
+	}
+
+	/// Like [`Config::bind`], but logs any errors and falls back to [`Default`].
+	pub fn bind_or_default<T: ConfigSection>(&self, key: &str) -> T {
+		self.bind(key)
+			.inspect_err(|error| log::error!("{error}"))
+			.unwrap_or_default()
+	}
+
+	/// Write `value` into the section at `key`, to be persisted by the next [`Config::save`].
+	pub fn store<T: ConfigSection>(&mut self, key: &str, value: &T) -> anyhow::Result<()> {
+		let section = to_table(value)?;
+		table::set_value(&mut self.base, key, Value::Table(section));
+		Ok(())
+	}
+
+	/// Write `value` into the section at `key` without persisting it, until [`Config::revert`] is called.
+	pub fn preview<T: ConfigSection>(&mut self, key: &str, value: &T) -> anyhow::Result<()> {
+		let section = to_table(value)?;
+		table::set_value(&mut self.preview, key, Value::Table(section));
+		Ok(())
+	}
+}
+
+
+fn to_table(value: &impl Serialize) -> anyhow::Result<Table> {
+	match Value::try_from(value)? {
+		Value::Table(table) => Ok(table),
+		_ => anyhow::bail!("Config sections must serialize to tables"),
+	}
+}
+
+fn merge_into(target: &mut Table, source: &Table, is_arguments: bool) {
+	for (key, value) in source {
+		match (target.get_mut(key), value) {
+			(Some(Value::Table(target_subtable)), Value::Table(subtable)) => {
+				merge_into(target_subtable, subtable, is_arguments);
+			}
+
+			// CLI arguments are always strings, so try to interpret them as whatever type the default is.
+			(Some(existing), Value::String(string)) if is_arguments && !existing.is_str() => {
+				let value = parse_value(string).unwrap_or_else(|| value.clone());
+				target.insert(key.clone(), value);
+			}
+
+			_ => {
+				target.insert(key.clone(), value.clone());
+			}
+		}
+	}
+}
+
+fn parse_value(string: &str) -> Option<Value> {
+	let mut table: Table = toml::from_str(&format!("value = {string}")).ok()?;
+	table.remove("value")
+}
@@ -2,7 +2,14 @@
 pub mod prelude {}
 
 mod table;
-use toml::{Table, Value};
+
+pub mod bind;
+pub use bind::{ConfigSection, check_range};
+use toml::Table;
+pub use toml::Value;
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 use tracing::instrument;
 
@@ -111,6 +118,27 @@ impl Config {
 			.and_then(Value::as_str)
 	}
 
+	/// Integers are also accepted, so `2` works as well as `2.0`.
+	pub fn get_float(&self, key: &str) -> Option<f64> {
+		self.get_value(key)
+			.and_then(|value| value.as_float().or_else(|| value.as_integer().map(|value| value as f64)))
+	}
+
+	/// Set a single value without persisting it, until [`Config::revert`] is called.
+	/// `value` is parsed as toml if possible - e.g., `true`, `1.5` or `"text"` - otherwise it's treated as a bare string.
+	pub fn preview_value_from_str(&mut self, key: &str, value: &str) {
+		let value = format!("value = {value}").parse::<Table>().ok()
+			.and_then(|mut table| table.remove("value"))
+			.unwrap_or_else(|| Value::String(value.to_owned()));
+
+		table::set_value(&mut self.preview, key, value);
+	}
+
+	/// Set a single value without persisting it, until [`Config::revert`] is called.
+	pub fn preview_value(&mut self, key: &str, value: impl Into<Value>) {
+		table::set_value(&mut self.preview, key, value.into());
+	}
+
 	// pub fn get_value_or(&mut self, key: &str, default: impl Into<Value>) -> &Value {
 	// }
 }
@@ -27,6 +27,12 @@ pub fn load_from_cli() -> anyhow::Result<Table> {
 	let mut table = Table::default();
 
 	for arg in args {
+		// Bare flags like `--portable` are treated as `portable=true`.
+		if arg.starts_with("--") && !arg.contains('=') {
+			set_value(&mut table, arg[2..].trim(), Value::Boolean(true));
+			continue
+		}
+
 		let Some((key, value_str)) = arg.split_once('=') else {
 			log::warn!("Failed to parse CLI argument: '{arg}'");
 			continue
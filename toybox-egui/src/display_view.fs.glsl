@@ -0,0 +1,49 @@
+in Vertex {
+	vec4 v_color;
+	vec2 v_uv;
+};
+
+out vec4 o_color;
+
+layout(binding=0) uniform sampler2D u_image;
+
+layout(binding=0) uniform P {
+	vec4 u_channel_mask;
+	float u_use_channel_mask;
+	float u_srgb_encoded;
+	float u_ignore_alpha;
+	float u_num_channels;
+};
+
+
+vec3 srgb_to_linear(vec3 srgb) {
+	vec3 low = srgb / 12.92;
+	vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+	return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
+
+void main() {
+	vec4 texel = texture(u_image, v_uv);
+
+	// Missing channels read as 0 with alpha 1, which shows single channel images as solid red.
+	if (u_num_channels < 1.5) {
+		texel = vec4(texel.rrr, 1.0);
+	} else if (u_num_channels < 2.5) {
+		texel = vec4(texel.rg, 0.0, 1.0);
+	}
+
+	if (u_use_channel_mask > 0.5) {
+		texel = vec4(vec3(dot(texel, u_channel_mask)), 1.0);
+	}
+
+	if (u_srgb_encoded > 0.5) {
+		texel.rgb = srgb_to_linear(clamp(texel.rgb, 0.0, 1.0));
+	}
+
+	if (u_ignore_alpha > 0.5) {
+		texel.a = 1.0;
+	}
+
+	o_color = texel;
+}
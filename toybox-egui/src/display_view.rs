@@ -0,0 +1,275 @@
+use toybox_gfx as gfx;
+use crate::prelude::*;
+use gfx::prelude::*;
+
+use gfx::core::*;
+use gfx::resource_manager::*;
+
+
+const DISPLAY_VIEW_FRAGMENT_SOURCE: &str = include_str!("display_view.fs.glsl");
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ImageChannel {
+	Red,
+	Green,
+	Blue,
+	Alpha,
+}
+
+impl ImageChannel {
+	fn mask(&self) -> [f32; 4] {
+		match self {
+			ImageChannel::Red => [1.0, 0.0, 0.0, 0.0],
+			ImageChannel::Green => [0.0, 1.0, 0.0, 0.0],
+			ImageChannel::Blue => [0.0, 0.0, 1.0, 0.0],
+			ImageChannel::Alpha => [0.0, 0.0, 0.0, 1.0],
+		}
+	}
+}
+
+
+/// How [`crate::show_image_name_with`] and [`crate::show_image_handle_with`] display an image. Images that egui can't
+/// show correctly as-is - depth, single and two channel, or with any of these options set - are converted into a
+/// display view first.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ImageDisplayOptions {
+	/// Show a single channel as grayscale.
+	pub channel: Option<ImageChannel>,
+
+	/// The image holds sRGB encoded values despite having a linear format - e.g., color data rendered into an
+	/// `Rgba8` target.
+	pub srgb_encoded: bool,
+
+	pub ignore_alpha: bool,
+
+	/// How depth images are mapped to brightness. Ignored for color images.
+	pub depth: gfx::DepthVisualization,
+
+	/// Size in points. Defaults to 128x128.
+	pub size: Option<egui::Vec2>,
+}
+
+impl Default for ImageDisplayOptions {
+	fn default() -> Self {
+		ImageDisplayOptions {
+			channel: None,
+			srgb_encoded: false,
+			ignore_alpha: false,
+			depth: gfx::DepthVisualization::Remap { min: 0.9, max: 1.0 },
+			size: None,
+		}
+	}
+}
+
+impl ImageDisplayOptions {
+	pub fn channel(self, channel: impl Into<Option<ImageChannel>>) -> Self {
+		Self { channel: channel.into(), .. self }
+	}
+
+	pub fn srgb_encoded(self) -> Self {
+		Self { srgb_encoded: true, .. self }
+	}
+
+	pub fn ignore_alpha(self) -> Self {
+		Self { ignore_alpha: true, .. self }
+	}
+
+	pub fn depth(self, depth: gfx::DepthVisualization) -> Self {
+		Self { depth, .. self }
+	}
+
+	pub fn size(self, size: impl Into<egui::Vec2>) -> Self {
+		Self { size: Some(size.into()), .. self }
+	}
+
+	fn needs_conversion(&self) -> bool {
+		self.channel.is_some() || self.srgb_encoded || self.ignore_alpha
+	}
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum DisplaySource {
+	Name(ImageName),
+	Handle(ImageHandle),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct DisplayRequest {
+	pub source: DisplaySource,
+	pub options: ImageDisplayOptions,
+}
+
+#[derive(Debug, Clone, Default)]
+struct DisplayRequests(Vec<DisplayRequest>);
+
+
+fn requests_id() -> egui::Id {
+	egui::Id::new("toybox_display_view_requests")
+}
+
+/// Queue `request` for this frame, returning the index of its display view.
+pub(crate) fn request_display_view(ctx: &egui::Context, request: DisplayRequest) -> usize {
+	ctx.data_mut(|data| {
+		let requests = &mut data.get_temp_mut_or_default::<DisplayRequests>(requests_id()).0;
+		requests.push(request);
+		requests.len() - 1
+	})
+}
+
+pub(crate) fn take_display_requests(ctx: &egui::Context) -> Vec<DisplayRequest> {
+	ctx.data_mut(|data| data.remove_temp::<DisplayRequests>(requests_id()))
+		.map_or_else(Vec::new, |requests| requests.0)
+}
+
+
+#[derive(Debug, Copy, Clone)]
+enum DisplayView {
+	/// Shown as-is.
+	Direct(ImageName),
+
+	/// Converted into a color image each frame.
+	Converted { image: ImageHandle, size: Vec2i },
+
+	/// Can't be sampled as a float texture, e.g., integer and stencil images, or handles that haven't loaded yet.
+	Unsupported,
+}
+
+
+/// Display views for images shown this frame, indexed by request order. Converted images are reused between frames
+/// while their size stays the same.
+pub(crate) struct DisplayViews {
+	fragment_shader: ShaderHandle,
+	views: Vec<DisplayView>,
+}
+
+impl DisplayViews {
+	pub fn new(gfx: &mut gfx::System) -> DisplayViews {
+		DisplayViews {
+			fragment_shader: gfx.resource_manager.request(CompileShaderRequest::fragment("egui display view fs", DISPLAY_VIEW_FRAGMENT_SOURCE)),
+			views: Vec::new(),
+		}
+	}
+
+	pub fn resolve(&self, index: usize) -> Option<ImageArgument> {
+		match self.views.get(index)? {
+			DisplayView::Direct(name) => Some((*name).into()),
+			DisplayView::Converted { image, .. } => Some((*image).into()),
+			DisplayView::Unsupported => None,
+		}
+	}
+
+	/// Encode conversions for this frame's requests. Must be called before egui is painted.
+	#[tracing::instrument(skip_all, name="egui DisplayViews::update")]
+	pub fn update(&mut self, gfx: &mut gfx::System, requests: Vec<DisplayRequest>) {
+		let mut previous_views = std::mem::take(&mut self.views).into_iter();
+		let mut conversions = Vec::new();
+
+		for (index, request) in requests.iter().enumerate() {
+			let previous = previous_views.next();
+
+			let name = match request.source {
+				DisplaySource::Name(name) => Some(name),
+				DisplaySource::Handle(handle) => gfx.resource_manager.images.get_name(handle),
+			};
+
+			let info = name.and_then(|name| gfx.core.get_image_info(name));
+			let (Some(name), Some(info)) = (name, info) else {
+				release_view(gfx, previous);
+				self.views.push(DisplayView::Unsupported);
+				continue
+			};
+
+			let format = info.format;
+			let is_depth = format.is_depth() || format.is_depth_stencil();
+			let is_supported = info.image_type == ImageType::Image2D
+				&& info.samples == 1
+				&& (is_depth || (format.is_normalized() && !format.is_stencil()));
+
+			if !is_supported {
+				release_view(gfx, previous);
+				self.views.push(DisplayView::Unsupported);
+				continue
+			}
+
+			let needs_conversion = is_depth
+				|| matches!(format, ImageFormat::Red(_) | ImageFormat::RedGreen(_))
+				|| request.options.needs_conversion();
+
+			if !needs_conversion {
+				release_view(gfx, previous);
+				self.views.push(DisplayView::Direct(name));
+				continue
+			}
+
+			let size = info.size.to_xy();
+			let image = match previous {
+				Some(DisplayView::Converted { image, size: previous_size }) if previous_size == size => image,
+				previous => {
+					release_view(gfx, previous);
+					gfx.resource_manager.request(CreateImageRequest::fixed_2d(format!("egui display view #{index}"),
+						size, ImageFormat::Srgba8))
+				}
+			};
+
+			self.views.push(DisplayView::Converted { image, size });
+			conversions.push((name, image, is_depth, format, request.options));
+		}
+
+		for previous in previous_views {
+			release_view(gfx, Some(previous));
+		}
+
+		if conversions.is_empty() {
+			return
+		}
+
+		// Encoded before the egui paint group, which shares the same stage.
+		let mut group = gfx.frame_encoder.command_group(gfx::FrameStage::DebugUi)
+			.annotate("Egui Display Views");
+
+		for (source, target, is_depth, format, options) in conversions {
+			if is_depth {
+				group.visualize_depth(source, options.depth)
+					.rendertargets(&[target]);
+				continue
+			}
+
+			let num_channels = match format {
+				ImageFormat::Red(_) => 1.0,
+				ImageFormat::RedGreen(_) => 2.0,
+				_ => 4.0,
+			};
+
+			#[repr(C)]
+			#[derive(Copy, Clone)]
+			struct Params {
+				channel_mask: [f32; 4],
+				use_channel_mask: f32,
+				srgb_encoded: f32,
+				ignore_alpha: f32,
+				num_channels: f32,
+			}
+
+			let params = Params {
+				channel_mask: options.channel.map_or([0.0; 4], |channel| channel.mask()),
+				use_channel_mask: options.channel.is_some() as u32 as f32,
+				srgb_encoded: options.srgb_encoded as u32 as f32,
+				ignore_alpha: options.ignore_alpha as u32 as f32,
+				num_channels,
+			};
+
+			group.draw_fullscreen(self.fragment_shader)
+				.sampled_image(0, source, CommonSampler::Nearest)
+				.ubo(0, &[params])
+				.rendertargets(&[target]);
+		}
+	}
+}
+
+fn release_view(gfx: &mut gfx::System, view: Option<DisplayView>) {
+	if let Some(DisplayView::Converted { image, .. }) = view {
+		gfx.resource_manager.release_image(&gfx.core, image);
+	}
+}
@@ -1,6 +1,7 @@
 #![feature(let_chains)]
 
 use toybox_gfx as gfx;
+use toybox_input as input;
 
 use egui_winit::winit::{self, event::WindowEvent, window::Window};
 use egui_winit::egui::{self, output::FullOutput};
@@ -10,6 +11,10 @@ use std::rc::Rc;
 mod renderer;
 mod textures;
 mod conversions;
+mod display_view;
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub mod prelude {
 	pub use egui_winit::egui;
@@ -20,6 +25,7 @@ pub mod prelude {
 }
 
 pub use textures::{image_name_to_egui, image_handle_to_egui};
+pub use display_view::{ImageDisplayOptions, ImageChannel};
 
 
 pub struct Integration {
@@ -29,6 +35,14 @@ pub struct Integration {
 
 	renderer: renderer::Renderer,
 	texture_manager: textures::TextureManager,
+
+	scale_factor_override: Option<f32>,
+
+	/// The part of the context's zoom factor that comes from `scale_factor_override`. The rest is zoom set by the app
+	/// or user, and is preserved when the override changes.
+	override_zoom_factor: f32,
+
+	queued_events: Vec<egui::Event>,
 }
 
 impl Integration {
@@ -59,9 +73,29 @@ impl Integration {
 		Ok(Integration {
 			ctx, state, window,
 			renderer, texture_manager,
+			scale_factor_override: None,
+			override_zoom_factor: 1.0,
+			queued_events: Vec::new(),
 		})
 	}
 
+	/// Use `scale_factor` instead of the one reported by the window, for compositors that misreport it.
+	pub fn set_scale_factor_override(&mut self, scale_factor_override: Option<f32>) {
+		self.scale_factor_override = scale_factor_override;
+		self.apply_scale_factor_override();
+	}
+
+	// egui_winit always takes the native scale factor from the window, so overriding it is done through zoom instead,
+	// which it also uses for converting event coordinates. The override is multiplied into whatever zoom is already set.
+	fn apply_scale_factor_override(&mut self) {
+		let native_scale_factor = self.window.scale_factor() as f32;
+		let override_zoom_factor = self.scale_factor_override.map_or(1.0, |scale_factor| scale_factor / native_scale_factor);
+
+		let base_zoom_factor = self.ctx.zoom_factor() / self.override_zoom_factor;
+		self.ctx.set_zoom_factor(base_zoom_factor * override_zoom_factor);
+		self.override_zoom_factor = override_zoom_factor;
+	}
+
 	// Returns whether or not egui wants to consume the event
 	#[instrument(skip_all, name="egui on_event")]
 	pub fn on_event(&mut self, event: &WindowEvent) -> bool {
@@ -76,16 +110,25 @@ impl Integration {
 			return false
 		}
 
-		if let WindowEvent::ScaleFactorChanged { scale_factor, .. } = event {
-			self.renderer.scaling = *scale_factor as f32;
+		let consumed = self.state.on_window_event(&self.window, event).consumed;
+
+		if let WindowEvent::ScaleFactorChanged { .. } = event {
+			self.apply_scale_factor_override();
 		}
 
-		self.state.on_window_event(&self.window, event).consumed
+		consumed
+	}
+
+	/// Synthetic events to be added to the input for the next frame, after any real events - e.g., key presses
+	/// generated from gamepad navigation.
+	pub fn queue_events(&mut self, events: impl IntoIterator<Item=egui::Event>) {
+		self.queued_events.extend(events);
 	}
 
 	#[instrument(skip_all, name="egui start_frame")]
 	pub fn start_frame(&mut self) -> egui::Context {
-		let input = self.state.take_egui_input(&self.window);
+		let mut input = self.state.take_egui_input(&self.window);
+		input.events.append(&mut self.queued_events);
 		self.ctx.begin_frame(input);
 		self.ctx.clone()
 	}
@@ -97,7 +140,11 @@ impl Integration {
 
 		let primitives = self.ctx.tessellate(shapes, pixels_per_point);
 
+		// Includes zoom, so always matches what shapes were laid out with.
+		self.renderer.scaling = pixels_per_point;
+
 		self.texture_manager.apply_textures(gfx, &textures_delta.set);
+		self.texture_manager.update_display_views(gfx, display_view::take_display_requests(&self.ctx));
 		self.renderer.paint_triangles(gfx, &primitives, &self.texture_manager);
 		self.texture_manager.free_textures(gfx, &textures_delta.free);
 	}
@@ -105,20 +152,31 @@ impl Integration {
 
 
 
+/// Shows `name` through a display view where needed, so that depth, single channel and linear images display sensibly.
+/// See [`ImageDisplayOptions`].
 pub fn show_image_name(ui: &mut egui::Ui, name: gfx::ImageName) {
-	let id = image_name_to_egui(name);
-
-	let widget = egui::Image::new(egui::load::SizedTexture::new(id, [128.0; 2]))
-		.uv([egui::pos2(0.0, 1.0), egui::pos2(1.0, 0.0)]);
+	show_image_name_with(ui, name, ImageDisplayOptions::default());
+}
 
-	ui.add(widget);
+pub fn show_image_name_with(ui: &mut egui::Ui, name: gfx::ImageName, options: ImageDisplayOptions) {
+	show_display_view(ui, display_view::DisplaySource::Name(name), options);
 }
 
 pub fn show_image_handle(ui: &mut egui::Ui, handle: gfx::ImageHandle) {
-	let id = image_handle_to_egui(handle);
+	show_image_handle_with(ui, handle, ImageDisplayOptions::default());
+}
+
+pub fn show_image_handle_with(ui: &mut egui::Ui, handle: gfx::ImageHandle, options: ImageDisplayOptions) {
+	show_display_view(ui, display_view::DisplaySource::Handle(handle), options);
+}
 
-	let widget = egui::Image::new(egui::load::SizedTexture::new(id, [128.0; 2]))
-		.uv([egui::pos2(0.0, 1.0), egui::pos2(1.0, 0.0)]);
+fn show_display_view(ui: &mut egui::Ui, source: display_view::DisplaySource, options: ImageDisplayOptions) {
+	let index = display_view::request_display_view(ui.ctx(), display_view::DisplayRequest { source, options });
+	let id = textures::display_view_to_egui(index);
+	let size = options.size.unwrap_or(egui::Vec2::splat(128.0));
+
+	let widget = egui::Image::new(egui::load::SizedTexture::new(id, size))
+		.uv(input::ImageUv::egui_rect());
 
 	ui.add(widget);
-}
\ No newline at end of file
+}
@@ -8,6 +8,8 @@ use epaint::image::{ImageDelta, ImageData};
 use gfx::core::*;
 use gfx::resource_manager::*;
 
+use crate::display_view::{DisplayViews, DisplayRequest};
+
 use std::collections::HashMap;
 
 
@@ -16,6 +18,7 @@ pub struct TextureManager {
 	default_image: ImageName,
 
 	managed_images: HashMap<TextureId, Option<ManagedImage>>,
+	display_views: DisplayViews,
 }
 
 #[derive(Debug)]
@@ -47,6 +50,7 @@ impl TextureManager {
 			default_image,
 
 			managed_images: HashMap::new(),
+			display_views: DisplayViews::new(gfx),
 		}
 	}
 
@@ -54,29 +58,34 @@ impl TextureManager {
 		self.sampler
 	}
 
-	pub fn image_from_texture_id(&self, resource_manager: &gfx::ResourceManager, id: TextureId) -> ImageName {
+	pub fn image_from_texture_id(&self, resource_manager: &gfx::ResourceManager, id: TextureId) -> ImageArgument {
 		if let TextureId::User(id) = id {
 			let value = (id & 0xffff_ffff) as u32;
+			let upper = (id >> 33) as u32;
 			let is_image_handle = (id & IMAGE_HANDLE_BIT) != 0;
 
-			// Map to either an ImageName directly or to an ImageHandle that is immediately resolved.
-			return match is_image_handle {
-				false => unsafe {
-					ImageName::from_raw(value)
+			// Map to either an ImageName directly, an ImageHandle that is immediately resolved, or a display view.
+			return match (is_image_handle, upper) {
+				(false, 0) => unsafe {
+					ImageName::from_raw(value).into()
 				}
 
-				true => {
-					let handle = gfx::ImageHandle::from_raw(value);
+				(false, view_index) => self.display_views.resolve(view_index as usize - 1)
+					.unwrap_or(self.default_image.into()),
+
+				(true, index) => {
+					let handle = gfx::ImageHandle::from_parts(index, value);
 					resource_manager.images.get_name(handle)
 						.unwrap_or(self.default_image)
+						.into()
 				}
 			}
 		}
 
 		if let Some(Some(managed_image)) = self.managed_images.get(&id) {
-			managed_image.name
+			managed_image.name.into()
 		} else {
-			self.default_image
+			self.default_image.into()
 		}
 	}
 
@@ -128,6 +137,10 @@ impl TextureManager {
 		}
 	}
 
+	pub(crate) fn update_display_views(&mut self, gfx: &mut gfx::System, requests: Vec<DisplayRequest>) {
+		self.display_views.update(gfx, requests);
+	}
+
 	pub fn free_textures(&mut self, gfx: &mut gfx::System, to_free: &[TextureId]) {
 		for id in to_free {
 			if let Some(Some(managed_image)) = self.managed_images.remove(id) {
@@ -195,6 +208,8 @@ fn upload_managed_image_data(core: &gfx::Core, managed_image: &mut ManagedImage,
 }
 
 
+// Ids with this bit set are image handles, with the full generation in the low 32 bits and the index above this bit.
+// Otherwise ids with nothing above this bit are image names, and the rest are display views.
 const IMAGE_HANDLE_BIT: u64 = 1<<32;
 
 pub fn image_name_to_egui(name: gfx::ImageName) -> egui::TextureId {
@@ -202,5 +217,11 @@ pub fn image_name_to_egui(name: gfx::ImageName) -> egui::TextureId {
 }
 
 pub fn image_handle_to_egui(handle: gfx::ImageHandle) -> egui::TextureId {
-	egui::TextureId::User(handle.0 as u64 | IMAGE_HANDLE_BIT)
-}
\ No newline at end of file
+	// Only 31 bits are left for the index, but generations can't be truncated without aliasing older handles.
+	assert!(handle.index() < 1<<31, "Image handle index {} too large to pack into an egui texture id", handle.index());
+	egui::TextureId::User((handle.index() as u64) << 33 | IMAGE_HANDLE_BIT | handle.generation() as u64)
+}
+
+pub(crate) fn display_view_to_egui(index: usize) -> egui::TextureId {
+	egui::TextureId::User((index as u64 + 1) << 33)
+}
@@ -0,0 +1,124 @@
+//! `#[derive(GlslStruct)]` - see `toybox_gfx::glsl` for details.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Data, Fields};
+
+#[cfg(test)]
+mod test;
+
+
+/// Implements `GlslType` and `GlslStruct` for a `#[repr(C)]` struct with named fields.
+/// Generated code refers to `::toybox::gfx` by default, which can be overridden with `#[glsl(crate = "path")]`.
+#[proc_macro_derive(GlslStruct, attributes(glsl))]
+pub fn derive_glsl_struct(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+
+	match expand(input) {
+		Ok(tokens) => tokens.into(),
+		Err(error) => error.to_compile_error().into(),
+	}
+}
+
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+	let mut crate_path: syn::Path = syn::parse_quote!(::toybox::gfx);
+	let mut is_repr_c = false;
+
+	for attr in input.attrs.iter() {
+		if attr.path().is_ident("glsl") {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("crate") {
+					let path: syn::LitStr = meta.value()?.parse()?;
+					crate_path = path.parse()?;
+					Ok(())
+				} else {
+					Err(meta.error("unknown glsl attribute"))
+				}
+			})?;
+		}
+
+		if attr.path().is_ident("repr") {
+			attr.parse_nested_meta(|meta| {
+				is_repr_c |= meta.path.is_ident("C");
+
+				// Skip arguments to other reprs, e.g., align(16).
+				if meta.input.peek(syn::token::Paren) {
+					let content;
+					syn::parenthesized!(content in meta.input);
+					content.parse::<proc_macro2::TokenStream>()?;
+				}
+
+				Ok(())
+			})?;
+		}
+	}
+
+	if !is_repr_c {
+		return Err(syn::Error::new_spanned(&input.ident, "GlslStruct requires #[repr(C)]"))
+	}
+
+	let Data::Struct(data) = &input.data else {
+		return Err(syn::Error::new_spanned(&input.ident, "GlslStruct can only be derived for structs"))
+	};
+
+	let Fields::Named(fields) = &data.fields else {
+		return Err(syn::Error::new_spanned(&input.ident, "GlslStruct requires named fields"))
+	};
+
+	if !input.generics.params.is_empty() {
+		return Err(syn::Error::new_spanned(&input.generics, "GlslStruct can't be derived for generic structs"))
+	}
+
+	let ident = &input.ident;
+	let name = ident.to_string();
+	let glsl = quote!(#crate_path::glsl);
+
+	let field_infos = fields.named.iter().map(|field| {
+		let field_ident = field.ident.as_ref().unwrap();
+		let field_name = field_ident.to_string();
+		let ty = &field.ty;
+
+		quote! {
+			#glsl::GlslField {
+				name: #field_name,
+				type_name: <#ty as #glsl::GlslType>::glsl_element_type_name(),
+				array_suffix: <#ty as #glsl::GlslType>::glsl_array_suffix(),
+				std140_align: <#ty as #glsl::GlslType>::std140_align(),
+				std140_size: <#ty as #glsl::GlslType>::std140_size(),
+				rust_offset: ::core::mem::offset_of!(#ident, #field_ident),
+			}
+		}
+	});
+
+	let field_types = fields.named.iter().map(|field| &field.ty);
+
+	Ok(quote! {
+		impl #glsl::GlslType for #ident {
+			fn glsl_type_name() -> String { #name.into() }
+
+			fn std140_align() -> usize {
+				#glsl::std140_struct_layout(&<Self as #glsl::GlslStruct>::glsl_fields()).0
+			}
+
+			fn std140_size() -> usize {
+				#glsl::std140_struct_layout(&<Self as #glsl::GlslStruct>::glsl_fields()).1
+			}
+
+			fn collect_struct_declarations(out: &mut Vec<String>) {
+				#( <#field_types as #glsl::GlslType>::collect_struct_declarations(out); )*
+
+				let declaration = <Self as #glsl::GlslStruct>::glsl_struct_declaration();
+				if !out.contains(&declaration) {
+					out.push(declaration);
+				}
+			}
+		}
+
+		impl #glsl::GlslStruct for #ident {
+			fn glsl_fields() -> Vec<#glsl::GlslField> {
+				vec![ #(#field_infos),* ]
+			}
+		}
+	})
+}
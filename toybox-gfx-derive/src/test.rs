@@ -0,0 +1,57 @@
+use super::*;
+
+
+fn expand_error(input: DeriveInput) -> String {
+	expand(input).expect_err("Expected expansion to fail").to_string()
+}
+
+
+#[test]
+fn repr_c_with_align_is_accepted() {
+	let input: DeriveInput = syn::parse_quote! {
+		#[repr(C, align(16))]
+		struct Light {
+			position: Vec3,
+			radius: f32,
+		}
+	};
+
+	assert!(expand(input).is_ok());
+}
+
+#[test]
+fn repr_args_before_c_are_skipped() {
+	let input: DeriveInput = syn::parse_quote! {
+		#[repr(align(16), C)]
+		struct Light {
+			radius: f32,
+		}
+	};
+
+	assert!(expand(input).is_ok());
+}
+
+#[test]
+fn missing_repr_c_is_rejected() {
+	let input: DeriveInput = syn::parse_quote! {
+		#[repr(align(16))]
+		struct Light {
+			radius: f32,
+		}
+	};
+
+	assert_eq!(expand_error(input), "GlslStruct requires #[repr(C)]");
+}
+
+#[test]
+fn unknown_glsl_attribute_is_rejected() {
+	let input: DeriveInput = syn::parse_quote! {
+		#[repr(C)]
+		#[glsl(prefix = "u_")]
+		struct Light {
+			radius: f32,
+		}
+	};
+
+	assert_eq!(expand_error(input), "unknown glsl attribute");
+}
@@ -0,0 +1,214 @@
+//! Renders small scenes with a headless context and compares them against golden images.
+//! Goldens live in `golden/`, and can be (re)generated by running tests with `TOYBOX_UPDATE_GOLDENS=1`.
+//! When a comparison fails the actual output is written to the system temp directory for inspection.
+//!
+//! The same harness is used to check compute utilities against CPU reference implementations.
+//!
+//! Headless contexts are only available on some platforms - see `toybox_host::HeadlessHost`. This is the only place
+//! that's gated on platform: elsewhere the harness constructors return None, so tests skip rather than fail to build.
+
+use {
+	toybox_gfx as gfx,
+	toybox_vfs as vfs,
+	common::math::*,
+	std::path::PathBuf,
+};
+
+#[cfg(all(unix, not(target_os="macos")))]
+use toybox_host as host;
+
+
+pub struct GoldenHarness {
+	pub gfx: Box<gfx::System>,
+	vfs: vfs::Vfs,
+
+	// Must outlive gfx.
+	#[cfg(all(unix, not(target_os="macos")))]
+	_host: host::HeadlessHost,
+}
+
+/// How different a rendered image can be from its golden while still passing.
+#[derive(Debug, Copy, Clone)]
+pub struct Tolerance {
+	/// Maximum absolute difference in any channel for a texel to be considered matching.
+	pub per_channel: u8,
+	/// Number of texels allowed to differ by more than `per_channel`.
+	pub max_differing_texels: usize,
+}
+
+impl Tolerance {
+	pub fn exact() -> Tolerance {
+		Tolerance { per_channel: 0, max_differing_texels: 0 }
+	}
+
+	pub fn per_channel(self, per_channel: u8) -> Self {
+		Self { per_channel, .. self }
+	}
+
+	pub fn max_differing_texels(self, max_differing_texels: usize) -> Self {
+		Self { max_differing_texels, .. self }
+	}
+}
+
+impl Default for Tolerance {
+	fn default() -> Self {
+		Tolerance { per_channel: 2, max_differing_texels: 0 }
+	}
+}
+
+impl GoldenHarness {
+	/// Returns None if no headless context could be created - e.g., on CI machines without a GPU.
+	#[cfg(all(unix, not(target_os="macos")))]
+	pub fn new(size: Vec2i) -> Option<GoldenHarness> {
+		let host = host::HeadlessHost::new(size.x as u32, size.y as u32)
+			.inspect_err(|error| eprintln!("Skipping golden image test - failed to create headless context: {error:#}"))
+			.ok()?;
+
+		let core = gfx::Core::new(host.gl.clone());
+		let mut gfx = gfx::System::new(core).expect("Failed to create gfx::System");
+		gfx.resize(size);
+
+		let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+		let vfs = vfs::Vfs::with_roots(root.join("resource"), std::env::temp_dir().join("toybox-gfx-tests"));
+
+		Some(GoldenHarness {
+			gfx,
+			vfs,
+			_host: host,
+		})
+	}
+
+	#[cfg(not(all(unix, not(target_os="macos"))))]
+	pub fn new(_size: Vec2i) -> Option<GoldenHarness> {
+		eprintln!("Skipping golden image test - headless contexts aren't available on this platform");
+		None
+	}
+
+	/// Run a single frame, calling `encode` to record commands.
+	pub fn execute(&mut self, encode: impl FnOnce(&mut gfx::System)) {
+		self.gfx.start_frame();
+		encode(&mut self.gfx);
+		self.gfx.execute_frame(&self.vfs);
+	}
+
+	/// Read back the first `count` u32s of `buffer`.
+	pub fn read_u32s(&self, buffer: gfx::BufferName, count: usize) -> Vec<u32> {
+		let range = gfx::BufferRange { offset: 0, size: count * 4 };
+		let data = self.gfx.core.read_buffer_data(buffer, range);
+
+		data.chunks_exact(4)
+			.map(|bytes| u32::from_ne_bytes(bytes.try_into().unwrap()))
+			.collect()
+	}
+
+	/// Run a single frame, calling `encode` to record commands, then read back the backbuffer.
+	pub fn render(&mut self, encode: impl FnOnce(&mut gfx::System)) -> image::RgbaImage {
+		self.execute(encode);
+
+		let size = self.gfx.backbuffer_size();
+		let data = self.gfx.core.read_framebuffer_rgba8(None, size);
+
+		let image = image::RgbaImage::from_raw(size.x as u32, size.y as u32, data)
+			.expect("Readback was an unexpected size");
+
+		// GL images are bottom row first.
+		image::imageops::flip_vertical(&image)
+	}
+
+	/// Panics with a description of the difference if `image` doesn't match golden `name` within `tolerance`.
+	pub fn assert_matches_golden(&self, name: &str, image: &image::RgbaImage, tolerance: Tolerance) {
+		let golden_path = golden_path(name);
+
+		if std::env::var_os("TOYBOX_UPDATE_GOLDENS").is_some() {
+			image.save(&golden_path).expect("Failed to write golden");
+			eprintln!("Updated golden '{}'", golden_path.display());
+			return
+		}
+
+		let golden = match image::open(&golden_path) {
+			Ok(golden) => golden.into_rgba8(),
+			Err(error) => {
+				let actual_path = save_actual(name, image);
+				panic!("Failed to load golden '{}': {error}. Output written to '{}'. \
+					Rerun with TOYBOX_UPDATE_GOLDENS=1 to accept it.", golden_path.display(), actual_path.display());
+			}
+		};
+
+		if golden.dimensions() != image.dimensions() {
+			let actual_path = save_actual(name, image);
+			panic!("Golden '{name}' is {:?} but output was {:?}. Output written to '{}'",
+				golden.dimensions(), image.dimensions(), actual_path.display());
+		}
+
+		let mut differing_texels = 0;
+		let mut max_difference = 0;
+
+		for (expected, actual) in golden.pixels().zip(image.pixels()) {
+			let difference = expected.0.iter().zip(actual.0)
+				.map(|(&a, b)| a.abs_diff(b))
+				.max()
+				.unwrap_or(0);
+
+			max_difference = max_difference.max(difference);
+
+			if difference > tolerance.per_channel {
+				differing_texels += 1;
+			}
+		}
+
+		if differing_texels > tolerance.max_differing_texels {
+			let actual_path = save_actual(name, image);
+			panic!("Output doesn't match golden '{name}': {differing_texels} texels differ by more than {} \
+				(max difference {max_difference}). Output written to '{}'",
+				tolerance.per_channel, actual_path.display());
+		}
+	}
+}
+
+/// Deterministic values in `0..max`, for checking compute utilities against CPU reference implementations.
+/// Small `max`s give plenty of duplicates, so sort stability matters.
+pub fn test_values(count: usize, max: u32) -> Vec<u32> {
+	let mut state = 0x1234_5678u32;
+
+	(0..count)
+		.map(|_| {
+			state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+			(state >> 16) % max
+		})
+		.collect()
+}
+
+/// Headless [`gfx::ComputeRunner`], plus a vfs for it to load resources through.
+/// Returns None if no headless context could be created, as with [`GoldenHarness::new`].
+#[cfg(all(unix, not(target_os="macos")))]
+pub fn compute_runner() -> Option<(gfx::ComputeRunner, vfs::Vfs)> {
+	let runner = gfx::ComputeRunner::new()
+		.inspect_err(|error| eprintln!("Skipping compute runner test - {error:#}"))
+		.ok()?;
+
+	let temp_dir = std::env::temp_dir().join("toybox-gfx-tests");
+	Some((runner, vfs::Vfs::with_roots(&temp_dir, &temp_dir)))
+}
+
+#[cfg(not(all(unix, not(target_os="macos"))))]
+pub fn compute_runner() -> Option<(gfx::ComputeRunner, vfs::Vfs)> {
+	eprintln!("Skipping compute runner test - headless contexts aren't available on this platform");
+	None
+}
+
+fn golden_path(name: &str) -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+		.join("golden")
+		.join(format!("{name}.png"))
+}
+
+fn save_actual(name: &str, image: &image::RgbaImage) -> PathBuf {
+	let dir = std::env::temp_dir().join("toybox-gfx-tests");
+	let path = dir.join(format!("{name}.actual.png"));
+
+	if let Err(error) = std::fs::create_dir_all(&dir).map_err(Into::into).and_then(|_| image.save(&path)) {
+		eprintln!("Failed to save output for '{name}': {error}");
+	}
+
+	path
+}
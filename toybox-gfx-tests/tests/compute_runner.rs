@@ -0,0 +1,33 @@
+//! Compute utilities themselves are covered by the harness tests - these only check ComputeRunner's own plumbing.
+
+use toybox_gfx_tests::{compute_runner, test_values};
+
+
+#[test]
+fn buffer_round_trip() {
+	let Some((runner, _)) = compute_runner() else { return };
+
+	let values = test_values(1000, u32::MAX);
+	let buffer = runner.create_buffer_with_data(&values);
+
+	let actual: Vec<u32> = runner.read_buffer(buffer, values.len());
+	assert_eq!(actual, values);
+}
+
+/// Submits with nothing encoded shouldn't disturb existing buffers.
+#[test]
+fn empty_submits() {
+	let Some((mut runner, vfs)) = compute_runner() else { return };
+
+	let values = test_values(300, 16);
+	let buffer = runner.create_buffer_with_data(&values);
+
+	for _ in 0..3 {
+		runner.submit(&vfs).unwrap();
+	}
+
+	let actual: Vec<u32> = runner.read_buffer(buffer, values.len());
+	assert_eq!(actual, values);
+
+	runner.core.destroy_buffer(buffer);
+}
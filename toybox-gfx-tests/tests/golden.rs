@@ -0,0 +1,38 @@
+use toybox_gfx as gfx;
+use toybox_gfx_tests::{GoldenHarness, Tolerance};
+
+use common::math::*;
+use common::Color;
+
+
+fn size() -> Vec2i {
+	Vec2i::splat(64)
+}
+
+
+#[test]
+fn clear_backbuffer() {
+	let Some(mut harness) = GoldenHarness::new(size()) else { return };
+
+	let image = harness.render(|gfx| {
+		gfx.frame_encoder.backbuffer_color(Color::white());
+	});
+
+	harness.assert_matches_golden("clear_backbuffer", &image, Tolerance::exact());
+}
+
+#[test]
+fn scissored_fullscreen_draw() {
+	let Some(mut harness) = GoldenHarness::new(size()) else { return };
+
+	let image = harness.render(|gfx| {
+		gfx.frame_encoder.backbuffer_color(Color::black());
+
+		gfx.frame_encoder.command_group(gfx::FrameStage::Main)
+			.draw_fullscreen(None)
+			.sampled_image(0, gfx::BlankImage::White, gfx::CommonSampler::Nearest)
+			.scissor(gfx::Aabb2i::from_size(size() / 2));
+	});
+
+	harness.assert_matches_golden("scissored_fullscreen_draw", &image, Tolerance::exact());
+}
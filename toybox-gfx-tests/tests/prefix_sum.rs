@@ -0,0 +1,66 @@
+use toybox_gfx as gfx;
+use toybox_gfx_tests::{GoldenHarness, test_values};
+
+use common::math::*;
+
+
+fn gpu_prefix_sum(harness: &mut GoldenHarness, values: &[u32]) -> Vec<u32> {
+	let buffer = harness.gfx.core.create_buffer();
+	harness.gfx.core.upload_immutable_buffer_immediate(buffer, values);
+
+	let mut prefix_sum = gfx::GpuPrefixSum::new(&mut harness.gfx);
+	let count = values.len() as u32;
+
+	harness.execute(|gfx| {
+		prefix_sum.scan(gfx, gfx::FrameStage::Main, buffer, count);
+	});
+
+	let result = harness.read_u32s(buffer, values.len());
+	harness.gfx.core.destroy_buffer(buffer);
+	result
+}
+
+fn check_prefix_sum(count: usize) {
+	let Some(mut harness) = GoldenHarness::new(Vec2i::splat(16)) else { return };
+
+	let values = test_values(count, 16);
+	let expected = gfx::gpu_scan::exclusive_prefix_sum(&values);
+	let actual = gpu_prefix_sum(&mut harness, &values);
+
+	if let Some(index) = expected.iter().zip(&actual).position(|(a, b)| a != b) {
+		panic!("Prefix sum of {count} values differs at index {index}: expected {}, got {}", expected[index], actual[index]);
+	}
+}
+
+
+#[test]
+fn cpu_reference() {
+	assert_eq!(gfx::gpu_scan::exclusive_prefix_sum(&[]), Vec::<u32>::new());
+	assert_eq!(gfx::gpu_scan::exclusive_prefix_sum(&[3, 1, 4, 1, 5]), vec![0, 3, 4, 8, 9]);
+	assert_eq!(gfx::gpu_scan::exclusive_prefix_sum(&[u32::MAX, 2, 0]), vec![0, u32::MAX, 1]);
+}
+
+#[test]
+fn single_value() {
+	check_prefix_sum(1);
+}
+
+#[test]
+fn partial_block() {
+	check_prefix_sum(100);
+}
+
+#[test]
+fn exact_block() {
+	check_prefix_sum(256);
+}
+
+#[test]
+fn two_levels() {
+	check_prefix_sum(1000);
+}
+
+#[test]
+fn three_levels() {
+	check_prefix_sum(70_000);
+}
@@ -0,0 +1,33 @@
+use toybox_gfx as gfx;
+use toybox_gfx_tests::{GoldenHarness, test_values};
+
+use common::math::*;
+
+
+#[test]
+fn sort_with_values() {
+	let Some(mut harness) = GoldenHarness::new(Vec2i::splat(16)) else { return };
+
+	let keys = test_values(5000, 4096);
+	let indices: Vec<u32> = (0..keys.len() as u32).collect();
+	let count = keys.len() as u32;
+
+	let key_buffer = harness.gfx.core.create_buffer();
+	let value_buffer = harness.gfx.core.create_buffer();
+	harness.gfx.core.upload_immutable_buffer_immediate(key_buffer, &keys);
+	harness.gfx.core.upload_immutable_buffer_immediate(value_buffer, &indices);
+
+	let mut sort = gfx::GpuRadixSort::new(&mut harness.gfx, gfx::SortKeyType::U32);
+	harness.execute(|gfx| {
+		sort.sort(gfx, gfx::FrameStage::Main, key_buffer, Some(value_buffer), count);
+	});
+
+	let mut expected: Vec<(u32, u32)> = keys.iter().copied().zip(indices.iter().copied()).collect();
+	expected.sort_by_key(|&(key, _)| key);
+
+	let sorted_keys = harness.read_u32s(key_buffer, keys.len());
+	let sorted_values = harness.read_u32s(value_buffer, keys.len());
+	let actual: Vec<(u32, u32)> = sorted_keys.into_iter().zip(sorted_values).collect();
+
+	assert_eq!(actual, expected);
+}
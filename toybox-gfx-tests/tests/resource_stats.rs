@@ -0,0 +1,33 @@
+use toybox_gfx as gfx;
+use toybox_gfx_tests::GoldenHarness;
+
+use common::math::*;
+
+
+/// Requesting the same resources every frame shouldn't accumulate anything once the first frame has been processed.
+#[test]
+fn steady_state_does_not_grow() {
+	let Some(mut harness) = GoldenHarness::new(Vec2i::splat(16)) else { return };
+
+	let mut encode_frame = |harness: &mut GoldenHarness| {
+		harness.execute(|gfx| {
+			let target = gfx.resource_manager.request(gfx::CreateImageRequest::rendertarget("soak target", gfx::ImageFormat::rgba16f()));
+
+			gfx.frame_encoder.command_group(gfx::FrameStage::Main)
+				.draw_fullscreen(None)
+				.sampled_image(0, gfx::BlankImage::White, gfx::CommonSampler::Nearest)
+				.rendertargets(&[target]);
+		});
+	};
+
+	encode_frame(&mut harness);
+	let initial = harness.gfx.resource_manager.stats();
+
+	assert_eq!(initial.pending_requests.total(), 0, "Requests left unprocessed after a frame: {:?}", initial.pending_requests);
+
+	for _ in 0..30 {
+		encode_frame(&mut harness);
+	}
+
+	assert_eq!(harness.gfx.resource_manager.stats(), initial);
+}
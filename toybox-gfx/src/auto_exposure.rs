@@ -0,0 +1,425 @@
+use crate::prelude::*;
+use crate::{
+	System, FrameStage, CommandGroupEncoder, ShaderHandle, ImageHandle, ImageFormat,
+	CreateImageRequest, BufferName, BufferRange, ImageArgument, CommonSampler,
+	command::draw::DrawCmdBuilder,
+	shaders,
+};
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+
+const NUM_HISTOGRAM_BINS: usize = 256;
+
+/// How many frames of metering results can be in flight at once. If all slots are still waiting on the GPU,
+/// metering is skipped rather than stalling.
+const NUM_READBACK_SLOTS: usize = 3;
+
+const CPU_METERING_SIZE: i32 = 64;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum MeteringMode {
+	/// Luminance histogram built in compute, with the result read back asynchronously a few frames later.
+	#[default]
+	Compute,
+
+	/// Source is downsampled on the GPU then read back synchronously and metered on the CPU.
+	/// Stalls, but doesn't require compute - for drivers where compute is slow or broken.
+	Cpu,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AutoExposureSettings {
+	/// Range of log2 luminance covered by the histogram. Anything outside is clamped.
+	pub min_log_luminance: f32,
+	pub max_log_luminance: f32,
+
+	/// The luminance that average scene luminance should be mapped to.
+	pub target_luminance: f32,
+
+	/// In stops, applied after metering.
+	pub exposure_compensation: f32,
+
+	/// Rate of adaptation in stops per second, when the scene gets darker or brighter respectively.
+	pub adaptation_speed_up: f32,
+	pub adaptation_speed_down: f32,
+
+	pub min_exposure: f32,
+	pub max_exposure: f32,
+}
+
+impl Default for AutoExposureSettings {
+	fn default() -> Self {
+		AutoExposureSettings {
+			min_log_luminance: -10.0,
+			max_log_luminance: 4.0,
+			target_luminance: 0.18,
+			exposure_compensation: 0.0,
+			adaptation_speed_up: 3.0,
+			adaptation_speed_down: 1.0,
+			min_exposure: 1.0 / 64.0,
+			max_exposure: 64.0,
+		}
+	}
+}
+
+
+/// Postprocess component that meters the average luminance of an HDR image and smoothly adapts an exposure value
+/// to match. Call [`AutoExposure::meter`] once per frame, then use [`AutoExposure::exposure`] directly or
+/// [`AutoExposure::apply`] to scale the image before tonemapping.
+/// Resources are only released by [`AutoExposure::destroy`].
+pub struct AutoExposure {
+	pub settings: AutoExposureSettings,
+	pub mode: MeteringMode,
+
+	/// Smoothed exposure, in stops.
+	log_exposure: f32,
+	snap_to_next_result: bool,
+
+	histogram_shader: ShaderHandle,
+	average_shader: ShaderHandle,
+	exposure_shader: ShaderHandle,
+
+	cpu_metering_image: ImageHandle,
+
+	readback: Rc<RefCell<Readback>>,
+}
+
+impl AutoExposure {
+	pub fn new(gfx: &mut System) -> AutoExposure {
+		let rm = &mut gfx.resource_manager;
+		let core = &gfx.core;
+
+		let histogram_shader = rm.compile_compute_shader("auto exposure histogram cs", shaders::AUTO_EXPOSURE_HISTOGRAM_CS_SHADER_SOURCE);
+		let average_shader = rm.compile_compute_shader("auto exposure average cs", shaders::AUTO_EXPOSURE_AVERAGE_CS_SHADER_SOURCE);
+		let exposure_shader = rm.compile_fragment_shader("exposure fs", shaders::EXPOSURE_FS_SHADER_SOURCE);
+
+		let cpu_metering_image = rm.request(CreateImageRequest::fixed_2d("auto exposure cpu metering", Vec2i::splat(CPU_METERING_SIZE), ImageFormat::rgba16f()));
+
+		AutoExposure {
+			settings: AutoExposureSettings::default(),
+			mode: MeteringMode::default(),
+
+			log_exposure: 0.0,
+			snap_to_next_result: true,
+
+			histogram_shader,
+			average_shader,
+			exposure_shader,
+
+			cpu_metering_image,
+
+			readback: Rc::new(RefCell::new(Readback::new(core))),
+		}
+	}
+
+	/// Release the metering image and shaders once the current frame is done with them.
+	/// Readback buffers are freed once any metering still in flight is dropped.
+	pub fn destroy(self, gfx: &mut System) {
+		let AutoExposure { histogram_shader, average_shader, exposure_shader, cpu_metering_image, .. } = self;
+
+		gfx.frame_encoder.command_group(FrameStage::Final)
+			.annotate("Destroy Auto Exposure")
+			.execute(move |core, rm| {
+				rm.release_image(core, cpu_metering_image);
+				rm.release_shader(core, histogram_shader);
+				rm.release_shader(core, average_shader);
+				rm.release_shader(core, exposure_shader);
+			});
+	}
+
+	pub fn exposure(&self) -> f32 {
+		self.log_exposure.exp2()
+	}
+
+	/// Average log2 luminance from the most recently completed metering, if any.
+	pub fn metered_log_luminance(&self) -> Option<f32> {
+		self.readback.borrow().latest
+	}
+
+	/// Jump straight to the next metered exposure instead of adapting - e.g., after a camera cut.
+	pub fn reset(&mut self) {
+		self.snap_to_next_result = true;
+	}
+
+	/// Schedule metering of `source` for this frame, and adapt exposure towards the most recent result.
+	/// Results lag a couple of frames behind in [`MeteringMode::Compute`].
+	pub fn meter(&mut self, gfx: &mut System, source: impl Into<ImageArgument>, dt: f32) {
+		let source = source.into();
+
+		self.readback.borrow_mut().poll(&gfx.core);
+		self.adapt(dt);
+
+		match self.mode {
+			MeteringMode::Compute => self.meter_compute(gfx, source),
+			MeteringMode::Cpu => self.meter_cpu(gfx, source),
+		}
+	}
+
+	/// Fullscreen draw that scales `source` by the current exposure into the bound rendertargets.
+	pub fn apply<'g>(&self, encoder: &'g mut CommandGroupEncoder<'_>, source: impl Into<ImageArgument>) -> DrawCmdBuilder<'g> {
+		let mut builder = encoder.draw_fullscreen(self.exposure_shader);
+		builder.sampled_image(0, source, CommonSampler::Nearest)
+			.ubo(0, &[self.exposure()]);
+
+		builder
+	}
+
+	fn adapt(&mut self, dt: f32) {
+		let Some(log_luminance) = self.metered_log_luminance() else { return };
+
+		let AutoExposureSettings{target_luminance, exposure_compensation, min_exposure, max_exposure, ..} = self.settings;
+
+		let target = (target_luminance.log2() - log_luminance + exposure_compensation)
+			.clamp(min_exposure.log2(), max_exposure.log2());
+
+		if std::mem::take(&mut self.snap_to_next_result) {
+			self.log_exposure = target;
+			return
+		}
+
+		let speed = match target > self.log_exposure {
+			true => self.settings.adaptation_speed_up,
+			false => self.settings.adaptation_speed_down,
+		};
+
+		let t = 1.0 - (-speed * dt).exp();
+		self.log_exposure += (target - self.log_exposure) * t;
+	}
+
+	fn log_luminance_params(&self) -> [f32; 2] {
+		let AutoExposureSettings{min_log_luminance, max_log_luminance, ..} = self.settings;
+		[min_log_luminance, (max_log_luminance - min_log_luminance).max(0.001)]
+	}
+
+	fn meter_compute(&self, gfx: &mut System, source: ImageArgument) {
+		let Some(reservation) = SlotReservation::new(&self.readback) else {
+			log::trace!("Skipping auto exposure metering - all readback slots in flight");
+			return
+		};
+
+		let (histogram_buffer, readback_buffer, slot_range) = {
+			let readback = self.readback.borrow();
+			(readback.histogram_buffer, readback.buffer, readback.slot_range(reservation.slot))
+		};
+
+		let params = self.log_luminance_params();
+
+		let mut group = gfx.frame_encoder.command_group(FrameStage::Postprocess)
+			.annotate("Auto Exposure Metering");
+
+		group.compute(self.histogram_shader)
+			.groups_from_image_size(source)
+			.sampled_image(0, source, CommonSampler::Nearest)
+			.ubo(0, &params)
+			.ssbo(0, histogram_buffer);
+
+		group.compute(self.average_shader)
+			.ubo(0, &params)
+			.ssbo(0, histogram_buffer)
+			.ssbo(1, (readback_buffer, slot_range));
+
+		group.execute(move |core, _| reservation.submit(core));
+	}
+
+	fn meter_cpu(&self, gfx: &mut System, source: ImageArgument) {
+		gfx.frame_encoder.command_group(FrameStage::Postprocess)
+			.annotate("Auto Exposure Downsample")
+			.draw_fullscreen(None)
+			.sampled_image(0, source, CommonSampler::Linear)
+			.rendertargets(&[self.cpu_metering_image]);
+
+		let image = self.cpu_metering_image;
+		let [min_log_luminance, log_luminance_range] = self.log_luminance_params();
+		let readback = self.readback.clone();
+
+		// Deferred to the end of the frame so that at least the rest of the frame is submitted before we stall.
+		gfx.frame_encoder.command_group(FrameStage::Final)
+			.annotate("Auto Exposure Readback")
+			.execute(move |core, rm| {
+				let Some(image_name) = rm.images.get_name(image) else { return };
+
+				let mut pixels = vec![[0.0f32; 4]; (CPU_METERING_SIZE * CPU_METERING_SIZE) as usize];
+				core.read_image(image_name, None, ImageFormat::Rgba(crate::ComponentFormat::F32), &mut pixels);
+
+				let luminances = pixels.iter()
+					.map(|&[r, g, b, _]| 0.2126 * r + 0.7152 * g + 0.0722 * b);
+
+				readback.borrow_mut().latest = Some(average_log_luminance(luminances, min_log_luminance, log_luminance_range));
+			});
+	}
+}
+
+
+/// CPU equivalent of the histogram and average passes - see `auto_exposure_histogram.cs.glsl`.
+pub fn average_log_luminance(luminances: impl IntoIterator<Item=f32>, min_log_luminance: f32, log_luminance_range: f32) -> f32 {
+	let mut histogram = [0u32; NUM_HISTOGRAM_BINS];
+
+	for luminance in luminances {
+		let bin = match luminance < 0.0001 {
+			true => 0,
+			false => {
+				let t = ((luminance.log2() - min_log_luminance) / log_luminance_range).clamp(0.0, 1.0);
+				(t * 254.0 + 1.0) as usize
+			}
+		};
+
+		histogram[bin] += 1;
+	}
+
+	let (weighted_sum, total) = histogram.iter().enumerate().skip(1)
+		.fold((0.0, 0), |(sum, total), (bin, &count)| (sum + bin as f32 * count as f32, total + count));
+
+	let average_bin = match total {
+		0 => 1.0,
+		_ => weighted_sum / total as f32,
+	};
+
+	min_log_luminance + (average_bin - 1.0) / 254.0 * log_luminance_range
+}
+
+
+
+/// GL objects used by compute metering, including a persistently mapped ring of results from the average pass, each
+/// guarded by a fence. Shared with the commands that use it, so that nothing is deleted while still in use.
+struct Readback {
+	gl: gl::Gl,
+
+	histogram_buffer: BufferName,
+
+	buffer: BufferName,
+	buffer_ptr: *const u8,
+	slot_stride: usize,
+
+	next_slot: usize,
+
+	/// Slots handed out by [`SlotReservation::new`] that haven't been submitted yet.
+	reserved: usize,
+	in_flight: VecDeque<(usize, gl::types::GLsync)>,
+
+	latest: Option<f32>,
+}
+
+impl Readback {
+	fn new(core: &crate::Core) -> Readback {
+		// Cleared by the average pass after each use.
+		let histogram_buffer = core.create_buffer();
+		core.upload_immutable_buffer_immediate(histogram_buffer, &[0u32; NUM_HISTOGRAM_BINS]);
+		core.set_debug_label(histogram_buffer, "Auto Exposure Histogram");
+
+		let slot_stride = core.capabilities().ssbo_bind_alignment.max(16);
+		let flags = gl::MAP_READ_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+		let buffer = core.create_buffer();
+		core.set_debug_label(buffer, "Auto Exposure Readback");
+		core.allocate_buffer_storage(buffer, slot_stride * NUM_READBACK_SLOTS, flags);
+
+		let buffer_ptr = unsafe { core.map_buffer(buffer, None) };
+		assert!(!buffer_ptr.is_null(), "Failed to map auto exposure readback buffer");
+
+		Readback {
+			gl: core.gl.clone(),
+
+			histogram_buffer,
+
+			buffer,
+			buffer_ptr,
+			slot_stride,
+
+			next_slot: 0,
+			reserved: 0,
+			in_flight: VecDeque::new(),
+
+			latest: None,
+		}
+	}
+
+	fn slot_range(&self, slot: usize) -> BufferRange {
+		BufferRange {
+			offset: slot * self.slot_stride,
+			size: 16,
+		}
+	}
+
+	fn submit(&mut self, core: &crate::Core, slot: usize) {
+		let fence = unsafe {
+			core.gl.MemoryBarrier(gl::CLIENT_MAPPED_BUFFER_BARRIER_BIT);
+			core.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
+		};
+
+		self.in_flight.push_back((slot, fence));
+	}
+
+	/// Take the result of any completed readbacks without waiting.
+	fn poll(&mut self, core: &crate::Core) {
+		while let Some(&(slot, fence)) = self.in_flight.front() {
+			let result = unsafe { core.gl.ClientWaitSync(fence, 0, 0) };
+			if !matches!(result, gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED) {
+				break
+			}
+
+			unsafe {
+				core.gl.DeleteSync(fence);
+
+				let value_ptr = self.buffer_ptr.add(slot * self.slot_stride).cast::<f32>();
+				self.latest = Some(value_ptr.read_volatile());
+			}
+
+			self.in_flight.pop_front();
+		}
+	}
+}
+
+impl Drop for Readback {
+	fn drop(&mut self) {
+		unsafe {
+			for (_, fence) in self.in_flight.drain(..) {
+				self.gl.DeleteSync(fence);
+			}
+
+			// Deleting a mapped buffer also unmaps it.
+			self.gl.DeleteBuffers(1, &self.buffer.0);
+			self.gl.DeleteBuffers(1, &self.histogram_buffer.0);
+		}
+	}
+}
+
+
+/// A readback slot, claimed when metering is encoded so that metering twice before a submit can't share a slot.
+/// Released if dropped without being submitted - e.g., if its command group is discarded.
+struct SlotReservation {
+	readback: Rc<RefCell<Readback>>,
+	slot: usize,
+}
+
+impl SlotReservation {
+	fn new(readback: &Rc<RefCell<Readback>>) -> Option<SlotReservation> {
+		let mut inner = readback.borrow_mut();
+
+		if inner.in_flight.len() + inner.reserved >= NUM_READBACK_SLOTS {
+			return None
+		}
+
+		let slot = inner.next_slot;
+		inner.next_slot = (slot + 1) % NUM_READBACK_SLOTS;
+		inner.reserved += 1;
+
+		Some(SlotReservation {
+			readback: readback.clone(),
+			slot,
+		})
+	}
+
+	fn submit(self, core: &crate::Core) {
+		self.readback.borrow_mut().submit(core, self.slot);
+	}
+}
+
+impl Drop for SlotReservation {
+	fn drop(&mut self) {
+		self.readback.borrow_mut().reserved -= 1;
+	}
+}
@@ -1,7 +1,7 @@
 use crate::prelude::*;
 use crate::core::*;
 use crate::resource_manager::{ResourceManager, arguments::*};
-use crate::upload_heap::{UploadStage, UploadHeap};
+use crate::upload_heap::{UploadStage, UploadHeap, StagedUploadId};
 
 
 // TODO: string interning would be great
@@ -123,6 +123,12 @@ impl BindingDescription {
 		}
 	}
 
+	pub fn remap_staged_uploads(&mut self, remap: &impl Fn(StagedUploadId) -> StagedUploadId) {
+		for bind_desc in self.buffer_bindings.iter_mut() {
+			remap_staged_bind_source(&mut bind_desc.source, remap);
+		}
+	}
+
 	pub fn resolve_staged_bind_sources(&mut self, upload_heap: &UploadHeap) {
 		for bind_desc in self.buffer_bindings.iter_mut() {
 			resolve_staged_bind_source(&mut bind_desc.source, upload_heap);
@@ -132,7 +138,8 @@ impl BindingDescription {
 	pub fn resolve_image_bind_sources(&mut self, rm: &mut ResourceManager) {
 		for ImageBindDesc{source, ..} in self.image_bindings.iter_mut() {
 			let name = match *source {
-				ImageArgument::Handle(handle) => rm.images.get_name(handle).expect("Failed to resolve image handle"),
+				ImageArgument::Handle(handle) => rm.images.get_name(handle)
+					.unwrap_or_else(|| rm.placeholder_for_unresolved_image()),
 				ImageArgument::Blank(image) => rm.get_blank_image(image),
 				ImageArgument::Name(_) => continue,
 			};
@@ -260,11 +267,17 @@ impl BindingDescription {
 	}
 }
 
+pub fn remap_staged_bind_source(source: &mut BufferArgument, remap: &impl Fn(StagedUploadId) -> StagedUploadId) {
+	if let BufferArgument::Staged(upload_id) = *source {
+		*source = BufferArgument::Staged(remap(upload_id));
+	}
+}
+
 pub fn resolve_staged_bind_source(source: &mut BufferArgument, upload_heap: &UploadHeap) {
 	if let BufferArgument::Staged(upload_id) = *source {
-		let allocation = upload_heap.resolve_allocation(upload_id);
+		let (name, allocation) = upload_heap.resolve_allocation(upload_id);
 		*source = BufferArgument::Name {
-			name: upload_heap.buffer_name(),
+			name,
 			range: Some(allocation),
 		};
 	}
@@ -0,0 +1,217 @@
+//! Packs grayscale material maps - occlusion, roughness, metalness - into the channels of a single RGBA texture, so a
+//! material needs one texture fetch and a quarter of the memory instead of one texture per map.
+//!
+//! Which map goes in which channel is declared by a [`PackingConvention`], defaulting to glTF's ORM layout. The same
+//! convention is used at bake time by `toybox::bake`, at runtime by [`LoadPackedImageRequest`](crate::LoadPackedImageRequest),
+//! and by shaders through the [`PACKED_MATERIAL_IMPORT`] import, so packers and shaders can't disagree about layout.
+//!
+//! ```glsl
+//! #import packed_material
+//! layout(binding=1) uniform sampler2D u_material_packed;
+//!
+//! PackedMaterial material = unpack_material(texture(u_material_packed, v_uv));
+//! float roughness = material.roughness;
+//! ```
+//!
+//! Apps using a different convention should re-register the import with [`PackingConvention::register_shader_import`].
+
+use crate::prelude::*;
+use crate::{
+	ImageFormat, ComponentFormat, DecodedImage,
+	glsl::ShaderImports,
+	shaders,
+};
+
+use tracing::instrument;
+
+
+/// Name of the shader import providing `PackedMaterial` and `unpack_material`.
+pub const PACKED_MATERIAL_IMPORT: &str = "packed_material";
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MaterialChannel {
+	Occlusion,
+	Roughness,
+	Metalness,
+	Height,
+}
+
+impl MaterialChannel {
+	pub const ALL: [MaterialChannel; 4] = [
+		MaterialChannel::Occlusion,
+		MaterialChannel::Roughness,
+		MaterialChannel::Metalness,
+		MaterialChannel::Height,
+	];
+
+	/// Value used when a map is missing, either from a packed texture or from the convention.
+	pub fn default_value(&self) -> f32 {
+		match self {
+			MaterialChannel::Occlusion => 1.0,
+			MaterialChannel::Roughness => 1.0,
+			MaterialChannel::Metalness => 0.0,
+			MaterialChannel::Height => 0.5,
+		}
+	}
+
+	/// Suffix of source map file stems, e.g., `brick_roughness.png`.
+	pub fn file_suffix(&self) -> &'static str {
+		match self {
+			MaterialChannel::Occlusion => "_occlusion",
+			MaterialChannel::Roughness => "_roughness",
+			MaterialChannel::Metalness => "_metalness",
+			MaterialChannel::Height => "_height",
+		}
+	}
+
+	fn glsl_name(&self) -> &'static str {
+		match self {
+			MaterialChannel::Occlusion => "OCCLUSION",
+			MaterialChannel::Roughness => "ROUGHNESS",
+			MaterialChannel::Metalness => "METALNESS",
+			MaterialChannel::Height => "HEIGHT",
+		}
+	}
+}
+
+
+/// Which [`MaterialChannel`] is stored in each of the red, green, blue and alpha channels of a packed texture.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct PackingConvention {
+	pub channels: [Option<MaterialChannel>; 4],
+}
+
+impl PackingConvention {
+	/// glTF's occlusion/roughness/metalness layout. Alpha is left unused.
+	pub const ORM: PackingConvention = PackingConvention {
+		channels: [Some(MaterialChannel::Occlusion), Some(MaterialChannel::Roughness), Some(MaterialChannel::Metalness), None],
+	};
+
+	/// [`PackingConvention::ORM`] with height in alpha, for parallax mapping.
+	pub const ORMH: PackingConvention = PackingConvention {
+		channels: [Some(MaterialChannel::Occlusion), Some(MaterialChannel::Roughness), Some(MaterialChannel::Metalness), Some(MaterialChannel::Height)],
+	};
+
+	pub fn channel_index(&self, channel: MaterialChannel) -> Option<usize> {
+		self.channels.iter().position(|&slot| slot == Some(channel))
+	}
+
+	/// `#define`s describing this convention, consumed by the [`PACKED_MATERIAL_IMPORT`] import.
+	pub fn glsl_defines(&self) -> String {
+		let mut defines = String::new();
+
+		for channel in MaterialChannel::ALL {
+			let index = self.channel_index(channel).map_or(-1, |index| index as i32);
+			let name = channel.glsl_name();
+
+			defines += &format!("#define PACKED_{name}_CHANNEL {index}\n");
+			defines += &format!("#define PACKED_{name}_DEFAULT {:?}\n", channel.default_value());
+		}
+
+		defines
+	}
+
+	/// Replace the [`PACKED_MATERIAL_IMPORT`] import with one reading this convention.
+	/// Only affects shaders compiled after this call.
+	pub fn register_shader_import(&self, imports: &mut ShaderImports) {
+		imports.register(PACKED_MATERIAL_IMPORT, self.glsl_defines() + shaders::PACKED_MATERIAL_GLSL_SOURCE);
+	}
+}
+
+impl Default for PackingConvention {
+	fn default() -> Self {
+		PackingConvention::ORM
+	}
+}
+
+
+/// Pack grayscale `sources` into a linear RGBA8 image laid out according to `convention`. Only the first channel of each
+/// source is read, and its values are copied as-is - 8-bit maps decode as sRGB but are treated as raw values, as is
+/// standard for material maps. Channels without a source are filled with [`MaterialChannel::default_value`], and sources
+/// for channels the convention doesn't include are ignored with a warning.
+#[instrument(skip_all, name="gfx pack_channels")]
+pub fn pack_channels(convention: &PackingConvention, sources: &[(MaterialChannel, &DecodedImage)]) -> anyhow::Result<DecodedImage> {
+	let Some(&(_, first)) = sources.first() else {
+		anyhow::bail!("Trying to pack material without any source maps")
+	};
+
+	let size = first.size;
+	let num_texels = (size.x * size.y) as usize;
+
+	let mut data = vec![0u8; num_texels * 4];
+
+	for (index, slot) in convention.channels.iter().enumerate() {
+		let default = slot.map_or(0, |channel| unorm_to_u8(channel.default_value()));
+
+		for texel in data.chunks_exact_mut(4) {
+			texel[index] = default;
+		}
+	}
+
+	for &(channel, source) in sources {
+		if source.size != size {
+			anyhow::bail!("Mismatched sizes while packing {channel:?}. Expected {}x{}, but got {}x{}",
+				size.x, size.y, source.size.x, source.size.y);
+		}
+
+		let Some(index) = convention.channel_index(channel) else {
+			log::warn!("Packing convention has no slot for {channel:?} - ignoring");
+			continue
+		};
+
+		let values = first_channel_values(source)?;
+		for (texel, value) in data.chunks_exact_mut(4).zip(values) {
+			texel[index] = value;
+		}
+	}
+
+	Ok(DecodedImage {
+		size,
+		format: ImageFormat::rgba8(),
+		data,
+	})
+}
+
+
+/// The first channel of each texel of `image`, as unorm8.
+fn first_channel_values(image: &DecodedImage) -> anyhow::Result<Vec<u8>> {
+	let read_u16 = |bytes: &[u8]| u16::from_ne_bytes([bytes[0], bytes[1]]);
+	let read_f32 = |bytes: &[u8]| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+
+	let values = match image.format {
+		ImageFormat::Red(ComponentFormat::Unorm8) => image.data.clone(),
+
+		ImageFormat::Srgba8 | ImageFormat::Rgba(ComponentFormat::Unorm8)
+			=> image.data.chunks_exact(4).map(|texel| texel[0]).collect(),
+
+		ImageFormat::Red(ComponentFormat::Unorm16)
+			=> image.data.chunks_exact(2).map(|texel| unorm16_to_u8(read_u16(texel))).collect(),
+
+		ImageFormat::RedGreen(ComponentFormat::Unorm16)
+			=> image.data.chunks_exact(4).map(|texel| unorm16_to_u8(read_u16(texel))).collect(),
+
+		ImageFormat::Rgba(ComponentFormat::Unorm16)
+			=> image.data.chunks_exact(8).map(|texel| unorm16_to_u8(read_u16(texel))).collect(),
+
+		ImageFormat::Rgba(ComponentFormat::F32)
+			=> image.data.chunks_exact(16).map(|texel| unorm_to_u8(read_f32(texel))).collect(),
+
+		format => anyhow::bail!("Can't pack channels from {format:?} images"),
+	};
+
+	Ok(values)
+}
+
+fn unorm_to_u8(value: f32) -> u8 {
+	(value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn unorm16_to_u8(value: u16) -> u8 {
+	((value as u32 * 255 + 32767) / 65535) as u8
+}
+
+
+pub(crate) fn register_shader_imports(imports: &mut ShaderImports) {
+	PackingConvention::default().register_shader_import(imports);
+}
@@ -0,0 +1,174 @@
+//! Color space helpers. [`Color`] values passed to gfx are always linear - the backbuffer and any sRGB images do
+//! the final encode - so colors picked in an image editor or written as hex need converting with
+//! [`ColorExt::from_srgb8`] or [`ColorExt::from_hex`] first.
+
+use crate::prelude::*;
+
+
+/// Decode a single sRGB encoded channel in [0, 1] to linear.
+pub fn srgb_to_linear(value: f32) -> f32 {
+	if value <= 0.04045 {
+		value / 12.92
+	} else {
+		((value + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+/// Encode a single linear channel in [0, 1] as sRGB.
+pub fn linear_to_srgb(value: f32) -> f32 {
+	if value <= 0.0031308 {
+		value * 12.92
+	} else {
+		1.055 * value.powf(1.0 / 2.4) - 0.055
+	}
+}
+
+
+/// A color in the OKLab perceptual color space. Interpolating in OKLab keeps perceived brightness and hue changing
+/// evenly, unlike interpolating in linear or sRGB, which goes muddy or dark between saturated colors.
+/// See https://bottosson.github.io/posts/oklab/
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Oklab {
+	/// Perceived lightness, roughly [0, 1].
+	pub l: f32,
+	pub a: f32,
+	pub b: f32,
+	pub alpha: f32,
+}
+
+impl Oklab {
+	pub fn from_linear(color: Color) -> Oklab {
+		let Color{r, g, b, a: alpha} = color;
+
+		let l = 0.4122214708*r + 0.5363325363*g + 0.0514459929*b;
+		let m = 0.2119034982*r + 0.6806995451*g + 0.1073969566*b;
+		let s = 0.0883024619*r + 0.2817188376*g + 0.6299787005*b;
+
+		let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+
+		Oklab {
+			l: 0.2104542553*l + 0.7936177850*m - 0.0040720468*s,
+			a: 1.9779984951*l - 2.4285922050*m + 0.4505937099*s,
+			b: 0.0259040371*l + 0.7827717662*m - 0.8086757660*s,
+			alpha,
+		}
+	}
+
+	pub fn to_linear(&self) -> Color {
+		let Oklab{l, a, b, alpha} = *self;
+
+		let l_ = l + 0.3963377774*a + 0.2158037573*b;
+		let m_ = l - 0.1055613458*a - 0.0638541728*b;
+		let s_ = l - 0.0894841775*a - 1.2914855480*b;
+
+		let (l, m, s) = (l_*l_*l_, m_*m_*m_, s_*s_*s_);
+
+		Color::rgba(
+			4.0767416621*l - 3.3077115913*m + 0.2309699292*s,
+			-1.2684380046*l + 2.6097574011*m - 0.3413193965*s,
+			-0.0041960863*l - 0.7034186147*m + 1.7076147010*s,
+			alpha,
+		)
+	}
+
+	pub fn lerp(&self, other: Oklab, t: f32) -> Oklab {
+		Oklab {
+			l: self.l + (other.l - self.l) * t,
+			a: self.a + (other.a - self.a) * t,
+			b: self.b + (other.b - self.b) * t,
+			alpha: self.alpha + (other.alpha - self.alpha) * t,
+		}
+	}
+}
+
+
+pub trait ColorExt: Sized {
+	/// From 8 bit sRGB encoded channels, e.g., as picked in an image editor. Alpha is always linear.
+	fn from_srgb8(rgba: [u8; 4]) -> Self;
+
+	/// Parse `#rgb`, `#rrggbb` or `#rrggbbaa` as sRGB. The `#` is optional.
+	fn from_hex(hex: &str) -> anyhow::Result<Self>;
+
+	/// Treat `self` as sRGB encoded and convert to linear.
+	fn srgb_to_linear(&self) -> Self;
+
+	/// Treat `self` as linear and convert to sRGB encoded.
+	fn linear_to_srgb(&self) -> Self;
+
+	/// Encode as 8 bit sRGB, e.g., for writing to images or displaying as hex.
+	fn to_srgb8(&self) -> [u8; 4];
+
+	fn to_hex(&self) -> String;
+
+	fn to_oklab(&self) -> Oklab;
+
+	/// Interpolate between two linear colors through OKLab.
+	fn lerp_oklab(&self, other: Self, t: f32) -> Self;
+}
+
+impl ColorExt for Color {
+	fn from_srgb8([r, g, b, a]: [u8; 4]) -> Color {
+		let decode = |value: u8| srgb_to_linear(value as f32 / 255.0);
+		Color::rgba(decode(r), decode(g), decode(b), a as f32 / 255.0)
+	}
+
+	fn from_hex(hex: &str) -> anyhow::Result<Color> {
+		let digits = hex.trim().trim_start_matches('#');
+		anyhow::ensure!(digits.is_ascii(), "Invalid hex color '{hex}'");
+
+		let parse = |digits: &str| u8::from_str_radix(digits, 16)
+			.map_err(|_| anyhow::anyhow!("Invalid hex color '{hex}'"));
+
+		let rgba = match digits.len() {
+			3 => {
+				let [r, g, b] = [0, 1, 2].map(|index| parse(&digits[index..index+1]));
+				[r? * 17, g? * 17, b? * 17, 255]
+			}
+
+			6 | 8 => {
+				let alpha = match digits.len() {
+					8 => parse(&digits[6..8])?,
+					_ => 255,
+				};
+
+				[parse(&digits[0..2])?, parse(&digits[2..4])?, parse(&digits[4..6])?, alpha]
+			}
+
+			_ => anyhow::bail!("Invalid hex color '{hex}' - expected 3, 6 or 8 digits"),
+		};
+
+		Ok(Color::from_srgb8(rgba))
+	}
+
+	fn srgb_to_linear(&self) -> Color {
+		Color::rgba(srgb_to_linear(self.r), srgb_to_linear(self.g), srgb_to_linear(self.b), self.a)
+	}
+
+	fn linear_to_srgb(&self) -> Color {
+		Color::rgba(linear_to_srgb(self.r), linear_to_srgb(self.g), linear_to_srgb(self.b), self.a)
+	}
+
+	fn to_srgb8(&self) -> [u8; 4] {
+		let encode = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+		let srgb = self.linear_to_srgb();
+
+		[encode(srgb.r), encode(srgb.g), encode(srgb.b), encode(srgb.a)]
+	}
+
+	fn to_hex(&self) -> String {
+		let [r, g, b, a] = self.to_srgb8();
+
+		match a {
+			255 => format!("#{r:02x}{g:02x}{b:02x}"),
+			_ => format!("#{r:02x}{g:02x}{b:02x}{a:02x}"),
+		}
+	}
+
+	fn to_oklab(&self) -> Oklab {
+		Oklab::from_linear(*self)
+	}
+
+	fn lerp_oklab(&self, other: Color, t: f32) -> Color {
+		self.to_oklab().lerp(other.to_oklab(), t).to_linear()
+	}
+}
@@ -1,5 +1,5 @@
 use crate::bindings::{self, BindingDescription};
-use crate::upload_heap::{UploadStage, UploadHeap};
+use crate::upload_heap::{UploadStage, UploadHeap, StagedUploadId};
 
 use crate::{
 	Capabilities,
@@ -8,14 +8,17 @@ use crate::{
 
 pub mod compute;
 pub mod draw;
+pub mod upload_image;
 
 pub use compute::{ComputeCmd, DispatchSize};
 pub use draw::{DrawCmd, PrimitiveType};
+pub use upload_image::UploadImageCmd;
 
 
 pub enum Command {
 	Draw(DrawCmd),
 	Compute(ComputeCmd),
+	UploadImage(UploadImageCmd),
 
 	ClearBuffer,
 	ClearTexture,
@@ -73,6 +76,40 @@ impl Command {
 				}
 			},
 
+			UploadImage(UploadImageCmd { source: BufferArgument::Staged(upload_id), .. }) => {
+				// Enough for any texel or compressed block.
+				upload_stage.update_staged_upload_alignment(*upload_id, 16);
+			},
+
+			_ => {}
+		}
+	}
+
+	/// Rewrite staged upload ids, e.g., after moving commands to a different [`UploadStage`].
+	pub fn remap_staged_uploads(&mut self, remap: &impl Fn(StagedUploadId) -> StagedUploadId) {
+		use Command::*;
+
+		match self {
+			Draw(DrawCmd { bindings, index_buffer, .. }) => {
+				bindings.remap_staged_uploads(remap);
+
+				if let Some(bind_source) = index_buffer {
+					bindings::remap_staged_bind_source(bind_source, remap);
+				}
+			},
+
+			Compute(ComputeCmd { bindings, dispatch_size, .. }) => {
+				bindings.remap_staged_uploads(remap);
+
+				if let DispatchSize::Indirect(bind_source) = dispatch_size {
+					bindings::remap_staged_bind_source(bind_source, remap);
+				}
+			},
+
+			UploadImage(UploadImageCmd { source, .. }) => {
+				bindings::remap_staged_bind_source(source, remap);
+			},
+
 			_ => {}
 		}
 	}
@@ -97,6 +134,10 @@ impl Command {
 				}
 			},
 
+			UploadImage(UploadImageCmd { source, .. }) => {
+				bindings::resolve_staged_bind_source(source, upload_heap);
+			},
+
 			_ => {}
 		}
 	}
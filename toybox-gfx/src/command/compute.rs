@@ -3,7 +3,8 @@ use crate::bindings::*;
 
 use crate::{
 	Core, ResourceManager,
-	upload_heap::UploadStage,
+	AsStageableSlice,
+	upload_heap::{UploadStage, PUSH_CONSTANTS_UBO_INDEX},
 	arguments::*,
 };
 
@@ -44,7 +45,8 @@ impl ComputeCmd {
 			ShaderArgument::Common(shader) => rm.get_common_shader(shader),
 		};
 
-		let pipeline = rm.resolve_compute_pipeline(core, shader_handle);
+		// Dispatches using shaders that failed to compile or aren't ready yet are skipped.
+		let Some(pipeline) = rm.resolve_compute_pipeline(core, shader_handle) else { return };
 		core.bind_shader_pipeline(pipeline);
 
 		self.bindings.bind(core, rm);
@@ -76,8 +78,17 @@ impl ComputeCmd {
 			DispatchSize::DeriveFromImage(bind_source) => {
 				let image_name = match bind_source {
 					ImageArgument::Name(name) => name,
-					ImageArgument::Handle(handle) => rm.images.get_name(handle).expect("Failed to resolve image handle"),
 					ImageArgument::Blank(image) => rm.get_blank_image(image),
+
+					// Work sized by an image that's still decoding or failed to load is skipped, rather than run
+					// over a placeholder.
+					ImageArgument::Handle(handle) => match rm.images.get_name(handle) {
+						Some(name) => name,
+						None => {
+							log::trace!("Skipping dispatch sized by unresolved image {handle:?}");
+							return
+						}
+					},
 				};
 
 				let workgroup_size = rm.shaders.get_resource(shader_handle)
@@ -138,6 +149,14 @@ impl<'cg> ComputeCmdBuilder<'cg> {
 		self.buffer(BufferBindTarget::SsboIndex(index), buffer)
 	}
 
+	/// Stage a small amount of data for this command only, bound to the UBO at [`PUSH_CONSTANTS_UBO_INDEX`].
+	pub fn push_constants<T>(&mut self, data: &T) -> &mut Self
+		where T: AsStageableSlice + ?Sized
+	{
+		let upload_id = self.upload_stage.stage_push_constants(data);
+		self.ubo(PUSH_CONSTANTS_UBO_INDEX, upload_id)
+	}
+
 	pub fn sampled_image(&mut self, unit: u32, image: impl Into<ImageArgument>, sampler: impl Into<SamplerArgument>) -> &mut Self {
 		self.cmd.bindings.bind_sampled_image(ImageBindTarget::Sampled(unit), image, sampler);
 		self
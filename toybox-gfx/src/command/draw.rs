@@ -1,11 +1,15 @@
 use crate::prelude::*;
 use crate::bindings::*;
 
+use std::borrow::Cow;
+
 use crate::{
 	Core, ResourceManager,
 	ShaderArgument,
+	DrawPipelineShaders,
 	BlendMode,
-	upload_heap::UploadStage,
+	AsStageableSlice,
+	upload_heap::{UploadStage, PUSH_CONSTANTS_UBO_INDEX},
 	arguments::*,
 };
 
@@ -16,6 +20,9 @@ pub enum PrimitiveType {
 	Points = gl::POINTS,
 	Lines = gl::LINES,
 	Triangles = gl::TRIANGLES,
+
+	/// Requires a tessellation evaluation shader. Vertices per patch are set by [`DrawCmd::patch_vertices`].
+	Patches = gl::PATCHES,
 }
 
 
@@ -24,9 +31,13 @@ pub struct DrawCmd {
 	pub bindings: BindingDescription,
 
 	vertex_shader: ShaderArgument,
+	tess_control_shader: Option<ShaderArgument>,
+	tess_evaluation_shader: Option<ShaderArgument>,
+	geometry_shader: Option<ShaderArgument>,
 	fragment_shader: Option<ShaderArgument>,
 
 	pub primitive_type: PrimitiveType,
+	pub patch_vertices: u32,
 
 	pub num_elements: u32,
 	pub num_instances: u32,
@@ -39,6 +50,14 @@ pub struct DrawCmd {
 	pub blend_mode: Option<BlendMode>,
 	pub depth_test: bool,
 	pub depth_write: bool,
+
+	pub scissor_rect: Option<Aabb2i>,
+
+	/// Wrapped around just this draw in a debug group, so that it can be found in captures. See [`DrawCmdBuilder::label`].
+	pub label: Option<Cow<'static, str>>,
+
+	#[cfg(feature="debug-uniforms")]
+	pub uniforms: Vec<(String, crate::UniformValue)>,
 }
 
 impl From<DrawCmd> for super::Command {
@@ -53,9 +72,13 @@ impl DrawCmd {
 			bindings: Default::default(),
 
 			vertex_shader,
+			tess_control_shader: None,
+			tess_evaluation_shader: None,
+			geometry_shader: None,
 			fragment_shader: fragment_shader,
 
 			primitive_type: PrimitiveType::Triangles,
+			patch_vertices: 3,
 
 			num_elements: 3,
 			num_instances: 1,
@@ -66,6 +89,12 @@ impl DrawCmd {
 			blend_mode: None,
 			depth_test: true,
 			depth_write: true,
+
+			scissor_rect: None,
+			label: None,
+
+			#[cfg(feature="debug-uniforms")]
+			uniforms: Vec::new(),
 		}
 	}
 
@@ -74,9 +103,13 @@ impl DrawCmd {
 			bindings: Default::default(),
 
 			vertex_shader: CommonShader::FullscreenVertex.into(),
+			tess_control_shader: None,
+			tess_evaluation_shader: None,
+			geometry_shader: None,
 			fragment_shader: Some(fragment_shader),
 
 			primitive_type: PrimitiveType::Triangles,
+			patch_vertices: 3,
 
 			num_elements: 6,
 			num_instances: 1,
@@ -87,32 +120,79 @@ impl DrawCmd {
 			blend_mode: None,
 			depth_test: false,
 			depth_write: false,
+
+			scissor_rect: None,
+			label: None,
+
+			#[cfg(feature="debug-uniforms")]
+			uniforms: Vec::new(),
 		}
 	}
 
-	#[tracing::instrument(skip_all, name="DrawCmd::execute")]
-	pub fn execute(&self, core: &mut Core, rm: &mut ResourceManager) {
-		let vertex_shader_handle = match self.vertex_shader {
-			ShaderArgument::Handle(name) => name,
+	pub(crate) fn has_tessellation(&self) -> bool {
+		self.tess_evaluation_shader.is_some()
+	}
+
+	pub(crate) fn has_tess_control_without_evaluation(&self) -> bool {
+		self.tess_control_shader.is_some() && self.tess_evaluation_shader.is_none()
+	}
+
+	fn resolve_shaders(&self, rm: &ResourceManager) -> DrawPipelineShaders {
+		let resolve = |argument: ShaderArgument| match argument {
+			ShaderArgument::Handle(handle) => handle,
 			ShaderArgument::Common(shader) => rm.get_common_shader(shader),
 		};
 
-		let fragment_shader_handle = match self.fragment_shader {
-			Some(ShaderArgument::Handle(name)) => Some(name),
-			Some(ShaderArgument::Common(shader)) => Some(rm.get_common_shader(shader)),
-			None => None,
-		};
+		DrawPipelineShaders {
+			vertex: resolve(self.vertex_shader),
+			tess_control: self.tess_control_shader.map(resolve),
+			tess_evaluation: self.tess_evaluation_shader.map(resolve),
+			geometry: self.geometry_shader.map(resolve),
+			fragment: self.fragment_shader.map(resolve),
+		}
+	}
+
+	#[tracing::instrument(skip_all, name="DrawCmd::execute")]
+	pub fn execute(&self, core: &mut Core, rm: &mut ResourceManager) {
+		if let Some(label) = &self.label {
+			core.push_debug_group(label);
+		}
+
+		self.execute_unlabelled(core, rm);
+
+		if self.label.is_some() {
+			core.pop_debug_group();
+		}
+	}
+
+	fn execute_unlabelled(&self, core: &mut Core, rm: &mut ResourceManager) {
+		let shaders = self.resolve_shaders(rm);
+
+		// Draws using shaders that failed to compile or aren't ready yet are skipped.
+		let Some(pipeline) = rm.resolve_draw_pipeline(core, shaders) else { return };
 
 		// TODO(pat.m): eugh. should probably be part of a larger pipeline state management system
-		let num_user_clip_planes = rm.shaders.get_resource(vertex_shader_handle).unwrap().num_user_clip_planes;
+		// Decided for the whole pipeline, since the stage writing clip distances isn't necessarily the last.
+		let num_user_clip_planes = shaders.pre_raster_stages()
+			.filter_map(|shader| rm.shaders.get_resource(shader))
+			.map(|resource| resource.num_user_clip_planes)
+			.max()
+			.unwrap_or(0);
 		core.set_user_clip_planes(num_user_clip_planes);
 
-		let pipeline = rm.resolve_draw_pipeline(core, vertex_shader_handle, fragment_shader_handle);
 		core.bind_shader_pipeline(pipeline);
 
+		#[cfg(feature="debug-uniforms")]
+		self.apply_uniforms(core, rm, &shaders);
+
+		if let PrimitiveType::Patches = self.primitive_type {
+			core.set_patch_vertices(self.patch_vertices);
+		}
+
 		core.set_blend_mode(self.blend_mode);
 		core.set_depth_test(self.depth_test);
 		core.set_depth_write(self.depth_write);
+		core.set_scissor_rect(self.scissor_rect);
 
 		self.bindings.bind(core, rm);
 
@@ -149,6 +229,27 @@ impl DrawCmd {
 			}
 		}
 	}
+
+	#[cfg(feature="debug-uniforms")]
+	fn apply_uniforms(&self, core: &Core, rm: &mut ResourceManager, shaders: &DrawPipelineShaders) {
+		for (name, value) in self.uniforms.iter() {
+			let mut found = false;
+
+			// Uniforms belong to individual programs, so set it in every stage that declares it.
+			for shader in shaders.iter() {
+				let Some(shader_name) = rm.shaders.get_name(shader) else { continue };
+
+				if let Some(location) = core.uniform_location(shader_name, name) {
+					core.set_uniform(shader_name, location, *value);
+					found = true;
+				}
+			}
+
+			if !found && rm.missing_debug_uniforms.insert(name.clone()) {
+				log::warn!("Uniform '{name}' not found in draw shaders - it may have been optimised out");
+			}
+		}
+	}
 }
 
 
@@ -173,6 +274,24 @@ impl<'cg> DrawCmdBuilder<'cg> {
 		self
 	}
 
+	/// Insert a geometry shader between the vertex (or tessellation) and fragment stages.
+	pub fn geometry_shader(&mut self, shader: impl Into<ShaderArgument>) -> &mut Self {
+		self.cmd.geometry_shader = Some(shader.into());
+		self
+	}
+
+	/// Tessellate patches of `patch_vertices` vertices. The control shader is optional - without one, default
+	/// tessellation levels are used. Also switches the primitive type to [`PrimitiveType::Patches`].
+	pub fn tessellation(&mut self, control_shader: impl Into<Option<ShaderArgument>>, evaluation_shader: impl Into<ShaderArgument>,
+		patch_vertices: u32) -> &mut Self
+	{
+		self.cmd.tess_control_shader = control_shader.into();
+		self.cmd.tess_evaluation_shader = Some(evaluation_shader.into());
+		self.cmd.primitive_type = PrimitiveType::Patches;
+		self.cmd.patch_vertices = patch_vertices;
+		self
+	}
+
 	pub fn indexed(&mut self, buffer: impl IntoBufferArgument) -> &mut Self {
 		let buffer_argument = buffer.into_buffer_argument(self.upload_stage);
 		self.cmd.index_buffer = Some(buffer_argument);
@@ -197,6 +316,14 @@ impl<'cg> DrawCmdBuilder<'cg> {
 		self.buffer(BufferBindTarget::SsboIndex(index), buffer)
 	}
 
+	/// Stage a small amount of data for this command only, bound to the UBO at [`PUSH_CONSTANTS_UBO_INDEX`].
+	pub fn push_constants<T>(&mut self, data: &T) -> &mut Self
+		where T: AsStageableSlice + ?Sized
+	{
+		let upload_id = self.upload_stage.stage_push_constants(data);
+		self.ubo(PUSH_CONSTANTS_UBO_INDEX, upload_id)
+	}
+
 	pub fn sampled_image(&mut self, unit: u32, image: impl Into<ImageArgument>, sampler: impl Into<SamplerArgument>) -> &mut Self {
 		self.cmd.bindings.bind_sampled_image(ImageBindTarget::Sampled(unit), image, sampler);
 		self
@@ -232,4 +359,38 @@ impl<'cg> DrawCmdBuilder<'cg> {
 		self.cmd.depth_write = depth_write;
 		self
 	}
+
+	pub fn scissor(&mut self, rect: impl Into<Option<Aabb2i>>) -> &mut Self {
+		self.cmd.scissor_rect = rect.into();
+		self
+	}
+
+	/// Name this draw in graphics debuggers - e.g., after the entity it draws. Only kept in debug builds, so static
+	/// labels are free in release. Use [`Self::label_with`] for labels that need formatting.
+	pub fn label(&mut self, label: impl Into<Cow<'static, str>>) -> &mut Self {
+		if cfg!(debug_assertions) {
+			self.cmd.label = Some(label.into());
+		}
+
+		self
+	}
+
+	/// Like [`Self::label`], but `label` is only called in debug builds.
+	pub fn label_with<L>(&mut self, label: impl FnOnce() -> L) -> &mut Self
+		where L: Into<Cow<'static, str>>
+	{
+		if cfg!(debug_assertions) {
+			self.cmd.label = Some(label().into());
+		}
+
+		self
+	}
+
+	/// Set a standalone uniform by name, for prototyping where a UBO struct is overkill.
+	/// Requires the `debug-uniforms` feature - prefer [`Self::ubo`] or [`Self::push_constants`] in hot paths.
+	#[cfg(feature="debug-uniforms")]
+	pub fn uniform(&mut self, name: &str, value: impl Into<crate::UniformValue>) -> &mut Self {
+		self.cmd.uniforms.push((name.to_owned(), value.into()));
+		self
+	}
 }
@@ -0,0 +1,48 @@
+use crate::{
+	Core, ResourceManager,
+	ImageFormat, ImageRange,
+	arguments::*,
+};
+
+
+/// Copies data staged in the upload heap into part of an image, ordered with the rest of the frame's commands.
+/// See [`crate::CommandGroupEncoder::upload_image`].
+#[derive(Debug)]
+pub struct UploadImageCmd {
+	pub image: ImageArgument,
+	pub level: u32,
+	pub range: Option<ImageRange>,
+	pub format: ImageFormat,
+	pub source: BufferArgument,
+}
+
+impl From<UploadImageCmd> for super::Command {
+	fn from(o: UploadImageCmd) -> Self {
+		Self::UploadImage(o)
+	}
+}
+
+impl UploadImageCmd {
+	#[tracing::instrument(skip_all, name="UploadImageCmd::execute")]
+	pub fn execute(&self, core: &mut Core, rm: &mut ResourceManager) {
+		let image_name = match self.image {
+			ImageArgument::Name(name) => name,
+
+			// Uploads to images that are still loading or failed to load are skipped.
+			ImageArgument::Handle(handle) => match rm.images.get_name(handle) {
+				Some(name) => name,
+				None => {
+					log::warn!("Skipping image upload to unresolved image {handle:?}");
+					return
+				}
+			},
+
+			ImageArgument::Blank(_) => panic!("Trying to upload to a basic image - these are immutable"),
+		};
+
+		let BufferArgument::Name{name: buffer_name, range: Some(buffer_range)} = self.source
+			else { panic!("Image upload source must be resolved to a buffer range before execution") };
+
+		core.copy_image_level_from_buffer(image_name, self.level, self.range, self.format, buffer_name, buffer_range);
+	}
+}
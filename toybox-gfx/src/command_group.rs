@@ -1,13 +1,14 @@
 use crate::prelude::*;
 use crate::bindings::*;
-use crate::command::{Command, compute, draw};
+use crate::command::{Command, UploadImageCmd, compute, draw};
 use crate::resource_manager::{ShaderHandle, arguments::*};
 use crate::upload_heap::{UploadStage, StagedUploadId};
+use crate::core::{ClearValue, FramebufferAttachment, ImageFormat, ImageRange};
 
 use std::ops::{Deref, DerefMut};
 
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub enum FrameStage {
 	Start,
 
@@ -37,6 +38,9 @@ pub struct CommandGroup {
 	pub commands: SmallVec<[Command; 16]>,
 
 	pub shared_bindings: BindingDescription,
+
+	/// Load and store ops for attachments of the group's shared framebuffer. Attachments not listed are loaded and stored.
+	pub attachment_ops: SmallVec<[(FramebufferAttachment, AttachmentOps); 2]>,
 }
 
 impl CommandGroup {
@@ -45,17 +49,84 @@ impl CommandGroup {
 			stage,
 			commands: SmallVec::new(),
 			shared_bindings: BindingDescription::new(),
+			attachment_ops: SmallVec::new(),
 		}
 	}
 
 	pub(crate) fn reset(&mut self) {
 		self.commands.clear();
 		self.shared_bindings.clear();
+		self.attachment_ops.clear();
+	}
+
+	pub fn attachment_ops(&self, attachment: FramebufferAttachment) -> AttachmentOps {
+		self.attachment_ops.iter()
+			.find(|(existing, _)| *existing == attachment)
+			.map_or(AttachmentOps::default(), |(_, ops)| *ops)
+	}
+
+	pub(crate) fn attachment_ops_mut(&mut self, attachment: FramebufferAttachment) -> &mut AttachmentOps {
+		let index = match self.attachment_ops.iter().position(|(existing, _)| *existing == attachment) {
+			Some(index) => index,
+			None => {
+				self.attachment_ops.push((attachment, AttachmentOps::default()));
+				self.attachment_ops.len() - 1
+			}
+		};
+
+		&mut self.attachment_ops[index].1
+	}
+}
+
+
+/// What happens to the contents of a framebuffer attachment before a command group runs.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum LoadOp {
+	/// Keep whatever was rendered previously.
+	#[default]
+	Load,
+
+	Clear(ClearValue),
+
+	/// Previous contents are undefined. Lets tiled drivers skip loading the attachment.
+	DontCare,
+}
+
+impl LoadOp {
+	pub fn clear(value: impl Into<ClearValue>) -> LoadOp {
+		LoadOp::Clear(value.into())
 	}
 }
 
+/// What happens to the contents of a framebuffer attachment after a command group runs.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StoreOp {
+	#[default]
+	Store,
+
+	/// Contents are undefined after the group, e.g., for depth only needed while drawing the group.
+	DontCare,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct AttachmentOps {
+	pub load: LoadOp,
+	pub store: StoreOp,
+}
+
+
 
 
+/// How [`CommandGroupEncoder::visualize_depth`] maps depth values to brightness.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DepthVisualization {
+	Raw,
+	/// Stretch raw depth values between `min` and `max` to the full range - useful since most of a perspective
+	/// depth buffer is close to 1.
+	Remap { min: f32, max: f32 },
+	/// Recover linear view depth assuming a standard perspective projection.
+	Linearize { near: f32, far: f32 },
+}
 
 pub struct CommandGroupEncoder<'g> {
 	group: &'g mut CommandGroup,
@@ -121,6 +192,17 @@ impl<'g> CommandGroupEncoder<'g> {
 	pub fn bind_rendertargets(&mut self, rts: impl Into<FramebufferArgument>)  {
 		self.group.shared_bindings.bind_framebuffer(rts);
 	}
+
+	/// Declare what happens to `attachment` of the shared framebuffer before any commands in the group run.
+	/// Applies to the whole group for this frame, regardless of when it is called.
+	pub fn set_load_op(&mut self, attachment: FramebufferAttachment, load: LoadOp) {
+		self.group.attachment_ops_mut(attachment).load = load;
+	}
+
+	/// Declare what happens to `attachment` of the shared framebuffer after all commands in the group have run.
+	pub fn set_store_op(&mut self, attachment: FramebufferAttachment, store: StoreOp) {
+		self.group.attachment_ops_mut(attachment).store = store;
+	}
 }
 
 /// Commands
@@ -154,6 +236,55 @@ impl<'g> CommandGroupEncoder<'g> {
 		draw::DrawCmdBuilder {cmd, upload_stage: self.upload_stage}
 	}
 
+	/// Fullscreen draw that applies a 3D color grading LUT (see [gfx::ResourceManager::load_lut]) to `source`,
+	/// blended with the ungraded color by `blend`.
+	pub fn color_grade(&mut self, source: impl Into<ImageArgument>, lut: impl Into<ImageArgument>, blend: f32) -> draw::DrawCmdBuilder<'_> {
+		self.add(draw::DrawCmd::from_fullscreen_shader(CommonShader::ColorGradeFragment.into()));
+		let Some(Command::Draw(cmd)) = self.group.commands.last_mut() else { unreachable!() };
+		let mut builder = draw::DrawCmdBuilder {cmd, upload_stage: self.upload_stage};
+
+		builder.sampled_image(0, source, CommonSampler::Linear)
+			.sampled_image(1, lut, CommonSampler::Linear)
+			.ubo(0, &[blend.clamp(0.0, 1.0)]);
+
+		builder
+	}
+
+	/// Fullscreen draw that copies `source` into the bound rendertargets, with blue noise dithering to hide banding.
+	/// `output_bits` should match the bits per channel of the rendertarget - see [gfx::Core::backbuffer_color_bits].
+	/// Only useful if `source` is higher precision than the rendertarget.
+	pub fn dither(&mut self, source: impl Into<ImageArgument>, output_bits: u32) -> draw::DrawCmdBuilder<'_> {
+		self.add(draw::DrawCmd::from_fullscreen_shader(CommonShader::DitherFragment.into()));
+		let Some(Command::Draw(cmd)) = self.group.commands.last_mut() else { unreachable!() };
+		let mut builder = draw::DrawCmdBuilder {cmd, upload_stage: self.upload_stage};
+
+		let output_steps = ((1u32 << output_bits) - 1) as f32;
+
+		builder.sampled_image(0, source, CommonSampler::Nearest)
+			.sampled_image(1, BlankImage::BlueNoise, CommonSampler::Nearest)
+			.ubo(0, &[output_steps]);
+
+		builder
+	}
+
+	/// Fullscreen draw that writes a grayscale visualization of the depth image `source` into the bound rendertargets.
+	pub fn visualize_depth(&mut self, source: impl Into<ImageArgument>, mode: DepthVisualization) -> draw::DrawCmdBuilder<'_> {
+		self.add(draw::DrawCmd::from_fullscreen_shader(CommonShader::DepthVisualizeFragment.into()));
+		let Some(Command::Draw(cmd)) = self.group.commands.last_mut() else { unreachable!() };
+		let mut builder = draw::DrawCmdBuilder {cmd, upload_stage: self.upload_stage};
+
+		let params = match mode {
+			DepthVisualization::Raw => [0.0, 0.0, 1.0, 0.0],
+			DepthVisualization::Remap{min, max} => [1.0, min, max, 0.0],
+			DepthVisualization::Linearize{near, far} => [2.0, near, far, 0.0],
+		};
+
+		builder.sampled_image(0, source, CommonSampler::Nearest)
+			.ubo(0, &params);
+
+		builder
+	}
+
 	pub fn compute(&mut self, compute_shader: impl Into<ShaderArgument>) -> compute::ComputeCmdBuilder<'_> {
 		self.add(compute::ComputeCmd::new(compute_shader.into()));
 		let Some(Command::Compute(cmd)) = self.group.commands.last_mut() else { unreachable!() };
@@ -166,13 +297,35 @@ impl<'g> CommandGroupEncoder<'g> {
 		self.execute(move |core, rm| {
 			let name = match image {
 				ImageArgument::Name(name) => name,
-				ImageArgument::Handle(handle) => rm.images.get_name(handle).expect("Failed to resolve image handle"),
+
+				// Still decoding or failed to load - there's nothing to clear yet.
+				ImageArgument::Handle(handle) => match rm.images.get_name(handle) {
+					Some(name) => name,
+					None => return,
+				},
+
 				ImageArgument::Blank(_) => panic!("Trying to clear a basic image - these are immutable"),
 			};
 
 			core.clear_image_to_default(name);
 		});
 	}
+
+	/// Stage `data` in the upload heap and copy it into `level` of `image` when this group executes.
+	/// `range` defaults to the whole level, and `data` must match `format` and the size of `range` exactly.
+	pub fn upload_image(&mut self, image: impl Into<ImageArgument>, level: u32, range: impl Into<Option<ImageRange>>,
+		format: ImageFormat, data: &impl crate::AsStageableSlice)
+	{
+		let source = self.upload(data).into();
+
+		self.add(UploadImageCmd {
+			image: image.into(),
+			level,
+			range: range.into(),
+			format,
+			source,
+		});
+	}
 }
 
 pub struct AnnotatedCommandGroupEncoder<'g> {
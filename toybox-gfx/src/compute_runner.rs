@@ -0,0 +1,154 @@
+use crate::prelude::*;
+#[cfg(all(unix, not(target_os="macos")))]
+use crate::host::HeadlessHost;
+use crate::command::Command;
+use crate::command_group::{CommandGroup, CommandGroupEncoder, FrameStage};
+use crate::upload_heap::UploadStage;
+use crate::{Core, ResourceManager, BufferName, BufferRange};
+
+use anyhow::Context;
+use tracing::instrument;
+
+
+/// Runs compute work outside of the game loop, with just a [`Core`] and [`ResourceManager`] - no window,
+/// [`System`](crate::System) or frame encoder. For asset bakers, tools and tests.
+///
+/// Commands are recorded with [`ComputeRunner::encode`], and executed in the order they were recorded by
+/// [`ComputeRunner::submit`]:
+/// ```ignore
+/// let mut runner = gfx::ComputeRunner::new()?;
+/// let data = runner.create_buffer_with_data(&values);
+///
+/// let mut sort = gfx::GpuRadixSort::with_resource_manager(&mut runner.resource_manager, gfx::SortKeyType::U32);
+/// runner.encode(|core, encoder| sort.sort_in(core, encoder, data, None, values.len() as u32));
+///
+/// runner.submit(&vfs)?;
+/// let sorted: Vec<u32> = runner.read_buffer(data, values.len());
+/// ```
+pub struct ComputeRunner {
+	pub core: Core,
+	pub resource_manager: ResourceManager,
+
+	group: CommandGroup,
+	upload_stage: UploadStage,
+
+	// Must be dropped after everything else using the context.
+	#[cfg(all(unix, not(target_os="macos")))]
+	_host: Option<HeadlessHost>,
+}
+
+impl ComputeRunner {
+	/// Creates a headless context to run on. Fails on machines without a GPU.
+	/// Only available where [`HeadlessHost`] is - elsewhere use [`ComputeRunner::with_core`].
+	#[cfg(all(unix, not(target_os="macos")))]
+	#[instrument(skip_all, name="gfx ComputeRunner::new")]
+	pub fn new() -> anyhow::Result<ComputeRunner> {
+		let host = HeadlessHost::new(1, 1)
+			.context("Creating headless context for ComputeRunner")?;
+
+		let core = Core::new(host.gl.clone());
+		let mut runner = ComputeRunner::with_core(core)?;
+		runner._host = Some(host);
+		Ok(runner)
+	}
+
+	/// Run on an existing context, which must stay current on this thread for as long as the runner is used.
+	pub fn with_core(mut core: Core) -> anyhow::Result<ComputeRunner> {
+		core.configure_debug_output();
+
+		let resource_manager = ResourceManager::new(&mut core)?;
+
+		Ok(ComputeRunner {
+			core,
+			resource_manager,
+
+			group: CommandGroup::new(FrameStage::Main),
+			upload_stage: UploadStage::new(),
+
+			#[cfg(all(unix, not(target_os="macos")))]
+			_host: None,
+		})
+	}
+
+	/// Record commands to be executed by the next [`ComputeRunner::submit`]. Only compute, image upload and
+	/// callback commands are supported - draws are skipped, since there is nothing to draw to.
+	/// `core` is available for creating any buffers the commands need.
+	pub fn encode<R>(&mut self, encode: impl FnOnce(&Core, &mut CommandGroupEncoder<'_>) -> R) -> R {
+		let mut encoder = CommandGroupEncoder::new(&mut self.group, &mut self.upload_stage);
+		encode(&self.core, &mut encoder)
+	}
+
+	/// Process resource requests, then execute everything recorded since the last submit.
+	/// Returns once commands have been sent to the driver - reading results back waits for them to complete.
+	#[instrument(skip_all, name="gfx ComputeRunner::submit")]
+	pub fn submit(&mut self, vfs: &vfs::Vfs) -> anyhow::Result<()> {
+		let ComputeRunner { core, resource_manager: rm, group, upload_stage, .. } = self;
+
+		rm.process_requests(core, vfs)
+			.context("Error while processing resource requests")?;
+
+		let capabilities = core.capabilities();
+
+		for command in group.commands.iter_mut() {
+			if let Some(bindings) = command.bindings_mut() {
+				bindings.merge_unspecified_from(&group.shared_bindings);
+				bindings.resolve_named_bind_targets();
+				bindings.resolve_image_bind_sources(rm);
+			}
+
+			command.resolve_staged_buffer_alignments(upload_stage, capabilities);
+		}
+
+		upload_stage.push_to_heap(core, &mut rm.upload_heap);
+
+		for command in group.commands.iter_mut() {
+			command.resolve_staged_bind_sources(&mut rm.upload_heap);
+		}
+
+		for command in group.commands.drain(..) {
+			match command {
+				Command::Compute(cmd) => cmd.execute(core, rm),
+				Command::UploadImage(cmd) => cmd.execute(core, rm),
+				Command::Callback(callback) => callback(core, rm),
+
+				Command::DebugMessage { label } => core.debug_marker(&label),
+				Command::PushDebugGroup { label } => core.push_debug_group(&label),
+				Command::PopDebugGroup => core.pop_debug_group(),
+
+				Command::Draw(_) => log::warn!("Skipping draw command submitted to ComputeRunner"),
+
+				Command::ClearBuffer | Command::ClearTexture
+					| Command::CopyBuffer | Command::CopyTexture => log::warn!("Skipping unimplemented command submitted to ComputeRunner"),
+			}
+		}
+
+		rm.upload_heap.create_end_frame_fence(core);
+		rm.upload_heap.reset(core);
+
+		group.reset();
+		upload_stage.reset();
+
+		Ok(())
+	}
+
+	/// Immutable buffer initialised with `data`, readable with [`ComputeRunner::read_buffer`].
+	pub fn create_buffer_with_data<T>(&self, data: &[T]) -> BufferName
+		where T: Copy + 'static
+	{
+		let name = self.core.create_buffer();
+		self.core.upload_immutable_buffer_immediate(name, data);
+		name
+	}
+
+	/// Synchronously read back the first `count` elements of `buffer`, waiting for any submitted work to complete.
+	pub fn read_buffer<T>(&self, buffer: BufferName, count: usize) -> Vec<T>
+		where T: bytemuck::Pod
+	{
+		let size = count * std::mem::size_of::<T>();
+		let data = self.core.read_buffer_data(buffer, BufferRange { offset: 0, size });
+
+		let mut values = vec![T::zeroed(); count];
+		bytemuck::cast_slice_mut::<T, u8>(&mut values).copy_from_slice(&data);
+		values
+	}
+}
@@ -14,18 +14,28 @@ mod image;
 pub mod shader;
 pub mod shader_pipeline;
 pub mod global_state;
+pub mod debug_policy;
+
+#[cfg(feature="debug-uniforms")]
+pub mod uniform;
 
 pub use capabilities::Capabilities;
 pub use fbo::*;
 pub use buffer::*;
-pub use sampler::{SamplerName, AddressingMode, FilterMode};
+pub use sampler::{SamplerName, SamplerDescription, AddressingMode, FilterMode};
 pub use self::image::*;
-pub use shader::{ShaderName, ShaderType};
+pub use shader::{ShaderName, ShaderType, PendingShader};
 pub use shader_pipeline::{ShaderPipelineName};
 pub use global_state::*;
+pub use debug_policy::{DebugPolicy, DebugAction, DebugMessage, DebugSource, DebugType, DebugSeverity};
+
+#[cfg(feature="debug-uniforms")]
+pub use uniform::UniformValue;
 
 use std::cell::{Cell, RefCell, RefMut};
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 
 
 pub struct Core {
@@ -35,6 +45,7 @@ pub struct Core {
 	barrier_tracker: RefCell<barrier::BarrierTracker>,
 
 	num_active_clip_planes: Cell<u32>,
+	patch_vertices: Cell<u32>,
 	bound_index_buffer: Cell<Option<BufferName>>,
 	bound_shader_pipeline: Cell<ShaderPipelineName>,
 	bound_framebuffer: Cell<Option<FramebufferName>>,
@@ -45,6 +56,7 @@ pub struct Core {
 	depth_write_enabled: Cell<bool>,
 
 	current_viewport_size: Cell<Vec2i>,
+	current_scissor_rect: Cell<Option<Aabb2i>>,
 
 	global_vao_name: u32,
 
@@ -53,6 +65,15 @@ pub struct Core {
 	framebuffer_info: RefCell<HashMap<FramebufferName, FramebufferInfo>>,
 
 	backbuffer_size: Vec2i,
+	backbuffer_color_bits: u32,
+	render_scale: f32,
+
+	reverse_z: bool,
+
+	// Boxed so the debug callback can hold a stable pointer to it.
+	debug_callback_state: Box<DebugCallbackState>,
+
+	simulated_memory_budget: Option<usize>,
 }
 
 impl Core {
@@ -87,6 +108,7 @@ impl Core {
 			barrier_tracker: RefCell::new(barrier::BarrierTracker::new()),
 
 			num_active_clip_planes: Cell::new(0),
+			patch_vertices: Cell::new(3),
 			bound_index_buffer: Cell::new(None),
 			bound_framebuffer: Cell::new(None),
 			bound_shader_pipeline: Cell::new(ShaderPipelineName(0)),
@@ -96,6 +118,7 @@ impl Core {
 			depth_write_enabled: Cell::new(true),
 
 			current_viewport_size: Cell::new(Vec2i::zero()),
+			current_scissor_rect: Cell::new(None),
 
 			global_vao_name,
 
@@ -104,6 +127,19 @@ impl Core {
 			framebuffer_info: RefCell::new(HashMap::new()),
 
 			backbuffer_size: Vec2i::zero(),
+			backbuffer_color_bits: 8,
+			render_scale: 1.0,
+
+			reverse_z: false,
+
+			debug_callback_state: Box::new(DebugCallbackState {
+				capture_safe: AtomicBool::new(true),
+				in_swap_region: AtomicBool::new(false),
+				suppressed_errors: AtomicU32::new(0),
+				policy: Mutex::new(DebugPolicy::standard()),
+			}),
+
+			simulated_memory_budget: None,
 		}
 	}
 
@@ -122,6 +158,70 @@ impl Core {
 	pub(crate) fn set_backbuffer_size(&mut self, new_size: Vec2i) {
 		self.backbuffer_size = new_size;
 	}
+
+	/// Fraction of the backbuffer size that scene rendertargets should be - see [`ImageResizePolicy::MatchRenderSize`](crate::ImageResizePolicy::MatchRenderSize).
+	pub fn render_scale(&self) -> f32 {
+		self.render_scale
+	}
+
+	/// Backbuffer size scaled by [`Core::render_scale`]. Always at least 1x1.
+	pub fn render_size(&self) -> Vec2i {
+		let size = self.backbuffer_size.to_vec2() * self.render_scale;
+		Vec2i::new((size.x.round() as i32).max(1), (size.y.round() as i32).max(1))
+	}
+
+	pub(crate) fn set_render_scale(&mut self, render_scale: f32) {
+		self.render_scale = render_scale;
+	}
+
+	/// Bits per color channel of the backbuffer. Usually 8, but may be 10 if requested from the host.
+	pub fn backbuffer_color_bits(&self) -> u32 {
+		self.backbuffer_color_bits
+	}
+
+	pub fn set_backbuffer_color_bits(&mut self, bits: u32) {
+		self.backbuffer_color_bits = bits;
+	}
+
+	/// Map near to depth 1.0 and far to 0.0, with a [0, 1] clip space depth range. Spreads floating point precision
+	/// more evenly over the view distance, which fixes z-fighting at large distances - most effective with
+	/// `ImageFormat::Depth32` targets.
+	///
+	/// Should only be changed at startup, since it affects how shaders are compiled. Projections must be adjusted to
+	/// match with [`Core::apply_depth_convention`].
+	pub fn set_reverse_z(&mut self, enabled: bool) {
+		self.reverse_z = enabled;
+
+		unsafe {
+			if enabled {
+				self.gl.ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+				self.gl.DepthFunc(gl::GREATER);
+			} else {
+				self.gl.ClipControl(gl::LOWER_LEFT, gl::NEGATIVE_ONE_TO_ONE);
+				self.gl.DepthFunc(gl::LESS);
+			}
+		}
+	}
+
+	pub fn is_reverse_z(&self) -> bool {
+		self.reverse_z
+	}
+
+	/// Depth value that depth targets are cleared to.
+	pub fn far_depth(&self) -> f32 {
+		match self.reverse_z {
+			true => 0.0,
+			false => 1.0,
+		}
+	}
+
+	/// Convert a standard OpenGL projection to whichever depth convention is active.
+	pub fn apply_depth_convention(&self, projection: Mat4) -> Mat4 {
+		match self.reverse_z {
+			true => crate::math::reverse_z_projection(&projection),
+			false => projection,
+		}
+	}
 }
 
 
@@ -176,9 +276,82 @@ impl Core {
 		}
 	}
 
+	/// Some window capture apps (discord at least) cause swap_buffers to emit GL_INVALID_ENUM, which would otherwise panic.
+	/// While capture-safe mode is enabled, GL_INVALID_ENUM errors emitted during the swap itself are logged and counted
+	/// instead. Debug output stays fully enabled everywhere else. Enabled by default.
+	pub fn set_capture_safe_mode(&self, enabled: bool) {
+		self.debug_callback_state.capture_safe.store(enabled, Ordering::Relaxed);
+	}
+
+	pub fn capture_safe_mode(&self) -> bool {
+		self.debug_callback_state.capture_safe.load(Ordering::Relaxed)
+	}
+
+	/// Number of errors suppressed by capture-safe mode so far.
+	pub fn num_suppressed_swap_errors(&self) -> u32 {
+		self.debug_callback_state.suppressed_errors.load(Ordering::Relaxed)
+	}
+
+	/// Marks the span of a buffer swap. Called by the host around `swap_buffers`, see [`Core::set_capture_safe_mode`].
+	pub fn set_in_swap_region(&self, in_swap_region: bool) {
+		self.debug_callback_state.in_swap_region.store(in_swap_region, Ordering::Relaxed);
+	}
+
+	/// Replace the policy deciding what happens to GL debug messages, returning the previous one.
+	/// [`DebugPolicy::standard`] is used by default. Capture-safe mode is applied before any policy.
+	pub fn set_debug_policy(&self, policy: DebugPolicy) -> DebugPolicy {
+		let mut current = self.debug_callback_state.policy.lock().unwrap();
+		log::info!("GL debug policy: {policy:?}");
+		std::mem::replace(&mut *current, policy)
+	}
+
+	/// Modify the current debug policy in place - e.g., to add or remove a single handler.
+	pub fn modify_debug_policy(&self, modify: impl FnOnce(DebugPolicy) -> DebugPolicy) {
+		let mut current = self.debug_callback_state.policy.lock().unwrap();
+		let policy = std::mem::replace(&mut *current, DebugPolicy::new(DebugAction::Ignore));
+		*current = modify(policy);
+	}
+
+	/// Names of the handlers in the current debug policy, in the order they run.
+	pub fn debug_policy_handlers(&self) -> Vec<String> {
+		self.debug_callback_state.policy.lock().unwrap()
+			.handler_names()
+			.map(String::from)
+			.collect()
+	}
+
+	/// Whether GL errors are reported at all. False for KHR_no_error contexts, where misuse is undefined behaviour
+	/// instead - see [`host::GlContextMode::NoError`].
+	pub fn reports_errors(&self) -> bool {
+		!self.capabilities.no_error_context
+	}
+
+	/// Whether debug output can be relied on to report every error. Only true for debug contexts.
+	pub fn has_full_debug_output(&self) -> bool {
+		self.capabilities.debug_context
+	}
+
+	/// Registers the debug callback, and makes debug output synchronous for debug contexts so errors panic at the
+	/// offending call. Release contexts get the callback without synchronous output, and no-error contexts get neither.
+	pub fn configure_debug_output(&self) {
+		if !self.reports_errors() {
+			log::warn!("Running with a no-error context - GL errors won't be reported");
+			return
+		}
+
+		// Debug contexts start with debug output enabled, but release contexts don't, so the callback would never fire.
+		unsafe {
+			self.gl.Enable(gl::DEBUG_OUTPUT);
+		}
+
+		self.register_debug_hook();
+		self.set_debugging_enabled(self.has_full_debug_output());
+	}
+
 	pub fn register_debug_hook(&self) {
 		unsafe {
-			self.gl.DebugMessageCallback(Some(default_gl_error_handler), std::ptr::null());
+			let user_param = &*self.debug_callback_state as *const DebugCallbackState;
+			self.gl.DebugMessageCallback(Some(default_gl_error_handler), user_param.cast());
 
 			// Disable performance messages
 			self.gl.DebugMessageControl(
@@ -228,6 +401,53 @@ impl Core {
 }
 
 
+/// Memory
+impl Core {
+	/// Rough total size of all buffers and images created through Core. Ignores driver overhead, padding and
+	/// framebuffer attachments created outside of Core.
+	pub fn estimated_memory_usage(&self) -> usize {
+		let buffer_usage: usize = self.buffer_info.borrow().values()
+			.map(|info| info.size)
+			.sum();
+
+		let image_usage: usize = self.image_info.borrow().values()
+			.map(|info| info.estimated_byte_size())
+			.sum();
+
+		buffer_usage + image_usage
+	}
+
+	/// Pretend the device only has `budget` bytes of memory, for testing how apps cope with running out.
+	/// Only enforced by [`Core::check_memory_budget`] - allocations themselves never fail.
+	pub fn set_simulated_memory_budget(&mut self, budget: Option<usize>) {
+		if budget != self.simulated_memory_budget {
+			log::info!("Simulated memory budget: {budget:?}");
+		}
+
+		self.simulated_memory_budget = budget;
+	}
+
+	pub fn simulated_memory_budget(&self) -> Option<usize> {
+		self.simulated_memory_budget
+	}
+
+	/// Fails if allocating `additional_bytes` would exceed the simulated memory budget.
+	pub fn check_memory_budget(&self, additional_bytes: usize) -> anyhow::Result<()> {
+		let Some(budget) = self.simulated_memory_budget else {
+			return Ok(())
+		};
+
+		let usage = self.estimated_memory_usage();
+		if usage + additional_bytes > budget {
+			anyhow::bail!("Simulated out of memory: allocating {}KiB with {}KiB of {}KiB budget in use",
+				additional_bytes >> 10, usage >> 10, budget >> 10);
+		}
+
+		Ok(())
+	}
+}
+
+
 /// Features
 impl Core {
 	pub fn set_user_clip_planes(&self, new_count: u32) {
@@ -253,12 +473,32 @@ impl Core {
 
 		self.num_active_clip_planes.set(new_count);
 	}
+
+	/// Number of vertices making up each patch, for draws using [`PrimitiveType::Patches`](crate::PrimitiveType::Patches).
+	pub fn set_patch_vertices(&self, patch_vertices: u32) {
+		assert!(patch_vertices > 0 && patch_vertices <= self.capabilities.max_patch_vertices as u32, "GL_MAX_PATCH_VERTICES exceeded");
+
+		if self.patch_vertices.get() != patch_vertices {
+			unsafe {
+				self.gl.PatchParameteri(gl::PATCH_VERTICES, patch_vertices as i32);
+			}
+
+			self.patch_vertices.set(patch_vertices);
+		}
+	}
 }
 
 
 impl Drop for Core {
 	fn drop(&mut self) {
 		self.destroy_global_vao();
+
+		// The callback points at debug_callback_state, which is about to be freed.
+		if self.reports_errors() {
+			unsafe {
+				self.gl.DebugMessageCallback(None, std::ptr::null());
+			}
+		}
 	}
 }
 
@@ -284,52 +524,59 @@ impl<T> ResourceName for Option<T>
 
 
 
+struct DebugCallbackState {
+	capture_safe: AtomicBool,
+	in_swap_region: AtomicBool,
+	suppressed_errors: AtomicU32,
+	policy: Mutex<DebugPolicy>,
+}
+
+impl DebugCallbackState {
+	/// Only the known spurious GL_INVALID_ENUM - drivers disagree on whether it's reported by id or just in the message.
+	fn should_suppress(&self, source: u32, ty: u32, msg_id: u32, message: &str) -> bool {
+		self.capture_safe.load(Ordering::Relaxed)
+			&& self.in_swap_region.load(Ordering::Relaxed)
+			&& ty == gl::DEBUG_TYPE_ERROR
+			&& matches!(source, gl::DEBUG_SOURCE_API | gl::DEBUG_SOURCE_WINDOW_SYSTEM)
+			&& (msg_id == gl::INVALID_ENUM || message.contains("GL_INVALID_ENUM"))
+	}
+}
+
 extern "system" fn default_gl_error_handler(source: u32, ty: u32, msg_id: u32, severity: u32,
-	length: i32, msg: *const i8, _ud: *mut std::ffi::c_void)
+	length: i32, msg: *const i8, user_param: *mut std::ffi::c_void)
 {
-	let severity_str = match severity {
-		gl::DEBUG_SEVERITY_HIGH => "high",
-		gl::DEBUG_SEVERITY_MEDIUM => "medium",
-		gl::DEBUG_SEVERITY_LOW => "low",
-		gl::DEBUG_SEVERITY_NOTIFICATION => return,
-		_ => panic!("Unknown severity {}", severity),
-	};
+	// SAFETY: user_param is always the boxed DebugCallbackState owned by Core, which outlives the context.
+	let Some(state) = (unsafe { user_param.cast::<DebugCallbackState>().as_ref() }) else { return };
 
-	let ty_str = match ty {
-		gl::DEBUG_TYPE_ERROR => "error",
-		gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behaviour",
-		gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behaviour",
-		gl::DEBUG_TYPE_PORTABILITY => "portability",
-		gl::DEBUG_TYPE_PERFORMANCE => "performance",
-		gl::DEBUG_TYPE_OTHER => "other",
-		_ => panic!("Unknown type {}", ty),
+	let message = unsafe {
+		let msg_slice = std::slice::from_raw_parts(msg.cast(), length as usize);
+		String::from_utf8_lossy(msg_slice)
 	};
 
-	let source = match source {
-		gl::DEBUG_SOURCE_API => "api",
-		gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
-		gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
-		gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
-		gl::DEBUG_SOURCE_APPLICATION => "application",
-		gl::DEBUG_SOURCE_OTHER => "other",
-		_ => panic!("Unknown source {}", source),
-	};
+	if state.should_suppress(source, ty, msg_id, &message) {
+		let count = state.suppressed_errors.fetch_add(1, Ordering::Relaxed) + 1;
 
-	eprintln!("GL ERROR!");
-	eprintln!("Source:   {source}");
-	eprintln!("Severity: {severity_str}");
-	eprintln!("Type:     {ty_str}");
-	eprintln!("Id:       {msg_id}");
+		// Don't spam every frame if capture is ongoing.
+		if count.is_power_of_two() {
+			log::warn!("Suppressed GL error during swap ({count} total, capture-safe mode): {message}");
+		}
 
-	unsafe {
-		let msg_slice = std::slice::from_raw_parts(msg.cast(), length as usize);
-		let msg_utf8 = String::from_utf8_lossy(msg_slice);
-		eprintln!("Message: {}", msg_utf8);
+		return
 	}
 
-	match (severity, ty) {
-		(_, gl::DEBUG_TYPE_PORTABILITY | gl::DEBUG_TYPE_PERFORMANCE | gl::DEBUG_TYPE_OTHER) => {}
-		(gl::DEBUG_SEVERITY_HIGH | gl::DEBUG_SEVERITY_MEDIUM, _) => panic!("GL ERROR!"),
-		_ => {}
-	}
-}
\ No newline at end of file
+	let message = DebugMessage {
+		source: DebugSource::from_raw(source),
+		ty: DebugType::from_raw(ty),
+		severity: DebugSeverity::from_raw(severity),
+		id: msg_id,
+		message: &message,
+	};
+
+	// NOTE: the lock is released before acting so that panicking doesn't poison it.
+	let action = match state.policy.lock() {
+		Ok(policy) => policy.resolve(&message),
+		Err(_) => DebugAction::Log(log::Level::Error),
+	};
+
+	debug_policy::execute_action(action, &message);
+}
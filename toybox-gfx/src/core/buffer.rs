@@ -144,6 +144,24 @@ impl super::Core {
 		}
 	}
 
+	/// Synchronously read back part of a buffer. Waits for any prior shader writes to complete, so this will stall.
+	/// Mostly useful for debugging.
+	pub fn read_buffer_data(&self, name: BufferName, range: BufferRange) -> Vec<u8> {
+		let buffer_size = self.get_buffer_info(name).map_or(0, |info| info.size);
+		assert!(range.offset + range.size <= buffer_size, "Trying to read buffer with out of bounds range");
+
+		let mut data = vec![0u8; range.size];
+
+		if range.size > 0 {
+			unsafe {
+				self.gl.MemoryBarrier(gl::BUFFER_UPDATE_BARRIER_BIT);
+				self.gl.GetNamedBufferSubData(name.as_raw(), range.offset as isize, range.size as isize, data.as_mut_ptr().cast());
+			}
+		}
+
+		data
+	}
+
 	/// SAFETY: Will invalidate the pointer returned from an earlier call to map_buffer.
 	/// Using that pointer after the mapped buffer is unmapped is undefined behaviour.
 	pub unsafe fn unmap_buffer(&self, name: BufferName) {
@@ -20,6 +20,30 @@ pub struct Capabilities {
 	pub max_ubo_size: usize,
 
 	pub parallel_shader_compilation_supported: bool,
+
+	/// Whether shaders can be created from SPIR-V modules.
+	pub spirv_supported: bool,
+
+	/// Guaranteed to be at least 16
+	pub max_viewports: usize,
+
+	/// Whether vertex shaders can write `gl_ViewportIndex` and `gl_Layer` (GL_ARB_shader_viewport_layer_array).
+	pub vertex_viewport_layer_supported: bool,
+
+	/// Whether images can be created without backing memory and committed page by page (GL_ARB_sparse_texture).
+	pub sparse_texture_supported: bool,
+
+	/// Guaranteed to be at least 2
+	pub max_anisotropy: f32,
+
+	/// Guaranteed to be at least 32
+	pub max_patch_vertices: usize,
+
+	/// Whether the context was created as a debug context, so debug output reports everything.
+	pub debug_context: bool,
+
+	/// Whether the context was created with KHR_no_error, so errors aren't reported at all.
+	pub no_error_context: bool,
 }
 
 impl Capabilities {
@@ -29,6 +53,10 @@ impl Capabilities {
 		let mut max_user_clip_planes = 0;
 		let mut max_texture_size = 0;
 		let mut max_ubo_size = 0;
+		let mut max_viewports = 0;
+		let mut max_anisotropy = 0.0;
+		let mut max_patch_vertices = 0;
+		let mut context_flags = 0;
 
 		let min_max_samples;
 		let max_image_units;
@@ -72,8 +100,14 @@ impl Capabilities {
 
 			gl.GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut max_texture_size);
 			gl.GetIntegerv(gl::MAX_UNIFORM_BLOCK_SIZE, &mut max_ubo_size);
+			gl.GetIntegerv(gl::MAX_VIEWPORTS, &mut max_viewports);
+			gl.GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy);
+			gl.GetIntegerv(gl::MAX_PATCH_VERTICES, &mut max_patch_vertices);
+			gl.GetIntegerv(gl::CONTEXT_FLAGS, &mut context_flags);
 		}
 
+		let context_flags = context_flags as u32;
+
 		Capabilities {
 			ubo_bind_alignment: ubo_bind_alignment as usize,
 			ssbo_bind_alignment: ssbo_bind_alignment as usize,
@@ -83,6 +117,28 @@ impl Capabilities {
 			max_samples: min_max_samples as usize,
 			max_ubo_size: max_ubo_size as usize,
 			parallel_shader_compilation_supported: gl.MaxShaderCompilerThreadsARB.is_loaded(),
+			spirv_supported: gl.SpecializeShader.is_loaded(),
+			max_viewports: max_viewports as usize,
+			vertex_viewport_layer_supported: has_extension(gl, "GL_ARB_shader_viewport_layer_array"),
+			sparse_texture_supported: gl.TexPageCommitmentARB.is_loaded() && has_extension(gl, "GL_ARB_sparse_texture"),
+			max_anisotropy,
+			max_patch_vertices: max_patch_vertices as usize,
+			debug_context: context_flags & gl::CONTEXT_FLAG_DEBUG_BIT != 0,
+			no_error_context: context_flags & gl::CONTEXT_FLAG_NO_ERROR_BIT != 0,
 		}
 	}
+}
+
+
+fn has_extension(gl: &gl::Gl, name: &str) -> bool {
+	let mut num_extensions = 0;
+
+	unsafe {
+		gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
+
+		(0..num_extensions as u32).any(|index| {
+			let extension = std::ffi::CStr::from_ptr(gl.GetStringi(gl::EXTENSIONS, index).cast());
+			extension.to_bytes() == name.as_bytes()
+		})
+	}
 }
\ No newline at end of file
@@ -0,0 +1,278 @@
+//! Filtering and handling of messages from the GL debug callback. See [`super::Core::set_debug_policy`].
+//!
+//! ```ignore
+//! let policy = DebugPolicy::standard()
+//! 	.ignore_ids("nvidia buffer info spam", [131185, 131204])
+//! 	.handler("crash reporter", |message| {
+//! 		if message.is_error() {
+//! 			crash_reporter.record(message.to_string());
+//! 		}
+//!
+//! 		// Fall through to the next handler.
+//! 		None
+//! 	});
+//!
+//! gfx.core.set_debug_policy(policy);
+//! ```
+
+use std::fmt;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DebugSource {
+	Api,
+	WindowSystem,
+	ShaderCompiler,
+	ThirdParty,
+	Application,
+	Other,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DebugType {
+	Error,
+	DeprecatedBehavior,
+	UndefinedBehavior,
+	Portability,
+	Performance,
+	Marker,
+	Other,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DebugSeverity {
+	Notification,
+	Low,
+	Medium,
+	High,
+}
+
+impl DebugSource {
+	pub fn from_raw(source: u32) -> Self {
+		match source {
+			gl::DEBUG_SOURCE_API => DebugSource::Api,
+			gl::DEBUG_SOURCE_WINDOW_SYSTEM => DebugSource::WindowSystem,
+			gl::DEBUG_SOURCE_SHADER_COMPILER => DebugSource::ShaderCompiler,
+			gl::DEBUG_SOURCE_THIRD_PARTY => DebugSource::ThirdParty,
+			gl::DEBUG_SOURCE_APPLICATION => DebugSource::Application,
+			_ => DebugSource::Other,
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			DebugSource::Api => "api",
+			DebugSource::WindowSystem => "window system",
+			DebugSource::ShaderCompiler => "shader compiler",
+			DebugSource::ThirdParty => "third party",
+			DebugSource::Application => "application",
+			DebugSource::Other => "other",
+		}
+	}
+}
+
+impl DebugType {
+	pub fn from_raw(ty: u32) -> Self {
+		match ty {
+			gl::DEBUG_TYPE_ERROR => DebugType::Error,
+			gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => DebugType::DeprecatedBehavior,
+			gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => DebugType::UndefinedBehavior,
+			gl::DEBUG_TYPE_PORTABILITY => DebugType::Portability,
+			gl::DEBUG_TYPE_PERFORMANCE => DebugType::Performance,
+			gl::DEBUG_TYPE_MARKER => DebugType::Marker,
+			_ => DebugType::Other,
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			DebugType::Error => "error",
+			DebugType::DeprecatedBehavior => "deprecated behaviour",
+			DebugType::UndefinedBehavior => "undefined behaviour",
+			DebugType::Portability => "portability",
+			DebugType::Performance => "performance",
+			DebugType::Marker => "marker",
+			DebugType::Other => "other",
+		}
+	}
+}
+
+impl DebugSeverity {
+	pub fn from_raw(severity: u32) -> Self {
+		match severity {
+			gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+			gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+			gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+			_ => DebugSeverity::Notification,
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			DebugSeverity::High => "high",
+			DebugSeverity::Medium => "medium",
+			DebugSeverity::Low => "low",
+			DebugSeverity::Notification => "notification",
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct DebugMessage<'a> {
+	pub source: DebugSource,
+	pub ty: DebugType,
+	pub severity: DebugSeverity,
+	pub id: u32,
+	pub message: &'a str,
+}
+
+impl DebugMessage<'_> {
+	/// Errors, undefined behaviour and deprecated behaviour of medium severity or above - what [`DebugPolicy::standard`] panics on.
+	pub fn is_error(&self) -> bool {
+		matches!(self.ty, DebugType::Error | DebugType::UndefinedBehavior | DebugType::DeprecatedBehavior)
+			&& self.severity >= DebugSeverity::Medium
+	}
+}
+
+impl fmt::Display for DebugMessage<'_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "GL {} {} ({} severity, id {}): {}",
+			self.source.label(), self.ty.label(), self.severity.label(), self.id, self.message)
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugAction {
+	/// Drop the message.
+	Ignore,
+
+	Log(log::Level),
+
+	/// Print the message and panic.
+	Panic,
+}
+
+
+type DebugHandlerFn = dyn Fn(&DebugMessage<'_>) -> Option<DebugAction> + Send + Sync;
+
+/// A stack of named handlers, tried in the order they were added. The first handler to return an action decides
+/// what happens to a message - handlers can return None to observe messages without consuming them.
+/// Messages no handler consumes get the fallback action.
+pub struct DebugPolicy {
+	handlers: Vec<(String, Box<DebugHandlerFn>)>,
+	fallback: DebugAction,
+}
+
+impl Default for DebugPolicy {
+	fn default() -> Self {
+		DebugPolicy::standard()
+	}
+}
+
+impl DebugPolicy {
+	/// No handlers, with every message getting `fallback`.
+	pub fn new(fallback: DebugAction) -> Self {
+		DebugPolicy {
+			handlers: Vec::new(),
+			fallback,
+		}
+	}
+
+	/// Panics on errors, logs everything else except notifications. Used unless another policy is set.
+	pub fn standard() -> Self {
+		DebugPolicy::new(DebugAction::Log(log::Level::Warn))
+			.handler("ignore notifications", |message| {
+				(message.severity == DebugSeverity::Notification).then_some(DebugAction::Ignore)
+			})
+			.handler("panic on errors", |message| {
+				message.is_error().then_some(DebugAction::Panic)
+			})
+	}
+
+	/// Like [`DebugPolicy::standard`], but errors are logged instead of panicking - e.g., for release builds.
+	pub fn log_only() -> Self {
+		DebugPolicy::new(DebugAction::Log(log::Level::Warn))
+			.handler("ignore notifications", |message| {
+				(message.severity == DebugSeverity::Notification).then_some(DebugAction::Ignore)
+			})
+			.handler("log errors", |message| {
+				message.is_error().then_some(DebugAction::Log(log::Level::Error))
+			})
+	}
+
+	/// Add a handler to the end of the stack.
+	pub fn handler<F>(mut self, name: impl Into<String>, handler: F) -> Self
+		where F: Fn(&DebugMessage<'_>) -> Option<DebugAction> + Send + Sync + 'static
+	{
+		self.handlers.push((name.into(), Box::new(handler)));
+		self
+	}
+
+	/// Add a handler to the start of the stack, so it runs before anything already added.
+	pub fn prepend_handler<F>(mut self, name: impl Into<String>, handler: F) -> Self
+		where F: Fn(&DebugMessage<'_>) -> Option<DebugAction> + Send + Sync + 'static
+	{
+		self.handlers.insert(0, (name.into(), Box::new(handler)));
+		self
+	}
+
+	/// Drop messages with any of `ids`, ahead of all other handlers. For known driver spam.
+	pub fn ignore_ids(self, name: impl Into<String>, ids: impl IntoIterator<Item=u32>) -> Self {
+		let ids: Vec<u32> = ids.into_iter().collect();
+		self.prepend_handler(name, move |message| {
+			ids.contains(&message.id).then_some(DebugAction::Ignore)
+		})
+	}
+
+	pub fn fallback(self, fallback: DebugAction) -> Self {
+		Self { fallback, .. self }
+	}
+
+	/// Remove all handlers called `name`. Returns whether any were removed.
+	pub fn remove_handler(&mut self, name: &str) -> bool {
+		let prev_len = self.handlers.len();
+		self.handlers.retain(|(handler_name, _)| handler_name != name);
+		self.handlers.len() != prev_len
+	}
+
+	/// Names of handlers in the order they run.
+	pub fn handler_names(&self) -> impl Iterator<Item=&str> + '_ {
+		self.handlers.iter().map(|(name, _)| name.as_str())
+	}
+
+	pub fn resolve(&self, message: &DebugMessage<'_>) -> DebugAction {
+		self.handlers.iter()
+			.find_map(|(_, handler)| handler(message))
+			.unwrap_or(self.fallback)
+	}
+}
+
+impl fmt::Debug for DebugPolicy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("DebugPolicy")
+			.field("handlers", &self.handler_names().collect::<Vec<_>>())
+			.field("fallback", &self.fallback)
+			.finish()
+	}
+}
+
+
+pub(super) fn execute_action(action: DebugAction, message: &DebugMessage<'_>) {
+	match action {
+		DebugAction::Ignore => {}
+		DebugAction::Log(level) => log::log!(level, "{message}"),
+
+		DebugAction::Panic => {
+			eprintln!("GL ERROR!");
+			eprintln!("Source:   {}", message.source.label());
+			eprintln!("Severity: {}", message.severity.label());
+			eprintln!("Type:     {}", message.ty.label());
+			eprintln!("Id:       {}", message.id);
+			eprintln!("Message: {}", message.message);
+
+			panic!("GL ERROR!");
+		}
+	}
+}
@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use crate::core::ImageName;
+use crate::core::{ImageName, FilterMode};
 
 use std::collections::HashMap;
 use std::cell::Ref;
@@ -26,6 +26,38 @@ pub enum FramebufferAttachment {
 	DepthStencil,
 }
 
+impl FramebufferAttachment {
+	pub fn to_raw(&self) -> u32 {
+		match self {
+			FramebufferAttachment::Color(index) => gl::COLOR_ATTACHMENT0 + index,
+			FramebufferAttachment::Depth => gl::DEPTH_ATTACHMENT,
+			FramebufferAttachment::Stencil => gl::STENCIL_ATTACHMENT,
+			FramebufferAttachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
+		}
+	}
+}
+
+
+/// Value to clear a single framebuffer attachment to. See [`Core::clear_framebuffer_attachment`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ClearValue {
+	Color(common::Color),
+	Depth(f32),
+	Stencil(u8),
+	DepthStencil(f32, u8),
+
+	/// Depth cleared to [`Core::far_depth`] - and stencil to 0 for depth-stencil attachments.
+	FarDepth,
+}
+
+impl<T> From<T> for ClearValue
+	where T: Into<common::Color>
+{
+	fn from(color: T) -> Self {
+		ClearValue::Color(color.into())
+	}
+}
+
 
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct FramebufferInfo {
@@ -48,6 +80,121 @@ impl super::Core {
 		}
 	}
 
+	/// Clear one attachment of `fbo`, or of the backbuffer if None. Ignores the current scissor rect and depth mask.
+	/// Color attachments are assumed to be bound to the draw buffer of the same index.
+	pub fn clear_framebuffer_attachment(&self, fbo: impl Into<Option<FramebufferName>>, attachment: FramebufferAttachment, value: ClearValue) {
+		let fbo = fbo.into().as_raw();
+
+		let previous_scissor_rect = self.current_scissor_rect.get();
+		let previous_depth_write = self.depth_write_enabled.get();
+
+		self.set_scissor_rect(None);
+
+		let (depth, stencil) = match value {
+			ClearValue::Depth(depth) => (depth, 0),
+			ClearValue::Stencil(stencil) => (self.far_depth(), stencil),
+			ClearValue::DepthStencil(depth, stencil) => (depth, stencil),
+			ClearValue::FarDepth | ClearValue::Color(_) => (self.far_depth(), 0),
+		};
+
+		unsafe {
+			match (attachment, value) {
+				(FramebufferAttachment::Color(index), ClearValue::Color(color)) => {
+					self.gl.ClearNamedFramebufferfv(fbo, gl::COLOR, index as i32, color.to_array().as_ptr());
+				}
+
+				(FramebufferAttachment::Color(_), _) => panic!("Color attachments must be cleared with ClearValue::Color"),
+				(_, ClearValue::Color(_)) => panic!("{attachment:?} attachments can't be cleared to a color"),
+
+				(FramebufferAttachment::Depth, _) => {
+					self.set_depth_write(true);
+					self.gl.ClearNamedFramebufferfv(fbo, gl::DEPTH, 0, &depth);
+				}
+
+				(FramebufferAttachment::Stencil, _) => {
+					self.gl.ClearNamedFramebufferiv(fbo, gl::STENCIL, 0, &(stencil as i32));
+				}
+
+				(FramebufferAttachment::DepthStencil, _) => {
+					self.set_depth_write(true);
+					self.gl.ClearNamedFramebufferfi(fbo, gl::DEPTH_STENCIL, 0, depth, stencil as i32);
+				}
+			}
+		}
+
+		self.set_depth_write(previous_depth_write);
+		self.set_scissor_rect(previous_scissor_rect);
+	}
+
+	/// Tell the driver the contents of `attachments` are no longer needed, so it can skip loading or storing them.
+	/// None for `fbo` means the backbuffer.
+	pub fn invalidate_framebuffer_attachments(&self, fbo: impl Into<Option<FramebufferName>>, attachments: &[FramebufferAttachment]) {
+		let fbo = fbo.into();
+
+		// The default framebuffer has its own enums for its attachments.
+		let raw_attachments: SmallVec<[u32; 4]> = match fbo {
+			Some(_) => attachments.iter().map(FramebufferAttachment::to_raw).collect(),
+			None => attachments.iter()
+				.flat_map(|attachment| match attachment {
+					FramebufferAttachment::Color(_) => &[gl::COLOR][..],
+					FramebufferAttachment::Depth => &[gl::DEPTH][..],
+					FramebufferAttachment::Stencil => &[gl::STENCIL][..],
+					FramebufferAttachment::DepthStencil => &[gl::DEPTH, gl::STENCIL][..],
+				})
+				.copied()
+				.collect(),
+		};
+
+		if raw_attachments.is_empty() {
+			return
+		}
+
+		unsafe {
+			self.gl.InvalidateNamedFramebufferData(fbo.as_raw(), raw_attachments.len() as i32, raw_attachments.as_ptr());
+		}
+	}
+
+	/// Synchronously reads back the first color attachment of `fbo` as tightly packed, bottom-up rgba8 texels.
+	/// If `fbo` is None, the backbuffer is read instead.
+	/// This will stall until all commands writing to the framebuffer have completed, so is best kept to tools and debugging.
+	pub fn read_framebuffer_rgba8(&self, fbo: impl Into<Option<FramebufferName>>, size: Vec2i) -> Vec<u8> {
+		let fbo = fbo.into();
+		let mut data = vec![0u8; 4 * (size.x * size.y) as usize];
+
+		// TODO(pat.m): Make this conditional and actually track state properly
+		self.bind_image_download_buffer(None);
+
+		unsafe {
+			self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, fbo.as_raw());
+			self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+			self.gl.ReadnPixels(0, 0, size.x, size.y, gl::RGBA, gl::UNSIGNED_BYTE,
+				data.len() as i32, data.as_mut_ptr().cast());
+
+			// Restore read framebuffer so that it matches the tracked binding.
+			self.gl.BindFramebuffer(gl::READ_FRAMEBUFFER, self.bound_framebuffer.get().as_raw());
+		}
+
+		data
+	}
+
+	/// Copies the color contents of `src_rect` in `src` to `dst_rect` in `dst`, scaling if required.
+	/// None for either framebuffer means the backbuffer.
+	pub fn blit_framebuffer_color(&self, src: impl Into<Option<FramebufferName>>, src_rect: Aabb2i,
+		dst: impl Into<Option<FramebufferName>>, dst_rect: Aabb2i, filter: FilterMode)
+	{
+		let filter = match filter {
+			FilterMode::Nearest => gl::NEAREST,
+			FilterMode::Linear => gl::LINEAR,
+		};
+
+		unsafe {
+			self.gl.BlitNamedFramebuffer(src.into().as_raw(), dst.into().as_raw(),
+				src_rect.min.x, src_rect.min.y, src_rect.max.x, src_rect.max.y,
+				dst_rect.min.x, dst_rect.min.y, dst_rect.max.x, dst_rect.max.y,
+				gl::COLOR_BUFFER_BIT, filter);
+		}
+	}
+
 	pub fn create_framebuffer(&self) -> FramebufferName {
 		let name = FramebufferName(unsafe {
 			let mut name = 0;
@@ -100,17 +247,27 @@ impl super::Core {
 			.attachments
 			.insert(attachment, image);
 
-		let attachment = match attachment {
-			FramebufferAttachment::Color(index) => gl::COLOR_ATTACHMENT0 + index,
-			FramebufferAttachment::Depth => gl::DEPTH_ATTACHMENT,
-			FramebufferAttachment::Stencil => gl::STENCIL_ATTACHMENT,
-			FramebufferAttachment::DepthStencil => gl::DEPTH_STENCIL_ATTACHMENT,
-		};
-
+		let attachment = attachment.to_raw();
 		let level = 0;
 
 		unsafe {
 			self.gl.NamedFramebufferTexture(framebuffer.as_raw(), attachment, image.as_raw(), level);
 		}
 	}
+
+	/// Attach a single layer of an array or 3D image.
+	pub fn set_framebuffer_attachment_layer(&self, framebuffer: FramebufferName, attachment: FramebufferAttachment, image: ImageName, layer: u32) {
+		self.framebuffer_info.borrow_mut()
+			.get_mut(&framebuffer)
+			.expect("Invalid FramebufferName")
+			.attachments
+			.insert(attachment, image);
+
+		let attachment = attachment.to_raw();
+		let level = 0;
+
+		unsafe {
+			self.gl.NamedFramebufferTextureLayer(framebuffer.as_raw(), attachment, image.as_raw(), level, layer as i32);
+		}
+	}
 }
\ No newline at end of file
@@ -12,6 +12,42 @@ impl super::Core {
 		}
 	}
 
+	/// Set viewports `first..first + viewports.len()`, for shaders that write `gl_ViewportIndex`.
+	/// Viewport 0 is managed by [`Core::set_viewport`] and overwritten on every draw, so `first` should usually be 1.
+	pub fn set_viewport_array(&self, first: u32, viewports: &[Aabb2i]) {
+		let values: Vec<f32> = viewports.iter()
+			.flat_map(|rect| {
+				let size = rect.size();
+				[rect.min.x as f32, rect.min.y as f32, size.x as f32, size.y as f32]
+			})
+			.collect();
+
+		unsafe {
+			self.gl.ViewportArrayv(first, viewports.len() as i32, values.as_ptr());
+		}
+	}
+
+	/// Restrict rendering to `rect` in framebuffer space, or disable scissoring if None.
+	pub fn set_scissor_rect(&self, rect: impl Into<Option<Aabb2i>>) {
+		let rect = rect.into();
+
+		if self.current_scissor_rect.get() == rect {
+			return
+		}
+
+		self.set_feature(gl::SCISSOR_TEST, rect.is_some());
+
+		if let Some(rect) = rect {
+			let size = rect.size();
+
+			unsafe {
+				self.gl.Scissor(rect.min.x, rect.min.y, size.x, size.y);
+			}
+		}
+
+		self.current_scissor_rect.set(rect);
+	}
+
 	pub fn set_blend_mode(&self, state: impl Into<Option<BlendMode>>) {
 		let state = state.into();
 
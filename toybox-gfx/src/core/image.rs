@@ -28,6 +28,9 @@ pub enum ImageType {
 	Image2D = gl::TEXTURE_2D,
 	Image3D = gl::TEXTURE_3D,
 	Image2DArray = gl::TEXTURE_2D_ARRAY,
+
+	/// Size z is always 6, and faces are addressed as layers.
+	Cube = gl::TEXTURE_CUBE_MAP,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -45,6 +48,31 @@ pub struct ImageInfo {
 	pub samples: u32,
 }
 
+impl ImageInfo {
+	/// Approximate size of all levels, ignoring any padding or compression the driver might apply.
+	pub fn estimated_byte_size(&self) -> usize {
+		(0..self.levels)
+			.map(|level| self.format.data_byte_size(self.level_size(level)))
+			.sum::<usize>() * self.samples.max(1) as usize
+	}
+
+	pub fn level_size(&self, level: u32) -> Vec3i {
+		// Only 3D images have mipmapped depth - array layers and cube faces don't shrink.
+		let depth = match self.image_type {
+			ImageType::Image3D => (self.size.z >> level).max(1),
+			_ => self.size.z,
+		};
+
+		Vec3i::new((self.size.x >> level).max(1), (self.size.y >> level).max(1), depth)
+	}
+}
+
+impl ImageInfoInternal {
+	pub(in crate::core) fn estimated_byte_size(&self) -> usize {
+		self.info.estimated_byte_size()
+	}
+}
+
 
 /// Images
 impl super::Core {
@@ -62,7 +90,7 @@ impl super::Core {
 			self.gl.CreateTextures(image_info.image_type as u32, 1, &mut name);
 
 			match image_info.image_type {
-				ImageType::Image2D => {
+				ImageType::Image2D | ImageType::Cube => {
 					self.gl.TextureStorage2D(name, levels, format.to_raw(), size.x, size.y)
 				}
 
@@ -96,6 +124,10 @@ impl super::Core {
 		self.create_typed_image(ImageType::Image2DArray, format, size.extend(layers as i32))
 	}
 
+	pub fn create_image_cube(&self, format: ImageFormat, size: i32) -> ImageName {
+		self.create_typed_image(ImageType::Cube, format, Vec3i::new(size, size, 6))
+	}
+
 	pub fn get_image_info(&self, name: ImageName) -> Option<ImageInfo> {
 		self.image_info.borrow().get(&name).map(|info_internal| info_internal.info.clone())
 	}
@@ -217,22 +249,62 @@ impl super::Core {
 
 	pub unsafe fn upload_image_raw(&self, name: ImageName, range: impl Into<Option<ImageRange>>,
 		format: ImageFormat, data_ptr: *const u8, data_size: usize)
+	{
+		unsafe {
+			self.upload_image_level_raw(name, 0, range, format, data_ptr, data_size);
+		}
+	}
+
+	/// As [`Core::upload_image_raw`], but for a specific mip level. `range` defaults to the whole level.
+	pub unsafe fn upload_image_level_raw(&self, name: ImageName, level: u32, range: impl Into<Option<ImageRange>>,
+		format: ImageFormat, data_ptr: *const u8, data_size: usize)
 	{
 		let Some(image_info) = self.get_image_info(name)
 			else { panic!("Trying to upload data for invalid ImageName") };
 
-		let ImageRange {offset, size} = range.into().unwrap_or(ImageRange::from_size(image_info.size));
+		assert!(level < image_info.levels, "Core::upload_image_level_raw passed level outside of image");
+
+		let level_size = image_info.level_size(level);
+		let ImageRange {offset, size} = range.into().unwrap_or(ImageRange::from_size(level_size));
 
-		let expected_size = format.texel_byte_size() * (size.x * size.y * size.z) as usize;
+		let expected_size = format.data_byte_size(size);
 		assert_eq!(data_size, expected_size, "Core::upload_image_raw not passed expected amount of data");
 
-		// TODO(pat.m): assert that size + offset < image_info.size
+		let image_bounds = crate::Aabb3i::from_size(level_size);
+		assert!(image_bounds.contains(&ImageRange{offset, size}.to_aabb()), "Core::upload_image_raw passed range outside of image bounds");
 
 		unsafe {
 			self.gl.PixelStorei(gl::UNPACK_ALIGNMENT, 1);
 		}
 
-		let level = 0;
+		let level = level as i32;
+
+		if format.is_compressed() {
+			assert_eq!(format, image_info.format, "Compressed image data must match the format of the image");
+
+			match image_info.image_type {
+				ImageType::Image2D => unsafe {
+					assert!(offset.z == 0);
+					self.gl.CompressedTextureSubImage2D(name.as_raw(), level,
+						offset.x, offset.y,
+						size.x, size.y,
+						format.to_raw(),
+						data_size as i32,
+						data_ptr.cast());
+				}
+
+				ImageType::Image3D | ImageType::Image2DArray | ImageType::Cube => unsafe {
+					self.gl.CompressedTextureSubImage3D(name.as_raw(), level,
+						offset.x, offset.y, offset.z,
+						size.x, size.y, size.z,
+						format.to_raw(),
+						data_size as i32,
+						data_ptr.cast());
+				}
+			}
+
+			return
+		}
 
 		match image_info.image_type {
 			ImageType::Image2D => unsafe {
@@ -245,7 +317,7 @@ impl super::Core {
 					data_ptr.cast());
 			}
 
-			ImageType::Image3D | ImageType::Image2DArray => unsafe {
+			ImageType::Image3D | ImageType::Image2DArray | ImageType::Cube => unsafe {
 				self.gl.TextureSubImage3D(name.as_raw(), level,
 					offset.x, offset.y, offset.z,
 					size.x, size.y, size.z,
@@ -297,18 +369,72 @@ impl super::Core {
 		self.bind_image_upload_buffer(None);
 	}
 
+	/// Upload to a mip level of `image_name` from `buffer_range` of a buffer - e.g., data staged in the upload heap.
+	pub fn copy_image_level_from_buffer(&self, image_name: ImageName, level: u32,
+		dest_range: impl Into<Option<ImageRange>>,
+		buffer_format: ImageFormat, buffer_name: BufferName, buffer_range: BufferRange)
+	{
+		self.bind_image_upload_buffer(buffer_name);
+
+		unsafe {
+			self.upload_image_level_raw(image_name, level, dest_range, buffer_format,
+				buffer_range.offset as *const u8, buffer_range.size);
+		}
+
+		self.bind_image_upload_buffer(None);
+	}
+
+	/// Synchronously reads back the contents of `name` into `data`. This will stall until
+	/// all commands writing to the image have completed, so is best kept to tools and debugging.
+	pub fn read_image<T>(&self, name: ImageName, range: impl Into<Option<ImageRange>>,
+		format: ImageFormat, data: &mut [T])
+		where T: Copy
+	{
+		let Some(image_info) = self.get_image_info(name)
+			else { panic!("Trying to read back data from invalid ImageName") };
+
+		let ImageRange {offset, size} = range.into().unwrap_or(ImageRange::from_size(image_info.size));
+
+		assert!(!format.is_compressed(), "Reading back compressed images is not supported");
+
+		let data_size = data.len() * std::mem::size_of::<T>();
+		let expected_size = format.data_byte_size(size);
+		assert_eq!(data_size, expected_size, "Core::read_image not passed expected amount of storage");
+
+		// TODO(pat.m): Make this conditional and actually track state properly
+		self.bind_image_download_buffer(None);
+
+		let level = 0;
+
+		unsafe {
+			self.gl.PixelStorei(gl::PACK_ALIGNMENT, 1);
+			self.gl.GetTextureSubImage(name.as_raw(), level,
+				offset.x, offset.y, offset.z,
+				size.x, size.y, size.z,
+				format.to_raw_unsized(),
+				format.to_raw_component(),
+				data_size as i32,
+				data.as_mut_ptr().cast());
+		}
+	}
+
 	// TODO(pat.m): clear_image with other formats
 	pub fn clear_image_to_default(&self, image_name: ImageName) {
 		let Some(info) = self.get_image_info(image_name) else { return };
 
+		if info.format.is_compressed() {
+			log::warn!("Trying to clear compressed image {image_name:?} - compressed images can't be cleared");
+			return
+		}
+
 		if info.format.is_depth() {
-			self.clear_image_with_raw(image_name, gl::DEPTH_COMPONENT, gl::FLOAT, 1.0f32);
+			self.clear_image_with_raw(image_name, gl::DEPTH_COMPONENT, gl::FLOAT, self.far_depth());
 
 		} else if info.format.is_stencil() {
 			self.clear_image_with_raw(image_name, gl::STENCIL_INDEX, gl::UNSIGNED_BYTE, 0u8);
 
 		} else if info.format.is_depth_stencil() {
-			self.clear_image_with_depth_stencil(image_name, 1.0, 0);
+			self.clear_image_with_depth_stencil(image_name, self.far_depth(), 0);
 
 		} else if info.format.is_normalized() {
 			self.clear_image_with_raw(image_name, gl::RGBA, gl::UNSIGNED_BYTE, [0u8, 0, 0, 0]);
@@ -347,6 +473,31 @@ impl super::Core {
 		self.clear_image_with_raw(image_name, gl::DEPTH_STENCIL, gl::FLOAT_32_UNSIGNED_INT_24_8_REV, clear_value);
 	}
 
+	/// Same as clear_image_with_raw, except only clears `range`. `range` will be clamped to the bounds of the image.
+	pub fn clear_image_range_with_raw<T>(&self, image_name: ImageName, range: impl Into<ImageRange>, format: u32, data_type: u32, data: T)
+		where T: Copy
+	{
+		let Some(info) = self.get_image_info(image_name) else { return };
+
+		let ImageRange{offset, size} = range.into().clamp_to_size(info.size);
+		if size.x <= 0 || size.y <= 0 || size.z <= 0 {
+			return
+		}
+
+		let level = 0;
+
+		unsafe {
+			self.gl.ClearTexSubImage(image_name.as_raw(), level,
+				offset.x, offset.y, offset.z,
+				size.x, size.y, size.z,
+				format, data_type, (&data as *const T).cast());
+		}
+	}
+
+	pub fn clear_image_range_with_color(&self, image_name: ImageName, range: impl Into<ImageRange>, value: Color) {
+		self.clear_image_range_with_raw(image_name, range, gl::RGBA, gl::FLOAT, value);
+	}
+
 	pub fn clear_image_with_raw<T>(&self, image_name: ImageName, format: u32, data_type: u32, data: T)
 		where T: Copy
 	{
@@ -359,6 +510,99 @@ impl super::Core {
 }
 
 
+/// Sparse images. Only usable if [`super::Capabilities::sparse_texture_supported`].
+impl super::Core {
+	/// Size of a single page of a sparse 2D image of `format`, or None if `format` can't be sparse.
+	pub fn sparse_page_size(&self, format: ImageFormat) -> Option<Vec2i> {
+		if !self.capabilities.sparse_texture_supported {
+			return None
+		}
+
+		let mut num_page_sizes = 0;
+		let mut page_size = Vec2i::zero();
+
+		unsafe {
+			self.gl.GetInternalformativ(gl::TEXTURE_2D, format.to_raw(), gl::NUM_VIRTUAL_PAGE_SIZES_ARB, 1, &mut num_page_sizes);
+			if num_page_sizes == 0 {
+				return None
+			}
+
+			// Always use the first page size - see GL_VIRTUAL_PAGE_SIZE_INDEX_ARB.
+			self.gl.GetInternalformativ(gl::TEXTURE_2D, format.to_raw(), gl::VIRTUAL_PAGE_SIZE_X_ARB, 1, &mut page_size.x);
+			self.gl.GetInternalformativ(gl::TEXTURE_2D, format.to_raw(), gl::VIRTUAL_PAGE_SIZE_Y_ARB, 1, &mut page_size.y);
+		}
+
+		Some(page_size)
+	}
+
+	/// Create a 2D image with no memory committed. Reading from uncommitted pages gives undefined results, and writes
+	/// to them are discarded. See [`Core::commit_image_pages`].
+	pub fn create_sparse_image_2d(&self, format: ImageFormat, size: Vec2i, levels: u32) -> Option<ImageName> {
+		let page_size = self.sparse_page_size(format)?;
+		if size.x % page_size.x != 0 || size.y % page_size.y != 0 {
+			log::warn!("Sparse image size {size:?} must be a multiple of the page size {page_size:?}");
+			return None
+		}
+
+		let mut name = 0;
+
+		unsafe {
+			self.gl.CreateTextures(gl::TEXTURE_2D, 1, &mut name);
+			self.gl.TextureParameteri(name, gl::TEXTURE_SPARSE_ARB, gl::TRUE as i32);
+			self.gl.TextureStorage2D(name, levels as i32, format.to_raw(), size.x, size.y);
+		}
+
+		let name = ImageName {raw: name};
+		self.image_info.borrow_mut().insert(name, ImageInfoInternal {
+			info: ImageInfo {
+				image_type: ImageType::Image2D,
+				format,
+				size: size.extend(1),
+				levels,
+				samples: 1,
+			},
+			views: Default::default(),
+		});
+
+		Some(name)
+	}
+
+	/// Number of levels of a sparse image that can be committed page by page. Any levels after this make up the
+	/// 'mip tail', which is committed all at once by committing any page of level `sparse_image_levels`.
+	pub fn sparse_image_levels(&self, name: ImageName) -> u32 {
+		let mut num_levels = 0;
+
+		unsafe {
+			self.gl.GetTextureParameteriv(name.as_raw(), gl::NUM_SPARSE_LEVELS_ARB, &mut num_levels);
+		}
+
+		num_levels as u32
+	}
+
+	/// Allocate or release memory for the pages of a sparse image overlapping `range` at `level`.
+	/// `range` must be aligned to the page size, except where it touches the edge of the level.
+	pub fn commit_image_pages(&self, name: ImageName, level: u32, range: ImageRange, commit: bool) {
+		let ImageRange {offset, size} = range;
+
+		// There's no DSA version of glTexPageCommitmentARB in core profile, so bind to unit 0 - the active unit,
+		// since glActiveTexture is never called. Whatever was bound there is restored afterwards, so that sampler
+		// bindings for later draws aren't stomped.
+		unsafe {
+			let mut previous_binding = 0;
+			self.gl.GetIntegerv(gl::TEXTURE_BINDING_2D, &mut previous_binding);
+
+			self.gl.BindTextureUnit(0, name.as_raw());
+			self.gl.TexPageCommitmentARB(gl::TEXTURE_2D, level as i32,
+				offset.x, offset.y, offset.z,
+				size.x, size.y, size.z,
+				commit as u8);
+
+			self.gl.BindTextureUnit(0, previous_binding as u32);
+		}
+	}
+}
+
+
 
 #[derive(Copy, Clone, Debug)]
 pub struct ImageRange {
@@ -382,5 +626,13 @@ impl ImageRange {
 	}
 }
 
-// TODO(pat.m): when Aabb3i exists
-// impl From<Aabb3i> for ImageRange {}
\ No newline at end of file
+impl ImageRange {
+	pub fn to_aabb(&self) -> crate::Aabb3i {
+		(*self).into()
+	}
+
+	/// Clamp range so that it lies entirely within an image of size `image_size`.
+	pub fn clamp_to_size(&self, image_size: Vec3i) -> ImageRange {
+		self.to_aabb().clamp_to(&crate::Aabb3i::from_size(image_size)).into()
+	}
+}
\ No newline at end of file
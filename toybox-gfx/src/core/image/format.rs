@@ -8,6 +8,7 @@ pub enum ImageFormat {
 	Red(ComponentFormat),
 
 	R11G11B10F,
+	Rgb9E5,
 	Rgb10A2,
 	Rgb10A2Ui,
 	Srgb8,
@@ -19,6 +20,11 @@ pub enum ImageFormat {
 
 	Depth16,
 	Depth32,
+
+	/// Block compressed unsigned HDR. Can only be uploaded to in whole 4x4 blocks.
+	Bc6hUfloat,
+	/// Block compressed signed HDR. Can only be uploaded to in whole 4x4 blocks.
+	Bc6hSfloat,
 }
 
 
@@ -87,6 +93,7 @@ impl ImageFormat {
 			ImageFormat::Red(ComponentFormat::F32) => gl::R32F,
 
 			ImageFormat::R11G11B10F => gl::R11F_G11F_B10F,
+			ImageFormat::Rgb9E5 => gl::RGB9_E5,
 			ImageFormat::Rgb10A2 => gl::RGB10_A2,
 			ImageFormat::Rgb10A2Ui => gl::RGB10_A2UI,
 			ImageFormat::Srgb8 => gl::SRGB8,
@@ -98,6 +105,9 @@ impl ImageFormat {
 
 			ImageFormat::Depth16 => gl::DEPTH_COMPONENT16,
 			ImageFormat::Depth32 => gl::DEPTH_COMPONENT32F,
+
+			ImageFormat::Bc6hUfloat => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+			ImageFormat::Bc6hSfloat => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
 		}
 	}
 
@@ -107,7 +117,17 @@ impl ImageFormat {
 		match self {
 			Red(component) | Rgb(component) | RedGreen(component) | Rgba(component) => component.to_raw(),
 			Srgb8 | Srgba8 | Stencil => gl::UNSIGNED_BYTE,
-			_ => panic!("Unsupported"),
+
+			R11G11B10F => gl::UNSIGNED_INT_10F_11F_11F_REV,
+			Rgb9E5 => gl::UNSIGNED_INT_5_9_9_9_REV,
+			Rgb10A2 | Rgb10A2Ui => gl::UNSIGNED_INT_2_10_10_10_REV,
+
+			Depth16 => gl::UNSIGNED_SHORT,
+			Depth => gl::UNSIGNED_INT,
+			Depth32 => gl::FLOAT,
+			DepthStencil => gl::UNSIGNED_INT_24_8,
+
+			Bc6hUfloat | Bc6hSfloat => panic!("Compressed formats have no component type"),
 		}
 	}
 
@@ -126,15 +146,20 @@ impl ImageFormat {
 			ImageFormat::Rgb10A2 => gl::RGBA,
 			ImageFormat::Rgb10A2Ui => gl::RGBA_INTEGER,
 			ImageFormat::R11G11B10F => gl::RGB,
+			ImageFormat::Rgb9E5 => gl::RGB,
 			ImageFormat::Srgb8 => gl::RGB,
 			ImageFormat::Srgba8 => gl::RGBA,
 
 			ImageFormat::Depth | ImageFormat::Depth16 | ImageFormat::Depth32 => gl::DEPTH_COMPONENT,
 			ImageFormat::Stencil => gl::STENCIL_INDEX,
 			ImageFormat::DepthStencil => gl::DEPTH_STENCIL,
+
+			ImageFormat::Bc6hUfloat | ImageFormat::Bc6hSfloat => gl::RGB,
 		}
 	}
 
+	/// For block compressed formats this is the average size of a texel, rounded up.
+	/// Use [`ImageFormat::data_byte_size`] to get the real size of an image.
 	pub fn texel_byte_size(&self) -> usize {
 		use ImageFormat::*;
 
@@ -144,15 +169,40 @@ impl ImageFormat {
 			Rgb(component) => component.byte_size() * 3,
 			Rgba(component) => component.byte_size() * 4,
 			Srgb8 => 3,
-			Rgb10A2 | Rgb10A2Ui | R11G11B10F | Srgba8 => 4,
+			Rgb10A2 | Rgb10A2Ui | R11G11B10F | Rgb9E5 | Srgba8 => 4,
 
 			Stencil => 1,
 			Depth16 => 2,
-			Depth => 3,
-			Depth32 | DepthStencil => 4,
+			// 24b depth has no packed client side representation, so transfers are always 32b.
+			Depth | Depth32 | DepthStencil => 4,
+
+			Bc6hUfloat | Bc6hSfloat => 1,
 		}
 	}
 
+	/// Size in bytes of tightly packed data for an image range of `size` in this format.
+	pub fn data_byte_size(&self, size: Vec3i) -> usize {
+		if let Some(block_byte_size) = self.block_byte_size() {
+			let blocks_x = (size.x + 3) / 4;
+			let blocks_y = (size.y + 3) / 4;
+			return block_byte_size * (blocks_x * blocks_y * size.z) as usize;
+		}
+
+		self.texel_byte_size() * (size.x * size.y * size.z) as usize
+	}
+
+	/// Size of a 4x4 block for block compressed formats, or None if format isn't compressed.
+	pub fn block_byte_size(&self) -> Option<usize> {
+		match self {
+			ImageFormat::Bc6hUfloat | ImageFormat::Bc6hSfloat => Some(16),
+			_ => None,
+		}
+	}
+
+	pub fn is_compressed(&self) -> bool {
+		self.block_byte_size().is_some()
+	}
+
 	pub fn is_normalized(&self) -> bool {
 		match self {
 			ImageFormat::Rgba(comp) => comp.is_normalized(),
@@ -163,6 +213,7 @@ impl ImageFormat {
 			ImageFormat::Rgb10A2Ui => false,
 			ImageFormat::Rgb10A2 => true,
 			ImageFormat::R11G11B10F => true,
+			ImageFormat::Rgb9E5 => true,
 			ImageFormat::Srgb8 => true,
 			ImageFormat::Srgba8 => true,
 
@@ -170,6 +221,19 @@ impl ImageFormat {
 			ImageFormat::Depth | ImageFormat::Depth16 | ImageFormat::Depth32 => true,
 			ImageFormat::Stencil => false,
 			ImageFormat::DepthStencil => false,
+
+			ImageFormat::Bc6hUfloat | ImageFormat::Bc6hSfloat => true,
+		}
+	}
+
+	/// Whether or not format can store values outside of 0..1.
+	pub fn is_hdr(&self) -> bool {
+		use ImageFormat::*;
+
+		match self {
+			Rgba(comp) | Rgb(comp) | RedGreen(comp) | Red(comp) => matches!(comp, ComponentFormat::F16 | ComponentFormat::F32),
+			R11G11B10F | Rgb9E5 | Bc6hUfloat | Bc6hSfloat => true,
+			_ => false,
 		}
 	}
 
@@ -27,8 +27,91 @@ pub enum FilterMode {
 }
 
 
+/// Full description of sampler state, for creating samplers in one go.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SamplerDescription {
+	pub minify_filter: FilterMode,
+	pub mip_filter: Option<FilterMode>,
+	pub magnify_filter: FilterMode,
+	pub addressing_mode: AddressingMode,
+
+	pub lod_bias: f32,
+	pub min_lod: f32,
+	pub max_lod: f32,
+
+	/// 1.0 disables anisotropic filtering. Clamped to [`Capabilities::max_anisotropy`](crate::Capabilities::max_anisotropy).
+	pub max_anisotropy: f32,
+}
+
+impl Default for SamplerDescription {
+	fn default() -> SamplerDescription {
+		SamplerDescription {
+			minify_filter: FilterMode::Linear,
+			mip_filter: None,
+			magnify_filter: FilterMode::Linear,
+			addressing_mode: AddressingMode::Clamp,
+
+			// GL defaults
+			lod_bias: 0.0,
+			min_lod: -1000.0,
+			max_lod: 1000.0,
+			max_anisotropy: 1.0,
+		}
+	}
+}
+
+impl SamplerDescription {
+	pub fn nearest() -> Self {
+		SamplerDescription {
+			minify_filter: FilterMode::Nearest,
+			magnify_filter: FilterMode::Nearest,
+			.. SamplerDescription::default()
+		}
+	}
+
+	pub fn linear() -> Self {
+		SamplerDescription::default()
+	}
+
+	pub fn addressing_mode(self, addressing_mode: AddressingMode) -> Self {
+		Self { addressing_mode, .. self }
+	}
+
+	pub fn mip_filter(self, mip_filter: impl Into<Option<FilterMode>>) -> Self {
+		Self { mip_filter: mip_filter.into(), .. self }
+	}
+
+	pub fn lod_bias(self, lod_bias: f32) -> Self {
+		Self { lod_bias, .. self }
+	}
+
+	pub fn lod_range(self, min_lod: f32, max_lod: f32) -> Self {
+		Self { min_lod, max_lod, .. self }
+	}
+
+	pub fn anisotropy(self, max_anisotropy: f32) -> Self {
+		Self { max_anisotropy, .. self }
+	}
+}
+
+
 /// Samplers
 impl super::Core {
+	pub fn create_sampler_from_description(&self, desc: &SamplerDescription) -> SamplerName {
+		let name = self.create_sampler();
+		self.apply_sampler_description(name, desc);
+		name
+	}
+
+	pub fn apply_sampler_description(&self, name: SamplerName, desc: &SamplerDescription) {
+		self.set_sampler_minify_filter(name, desc.minify_filter, desc.mip_filter);
+		self.set_sampler_magnify_filter(name, desc.magnify_filter);
+		self.set_sampler_addressing_mode(name, desc.addressing_mode);
+		self.set_sampler_lod_bias(name, desc.lod_bias);
+		self.set_sampler_lod_range(name, desc.min_lod, desc.max_lod);
+		self.set_sampler_max_anisotropy(name, desc.max_anisotropy);
+	}
+
 	pub fn create_sampler(&self) -> SamplerName {
 		SamplerName {
 			raw: unsafe {
@@ -101,4 +184,28 @@ impl super::Core {
 			self.gl.SamplerParameteri(name.raw, gl::TEXTURE_MAG_FILTER, value as i32);
 		}
 	}
-}
\ No newline at end of file
+
+	/// Bias added to the calculated level of detail before mip selection. Negative values sharpen, positive values blur.
+	pub fn set_sampler_lod_bias(&self, name: SamplerName, bias: f32) {
+		unsafe {
+			self.gl.SamplerParameterf(name.raw, gl::TEXTURE_LOD_BIAS, bias);
+		}
+	}
+
+	/// Clamp the level of detail used for mip selection. Defaults to -1000..1000, effectively unclamped.
+	pub fn set_sampler_lod_range(&self, name: SamplerName, min_lod: f32, max_lod: f32) {
+		unsafe {
+			self.gl.SamplerParameterf(name.raw, gl::TEXTURE_MIN_LOD, min_lod);
+			self.gl.SamplerParameterf(name.raw, gl::TEXTURE_MAX_LOD, max_lod);
+		}
+	}
+
+	/// Maximum degree of anisotropic filtering, from 1.0 (disabled) up to [`Capabilities::max_anisotropy`](crate::Capabilities::max_anisotropy).
+	pub fn set_sampler_max_anisotropy(&self, name: SamplerName, max_anisotropy: f32) {
+		let max_anisotropy = max_anisotropy.clamp(1.0, self.capabilities.max_anisotropy.max(1.0));
+
+		unsafe {
+			self.gl.SamplerParameterf(name.raw, gl::TEXTURE_MAX_ANISOTROPY, max_anisotropy);
+		}
+	}
+}
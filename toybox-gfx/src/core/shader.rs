@@ -17,15 +17,36 @@ impl super::ResourceName for ShaderName {
 #[repr(u32)]
 pub enum ShaderType {
 	Vertex = gl::VERTEX_SHADER,
+	TessControl = gl::TESS_CONTROL_SHADER,
+	TessEvaluation = gl::TESS_EVALUATION_SHADER,
+	Geometry = gl::GEOMETRY_SHADER,
 	Fragment = gl::FRAGMENT_SHADER,
 	Compute = gl::COMPUTE_SHADER,
 }
 
 
+/// A shader that has been submitted for compilation, but may not have finished compiling.
+/// See [`Core::begin_create_shader`](super::Core::begin_create_shader).
+#[derive(Debug)]
+#[must_use]
+pub struct PendingShader {
+	raw: u32,
+	shader_type: ShaderType,
+}
+
+
 /// Shaders
 impl super::Core {
 	#[tracing::instrument(skip_all, name="gfx Core::create_shader")]
 	pub fn create_shader(&self, shader_type: ShaderType, src_chunks: &[&str]) -> anyhow::Result<ShaderName> {
+		let pending = self.begin_create_shader(shader_type, src_chunks)?;
+		self.finish_create_shader(pending)
+	}
+
+	/// Submit a shader for compilation without waiting for the result. If parallel shader compilation is supported,
+	/// the driver can compile many shaders at once while the cpu gets on with other work.
+	#[tracing::instrument(skip_all, name="gfx Core::begin_create_shader")]
+	pub fn begin_create_shader(&self, shader_type: ShaderType, src_chunks: &[&str]) -> anyhow::Result<PendingShader> {
 		use std::ffi::CString;
 
 		let c_strings: Vec<_> = src_chunks.iter()
@@ -46,6 +67,81 @@ impl super::Core {
 			anyhow::bail!("Failed to compile shader")
 		}
 
+		Ok(PendingShader {
+			raw: program_name,
+			shader_type,
+		})
+	}
+
+	/// Create a shader from a SPIR-V module, e.g., compiled offline with glslang. `specialization` is a list of
+	/// specialization constant ids and values.
+	/// Specialization is done immediately, but linking can still happen in parallel - see [`Core::begin_create_shader`].
+	#[tracing::instrument(skip_all, name="gfx Core::begin_create_shader_from_spirv")]
+	pub fn begin_create_shader_from_spirv(&self, shader_type: ShaderType, spirv: &[u8], entry_point: &str, specialization: &[(u32, u32)])
+		-> anyhow::Result<PendingShader>
+	{
+		anyhow::ensure!(self.capabilities.spirv_supported, "SPIR-V shaders not supported");
+		anyhow::ensure!(spirv.len() % 4 == 0, "SPIR-V module size must be a multiple of 4");
+
+		let entry_point = std::ffi::CString::new(entry_point)?;
+		let (constant_indices, constant_values): (Vec<u32>, Vec<u32>) = specialization.iter().copied().unzip();
+
+		unsafe {
+			let shader = self.gl.CreateShader(shader_type as u32);
+			self.gl.ShaderBinary(1, &shader, gl::SHADER_BINARY_FORMAT_SPIR_V, spirv.as_ptr().cast(), spirv.len() as _);
+			self.gl.SpecializeShader(shader, entry_point.as_ptr(), constant_indices.len() as _,
+				constant_indices.as_ptr(), constant_values.as_ptr());
+
+			let mut status = 0;
+			self.gl.GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+
+			if status == 0 {
+				let mut buf = [0u8; 1024];
+				let mut len = 0;
+
+				self.gl.GetShaderInfoLog(shader, buf.len() as _, &mut len, buf.as_mut_ptr() as _);
+				self.gl.DeleteShader(shader);
+
+				let error = std::str::from_utf8(&buf[..len as usize])?;
+				anyhow::bail!("Failed to specialize SPIR-V shader: {error}");
+			}
+
+			let program_name = self.gl.CreateProgram();
+			self.gl.ProgramParameteri(program_name, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
+			self.gl.AttachShader(program_name, shader);
+			self.gl.LinkProgram(program_name);
+
+			// The shader object is only needed until link time.
+			self.gl.DetachShader(program_name, shader);
+			self.gl.DeleteShader(shader);
+
+			Ok(PendingShader {
+				raw: program_name,
+				shader_type,
+			})
+		}
+	}
+
+	/// Whether [`Core::finish_create_shader`] can be called without blocking.
+	/// Always true if parallel shader compilation isn't supported.
+	pub fn is_shader_compile_complete(&self, pending: &PendingShader) -> bool {
+		if !self.capabilities.parallel_shader_compilation_supported {
+			return true
+		}
+
+		let mut status = 0;
+		unsafe {
+			self.gl.GetProgramiv(pending.raw, gl::COMPLETION_STATUS_ARB, &mut status);
+		}
+
+		status != 0
+	}
+
+	/// Wait for a pending shader to finish compiling and check the result.
+	#[tracing::instrument(skip_all, name="gfx Core::finish_create_shader")]
+	pub fn finish_create_shader(&self, pending: PendingShader) -> anyhow::Result<ShaderName> {
+		let PendingShader{raw: program_name, shader_type} = pending;
+
 		let mut status = 0;
 		unsafe {
 			self.gl.GetProgramiv(program_name, gl::LINK_STATUS, &mut status);
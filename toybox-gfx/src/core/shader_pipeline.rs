@@ -38,6 +38,9 @@ impl super::Core {
 	pub fn attach_shader_to_pipeline(&self, pipeline: ShaderPipelineName, shader: ShaderName) {
 		let stage_bit = match shader.shader_type {
 			ShaderType::Vertex => gl::VERTEX_SHADER_BIT,
+			ShaderType::TessControl => gl::TESS_CONTROL_SHADER_BIT,
+			ShaderType::TessEvaluation => gl::TESS_EVALUATION_SHADER_BIT,
+			ShaderType::Geometry => gl::GEOMETRY_SHADER_BIT,
 			ShaderType::Fragment => gl::FRAGMENT_SHADER_BIT,
 			ShaderType::Compute => gl::COMPUTE_SHADER_BIT,
 		};
@@ -0,0 +1,60 @@
+use crate::prelude::*;
+use super::ShaderName;
+
+
+/// Value for a standalone (non-block) uniform. See [`Core::set_uniform`](super::Core::set_uniform).
+#[derive(Debug, Copy, Clone)]
+pub enum UniformValue {
+	F32(f32),
+	Vec2(Vec2),
+	Vec3(Vec3),
+	Vec4(Vec4),
+	I32(i32),
+	U32(u32),
+	Mat4(Mat4),
+}
+
+impl From<f32> for UniformValue { fn from(o: f32) -> Self { UniformValue::F32(o) } }
+impl From<Vec2> for UniformValue { fn from(o: Vec2) -> Self { UniformValue::Vec2(o) } }
+impl From<Vec3> for UniformValue { fn from(o: Vec3) -> Self { UniformValue::Vec3(o) } }
+impl From<Vec4> for UniformValue { fn from(o: Vec4) -> Self { UniformValue::Vec4(o) } }
+impl From<i32> for UniformValue { fn from(o: i32) -> Self { UniformValue::I32(o) } }
+impl From<u32> for UniformValue { fn from(o: u32) -> Self { UniformValue::U32(o) } }
+impl From<Mat4> for UniformValue { fn from(o: Mat4) -> Self { UniformValue::Mat4(o) } }
+
+
+/// Standalone uniforms. Only intended for prototyping - these are set per program with a reflection query each time,
+/// so UBOs or push constants should be used for anything that sticks around.
+impl super::Core {
+	/// Location of uniform `name` in `shader`, or None if it isn't declared or has been optimised out.
+	pub fn uniform_location(&self, shader: ShaderName, name: &str) -> Option<i32> {
+		let name = std::ffi::CString::new(name).ok()?;
+
+		let location = unsafe {
+			self.gl.GetProgramResourceLocation(shader.raw, gl::UNIFORM, name.as_ptr())
+		};
+
+		(location >= 0).then_some(location)
+	}
+
+	pub fn set_uniform(&self, shader: ShaderName, location: i32, value: UniformValue) {
+		let program = shader.raw;
+
+		unsafe {
+			match value {
+				UniformValue::F32(v) => self.gl.ProgramUniform1f(program, location, v),
+				UniformValue::Vec2(v) => self.gl.ProgramUniform2f(program, location, v.x, v.y),
+				UniformValue::Vec3(v) => self.gl.ProgramUniform3f(program, location, v.x, v.y, v.z),
+				UniformValue::Vec4(v) => self.gl.ProgramUniform4f(program, location, v.x, v.y, v.z, v.w),
+				UniformValue::I32(v) => self.gl.ProgramUniform1i(program, location, v),
+				UniformValue::U32(v) => self.gl.ProgramUniform1ui(program, location, v),
+				UniformValue::Mat4(m) => {
+					// Mat4 is row major, and the preamble's row_major qualifier only applies to blocks, so loose
+					// uniforms have to be transposed to match values passed through UBOs.
+					let ptr = (&m as *const Mat4).cast();
+					self.gl.ProgramUniformMatrix4fv(program, location, 1, gl::TRUE, ptr);
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,112 @@
+use crate::prelude::*;
+
+
+/// View frustum in world space, for coarse visibility tests on the cpu.
+#[derive(Debug, Copy, Clone)]
+pub struct Frustum {
+	/// Planes as (normal, distance) with normals pointing inwards. Not necessarily normalized for far planes
+	/// at infinity, but always normalized otherwise.
+	planes: [Vec4; 6],
+}
+
+impl Frustum {
+	/// Extracts frustum planes from the matrix used to transform world space into clip space, with the standard -1..1
+	/// depth range - i.e., before [`crate::math::reverse_z_projection`]. See [`Frustum::from_reverse_z_projection_view`]
+	/// otherwise.
+	/// https://www.gamedevs.org/uploads/fast-extraction-viewing-frustum-planes-from-world-view-projection-matrix.pdf
+	pub fn from_projection_view(projection_view: &Mat4) -> Frustum {
+		let [r0, r1, r2, r3] = projection_view.rows;
+
+		Frustum::from_planes([
+			r3 + r0, // left
+			r3 - r0, // right
+			r3 + r1, // bottom
+			r3 - r1, // top
+			r3 + r2, // near
+			r3 - r2, // far
+		])
+	}
+
+	/// Like [`Frustum::from_projection_view`], but for matrices adjusted with [`crate::math::reverse_z_projection`],
+	/// where clip depth runs from w at the near plane to 0 at the far plane.
+	pub fn from_reverse_z_projection_view(projection_view: &Mat4) -> Frustum {
+		let [r0, r1, r2, r3] = projection_view.rows;
+
+		Frustum::from_planes([
+			r3 + r0, // left
+			r3 - r0, // right
+			r3 + r1, // bottom
+			r3 - r1, // top
+			r3 - r2, // near
+			r2, // far
+		])
+	}
+
+	fn from_planes(planes: [Vec4; 6]) -> Frustum {
+		Frustum {
+			planes: planes.map(|plane| {
+				let length = Vec3::new(plane.x, plane.y, plane.z).length();
+				if length > 0.0 { plane / length } else { plane }
+			}),
+		}
+	}
+
+	pub fn contains_point(&self, point: Vec3) -> bool {
+		self.planes.iter()
+			.all(|plane| signed_distance(plane, point) >= 0.0)
+	}
+
+	/// Conservative - may return true for spheres just outside the corners of the frustum.
+	pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+		self.planes.iter()
+			.all(|plane| signed_distance(plane, center) >= -radius)
+	}
+
+	/// Conservative - may return true for boxes just outside the corners of the frustum.
+	pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+		self.planes.iter()
+			.all(|plane| {
+				// Test the corner furthest along the plane normal.
+				let positive_corner = Vec3::new(
+					if plane.x >= 0.0 { max.x } else { min.x },
+					if plane.y >= 0.0 { max.y } else { min.y },
+					if plane.z >= 0.0 { max.z } else { min.z },
+				);
+
+				signed_distance(plane, positive_corner) >= 0.0
+			})
+	}
+}
+
+fn signed_distance(plane: &Vec4, point: Vec3) -> f32 {
+	plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+}
+
+
+
+/// Picks a level of detail given a list of increasing distance thresholds.
+/// Returns 0 if `distance` is less than `thresholds[0]`, and `thresholds.len()` if it is beyond all thresholds.
+pub fn select_lod_by_distance(thresholds: &[f32], distance: f32) -> usize {
+	thresholds.iter()
+		.position(|&threshold| distance < threshold)
+		.unwrap_or(thresholds.len())
+}
+
+/// Approximate fraction of the screen height covered by a sphere - 1.0 means it fills the screen vertically.
+/// `fov_y` is the vertical field of view in radians.
+pub fn screen_coverage(radius: f32, distance: f32, fov_y: f32) -> f32 {
+	if distance <= radius {
+		return 1.0
+	}
+
+	let projected_radius = radius / (distance * (fov_y / 2.0).tan());
+	projected_radius.min(1.0)
+}
+
+/// Picks a level of detail given a list of decreasing screen coverage thresholds - see [`screen_coverage`].
+/// Returns 0 if `coverage` is greater than `thresholds[0]`, and `thresholds.len()` if it is below all thresholds.
+pub fn select_lod_by_coverage(thresholds: &[f32], coverage: f32) -> usize {
+	thresholds.iter()
+		.position(|&threshold| coverage > threshold)
+		.unwrap_or(thresholds.len())
+}
@@ -0,0 +1,222 @@
+//! Renders the scene from a point into a cube map - for quick environment probes, or for photo-mode panoramas.
+//!
+//! The scene is drawn once per face by the same callback the app uses for its main view, so anything it can draw ends
+//! up in the capture. Captures are encoded like any other commands and execute with the rest of the frame - poll the
+//! returned [`EnvironmentCapture`] to find out when the GPU has finished.
+//!
+//! ```ignore
+//! let capture = gfx.capture_environment(probe_position, &EnvironmentCaptureSettings::default(), |face, group| {
+//!     scene.draw(group, &face.frustum);
+//! });
+//!
+//! // Some frames later
+//! if capture.poll(&gfx.core) {
+//!     main_group.draw(...).sampled_image(0, capture.image(), CommonSampler::Linear);
+//! }
+//! ```
+
+use crate::prelude::*;
+use crate::{
+	System, FrameStage, CommandGroupEncoder, ImageHandle, ImageFormat, ImageClearPolicy,
+	CreateImageRequest, FramebufferDescription, Frustum,
+	shadows::{cube_face_directions, apply_depth_convention, perspective_90, look_along_matrix},
+};
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentCaptureSettings {
+	/// Captures with the same label share images, so recapturing a probe overwrites its previous result.
+	/// Recapturing at a different resolution or format releases the previous images.
+	pub label: String,
+
+	/// Size of each face.
+	pub resolution: i32,
+	pub format: ImageFormat,
+
+	pub near: f32,
+	pub far: f32,
+
+	/// Color each face is cleared to before drawing.
+	pub clear_color: Color,
+
+	/// Face `i` is rendered in `FrameStage::BeforeMain(first_stage + i)`.
+	pub first_stage: i8,
+}
+
+impl Default for EnvironmentCaptureSettings {
+	fn default() -> Self {
+		EnvironmentCaptureSettings {
+			label: String::from("environment capture"),
+			resolution: 256,
+			format: ImageFormat::rgba16f(),
+			near: 0.1,
+			far: 1000.0,
+			clear_color: Color::black(),
+			first_stage: -80,
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct EnvironmentCaptureFace {
+	/// Cube face index, in the order +X, -X, +Y, -Y, +Z, -Z.
+	pub index: usize,
+
+	pub view: Mat4,
+	pub projection: Mat4,
+
+	/// Adjusted for reverse-z if enabled, and bound to UBO 0.
+	pub projection_view: Mat4,
+
+	/// For culling.
+	pub frustum: Frustum,
+}
+
+
+#[derive(Debug, Copy, Clone)]
+enum CaptureState {
+	Encoded,
+	Submitted(gl::types::GLsync),
+	Complete,
+}
+
+/// Shared between a capture and the command that submits it, so the fence is deleted even if the capture is dropped
+/// before it completes.
+struct CaptureFence {
+	gl: gl::Gl,
+	state: Cell<CaptureState>,
+}
+
+impl Drop for CaptureFence {
+	fn drop(&mut self) {
+		if let CaptureState::Submitted(fence) = self.state.get() {
+			unsafe { self.gl.DeleteSync(fence) };
+		}
+	}
+}
+
+impl std::fmt::Debug for CaptureFence {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_tuple("CaptureFence").field(&self.state.get()).finish()
+	}
+}
+
+
+/// Completion handle for a capture started with [`System::capture_environment`].
+#[derive(Debug, Clone)]
+pub struct EnvironmentCapture {
+	image: ImageHandle,
+	depth_image: ImageHandle,
+	fence: Rc<CaptureFence>,
+}
+
+impl EnvironmentCapture {
+	/// Cube map holding the capture. Contents are undefined until [`EnvironmentCapture::poll`] returns true, but it can
+	/// be bound in any stage after the capture's, since commands execute in order.
+	pub fn image(&self) -> ImageHandle {
+		self.image
+	}
+
+	pub fn depth_image(&self) -> ImageHandle {
+		self.depth_image
+	}
+
+	/// Whether the GPU has finished rendering the capture. Never blocks.
+	pub fn poll(&self, core: &crate::Core) -> bool {
+		match self.fence.state.get() {
+			CaptureState::Encoded => false,
+			CaptureState::Complete => true,
+
+			CaptureState::Submitted(fence) => {
+				let result = unsafe { core.gl.ClientWaitSync(fence, 0, 0) };
+				if !matches!(result, gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED) {
+					return false
+				}
+
+				unsafe { core.gl.DeleteSync(fence) };
+				self.fence.state.set(CaptureState::Complete);
+				true
+			}
+		}
+	}
+}
+
+
+impl System {
+	/// Encode six command groups rendering the scene from `position` into a cube map, one per face.
+	/// Each group has its face bound as the rendertarget, and [`EnvironmentCaptureFace::projection_view`] bound to UBO 0,
+	/// matching [`crate::shaders::STANDARD_VS_SHADER_SOURCE`].
+	pub fn capture_environment(&mut self, position: Vec3, settings: &EnvironmentCaptureSettings,
+		mut draw_scene: impl FnMut(&EnvironmentCaptureFace, &mut CommandGroupEncoder<'_>))
+		-> EnvironmentCapture
+	{
+		let EnvironmentCaptureSettings{label, resolution, format, near, far, clear_color, first_stage} = settings;
+		assert!(*resolution > 0, "Environment capture resolution must be positive");
+
+		let rm = &mut self.resource_manager;
+		let image = rm.request(CreateImageRequest::fixed_cube(format!("{label} color"), *resolution, *format));
+		let depth_image = rm.request(CreateImageRequest::fixed_cube(format!("{label} depth"), *resolution, ImageFormat::Depth)
+			.clear_policy(ImageClearPolicy::DefaultAtFrameStart));
+
+		// Release images from any previous capture with this label, now that the new ones hold a reference.
+		if let Some((previous_image, previous_depth_image)) = self.environment_captures.insert(label.clone(), (image, depth_image)) {
+			rm.release_image(&self.core, previous_image);
+			rm.release_image(&self.core, previous_depth_image);
+		}
+
+		let fence = Rc::new(CaptureFence {
+			gl: self.core.gl.clone(),
+			state: Cell::new(CaptureState::Encoded),
+		});
+		let projection = perspective_90(*near, *far);
+		let reverse_z = self.core.is_reverse_z();
+
+		for (index, (forward, up)) in cube_face_directions().into_iter().enumerate() {
+			let view = look_along_matrix(position, forward, up);
+			let projection_view = projection * view;
+
+			let face = EnvironmentCaptureFace {
+				index,
+				view,
+				projection,
+				projection_view: apply_depth_convention(projection_view, reverse_z),
+				frustum: Frustum::from_projection_view(&projection_view),
+			};
+
+			let stage = FrameStage::BeforeMain(first_stage.saturating_add(index as i8));
+			let framebuffer = FramebufferDescription::from(&[image, depth_image]).with_layer(index as u32);
+
+			let mut group = self.frame_encoder.command_group(stage)
+				.annotate(format!("{label} face {index}"));
+
+			// Clears every face at once.
+			if index == 0 {
+				let clear_color = *clear_color;
+				group.execute(move |core, rm| {
+					if let Some(image_name) = rm.images.get_name(image) {
+						core.clear_image_with_color(image_name, clear_color);
+					}
+				});
+			}
+
+			group.bind_rendertargets(framebuffer);
+			group.bind_shared_ubo(0, &[face.projection_view]);
+
+			draw_scene(&face, &mut *group);
+
+			if index == 5 {
+				let fence = fence.clone();
+				group.execute(move |core, _| {
+					let sync = unsafe { core.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+					fence.state.set(CaptureState::Submitted(sync));
+				});
+			}
+		}
+
+		EnvironmentCapture { image, depth_image, fence }
+	}
+}
@@ -0,0 +1,35 @@
+use std::hash::Hasher;
+
+
+/// FNV-1a. Used instead of std's hashers since those aren't guaranteed to be stable between builds.
+#[derive(Debug, Copy, Clone)]
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+	fn default() -> Self {
+		FnvHasher(0xcbf2_9ce4_8422_2325)
+	}
+}
+
+impl Hasher for FnvHasher {
+	fn finish(&self) -> u64 {
+		self.0
+	}
+
+	fn write(&mut self, bytes: &[u8]) {
+		self.0 = fnv1a(self.0, bytes);
+	}
+}
+
+impl std::fmt::Write for FnvHasher {
+	fn write_str(&mut self, s: &str) -> std::fmt::Result {
+		Hasher::write(self, s.as_bytes());
+		Ok(())
+	}
+}
+
+/// Continue an FNV-1a hash from `hash`.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+	bytes.iter()
+		.fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+}
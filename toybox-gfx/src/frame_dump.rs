@@ -0,0 +1,115 @@
+use crate::System;
+use crate::command::Command;
+
+use crate::fnv::FnvHasher;
+
+use std::fmt::Write;
+use std::hash::Hasher;
+use std::path::PathBuf;
+
+
+impl System {
+	/// Write a text description of every command group in the next executed frame to `virtual_path` in user data.
+	/// Bindings are written after resolution, so dumps from different builds can be diffed to catch encoding regressions.
+	pub fn dump_next_frame(&mut self, virtual_path: impl Into<PathBuf>) {
+		self.pending_frame_dump = Some(virtual_path.into());
+	}
+
+	/// When enabled, a hash of every frame's commands is available from [`System::last_command_hash`].
+	/// Commands are hashed before resolution - handles, staged upload contents and parameters rather than GL names
+	/// and heap offsets - so the hash is stable between runs and machines given the same commands, and can be used
+	/// to find where two runs diverge. Expensive, so disabled by default.
+	pub fn set_command_hashing(&mut self, enabled: bool) {
+		self.command_hashing_enabled = enabled;
+
+		if !enabled {
+			self.last_command_hash = None;
+		}
+	}
+
+	pub fn last_command_hash(&self) -> Option<u64> {
+		self.last_command_hash
+	}
+
+	/// Must be called before bind sources are resolved and staged data is pushed to the upload heap.
+	pub(crate) fn update_command_hash(&mut self) {
+		if !self.command_hashing_enabled {
+			return
+		}
+
+		let mut hasher = FnvHasher::default();
+
+		write!(hasher, "{:?} {:?}", self.core.backbuffer_size(), self.frame_encoder.backbuffer_clear_color).unwrap();
+		self.frame_encoder.upload_stage.hash_staged_data(&mut hasher);
+
+		for command_group in self.frame_encoder.command_groups.iter() {
+			write!(hasher, "{:?} {}", command_group.stage, self.frame_encoder.is_stage_enabled(command_group.stage)).unwrap();
+
+			for (attachment, ops) in command_group.attachment_ops.iter() {
+				write!(hasher, "{attachment:?} {:?} {:?}", ops.load, ops.store).unwrap();
+			}
+
+			// Unresolved arguments only refer to handles and staged upload ids, both of which are assigned in request order.
+			for command in command_group.commands.iter() {
+				match command {
+					Command::Draw(cmd) => write!(hasher, "{cmd:?}"),
+					Command::Compute(cmd) => write!(hasher, "{cmd:?}"),
+					Command::UploadImage(cmd) => write!(hasher, "{cmd:?}"),
+					Command::DebugMessage{label} => write!(hasher, "DebugMessage {label}"),
+					Command::PushDebugGroup{label} => write!(hasher, "PushDebugGroup {label}"),
+					Command::PopDebugGroup => write!(hasher, "PopDebugGroup"),
+					Command::Callback(_) => write!(hasher, "Callback"),
+					Command::ClearBuffer | Command::ClearTexture
+						| Command::CopyBuffer | Command::CopyTexture => write!(hasher, "Unimplemented"),
+				}.unwrap();
+			}
+		}
+
+		self.last_command_hash = Some(hasher.finish());
+	}
+
+	pub(crate) fn write_pending_frame_dump(&mut self, vfs: &toybox_vfs::Vfs) {
+		let Some(virtual_path) = self.pending_frame_dump.take() else { return };
+
+		let dump = self.format_frame_dump();
+
+		match vfs.save_data(toybox_vfs::PathKind::UserData, &virtual_path, dump) {
+			Ok(()) => log::info!("Frame dumped to '{}'", virtual_path.display()),
+			Err(error) => log::error!("Failed to dump frame to '{}': {error}", virtual_path.display()),
+		}
+	}
+
+	fn format_frame_dump(&self) -> String {
+		let mut dump = String::new();
+
+		writeln!(dump, "backbuffer size: {:?}", self.core.backbuffer_size()).unwrap();
+		writeln!(dump, "clear color: {:?}", self.frame_encoder.backbuffer_clear_color).unwrap();
+
+		for command_group in self.frame_encoder.command_groups.iter() {
+			let disabled = !self.frame_encoder.is_stage_enabled(command_group.stage);
+
+			writeln!(dump).unwrap();
+			writeln!(dump, "== {:?}{} ==", command_group.stage, if disabled { " (disabled)" } else { "" }).unwrap();
+
+			for (attachment, ops) in command_group.attachment_ops.iter() {
+				writeln!(dump, "{attachment:?}: load {:?}, store {:?}", ops.load, ops.store).unwrap();
+			}
+
+			for (index, command) in command_group.commands.iter().enumerate() {
+				match command {
+					Command::Draw(cmd) => writeln!(dump, "[{index}] {cmd:#?}"),
+					Command::Compute(cmd) => writeln!(dump, "[{index}] {cmd:#?}"),
+					Command::UploadImage(cmd) => writeln!(dump, "[{index}] {cmd:#?}"),
+					Command::DebugMessage{label} => writeln!(dump, "[{index}] DebugMessage '{label}'"),
+					Command::PushDebugGroup{label} => writeln!(dump, "[{index}] PushDebugGroup '{label}'"),
+					Command::PopDebugGroup => writeln!(dump, "[{index}] PopDebugGroup"),
+					Command::Callback(_) => writeln!(dump, "[{index}] Callback"),
+					Command::ClearBuffer | Command::ClearTexture
+						| Command::CopyBuffer | Command::CopyTexture => writeln!(dump, "[{index}] Unimplemented command"),
+				}.unwrap();
+			}
+		}
+
+		dump
+	}
+}
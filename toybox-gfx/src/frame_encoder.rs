@@ -4,6 +4,9 @@ use crate::core::Core;
 use crate::upload_heap::{UploadStage, StagedUploadId};
 use crate::bindings::*;
 use crate::arguments::*;
+use crate::thread_encoder::ThreadEncoder;
+
+use std::collections::{HashSet, BTreeSet};
 
 
 
@@ -15,6 +18,21 @@ pub struct FrameEncoder {
 	pub upload_stage: UploadStage,
 
 	pub global_bindings: BindingDescription,
+
+	/// Stages and annotated groups that will be skipped at dispatch. For debugging.
+	pub(crate) disabled_stages: HashSet<FrameStage>,
+	pub(crate) disabled_annotations: HashSet<String>,
+
+	/// Stages and annotated groups whose conditions weren't met this frame - e.g., because of quality settings.
+	/// Kept separate from the debug toggles so that neither overrides the other.
+	pub(crate) unmet_stage_conditions: HashSet<FrameStage>,
+	pub(crate) unmet_annotation_conditions: HashSet<String>,
+
+	/// Every annotation label seen at dispatch so far, so they can be toggled from a debug ui.
+	pub(crate) known_annotations: BTreeSet<String>,
+
+	pub(crate) next_thread_submission_index: u32,
+	pub(crate) pending_thread_encoders: Vec<ThreadEncoder>,
 }
 
 impl FrameEncoder {
@@ -25,6 +43,15 @@ impl FrameEncoder {
 
 			upload_stage: UploadStage::new(),
 			global_bindings: BindingDescription::new(),
+
+			disabled_stages: HashSet::new(),
+			disabled_annotations: HashSet::new(),
+			unmet_stage_conditions: HashSet::new(),
+			unmet_annotation_conditions: HashSet::new(),
+			known_annotations: BTreeSet::new(),
+
+			next_thread_submission_index: 0,
+			pending_thread_encoders: Vec::new(),
 		}
 	}
 
@@ -40,6 +67,13 @@ impl FrameEncoder {
 
 		self.global_bindings.clear();
 		self.upload_stage.reset();
+
+		if !self.pending_thread_encoders.is_empty() {
+			log::warn!("{} thread encoders were merged after the frame was executed - dropping", self.pending_thread_encoders.len());
+			self.pending_thread_encoders.clear();
+		}
+
+		self.next_thread_submission_index = 0;
 	}
 }
 
@@ -76,6 +110,79 @@ impl FrameEncoder {
 	}
 }
 
+/// Debug toggles. Disabled stages and annotated groups are still encoded and uploaded, but never dispatched.
+impl FrameEncoder {
+	pub fn set_stage_enabled(&mut self, stage: FrameStage, enabled: bool) {
+		if enabled {
+			self.disabled_stages.remove(&stage);
+		} else {
+			self.disabled_stages.insert(stage);
+		}
+	}
+
+	pub fn is_stage_enabled(&self, stage: FrameStage) -> bool {
+		!self.disabled_stages.contains(&stage)
+	}
+
+	/// Enables or disables any group created with [`CommandGroupEncoder::annotate`] with a matching label.
+	pub fn set_annotation_enabled(&mut self, label: &str, enabled: bool) {
+		if enabled {
+			self.disabled_annotations.remove(label);
+		} else {
+			self.disabled_annotations.insert(label.to_owned());
+		}
+	}
+
+	pub fn is_annotation_enabled(&self, label: &str) -> bool {
+		!self.disabled_annotations.contains(label)
+	}
+
+	/// All stages that have been encoded to at least once.
+	pub fn known_stages(&self) -> impl Iterator<Item=FrameStage> + '_ {
+		self.command_groups.iter().map(|group| group.stage)
+	}
+
+	/// All annotation labels that have been dispatched at least once.
+	pub fn known_annotations(&self) -> impl Iterator<Item=&str> + '_ {
+		self.known_annotations.iter().map(String::as_str)
+	}
+}
+
+/// Conditional dispatch. Like the debug toggles, groups whose conditions aren't met are still encoded, but never
+/// dispatched - so submission sites don't need to check quality settings themselves.
+impl FrameEncoder {
+	pub fn set_stage_condition(&mut self, stage: FrameStage, met: bool) {
+		if met {
+			self.unmet_stage_conditions.remove(&stage);
+		} else {
+			self.unmet_stage_conditions.insert(stage);
+		}
+	}
+
+	/// Applies to any group created with [`CommandGroupEncoder::annotate`] with a matching label.
+	pub fn set_annotation_condition(&mut self, label: &str, met: bool) {
+		if met {
+			self.unmet_annotation_conditions.remove(label);
+		} else {
+			self.unmet_annotation_conditions.insert(label.to_owned());
+		}
+	}
+
+	pub fn clear_conditions(&mut self) {
+		self.unmet_stage_conditions.clear();
+		self.unmet_annotation_conditions.clear();
+	}
+
+	/// Whether `stage` will be dispatched this frame, taking both debug toggles and conditions into account.
+	pub fn will_dispatch_stage(&self, stage: FrameStage) -> bool {
+		!self.disabled_stages.contains(&stage) && !self.unmet_stage_conditions.contains(&stage)
+	}
+
+	pub fn will_dispatch_annotation(&self, label: &str) -> bool {
+		!self.disabled_annotations.contains(label) && !self.unmet_annotation_conditions.contains(label)
+	}
+}
+
 /// Global per-frame bindings.
 impl FrameEncoder {
 	pub fn bind_global_buffer(&mut self, target: impl Into<BufferBindTarget>, buffer: impl IntoBufferArgument) {
@@ -0,0 +1,212 @@
+//! Generating GLSL declarations from Rust types, so that uniform and storage blocks don't drift out of sync with the
+//! structs used to fill them. Usually used via `#[derive(GlslStruct)]`.
+//!
+//! ```ignore
+//! #[derive(Copy, Clone, GlslStruct)]
+//! #[repr(C)]
+//! struct Light {
+//! 	position: Vec4,
+//! 	color: Vec4,
+//! }
+//!
+//! gfx.resource_manager.shader_imports.register_struct::<Light>();
+//! ```
+//!
+//! Shaders can then `#import Light` to get the matching struct declaration.
+
+use std::collections::HashMap;
+
+pub use toybox_gfx_derive::GlslStruct;
+
+
+/// Types that can appear as fields in a [`GlslStruct`].
+pub trait GlslType {
+	fn glsl_type_name() -> String;
+
+	/// Alignment and size under std140 rules.
+	fn std140_align() -> usize;
+	fn std140_size() -> usize;
+
+	/// Structs this type depends on, in declaration order.
+	fn collect_struct_declarations(_: &mut Vec<String>) {}
+
+	/// e.g., `[4]` for arrays.
+	fn glsl_array_suffix() -> String { String::new() }
+
+	/// Type name for arrays of this type - only differs for arrays of arrays.
+	fn glsl_element_type_name() -> String { Self::glsl_type_name() }
+}
+
+#[derive(Debug, Clone)]
+pub struct GlslField {
+	pub name: &'static str,
+	pub type_name: String,
+	pub array_suffix: String,
+	pub std140_align: usize,
+	pub std140_size: usize,
+	pub rust_offset: usize,
+}
+
+/// Implemented by `#[derive(GlslStruct)]`.
+pub trait GlslStruct: GlslType {
+	fn glsl_fields() -> Vec<GlslField>;
+
+	/// `struct Name { ... };`
+	fn glsl_struct_declaration() -> String {
+		let mut declaration = format!("struct {} {{\n", Self::glsl_type_name());
+		for field in Self::glsl_fields() {
+			declaration += &format!("\t{} {}{};\n", field.type_name, field.name, field.array_suffix);
+		}
+		declaration += "};\n";
+		declaration
+	}
+
+	/// This struct and everything it depends on.
+	fn glsl_declarations() -> String {
+		let mut declarations = Vec::new();
+		Self::collect_struct_declarations(&mut declarations);
+		declarations.concat()
+	}
+
+	/// `layout(binding=N) uniform BlockName { Name instance_name; };`, along with any struct declarations.
+	fn glsl_ubo_declaration(binding: u32, instance_name: &str) -> String {
+		let type_name = Self::glsl_type_name();
+		format!("{}layout(binding={binding}) uniform {type_name}Block {{ {type_name} {instance_name}; }};\n",
+			Self::glsl_declarations())
+	}
+
+	/// Fields whose offsets in the Rust struct don't match where std140 would place them.
+	fn std140_layout_mismatches() -> Vec<String> {
+		let mut offset = 0;
+		let mut mismatches = Vec::new();
+
+		for field in Self::glsl_fields() {
+			offset = offset.next_multiple_of(field.std140_align);
+
+			if offset != field.rust_offset {
+				mismatches.push(format!("{}::{} is at offset {} but std140 expects {offset}",
+					Self::glsl_type_name(), field.name, field.rust_offset));
+			}
+
+			offset += field.std140_size;
+		}
+
+		mismatches
+	}
+}
+
+
+/// Alignment and size of a std140 struct given its fields.
+pub fn std140_struct_layout(fields: &[GlslField]) -> (usize, usize) {
+	let align = fields.iter()
+		.map(|field| field.std140_align)
+		.max().unwrap_or(4)
+		.next_multiple_of(16);
+
+	let size = fields.iter()
+		.fold(0, |offset, field| offset.next_multiple_of(field.std140_align) + field.std140_size)
+		.next_multiple_of(align);
+
+	(align, size)
+}
+
+
+macro_rules! impl_glsl_type {
+	($ty:ty, $name:literal, $align:literal, $size:literal) => {
+		impl GlslType for $ty {
+			fn glsl_type_name() -> String { $name.into() }
+			fn std140_align() -> usize { $align }
+			fn std140_size() -> usize { $size }
+		}
+	};
+}
+
+impl_glsl_type!(f32, "float", 4, 4);
+impl_glsl_type!(i32, "int", 4, 4);
+impl_glsl_type!(u32, "uint", 4, 4);
+
+impl_glsl_type!(common::Vec2, "vec2", 8, 8);
+impl_glsl_type!(common::Vec3, "vec3", 16, 12);
+impl_glsl_type!(common::Vec4, "vec4", 16, 16);
+impl_glsl_type!(common::Color, "vec4", 16, 16);
+
+impl_glsl_type!(common::Vec2i, "ivec2", 8, 8);
+impl_glsl_type!(common::Vec3i, "ivec3", 16, 12);
+
+impl_glsl_type!(common::Mat4, "mat4", 16, 64);
+
+
+impl<T: GlslType, const N: usize> GlslType for [T; N] {
+	fn glsl_type_name() -> String { format!("{}{}", T::glsl_element_type_name(), Self::glsl_array_suffix()) }
+	fn glsl_element_type_name() -> String { T::glsl_element_type_name() }
+	fn glsl_array_suffix() -> String { format!("[{N}]{}", T::glsl_array_suffix()) }
+
+	// Array elements are always padded out to vec4 alignment in std140.
+	fn std140_align() -> usize { T::std140_align().next_multiple_of(16) }
+	fn std140_size() -> usize { N * T::std140_size().next_multiple_of(Self::std140_align()) }
+
+	fn collect_struct_declarations(out: &mut Vec<String>) {
+		T::collect_struct_declarations(out);
+	}
+}
+
+
+
+/// Named snippets of GLSL that shaders can pull in with `#import <name>`.
+#[derive(Debug, Default)]
+pub struct ShaderImports {
+	imports: HashMap<String, String>,
+}
+
+impl ShaderImports {
+	/// Only affects shaders compiled after registration.
+	pub fn register(&mut self, name: impl Into<String>, source: impl Into<String>) {
+		self.imports.insert(name.into(), source.into());
+	}
+
+	/// Register the declaration of `T` and all structs it depends on under its GLSL type name.
+	pub fn register_struct<T: GlslStruct>(&mut self) {
+		if let Some(mismatch) = T::std140_layout_mismatches().first() {
+			log::warn!("Registering shader import for struct with mismatched layout: {mismatch}");
+		}
+
+		self.register(T::glsl_type_name(), T::glsl_declarations());
+	}
+
+	/// Replace `#import <name>` lines with registered snippets. Each import is only included once.
+	/// Imports get their own source string numbers in error messages, from 2 up in the order they're first imported -
+	/// 1 is the shader itself.
+	pub fn resolve(&self, source: &str) -> anyhow::Result<String> {
+		if !source.contains("#import") {
+			return Ok(source.to_owned())
+		}
+
+		let mut included = Vec::new();
+		let mut output = String::with_capacity(source.len());
+
+		for (line_index, line) in source.lines().enumerate() {
+			let Some(name) = line.trim().strip_prefix("#import") else {
+				output += line;
+				output += "\n";
+				continue
+			};
+
+			let name = name.trim();
+			let snippet = self.imports.get(name)
+				.ok_or_else(|| anyhow::format_err!("Unknown shader import '{name}' on line {}", line_index + 1))?;
+
+			if !included.contains(&name) {
+				included.push(name);
+
+				let source_id = included.len() + 1;
+				output += &format!("// #import {name} - source string {source_id}\n#line 1 {source_id}\n");
+				output += snippet;
+			}
+
+			// Keep line numbers in error messages pointing at the original source. #line sets the number of the next line.
+			output += &format!("\n#line {} 1\n", line_index + 2);
+		}
+
+		Ok(output)
+	}
+}
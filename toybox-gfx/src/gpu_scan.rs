@@ -0,0 +1,182 @@
+use crate::prelude::*;
+use crate::{
+	System, Core, ResourceManager, FrameStage, ShaderHandle, BufferName, CommandGroupEncoder,
+	shaders,
+};
+
+
+const BLOCK_SIZE: u32 = 256;
+
+/// Each level is dispatched as one workgroup per block, so this is limited by the minimum guaranteed workgroup count.
+pub const MAX_SCAN_COUNT: u32 = 65535 * BLOCK_SIZE;
+
+
+/// Exclusive prefix sum of u32s in an SSBO, in place. Handles any length up to [`MAX_SCAN_COUNT`] by scanning
+/// blocks, recursively scanning the block totals, then adding them back.
+///
+/// To also get the total, scan one extra trailing zero - it'll be replaced with the sum of everything before it.
+pub struct GpuPrefixSum {
+	block_shader: ShaderHandle,
+	add_shader: ShaderHandle,
+
+	scratch: Option<ScanScratch>,
+}
+
+impl GpuPrefixSum {
+	pub fn new(gfx: &mut System) -> GpuPrefixSum {
+		GpuPrefixSum::with_resource_manager(&mut gfx.resource_manager)
+	}
+
+	/// For use without a [`System`], e.g., with a [`ComputeRunner`](crate::ComputeRunner).
+	pub fn with_resource_manager(rm: &mut ResourceManager) -> GpuPrefixSum {
+		GpuPrefixSum {
+			block_shader: rm.compile_compute_shader("prefix sum block cs", shaders::PREFIX_SUM_BLOCK_CS_SHADER_SOURCE),
+			add_shader: rm.compile_compute_shader("prefix sum add cs", shaders::PREFIX_SUM_ADD_CS_SHADER_SOURCE),
+			scratch: None,
+		}
+	}
+
+	/// Replace the first `count` u32s in `data` with their exclusive prefix sum.
+	/// Encoded into the command group for `stage`, so anything reading `data` later in the frame sees the result.
+	pub fn scan(&mut self, gfx: &mut System, stage: FrameStage, data: BufferName, count: u32) {
+		assert!(count <= MAX_SCAN_COUNT, "Trying to scan {count} values, but GpuPrefixSum can only scan up to {MAX_SCAN_COUNT}");
+
+		if count == 0 {
+			return
+		}
+
+		self.reserve(gfx, count);
+
+		let mut group = gfx.frame_encoder.command_group(stage)
+			.annotate("Prefix Sum");
+
+		self.encode(&mut group, data, count);
+	}
+
+	/// Like [`GpuPrefixSum::scan`], but encoded into an existing command group - e.g., from
+	/// [`ComputeRunner::encode`](crate::ComputeRunner::encode).
+	/// If scratch buffers need to grow, the old ones are destroyed at this point in `group` - so within a frame, prefer
+	/// [`GpuPrefixSum::scan`] or call [`GpuPrefixSum::reserve`] up front.
+	pub fn scan_in(&mut self, core: &Core, group: &mut CommandGroupEncoder<'_>, data: BufferName, count: u32) {
+		assert!(count <= MAX_SCAN_COUNT, "Trying to scan {count} values, but GpuPrefixSum can only scan up to {MAX_SCAN_COUNT}");
+
+		if count == 0 {
+			return
+		}
+
+		self.reserve_in(core, group, count);
+		self.encode(group, data, count);
+	}
+
+	/// Like [`GpuPrefixSum::reserve`], but old scratch buffers are destroyed at this point in `group`.
+	pub fn reserve_in(&mut self, core: &Core, group: &mut CommandGroupEncoder<'_>, count: u32) {
+		if let Some(old_scratch) = self.grow_scratch(core, count) {
+			group.execute(move |core, _| old_scratch.destroy(core));
+		}
+	}
+
+	/// Scratch must already be big enough for `count`.
+	pub(crate) fn encode(&self, group: &mut CommandGroupEncoder<'_>, data: BufferName, count: u32) {
+		let scratch = self.scratch.as_ref().unwrap();
+
+		let level_counts = level_counts(count);
+
+		// Level 0 is `data`, and each following level is the block totals of the level before.
+		let level_buffers: Vec<_> = std::iter::once(data)
+			.chain(scratch.block_sums.iter().copied())
+			.collect();
+
+		for (level, &level_count) in level_counts.iter().enumerate() {
+			group.compute(self.block_shader)
+				.groups(Vec3i::new(level_count.div_ceil(BLOCK_SIZE) as i32, 1, 1))
+				.ubo(0, &[level_count])
+				.ssbo(0, level_buffers[level])
+				.ssbo(1, level_buffers[level + 1]);
+		}
+
+		// The last level is a single block, so is already complete.
+		for (level, &level_count) in level_counts.iter().enumerate().rev().skip(1) {
+			group.compute(self.add_shader)
+				.groups(Vec3i::new(level_count.div_ceil(BLOCK_SIZE) as i32, 1, 1))
+				.ubo(0, &[level_count])
+				.ssbo(0, level_buffers[level])
+				.ssbo(1, level_buffers[level + 1]);
+		}
+	}
+
+	/// Make sure scratch buffers are big enough to scan `count` values without reallocating.
+	pub fn reserve(&mut self, gfx: &mut System, count: u32) {
+		if let Some(old_scratch) = self.grow_scratch(&gfx.core, count) {
+			// Commands using the old buffers may already have been encoded this frame.
+			gfx.frame_encoder.command_group(FrameStage::Final)
+				.annotate("Prefix Sum Cleanup")
+				.execute(move |core, _| old_scratch.destroy(core));
+		}
+	}
+
+	/// Returns the old scratch buffers if they were too small, for the caller to destroy once nothing uses them.
+	fn grow_scratch(&mut self, core: &Core, count: u32) -> Option<ScanScratch> {
+		if self.scratch.as_ref().is_some_and(|scratch| scratch.capacity >= count) {
+			return None
+		}
+
+		let capacity = count.next_power_of_two().min(MAX_SCAN_COUNT);
+		self.scratch.replace(ScanScratch::new(core, capacity))
+	}
+}
+
+
+/// CPU equivalent of [`GpuPrefixSum::scan`].
+pub fn exclusive_prefix_sum(values: &[u32]) -> Vec<u32> {
+	values.iter()
+		.scan(0u32, |sum, &value| {
+			let result = *sum;
+			*sum = sum.wrapping_add(value);
+			Some(result)
+		})
+		.collect()
+}
+
+
+/// Number of values scanned at each level, down to the level that fits in a single block.
+fn level_counts(count: u32) -> Vec<u32> {
+	let mut counts = vec![count];
+
+	while let Some(&last) = counts.last() && last > BLOCK_SIZE {
+		counts.push(last.div_ceil(BLOCK_SIZE));
+	}
+
+	counts
+}
+
+
+struct ScanScratch {
+	capacity: u32,
+
+	/// Block totals for each level, including one for the final single block level.
+	block_sums: Vec<BufferName>,
+}
+
+impl ScanScratch {
+	fn new(core: &crate::Core, capacity: u32) -> ScanScratch {
+		let block_sums = level_counts(capacity).into_iter()
+			.enumerate()
+			.map(|(level, level_count)| {
+				let size = level_count.div_ceil(BLOCK_SIZE) as usize * 4;
+
+				let name = core.create_buffer();
+				core.allocate_buffer_storage(name, size, 0);
+				core.set_debug_label(name, &format!("Prefix Sum Block Sums {level}"));
+				name
+			})
+			.collect();
+
+		ScanScratch { capacity, block_sums }
+	}
+
+	fn destroy(&self, core: &crate::Core) {
+		for &name in self.block_sums.iter() {
+			core.destroy_buffer(name);
+		}
+	}
+}
@@ -0,0 +1,231 @@
+use crate::prelude::*;
+use crate::{
+	System, Core, ResourceManager, FrameStage, ShaderHandle, BufferName, CommandGroupEncoder,
+	gpu_scan::GpuPrefixSum,
+	shaders,
+};
+
+
+const BLOCK_SIZE: u32 = 256;
+const NUM_BUCKETS: u32 = 16;
+const BITS_PER_PASS: u32 = 4;
+
+/// Dispatches are one workgroup per block, so this is limited by the minimum guaranteed workgroup count.
+pub const MAX_SORT_COUNT: u32 = 65535 * BLOCK_SIZE;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SortKeyType {
+	U32,
+
+	/// Pairs of u32s - low word first - e.g., a `uvec2` in glsl.
+	U64,
+}
+
+impl SortKeyType {
+	pub fn size(&self) -> usize {
+		match self {
+			SortKeyType::U32 => 4,
+			SortKeyType::U64 => 8,
+		}
+	}
+
+	pub fn bits(&self) -> u32 {
+		self.size() as u32 * 8
+	}
+}
+
+
+/// Stable GPU radix sort of keys in an SSBO, optionally carrying a u32 value per key along with it - e.g.,
+/// an index into some other buffer. Sorts 4 bits per pass, so 8 passes for u32 keys and 16 for u64 keys,
+/// fewer if [`GpuRadixSort::key_bits`] is reduced.
+///
+/// Keys and values are sorted in place, ping-ponging through internal scratch buffers which grow to fit.
+pub struct GpuRadixSort {
+	key_type: SortKeyType,
+
+	/// Only the lowest `key_bits` of each key are considered, which saves passes if keys are known to be small,
+	/// e.g., quantised depth.
+	pub key_bits: u32,
+
+	count_shader: ShaderHandle,
+	scatter_shader: ShaderHandle,
+	prefix_sum: GpuPrefixSum,
+
+	scratch: Option<SortScratch>,
+}
+
+impl GpuRadixSort {
+	pub fn new(gfx: &mut System, key_type: SortKeyType) -> GpuRadixSort {
+		GpuRadixSort::with_resource_manager(&mut gfx.resource_manager, key_type)
+	}
+
+	/// For use without a [`System`], e.g., with a [`ComputeRunner`](crate::ComputeRunner).
+	pub fn with_resource_manager(rm: &mut ResourceManager, key_type: SortKeyType) -> GpuRadixSort {
+		let prefix = match key_type {
+			SortKeyType::U32 => "",
+			SortKeyType::U64 => "#define RADIX_SORT_KEY_64\n",
+		};
+
+		let source = |pass_source: &str| format!("{prefix}{}{pass_source}", shaders::RADIX_SORT_GLSL_SOURCE);
+
+		let count_shader = rm.compile_compute_shader("radix sort count cs", source(shaders::RADIX_SORT_COUNT_CS_SHADER_SOURCE));
+		let scatter_shader = rm.compile_compute_shader("radix sort scatter cs", source(shaders::RADIX_SORT_SCATTER_CS_SHADER_SOURCE));
+
+		GpuRadixSort {
+			key_type,
+			key_bits: key_type.bits(),
+
+			count_shader,
+			scatter_shader,
+			prefix_sum: GpuPrefixSum::with_resource_manager(rm),
+
+			scratch: None,
+		}
+	}
+
+	pub fn key_type(&self) -> SortKeyType {
+		self.key_type
+	}
+
+	/// Sort the first `count` keys in `keys` in ascending order, permuting the first `count` u32s in `values` to match.
+	/// Encoded into the command group for `stage`, so anything reading `keys` or `values` later in the frame sees
+	/// sorted results.
+	pub fn sort(&mut self, gfx: &mut System, stage: FrameStage, keys: BufferName, values: Option<BufferName>, count: u32) {
+		assert!(count <= MAX_SORT_COUNT, "Trying to sort {count} keys, but GpuRadixSort can only sort up to {MAX_SORT_COUNT}");
+
+		if count == 0 {
+			return
+		}
+
+		self.reserve(gfx, count);
+
+		let mut group = gfx.frame_encoder.command_group(stage)
+			.annotate("Radix Sort");
+
+		self.encode(&mut group, keys, values, count);
+	}
+
+	/// Like [`GpuRadixSort::sort`], but encoded into an existing command group - e.g., from
+	/// [`ComputeRunner::encode`](crate::ComputeRunner::encode).
+	/// If scratch buffers need to grow, the old ones are destroyed at this point in `group` - so within a frame, prefer
+	/// [`GpuRadixSort::sort`] or call [`GpuRadixSort::reserve`] up front.
+	pub fn sort_in(&mut self, core: &Core, group: &mut CommandGroupEncoder<'_>, keys: BufferName, values: Option<BufferName>, count: u32) {
+		assert!(count <= MAX_SORT_COUNT, "Trying to sort {count} keys, but GpuRadixSort can only sort up to {MAX_SORT_COUNT}");
+
+		if count == 0 {
+			return
+		}
+
+		if let Some(old_scratch) = self.grow_scratch(core, count) {
+			group.execute(move |core, _| old_scratch.destroy(core));
+		}
+
+		self.prefix_sum.reserve_in(core, group, num_histogram_values(count));
+
+		self.encode(group, keys, values, count);
+	}
+
+	/// Scratch must already be big enough for `count`.
+	fn encode(&self, group: &mut CommandGroupEncoder<'_>, keys: BufferName, values: Option<BufferName>, count: u32) {
+		let scratch = self.scratch.as_ref().unwrap();
+
+		let num_blocks = count.div_ceil(BLOCK_SIZE);
+
+		// Always an even number of passes, so that results end up back in the source buffers.
+		let num_passes = self.key_bits.min(self.key_type.bits()).div_ceil(BITS_PER_PASS).next_multiple_of(2);
+
+		for pass in 0..num_passes {
+			let (keys_in, keys_out, values_in, values_out) = match pass % 2 {
+				0 => (keys, scratch.keys, values, Some(scratch.values)),
+				_ => (scratch.keys, keys, Some(scratch.values), values),
+			};
+
+			let has_values = values.is_some() as u32;
+			let params = [count, pass * BITS_PER_PASS, num_blocks, has_values];
+
+			group.compute(self.count_shader)
+				.groups(Vec3i::new(num_blocks as i32, 1, 1))
+				.ubo(0, &params)
+				.ssbo(0, keys_in)
+				.ssbo(1, scratch.histograms);
+
+			self.prefix_sum.encode(group, scratch.histograms, num_histogram_values(count));
+
+			let mut scatter = group.compute(self.scatter_shader);
+			scatter.groups(Vec3i::new(num_blocks as i32, 1, 1))
+				.ubo(0, &params)
+				.ssbo(0, keys_in)
+				.ssbo(1, scratch.histograms)
+				.ssbo(2, keys_out);
+
+			// Values are only touched if has_values is set, so can be left unbound otherwise.
+			if let (Some(values_in), Some(values_out)) = (values_in, values_out) {
+				scatter.ssbo(3, values_in)
+					.ssbo(4, values_out);
+			}
+		}
+	}
+
+	/// Make sure scratch buffers are big enough to sort `count` keys without reallocating.
+	pub fn reserve(&mut self, gfx: &mut System, count: u32) {
+		if let Some(old_scratch) = self.grow_scratch(&gfx.core, count) {
+			// Commands using the old buffers may already have been encoded this frame.
+			gfx.frame_encoder.command_group(FrameStage::Final)
+				.annotate("Radix Sort Cleanup")
+				.execute(move |core, _| old_scratch.destroy(core));
+		}
+
+		self.prefix_sum.reserve(gfx, num_histogram_values(count));
+	}
+
+	/// Returns the old scratch buffers if they were too small, for the caller to destroy once nothing uses them.
+	fn grow_scratch(&mut self, core: &Core, count: u32) -> Option<SortScratch> {
+		if self.scratch.as_ref().is_some_and(|scratch| scratch.capacity >= count) {
+			return None
+		}
+
+		let capacity = count.next_power_of_two().min(MAX_SORT_COUNT);
+		self.scratch.replace(SortScratch::new(core, self.key_type, capacity))
+	}
+}
+
+
+/// Per-block bucket counts are scanned together to find where each block scatters each bucket.
+fn num_histogram_values(count: u32) -> u32 {
+	count.div_ceil(BLOCK_SIZE) * NUM_BUCKETS
+}
+
+
+struct SortScratch {
+	capacity: u32,
+	keys: BufferName,
+	values: BufferName,
+	histograms: BufferName,
+}
+
+impl SortScratch {
+	fn new(core: &crate::Core, key_type: SortKeyType, capacity: u32) -> SortScratch {
+		let create = |label: &str, size: usize| {
+			let name = core.create_buffer();
+			core.allocate_buffer_storage(name, size, 0);
+			core.set_debug_label(name, label);
+			name
+		};
+
+		let num_blocks = capacity.div_ceil(BLOCK_SIZE) as usize;
+
+		SortScratch {
+			capacity,
+			keys: create("Radix Sort Scratch Keys", capacity as usize * key_type.size()),
+			values: create("Radix Sort Scratch Values", capacity as usize * 4),
+			histograms: create("Radix Sort Histograms", num_blocks * NUM_BUCKETS as usize * 4),
+		}
+	}
+
+	fn destroy(&self, core: &crate::Core) {
+		core.destroy_buffer(self.keys);
+		core.destroy_buffer(self.values);
+		core.destroy_buffer(self.histograms);
+	}
+}
@@ -0,0 +1,254 @@
+use crate::prelude::*;
+use crate::{
+	System, FrameStage, CommandGroupEncoder, AnnotatedCommandGroupEncoder, ImageHandle, ImageFormat, ImageClearPolicy,
+	CreateImageRequest, FramebufferDescription, ShaderHandle, CommonSampler, ImageRange,
+	shaders,
+};
+
+
+/// Max number of elevations an impostor can be baked from.
+pub const MAX_IMPOSTOR_PITCHES: usize = 8;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImpostorSettings {
+	/// Width and height of each view in the atlas.
+	pub cell_size: u32,
+
+	/// Number of views around the vertical axis, for each pitch.
+	pub yaw_steps: u32,
+
+	/// Elevations to bake views from, in degrees. 0 is level with the object, 90 is directly above.
+	pub pitches: Vec<f32>,
+}
+
+impl Default for ImpostorSettings {
+	fn default() -> Self {
+		ImpostorSettings {
+			cell_size: 128,
+			yaw_steps: 8,
+			pitches: vec![0.0, 35.0],
+		}
+	}
+}
+
+
+/// A mesh pre-rendered from a set of view angles into a layered atlas, for drawing distant objects as billboards with
+/// [`ImpostorRenderer`]. Rendering works like [`crate::TextureCamera`], with one view per atlas layer.
+///
+/// ```ignore
+/// let impostor = Impostor::new(gfx, "tree impostor", ImpostorSettings::default(), bounds_center, bounds_radius);
+///
+/// // Once, e.g., after loading.
+/// impostor.bake(gfx, -10, |group| {
+///     group.draw(CommonShader::StandardVertex, CommonShader::FlatTexturedFragment)
+///         .ssbo(0, &tree_vertices)
+///         .elements(tree_vertices.len() as u32);
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Impostor {
+	settings: ImpostorSettings,
+
+	/// Bounding sphere of the baked mesh, relative to its origin.
+	center: Vec3,
+	radius: f32,
+
+	label: String,
+	color_image: ImageHandle,
+	depth_image: ImageHandle,
+}
+
+impl Impostor {
+	/// `center` and `radius` should bound the mesh - anything outside is clipped.
+	/// `label` must be unique per impostor, since image requests are deduplicated.
+	pub fn new(gfx: &mut System, label: impl Into<String>, settings: ImpostorSettings, center: Vec3, radius: f32) -> Impostor {
+		assert!(settings.cell_size > 0 && settings.yaw_steps > 0, "Impostor must have a non-zero cell size and yaw steps");
+		assert!((1..=MAX_IMPOSTOR_PITCHES).contains(&settings.pitches.len()),
+			"Impostor must have between 1 and {MAX_IMPOSTOR_PITCHES} pitches");
+
+		let label = label.into();
+		let size = Vec2i::splat(settings.cell_size as i32);
+		let num_views = settings.yaw_steps * settings.pitches.len() as u32;
+
+		let rm = &mut gfx.resource_manager;
+		let color_image = rm.request(CreateImageRequest::fixed_2d_array(format!("{label} atlas"), size, num_views, ImageFormat::Srgba8));
+		let depth_image = rm.request(CreateImageRequest::fixed_2d_array(format!("{label} depth"), size, num_views, ImageFormat::Depth)
+			.clear_policy(ImageClearPolicy::DefaultAtFrameStart));
+
+		Impostor {
+			settings,
+			center,
+			radius,
+
+			label,
+			color_image,
+			depth_image,
+		}
+	}
+
+	pub fn settings(&self) -> &ImpostorSettings {
+		&self.settings
+	}
+
+	pub fn num_views(&self) -> u32 {
+		self.settings.yaw_steps * self.settings.pitches.len() as u32
+	}
+
+	/// Layered atlas, one view per layer. Layers are ordered by pitch, then yaw.
+	pub fn atlas(&self) -> ImageHandle {
+		self.color_image
+	}
+
+	/// Direction from the object towards the camera for a view.
+	pub fn view_direction(&self, view: u32) -> Vec3 {
+		let yaw_index = view % self.settings.yaw_steps;
+		let pitch_index = view / self.settings.yaw_steps;
+
+		let yaw = yaw_index as f32 / self.settings.yaw_steps as f32 * std::f32::consts::TAU;
+		let pitch = self.settings.pitches[pitch_index as usize].to_radians();
+
+		Vec3::new(yaw.sin() * pitch.cos(), pitch.sin(), yaw.cos() * pitch.cos())
+	}
+
+	/// Standard OpenGL projection view used to bake a view.
+	pub fn view_projection_view(&self, view: u32) -> Mat4 {
+		let direction = self.view_direction(view);
+		let eye = self.center + direction * self.radius * 2.0;
+		let (right, up, forward) = impostor_basis(-direction);
+
+		let view_matrix = Mat4::from_rows([
+			Vec4::new(right.x, right.y, right.z, -right.dot(eye)),
+			Vec4::new(up.x, up.y, up.z, -up.dot(eye)),
+			Vec4::new(-forward.x, -forward.y, -forward.z, forward.dot(eye)),
+			Vec4::new(0.0, 0.0, 0.0, 1.0),
+		]);
+
+		let r = self.radius;
+		Mat4::ortho(-r, r, -r, r, r, 3.0 * r) * view_matrix
+	}
+
+	/// Encode `draw_mesh` once per view, each into its own command group in `FrameStage::BeforeMain(stage)`, with the
+	/// view's layer bound as the rendertarget and its projection view bound to UBO 0, matching
+	/// [`crate::shaders::STANDARD_VS_SHADER_SOURCE`]. Draws should write alpha, since it's used to cut out the billboard.
+	///
+	/// The atlas keeps its contents, so this only needs calling again if the mesh changes.
+	pub fn bake(&self, gfx: &mut System, stage: i8, mut draw_mesh: impl FnMut(&mut AnnotatedCommandGroupEncoder<'_>)) {
+		let cell_size = self.settings.cell_size as i32;
+
+		for view in 0..self.num_views() {
+			let projection_view = gfx.core.apply_depth_convention(self.view_projection_view(view));
+
+			let mut group = gfx.frame_encoder.command_group(FrameStage::BeforeMain(stage))
+				.annotate(format!("{} view {view}", self.label));
+
+			let color_image = self.color_image;
+			group.execute(move |core, rm| {
+				if let Some(image_name) = rm.images.get_name(color_image) {
+					let range = ImageRange {
+						offset: Vec3i::new(0, 0, view as i32),
+						size: Vec3i::new(cell_size, cell_size, 1),
+					};
+
+					core.clear_image_range_with_color(image_name, range, Color::rgba(0.0, 0.0, 0.0, 0.0));
+				}
+			});
+
+			group.bind_rendertargets(FramebufferDescription::from(&[self.color_image, self.depth_image]).with_layer(view));
+			group.bind_shared_ubo(0, &[projection_view]);
+
+			draw_mesh(&mut group);
+		}
+	}
+}
+
+
+/// Right, up and forward vectors for a view looking along `forward`. Matches `impostor.vs.glsl`.
+fn impostor_basis(forward: Vec3) -> (Vec3, Vec3, Vec3) {
+	let up_hint = match forward.y.abs() > 0.999 {
+		true => Vec3::new(0.0, 0.0, 1.0),
+		false => Vec3::new(0.0, 1.0, 0.0),
+	};
+
+	let right = forward.cross(up_hint).normalize();
+	let up = right.cross(forward);
+
+	(right, up, forward)
+}
+
+
+/// Matches `ImpostorBlock` in `impostor.vs.glsl`.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct ImpostorUniforms {
+	projection_view: Mat4,
+	camera_position: Vec4,
+	center_radius: Vec4,
+	params: Vec4,
+	pitches: [Vec4; MAX_IMPOSTOR_PITCHES / 4],
+}
+
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct ImpostorInstance {
+	/// Where the baked mesh's origin would be.
+	pub position: Vec3,
+
+	/// Uniform scale relative to the baked mesh.
+	pub scale: f32,
+}
+
+
+/// Draws instances of an [`Impostor`] as camera facing billboards, each showing the baked view nearest to the
+/// direction it's seen from. Billboards are alpha tested, so can be drawn alongside opaque geometry.
+/// Instances don't rotate - views are picked by world space direction.
+pub struct ImpostorRenderer {
+	vertex_shader: ShaderHandle,
+	fragment_shader: ShaderHandle,
+}
+
+impl ImpostorRenderer {
+	pub fn new(gfx: &mut System) -> ImpostorRenderer {
+		let rm = &mut gfx.resource_manager;
+
+		ImpostorRenderer {
+			vertex_shader: rm.compile_vertex_shader("impostor vs", shaders::IMPOSTOR_VS_SHADER_SOURCE),
+			fragment_shader: rm.compile_fragment_shader("impostor fs", shaders::IMPOSTOR_FS_SHADER_SOURCE),
+		}
+	}
+
+	/// `projection_view` should already be adjusted for the active depth convention, as with other draws.
+	pub fn draw(&self, group: &mut CommandGroupEncoder<'_>, impostor: &Impostor, instances: &[ImpostorInstance],
+		projection_view: Mat4, camera_position: Vec3)
+	{
+		if instances.is_empty() {
+			return
+		}
+
+		let mut packed_pitches = [0.0f32; MAX_IMPOSTOR_PITCHES];
+		for (packed, pitch) in packed_pitches.iter_mut().zip(&impostor.settings.pitches) {
+			*packed = pitch.to_radians();
+		}
+
+		let pitches = [0, 4].map(|start| {
+			let [x, y, z, w] = [0, 1, 2, 3].map(|offset| packed_pitches[start + offset]);
+			Vec4::new(x, y, z, w)
+		});
+
+		let uniforms = ImpostorUniforms {
+			projection_view,
+			camera_position: camera_position.extend(1.0),
+			center_radius: impostor.center.extend(impostor.radius),
+			params: Vec4::new(impostor.settings.yaw_steps as f32, impostor.settings.pitches.len() as f32, 0.0, 0.0),
+			pitches,
+		};
+
+		group.draw(self.vertex_shader, self.fragment_shader)
+			.ubo(0, &[uniforms])
+			.ssbo(0, instances)
+			.sampled_image(0, impostor.color_image, CommonSampler::Linear)
+			.elements(6)
+			.instances(instances.len() as u32);
+	}
+}
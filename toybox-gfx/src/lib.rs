@@ -1,17 +1,37 @@
 #![feature(let_chains)]
 
 use toybox_host as host;
-use anyhow::Context;
 use tracing::instrument;
 
+pub mod auto_exposure;
 pub mod bindings;
+pub mod channel_packing;
+pub mod color;
 pub mod command;
 pub mod command_group;
+pub mod compute_runner;
 pub mod core;
+pub mod culling;
+pub mod environment_capture;
+pub mod fnv;
+pub mod frame_dump;
 pub mod frame_encoder;
+pub mod glsl;
+pub mod gpu_scan;
+pub mod gpu_sort;
+pub mod impostor;
+pub mod low_res;
+pub mod math;
+pub mod multi_view;
 pub mod resource_manager;
 pub mod shaders;
+pub mod shadows;
+pub mod stats;
+pub mod texture_camera;
+pub mod thread_encoder;
 pub mod upload_heap;
+pub mod validation;
+pub mod virtual_texture;
 
 pub use crate::core::*;
 pub use resource_manager::*;
@@ -19,10 +39,33 @@ pub use frame_encoder::*;
 pub use command::PrimitiveType;
 pub use command_group::*;
 pub use shaders::*;
+pub use math::*;
+pub use stats::{FrameStats, StageStats};
+pub use culling::Frustum;
+pub use fnv::{FnvHasher, fnv1a};
+pub use auto_exposure::{AutoExposure, AutoExposureSettings, MeteringMode};
+pub use glsl::{GlslStruct, ShaderImports};
+pub use texture_camera::TextureCamera;
+pub use environment_capture::{EnvironmentCapture, EnvironmentCaptureSettings, EnvironmentCaptureFace};
+pub use thread_encoder::{ThreadEncoder, ThreadGroupEncoder};
+pub use multi_view::MultiView;
+pub use compute_runner::ComputeRunner;
+pub use gpu_scan::GpuPrefixSum;
+pub use gpu_sort::{GpuRadixSort, SortKeyType};
+pub use impostor::{Impostor, ImpostorSettings, ImpostorInstance, ImpostorRenderer};
+pub use color::Oklab;
+pub use validation::{ValidationError, ValidationErrorKind};
+pub use channel_packing::{MaterialChannel, PackingConvention, pack_channels};
+pub use virtual_texture::{VirtualTexture, VirtualTextureSettings, VirtualTextureBacking, PageId};
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub mod prelude {
 	pub use crate::host::gl;
 	pub use crate::{ResourceName, BufferRangeExt};
+	pub use crate::color::ColorExt;
+	pub use crate::math::{Aabb2i, Aabb3i};
 
 	pub use smallvec::SmallVec;
 
@@ -37,6 +80,22 @@ pub struct System {
 	pub core: core::Core,
 	pub resource_manager: resource_manager::ResourceManager,
 	pub frame_encoder: frame_encoder::FrameEncoder,
+
+	low_res_mode: Option<low_res::LowResMode>,
+	scale_factor: f32,
+
+	/// Color and depth images of the latest environment capture for each label.
+	environment_captures: std::collections::HashMap<String, (ImageHandle, ImageHandle)>,
+
+	/// Stats from the most recently executed frame.
+	pub frame_stats: FrameStats,
+
+	pending_frame_dump: Option<std::path::PathBuf>,
+	command_hashing_enabled: bool,
+	last_command_hash: Option<u64>,
+
+	validation_enabled: bool,
+	validation_errors: Vec<ValidationError>,
 }
 
 impl System {
@@ -47,12 +106,26 @@ impl System {
 	pub fn backbuffer_aspect(&self) -> f32 {
 		self.core.backbuffer_size().x as f32 / self.core.backbuffer_size().y as f32
 	}
+
+	/// Physical pixels per logical pixel of the window the backbuffer belongs to. 1.0 on standard density displays,
+	/// and may be fractional - e.g., 1.25 or 1.5 on Wayland or Windows.
+	pub fn scale_factor(&self) -> f32 {
+		self.scale_factor
+	}
+
+	/// Backbuffer size in logical pixels - for sizing ui, text and sprites so they look the same on any display.
+	pub fn logical_backbuffer_size(&self) -> Vec2 {
+		self.core.backbuffer_size().to_vec2() / self.scale_factor
+	}
 }
 
 impl System {
 	#[instrument(skip_all, name="gfxsys System::new")]
 	pub fn new(mut core: core::Core) -> anyhow::Result<Box<System>> {
-		core.register_debug_hook();
+		core.configure_debug_output();
+
+		// Follows the context rather than the build, so release builds can still opt into debug contexts for testing.
+		let validation_enabled = core.has_full_debug_output();
 
 		let resource_manager = resource_manager::ResourceManager::new(&mut core)?;
 		let frame_encoder = frame_encoder::FrameEncoder::new(&mut core);
@@ -69,6 +142,18 @@ impl System {
 			core,
 			resource_manager,
 			frame_encoder,
+
+			low_res_mode: None,
+			scale_factor: 1.0,
+			environment_captures: Default::default(),
+			frame_stats: FrameStats::default(),
+
+			pending_frame_dump: None,
+			command_hashing_enabled: false,
+			last_command_hash: None,
+
+			validation_enabled,
+			validation_errors: Vec::new(),
 		}))
 	}
 
@@ -79,19 +164,45 @@ impl System {
 		}
 	}
 
+	pub fn set_scale_factor(&mut self, scale_factor: f32) {
+		self.scale_factor = scale_factor;
+	}
+
+	/// Resize rendertargets created with [`CreateImageRequest::scaled_rendertarget`] to a fraction of the backbuffer.
+	/// Clamped to 0.25..=2.0. Takes effect at the start of the next frame.
+	pub fn set_render_scale(&mut self, render_scale: f32) {
+		let render_scale = render_scale.clamp(0.25, 2.0);
+
+		if self.core.render_scale() != render_scale {
+			self.core.set_render_scale(render_scale);
+			self.resource_manager.request_resize(self.core.backbuffer_size());
+		}
+	}
+
+	pub fn render_scale(&self) -> f32 {
+		self.core.render_scale()
+	}
+
+	pub fn render_size(&self) -> Vec2i {
+		self.core.render_size()
+	}
+
 	#[instrument(skip_all, name="gfxsys start_frame")]
 	pub fn start_frame(&mut self) {
-		self.core.set_debugging_enabled(true);
-
 		self.resource_manager.start_frame(&mut self.core);
 		self.frame_encoder.start_frame();
 	}
 
 	#[instrument(skip_all, name="gfxsys execute_frame")]
 	pub fn execute_frame(&mut self, vfs: &toybox_vfs::Vfs) {
-		self.resource_manager.process_requests(&mut self.core, vfs)
-			.context("Error while processing resource requests")
-			.unwrap();
+		// Failures are also recorded per request, so one bad asset shouldn't take down the whole app.
+		if let Err(error) = self.resource_manager.process_requests(&mut self.core, vfs) {
+			log::error!("Error while processing resource requests: {error:?}");
+		}
+
+		self.frame_encoder.stitch_thread_encoders();
+
+		self.apply_low_res_mode();
 
 		{
 			let _span = tracing::info_span!("sort command groups").entered();
@@ -102,12 +213,15 @@ impl System {
 		// clearing the framebuffer seems useless for any mildly involved rendering
 
 		let clear_color = self.frame_encoder.backbuffer_clear_color;
-		let clear_depth = 1.0; // 1.0 is the default clear depth for opengl
+		let clear_depth = self.core.far_depth();
 		let clear_stencil = 0;
 
 		{
 			let _span = tracing::info_span!("clear backbuffer").entered();
 			let backbuffer_handle = FramebufferName::backbuffer();
+
+			// Framebuffer clears respect the scissor rect, so make sure it doesn't leak from last frame.
+			self.core.set_scissor_rect(None);
 			self.core.clear_framebuffer_color_buffer(backbuffer_handle, 0, clear_color);
 			self.core.clear_framebuffer_depth_stencil(backbuffer_handle, clear_depth, clear_stencil);
 		}
@@ -119,6 +233,9 @@ impl System {
 		// moving from binding merging to a hierarchical lookup, or to a just-in-time lookup might improve this
 		self.merge_bindings();
 
+		// Hashed before anything is resolved to GL names or heap offsets, which aren't stable between runs.
+		self.update_command_hash();
+
 		self.resolve_named_bind_targets();
 		self.resolve_image_bind_sources();
 
@@ -131,18 +248,17 @@ impl System {
 		// Resolve all staged bind sources to concrete names and ranges
 		self.resolve_staged_bind_sources();
 
+		self.write_pending_frame_dump(vfs);
+
+		self.validate_commands();
+
 		// Dispatch commands to GPU
 		self.dispatch_commands();
 
         self.resource_manager.upload_heap.create_end_frame_fence(&mut self.core);
 
 		self.frame_encoder.end_frame();
-        self.resource_manager.upload_heap.reset();
-
-		// HACK: For some reason capturing the app with discord (and I suspect other window capture apps)
-		// causes swap_buffers to emit GL_INVALID_ENUM on my machine, which panics ofc.
-		// So for now, we are just not emitting errors pls.
-		self.core.set_debugging_enabled(false);
+        self.resource_manager.upload_heap.reset(&mut self.core);
 	}
 
 	#[instrument(skip_all, name="gfxsys resolve_named_bind_targets")]
@@ -220,22 +336,68 @@ impl System {
 
 		let core = &mut self.core;
 		let resource_manager = &mut self.resource_manager;
+		let frame_stats = &mut self.frame_stats;
+
+		frame_stats.clear();
+
+		let disabled_stages = &self.frame_encoder.disabled_stages;
+		let disabled_annotations = &self.frame_encoder.disabled_annotations;
+		let unmet_stage_conditions = &self.frame_encoder.unmet_stage_conditions;
+		let unmet_annotation_conditions = &self.frame_encoder.unmet_annotation_conditions;
+		let known_annotations = &mut self.frame_encoder.known_annotations;
 
 		for command_group in self.frame_encoder.command_groups.iter_mut() {
 			if command_group.commands.is_empty() {
 				continue
 			}
 
+			if disabled_stages.contains(&command_group.stage) || unmet_stage_conditions.contains(&command_group.stage) {
+				command_group.commands.clear();
+				continue
+			}
+
 			core.push_debug_group(&format!("{:?}", command_group.stage));
 
+			// Shared bindings have been merged with global bindings by now, so the framebuffer is always specified.
+			let attachment_ops_framebuffer = match command_group.attachment_ops.is_empty() {
+				true => None,
+				false => command_group.shared_bindings.framebuffer.as_ref()
+					.map(|framebuffer| framebuffer.resolve_name(core, resource_manager)),
+			};
+
+			if let Some(framebuffer) = attachment_ops_framebuffer {
+				apply_load_ops(core, framebuffer, &command_group.attachment_ops);
+			}
+
+			// Nesting depth of annotated groups within a disabled annotated group.
+			let mut skip_depth = 0;
+
 			for command in command_group.commands.drain(..) {
+				if skip_depth > 0 {
+					match command {
+						PushDebugGroup{..} => skip_depth += 1,
+						PopDebugGroup => skip_depth -= 1,
+						_ => {}
+					}
+
+					continue
+				}
+
 				match command {
 					DebugMessage { label } => {
 						core.debug_marker(&label);
 					}
 
 					PushDebugGroup { label } => {
-						core.push_debug_group(&label);
+						if !known_annotations.contains(&label) {
+							known_annotations.insert(label.clone());
+						}
+
+						if disabled_annotations.contains(&label) || unmet_annotation_conditions.contains(&label) {
+							skip_depth = 1;
+						} else {
+							core.push_debug_group(&label);
+						}
 					}
 
 					PopDebugGroup => {
@@ -244,19 +406,56 @@ impl System {
 
 					Callback(callback) => callback(core, resource_manager),
 
-					Draw(cmd) => cmd.execute(core, resource_manager),
-					Compute(cmd) => cmd.execute(core, resource_manager),
+					Draw(cmd) => {
+						frame_stats.record_draw(command_group.stage, &cmd);
+						cmd.execute(core, resource_manager);
+					}
+
+					Compute(cmd) => {
+						frame_stats.record_dispatch(command_group.stage);
+						cmd.execute(core, resource_manager);
+					}
+
+					UploadImage(cmd) => cmd.execute(core, resource_manager),
 
 					_ => unimplemented!(),
 				}
 			}
 
+			if let Some(framebuffer) = attachment_ops_framebuffer {
+				apply_store_ops(core, framebuffer, &command_group.attachment_ops);
+			}
+
 			core.pop_debug_group();
 		}
 	}
 }
 
 
+fn apply_load_ops(core: &core::Core, framebuffer: Option<FramebufferName>, ops: &[(FramebufferAttachment, AttachmentOps)]) {
+	let mut dont_care = SmallVec::<[FramebufferAttachment; 4]>::new();
+
+	for &(attachment, AttachmentOps{load, ..}) in ops {
+		match load {
+			LoadOp::Load => {}
+			LoadOp::Clear(value) => core.clear_framebuffer_attachment(framebuffer, attachment, value),
+			LoadOp::DontCare => dont_care.push(attachment),
+		}
+	}
+
+	core.invalidate_framebuffer_attachments(framebuffer, &dont_care);
+}
+
+fn apply_store_ops(core: &core::Core, framebuffer: Option<FramebufferName>, ops: &[(FramebufferAttachment, AttachmentOps)]) {
+	let dont_care: SmallVec<[FramebufferAttachment; 4]> = ops.iter()
+		.filter(|(_, ops)| ops.store == StoreOp::DontCare)
+		.map(|(attachment, _)| *attachment)
+		.collect();
+
+	core.invalidate_framebuffer_attachments(framebuffer, &dont_care);
+}
+
+
 
 pub trait AsStageableSlice {
 	type Target : Copy + Sized + 'static;
@@ -0,0 +1,89 @@
+use crate::prelude::*;
+use crate::{System, FrameStage, ImageHandle, ImageFormat, FilterMode, CreateImageRequest, ImageClearPolicy};
+
+
+/// Renders everything before [`FrameStage::Ui`] to a fixed size target, which is then integer scaled and
+/// letterboxed into the backbuffer. For pixel art.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct LowResMode {
+	pub size: Vec2i,
+	pub color_image: ImageHandle,
+	pub depth_image: ImageHandle,
+}
+
+impl System {
+	/// Enable or disable low res mode. See [`System::low_res_viewport`].
+	pub fn set_low_res_mode(&mut self, size: impl Into<Option<Vec2i>>) {
+		let previous_mode = self.low_res_mode;
+
+		self.low_res_mode = size.into().map(|size| {
+			assert!(size.x > 0 && size.y > 0, "Low res mode must have a non-zero size");
+
+			// NOTE: requesting the same size again returns the same images, with an extra reference that is released below.
+			let color_image = self.resource_manager.request(CreateImageRequest::fixed_2d("low res color", size, ImageFormat::Srgba8));
+			let depth_image = self.resource_manager.request(CreateImageRequest::fixed_2d("low res depth", size, ImageFormat::Depth)
+				.clear_policy(ImageClearPolicy::DefaultAtFrameStart));
+
+			LowResMode { size, color_image, depth_image }
+		});
+
+		// Images for other sizes are destroyed once unreferenced, so toggling between sizes doesn't accumulate them.
+		if let Some(previous_mode) = previous_mode {
+			self.resource_manager.release_image(&self.core, previous_mode.color_image);
+			self.resource_manager.release_image(&self.core, previous_mode.depth_image);
+		}
+	}
+
+	pub fn low_res_size(&self) -> Option<Vec2i> {
+		self.low_res_mode.map(|mode| mode.size)
+	}
+
+	/// The low res color target, for postprocessing before it is scaled to the backbuffer.
+	pub fn low_res_color_image(&self) -> Option<ImageHandle> {
+		self.low_res_mode.map(|mode| mode.color_image)
+	}
+
+	/// The integer scale factor used to scale the low res target up to the backbuffer. Always at least 1.
+	pub fn low_res_scale(&self) -> Option<i32> {
+		let size = self.low_res_size()?;
+		let backbuffer_size = self.backbuffer_size();
+
+		let scale = (backbuffer_size.x / size.x).min(backbuffer_size.y / size.y);
+		Some(scale.max(1))
+	}
+
+	/// The region of the backbuffer that the low res target is scaled into, centered with letterboxing.
+	/// Returns None if low res mode isn't enabled.
+	pub fn low_res_viewport(&self) -> Option<Aabb2i> {
+		let size = self.low_res_size()? * self.low_res_scale()?;
+		let offset = (self.backbuffer_size() - size) / 2;
+		Some(Aabb2i::from_min_size(offset, size))
+	}
+
+	/// Redirects all scene stages to the low res target, and schedules the upscale to the backbuffer.
+	pub(crate) fn apply_low_res_mode(&mut self) {
+		let Some(mode) = self.low_res_mode else { return };
+		let Some(viewport) = self.low_res_viewport() else { return };
+
+		// Color isn't cleared by policy since it should match the backbuffer clear color.
+		if let Some(color_image) = self.resource_manager.images.get_name(mode.color_image) {
+			self.core.clear_image_with_color(color_image, self.frame_encoder.backbuffer_clear_color);
+		}
+
+		let framebuffer = [mode.color_image, mode.depth_image];
+
+		self.frame_encoder.command_group(FrameStage::AfterPostprocess(i8::MAX))
+			.annotate("Low Res Upscale")
+			.execute(move |core, rm| {
+				let framebuffer_name = rm.resolve_framebuffer(core, &framebuffer);
+				core.set_scissor_rect(None);
+				core.blit_framebuffer_color(framebuffer_name, Aabb2i::from_size(mode.size), None, viewport, FilterMode::Nearest);
+			});
+
+		for command_group in self.frame_encoder.command_groups.iter_mut() {
+			if command_group.stage < FrameStage::Ui(i32::MIN) && command_group.shared_bindings.framebuffer.is_none() {
+				command_group.shared_bindings.bind_framebuffer(&framebuffer);
+			}
+		}
+	}
+}
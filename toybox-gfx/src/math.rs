@@ -0,0 +1,182 @@
+use crate::prelude::*;
+use crate::core::ImageRange;
+
+
+// TODO(pat.m): move into common
+/// Integer 2D bounding box, with `min` inclusive and `max` exclusive - matching how texel ranges are usually described.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Aabb2i {
+	pub min: Vec2i,
+	pub max: Vec2i,
+}
+
+impl Aabb2i {
+	pub fn new(min: Vec2i, max: Vec2i) -> Aabb2i {
+		Aabb2i { min, max }
+	}
+
+	pub fn from_min_size(min: Vec2i, size: Vec2i) -> Aabb2i {
+		Aabb2i { min, max: min + size }
+	}
+
+	pub fn from_size(size: Vec2i) -> Aabb2i {
+		Aabb2i::from_min_size(Vec2i::zero(), size)
+	}
+
+	pub fn size(&self) -> Vec2i {
+		let Vec2i{x, y} = self.max - self.min;
+		Vec2i::new(x.max(0), y.max(0))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.max.x <= self.min.x || self.max.y <= self.min.y
+	}
+
+	pub fn contains_point(&self, point: Vec2i) -> bool {
+		point.x >= self.min.x && point.x < self.max.x
+			&& point.y >= self.min.y && point.y < self.max.y
+	}
+
+	pub fn contains(&self, other: &Aabb2i) -> bool {
+		other.min.x >= self.min.x && other.max.x <= self.max.x
+			&& other.min.y >= self.min.y && other.max.y <= self.max.y
+	}
+
+	/// Returns the overlapping region of two boxes, or None if they don't overlap.
+	pub fn intersection(&self, other: &Aabb2i) -> Option<Aabb2i> {
+		let result = Aabb2i {
+			min: Vec2i::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y)),
+			max: Vec2i::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y)),
+		};
+
+		(!result.is_empty()).then_some(result)
+	}
+
+	/// Shrinks this box so that it lies entirely within `bounds`. May return an empty box.
+	pub fn clamp_to(&self, bounds: &Aabb2i) -> Aabb2i {
+		let clamp = |v: Vec2i| Vec2i::new(
+			v.x.clamp(bounds.min.x, bounds.max.x),
+			v.y.clamp(bounds.min.y, bounds.max.y),
+		);
+
+		Aabb2i {
+			min: clamp(self.min),
+			max: clamp(self.max),
+		}
+	}
+
+	pub fn extend(&self, min_z: i32, max_z: i32) -> Aabb3i {
+		Aabb3i {
+			min: self.min.extend(min_z),
+			max: self.max.extend(max_z),
+		}
+	}
+}
+
+
+
+/// Remap a standard OpenGL projection, with near at -1 and far at +1 in ndc, so that near maps to 1 and far to 0
+/// in a [0, 1] depth range. See [`Core::set_reverse_z`](crate::Core::set_reverse_z).
+pub fn reverse_z_projection(projection: &Mat4) -> Mat4 {
+	let [x, y, z, w] = projection.rows;
+	Mat4::from_rows([x, y, (w - z) * 0.5, w])
+}
+
+
+
+// TODO(pat.m): move into common
+/// Integer 3D bounding box, with `min` inclusive and `max` exclusive - matching how texel ranges are usually described.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Aabb3i {
+	pub min: Vec3i,
+	pub max: Vec3i,
+}
+
+impl Aabb3i {
+	pub fn new(min: Vec3i, max: Vec3i) -> Aabb3i {
+		Aabb3i { min, max }
+	}
+
+	pub fn from_min_size(min: Vec3i, size: Vec3i) -> Aabb3i {
+		Aabb3i { min, max: min + size }
+	}
+
+	pub fn from_size(size: Vec3i) -> Aabb3i {
+		Aabb3i::from_min_size(Vec3i::zero(), size)
+	}
+
+	pub fn size(&self) -> Vec3i {
+		let Vec3i{x, y, z} = self.max - self.min;
+		Vec3i::new(x.max(0), y.max(0), z.max(0))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.max.x <= self.min.x || self.max.y <= self.min.y || self.max.z <= self.min.z
+	}
+
+	pub fn contains_point(&self, point: Vec3i) -> bool {
+		point.x >= self.min.x && point.x < self.max.x
+			&& point.y >= self.min.y && point.y < self.max.y
+			&& point.z >= self.min.z && point.z < self.max.z
+	}
+
+	pub fn contains(&self, other: &Aabb3i) -> bool {
+		other.min.x >= self.min.x && other.max.x <= self.max.x
+			&& other.min.y >= self.min.y && other.max.y <= self.max.y
+			&& other.min.z >= self.min.z && other.max.z <= self.max.z
+	}
+
+	/// Returns the overlapping region of two boxes, or None if they don't overlap.
+	pub fn intersection(&self, other: &Aabb3i) -> Option<Aabb3i> {
+		let result = Aabb3i {
+			min: Vec3i::new(self.min.x.max(other.min.x), self.min.y.max(other.min.y), self.min.z.max(other.min.z)),
+			max: Vec3i::new(self.max.x.min(other.max.x), self.max.y.min(other.max.y), self.max.z.min(other.max.z)),
+		};
+
+		(!result.is_empty()).then_some(result)
+	}
+
+	/// Shrinks this box so that it lies entirely within `bounds`. May return an empty box.
+	pub fn clamp_to(&self, bounds: &Aabb3i) -> Aabb3i {
+		let clamp = |v: Vec3i| Vec3i::new(
+			v.x.clamp(bounds.min.x, bounds.max.x),
+			v.y.clamp(bounds.min.y, bounds.max.y),
+			v.z.clamp(bounds.min.z, bounds.max.z),
+		);
+
+		Aabb3i {
+			min: clamp(self.min),
+			max: clamp(self.max),
+		}
+	}
+
+	pub fn to_xy(&self) -> Aabb2i {
+		Aabb2i {
+			min: self.min.to_xy(),
+			max: self.max.to_xy(),
+		}
+	}
+}
+
+
+
+impl From<Aabb3i> for ImageRange {
+	fn from(aabb: Aabb3i) -> ImageRange {
+		ImageRange {
+			offset: aabb.min,
+			size: aabb.size(),
+		}
+	}
+}
+
+impl From<Aabb2i> for ImageRange {
+	fn from(aabb: Aabb2i) -> ImageRange {
+		ImageRange::from_2d_range(aabb.min, aabb.size())
+	}
+}
+
+impl From<ImageRange> for Aabb3i {
+	fn from(range: ImageRange) -> Aabb3i {
+		Aabb3i::from_min_size(range.offset, range.size)
+	}
+}
@@ -0,0 +1,157 @@
+use crate::prelude::*;
+use crate::{
+	System, CommandGroupEncoder, ShaderHandle,
+	glsl::{GlslStruct, ShaderImports},
+	shaders,
+};
+
+
+pub const MAX_VIEWS: usize = 4;
+
+/// Name of the shader import providing `MultiViewUniforms`, `multi_view_position` and friends.
+pub const MULTI_VIEW_IMPORT: &str = "multi_view";
+
+
+/// Matches `MultiViewUniforms` in the [`MULTI_VIEW_IMPORT`] shader import.
+#[derive(Debug, Copy, Clone, GlslStruct)]
+#[glsl(crate = "crate")]
+#[repr(C)]
+pub struct MultiViewUniforms {
+	pub projection_views: [Mat4; MAX_VIEWS],
+
+	/// x: num views, y: 1.0 if views are routed to layers rather than viewports
+	pub params: Vec4,
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MultiViewTarget {
+	/// Each view renders to a region of the same rendertarget, e.g., for split-screen.
+	Viewports(Vec<Aabb2i>),
+
+	/// Each view renders to a layer of an array rendertarget, e.g., for stereo. The array image must be bound
+	/// without a layer so that it is attached as layered.
+	Layers,
+}
+
+
+/// Renders the scene from up to [`MAX_VIEWS`] cameras in a single pass, by instancing each draw once per view and
+/// routing instances to a viewport or layer with `gl_ViewportIndex`/`gl_Layer` from the vertex shader.
+///
+/// Draws should use [`MultiView::vertex_shader`], or a custom vertex shader that `#import multi_view`s, and multiply
+/// their instance count by [`MultiView::num_views`].
+///
+/// Requires GL_ARB_shader_viewport_layer_array - see [`MultiView::is_supported`].
+pub struct MultiView {
+	projection_views: Vec<Mat4>,
+	target: MultiViewTarget,
+	vertex_shader: ShaderHandle,
+}
+
+impl MultiView {
+	pub fn is_supported(gfx: &System) -> bool {
+		gfx.core.capabilities().vertex_viewport_layer_supported
+	}
+
+	/// One view per viewport. See [`split_screen_viewports`].
+	pub fn with_viewports(gfx: &mut System, viewports: Vec<Aabb2i>) -> MultiView {
+		assert!(viewports.len() < gfx.core.capabilities().max_viewports, "Too many viewports for multi view");
+		Self::new(gfx, viewports.len(), MultiViewTarget::Viewports(viewports))
+	}
+
+	pub fn with_layers(gfx: &mut System, num_views: usize) -> MultiView {
+		Self::new(gfx, num_views, MultiViewTarget::Layers)
+	}
+
+	fn new(gfx: &mut System, num_views: usize, target: MultiViewTarget) -> MultiView {
+		assert!((1..=MAX_VIEWS).contains(&num_views), "Multi view count must be between 1 and {MAX_VIEWS}");
+
+		if !Self::is_supported(gfx) {
+			log::warn!("GL_ARB_shader_viewport_layer_array not supported - all views will render to the first viewport or layer");
+		}
+
+		let vertex_shader = gfx.resource_manager.compile_vertex_shader("multi view vs", shaders::MULTI_VIEW_VS_SHADER_SOURCE);
+
+		MultiView {
+			projection_views: vec![Mat4::identity(); num_views],
+			target,
+			vertex_shader,
+		}
+	}
+
+	pub fn num_views(&self) -> usize {
+		self.projection_views.len()
+	}
+
+	pub fn target(&self) -> &MultiViewTarget {
+		&self.target
+	}
+
+	/// Replace viewports, e.g., after a resize. Must have the same number of views.
+	pub fn set_viewports(&mut self, viewports: Vec<Aabb2i>) {
+		assert_eq!(viewports.len(), self.num_views(), "Multi view viewport count can't change");
+		self.target = MultiViewTarget::Viewports(viewports);
+	}
+
+	pub fn set_projection_view(&mut self, view: usize, projection_view: Mat4) {
+		self.projection_views[view] = projection_view;
+	}
+
+	/// Standard vertex shader equivalent, expecting [`MultiViewUniforms`] at UBO 0.
+	pub fn vertex_shader(&self) -> ShaderHandle {
+		self.vertex_shader
+	}
+
+	pub fn uniforms(&self) -> MultiViewUniforms {
+		let mut projection_views = [Mat4::identity(); MAX_VIEWS];
+		projection_views[..self.num_views()].copy_from_slice(&self.projection_views);
+
+		let layered = matches!(self.target, MultiViewTarget::Layers);
+
+		MultiViewUniforms {
+			projection_views,
+			params: Vec4::new(self.num_views() as f32, layered as u32 as f32, 0.0, 0.0),
+		}
+	}
+
+	/// Bind [`MultiViewUniforms`] to UBO 0 and set up viewports for all following draws in `group`.
+	pub fn bind(&self, group: &mut CommandGroupEncoder<'_>) {
+		group.bind_shared_ubo(0, &[self.uniforms()]);
+
+		if let MultiViewTarget::Viewports(viewports) = &self.target {
+			// Viewport 0 is reset on every draw, so views start at 1. Matches `multi_view_position`.
+			let viewports = viewports.clone();
+			group.execute(move |core, _| core.set_viewport_array(1, &viewports));
+		}
+	}
+}
+
+
+/// Divide a target of `size` between `count` players - side by side for two, and a 2x2 grid for three or four.
+/// The first viewport is top left.
+pub fn split_screen_viewports(size: Vec2i, count: usize) -> Vec<Aabb2i> {
+	let (columns, rows) = match count {
+		0 | 1 => (1, 1),
+		2 => (2, 1),
+		_ => (2, 2),
+	};
+
+	let cell_size = Vec2i::new(size.x / columns, size.y / rows);
+
+	(0..count.max(1) as i32)
+		.map(|index| {
+			let column = index % columns;
+			let row = index / columns;
+
+			// Viewports are bottom-up.
+			let min = Vec2i::new(column * cell_size.x, size.y - (row + 1) * cell_size.y);
+			Aabb2i::from_min_size(min, cell_size)
+		})
+		.collect()
+}
+
+
+pub(crate) fn register_shader_imports(imports: &mut ShaderImports) {
+	let source = MultiViewUniforms::glsl_declarations() + shaders::MULTI_VIEW_GLSL_SOURCE;
+	imports.register(MULTI_VIEW_IMPORT, source);
+}
@@ -2,12 +2,14 @@ use crate::core;
 use std::fmt::Debug;
 use std::hash::Hash;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 use anyhow::Context;
 use tracing::instrument;
 
 use crate::prelude::*;
 use crate::upload_heap::UploadHeap;
+use crate::glsl::ShaderImports;
 use crate::{shaders, ImageName, SamplerName};
 
 pub mod arguments;
@@ -25,6 +27,12 @@ pub use self::image::*;
 mod framebuffer;
 pub use framebuffer::*;
 
+mod named_buffer;
+pub use named_buffer::*;
+
+mod stats;
+pub use stats::*;
+
 // Create/Destroy api for gpu resources
 // Load/Cache resources from disk
 // Render target/FBO/temporary image cache
@@ -38,16 +46,27 @@ pub struct ResourceManager {
 	pub shaders: ResourceStorage<ShaderResource>,
 
 	load_image_requests: ResourceRequestMap<LoadImageRequest>,
+	pending_image_decodes: Vec<PendingRequest<LoadImageRequest, DecodeTicket>>,
 	load_image_array_requests: ResourceRequestMap<LoadImageArrayRequest>,
+	load_lut_requests: ResourceRequestMap<LoadLutRequest>,
+	load_packed_image_requests: ResourceRequestMap<LoadPackedImageRequest>,
 	create_image_requests: ResourceRequestMap<CreateImageRequest>,
 	pub images: ResourceStorage<ImageResource>,
 
+	image_decoders: Arc<ImageDecoderRegistry>,
+	image_decode_worker: ImageDecodeWorker,
+	image_decode_timings: VecDeque<ImageDecodeTiming>,
+
 	standard_vs_shader: ShaderHandle,
 	fullscreen_vs_shader: ShaderHandle,
 	flat_textured_fs_shader: ShaderHandle,
+	color_grade_fs_shader: ShaderHandle,
+	dither_fs_shader: ShaderHandle,
+	depth_visualize_fs_shader: ShaderHandle,
 
 	blank_white_image: ImageName,
 	blank_black_image: ImageName,
+	blue_noise_image: ImageName,
 
 	nearest_sampler: SamplerName,
 	linear_sampler: SamplerName,
@@ -55,13 +74,29 @@ pub struct ResourceManager {
 	nearest_sampler_repeat: SamplerName,
 	linear_sampler_repeat: SamplerName,
 
-	draw_pipelines: HashMap<(ShaderHandle, Option<ShaderHandle>), core::ShaderPipelineName>,
+	/// Applied on top of each [`CommonSampler::description`] - see [`ResourceManager::set_common_sampler_lod`].
+	common_sampler_lod: (f32, f32, f32),
+	common_sampler_anisotropy: f32,
+
+	draw_pipelines: HashMap<DrawPipelineShaders, core::ShaderPipelineName>,
 	compute_pipelines: HashMap<ShaderHandle, core::ShaderPipelineName>,
 
 	framebuffer_cache: FramebufferCache,
 
+	/// Failed shaders that work has already been skipped for, so they're only reported once.
+	reported_failed_shaders: HashSet<ShaderHandle>,
+
+	/// Uniforms that have already been reported as missing, so they're only reported once.
+	#[cfg(feature="debug-uniforms")]
+	pub(crate) missing_debug_uniforms: std::collections::HashSet<String>,
+
+	pub named_buffers: NamedBufferRegistry,
+
 	pub upload_heap: UploadHeap,
 
+	/// Snippets available to shaders via `#import <name>`.
+	pub shader_imports: ShaderImports,
+
 	resize_request: Option<common::Vec2i>,
 }
 
@@ -79,6 +114,21 @@ impl ResourceManager {
 		let flat_textured_fs_shader = compile_shader_requests.request_handle(&mut shaders,
 			CompileShaderRequest::fragment("flat textured fs", shaders::FLAT_TEXTURED_FS_SHADER_SOURCE));
 
+		let color_grade_fs_shader = compile_shader_requests.request_handle(&mut shaders,
+			CompileShaderRequest::fragment("color grade fs", shaders::COLOR_GRADE_FS_SHADER_SOURCE));
+
+		let dither_fs_shader = compile_shader_requests.request_handle(&mut shaders,
+			CompileShaderRequest::fragment("dither fs", shaders::DITHER_FS_SHADER_SOURCE));
+
+		let depth_visualize_fs_shader = compile_shader_requests.request_handle(&mut shaders,
+			CompileShaderRequest::fragment("depth visualize fs", shaders::DEPTH_VISUALIZE_FS_SHADER_SOURCE));
+
+		let mut shader_imports = ShaderImports::default();
+		crate::shadows::register_shader_imports(&mut shader_imports);
+		crate::multi_view::register_shader_imports(&mut shader_imports);
+		crate::virtual_texture::register_shader_imports(&mut shader_imports);
+		crate::channel_packing::register_shader_imports(&mut shader_imports);
+
 		let blank_white_image = {
 			let format = crate::ImageFormat::Rgba(crate::ComponentFormat::Unorm8);
 			let image = core.create_image_2d(format, Vec2i::splat(1));
@@ -95,41 +145,27 @@ impl ResourceManager {
 			image
 		};
 
-		let nearest_sampler = {
-			let sampler = core.create_sampler();
-			core.set_sampler_minify_filter(sampler, crate::FilterMode::Nearest, None);
-			core.set_sampler_magnify_filter(sampler, crate::FilterMode::Nearest);
-			core.set_sampler_addressing_mode(sampler, crate::AddressingMode::Clamp);
-			core.set_debug_label(sampler, "Nearest sampler");
-			sampler
-		};
+		let blue_noise_image = {
+			let noise = ::image::load_from_memory(shaders::BLUE_NOISE_PNG)?.into_luma8();
+			let (width, height) = noise.dimensions();
 
-		let linear_sampler = {
-			let sampler = core.create_sampler();
-			core.set_sampler_minify_filter(sampler, crate::FilterMode::Linear, None);
-			core.set_sampler_magnify_filter(sampler, crate::FilterMode::Linear);
-			core.set_sampler_addressing_mode(sampler, crate::AddressingMode::Clamp);
-			core.set_debug_label(sampler, "Linear sampler");
-			sampler
+			let format = crate::ImageFormat::unorm8();
+			let image = core.create_image_2d(format, Vec2i::new(width as i32, height as i32));
+			core.upload_image(image, None, format, noise.as_raw());
+			core.set_debug_label(image, "Blue noise image");
+			image
 		};
 
-		let nearest_sampler_repeat = {
-			let sampler = core.create_sampler();
-			core.set_sampler_minify_filter(sampler, crate::FilterMode::Nearest, None);
-			core.set_sampler_magnify_filter(sampler, crate::FilterMode::Nearest);
-			core.set_sampler_addressing_mode(sampler, crate::AddressingMode::Repeat);
-			core.set_debug_label(sampler, "Nearest repeating sampler");
-			sampler
+		let create_common_sampler = |sampler: CommonSampler| {
+			let name = core.create_sampler_from_description(&sampler.description());
+			core.set_debug_label(name, sampler.label());
+			name
 		};
 
-		let linear_sampler_repeat = {
-			let sampler = core.create_sampler();
-			core.set_sampler_minify_filter(sampler, crate::FilterMode::Linear, None);
-			core.set_sampler_magnify_filter(sampler, crate::FilterMode::Linear);
-			core.set_sampler_addressing_mode(sampler, crate::AddressingMode::Repeat);
-			core.set_debug_label(sampler, "Linear repeating sampler");
-			sampler
-		};
+		let nearest_sampler = create_common_sampler(CommonSampler::Nearest);
+		let linear_sampler = create_common_sampler(CommonSampler::Linear);
+		let nearest_sampler_repeat = create_common_sampler(CommonSampler::NearestRepeat);
+		let linear_sampler_repeat = create_common_sampler(CommonSampler::LinearRepeat);
 
 		Ok(ResourceManager {
 			load_shader_requests: ResourceRequestMap::new(),
@@ -137,29 +173,52 @@ impl ResourceManager {
 			shaders,
 
 			load_image_requests: ResourceRequestMap::new(),
+			pending_image_decodes: Vec::new(),
 			load_image_array_requests: ResourceRequestMap::new(),
+			load_lut_requests: ResourceRequestMap::new(),
+			load_packed_image_requests: ResourceRequestMap::new(),
 			create_image_requests: ResourceRequestMap::new(),
 			images: ResourceStorage::new(),
 
+			image_decoders: Arc::new(ImageDecoderRegistry::default()),
+			image_decode_worker: ImageDecodeWorker::new(),
+			image_decode_timings: VecDeque::new(),
+
 			standard_vs_shader,
 			fullscreen_vs_shader,
 			flat_textured_fs_shader,
+			color_grade_fs_shader,
+			dither_fs_shader,
+			depth_visualize_fs_shader,
 
 			blank_white_image,
 			blank_black_image,
+			blue_noise_image,
 
 			nearest_sampler,
 			linear_sampler,
 			nearest_sampler_repeat,
 			linear_sampler_repeat,
 
+			common_sampler_lod: (0.0, -1000.0, 1000.0),
+			common_sampler_anisotropy: 1.0,
+
 			draw_pipelines: HashMap::new(),
 			compute_pipelines: HashMap::new(),
 
 			framebuffer_cache: FramebufferCache::new(),
 
+			reported_failed_shaders: HashSet::new(),
+
+			#[cfg(feature="debug-uniforms")]
+			missing_debug_uniforms: Default::default(),
+
+			named_buffers: NamedBufferRegistry::default(),
+
 			upload_heap: UploadHeap::new(core),
 
+			shader_imports,
+
 			resize_request: None,
 		})
 	}
@@ -198,90 +257,242 @@ impl ResourceManager {
 	}
 
 	/// Attempt to turn requested resources into committed GPU resources.
+	/// Every request queue is processed even if some requests fail, and all failures are reported together.
 	#[instrument(skip_all, name="gfx rm process_requests")]
 	pub fn process_requests(&mut self, core: &mut core::Core, vfs: &vfs::Vfs) -> anyhow::Result<()> {
 		core.push_debug_group("Process Resource Requests");
 
 		let _debug_group_guard = common::defer(|| core.pop_debug_group());
 
-		self.load_shader_requests.process_requests(&mut self.shaders, |def| {
+		self.reload_changed_images(core, vfs);
+		self.retry_changed_failed_requests(vfs);
+
+		let mut errors = Vec::new();
+
+		// Kick off all shader compiles first, so that they can happen in parallel with each other and with image decoding.
+		let mut pending_loaded_shaders = self.load_shader_requests.start_pending_requests(&mut errors, |def| {
 			let label = def.path.display().to_string();
 
-			ShaderResource::from_vfs(core, vfs, def.shader_type, &def.path, &label)
+			ShaderResource::begin_from_vfs(core, vfs, &self.shader_imports, def.shader_type, &def.path, &label)
 				.with_context(|| format!("Compiling shader '{}'", def.path.display()))
-		})?;
+		});
 
-		self.compile_shader_requests.process_requests(&mut self.shaders, |def| {
-			ShaderResource::from_source(core, def.shader_type, &def.src, &def.label)
+		let mut pending_compiled_shaders = self.compile_shader_requests.start_pending_requests(&mut errors, |def| {
+			self.shader_imports.resolve(&def.src)
+				.and_then(|src| ShaderResource::begin_from_source(core, def.shader_type, &src, &def.label))
 				.with_context(|| format!("Compiling shader '{}' from source", def.label))
-		})?;
+		});
 
-		self.load_image_requests.process_requests(&mut self.images, |def| {
-			let label = def.path.display().to_string();
-			ImageResource::from_vfs(core, vfs, &def.path, label)
-				.with_context(|| format!("Loading image '{}'", def.path.display()))
-		})?;
+		// Images are decoded in the background, and finished over the following frames.
+		let image_decoders = &self.image_decoders;
+		let image_decode_worker = &mut self.image_decode_worker;
+
+		let started_image_decodes = self.load_image_requests.start_pending_requests(&mut errors, |def| {
+			// TODO(pat.m): read on the decode threads too - this still stalls on slow disks
+			let data = vfs.load_resource_data(&def.path)
+				.with_context(|| format!("Loading image '{}'", def.path.display()))?;
 
-		self.load_image_array_requests.process_requests(&mut self.images, |def| {
-			ImageResource::array_from_vfs(core, vfs, &def.paths, def.label.clone())
+			Ok(image_decode_worker.submit(image_decoders, def.path.clone(), data))
+		});
+
+		self.pending_image_decodes.extend(started_image_decodes);
+		errors.extend(self.finish_image_decodes(core).err());
+
+		errors.extend(self.load_image_array_requests.process_requests(&mut self.images, |def| {
+			ImageResource::array_from_vfs(core, vfs, &self.image_decoders, &def.paths, def.label.clone())
 				.with_context(|| format!("Loading image array '{}'", def.label))
-		})?;
+		}).err());
 
-		self.create_image_requests.process_requests(&mut self.images, |def| {
+		errors.extend(self.load_lut_requests.process_requests(&mut self.images, |def| {
+			let label = def.path.display().to_string();
+			ImageResource::lut_from_vfs(core, vfs, &def.path, label)
+				.with_context(|| format!("Loading LUT '{}'", def.path.display()))
+		}).err());
+
+		errors.extend(self.load_packed_image_requests.process_requests(&mut self.images, |def| {
+			ImageResource::packed_from_vfs(core, vfs, &self.image_decoders, def)
+				.with_context(|| format!("Loading packed image '{}'", def.label))
+		}).err());
+
+		errors.extend(self.create_image_requests.process_requests(&mut self.images, |def| {
 			Ok(ImageResource::from_create_request(core, def))
-		})?;
+		}).err());
+
+		// Collect shaders that have already finished first, so we only block on the ones that haven't.
+		pending_loaded_shaders.sort_by_key(|pending| !pending.pending.is_complete(core));
+		pending_compiled_shaders.sort_by_key(|pending| !pending.pending.is_complete(core));
+
+		errors.extend(self.load_shader_requests.finish_pending_requests(&mut self.shaders, pending_loaded_shaders, |def, pending| {
+			pending.finish(core)
+				.with_context(|| format!("Compiling shader '{}'", def.path.display()))
+		}).err());
 
-		Ok(())
+		errors.extend(self.compile_shader_requests.finish_pending_requests(&mut self.shaders, pending_compiled_shaders, |def, pending| {
+			pending.finish(core)
+				.with_context(|| format!("Compiling shader '{}' from source", def.label))
+		}).err());
+
+		request::combine_errors(errors)
+	}
+}
+
+impl ResourceManager {
+	/// Reloads any loaded images whose files have changed on disk. Handles remain the same.
+	#[instrument(skip_all, name="gfx rm reload_changed_images")]
+	fn reload_changed_images(&mut self, core: &core::Core, vfs: &vfs::Vfs) {
+		if vfs.changed_resource_paths().is_empty() {
+			return
+		}
+
+		let mut any_names_changed = false;
+
+		for (request, handle) in self.load_image_requests.iter_processed() {
+			if !vfs.resource_changed(&request.path) {
+				continue
+			}
+
+			let Some(image) = self.images.get_resource_mut(handle) else { continue };
+
+			log::info!("Reloading image '{}'", request.path.display());
+
+			match image.reload_from_vfs(core, vfs, &self.image_decoders, &request.path) {
+				Ok(name_changed) => any_names_changed |= name_changed,
+				Err(error) => log::error!("Failed to reload image '{}': {error}", request.path.display()),
+			}
+		}
+
+		// TODO(pat.m): image arrays and luts
+
+		if any_names_changed {
+			self.framebuffer_cache.refresh_attachments(core, &self.images);
+		}
+	}
+
+	/// Retries any failed requests whose files have changed on disk, keeping their handles.
+	fn retry_changed_failed_requests(&mut self, vfs: &vfs::Vfs) {
+		if vfs.changed_resource_paths().is_empty() {
+			return
+		}
+
+		self.load_shader_requests.retry_failed(|request| vfs.resource_changed(&request.path));
+		self.load_image_requests.retry_failed(|request| vfs.resource_changed(&request.path));
+		self.load_lut_requests.retry_failed(|request| vfs.resource_changed(&request.path));
+		self.load_image_array_requests.retry_failed(|request| request.paths.iter().any(|path| vfs.resource_changed(path)));
+		self.load_packed_image_requests.retry_failed(|request| request.sources.iter().any(|(_, path)| vfs.resource_changed(path)));
+
+		self.reported_failed_shaders.retain(|&handle| self.load_shader_requests.has_failed(handle)
+			|| self.compile_shader_requests.has_failed(handle));
+	}
+
+	/// Turn any images that have finished decoding since last frame into image resources.
+	#[instrument(skip_all, name="gfx rm finish_image_decodes")]
+	fn finish_image_decodes(&mut self, core: &core::Core) -> anyhow::Result<()> {
+		const MAX_DECODE_TIMINGS: usize = 64;
+
+		let mut completed = HashMap::new();
+
+		for DecodeResult{ticket, result, timing} in self.image_decode_worker.poll() {
+			log::debug!("Decoded '{}' ({}x{}) in {:.1}ms", timing.path.display(), timing.size.x, timing.size.y,
+				timing.decode_time.as_secs_f32() * 1000.0);
+
+			if self.image_decode_timings.len() >= MAX_DECODE_TIMINGS {
+				self.image_decode_timings.pop_front();
+			}
+
+			self.image_decode_timings.push_back(timing);
+			completed.insert(ticket, result);
+		}
+
+		if completed.is_empty() {
+			return Ok(())
+		}
+
+		// Results for images that were unloaded while decoding won't have a pending request, and are dropped here.
+		let (finished, still_pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_image_decodes)
+			.into_iter()
+			.partition(|pending| completed.contains_key(&pending.pending));
+
+		self.pending_image_decodes = still_pending;
+
+		self.load_image_requests.finish_pending_requests(&mut self.images, finished, |def, ticket| {
+			let mut image = completed.remove(&ticket).unwrap()
+				.with_context(|| format!("Decoding image '{}'", def.path.display()))?;
+
+			// Only ever fails under a simulated memory budget. Falling back keeps the app running so that
+			// missing textures are visible, rather than bringing everything down.
+			if let Err(error) = core.check_memory_budget(image.data.len()) {
+				log::error!("Failed to create image '{}': {error}", def.path.display());
+				image = out_of_memory_placeholder();
+			}
+
+			Ok(ImageResource::from_decoded(core, &image, def.path.display().to_string()))
+		})
 	}
 }
 
 /// Execution api
 impl ResourceManager {
+	/// Returns None if any shader isn't ready, in which case the draw should be skipped.
+	/// See [`ResourceManager::resolve_shader_name`].
 	#[instrument(skip_all, name="gfx rm resolve_draw_pipeline")]
-	pub fn resolve_draw_pipeline(&mut self, core: &mut core::Core,
-		vertex_shader: shader::ShaderHandle, fragment_shader: impl Into<Option<shader::ShaderHandle>>)
-		-> core::ShaderPipelineName
-	{
-		let fragment_shader = fragment_shader.into();
-		let key = (vertex_shader, fragment_shader);
+	pub fn resolve_draw_pipeline(&mut self, core: &mut core::Core, shaders: DrawPipelineShaders) -> Option<core::ShaderPipelineName> {
+		if let Some(&name) = self.draw_pipelines.get(&shaders) {
+			return Some(name);
+		}
 
-		if let Some(&name) = self.draw_pipelines.get(&key) {
-			return name;
+		let mut shader_names = SmallVec::<[core::ShaderName; 5]>::new();
+		for shader in shaders.iter() {
+			shader_names.push(self.resolve_shader_name(shader)?);
 		}
 
 		let pipeline = core.create_shader_pipeline();
 
-		let vertex_shader_name = self.shaders.get_name(vertex_shader).unwrap();
-		core.attach_shader_to_pipeline(pipeline, vertex_shader_name);
-
-		if let Some(fragment_shader) = fragment_shader {
-			let fragment_shader_name = self.shaders.get_name(fragment_shader).unwrap();
-			core.attach_shader_to_pipeline(pipeline, fragment_shader_name);
+		for shader_name in shader_names {
+			core.attach_shader_to_pipeline(pipeline, shader_name);
 		}
 
 		core.set_debug_label(pipeline, "draw pipeline");
 
-		self.draw_pipelines.insert(key, pipeline);
+		self.draw_pipelines.insert(shaders, pipeline);
 
-		pipeline
+		Some(pipeline)
 	}
 
+	/// Returns None if `compute_shader` isn't ready, in which case the dispatch should be skipped.
+	/// See [`ResourceManager::resolve_shader_name`].
 	#[instrument(skip_all, name="gfx rm resolve_compute_pipeline")]
 	pub fn resolve_compute_pipeline(&mut self, core: &mut core::Core, compute_shader: shader::ShaderHandle)
-		-> core::ShaderPipelineName
+		-> Option<core::ShaderPipelineName>
 	{
 		if let Some(&name) = self.compute_pipelines.get(&compute_shader) {
-			return name;
+			return Some(name);
 		}
 
+		let compute_shader_name = self.resolve_shader_name(compute_shader)?;
+
 		let pipeline = core.create_shader_pipeline();
-		let compute_shader_name = self.shaders.get_name(compute_shader).unwrap();
 		core.attach_shader_to_pipeline(pipeline, compute_shader_name);
 		core.set_debug_label(pipeline, "compute pipeline");
 
 		self.compute_pipelines.insert(compute_shader, pipeline);
 
-		pipeline
+		Some(pipeline)
+	}
+
+	/// Name of `shader`, or None if it's still being processed or failed to load or compile.
+	/// Failed shaders are only reported the first time they're used, so that skipped work doesn't spam the log.
+	pub fn resolve_shader_name(&mut self, shader: ShaderHandle) -> Option<core::ShaderName> {
+		let Some(name) = self.shaders.get_name(shader) else {
+			if !self.shader_failed(shader) {
+				log::trace!("Skipping work using unresolved shader {shader:?}");
+			} else if self.reported_failed_shaders.insert(shader) {
+				log::error!("Skipping work using shader {shader:?}, which failed to load or compile");
+			}
+
+			return None
+		};
+
+		Some(name)
 	}
 
 	#[instrument(skip_all, name="gfx rm resolve_framebuffer")]
@@ -291,10 +502,117 @@ impl ResourceManager {
 		self.framebuffer_cache.resolve(core, &self.images, desc.into())
 	}
 
+	/// Create a persistent buffer that can be retrieved later with [`ResourceManager::get_named_buffer`].
+	/// Creating a buffer that already exists returns the existing buffer, but fails if the size or usage differ.
+	pub fn create_named_buffer(&mut self, core: &core::Core, label: &str, size: usize, usage: u32) -> anyhow::Result<core::BufferName> {
+		if self.named_buffers.get(label).is_none() {
+			core.check_memory_budget(size)
+				.with_context(|| format!("Creating named buffer '{label}'"))?;
+		}
+
+		self.named_buffers.create(core, label, size, usage)
+	}
+
+	pub fn get_named_buffer(&self, label: &str) -> Option<core::BufferName> {
+		self.named_buffers.get(label)
+	}
+
+	/// All currently cached framebuffers, for debugging.
+	pub fn framebuffers(&self) -> impl Iterator<Item=(&FramebufferDescription, core::FramebufferName)> + '_ {
+		self.framebuffer_cache.iter()
+	}
+
+	/// Images that are still decoding, or that failed to load, are bound as [`BlankImage::White`].
+	pub(crate) fn placeholder_for_unresolved_image(&self) -> ImageName {
+		self.blank_white_image
+	}
+
+	/// Replace or add to the decoders used for loading images. Takes effect for images requested after this call.
+	pub fn register_image_decoder(&mut self, decoder: impl ImageDecoder + 'static) {
+		Arc::make_mut(&mut self.image_decoders).register(decoder);
+	}
+
+	/// Decoders used for loading images, e.g., for checking whether a file extension can be loaded as an image.
+	pub fn image_decoders(&self) -> &ImageDecoderRegistry {
+		&self.image_decoders
+	}
+
+	/// Whether `handle` failed to load, and so will never resolve.
+	pub fn image_failed(&self, handle: ImageHandle) -> bool {
+		self.load_image_requests.has_failed(handle)
+			|| self.load_image_array_requests.has_failed(handle)
+			|| self.load_lut_requests.has_failed(handle)
+			|| self.load_packed_image_requests.has_failed(handle)
+			|| self.create_image_requests.has_failed(handle)
+	}
+
+	/// Whether `handle` failed to load or compile, and so will never resolve.
+	pub fn shader_failed(&self, handle: ShaderHandle) -> bool {
+		self.load_shader_requests.has_failed(handle)
+			|| self.compile_shader_requests.has_failed(handle)
+	}
+
+	/// Queue every failed request to be processed again on the next [`ResourceManager::process_requests`].
+	/// Failed requests are otherwise only retried when their files change.
+	pub fn retry_failed_requests(&mut self) {
+		self.load_shader_requests.retry_failed(|_| true);
+		self.compile_shader_requests.retry_failed(|_| true);
+		self.load_image_requests.retry_failed(|_| true);
+		self.load_image_array_requests.retry_failed(|_| true);
+		self.load_lut_requests.retry_failed(|_| true);
+		self.load_packed_image_requests.retry_failed(|_| true);
+		self.create_image_requests.retry_failed(|_| true);
+
+		self.reported_failed_shaders.clear();
+	}
+
+	/// Number of images currently waiting to be decoded.
+	pub fn num_pending_image_decodes(&self) -> usize {
+		self.pending_image_decodes.len()
+	}
+
+	pub fn stats(&self) -> ResourceManagerStats {
+		let images = ResourceCategoryStats {
+			count: self.images.len(),
+			bytes: self.images.iter().map(|image| image.image_info.estimated_byte_size()).sum(),
+		};
+
+		let named_buffers = ResourceCategoryStats {
+			count: self.named_buffers.iter().count(),
+			bytes: self.named_buffers.iter().map(|(_, buffer)| buffer.size).sum(),
+		};
+
+		ResourceManagerStats {
+			shaders: ResourceCategoryStats { count: self.shaders.len(), bytes: 0 },
+			images,
+			draw_pipelines: ResourceCategoryStats { count: self.draw_pipelines.len(), bytes: 0 },
+			compute_pipelines: ResourceCategoryStats { count: self.compute_pipelines.len(), bytes: 0 },
+			framebuffers: ResourceCategoryStats { count: self.framebuffer_cache.len(), bytes: 0 },
+			named_buffers,
+
+			pending_requests: RequestQueueStats {
+				load_shader: self.load_shader_requests.num_pending(),
+				compile_shader: self.compile_shader_requests.num_pending(),
+				load_image: self.load_image_requests.num_pending(),
+				load_image_array: self.load_image_array_requests.num_pending(),
+				load_lut: self.load_lut_requests.num_pending(),
+				load_packed_image: self.load_packed_image_requests.num_pending(),
+				create_image: self.create_image_requests.num_pending(),
+				image_decodes: self.pending_image_decodes.len(),
+			},
+		}
+	}
+
+	/// How long recently loaded images took to decode, oldest first.
+	pub fn image_decode_timings(&self) -> impl Iterator<Item=&ImageDecodeTiming> + '_ {
+		self.image_decode_timings.iter()
+	}
+
 	pub fn get_blank_image(&self, image: BlankImage) -> ImageName {
 		match image {
 			BlankImage::White => self.blank_white_image,
 			BlankImage::Black => self.blank_black_image,
+			BlankImage::BlueNoise => self.blue_noise_image,
 		}
 	}
 
@@ -307,12 +625,48 @@ impl ResourceManager {
 		}
 	}
 
+	/// Adjust lod bias and lod clamping for all common samplers. Takes effect immediately.
+	pub fn set_common_sampler_lod(&mut self, core: &core::Core, lod_bias: f32, min_lod: f32, max_lod: f32) {
+		self.common_sampler_lod = (lod_bias, min_lod, max_lod);
+		self.apply_common_sampler_descriptions(core);
+	}
+
+	/// Adjust anisotropic filtering for the linear common samplers - nearest samplers are left crisp.
+	/// 1.0 disables it. Takes effect immediately.
+	pub fn set_common_sampler_anisotropy(&mut self, core: &core::Core, max_anisotropy: f32) {
+		self.common_sampler_anisotropy = max_anisotropy;
+		self.apply_common_sampler_descriptions(core);
+	}
+
+	pub fn common_sampler_anisotropy(&self) -> f32 {
+		self.common_sampler_anisotropy
+	}
+
+	fn apply_common_sampler_descriptions(&self, core: &core::Core) {
+		let (lod_bias, min_lod, max_lod) = self.common_sampler_lod;
+
+		for sampler in CommonSampler::ALL {
+			let mut desc = sampler.description()
+				.lod_bias(lod_bias)
+				.lod_range(min_lod, max_lod);
+
+			if matches!(sampler, CommonSampler::Linear | CommonSampler::LinearRepeat) {
+				desc = desc.anisotropy(self.common_sampler_anisotropy);
+			}
+
+			core.apply_sampler_description(self.get_common_sampler(sampler), &desc);
+		}
+	}
+
 	pub fn get_common_shader(&self, shader: CommonShader) -> ShaderHandle {
 		match shader {
 			CommonShader::StandardVertex => self.standard_vs_shader,
 			CommonShader::FullscreenVertex => self.fullscreen_vs_shader,
 
 			CommonShader::FlatTexturedFragment => self.flat_textured_fs_shader,
+			CommonShader::ColorGradeFragment => self.color_grade_fs_shader,
+			CommonShader::DitherFragment => self.dither_fs_shader,
+			CommonShader::DepthVisualizeFragment => self.depth_visualize_fs_shader,
 		}
 	}
 }
@@ -320,14 +674,105 @@ impl ResourceManager {
 
 /// Request api
 impl ResourceManager {
+	/// Identical requests are deduplicated and will return the same handle. Each request adds a reference to that handle,
+	/// which can be released with [`ResourceManager::release_image`] or [`ResourceManager::release_shader`].
 	pub fn request<R: ResourceRequest>(&mut self, request: R) -> <R::Resource as Resource>::Handle {
 		request.register(self)
 	}
+
+	/// Releases a reference to an image, destroying it once no references remain.
+	pub fn release_image(&mut self, core: &core::Core, handle: ImageHandle) {
+		let remaining = self.load_image_requests.release(handle)
+			.or_else(|| self.load_image_array_requests.release(handle))
+			.or_else(|| self.load_lut_requests.release(handle))
+			.or_else(|| self.load_packed_image_requests.release(handle))
+			.or_else(|| self.create_image_requests.release(handle));
+
+		match remaining {
+			None => log::warn!("Trying to release unknown image handle {handle:?}"),
+			Some(0) => self.unload_image(core, handle),
+			Some(_) => {}
+		}
+	}
+
+	/// Releases a reference to a shader, destroying it once no references remain.
+	pub fn release_shader(&mut self, core: &core::Core, handle: ShaderHandle) {
+		let common_shaders = [self.standard_vs_shader, self.fullscreen_vs_shader, self.flat_textured_fs_shader,
+			self.color_grade_fs_shader, self.dither_fs_shader, self.depth_visualize_fs_shader];
+
+		if common_shaders.contains(&handle) {
+			log::warn!("Trying to release common shader {handle:?}");
+			return
+		}
+
+		let remaining = self.load_shader_requests.release(handle)
+			.or_else(|| self.compile_shader_requests.release(handle));
+
+		match remaining {
+			None => log::warn!("Trying to release unknown shader handle {handle:?}"),
+			Some(0) => self.unload_shader(core, handle),
+			Some(_) => {}
+		}
+	}
+
+	/// Immediately destroy an image regardless of how many references remain. Any framebuffers using it are destroyed too.
+	pub fn unload_image(&mut self, core: &core::Core, handle: ImageHandle) {
+		self.load_image_requests.forget(handle);
+		self.pending_image_decodes.retain(|pending| pending.handle != handle);
+		self.load_image_array_requests.forget(handle);
+		self.load_lut_requests.forget(handle);
+		self.load_packed_image_requests.forget(handle);
+		self.create_image_requests.forget(handle);
+
+		if let Some(resource) = self.images.remove(handle) {
+			core.destroy_image(resource.name);
+		}
+
+		self.framebuffer_cache.remove_entries_using(core, handle);
+		handle.release();
+	}
+
+	/// Immediately destroy a shader regardless of how many references remain. Any pipelines using it are destroyed too.
+	pub fn unload_shader(&mut self, core: &core::Core, handle: ShaderHandle) {
+		self.load_shader_requests.forget(handle);
+		self.compile_shader_requests.forget(handle);
+		self.reported_failed_shaders.remove(&handle);
+
+		if let Some(resource) = self.shaders.remove(handle) {
+			core.destroy_shader(resource.name);
+		}
+
+		self.draw_pipelines.retain(|shaders, &mut pipeline| {
+			let uses_shader = shaders.contains(handle);
+			if uses_shader {
+				core.destroy_shader_pipeline(pipeline);
+			}
+
+			!uses_shader
+		});
+
+		if let Some(pipeline) = self.compute_pipelines.remove(&handle) {
+			core.destroy_shader_pipeline(pipeline);
+		}
+
+		handle.release();
+	}
 }
 
 
 pub trait ResourceHandle : Copy + Clone + Eq + PartialEq + Debug + Hash {
-	fn from_raw(value: u32) -> Self;
+	fn allocate(debug_name: String) -> Self;
+	fn release(self);
+}
+
+impl<T: 'static> ResourceHandle for toybox_handle::Handle<T> {
+	fn allocate(debug_name: String) -> Self {
+		toybox_handle::Handle::new(debug_name)
+	}
+
+	fn release(self) {
+		toybox_handle::Handle::release(self);
+	}
 }
 
 
@@ -336,7 +781,6 @@ pub trait Resource : Debug {
 	type Handle : ResourceHandle;
 	type Name : core::ResourceName;
 
-	// TODO(pat.m): ref counting?
 	fn get_name(&self) -> Self::Name;
 }
 
@@ -344,14 +788,12 @@ pub trait Resource : Debug {
 #[derive(Debug)]
 pub struct ResourceStorage<R: Resource> {
 	resources: HashMap<R::Handle, R>,
-	handle_counter: u32,
 }
 
 impl<R: Resource> ResourceStorage<R> {
 	fn new() -> Self {
 		ResourceStorage {
 			resources: HashMap::new(),
-			handle_counter: 0,
 		}
 	}
 
@@ -365,6 +807,10 @@ impl<R: Resource> ResourceStorage<R> {
 		self.resources.get(&handle)
 	}
 
+	pub fn get_resource_mut(&mut self, handle: R::Handle) -> Option<&'_ mut R> {
+		self.resources.get_mut(&handle)
+	}
+
 	pub fn iter(&self) -> impl Iterator<Item=&R> {
 		self.resources.values()
 	}
@@ -373,15 +819,35 @@ impl<R: Resource> ResourceStorage<R> {
 		self.resources.values_mut()
 	}
 
+	pub fn len(&self) -> usize {
+		self.resources.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.resources.is_empty()
+	}
+
 	fn insert(&mut self, handle: R::Handle, resource: R) {
 		self.resources.insert(handle, resource);
 	}
 
-	fn new_handle(&mut self) -> R::Handle {
-		let value = self.handle_counter;
-		self.handle_counter += 1;
-		R::Handle::from_raw(value)
+	fn remove(&mut self, handle: R::Handle) -> Option<R> {
+		self.resources.remove(&handle)
+	}
+
+	fn new_handle(&mut self, debug_name: String) -> R::Handle {
+		R::Handle::allocate(debug_name)
 	}
 }
 
 
+
+
+/// Magenta, so images that failed to fit in memory stand out.
+fn out_of_memory_placeholder() -> DecodedImage {
+	DecodedImage {
+		size: Vec2i::splat(1),
+		format: core::ImageFormat::Srgba8,
+		data: vec![255, 0, 255, 255],
+	}
+}
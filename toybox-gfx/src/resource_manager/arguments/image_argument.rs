@@ -35,4 +35,7 @@ impl From<BlankImage> for ImageArgument {
 pub enum BlankImage {
 	White,
 	Black,
+
+	/// Tiling 64x64 single channel blue noise, for dithering.
+	BlueNoise,
 }
\ No newline at end of file
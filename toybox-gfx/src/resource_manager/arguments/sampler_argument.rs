@@ -1,4 +1,4 @@
-use crate::SamplerName;
+use crate::{SamplerName, SamplerDescription, AddressingMode};
 
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
@@ -26,4 +26,31 @@ pub enum CommonSampler {
 	Linear,
 	NearestRepeat,
 	LinearRepeat,
-}
\ No newline at end of file
+}
+
+impl CommonSampler {
+	pub const ALL: [CommonSampler; 4] = [
+		CommonSampler::Nearest,
+		CommonSampler::Linear,
+		CommonSampler::NearestRepeat,
+		CommonSampler::LinearRepeat,
+	];
+
+	pub fn description(&self) -> SamplerDescription {
+		match self {
+			CommonSampler::Nearest => SamplerDescription::nearest(),
+			CommonSampler::Linear => SamplerDescription::linear(),
+			CommonSampler::NearestRepeat => SamplerDescription::nearest().addressing_mode(AddressingMode::Repeat),
+			CommonSampler::LinearRepeat => SamplerDescription::linear().addressing_mode(AddressingMode::Repeat),
+		}
+	}
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			CommonSampler::Nearest => "Nearest sampler",
+			CommonSampler::Linear => "Linear sampler",
+			CommonSampler::NearestRepeat => "Nearest repeating sampler",
+			CommonSampler::LinearRepeat => "Linear repeating sampler",
+		}
+	}
+}
@@ -28,5 +28,8 @@ pub enum CommonShader {
 	FullscreenVertex,
 
 	FlatTexturedFragment,
+	ColorGradeFragment,
+	DitherFragment,
+	DepthVisualizeFragment,
 }
 
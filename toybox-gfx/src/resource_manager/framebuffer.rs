@@ -1,5 +1,5 @@
 use crate::prelude::*;
-use crate::{Core, ImageHandle, FramebufferName, ResourceStorage, ImageResource, ImageFormat, FramebufferAttachment};
+use crate::{Core, ImageHandle, ImageType, FramebufferName, ResourceStorage, ImageResource, FramebufferAttachment};
 
 use std::collections::HashMap;
 
@@ -10,12 +10,20 @@ const MAX_ATTACHMENTS: usize = 5;
 #[derive(Debug, Default, Hash, Eq, PartialEq, Clone)]
 pub struct FramebufferDescription {
 	pub attachments: [Option<ImageHandle>; MAX_ATTACHMENTS],
+
+	/// If set, only this layer of any array attachments will be attached. Otherwise array attachments are attached
+	/// as layered.
+	pub layer: Option<u32>,
 }
 
 impl FramebufferDescription {
 	pub fn is_default(&self) -> bool {
 		self.attachments.iter().all(Option::is_none)
 	}
+
+	pub fn with_layer(self, layer: impl Into<Option<u32>>) -> Self {
+		Self { layer: layer.into(), .. self }
+	}
 }
 
 
@@ -31,6 +39,14 @@ impl FramebufferCache {
 		}
 	}
 
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
 	pub fn resolve(&mut self, core: &Core, images: &ResourceStorage<ImageResource>, desc: FramebufferDescription) -> Option<FramebufferName> {
 		if desc.is_default() {
 			return None
@@ -44,7 +60,23 @@ impl FramebufferCache {
 		Some(name)
 	}
 
-	pub fn refresh_attachments(&mut self, core: &mut Core, images: &ResourceStorage<ImageResource>) {
+	/// Destroy any framebuffers with `image` attached.
+	pub fn remove_entries_using(&mut self, core: &Core, image: ImageHandle) {
+		self.entries.retain(|desc, entry| {
+			let uses_image = desc.attachments.contains(&Some(image));
+			if uses_image {
+				core.destroy_framebuffer(entry.name);
+			}
+
+			!uses_image
+		});
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item=(&FramebufferDescription, FramebufferName)> + '_ {
+		self.entries.iter().map(|(desc, entry)| (desc, entry.name))
+	}
+
+	pub fn refresh_attachments(&mut self, core: &Core, images: &ResourceStorage<ImageResource>) {
 		for (desc, Entry{ name, .. }) in self.entries.iter() {
 			attach_attachments(*name, core, images, desc);
 		}
@@ -83,10 +115,11 @@ fn attach_attachments(framebuffer_name: FramebufferName, core: &Core,
 			// continue
 		};
 
-		let attachment = match image.image_info.format {
-			ImageFormat::Depth | ImageFormat::Depth16 | ImageFormat::Depth32 => FramebufferAttachment::Depth,
-			ImageFormat::Stencil => FramebufferAttachment::Stencil,
-			ImageFormat::DepthStencil => FramebufferAttachment::DepthStencil,
+		let format = image.image_info.format;
+		let attachment = match format {
+			_ if format.is_depth() => FramebufferAttachment::Depth,
+			_ if format.is_stencil() => FramebufferAttachment::Stencil,
+			_ if format.is_depth_stencil() => FramebufferAttachment::DepthStencil,
 			_ => {
 				let idx = color_attachment_idx;
 				color_attachment_idx += 1;
@@ -94,7 +127,13 @@ fn attach_attachments(framebuffer_name: FramebufferName, core: &Core,
 			}
 		};
 
-		core.set_framebuffer_attachment(framebuffer_name, attachment, image.name);
+		match desc.layer {
+			Some(layer) if image.image_info.image_type != ImageType::Image2D => {
+				core.set_framebuffer_attachment_layer(framebuffer_name, attachment, image.name, layer);
+			}
+
+			_ => core.set_framebuffer_attachment(framebuffer_name, attachment, image.name),
+		}
 
 		if !image.label.is_empty() {
 			debug_label.push_str(&format!("{attachment:?}:\"{}\" ", &image.label));
@@ -117,7 +156,7 @@ impl From<&[ImageHandle]> for FramebufferDescription {
 			*attachment = Some(*handle);
 		}
 
-		FramebufferDescription { attachments }
+		FramebufferDescription { attachments, layer: None }
 	}
 }
 
@@ -129,6 +168,6 @@ impl<const N: usize> From<&[ImageHandle; N]> for FramebufferDescription {
 		for (attachment, handle) in std::iter::zip(&mut attachments, handles) {
 			*attachment = Some(*handle);
 		}
-		FramebufferDescription { attachments }
+		FramebufferDescription { attachments, layer: None }
 	}
 }
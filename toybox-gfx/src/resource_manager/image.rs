@@ -1,23 +1,28 @@
 use crate::prelude::*;
 use std::path::{Path, PathBuf};
+use anyhow::Context;
 use tracing::instrument;
 
 use crate::core::*;
 
 
 mod load_image;
+mod load_lut;
+mod load_packed;
 mod create_image;
+mod decode;
+mod ktx2;
 pub use load_image::*;
+pub use load_lut::*;
+pub use load_packed::*;
 pub use create_image::*;
+pub use decode::*;
+pub use ktx2::*;
+pub(crate) use decode::{ImageDecodeWorker, DecodeTicket, DecodeResult};
 
 
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub struct ImageHandle(pub u32);
-
-impl super::ResourceHandle for ImageHandle {
-	fn from_raw(value: u32) -> Self { ImageHandle(value) }
-}
+pub type ImageHandle = toybox_handle::Handle<ImageResource>;
 
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -30,6 +35,9 @@ pub enum ImageResizePolicy {
 
 	/// Automatically resize to match a fraction of the backbuffers size.
 	MatchBackbufferFraction(u32),
+
+	/// Automatically resize to match the backbuffer size scaled by [`Core::render_scale`].
+	MatchRenderSize,
 }
 
 
@@ -62,64 +70,57 @@ impl super::Resource for ImageResource {
 }
 
 impl ImageResource {
+	/// Decodes `virtual_path` with whichever of `decoders` matches its extension. Format depends on the file - see
+	/// [`decoded_from_dynamic_image`] and [`QoiDecoder`].
 	#[instrument(skip_all, name="gfx ImageResource::from_vfs")]
-	pub fn from_vfs(core: &Core, vfs: &vfs::Vfs, virtual_path: &Path, label: String) -> anyhow::Result<ImageResource> {
-		// TODO(pat.m): use a BufReader instead so that image can read only what it needs
-		let data = vfs.load_resource_data(virtual_path)?;
-
-		let image = ::image::load_from_memory(&data)?.flipv().into_rgba8();
-		let (width, height) = image.dimensions();
-		let size = Vec2i::new(width as i32, height as i32);
-		let data = image.into_vec();
-
-		// TODO(pat.m): allow diff texel formats
-		let name = core.create_image_2d(ImageFormat::Srgba8, size);
-		core.upload_image(name, None, ImageFormat::Srgba8, &data);
+	pub fn from_vfs(core: &Core, vfs: &vfs::Vfs, decoders: &ImageDecoderRegistry, virtual_path: &Path, label: String) -> anyhow::Result<ImageResource> {
+		let image = decode_from_vfs(vfs, decoders, virtual_path)?;
+		Ok(Self::from_decoded(core, &image, label))
+	}
+
+	#[instrument(skip_all, name="gfx ImageResource::from_decoded")]
+	pub fn from_decoded(core: &Core, image: &DecodedImage, label: String) -> ImageResource {
+		let name = core.create_image_2d(image.format, image.size);
+		core.upload_image(name, None, image.format, &image.data);
 		core.set_debug_label(name, &label);
 
-		Ok(ImageResource {
+		ImageResource {
 			name,
 			image_info: core.get_image_info(name).unwrap(),
 			resize_policy: ImageResizePolicy::Fixed,
 			clear_policy: ImageClearPolicy::Never,
 			label,
-		})
+		}
 	}
 
 	#[instrument(skip_all, name="gfx ImageResource::array_from_vfs")]
-	pub fn array_from_vfs(core: &Core, vfs: &vfs::Vfs, virtual_paths: &[PathBuf], label: String) -> anyhow::Result<ImageResource> {
+	pub fn array_from_vfs(core: &Core, vfs: &vfs::Vfs, decoders: &ImageDecoderRegistry, virtual_paths: &[PathBuf], label: String) -> anyhow::Result<ImageResource> {
 		if virtual_paths.is_empty() {
 			anyhow::bail!("Trying to create empty image array")
 		}
 
 		let mut image_data = Vec::new();
 
-		let mut common_size = None;
+		let mut common_size_format = None;
 
 		for virtual_path in virtual_paths {
-			// TODO(pat.m): use a BufReader instead so that image can read only what it needs
-			let file_data = vfs.load_resource_data(virtual_path)?;
+			let image = decode_from_vfs(vfs, decoders, virtual_path)?;
 
-			let image = ::image::load_from_memory(&file_data)?.flipv().into_rgba8();
-			let size = image.dimensions();
-
-			if *common_size.get_or_insert(size) != size {
-				let (w, h) = common_size.unwrap();
-				let (w2, h2) = size;
+			let &mut (size, format) = common_size_format.get_or_insert((image.size, image.format));
+			if size != image.size || format != image.format {
 				let path = virtual_path.display();
-				anyhow::bail!("Size mismatch while loading image array '{label}'. Expected {w}x{h}, but {path} was {w2}x{h2}");
+				anyhow::bail!("Mismatch while loading image array '{label}'. Expected {}x{} {format:?}, but {path} was {}x{} {:?}",
+					size.x, size.y, image.size.x, image.size.y, image.format);
 			}
 
-			image_data.extend(image.into_vec());
+			image_data.extend(image.data);
 		}
 
-		let (width, height) = common_size.unwrap();
+		let (size, format) = common_size_format.unwrap();
 		let num_layers = virtual_paths.len() as u32;
-		let size = Vec2i::new(width as i32, height as i32);
 
-		// TODO(pat.m): allow diff texel formats
-		let name = core.create_image_2d_array(ImageFormat::Srgba8, size, num_layers);
-		core.upload_image(name, None, ImageFormat::Srgba8, &image_data);
+		let name = core.create_image_2d_array(format, size, num_layers);
+		core.upload_image(name, None, format, &image_data);
 		core.set_debug_label(name, &label);
 
 		Ok(ImageResource {
@@ -131,6 +132,96 @@ impl ImageResource {
 		})
 	}
 
+	#[instrument(skip_all, name="gfx ImageResource::packed_from_vfs")]
+	pub fn packed_from_vfs(core: &Core, vfs: &vfs::Vfs, decoders: &ImageDecoderRegistry, request: &LoadPackedImageRequest) -> anyhow::Result<ImageResource> {
+		let sources = request.sources.iter()
+			.map(|(channel, virtual_path)| {
+				let image = decode_from_vfs(vfs, decoders, virtual_path)
+					.with_context(|| format!("Loading {channel:?} map '{}'", virtual_path.display()))?;
+				Ok((*channel, image))
+			})
+			.collect::<anyhow::Result<Vec<_>>>()?;
+
+		let sources: Vec<_> = sources.iter().map(|(channel, image)| (*channel, image)).collect();
+		let image = crate::channel_packing::pack_channels(&request.convention, &sources)?;
+
+		Ok(Self::from_decoded(core, &image, request.label.clone()))
+	}
+
+	#[instrument(skip_all, name="gfx ImageResource::lut_from_vfs")]
+	pub fn lut_from_vfs(core: &Core, vfs: &vfs::Vfs, virtual_path: &Path, label: String) -> anyhow::Result<ImageResource> {
+		let is_cube_file = virtual_path.extension()
+			.is_some_and(|ext| ext.eq_ignore_ascii_case("cube"));
+
+		let name = if is_cube_file {
+			let source = vfs.load_string(vfs::PathKind::Resource, virtual_path)?;
+			let (lut_size, data) = parse_cube_lut(&source)?;
+
+			let name = core.create_image_3d(ImageFormat::Rgba(ComponentFormat::F16), Vec3i::splat(lut_size as i32));
+			core.upload_image(name, None, ImageFormat::Rgb(ComponentFormat::F32), &data);
+			name
+
+		} else {
+			let data = vfs.load_resource_data(virtual_path)?;
+
+			// NOTE: not flipped, since green is expected to increase from top to bottom.
+			let image = ::image::load_from_memory(&data)?.into_rgba8();
+			let (width, height) = image.dimensions();
+			let lut_size = height as usize;
+
+			anyhow::ensure!(width as usize == lut_size * lut_size,
+				"LUT strip must be N*N by N texels - got {width}x{height}");
+
+			// Reorder so that slices are contiguous.
+			let mut data = Vec::with_capacity(lut_size * lut_size * lut_size * 4);
+			for b in 0..lut_size {
+				for g in 0..lut_size {
+					for r in 0..lut_size {
+						let x = (b * lut_size + r) as u32;
+						data.extend(image.get_pixel(x, g as u32).0);
+					}
+				}
+			}
+
+			let format = ImageFormat::Rgba(ComponentFormat::Unorm8);
+			let name = core.create_image_3d(format, Vec3i::splat(lut_size as i32));
+			core.upload_image(name, None, format, &data);
+			name
+		};
+
+		core.set_debug_label(name, &label);
+
+		Ok(ImageResource {
+			name,
+			image_info: core.get_image_info(name).unwrap(),
+			resize_policy: ImageResizePolicy::Fixed,
+			clear_policy: ImageClearPolicy::Never,
+			label,
+		})
+	}
+
+	/// Reloads image data from disk, keeping the same ImageName if the size and format haven't changed.
+	/// Returns true if the ImageName changed.
+	#[instrument(skip_all, name="gfx ImageResource::reload_from_vfs")]
+	pub(crate) fn reload_from_vfs(&mut self, core: &Core, vfs: &vfs::Vfs, decoders: &ImageDecoderRegistry, virtual_path: &Path) -> anyhow::Result<bool> {
+		let DecodedImage{size, format, data} = decode_from_vfs(vfs, decoders, virtual_path)?;
+
+		if self.image_info.size == size.extend(1) && self.image_info.format == format {
+			core.upload_image(self.name, None, format, &data);
+			return Ok(false)
+		}
+
+		log::info!("'{}' changed size or format while reloading - recreating image", self.label);
+
+		core.destroy_image(self.name);
+		self.name = core.create_image_2d(format, size);
+		self.image_info = core.get_image_info(self.name).unwrap();
+		core.upload_image(self.name, None, format, &data);
+		core.set_debug_label(self.name, &self.label);
+
+		Ok(true)
+	}
+
 	#[instrument(skip_all, name="gfx ImageResource::from_create_request")]
 	pub fn from_create_request(core: &Core, req: &CreateImageRequest) -> ImageResource {
 		let mut image_info = req.image_info.clone();
@@ -144,6 +235,10 @@ impl ImageResource {
 				image_info.size = (core.backbuffer_size() / fraction as i32).extend(1);
 			}
 
+			ImageResizePolicy::MatchRenderSize => {
+				image_info.size = core.render_size().extend(1);
+			}
+
 			_ => {}
 		}
 
@@ -171,6 +266,7 @@ impl ImageResource {
 			ImageResizePolicy::Fixed => return,
 			ImageResizePolicy::MatchBackbuffer => core.backbuffer_size(),
 			ImageResizePolicy::MatchBackbufferFraction(fraction) => core.backbuffer_size() / fraction as i32,
+			ImageResizePolicy::MatchRenderSize => core.render_size(),
 		};
 
 		self.image_info.size = size_2d.extend(1);
@@ -184,3 +280,70 @@ impl ImageResource {
 
 
 
+
+
+
+fn decode_from_vfs(vfs: &vfs::Vfs, decoders: &ImageDecoderRegistry, virtual_path: &Path) -> anyhow::Result<DecodedImage> {
+	let data = vfs.load_resource_data(virtual_path)?;
+	decoders.decode(virtual_path, &data)
+}
+
+/// Parses an Adobe/Resolve style .cube file. Returns the edge length and tightly packed rgb values, red fastest.
+fn parse_cube_lut(source: &str) -> anyhow::Result<(u32, Vec<[f32; 3]>)> {
+	let mut lut_size = None;
+	let mut data = Vec::new();
+
+	for (line_number, line) in source.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue
+		}
+
+		let mut parts = line.split_whitespace();
+		let first = parts.next().unwrap();
+
+		match first {
+			"LUT_3D_SIZE" => {
+				let size: u32 = parts.next()
+					.context("Missing LUT_3D_SIZE value")?
+					.parse()?;
+
+				anyhow::ensure!((2..=256).contains(&size), "LUT_3D_SIZE {size} out of range - expected 2 to 256");
+
+				lut_size = Some(size);
+				data.reserve(lut_entry_count(size)?);
+			}
+
+			"LUT_1D_SIZE" => anyhow::bail!("1D LUTs are not supported"),
+
+			// TODO(pat.m): support non-default domains
+			"TITLE" | "DOMAIN_MIN" | "DOMAIN_MAX" => {}
+
+			_ => {
+				let parse = |s: Option<&str>| -> anyhow::Result<f32> {
+					s.with_context(|| format!("Malformed LUT entry on line {}", line_number+1))?
+						.parse::<f32>()
+						.map_err(Into::into)
+				};
+
+				let r = parse(Some(first))?;
+				let g = parse(parts.next())?;
+				let b = parse(parts.next())?;
+				data.push([r, g, b]);
+			}
+		}
+	}
+
+	let lut_size = lut_size.context("Missing LUT_3D_SIZE")?;
+	let expected_entries = lut_entry_count(lut_size)?;
+
+	anyhow::ensure!(data.len() == expected_entries, "Expected {expected_entries} LUT entries, but found {}", data.len());
+
+	Ok((lut_size, data))
+}
+
+fn lut_entry_count(lut_size: u32) -> anyhow::Result<usize> {
+	(lut_size as usize).checked_mul(lut_size as usize)
+		.and_then(|count| count.checked_mul(lut_size as usize))
+		.context("LUT_3D_SIZE too large")
+}
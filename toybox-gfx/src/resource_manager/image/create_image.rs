@@ -32,6 +32,12 @@ impl CreateImageRequest {
 			.resize_to_backbuffer_fraction(fraction)
 	}
 
+	/// Rendertarget for scene rendering, resized with [`crate::System::set_render_scale`].
+	pub fn scaled_rendertarget(label: impl Into<String>, format: ImageFormat) -> CreateImageRequest {
+		CreateImageRequest::rendertarget(label, format)
+			.resize_to_render_size()
+	}
+
 	pub fn fixed_2d(label: impl Into<String>, size: Vec2i, format: ImageFormat) -> CreateImageRequest {
 		CreateImageRequest {
 			image_info: ImageInfo {
@@ -47,6 +53,40 @@ impl CreateImageRequest {
 			label: label.into(),
 		}
 	}
+
+	/// Individual layers can be rendered to with [`FramebufferDescription::with_layer`].
+	pub fn fixed_2d_array(label: impl Into<String>, size: Vec2i, layers: u32, format: ImageFormat) -> CreateImageRequest {
+		CreateImageRequest {
+			image_info: ImageInfo {
+				image_type: ImageType::Image2DArray,
+				format,
+				size: size.extend(layers as i32),
+				levels: 1,
+				samples: 1,
+			},
+
+			resize_policy: ImageResizePolicy::Fixed,
+			clear_policy: ImageClearPolicy::Never,
+			label: label.into(),
+		}
+	}
+
+	/// Faces are treated as layers, in the order +X, -X, +Y, -Y, +Z, -Z.
+	pub fn fixed_cube(label: impl Into<String>, size: i32, format: ImageFormat) -> CreateImageRequest {
+		CreateImageRequest {
+			image_info: ImageInfo {
+				image_type: ImageType::Cube,
+				format,
+				size: Vec3i::new(size, size, 6),
+				levels: 1,
+				samples: 1,
+			},
+
+			resize_policy: ImageResizePolicy::Fixed,
+			clear_policy: ImageClearPolicy::Never,
+			label: label.into(),
+		}
+	}
 }
 
 impl CreateImageRequest {
@@ -65,6 +105,10 @@ impl CreateImageRequest {
 	pub fn resize_to_backbuffer_fraction(self, fraction: u32) -> Self {
 		self.resize_policy(ImageResizePolicy::MatchBackbufferFraction(fraction))
 	}
+
+	pub fn resize_to_render_size(self) -> Self {
+		self.resize_policy(ImageResizePolicy::MatchRenderSize)
+	}
 }
 
 
@@ -74,4 +118,8 @@ impl ResourceRequest for CreateImageRequest {
 	fn register(self, rm: &mut ResourceManager) -> ImageHandle {
 		rm.create_image_requests.request_handle(&mut rm.images, self)
 	}
+
+	fn debug_name(&self) -> String {
+		self.label.clone()
+	}
 }
\ No newline at end of file
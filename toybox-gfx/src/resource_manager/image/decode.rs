@@ -0,0 +1,306 @@
+use crate::prelude::*;
+use crate::core::{ImageFormat, ComponentFormat};
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+
+
+/// Texel data ready to be uploaded, bottom row first.
+#[derive(Debug, Clone)]
+pub struct DecodedImage {
+	pub size: Vec2i,
+	pub format: ImageFormat,
+	pub data: Vec<u8>,
+}
+
+
+/// Turns encoded file data into [`DecodedImage`]s. Called from decode worker threads.
+pub trait ImageDecoder: Send + Sync {
+	/// Lowercase file extensions this decoder handles, without the leading '.'.
+	fn extensions(&self) -> &[&str];
+
+	fn decode(&self, data: &[u8]) -> anyhow::Result<DecodedImage>;
+}
+
+
+/// Decodes anything the `image` crate supports. 8-bit images are treated as [`ImageFormat::Srgba8`], but higher precision
+/// images keep their precision and are assumed to be linear - 16-bit PNGs are usually heightmaps or normal data.
+/// See [`decoded_from_dynamic_image`].
+#[derive(Debug)]
+pub struct ImageCrateDecoder {
+	format: ::image::ImageFormat,
+	extensions: &'static [&'static str],
+}
+
+impl ImageCrateDecoder {
+	pub const fn new(format: ::image::ImageFormat, extensions: &'static [&'static str]) -> ImageCrateDecoder {
+		ImageCrateDecoder { format, extensions }
+	}
+}
+
+impl ImageDecoder for ImageCrateDecoder {
+	fn extensions(&self) -> &[&str] {
+		self.extensions
+	}
+
+	fn decode(&self, data: &[u8]) -> anyhow::Result<DecodedImage> {
+		let image = ::image::load_from_memory_with_format(data, self.format)?;
+		Ok(decoded_from_dynamic_image(image.flipv()))
+	}
+}
+
+
+/// Decodes jpegs with `zune-jpeg`, which uses SIMD where the cpu supports it - several times faster than the `image`
+/// crate for large photos.
+#[derive(Debug, Default)]
+pub struct ZuneJpegDecoder;
+
+impl ImageDecoder for ZuneJpegDecoder {
+	fn extensions(&self) -> &[&str] {
+		&["jpg", "jpeg"]
+	}
+
+	fn decode(&self, data: &[u8]) -> anyhow::Result<DecodedImage> {
+		use zune_core::{colorspace::ColorSpace, options::DecoderOptions};
+
+		let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+		let mut decoder = zune_jpeg::JpegDecoder::new_with_options(data, options);
+
+		let pixels = decoder.decode()
+			.map_err(|error| anyhow::anyhow!("Decoding jpeg: {error:?}"))?;
+
+		let (width, height) = decoder.dimensions()
+			.ok_or_else(|| anyhow::anyhow!("Decoding jpeg: missing dimensions"))?;
+
+		// Rows are decoded top first.
+		let data = pixels.chunks_exact(width * 4).rev().flatten().copied().collect();
+
+		Ok(DecodedImage {
+			size: Vec2i::new(width as i32, height as i32),
+			format: ImageFormat::Srgba8,
+			data,
+		})
+	}
+}
+
+
+/// QOI files say whether they're sRGB or linear in their header, which the `image` crate ignores.
+#[derive(Debug, Default)]
+pub struct QoiDecoder;
+
+impl ImageDecoder for QoiDecoder {
+	fn extensions(&self) -> &[&str] {
+		&["qoi"]
+	}
+
+	fn decode(&self, data: &[u8]) -> anyhow::Result<DecodedImage> {
+		// Header is magic, width, height, channels, colorspace. Colorspace 1 means all channels are linear.
+		const COLORSPACE_OFFSET: usize = 13;
+		let is_linear = data.get(COLORSPACE_OFFSET) == Some(&1);
+
+		let image = ::image::load_from_memory_with_format(data, ::image::ImageFormat::Qoi)?.flipv().into_rgba8();
+		let (width, height) = image.dimensions();
+
+		let format = match is_linear {
+			true => ImageFormat::rgba8(),
+			false => ImageFormat::Srgba8,
+		};
+
+		Ok(DecodedImage {
+			size: Vec2i::new(width as i32, height as i32),
+			format,
+			data: image.into_vec(),
+		})
+	}
+}
+
+
+/// Picks the closest [`ImageFormat`] to `image`s color type.
+/// - 8-bit images become [`ImageFormat::Srgba8`]
+/// - 16-bit grey, grey-alpha and color images become `Red`, `RedGreen` and `Rgba` [`ComponentFormat::Unorm16`]
+/// - float images become `Rgba` [`ComponentFormat::F32`]
+pub fn decoded_from_dynamic_image(image: ::image::DynamicImage) -> DecodedImage {
+	use ::image::DynamicImage;
+
+	let size = Vec2i::new(image.width() as i32, image.height() as i32);
+
+	let (format, data) = match image {
+		DynamicImage::ImageLuma16(image) => (ImageFormat::Red(ComponentFormat::Unorm16), u16_to_bytes(image.into_raw())),
+		DynamicImage::ImageLumaA16(image) => (ImageFormat::RedGreen(ComponentFormat::Unorm16), u16_to_bytes(image.into_raw())),
+
+		// Rgb is avoided since three component rows aren't always 4 byte aligned.
+		image @ (DynamicImage::ImageRgb16(_) | DynamicImage::ImageRgba16(_))
+			=> (ImageFormat::Rgba(ComponentFormat::Unorm16), u16_to_bytes(image.into_rgba16().into_raw())),
+
+		image @ (DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_))
+			=> (ImageFormat::Rgba(ComponentFormat::F32), f32_to_bytes(image.into_rgba32f().into_raw())),
+
+		image => (ImageFormat::Srgba8, image.into_rgba8().into_raw()),
+	};
+
+	DecodedImage { size, format, data }
+}
+
+fn u16_to_bytes(data: Vec<u16>) -> Vec<u8> {
+	data.into_iter().flat_map(u16::to_ne_bytes).collect()
+}
+
+fn f32_to_bytes(data: Vec<f32>) -> Vec<u8> {
+	data.into_iter().flat_map(f32::to_ne_bytes).collect()
+}
+
+
+/// Maps file extensions to [`ImageDecoder`]s. Decoders registered later take precedence.
+#[derive(Clone)]
+pub struct ImageDecoderRegistry {
+	decoders: Vec<Arc<dyn ImageDecoder>>,
+}
+
+impl ImageDecoderRegistry {
+	pub fn empty() -> ImageDecoderRegistry {
+		ImageDecoderRegistry { decoders: Vec::new() }
+	}
+
+	pub fn register(&mut self, decoder: impl ImageDecoder + 'static) {
+		self.decoders.push(Arc::new(decoder));
+	}
+
+	pub fn find(&self, extension: &str) -> Option<&dyn ImageDecoder> {
+		self.decoders.iter().rev()
+			.find(|decoder| decoder.extensions().iter().any(|ext| ext.eq_ignore_ascii_case(extension)))
+			.map(|decoder| &**decoder)
+	}
+
+	/// Picks a decoder by `path`s extension - except for KTX2 data, which is recognised by its header, since baked
+	/// images keep their source path.
+	pub fn decode(&self, path: &Path, data: &[u8]) -> anyhow::Result<DecodedImage> {
+		let extension = match super::is_ktx2(data) {
+			true => "ktx2",
+			false => path.extension()
+				.and_then(|ext| ext.to_str())
+				.unwrap_or_default(),
+		};
+
+		let Some(decoder) = self.find(extension) else {
+			anyhow::bail!("No image decoder registered for '.{extension}' files")
+		};
+
+		decoder.decode(data)
+	}
+}
+
+impl Default for ImageDecoderRegistry {
+	fn default() -> ImageDecoderRegistry {
+		use ::image::ImageFormat as Codec;
+
+		let mut registry = ImageDecoderRegistry::empty();
+		registry.register(ImageCrateDecoder::new(Codec::Png, &["png"]));
+		registry.register(ZuneJpegDecoder);
+		registry.register(QoiDecoder);
+		registry.register(ImageCrateDecoder::new(Codec::Tga, &["tga"]));
+		registry.register(super::Ktx2Decoder);
+		registry
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ImageDecodeTiming {
+	pub path: PathBuf,
+	pub size: Vec2i,
+	pub decode_time: Duration,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct DecodeTicket(u64);
+
+struct DecodeJob {
+	ticket: DecodeTicket,
+	path: PathBuf,
+	data: Vec<u8>,
+	decoders: Arc<ImageDecoderRegistry>,
+}
+
+pub(crate) struct DecodeResult {
+	pub ticket: DecodeTicket,
+	pub result: anyhow::Result<DecodedImage>,
+	pub timing: ImageDecodeTiming,
+}
+
+
+/// Pool of threads decoding images in the background, so that large textures don't stall the frame.
+pub(crate) struct ImageDecodeWorker {
+	job_tx: mpsc::Sender<DecodeJob>,
+	result_rx: mpsc::Receiver<DecodeResult>,
+	next_ticket: u64,
+}
+
+impl ImageDecodeWorker {
+	pub fn new() -> ImageDecodeWorker {
+		let (job_tx, job_rx) = mpsc::channel::<DecodeJob>();
+		let (result_tx, result_rx) = mpsc::channel();
+
+		let job_rx = Arc::new(Mutex::new(job_rx));
+
+		let num_threads = std::thread::available_parallelism()
+			.map_or(1, |n| n.get().saturating_sub(1))
+			.clamp(1, 4);
+
+		for index in 0..num_threads {
+			let job_rx = Arc::clone(&job_rx);
+			let result_tx = result_tx.clone();
+
+			std::thread::Builder::new()
+				.name(format!("image decode {index}"))
+				.spawn(move || decode_worker_main(job_rx, result_tx))
+				.expect("Failed to spawn image decode thread");
+		}
+
+		ImageDecodeWorker {
+			job_tx,
+			result_rx,
+			next_ticket: 0,
+		}
+	}
+
+	pub fn submit(&mut self, decoders: &Arc<ImageDecoderRegistry>, path: PathBuf, data: Vec<u8>) -> DecodeTicket {
+		let ticket = DecodeTicket(self.next_ticket);
+		self.next_ticket += 1;
+
+		let job = DecodeJob { ticket, path, data, decoders: Arc::clone(decoders) };
+		self.job_tx.send(job).expect("Image decode threads have gone away");
+
+		ticket
+	}
+
+	/// Returns decodes that have completed since last call, without blocking.
+	pub fn poll(&self) -> impl Iterator<Item=DecodeResult> + '_ {
+		self.result_rx.try_iter()
+	}
+}
+
+fn decode_worker_main(job_rx: Arc<Mutex<mpsc::Receiver<DecodeJob>>>, result_tx: mpsc::Sender<DecodeResult>) {
+	loop {
+		// Only hold the lock while waiting, so other threads can pick up jobs while this one decodes.
+		let job = match job_rx.lock().unwrap().recv() {
+			Ok(job) => job,
+			Err(_) => return,
+		};
+
+		let start = Instant::now();
+		let result = job.decoders.decode(&job.path, &job.data);
+		let decode_time = start.elapsed();
+
+		let timing = ImageDecodeTiming {
+			size: result.as_ref().map_or(Vec2i::zero(), |image| image.size),
+			path: job.path,
+			decode_time,
+		};
+
+		if result_tx.send(DecodeResult { ticket: job.ticket, result, timing }).is_err() {
+			return
+		}
+	}
+}
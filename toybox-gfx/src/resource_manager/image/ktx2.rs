@@ -0,0 +1,143 @@
+use crate::prelude::*;
+use crate::core::{ImageFormat, ComponentFormat};
+
+use super::{DecodedImage, ImageDecoder};
+
+
+const IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Identifier, then the nine u32 header fields, then the dfd/kvd/sgd index.
+const INDEX_OFFSET: usize = 12 + 9*4;
+const LEVEL_INDEX_OFFSET: usize = INDEX_OFFSET + 4*4 + 2*8;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 3*8;
+
+
+/// Vulkan format enums for the formats we can round trip, and their KTX2 `typeSize`.
+const FORMAT_TABLE: &[(ImageFormat, u32, u32)] = &[
+	(ImageFormat::Srgba8, 43, 1),
+	(ImageFormat::Rgba(ComponentFormat::Unorm8), 37, 1),
+	(ImageFormat::Red(ComponentFormat::Unorm8), 9, 1),
+	(ImageFormat::Red(ComponentFormat::Unorm16), 70, 2),
+	(ImageFormat::RedGreen(ComponentFormat::Unorm16), 77, 2),
+	(ImageFormat::Rgba(ComponentFormat::Unorm16), 91, 2),
+	(ImageFormat::Rgba(ComponentFormat::F16), 97, 2),
+	(ImageFormat::Rgba(ComponentFormat::F32), 109, 4),
+];
+
+
+/// Writes a single level, uncompressed 2D KTX2 file. Used by the asset baker, so that baked images are read back with
+/// the exact same format mapping by [`Ktx2Decoder`].
+// TODO(pat.m): write a data format descriptor. Toybox doesn't need one, but other tools may refuse files without it.
+pub fn encode_ktx2(image: &DecodedImage) -> anyhow::Result<Vec<u8>> {
+	let Some(&(_, vk_format, type_size)) = FORMAT_TABLE.iter().find(|(format, ..)| *format == image.format) else {
+		anyhow::bail!("{:?} can't be written to KTX2", image.format)
+	};
+
+	anyhow::ensure!(image.size.x > 0 && image.size.y > 0, "Can't write empty image to KTX2");
+
+	let row_size = image.format.texel_byte_size() * image.size.x as usize;
+	anyhow::ensure!(image.data.len() == row_size * image.size.y as usize, "Image data doesn't match its size and format");
+
+	let level_offset = (LEVEL_INDEX_OFFSET + LEVEL_INDEX_ENTRY_SIZE).next_multiple_of(level_alignment(image.format));
+
+	let mut data = Vec::with_capacity(level_offset + image.data.len());
+	data.extend_from_slice(&IDENTIFIER);
+
+	let header = [
+		vk_format, type_size,
+		image.size.x as u32, image.size.y as u32, 0,
+		0, 1, 1, // layers, faces, levels
+		0, // supercompression
+	];
+
+	for value in header {
+		data.extend_from_slice(&value.to_le_bytes());
+	}
+
+	// No dfd, kvd or sgd.
+	data.extend_from_slice(&[0; 4*4 + 2*8]);
+
+	for value in [level_offset as u64, image.data.len() as u64, image.data.len() as u64] {
+		data.extend_from_slice(&value.to_le_bytes());
+	}
+
+	data.resize(level_offset, 0);
+
+	// KTX2 images are top row first by default.
+	for row in image.data.chunks_exact(row_size).rev() {
+		data.extend_from_slice(row);
+	}
+
+	Ok(data)
+}
+
+
+/// Whether `data` starts with the KTX2 file identifier.
+pub fn is_ktx2(data: &[u8]) -> bool {
+	data.starts_with(&IDENTIFIER)
+}
+
+
+/// Reads KTX2 files written by [`encode_ktx2`] - or anything else uncompressed, 2D and in a supported format.
+/// Only the first mip level is read.
+#[derive(Debug, Default)]
+pub struct Ktx2Decoder;
+
+impl ImageDecoder for Ktx2Decoder {
+	fn extensions(&self) -> &[&str] {
+		&["ktx2"]
+	}
+
+	fn decode(&self, data: &[u8]) -> anyhow::Result<DecodedImage> {
+		anyhow::ensure!(is_ktx2(data), "Not a KTX2 file");
+		anyhow::ensure!(data.len() >= LEVEL_INDEX_OFFSET + LEVEL_INDEX_ENTRY_SIZE, "KTX2 file truncated");
+
+		let read_u32 = |offset: usize| u32::from_le_bytes(data[offset..offset+4].try_into().unwrap());
+		let read_u64 = |offset: usize| u64::from_le_bytes(data[offset..offset+8].try_into().unwrap());
+
+		let vk_format = read_u32(12);
+		let width = read_u32(20);
+		let height = read_u32(24);
+		let depth = read_u32(28);
+		let supercompression = read_u32(44);
+
+		let Some(&(format, ..)) = FORMAT_TABLE.iter().find(|(_, vk, _)| *vk == vk_format) else {
+			anyhow::bail!("Unsupported KTX2 vkFormat {vk_format}")
+		};
+
+		anyhow::ensure!(width > 0 && height > 0, "Empty KTX2 image");
+		anyhow::ensure!(depth <= 1, "3D KTX2 images aren't supported");
+		anyhow::ensure!(supercompression == 0, "Supercompressed KTX2 images aren't supported");
+
+		let level_offset = read_u64(LEVEL_INDEX_OFFSET);
+		let level_size = read_u64(LEVEL_INDEX_OFFSET + 8);
+
+		// Sizes come straight from the file, so may be nonsense.
+		let row_size = format.texel_byte_size().checked_mul(width as usize);
+		let image_size = row_size.and_then(|row_size| row_size.checked_mul(height as usize));
+		let (Some(row_size), Some(image_size)) = (row_size, image_size) else {
+			anyhow::bail!("KTX2 image too large")
+		};
+
+		anyhow::ensure!(level_size >= image_size as u64, "KTX2 level is smaller than expected");
+
+		let level_range = usize::try_from(level_offset).ok()
+			.and_then(|start| Some(start..start.checked_add(image_size)?));
+
+		let Some(level_data) = level_range.and_then(|range| data.get(range)) else {
+			anyhow::bail!("KTX2 file truncated")
+		};
+
+		Ok(DecodedImage {
+			size: Vec2i::new(width as i32, height as i32),
+			format,
+			data: level_data.chunks_exact(row_size).rev().flatten().copied().collect(),
+		})
+	}
+}
+
+
+/// Levels must be aligned to lcm(texel size, 4). All formats in [`FORMAT_TABLE`] have power of two texel sizes.
+fn level_alignment(format: ImageFormat) -> usize {
+	format.texel_byte_size().max(4)
+}
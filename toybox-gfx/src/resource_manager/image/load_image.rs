@@ -20,6 +20,10 @@ impl ResourceRequest for LoadImageRequest {
 	fn register(self, rm: &mut ResourceManager) -> ImageHandle {
 		rm.load_image_requests.request_handle(&mut rm.images, self)
 	}
+
+	fn debug_name(&self) -> String {
+		self.path.display().to_string()
+	}
 }
 
 
@@ -47,6 +51,10 @@ impl ResourceRequest for LoadImageArrayRequest {
 	fn register(self, rm: &mut ResourceManager) -> ImageHandle {
 		rm.load_image_array_requests.request_handle(&mut rm.images, self)
 	}
+
+	fn debug_name(&self) -> String {
+		self.label.clone()
+	}
 }
 
 
@@ -0,0 +1,36 @@
+use crate::resource_manager::*;
+use std::path::PathBuf;
+
+/// Loads a 3D color grading LUT, either from a `.cube` file or from a png strip of `N` horizontally
+/// laid out `NxN` slices, with blue increasing from left to right.
+#[derive(Hash, Clone, Debug, Eq, PartialEq)]
+pub struct LoadLutRequest {
+	pub path: PathBuf,
+}
+
+
+impl LoadLutRequest {
+	pub fn from(path: impl Into<PathBuf>) -> LoadLutRequest {
+		LoadLutRequest { path: path.into() }
+	}
+}
+
+
+impl ResourceRequest for LoadLutRequest {
+	type Resource = ImageResource;
+
+	fn register(self, rm: &mut ResourceManager) -> ImageHandle {
+		rm.load_lut_requests.request_handle(&mut rm.images, self)
+	}
+
+	fn debug_name(&self) -> String {
+		self.path.display().to_string()
+	}
+}
+
+
+impl ResourceManager {
+	pub fn load_lut(&mut self, path: impl Into<PathBuf>) -> ImageHandle {
+		self.request(LoadLutRequest::from(path))
+	}
+}
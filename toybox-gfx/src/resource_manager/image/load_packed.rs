@@ -0,0 +1,42 @@
+use crate::resource_manager::*;
+use crate::channel_packing::{MaterialChannel, PackingConvention};
+use std::path::PathBuf;
+
+/// Loads grayscale material maps and packs them into one image according to a [`PackingConvention`].
+/// See [`crate::channel_packing`]. Prefer packing at bake time where possible - this decodes every source on load.
+#[derive(Hash, Clone, Debug, Eq, PartialEq)]
+pub struct LoadPackedImageRequest {
+	pub label: String,
+	pub convention: PackingConvention,
+	pub sources: Vec<(MaterialChannel, PathBuf)>,
+}
+
+
+impl LoadPackedImageRequest {
+	pub fn new(label: impl Into<String>, convention: PackingConvention) -> LoadPackedImageRequest {
+		LoadPackedImageRequest {
+			label: label.into(),
+			convention,
+			sources: Vec::new(),
+		}
+	}
+
+	pub fn source(mut self, channel: MaterialChannel, path: impl Into<PathBuf>) -> Self {
+		self.sources.push((channel, path.into()));
+		self
+	}
+}
+
+
+impl ResourceRequest for LoadPackedImageRequest {
+	type Resource = ImageResource;
+
+	fn register(self, rm: &mut ResourceManager) -> ImageHandle {
+		rm.load_packed_image_requests.request_handle(&mut rm.images, self)
+	}
+
+	fn debug_name(&self) -> String {
+		self.label.clone()
+	}
+}
+
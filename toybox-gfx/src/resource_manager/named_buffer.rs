@@ -0,0 +1,56 @@
+use crate::prelude::*;
+use crate::core::{Core, BufferName};
+
+use std::collections::BTreeMap;
+
+
+/// Persistent buffers that can be looked up by label, so that independent passes can share them without
+/// having to pass names around.
+#[derive(Debug, Default)]
+pub struct NamedBufferRegistry {
+	buffers: BTreeMap<String, NamedBuffer>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct NamedBuffer {
+	pub name: BufferName,
+	pub size: usize,
+	pub usage: u32,
+}
+
+impl NamedBufferRegistry {
+	pub fn create(&mut self, core: &Core, label: &str, size: usize, usage: u32) -> anyhow::Result<BufferName> {
+		if let Some(existing) = self.buffers.get(label) {
+			anyhow::ensure!(existing.size == size && existing.usage == usage,
+				"Named buffer '{label}' already exists with different parameters - \
+				existing: {} bytes with usage {:#x}, requested: {size} bytes with usage {usage:#x}",
+				existing.size, existing.usage);
+
+			return Ok(existing.name)
+		}
+
+		let name = core.create_buffer();
+		core.allocate_buffer_storage(name, size, usage);
+		core.set_debug_label(name, label);
+
+		self.buffers.insert(label.into(), NamedBuffer { name, size, usage });
+
+		Ok(name)
+	}
+
+	pub fn get(&self, label: &str) -> Option<BufferName> {
+		self.buffers.get(label).map(|buffer| buffer.name)
+	}
+
+	pub fn destroy(&mut self, core: &Core, label: &str) {
+		if let Some(buffer) = self.buffers.remove(label) {
+			core.destroy_buffer(buffer.name);
+		} else {
+			log::warn!("Trying to destroy unknown named buffer '{label}'");
+		}
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item=(&str, &NamedBuffer)> + '_ {
+		self.buffers.iter().map(|(label, buffer)| (label.as_str(), buffer))
+	}
+}
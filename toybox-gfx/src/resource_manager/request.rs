@@ -1,4 +1,6 @@
 use super::*;
+use std::collections::HashSet;
+use std::collections::hash_map::Entry;
 
 
 pub trait ResourceRequest : PartialEq + Eq + Hash {
@@ -6,6 +8,9 @@ pub trait ResourceRequest : PartialEq + Eq + Hash {
 
 	fn register(self, rm: &mut ResourceManager) -> <Self::Resource as Resource>::Handle;
 
+	/// Name given to the handle for this request, for debugging.
+	fn debug_name(&self) -> String;
+
 	// fn process(&self, ctx: &mut ResourceRequestContext<'_, '_>) -> 
 }
 
@@ -18,6 +23,13 @@ pub struct ResourceRequestMap<Request>
 {
 	request_to_handle: HashMap<Request, <Request::Resource as Resource>::Handle>,
 	requests: HashMap<Request, <Request::Resource as Resource>::Handle>,
+
+	/// Number of times each handle has been requested, minus the number of times it has been released.
+	ref_counts: HashMap<<Request::Resource as Resource>::Handle, u32>,
+
+	/// Handles whose requests failed, and so won't have a resource unless retried with [`Self::retry_failed`].
+	/// Failed requests stay in `request_to_handle` so that repeated requests don't retry every frame.
+	failed: HashSet<<Request::Resource as Resource>::Handle>,
 }
 
 impl<Request> ResourceRequestMap<Request>
@@ -27,6 +39,8 @@ impl<Request> ResourceRequestMap<Request>
 		ResourceRequestMap {
 			request_to_handle: HashMap::new(),
 			requests: HashMap::new(),
+			ref_counts: HashMap::new(),
+			failed: HashSet::new(),
 		}
 	}
 
@@ -34,29 +48,173 @@ impl<Request> ResourceRequestMap<Request>
 		self.request_to_handle.get(request).cloned()
 	}
 
+	/// Identical requests will always return the same handle, until all references to it are released.
 	pub fn request_handle(&mut self, storage: &mut ResourceStorage<Request::Resource>, request: Request) -> <Request::Resource as Resource>::Handle {
-		if let Some(handle) = self.get_handle(&request) {
-			return handle
+		let handle = match self.get_handle(&request) {
+			Some(handle) => handle,
+			None => match self.requests.entry(request) {
+				Entry::Occupied(entry) => *entry.get(),
+				Entry::Vacant(entry) => {
+					let handle = storage.new_handle(entry.key().debug_name());
+					*entry.insert(handle)
+				}
+			},
+		};
+
+		*self.ref_counts.entry(handle).or_default() += 1;
+
+		handle
+	}
+
+	/// Number of requests not yet processed.
+	pub fn num_pending(&self) -> usize {
+		self.requests.len()
+	}
+
+	/// Whether the request for `handle` failed. Identical requests made after a failure return the same failed handle,
+	/// until the request is retried with [`Self::retry_failed`].
+	pub fn has_failed(&self, handle: <Request::Resource as Resource>::Handle) -> bool {
+		self.failed.contains(&handle)
+	}
+
+	pub fn ref_count(&self, handle: <Request::Resource as Resource>::Handle) -> u32 {
+		self.ref_counts.get(&handle).copied().unwrap_or(0)
+	}
+
+	/// Releases a reference to `handle`. Returns the number of remaining references, or None if `handle` didn't
+	/// come from this map. Once no references remain, the request is forgotten and the caller is expected to destroy
+	/// the associated resource.
+	pub(crate) fn release(&mut self, handle: <Request::Resource as Resource>::Handle) -> Option<u32> {
+		let ref_count = self.ref_counts.get_mut(&handle)?;
+		*ref_count = ref_count.saturating_sub(1);
+
+		let remaining = *ref_count;
+		if remaining == 0 {
+			self.forget(handle);
+		}
+
+		Some(remaining)
+	}
+
+	/// Requests that have been processed, including those that failed.
+	pub(crate) fn iter_processed(&self) -> impl Iterator<Item=(&Request, <Request::Resource as Resource>::Handle)> + '_ {
+		self.request_to_handle.iter().map(|(request, &handle)| (request, handle))
+	}
+
+	/// Forget any requests associated with `handle`, so that future identical requests will create a new resource.
+	pub(crate) fn forget(&mut self, handle: <Request::Resource as Resource>::Handle) {
+		self.ref_counts.remove(&handle);
+		self.failed.remove(&handle);
+		self.request_to_handle.retain(|_, h| *h != handle);
+		self.requests.retain(|_, h| *h != handle);
+	}
+
+	/// Queue failed requests matching `should_retry` to be processed again, keeping their handles.
+	pub(crate) fn retry_failed(&mut self, mut should_retry: impl FnMut(&Request) -> bool) {
+		if self.failed.is_empty() {
+			return
+		}
+
+		for (request, handle) in std::mem::take(&mut self.request_to_handle) {
+			if self.failed.contains(&handle) && should_retry(&request) {
+				self.failed.remove(&handle);
+				self.requests.insert(request, handle);
+			} else {
+				self.request_to_handle.insert(request, handle);
+			}
+		}
+	}
+
+	/// Like [`Self::process_requests`], but split into two phases so that work can overlap between them.
+	/// Returned pending resources can be passed to [`Self::finish_pending_requests`] in any order.
+	/// Requests that fail to start are marked as failed, and their errors added to `errors`.
+	pub(crate) fn start_pending_requests<P, F>(&mut self, errors: &mut Vec<anyhow::Error>, mut f: F) -> Vec<PendingRequest<Request, P>>
+		where F: FnMut(&Request) -> anyhow::Result<P>
+	{
+		self.requests.drain()
+			.filter_map(|(request, handle)| match f(&request) {
+				Ok(pending) => Some(PendingRequest { request, handle, pending }),
+				Err(error) => {
+					self.failed.insert(handle);
+					self.request_to_handle.insert(request, handle);
+					errors.push(error);
+					None
+				}
+			})
+			.collect()
+	}
+
+	/// Every pending request is finished, even if some fail. Failed requests are left without a resource.
+	pub(crate) fn finish_pending_requests<P, F>(&mut self, storage: &mut ResourceStorage<Request::Resource>,
+		pending_requests: impl IntoIterator<Item=PendingRequest<Request, P>>, mut f: F) -> anyhow::Result<()>
+		where F: FnMut(&Request, P) -> anyhow::Result<Request::Resource>
+	{
+		let mut errors = Vec::new();
+
+		for PendingRequest{request, handle, pending} in pending_requests {
+			match f(&request, pending) {
+				Ok(resource) => {
+					storage.insert(handle, resource);
+					self.request_to_handle.insert(request, handle);
+				}
+
+				Err(error) => {
+					self.failed.insert(handle);
+					self.request_to_handle.insert(request, handle);
+					errors.push(error);
+				}
+			}
 		}
 
-		*self.requests.entry(request)
-			.or_insert_with(|| storage.new_handle())
+		combine_errors(errors)
 	}
 
+	/// Every request is processed, even if some fail. Failed requests are left without a resource.
 	pub(crate) fn process_requests<F>(&mut self, storage: &mut ResourceStorage<Request::Resource>, mut f: F) -> anyhow::Result<()>
 		where F: FnMut(&Request) -> anyhow::Result<Request::Resource>
 	{
+		let mut errors = Vec::new();
+
 		for (request, handle) in self.requests.drain() {
-			let resource = f(&request)?;
-			storage.insert(handle, resource);
-			self.request_to_handle.insert(request, handle);
+			match f(&request) {
+				Ok(resource) => {
+					storage.insert(handle, resource);
+					self.request_to_handle.insert(request, handle);
+				}
+
+				Err(error) => {
+					self.failed.insert(handle);
+					self.request_to_handle.insert(request, handle);
+					errors.push(error);
+				}
+			}
 		}
 
-		Ok(())
+		combine_errors(errors)
 	}
 }
 
 
+/// Reports every error from a batch of requests, so that one failure doesn't hide the rest.
+pub(crate) fn combine_errors(mut errors: Vec<anyhow::Error>) -> anyhow::Result<()> {
+	match errors.len() {
+		0 => Ok(()),
+		1 => Err(errors.remove(0)),
+		num_errors => {
+			let messages: Vec<_> = errors.iter().map(|error| format!("{error:?}")).collect();
+			anyhow::bail!("{num_errors} resource requests failed:\n{}", messages.join("\n\n"))
+		}
+	}
+}
+
+
+pub(crate) struct PendingRequest<Request: ResourceRequest, P> {
+	pub request: Request,
+	pub handle: <Request::Resource as Resource>::Handle,
+	pub pending: P,
+}
+
+
 // pub struct ResourceRequestContext<'core, 'rm> {
 // 	pub core: &'core mut Core,
 // 	pub resource_path: &'rm Path,
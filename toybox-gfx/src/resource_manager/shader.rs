@@ -1,4 +1,5 @@
 use crate::prelude::*;
+use crate::glsl::ShaderImports;
 use std::path::Path;
 use tracing::instrument;
 
@@ -14,11 +15,59 @@ pub use load_shader_request::LoadShaderRequest;
 pub use compile_shader_request::CompileShaderRequest;
 
 
+pub type ShaderHandle = toybox_handle::Handle<ShaderResource>;
+
+
+/// The shaders making up a draw pipeline. Also used as the key for cached pipelines.
+/// See [`ResourceManager::resolve_draw_pipeline`](super::ResourceManager::resolve_draw_pipeline).
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
-pub struct ShaderHandle(pub u32);
+pub struct DrawPipelineShaders {
+	pub vertex: ShaderHandle,
 
-impl super::ResourceHandle for ShaderHandle {
-	fn from_raw(value: u32) -> Self { ShaderHandle(value) }
+	/// Optional even with tessellation - default outer and inner levels are used without one.
+	pub tess_control: Option<ShaderHandle>,
+	pub tess_evaluation: Option<ShaderHandle>,
+
+	pub geometry: Option<ShaderHandle>,
+	pub fragment: Option<ShaderHandle>,
+}
+
+impl DrawPipelineShaders {
+	pub fn new(vertex: ShaderHandle, fragment: impl Into<Option<ShaderHandle>>) -> DrawPipelineShaders {
+		DrawPipelineShaders {
+			vertex,
+			tess_control: None,
+			tess_evaluation: None,
+			geometry: None,
+			fragment: fragment.into(),
+		}
+	}
+
+	pub fn tessellation(self, tess_control: impl Into<Option<ShaderHandle>>, tess_evaluation: ShaderHandle) -> Self {
+		Self { tess_control: tess_control.into(), tess_evaluation: Some(tess_evaluation), .. self }
+	}
+
+	pub fn geometry(self, geometry: impl Into<Option<ShaderHandle>>) -> Self {
+		Self { geometry: geometry.into(), .. self }
+	}
+
+	/// All shaders in pipeline order.
+	pub fn iter(&self) -> impl Iterator<Item=ShaderHandle> {
+		[Some(self.vertex), self.tess_control, self.tess_evaluation, self.geometry, self.fragment]
+			.into_iter()
+			.flatten()
+	}
+
+	/// Every stage before rasterization, in pipeline order.
+	pub fn pre_raster_stages(&self) -> impl Iterator<Item=ShaderHandle> {
+		[Some(self.vertex), self.tess_control, self.tess_evaluation, self.geometry]
+			.into_iter()
+			.flatten()
+	}
+
+	pub fn contains(&self, shader: ShaderHandle) -> bool {
+		self.iter().any(|handle| handle == shader)
+	}
 }
 
 
@@ -36,54 +85,129 @@ impl super::Resource for ShaderResource {
 	fn get_name(&self) -> ShaderName { self.name }
 }
 
+/// A [`ShaderResource`] that is still compiling.
+#[derive(Debug)]
+pub struct PendingShaderResource {
+	shader: core::PendingShader,
+	label: String,
+	num_user_clip_planes: u32,
+}
+
+impl PendingShaderResource {
+	pub fn is_complete(&self, core: &core::Core) -> bool {
+		core.is_shader_compile_complete(&self.shader)
+	}
+
+	#[instrument(skip_all, name="gfx PendingShaderResource::finish")]
+	pub fn finish(self, core: &core::Core) -> anyhow::Result<ShaderResource> {
+		let name = core.finish_create_shader(self.shader)?;
+
+		core.set_debug_label(name, &self.label);
+		core.debug_marker(&self.label);
+
+		Ok(ShaderResource {
+			name,
+			workgroup_size: reflect_workgroup_size(core, name),
+			num_user_clip_planes: self.num_user_clip_planes,
+		})
+	}
+}
+
 impl ShaderResource {
 	#[instrument(skip_all, name="gfx ShaderResource::from_source")]
 	pub fn from_source(core: &core::Core, shader_type: ShaderType, data: &str, label: &str) -> anyhow::Result<ShaderResource> {
+		Self::begin_from_source(core, shader_type, data, label)?
+			.finish(core)
+	}
+
+	#[instrument(skip_all, name="gfx ShaderResource::begin_from_source")]
+	pub fn begin_from_source(core: &core::Core, shader_type: ShaderType, data: &str, label: &str) -> anyhow::Result<PendingShaderResource> {
 		// TODO(pat.m): ugh
 		let uses_user_clipping = data.contains("gl_ClipDistance");
 
+		// Separable programs need gl_PerVertex redeclared identically on both sides of every interface between pre-raster
+		// stages, and shaders are compiled without knowing what they'll be paired with - so clip distances are always
+		// declared, whether or not this shader writes them. Stages after the one writing them must pass them on.
+		// TODO(pat.m): fixed clip distances is no bueno
+		let per_vertex_members = "vec4 gl_Position; float gl_ClipDistance[4]; float gl_PointSize;";
+
 		let std_output_block = match shader_type {
-			ShaderType::Vertex => {
-				if uses_user_clipping {
-					// TODO(pat.m): fixed clip distances is no bueno
-					"out gl_PerVertex { vec4 gl_Position; float gl_ClipDistance[4]; float gl_PointSize; };"
-				} else {
-					"out gl_PerVertex { vec4 gl_Position; float gl_PointSize; };"
-				}
-			}
-			_ => "",
+			ShaderType::Vertex => format!("out gl_PerVertex {{ {per_vertex_members} }};"),
+			ShaderType::TessControl => format!("in gl_PerVertex {{ {per_vertex_members} }} gl_in[gl_MaxPatchVertices];\n\
+				out gl_PerVertex {{ {per_vertex_members} }} gl_out[];"),
+			ShaderType::TessEvaluation => format!("in gl_PerVertex {{ {per_vertex_members} }} gl_in[gl_MaxPatchVertices];\n\
+				out gl_PerVertex {{ {per_vertex_members} }};"),
+			ShaderType::Geometry => format!("in gl_PerVertex {{ {per_vertex_members} }} gl_in[];\n\
+				out gl_PerVertex {{ {per_vertex_members} }};"),
+			_ => String::new(),
 		};
 
 		let ubo_options = "layout(row_major, std140) uniform;";
 		let ssbo_options = "layout(row_major, std430) buffer;";
 
-		let reset_line_directives = "#line 0 1";
+		// Source string 1 is the shader itself, numbered from its first line. See `ShaderImports::resolve`.
+		let reset_line_directives = "#line 1 1";
+
+		// Lets shaders that interpret depth values handle either convention. See `Core::set_reverse_z`.
+		let depth_convention = match core.is_reverse_z() {
+			true => "#define TOYBOX_REVERSE_Z 1",
+			false => "",
+		};
+
+		// Must come before anything else. Shaders can check for support with `#ifdef GL_ARB_shader_viewport_layer_array`.
+		let extensions = match core.capabilities().vertex_viewport_layer_supported {
+			true => "#extension GL_ARB_shader_viewport_layer_array : enable",
+			false => "",
+		};
 
-		let name = core.create_shader(shader_type, &[
+		let shader = core.begin_create_shader(shader_type, &[
 			"#version 450",
+			extensions,
 			ubo_options,
 			ssbo_options,
-			std_output_block,
+			&std_output_block,
+			depth_convention,
 			reset_line_directives,
 			&data
 		])?;
 
-		core.set_debug_label(name, &label);
-		core.debug_marker(&label);
-
-		Ok(ShaderResource {
-			name,
-			workgroup_size: reflect_workgroup_size(core, name),
+		Ok(PendingShaderResource {
+			shader,
+			label: label.into(),
 			num_user_clip_planes: if uses_user_clipping { 4 } else { 0 },
 		})
 	}
 
 	#[instrument(skip_all, name="gfx ShaderResource::from_vfs")]
-	pub fn from_vfs(core: &core::Core, vfs: &vfs::Vfs, shader_type: ShaderType, virtual_path: &Path, label: &str) -> anyhow::Result<ShaderResource> {
+	pub fn from_vfs(core: &core::Core, vfs: &vfs::Vfs, imports: &ShaderImports, shader_type: ShaderType, virtual_path: &Path, label: &str) -> anyhow::Result<ShaderResource> {
+		Self::begin_from_vfs(core, vfs, imports, shader_type, virtual_path, label)?
+			.finish(core)
+	}
+
+	/// Expects a SPIR-V module with a `main` entry point.
+	#[instrument(skip_all, name="gfx ShaderResource::begin_from_spirv")]
+	pub fn begin_from_spirv(core: &core::Core, shader_type: ShaderType, spirv: &[u8], label: &str) -> anyhow::Result<PendingShaderResource> {
+		let shader = core.begin_create_shader_from_spirv(shader_type, spirv, "main", &[])?;
+
+		Ok(PendingShaderResource {
+			shader,
+			label: label.into(),
+			// TODO(pat.m): reflect from the module, or from metadata shipped alongside it.
+			num_user_clip_planes: 0,
+		})
+	}
+
+	#[instrument(skip_all, name="gfx ShaderResource::begin_from_vfs")]
+	pub fn begin_from_vfs(core: &core::Core, vfs: &vfs::Vfs, imports: &ShaderImports, shader_type: ShaderType, virtual_path: &Path, label: &str) -> anyhow::Result<PendingShaderResource> {
 		let data = vfs.load_resource_data(virtual_path)?;
-		let data = String::from_utf8(data)?;
 
-		Self::from_source(core, shader_type, &data, label)
+		if virtual_path.extension().is_some_and(|extension| extension == "spv") {
+			return Self::begin_from_spirv(core, shader_type, &data, label)
+		}
+
+		let data = imports.resolve(&String::from_utf8(data)?)?;
+
+		Self::begin_from_source(core, shader_type, &data, label)
 	}
 }
 
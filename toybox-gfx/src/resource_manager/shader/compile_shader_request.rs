@@ -18,6 +18,30 @@ impl CompileShaderRequest {
 		}
 	}
 
+	pub fn tess_control(label: impl Into<String>, src: impl Into<String>) -> CompileShaderRequest {
+		CompileShaderRequest {
+			label: label.into(),
+			src: src.into(),
+			shader_type: ShaderType::TessControl,
+		}
+	}
+
+	pub fn tess_evaluation(label: impl Into<String>, src: impl Into<String>) -> CompileShaderRequest {
+		CompileShaderRequest {
+			label: label.into(),
+			src: src.into(),
+			shader_type: ShaderType::TessEvaluation,
+		}
+	}
+
+	pub fn geometry(label: impl Into<String>, src: impl Into<String>) -> CompileShaderRequest {
+		CompileShaderRequest {
+			label: label.into(),
+			src: src.into(),
+			shader_type: ShaderType::Geometry,
+		}
+	}
+
 	pub fn fragment(label: impl Into<String>, src: impl Into<String>) -> CompileShaderRequest {
 		CompileShaderRequest {
 			label: label.into(),
@@ -42,6 +66,10 @@ impl ResourceRequest for CompileShaderRequest {
 	fn register(self, rm: &mut ResourceManager) -> ShaderHandle {
 		rm.compile_shader_requests.request_handle(&mut rm.shaders, self)
 	}
+
+	fn debug_name(&self) -> String {
+		self.label.clone()
+	}
 }
 
 
@@ -50,6 +78,18 @@ impl ResourceManager {
 		self.request(CompileShaderRequest::vertex(label, src))
 	}
 
+	pub fn compile_tess_control_shader(&mut self, label: impl Into<String>, src: impl Into<String>) -> ShaderHandle {
+		self.request(CompileShaderRequest::tess_control(label, src))
+	}
+
+	pub fn compile_tess_evaluation_shader(&mut self, label: impl Into<String>, src: impl Into<String>) -> ShaderHandle {
+		self.request(CompileShaderRequest::tess_evaluation(label, src))
+	}
+
+	pub fn compile_geometry_shader(&mut self, label: impl Into<String>, src: impl Into<String>) -> ShaderHandle {
+		self.request(CompileShaderRequest::geometry(label, src))
+	}
+
 	pub fn compile_fragment_shader(&mut self, label: impl Into<String>, src: impl Into<String>) -> ShaderHandle {
 		self.request(CompileShaderRequest::fragment(label, src))
 	}
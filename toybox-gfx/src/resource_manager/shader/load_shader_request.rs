@@ -3,6 +3,7 @@ use crate::resource_manager::*;
 use std::path::PathBuf;
 
 
+/// Shaders are loaded from GLSL source, or from precompiled SPIR-V modules if the path ends in `.spv`.
 #[derive(Hash, Clone, Debug, Eq, PartialEq)]
 pub struct LoadShaderRequest {
 	pub path: PathBuf,
@@ -17,8 +18,8 @@ impl LoadShaderRequest {
 			anyhow::bail!("Path missing extension: '{}'", path.display())
 		};
 
-		if extension != "glsl" {
-			anyhow::bail!("Extension must end in 'glsl': '{}'", path.display())
+		if extension != "glsl" && extension != "spv" {
+			anyhow::bail!("Extension must end in 'glsl' or 'spv': '{}'", path.display())
 		}
 
 		let Some(stem) = path.file_stem().and_then(std::ffi::OsStr::to_str) else {
@@ -26,6 +27,9 @@ impl LoadShaderRequest {
 		};
 
 		let shader_type = if stem.ends_with(".vs") { ShaderType::Vertex }
+			else if stem.ends_with(".tcs") { ShaderType::TessControl }
+			else if stem.ends_with(".tes") { ShaderType::TessEvaluation }
+			else if stem.ends_with(".gs") { ShaderType::Geometry }
 			else if stem.ends_with(".fs") { ShaderType::Fragment }
 			else if stem.ends_with(".cs") { ShaderType::Compute }
 			else { anyhow::bail!("Unknown shader extension: '{}'", path.display()) };
@@ -43,6 +47,27 @@ impl LoadShaderRequest {
 		}
 	}
 
+	pub fn tess_control(path: impl Into<PathBuf>) -> LoadShaderRequest {
+		LoadShaderRequest {
+			path: path.into(),
+			shader_type: ShaderType::TessControl,
+		}
+	}
+
+	pub fn tess_evaluation(path: impl Into<PathBuf>) -> LoadShaderRequest {
+		LoadShaderRequest {
+			path: path.into(),
+			shader_type: ShaderType::TessEvaluation,
+		}
+	}
+
+	pub fn geometry(path: impl Into<PathBuf>) -> LoadShaderRequest {
+		LoadShaderRequest {
+			path: path.into(),
+			shader_type: ShaderType::Geometry,
+		}
+	}
+
 	pub fn fragment(path: impl Into<PathBuf>) -> LoadShaderRequest {
 		LoadShaderRequest {
 			path: path.into(),
@@ -65,6 +90,10 @@ impl ResourceRequest for LoadShaderRequest {
 	fn register(self, rm: &mut ResourceManager) -> ShaderHandle {
 		rm.load_shader_requests.request_handle(&mut rm.shaders, self)
 	}
+
+	fn debug_name(&self) -> String {
+		self.path.display().to_string()
+	}
 }
 
 impl ResourceManager {
@@ -72,6 +101,18 @@ impl ResourceManager {
 		self.request(LoadShaderRequest::vertex(path))
 	}
 
+	pub fn load_tess_control_shader(&mut self, path: impl Into<PathBuf>) -> ShaderHandle {
+		self.request(LoadShaderRequest::tess_control(path))
+	}
+
+	pub fn load_tess_evaluation_shader(&mut self, path: impl Into<PathBuf>) -> ShaderHandle {
+		self.request(LoadShaderRequest::tess_evaluation(path))
+	}
+
+	pub fn load_geometry_shader(&mut self, path: impl Into<PathBuf>) -> ShaderHandle {
+		self.request(LoadShaderRequest::geometry(path))
+	}
+
 	pub fn load_fragment_shader(&mut self, path: impl Into<PathBuf>) -> ShaderHandle {
 		self.request(LoadShaderRequest::fragment(path))
 	}
@@ -0,0 +1,73 @@
+/// Snapshot of everything a [`ResourceManager`](super::ResourceManager) is holding on to - see
+/// [`ResourceManager::stats`](super::ResourceManager::stats).
+/// Cheap enough to take every frame, so soak tests can assert nothing grows without bound.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceManagerStats {
+	pub shaders: ResourceCategoryStats,
+	pub images: ResourceCategoryStats,
+	pub draw_pipelines: ResourceCategoryStats,
+	pub compute_pipelines: ResourceCategoryStats,
+	pub framebuffers: ResourceCategoryStats,
+	pub named_buffers: ResourceCategoryStats,
+
+	pub pending_requests: RequestQueueStats,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceCategoryStats {
+	pub count: usize,
+
+	/// Estimated gpu memory. Zero for categories whose size isn't tracked, like shaders and pipelines.
+	pub bytes: usize,
+}
+
+/// Requests that have been made but not yet turned into resources.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestQueueStats {
+	pub load_shader: usize,
+	pub compile_shader: usize,
+	pub load_image: usize,
+	pub load_image_array: usize,
+	pub load_lut: usize,
+	pub load_packed_image: usize,
+	pub create_image: usize,
+
+	/// Images handed off to the decode worker and not yet uploaded.
+	pub image_decodes: usize,
+}
+
+impl ResourceManagerStats {
+	pub fn total_bytes(&self) -> usize {
+		self.categories().map(|(_, stats)| stats.bytes).sum()
+	}
+
+	pub fn categories(&self) -> impl Iterator<Item=(&'static str, ResourceCategoryStats)> {
+		[
+			("Shaders", self.shaders),
+			("Images", self.images),
+			("Draw Pipelines", self.draw_pipelines),
+			("Compute Pipelines", self.compute_pipelines),
+			("Framebuffers", self.framebuffers),
+			("Named Buffers", self.named_buffers),
+		].into_iter()
+	}
+}
+
+impl RequestQueueStats {
+	pub fn total(&self) -> usize {
+		self.queues().map(|(_, depth)| depth).sum()
+	}
+
+	pub fn queues(&self) -> impl Iterator<Item=(&'static str, usize)> {
+		[
+			("Load Shader", self.load_shader),
+			("Compile Shader", self.compile_shader),
+			("Load Image", self.load_image),
+			("Load Image Array", self.load_image_array),
+			("Load LUT", self.load_lut),
+			("Load Packed Image", self.load_packed_image),
+			("Create Image", self.create_image),
+			("Image Decodes", self.image_decodes),
+		].into_iter()
+	}
+}
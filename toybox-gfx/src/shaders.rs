@@ -4,6 +4,46 @@ use crate::prelude::*;
 pub const STANDARD_VS_SHADER_SOURCE: &str = include_str!("shaders/standard.vs.glsl");
 pub const FULLSCREEN_VS_SHADER_SOURCE: &str = include_str!("shaders/fullscreen.vs.glsl");
 pub const FLAT_TEXTURED_FS_SHADER_SOURCE: &str = include_str!("shaders/flat.fs.glsl");
+pub const COLOR_GRADE_FS_SHADER_SOURCE: &str = include_str!("shaders/color_grade.fs.glsl");
+pub const DITHER_FS_SHADER_SOURCE: &str = include_str!("shaders/dither.fs.glsl");
+pub const DEPTH_VISUALIZE_FS_SHADER_SOURCE: &str = include_str!("shaders/depth_visualize.fs.glsl");
+pub const EXPOSURE_FS_SHADER_SOURCE: &str = include_str!("shaders/exposure.fs.glsl");
+pub const AUTO_EXPOSURE_HISTOGRAM_CS_SHADER_SOURCE: &str = include_str!("shaders/auto_exposure_histogram.cs.glsl");
+pub const AUTO_EXPOSURE_AVERAGE_CS_SHADER_SOURCE: &str = include_str!("shaders/auto_exposure_average.cs.glsl");
+
+/// Shader import, see [`crate::shadows::SHADOW_CASCADES_IMPORT`].
+pub const SHADOW_CASCADES_GLSL_SOURCE: &str = include_str!("shaders/shadow_cascades.glsl");
+
+pub const POINT_SHADOW_VS_SHADER_SOURCE: &str = include_str!("shaders/point_shadow.vs.glsl");
+pub const POINT_SHADOW_FS_SHADER_SOURCE: &str = include_str!("shaders/point_shadow.fs.glsl");
+
+/// Shader import, see [`crate::shadows::POINT_SHADOWS_IMPORT`].
+pub const POINT_SHADOWS_GLSL_SOURCE: &str = include_str!("shaders/point_shadows.glsl");
+
+pub const MULTI_VIEW_VS_SHADER_SOURCE: &str = include_str!("shaders/multi_view.vs.glsl");
+
+/// Shader import, see [`crate::multi_view::MULTI_VIEW_IMPORT`].
+pub const MULTI_VIEW_GLSL_SOURCE: &str = include_str!("shaders/multi_view.glsl");
+
+/// Shader import, see [`crate::virtual_texture::VIRTUAL_TEXTURE_IMPORT`].
+pub const VIRTUAL_TEXTURE_GLSL_SOURCE: &str = include_str!("shaders/virtual_texture.glsl");
+
+/// Shader import, see [`crate::channel_packing::PACKED_MATERIAL_IMPORT`].
+pub const PACKED_MATERIAL_GLSL_SOURCE: &str = include_str!("shaders/packed_material.glsl");
+
+/// Prepended to each radix sort pass, see [`crate::gpu_sort::GpuRadixSort`].
+pub const RADIX_SORT_GLSL_SOURCE: &str = include_str!("shaders/radix_sort.glsl");
+pub const RADIX_SORT_COUNT_CS_SHADER_SOURCE: &str = include_str!("shaders/radix_sort_count.cs.glsl");
+pub const RADIX_SORT_SCATTER_CS_SHADER_SOURCE: &str = include_str!("shaders/radix_sort_scatter.cs.glsl");
+
+pub const PREFIX_SUM_BLOCK_CS_SHADER_SOURCE: &str = include_str!("shaders/prefix_sum_block.cs.glsl");
+pub const PREFIX_SUM_ADD_CS_SHADER_SOURCE: &str = include_str!("shaders/prefix_sum_add.cs.glsl");
+
+pub const IMPOSTOR_VS_SHADER_SOURCE: &str = include_str!("shaders/impostor.vs.glsl");
+pub const IMPOSTOR_FS_SHADER_SOURCE: &str = include_str!("shaders/impostor.fs.glsl");
+
+/// 64x64 single channel blue noise, generated with void-and-cluster.
+pub const BLUE_NOISE_PNG: &[u8] = include_bytes!("shaders/blue_noise.png");
 
 
 
@@ -0,0 +1,51 @@
+layout(local_size_x=256) in;
+
+layout(binding=0) uniform P {
+	float u_min_log_luminance;
+	float u_log_luminance_range;
+};
+
+layout(binding=0) buffer Histogram {
+	uint histogram[256];
+};
+
+layout(binding=1) buffer Result {
+	float average_log_luminance;
+	uint num_metered_pixels;
+};
+
+
+shared float s_weighted_bins[256];
+shared uint s_counts[256];
+
+
+void main() {
+	uint bin = gl_LocalInvocationIndex;
+	uint count = histogram[bin];
+
+	// Clear for next frame.
+	histogram[bin] = 0;
+
+	// Black pixels are excluded from the average entirely.
+	s_weighted_bins[bin] = float(count) * float(bin);
+	s_counts[bin] = bin == 0 ? 0 : count;
+
+	barrier();
+
+	for (uint stride = 128; stride > 0; stride >>= 1) {
+		if (bin < stride) {
+			s_weighted_bins[bin] += s_weighted_bins[bin + stride];
+			s_counts[bin] += s_counts[bin + stride];
+		}
+
+		barrier();
+	}
+
+	if (bin == 0) {
+		uint total = s_counts[0];
+		float average_bin = total > 0 ? s_weighted_bins[0] / float(total) : 1.0;
+
+		average_log_luminance = u_min_log_luminance + (average_bin - 1.0) / 254.0 * u_log_luminance_range;
+		num_metered_pixels = total;
+	}
+}
@@ -0,0 +1,44 @@
+layout(local_size_x=16, local_size_y=16) in;
+
+layout(binding=0) uniform sampler2D u_source;
+
+layout(binding=0) uniform P {
+	float u_min_log_luminance;
+	float u_log_luminance_range;
+};
+
+layout(binding=0) buffer Histogram {
+	uint histogram[256];
+};
+
+
+shared uint s_histogram[256];
+
+
+// Bin 0 is reserved for pixels too dark to meaningfully contribute to the average.
+uint luminance_to_bin(float luminance) {
+	if (luminance < 0.0001) {
+		return 0;
+	}
+
+	float t = clamp((log2(luminance) - u_min_log_luminance) / u_log_luminance_range, 0.0, 1.0);
+	return uint(t * 254.0 + 1.0);
+}
+
+
+void main() {
+	s_histogram[gl_LocalInvocationIndex] = 0;
+	barrier();
+
+	ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+
+	if (all(lessThan(pixel, textureSize(u_source, 0)))) {
+		vec3 color = texelFetch(u_source, pixel, 0).rgb;
+		float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+		atomicAdd(s_histogram[luminance_to_bin(luminance)], 1);
+	}
+
+	barrier();
+
+	atomicAdd(histogram[gl_LocalInvocationIndex], s_histogram[gl_LocalInvocationIndex]);
+}
@@ -0,0 +1,38 @@
+in Vertex {
+	vec4 v_color;
+	vec2 v_uv;
+};
+
+out vec4 o_color;
+
+layout(binding=0) uniform sampler2D u_texture;
+layout(binding=1) uniform sampler3D u_lut;
+
+layout(binding=0) uniform P {
+	float u_blend;
+};
+
+
+vec3 linear_to_srgb(vec3 c) {
+	return mix(c * 12.92, 1.055 * pow(c, vec3(1.0/2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec3 srgb_to_linear(vec3 c) {
+	return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+
+void main() {
+	vec4 color = texture(u_texture, v_uv);
+
+	// LUTs are authored against display referred (sRGB encoded) color.
+	vec3 encoded = linear_to_srgb(clamp(color.rgb, 0.0, 1.0));
+
+	// Remap so that 0 and 1 land on the centers of the edge texels.
+	float lut_size = float(textureSize(u_lut, 0).x);
+	vec3 lut_uvw = encoded * ((lut_size - 1.0) / lut_size) + 0.5 / lut_size;
+
+	vec3 graded = srgb_to_linear(texture(u_lut, lut_uvw).rgb);
+
+	o_color = vec4(mix(color.rgb, graded, u_blend), color.a);
+}
@@ -0,0 +1,36 @@
+in Vertex {
+	vec4 v_color;
+	vec2 v_uv;
+};
+
+out vec4 o_color;
+
+layout(binding=0) uniform sampler2D u_depth;
+
+layout(binding=0) uniform P {
+	// 0: raw, 1: remap [a, b] to [0, 1], 2: linearize with near plane a and far plane b
+	float u_mode;
+	float u_a;
+	float u_b;
+};
+
+
+void main() {
+	float depth = texture(u_depth, v_uv).r;
+	float value = depth;
+
+	if (u_mode > 1.5) {
+#ifdef TOYBOX_REVERSE_Z
+		float ndc_z = 1.0 - depth * 2.0;
+#else
+		float ndc_z = depth * 2.0 - 1.0;
+#endif
+		float view_z = 2.0 * u_a * u_b / (u_b + u_a - ndc_z * (u_b - u_a));
+		value = (view_z - u_a) / (u_b - u_a);
+
+	} else if (u_mode > 0.5) {
+		value = (depth - u_a) / max(u_b - u_a, 1e-6);
+	}
+
+	o_color = vec4(vec3(clamp(value, 0.0, 1.0)), 1.0);
+}
@@ -0,0 +1,37 @@
+in Vertex {
+	vec4 v_color;
+	vec2 v_uv;
+};
+
+out vec4 o_color;
+
+layout(binding=0) uniform sampler2D u_texture;
+layout(binding=1) uniform sampler2D u_blue_noise;
+
+layout(binding=0) uniform P {
+	// Number of quantization steps in the output - e.g., 255 for an 8b target.
+	float u_output_steps;
+};
+
+
+vec3 linear_to_srgb(vec3 c) {
+	return mix(c * 12.92, 1.055 * pow(c, vec3(1.0/2.4)) - 0.055, step(0.0031308, c));
+}
+
+vec3 srgb_to_linear(vec3 c) {
+	return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), step(0.04045, c));
+}
+
+
+void main() {
+	vec4 color = texture(u_texture, v_uv);
+
+	ivec2 noise_size = textureSize(u_blue_noise, 0);
+	float noise = texelFetch(u_blue_noise, ivec2(gl_FragCoord.xy) % noise_size, 0).r;
+
+	// Quantization happens after sRGB encoding, so dither in that space.
+	vec3 encoded = linear_to_srgb(clamp(color.rgb, 0.0, 1.0));
+	encoded += (noise - 0.5) / u_output_steps;
+
+	o_color = vec4(srgb_to_linear(clamp(encoded, 0.0, 1.0)), color.a);
+}
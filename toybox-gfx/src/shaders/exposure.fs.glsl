@@ -0,0 +1,18 @@
+in Vertex {
+	vec4 v_color;
+	vec2 v_uv;
+};
+
+out vec4 o_color;
+
+layout(binding=0) uniform sampler2D u_texture;
+
+layout(binding=0) uniform P {
+	float u_exposure;
+};
+
+
+void main() {
+	vec4 color = texture(u_texture, v_uv);
+	o_color = vec4(color.rgb * u_exposure, color.a);
+}
@@ -0,0 +1,19 @@
+in Vertex {
+	vec2 v_uv;
+	flat int v_layer;
+};
+
+out vec4 o_color;
+
+layout(binding=0) uniform sampler2DArray u_atlas;
+
+void main() {
+	vec4 color = texture(u_atlas, vec3(v_uv, float(v_layer)));
+
+	// Alpha tested so impostors can be drawn with the opaque pass.
+	if (color.a < 0.5) {
+		discard;
+	}
+
+	o_color = vec4(color.rgb, 1.0);
+}
@@ -0,0 +1,78 @@
+// See gfx::impostor::ImpostorRenderer
+
+struct Instance {
+	vec4 position_scale;
+};
+
+
+layout(binding=0) uniform ImpostorBlock {
+	mat4 u_projection_view;
+	vec4 u_camera_position;
+
+	// xyz: bounds center relative to the instance origin, w: bounds radius
+	vec4 u_center_radius;
+
+	// x: yaw steps, y: num pitches
+	vec4 u_params;
+	vec4 u_pitches[2];
+};
+
+layout(binding=0) readonly buffer I {
+	Instance s_instances[];
+};
+
+
+out OutVertex {
+	vec2 v_uv;
+	flat int v_layer;
+};
+
+
+const float TAU = 6.28318530718;
+
+const vec2 CORNERS[6] = vec2[](
+	vec2(-1.0, -1.0), vec2(1.0, -1.0), vec2(1.0, 1.0),
+	vec2(-1.0, -1.0), vec2(1.0, 1.0), vec2(-1.0, 1.0)
+);
+
+float pitch_at(int index) {
+	return u_pitches[index / 4][index % 4];
+}
+
+void main() {
+	Instance instance = s_instances[gl_InstanceID];
+	float scale = instance.position_scale.w;
+
+	vec3 center = instance.position_scale.xyz + u_center_radius.xyz * scale;
+	float radius = u_center_radius.w * scale;
+
+	vec3 to_camera = normalize(u_camera_position.xyz - center);
+
+	// Pick the nearest baked view.
+	int yaw_steps = int(u_params.x);
+	float yaw = atan(to_camera.x, to_camera.z);
+	int yaw_index = int(round(yaw / TAU * float(yaw_steps)));
+	yaw_index = ((yaw_index % yaw_steps) + yaw_steps) % yaw_steps;
+
+	float pitch = asin(clamp(to_camera.y, -1.0, 1.0));
+	int pitch_index = 0;
+	for (int index = 1; index < int(u_params.y); index++) {
+		if (abs(pitch_at(index) - pitch) < abs(pitch_at(pitch_index) - pitch)) {
+			pitch_index = index;
+		}
+	}
+
+	// Must match `impostor_basis`.
+	vec3 forward = -to_camera;
+	vec3 up_hint = abs(forward.y) > 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(0.0, 1.0, 0.0);
+	vec3 right = normalize(cross(forward, up_hint));
+	vec3 up = cross(right, forward);
+
+	vec2 corner = CORNERS[gl_VertexID];
+	vec3 world_pos = center + (right * corner.x + up * corner.y) * radius;
+
+	gl_Position = u_projection_view * vec4(world_pos, 1.0);
+
+	v_uv = corner * 0.5 + 0.5;
+	v_layer = pitch_index * yaw_steps + yaw_index;
+}
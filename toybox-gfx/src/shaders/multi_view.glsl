@@ -0,0 +1,25 @@
+// Helpers for gfx::multi_view::MultiView - only usable in vertex shaders.
+// Draws are instanced once per view, so `multi_view_instance` should be used in place of gl_InstanceID.
+
+int multi_view_index(MultiViewUniforms views) {
+	return gl_InstanceID % int(views.params.x);
+}
+
+int multi_view_instance(MultiViewUniforms views) {
+	return gl_InstanceID / int(views.params.x);
+}
+
+// Returns the clip space position for the current view, and routes the primitive to its viewport or layer.
+vec4 multi_view_position(MultiViewUniforms views, vec3 world_pos) {
+	int view = multi_view_index(views);
+
+#ifdef GL_ARB_shader_viewport_layer_array
+	if (views.params.y > 0.5) {
+		gl_Layer = view;
+	} else {
+		gl_ViewportIndex = view + 1;
+	}
+#endif
+
+	return views.projection_views[view] * vec4(world_pos, 1.0);
+}
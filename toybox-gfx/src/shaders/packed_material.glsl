@@ -0,0 +1,43 @@
+// Helpers for gfx::channel_packing - reads material maps from a texture packed according to a PackingConvention.
+// PACKED_*_CHANNEL and PACKED_*_DEFAULT are defined by the convention. Channels it doesn't include read as their default.
+
+struct PackedMaterial {
+	float occlusion;
+	float roughness;
+	float metalness;
+	float height;
+};
+
+PackedMaterial unpack_material(vec4 texel) {
+	PackedMaterial material;
+
+#if PACKED_OCCLUSION_CHANNEL >= 0
+	material.occlusion = texel[PACKED_OCCLUSION_CHANNEL];
+#else
+	material.occlusion = PACKED_OCCLUSION_DEFAULT;
+#endif
+
+#if PACKED_ROUGHNESS_CHANNEL >= 0
+	material.roughness = texel[PACKED_ROUGHNESS_CHANNEL];
+#else
+	material.roughness = PACKED_ROUGHNESS_DEFAULT;
+#endif
+
+#if PACKED_METALNESS_CHANNEL >= 0
+	material.metalness = texel[PACKED_METALNESS_CHANNEL];
+#else
+	material.metalness = PACKED_METALNESS_DEFAULT;
+#endif
+
+#if PACKED_HEIGHT_CHANNEL >= 0
+	material.height = texel[PACKED_HEIGHT_CHANNEL];
+#else
+	material.height = PACKED_HEIGHT_DEFAULT;
+#endif
+
+	return material;
+}
+
+PackedMaterial sample_packed_material(sampler2D packed_image, vec2 uv) {
+	return unpack_material(texture(packed_image, uv));
+}
@@ -0,0 +1,22 @@
+in Vertex {
+	vec3 v_world_pos;
+};
+
+layout(binding=0) uniform P {
+	mat4 u_projection_view;
+	vec4 u_light_position_far;
+};
+
+
+// Store linear distance from the light rather than perspective depth, so that sampling doesn't need to know
+// which face a direction falls in.
+void main() {
+	float distance = length(v_world_pos - u_light_position_far.xyz);
+	float depth = distance / u_light_position_far.w;
+
+#ifdef TOYBOX_REVERSE_Z
+	gl_FragDepth = 1.0 - depth;
+#else
+	gl_FragDepth = depth;
+#endif
+}
@@ -0,0 +1,28 @@
+struct Vertex {
+	vec3 pos;
+	uint uv_packed;
+	uvec2 color_packed;
+	uvec2 _padding;
+};
+
+
+layout(binding=0) uniform P {
+	mat4 u_projection_view;
+	vec4 u_light_position_far;
+};
+
+layout(binding=0) readonly buffer V {
+	Vertex s_vertices[];
+};
+
+
+out OutVertex {
+	vec3 v_world_pos;
+};
+
+void main() {
+	Vertex vertex = s_vertices[gl_VertexID];
+
+	gl_Position = u_projection_view * vec4(vertex.pos.xyz, 1.0);
+	v_world_pos = vertex.pos.xyz;
+}
@@ -0,0 +1,39 @@
+// Sampling helpers for gfx::shadows::PointShadowMap.
+// Cube maps should be sampled with a nearest sampler, and store distance from the light divided by `far`.
+
+float point_shadow_distance(samplerCube shadow_map, vec3 direction, float far) {
+	float depth = texture(shadow_map, direction).r;
+
+#ifdef TOYBOX_REVERSE_Z
+	depth = 1.0 - depth;
+#endif
+
+	return depth * far;
+}
+
+// Returns 1.0 for fully lit, 0.0 for fully shadowed.
+float sample_point_shadow(samplerCube shadow_map, vec3 world_pos, vec3 light_position, float far, float bias) {
+	vec3 light_to_pos = world_pos - light_position;
+	float occluder_distance = point_shadow_distance(shadow_map, light_to_pos, far);
+	return step(length(light_to_pos) - bias, occluder_distance);
+}
+
+// As above, but averaged over 8 samples with directions offset by `radius` world units.
+float sample_point_shadow_pcf(samplerCube shadow_map, vec3 world_pos, vec3 light_position, float far, float bias, float radius) {
+	vec3 light_to_pos = world_pos - light_position;
+	float distance = length(light_to_pos);
+
+	const vec3 offsets[8] = vec3[](
+		vec3( 1, 1, 1), vec3( 1,-1, 1), vec3(-1,-1, 1), vec3(-1, 1, 1),
+		vec3( 1, 1,-1), vec3( 1,-1,-1), vec3(-1,-1,-1), vec3(-1, 1,-1)
+	);
+
+	float lit = 0.0;
+
+	for (int i = 0; i < 8; i++) {
+		float occluder_distance = point_shadow_distance(shadow_map, light_to_pos + offsets[i] * radius, far);
+		lit += step(distance - bias, occluder_distance);
+	}
+
+	return lit / 8.0;
+}
@@ -0,0 +1,24 @@
+// Offset each block by the scanned total of all blocks before it.
+
+layout(local_size_x=256) in;
+
+layout(binding=0) uniform P {
+	uint u_count;
+};
+
+layout(binding=0) buffer Data {
+	uint data[];
+};
+
+layout(binding=1) readonly buffer BlockSums {
+	uint block_sums[];
+};
+
+
+void main() {
+	uint index = gl_GlobalInvocationID.x;
+
+	if (index < u_count) {
+		data[index] += block_sums[gl_WorkGroupID.x];
+	}
+}
@@ -0,0 +1,44 @@
+// Exclusive scan of each block in place, writing the total of each block to block_sums.
+
+layout(local_size_x=256) in;
+
+layout(binding=0) uniform P {
+	uint u_count;
+};
+
+layout(binding=0) buffer Data {
+	uint data[];
+};
+
+layout(binding=1) writeonly buffer BlockSums {
+	uint block_sums[];
+};
+
+
+shared uint s_values[256];
+
+
+void main() {
+	uint local = gl_LocalInvocationIndex;
+	uint index = gl_GlobalInvocationID.x;
+
+	uint value = index < u_count ? data[index] : 0;
+
+	s_values[local] = value;
+	barrier();
+
+	for (uint offset = 1; offset < 256; offset <<= 1) {
+		uint addend = local >= offset ? s_values[local - offset] : 0;
+		barrier();
+		s_values[local] += addend;
+		barrier();
+	}
+
+	if (index < u_count) {
+		data[index] = s_values[local] - value;
+	}
+
+	if (local == 255) {
+		block_sums[gl_WorkGroupID.x] = s_values[local];
+	}
+}
@@ -0,0 +1,30 @@
+// Shared between radix sort passes. Prepended to each pass, optionally after `#define RADIX_SORT_KEY_64`.
+
+#define RADIX_SORT_BLOCK_SIZE 256
+#define RADIX_SORT_NUM_BUCKETS 16
+
+#ifdef RADIX_SORT_KEY_64
+	// Low word first.
+	#define SortKey uvec2
+#else
+	#define SortKey uint
+#endif
+
+layout(local_size_x=RADIX_SORT_BLOCK_SIZE) in;
+
+layout(binding=0) uniform P {
+	uint u_count;
+	uint u_shift;
+	uint u_num_blocks;
+	uint u_has_values;
+};
+
+uint key_digit(SortKey key) {
+#ifdef RADIX_SORT_KEY_64
+	uint word = u_shift < 32 ? key.x : key.y;
+	return (word >> (u_shift & 31)) & (RADIX_SORT_NUM_BUCKETS - 1);
+#else
+	return (key >> u_shift) & (RADIX_SORT_NUM_BUCKETS - 1);
+#endif
+}
+
@@ -0,0 +1,36 @@
+// Count digits per block. Histograms are stored digit major, so that a single exclusive prefix sum gives
+// the output offset of each digit in each block.
+
+layout(binding=0) readonly buffer Keys {
+	SortKey keys[];
+};
+
+layout(binding=1) writeonly buffer Histograms {
+	uint histograms[];
+};
+
+
+shared uint s_histogram[RADIX_SORT_NUM_BUCKETS];
+
+
+void main() {
+	uint local = gl_LocalInvocationIndex;
+	uint block = gl_WorkGroupID.x;
+	uint index = block * RADIX_SORT_BLOCK_SIZE + local;
+
+	if (local < RADIX_SORT_NUM_BUCKETS) {
+		s_histogram[local] = 0;
+	}
+
+	barrier();
+
+	if (index < u_count) {
+		atomicAdd(s_histogram[key_digit(keys[index])], 1);
+	}
+
+	barrier();
+
+	if (local < RADIX_SORT_NUM_BUCKETS) {
+		histograms[local * u_num_blocks + block] = s_histogram[local];
+	}
+}
@@ -0,0 +1,101 @@
+// Sort each block locally by digit with four stable 1-bit splits, then write each key to its digit's offset
+// for this block plus its rank within the digit.
+
+layout(binding=0) readonly buffer KeysIn {
+	SortKey keys_in[];
+};
+
+layout(binding=1) readonly buffer Histograms {
+	uint histograms[];
+};
+
+layout(binding=2) writeonly buffer KeysOut {
+	SortKey keys_out[];
+};
+
+layout(binding=3) readonly buffer ValuesIn {
+	uint values_in[];
+};
+
+layout(binding=4) writeonly buffer ValuesOut {
+	uint values_out[];
+};
+
+
+shared SortKey s_keys[RADIX_SORT_BLOCK_SIZE];
+shared uint s_values[RADIX_SORT_BLOCK_SIZE];
+shared uint s_digits[RADIX_SORT_BLOCK_SIZE];
+shared uint s_scan[RADIX_SORT_BLOCK_SIZE];
+shared uint s_digit_start[RADIX_SORT_NUM_BUCKETS];
+
+
+uint exclusive_scan(uint value, out uint total) {
+	uint local = gl_LocalInvocationIndex;
+
+	s_scan[local] = value;
+	barrier();
+
+	for (uint offset = 1; offset < RADIX_SORT_BLOCK_SIZE; offset <<= 1) {
+		uint addend = local >= offset ? s_scan[local - offset] : 0;
+		barrier();
+		s_scan[local] += addend;
+		barrier();
+	}
+
+	total = s_scan[RADIX_SORT_BLOCK_SIZE - 1];
+	uint result = s_scan[local] - value;
+	barrier();
+
+	return result;
+}
+
+
+void main() {
+	uint local = gl_LocalInvocationIndex;
+	uint block = gl_WorkGroupID.x;
+	uint index = block * RADIX_SORT_BLOCK_SIZE + local;
+	bool valid = index < u_count;
+
+	SortKey key = valid ? keys_in[index] : SortKey(0);
+	uint value = valid && u_has_values != 0 ? values_in[index] : 0;
+
+	// Out of range elements are at the end of the block, so treating them as the largest digit keeps them there.
+	uint digit = valid ? key_digit(key) : RADIX_SORT_NUM_BUCKETS - 1;
+
+	for (uint bit = 0; bit < 4; bit++) {
+		uint is_set = (digit >> bit) & 1;
+
+		uint num_unset;
+		uint unset_before = exclusive_scan(1 - is_set, num_unset);
+		uint new_position = is_set == 0 ? unset_before : num_unset + local - unset_before;
+
+		s_keys[new_position] = key;
+		s_values[new_position] = value;
+		s_digits[new_position] = digit;
+		barrier();
+
+		key = s_keys[local];
+		value = s_values[local];
+		digit = s_digits[local];
+		barrier();
+	}
+
+	if (local == 0 || s_digits[local - 1] != digit) {
+		s_digit_start[digit] = local;
+	}
+
+	barrier();
+
+	uint num_valid = min(RADIX_SORT_BLOCK_SIZE, u_count - block * RADIX_SORT_BLOCK_SIZE);
+	if (local >= num_valid) {
+		return;
+	}
+
+	uint output_index = histograms[digit * u_num_blocks + block] + local - s_digit_start[digit];
+
+	keys_out[output_index] = key;
+
+	if (u_has_values != 0) {
+		values_out[output_index] = value;
+	}
+}
@@ -0,0 +1,56 @@
+// Cascade selection and PCF for gfx::shadows::CascadedShadowMap.
+// Expects `ShadowCascadeUniforms` to be bound, and the cascade depth array to be sampled with a nearest sampler.
+
+int select_shadow_cascade(ShadowCascadeUniforms cascades, float view_depth) {
+	int num_cascades = int(cascades.params.x);
+
+	for (int i = 0; i < num_cascades; i++) {
+		if (view_depth < cascades.split_depths[i]) {
+			return i;
+		}
+	}
+
+	return -1;
+}
+
+// Returns 1.0 for fully lit, 0.0 for fully shadowed.
+float sample_shadow_cascade(sampler2DArray shadow_map, ShadowCascadeUniforms cascades, int cascade, vec3 world_pos) {
+	if (cascade < 0) {
+		return 1.0;
+	}
+
+	vec4 light_clip = cascades.projection_views[cascade] * vec4(world_pos, 1.0);
+	vec3 light_ndc = light_clip.xyz / light_clip.w;
+	vec2 light_uv = light_ndc.xy * 0.5 + 0.5;
+
+#ifdef TOYBOX_REVERSE_Z
+	// Depth is already in [0, 1], but with near at 1.
+	float depth = 1.0 - light_ndc.z;
+#else
+	float depth = light_ndc.z * 0.5 + 0.5;
+#endif
+
+	float depth_bias = cascades.params.y;
+	float texel_size = cascades.params.z;
+
+	float lit = 0.0;
+
+	for (int y = -1; y <= 1; y++) {
+		for (int x = -1; x <= 1; x++) {
+			vec2 uv = light_uv + vec2(x, y) * texel_size;
+			float occluder_depth = texture(shadow_map, vec3(uv, float(cascade))).r;
+
+#ifdef TOYBOX_REVERSE_Z
+			occluder_depth = 1.0 - occluder_depth;
+#endif
+
+			lit += step(depth - depth_bias, occluder_depth);
+		}
+	}
+
+	return lit / 9.0;
+}
+
+float sample_shadow(sampler2DArray shadow_map, ShadowCascadeUniforms cascades, vec3 world_pos, float view_depth) {
+	return sample_shadow_cascade(shadow_map, cascades, select_shadow_cascade(cascades, view_depth), world_pos);
+}
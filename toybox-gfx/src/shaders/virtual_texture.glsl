@@ -0,0 +1,74 @@
+// Helpers for gfx::virtual_texture::VirtualTexture - only usable in fragment shaders.
+// The page table and image should be bound with VirtualTexture::bind, which also binds the feedback buffer.
+
+layout(std430, binding=7) buffer VirtualTextureFeedback {
+	uint vt_feedback_bits[];
+};
+
+// Number of pages covering `level`.
+ivec2 vt_level_pages(VirtualTextureInfo vt, int level) {
+	return max(ivec2(vt.size.zw) >> level, ivec2(1));
+}
+
+// Position within the page grid of `level`, in pages.
+vec2 vt_page_position(VirtualTextureInfo vt, vec2 uv, int level) {
+	return fract(uv) * vt.size.zw / float(1 << level);
+}
+
+// The mip level that would be sampled at `uv` if everything were resident.
+float vt_desired_lod(VirtualTextureInfo vt, vec2 uv) {
+	vec2 dx = dFdx(uv * vt.size.xy);
+	vec2 dy = dFdy(uv * vt.size.xy);
+	float max_length_sq = max(dot(dx, dx), dot(dy, dy));
+	return clamp(0.5 * log2(max(max_length_sq, 1e-8)), 0.0, vt.params.x - 1.0);
+}
+
+// Flags the page containing `uv` at `level` as needed, to be read back a couple of frames later.
+void vt_request_page(VirtualTextureInfo vt, vec2 uv, int level) {
+	int index = 0;
+	for (int prev_level = 0; prev_level < level; prev_level++) {
+		ivec2 pages = vt_level_pages(vt, prev_level);
+		index += pages.x * pages.y;
+	}
+
+	ivec2 pages = vt_level_pages(vt, level);
+	ivec2 page = clamp(ivec2(vt_page_position(vt, uv, level)), ivec2(0), pages - 1);
+	index += page.y * pages.x + page.x;
+
+	atomicOr(vt_feedback_bits[index / 32], 1u << uint(index % 32));
+}
+
+// Samples the finest resident level at or above the desired level, and requests the desired page.
+// Returns transparent black if nothing is resident at all.
+vec4 vt_sample(VirtualTextureInfo vt, usampler2D page_table, sampler2D image, vec2 uv) {
+	float lod = vt_desired_lod(vt, uv);
+	int desired_level = int(lod);
+	int num_levels = int(vt.params.x);
+
+	// Only one in every 4x4 block of fragments writes feedback, since pages are much larger than that anyway.
+	if (vt.params.w > 0.5 && all(equal(ivec2(gl_FragCoord.xy) & 3, ivec2(0)))) {
+		vt_request_page(vt, uv, desired_level);
+	}
+
+	for (int level = desired_level; level < num_levels; level++) {
+		vec2 page_position = vt_page_position(vt, uv, level);
+		ivec2 page = clamp(ivec2(page_position), ivec2(0), vt_level_pages(vt, level) - 1);
+
+		uvec2 entry = texelFetch(page_table, page, level).xy;
+		if (entry.x == 0u) {
+			continue;
+		}
+
+		// Sparse image - the page is committed, so sample it. Only this level is known to be committed, so the lod can't
+		// be blended with the next one.
+		if (vt.params.z < 0.5) {
+			return textureLod(image, uv, float(level));
+		}
+
+		// Atlas - entries are slot + 1. Filtering doesn't cross page edges, so seams can be visible.
+		vec2 atlas_uv = (vec2(entry - 1u) + fract(page_position)) / vt.params.z;
+		return textureLod(image, atlas_uv, 0.0);
+	}
+
+	return vec4(0.0);
+}
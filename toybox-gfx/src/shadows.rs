@@ -0,0 +1,498 @@
+use crate::prelude::*;
+use crate::{
+	System, ResourceManager, FrameStage, CommandGroupEncoder, ImageHandle, ShaderHandle, ImageFormat, ImageClearPolicy,
+	CreateImageRequest, FramebufferDescription, Frustum,
+	glsl::{GlslStruct, ShaderImports},
+	shaders,
+};
+
+
+pub const MAX_CASCADES: usize = 4;
+
+/// Name of the shader import providing `ShadowCascadeUniforms`, `select_shadow_cascade` and `sample_shadow`.
+pub const SHADOW_CASCADES_IMPORT: &str = "shadow_cascades";
+
+/// Name of the shader import providing `sample_point_shadow` and `sample_point_shadow_pcf`.
+pub const POINT_SHADOWS_IMPORT: &str = "point_shadows";
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CascadeSplitScheme {
+	Uniform,
+	Logarithmic,
+
+	/// Blend between logarithmic (1.0) and uniform (0.0) splits.
+	/// https://developer.nvidia.com/gpugems/gpugems3/part-ii-light-and-shadows/chapter-10-parallel-split-shadow-maps-programmable-gpus
+	Practical(f32),
+
+	/// Far distance of each cascade in view space. Should have an entry per cascade.
+	Manual(Vec<f32>),
+}
+
+impl CascadeSplitScheme {
+	/// Far distance of each cascade.
+	pub fn split_distances(&self, num_cascades: usize, near: f32, far: f32) -> Vec<f32> {
+		let uniform = |i: usize| near + (far - near) * i as f32 / num_cascades as f32;
+		let logarithmic = |i: usize| near * (far / near).powf(i as f32 / num_cascades as f32);
+
+		match self {
+			CascadeSplitScheme::Uniform => (1..=num_cascades).map(uniform).collect(),
+			CascadeSplitScheme::Logarithmic => (1..=num_cascades).map(logarithmic).collect(),
+			CascadeSplitScheme::Practical(lambda) => (1..=num_cascades)
+				.map(|i| logarithmic(i) * lambda + uniform(i) * (1.0 - lambda))
+				.collect(),
+
+			CascadeSplitScheme::Manual(splits) => (0..num_cascades)
+				.map(|i| splits.get(i).copied().unwrap_or(far).min(far))
+				.collect(),
+		}
+	}
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CascadedShadowSettings {
+	/// At most [`MAX_CASCADES`].
+	pub num_cascades: usize,
+	pub resolution: i32,
+	pub split_scheme: CascadeSplitScheme,
+
+	/// Cascades won't extend beyond this distance from the camera, even if the camera far plane does.
+	pub shadow_distance: f32,
+
+	/// How far towards the light to extend each cascade, so casters outside of the view still cast shadows into it.
+	pub caster_margin: f32,
+
+	pub depth_bias: f32,
+
+	/// Cascade `i` is rendered in `FrameStage::BeforeMain(first_stage + i)`.
+	pub first_stage: i8,
+}
+
+impl Default for CascadedShadowSettings {
+	fn default() -> Self {
+		CascadedShadowSettings {
+			num_cascades: 4,
+			resolution: 2048,
+			split_scheme: CascadeSplitScheme::Practical(0.75),
+			shadow_distance: 100.0,
+			caster_margin: 50.0,
+			depth_bias: 0.001,
+			first_stage: -100,
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct ShadowCascade {
+	pub index: usize,
+
+	/// View space distances covered by this cascade.
+	pub near: f32,
+	pub far: f32,
+
+	pub projection_view: Mat4,
+
+	/// For culling casters.
+	pub frustum: Frustum,
+
+	/// Size of a shadow map texel in world units.
+	pub texel_world_size: f32,
+}
+
+
+/// Matches `ShadowCascadeUniforms` in the [`SHADOW_CASCADES_IMPORT`] shader import.
+#[derive(Debug, Copy, Clone, GlslStruct)]
+#[glsl(crate = "crate")]
+#[repr(C)]
+pub struct ShadowCascadeUniforms {
+	pub projection_views: [Mat4; MAX_CASCADES],
+
+	/// Far distance of each cascade.
+	pub split_depths: Vec4,
+
+	/// x: num cascades, y: depth bias, z: texel size in uv space
+	pub params: Vec4,
+}
+
+
+/// Frustum-fitted cascaded shadow maps for a directional light.
+/// Call [`CascadedShadowMap::update`] once the camera is known, then [`CascadedShadowMap::render_cascades`] to encode
+/// caster draws. Receivers should `#import shadow_cascades`, and bind [`CascadedShadowMap::uniforms`] and
+/// [`CascadedShadowMap::depth_image`].
+pub struct CascadedShadowMap {
+	pub settings: CascadedShadowSettings,
+	depth_image: ImageHandle,
+	cascades: Vec<ShadowCascade>,
+	reverse_z: bool,
+}
+
+impl CascadedShadowMap {
+	pub fn new(gfx: &mut System, settings: CascadedShadowSettings) -> CascadedShadowMap {
+		assert!((1..=MAX_CASCADES).contains(&settings.num_cascades), "Shadow cascade count must be between 1 and {MAX_CASCADES}");
+
+		let depth_image = Self::request_depth_image(gfx, &settings);
+
+		CascadedShadowMap {
+			settings,
+			depth_image,
+			cascades: Vec::new(),
+			reverse_z: gfx.core.is_reverse_z(),
+		}
+	}
+
+	/// Change the resolution of each cascade, recreating the depth image. The old image is released, so rebind
+	/// [`CascadedShadowMap::depth_image`] afterwards.
+	pub fn set_resolution(&mut self, gfx: &mut System, resolution: i32) {
+		if self.settings.resolution != resolution {
+			self.settings.resolution = resolution;
+
+			let old_depth_image = std::mem::replace(&mut self.depth_image, Self::request_depth_image(gfx, &self.settings));
+			gfx.resource_manager.release_image(&gfx.core, old_depth_image);
+		}
+	}
+
+	fn request_depth_image(gfx: &mut System, settings: &CascadedShadowSettings) -> ImageHandle {
+		assert!(settings.resolution > 0, "Shadow resolution must be positive");
+
+		let size = Vec2i::splat(settings.resolution);
+		gfx.resource_manager.request(
+			CreateImageRequest::fixed_2d_array("shadow cascades", size, settings.num_cascades as u32, ImageFormat::Depth32)
+				.clear_policy(ImageClearPolicy::DefaultAtFrameStart))
+	}
+
+	/// Array image with a layer per cascade.
+	pub fn depth_image(&self) -> ImageHandle {
+		self.depth_image
+	}
+
+	pub fn cascades(&self) -> &[ShadowCascade] {
+		&self.cascades
+	}
+
+	/// Fit cascades to the view frustum of a camera. `projection_view`, `near` and `far` should describe the
+	/// camera's standard perspective projection. `light_direction` is the direction light travels in.
+	pub fn update(&mut self, projection_view: &Mat4, near: f32, far: f32, light_direction: Vec3) {
+		let settings = &self.settings;
+		let shadow_far = far.min(settings.shadow_distance);
+
+		let splits = settings.split_scheme.split_distances(settings.num_cascades, near, shadow_far);
+		let inverse_projection_view = projection_view.inverse();
+
+		// Inverse of the perspective depth mapping, so view distances can be unprojected.
+		let ndc_depth = |distance: f32| (far + near) / (far - near) - 2.0 * far * near / ((far - near) * distance);
+
+		let light_view = light_view_matrix(light_direction);
+		let [right, up, back, _] = light_view.rows.map(|row| Vec3::new(row.x, row.y, row.z));
+
+		let mut cascade_near = near;
+		self.cascades.clear();
+
+		for (index, &cascade_far) in splits.iter().enumerate() {
+			let mut corners = [Vec3::zero(); 8];
+			for (corner_index, corner) in corners.iter_mut().enumerate() {
+				let x = if corner_index & 1 == 0 { -1.0 } else { 1.0 };
+				let y = if corner_index & 2 == 0 { -1.0 } else { 1.0 };
+				let z = ndc_depth(if corner_index & 4 == 0 { cascade_near } else { cascade_far });
+
+				let Vec4{x, y, z, w} = inverse_projection_view * Vec4::new(x, y, z, 1.0);
+				*corner = Vec3::new(x, y, z) / w;
+			}
+
+			// Fitting a sphere rather than a box keeps the cascade size constant as the camera rotates,
+			// which along with snapping to texels avoids shimmering edges.
+			let center = corners.iter().fold(Vec3::zero(), |sum, &corner| sum + corner) / 8.0;
+			let radius = corners.iter()
+				.map(|&corner| (corner - center).length())
+				.fold(0.0f32, f32::max);
+			let radius = (radius * 16.0).ceil() / 16.0;
+
+			let texel_world_size = 2.0 * radius / settings.resolution as f32;
+			let snap = |value: f32| (value / texel_world_size).floor() * texel_world_size;
+
+			let center_x = snap(center.dot(right));
+			let center_y = snap(center.dot(up));
+			let center_distance = -center.dot(back);
+
+			let projection = Mat4::ortho(
+				center_x - radius, center_x + radius,
+				center_y - radius, center_y + radius,
+				center_distance - radius - settings.caster_margin, center_distance + radius);
+
+			let projection_view = projection * light_view;
+
+			self.cascades.push(ShadowCascade {
+				index,
+				near: cascade_near,
+				far: cascade_far,
+				projection_view: apply_depth_convention(projection_view, self.reverse_z),
+				frustum: Frustum::from_projection_view(&projection_view),
+				texel_world_size,
+			});
+
+			cascade_near = cascade_far;
+		}
+	}
+
+	/// Encode caster draws for each cascade. Each cascade gets its own command group with its layer of
+	/// [`CascadedShadowMap::depth_image`] bound as the rendertarget, and its `projection_view` bound to UBO 0
+	/// to match the standard vertex shader. `draw_casters` can cull against [`ShadowCascade::frustum`].
+	pub fn render_cascades(&self, gfx: &mut System, mut draw_casters: impl FnMut(&ShadowCascade, &mut CommandGroupEncoder<'_>)) {
+		for cascade in self.cascades.iter() {
+			let stage = FrameStage::BeforeMain(self.settings.first_stage.saturating_add(cascade.index as i8));
+			let framebuffer = FramebufferDescription::from(&[self.depth_image]).with_layer(cascade.index as u32);
+
+			let mut group = gfx.frame_encoder.command_group(stage)
+				.annotate(format!("Shadow Cascade {}", cascade.index));
+
+			group.bind_rendertargets(framebuffer);
+			group.bind_shared_ubo(0, &[cascade.projection_view]);
+
+			draw_casters(cascade, &mut *group);
+		}
+	}
+
+	/// For binding to shaders using the [`SHADOW_CASCADES_IMPORT`] import.
+	pub fn uniforms(&self) -> ShadowCascadeUniforms {
+		let mut projection_views = [Mat4::identity(); MAX_CASCADES];
+		let mut split_depths = [f32::MAX; MAX_CASCADES];
+
+		for cascade in self.cascades.iter() {
+			projection_views[cascade.index] = cascade.projection_view;
+			split_depths[cascade.index] = cascade.far;
+		}
+
+		let [x, y, z, w] = split_depths;
+
+		ShadowCascadeUniforms {
+			projection_views,
+			split_depths: Vec4::new(x, y, z, w),
+			params: Vec4::new(self.cascades.len() as f32, self.settings.depth_bias, 1.0 / self.settings.resolution as f32, 0.0),
+		}
+	}
+}
+
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PointShadowSettings {
+	pub resolution: i32,
+	pub near: f32,
+
+	/// Also the maximum distance stored in the shadow map.
+	pub far: f32,
+
+	/// Face `i` is rendered in `FrameStage::BeforeMain(first_stage + i)`.
+	pub first_stage: i8,
+}
+
+impl Default for PointShadowSettings {
+	fn default() -> Self {
+		PointShadowSettings {
+			resolution: 512,
+			near: 0.05,
+			far: 25.0,
+			first_stage: -90,
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct PointShadowFace {
+	/// Cube face index, in the order +X, -X, +Y, -Y, +Z, -Z.
+	pub index: usize,
+	pub projection_view: Mat4,
+
+	/// For culling casters.
+	pub frustum: Frustum,
+}
+
+/// Matches the UBO expected by the point shadow caster shaders. Since `projection_view` comes first, this can also
+/// be bound for shaders expecting only a `projection_view` at UBO 0, like the standard vertex shader.
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct PointShadowUniforms {
+	pub projection_view: Mat4,
+	pub light_position_far: Vec4,
+}
+
+
+/// Omnidirectional shadows for a point light, rendered as six passes into a cube map storing distance from the light.
+/// Call [`PointShadowMap::update`] with the light position, then [`PointShadowMap::render_faces`] to encode caster
+/// draws. Receivers should `#import point_shadows` and sample [`PointShadowMap::depth_image`].
+pub struct PointShadowMap {
+	pub settings: PointShadowSettings,
+	depth_image: ImageHandle,
+
+	caster_vs_shader: ShaderHandle,
+	caster_fs_shader: ShaderHandle,
+
+	light_position: Vec3,
+	faces: Vec<PointShadowFace>,
+	reverse_z: bool,
+}
+
+impl PointShadowMap {
+	pub fn new(gfx: &mut System, settings: PointShadowSettings) -> PointShadowMap {
+		let rm = &mut gfx.resource_manager;
+
+		let depth_image = Self::request_depth_image(rm, settings.resolution);
+
+		let caster_vs_shader = rm.compile_vertex_shader("point shadow vs", shaders::POINT_SHADOW_VS_SHADER_SOURCE);
+		let caster_fs_shader = rm.compile_fragment_shader("point shadow fs", shaders::POINT_SHADOW_FS_SHADER_SOURCE);
+
+		PointShadowMap {
+			settings,
+			depth_image,
+
+			caster_vs_shader,
+			caster_fs_shader,
+
+			light_position: Vec3::zero(),
+			faces: Vec::new(),
+			reverse_z: gfx.core.is_reverse_z(),
+		}
+	}
+
+	/// Cube map storing distance from the light divided by [`PointShadowSettings::far`].
+	pub fn depth_image(&self) -> ImageHandle {
+		self.depth_image
+	}
+
+	/// Change the resolution of each face, recreating the depth image. The old image is released, so rebind
+	/// [`PointShadowMap::depth_image`] afterwards.
+	pub fn set_resolution(&mut self, gfx: &mut System, resolution: i32) {
+		if self.settings.resolution != resolution {
+			self.settings.resolution = resolution;
+
+			let old_depth_image = std::mem::replace(&mut self.depth_image, Self::request_depth_image(&mut gfx.resource_manager, resolution));
+			gfx.resource_manager.release_image(&gfx.core, old_depth_image);
+		}
+	}
+
+	fn request_depth_image(rm: &mut ResourceManager, resolution: i32) -> ImageHandle {
+		assert!(resolution > 0, "Shadow resolution must be positive");
+
+		// Cleared to the far depth, which is the furthest representable distance.
+		rm.request(CreateImageRequest::fixed_cube("point shadow", resolution, ImageFormat::Depth32)
+			.clear_policy(ImageClearPolicy::DefaultAtFrameStart))
+	}
+
+	pub fn faces(&self) -> &[PointShadowFace] {
+		&self.faces
+	}
+
+	/// Vertex and fragment shader for drawing casters with [`StandardVertex`](crate::StandardVertex)s.
+	/// Custom caster shaders must write distance to `gl_FragDepth` the same way.
+	pub fn caster_shaders(&self) -> (ShaderHandle, ShaderHandle) {
+		(self.caster_vs_shader, self.caster_fs_shader)
+	}
+
+	pub fn update(&mut self, light_position: Vec3) {
+		let PointShadowSettings{near, far, ..} = self.settings;
+		let projection = perspective_90(near, far);
+
+		self.light_position = light_position;
+		self.faces = cube_face_directions().into_iter().enumerate()
+			.map(|(index, (forward, up))| {
+				let projection_view = projection * look_along_matrix(light_position, forward, up);
+
+				PointShadowFace {
+					index,
+					projection_view: apply_depth_convention(projection_view, self.reverse_z),
+					frustum: Frustum::from_projection_view(&projection_view),
+				}
+			})
+			.collect();
+	}
+
+	pub fn uniforms(&self, face: &PointShadowFace) -> PointShadowUniforms {
+		let Vec3{x, y, z} = self.light_position;
+
+		PointShadowUniforms {
+			projection_view: face.projection_view,
+			light_position_far: Vec4::new(x, y, z, self.settings.far),
+		}
+	}
+
+	/// Encode caster draws for each face. Each face gets its own command group with its face of
+	/// [`PointShadowMap::depth_image`] bound as the rendertarget, and [`PointShadowUniforms`] bound to UBO 0.
+	/// `draw_casters` can cull against [`PointShadowFace::frustum`].
+	pub fn render_faces(&self, gfx: &mut System, mut draw_casters: impl FnMut(&PointShadowFace, &mut CommandGroupEncoder<'_>)) {
+		for face in self.faces.iter() {
+			let stage = FrameStage::BeforeMain(self.settings.first_stage.saturating_add(face.index as i8));
+			let framebuffer = FramebufferDescription::from(&[self.depth_image]).with_layer(face.index as u32);
+
+			let mut group = gfx.frame_encoder.command_group(stage)
+				.annotate(format!("Point Shadow Face {}", face.index));
+
+			group.bind_rendertargets(framebuffer);
+			group.bind_shared_ubo(0, &[self.uniforms(face)]);
+
+			draw_casters(face, &mut *group);
+		}
+	}
+}
+
+/// (forward, up) for each cube face, matching GL cube map conventions.
+pub(crate) fn cube_face_directions() -> [(Vec3, Vec3); 6] {
+	[
+		(Vec3::new( 1.0, 0.0, 0.0), Vec3::new(0.0,-1.0, 0.0)),
+		(Vec3::new(-1.0, 0.0, 0.0), Vec3::new(0.0,-1.0, 0.0)),
+		(Vec3::new( 0.0, 1.0, 0.0), Vec3::new(0.0, 0.0, 1.0)),
+		(Vec3::new( 0.0,-1.0, 0.0), Vec3::new(0.0, 0.0,-1.0)),
+		(Vec3::new( 0.0, 0.0, 1.0), Vec3::new(0.0,-1.0, 0.0)),
+		(Vec3::new( 0.0, 0.0,-1.0), Vec3::new(0.0,-1.0, 0.0)),
+	]
+}
+
+// Frustums are extracted from the standard projection - see `Frustum::from_projection_view`.
+pub(crate) fn apply_depth_convention(projection_view: Mat4, reverse_z: bool) -> Mat4 {
+	match reverse_z {
+		true => crate::math::reverse_z_projection(&projection_view),
+		false => projection_view,
+	}
+}
+
+pub(crate) fn perspective_90(near: f32, far: f32) -> Mat4 {
+	Mat4::from_rows([
+		Vec4::new(1.0, 0.0, 0.0, 0.0),
+		Vec4::new(0.0, 1.0, 0.0, 0.0),
+		Vec4::new(0.0, 0.0, (far + near) / (near - far), 2.0 * far * near / (near - far)),
+		Vec4::new(0.0, 0.0, -1.0, 0.0),
+	])
+}
+
+pub(crate) fn look_along_matrix(eye: Vec3, forward: Vec3, up: Vec3) -> Mat4 {
+	let right = forward.cross(up).normalize();
+	let up = right.cross(forward);
+
+	Mat4::from_rows([
+		Vec4::new(right.x, right.y, right.z, -right.dot(eye)),
+		Vec4::new(up.x, up.y, up.z, -up.dot(eye)),
+		Vec4::new(-forward.x, -forward.y, -forward.z, forward.dot(eye)),
+		Vec4::new(0.0, 0.0, 0.0, 1.0),
+	])
+}
+
+
+
+/// Rotation only view matrix looking along `direction`.
+pub fn light_view_matrix(direction: Vec3) -> Mat4 {
+	let forward = direction.normalize();
+	let up_hint = match forward.y.abs() > 0.99 {
+		true => Vec3::new(1.0, 0.0, 0.0),
+		false => Vec3::new(0.0, 1.0, 0.0),
+	};
+
+	look_along_matrix(Vec3::zero(), forward, up_hint)
+}
+
+
+pub(crate) fn register_shader_imports(imports: &mut ShaderImports) {
+	let source = ShadowCascadeUniforms::glsl_declarations() + shaders::SHADOW_CASCADES_GLSL_SOURCE;
+	imports.register(SHADOW_CASCADES_IMPORT, source);
+	imports.register(POINT_SHADOWS_IMPORT, shaders::POINT_SHADOWS_GLSL_SOURCE);
+}
@@ -0,0 +1,69 @@
+use crate::command::{DrawCmd, PrimitiveType};
+use crate::FrameStage;
+
+use std::collections::BTreeMap;
+
+
+/// Counters describing what was submitted during a frame.
+#[derive(Debug, Default, Clone)]
+pub struct FrameStats {
+	pub stages: BTreeMap<FrameStage, StageStats>,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct StageStats {
+	pub draw_calls: u32,
+	pub compute_dispatches: u32,
+
+	/// Total instances across all draw calls.
+	pub instances: u32,
+
+	/// Total primitives across all draw calls, taking instancing into account.
+	pub triangles: u64,
+	pub lines: u64,
+	pub points: u64,
+	pub patches: u64,
+}
+
+impl FrameStats {
+	pub fn total(&self) -> StageStats {
+		let mut total = StageStats::default();
+
+		for stats in self.stages.values() {
+			total.draw_calls += stats.draw_calls;
+			total.compute_dispatches += stats.compute_dispatches;
+			total.instances += stats.instances;
+			total.triangles += stats.triangles;
+			total.lines += stats.lines;
+			total.points += stats.points;
+			total.patches += stats.patches;
+		}
+
+		total
+	}
+
+	pub(crate) fn clear(&mut self) {
+		self.stages.clear();
+	}
+
+	pub(crate) fn record_draw(&mut self, stage: FrameStage, cmd: &DrawCmd) {
+		let stats = self.stages.entry(stage).or_default();
+
+		let num_elements = cmd.num_elements as u64;
+		let num_instances = cmd.num_instances as u64;
+
+		stats.draw_calls += 1;
+		stats.instances += cmd.num_instances;
+
+		match cmd.primitive_type {
+			PrimitiveType::Triangles => stats.triangles += num_elements / 3 * num_instances,
+			PrimitiveType::Lines => stats.lines += num_elements / 2 * num_instances,
+			PrimitiveType::Points => stats.points += num_elements * num_instances,
+			PrimitiveType::Patches => stats.patches += num_elements / cmd.patch_vertices.max(1) as u64 * num_instances,
+		}
+	}
+
+	pub(crate) fn record_dispatch(&mut self, stage: FrameStage) {
+		self.stages.entry(stage).or_default().compute_dispatches += 1;
+	}
+}
@@ -0,0 +1,101 @@
+use crate::prelude::*;
+use crate::{
+	System, FrameStage, AnnotatedCommandGroupEncoder, ImageHandle, ImageFormat, ImageClearPolicy,
+	CreateImageRequest, FramebufferDescription,
+};
+
+
+/// A secondary camera that renders into its own image rather than the backbuffer. For minimaps, mirrors,
+/// security cameras and the like.
+///
+/// ```ignore
+/// let mut group = camera.begin(gfx);
+/// group.draw(...);
+/// drop(group);
+///
+/// main_group.draw(...).sampled_image(0, camera.image(), CommonSampler::Linear);
+/// ```
+#[derive(Debug, Clone)]
+pub struct TextureCamera {
+	pub projection_view: Mat4,
+
+	/// Color the target is cleared to at the start of each [`TextureCamera::begin`].
+	pub clear_color: Option<Color>,
+
+	label: String,
+	size: Vec2i,
+	stage: FrameStage,
+
+	color_image: ImageHandle,
+	depth_image: ImageHandle,
+}
+
+impl TextureCamera {
+	/// `label` must be unique per camera, since image requests are deduplicated.
+	/// Commands are encoded in `FrameStage::BeforeMain(stage)`, so every camera needs its own `stage`.
+	pub fn new(gfx: &mut System, label: impl Into<String>, size: Vec2i, stage: i8) -> TextureCamera {
+		assert!(size.x > 0 && size.y > 0, "TextureCamera must have a non-zero size");
+
+		let label = label.into();
+		let rm = &mut gfx.resource_manager;
+
+		let color_image = rm.request(CreateImageRequest::fixed_2d(format!("{label} color"), size, ImageFormat::Srgba8));
+		let depth_image = rm.request(CreateImageRequest::fixed_2d(format!("{label} depth"), size, ImageFormat::Depth)
+			.clear_policy(ImageClearPolicy::DefaultAtFrameStart));
+
+		TextureCamera {
+			projection_view: Mat4::identity(),
+			clear_color: Some(Color::black()),
+
+			label,
+			size,
+			stage: FrameStage::BeforeMain(stage),
+
+			color_image,
+			depth_image,
+		}
+	}
+
+	/// The rendered result, valid for sampling in any stage after this camera's.
+	/// Can be shown in egui with `toybox_egui::image_handle_to_egui`.
+	pub fn image(&self) -> ImageHandle {
+		self.color_image
+	}
+
+	pub fn depth_image(&self) -> ImageHandle {
+		self.depth_image
+	}
+
+	pub fn size(&self) -> Vec2i {
+		self.size
+	}
+
+	pub fn aspect(&self) -> f32 {
+		self.size.x as f32 / self.size.y as f32
+	}
+
+	pub fn stage(&self) -> FrameStage {
+		self.stage
+	}
+
+	/// Start encoding commands for this camera. The target is bound as the rendertarget and `projection_view`
+	/// is bound to UBO 0, matching [`crate::shaders::STANDARD_VS_SHADER_SOURCE`].
+	/// Should only be called once per frame, since it clears the target.
+	pub fn begin<'g>(&self, gfx: &'g mut System) -> AnnotatedCommandGroupEncoder<'g> {
+		let mut group = gfx.frame_encoder.command_group(self.stage)
+			.annotate(self.label.clone());
+
+		if let Some(clear_color) = self.clear_color {
+			let color_image = self.color_image;
+			group.execute(move |core, rm| {
+				if let Some(image_name) = rm.images.get_name(color_image) {
+					core.clear_image_with_color(image_name, clear_color);
+				}
+			});
+		}
+
+		group.bind_rendertargets(FramebufferDescription::from(&[self.color_image, self.depth_image]));
+		group.bind_shared_ubo(0, &[self.projection_view]);
+		group
+	}
+}
@@ -0,0 +1,203 @@
+use crate::prelude::*;
+use crate::command_group::*;
+use crate::command::{Command, draw, compute};
+use crate::upload_heap::{UploadStage, StagedUploadId};
+use crate::bindings::*;
+use crate::resource_manager::{ShaderHandle, arguments::*};
+use crate::FrameEncoder;
+use crate::core::FramebufferAttachment;
+
+
+/// Records commands on another thread, with its own upload staging. Created with [`FrameEncoder::thread_encoder`] and
+/// handed back with [`FrameEncoder::merge`] once recording is done.
+///
+/// At the start of [`crate::System::execute_frame`], merged commands are appended to the frame's command group for the
+/// same stage - after anything encoded directly, then in the order encoders were created - so results are deterministic
+/// regardless of which thread finishes first.
+///
+/// Callbacks can't be recorded, since they often capture non-thread safe state.
+pub struct ThreadEncoder {
+	submission_index: u32,
+	groups: Vec<CommandGroup>,
+	upload_stage: UploadStage,
+}
+
+// SAFETY: Command is only !Send because of Command::Callback, and ThreadGroupEncoder provides no way to record one.
+unsafe impl Send for ThreadEncoder {}
+
+impl ThreadEncoder {
+	pub fn submission_index(&self) -> u32 {
+		self.submission_index
+	}
+
+	pub fn upload(&mut self, data: &impl crate::AsStageableSlice) -> StagedUploadId {
+		self.upload_stage.stage_data(data.as_slice())
+	}
+
+	pub fn command_group(&mut self, stage: FrameStage) -> ThreadGroupEncoder<'_> {
+		let group_index = match self.groups.iter().position(|group| group.stage == stage) {
+			Some(index) => index,
+			None => {
+				self.groups.push(CommandGroup::new(stage));
+				self.groups.len() - 1
+			}
+		};
+
+		ThreadGroupEncoder {
+			enc: CommandGroupEncoder::new(&mut self.groups[group_index], &mut self.upload_stage),
+			annotation_depth: 0,
+		}
+	}
+}
+
+
+/// The subset of [`CommandGroupEncoder`] that is safe to use from a [`ThreadEncoder`].
+pub struct ThreadGroupEncoder<'g> {
+	enc: CommandGroupEncoder<'g>,
+	annotation_depth: u32,
+}
+
+impl<'g> ThreadGroupEncoder<'g> {
+	/// See [`CommandGroupEncoder::annotate`].
+	pub fn annotate(mut self, label: impl Into<String>) -> Self {
+		self.enc.add(Command::PushDebugGroup { label: label.into() });
+		self.annotation_depth += 1;
+		self
+	}
+
+	pub fn upload(&mut self, data: &impl crate::AsStageableSlice) -> StagedUploadId {
+		self.enc.upload(data)
+	}
+
+	pub fn upload_iter<T, I>(&mut self, iter: I) -> StagedUploadId
+		where I: IntoIterator<Item=T>
+			, I::IntoIter: ExactSizeIterator
+			, T: Copy + 'static
+	{
+		self.enc.upload_iter(iter)
+	}
+
+	pub fn bind_shared_buffer(&mut self, target: impl Into<BufferBindTarget>, buffer: impl IntoBufferArgument) {
+		self.enc.bind_shared_buffer(target, buffer);
+	}
+
+	pub fn bind_shared_ubo(&mut self, index: u32, buffer: impl IntoBufferArgument) {
+		self.enc.bind_shared_ubo(index, buffer);
+	}
+
+	pub fn bind_shared_ssbo(&mut self, index: u32, buffer: impl IntoBufferArgument) {
+		self.enc.bind_shared_ssbo(index, buffer);
+	}
+
+	pub fn bind_shared_sampled_image(&mut self, unit: u32, image: impl Into<ImageArgument>, sampler: impl Into<SamplerArgument>) {
+		self.enc.bind_shared_sampled_image(unit, image, sampler);
+	}
+
+	pub fn bind_shared_image(&mut self, unit: u32, image: impl Into<ImageArgument>) {
+		self.enc.bind_shared_image(unit, image);
+	}
+
+	pub fn bind_shared_image_rw(&mut self, unit: u32, image: impl Into<ImageArgument>) {
+		self.enc.bind_shared_image_rw(unit, image);
+	}
+
+	pub fn bind_rendertargets(&mut self, rts: impl Into<FramebufferArgument>) {
+		self.enc.bind_rendertargets(rts);
+	}
+
+	/// See [`CommandGroupEncoder::set_load_op`]. Overrides ops set directly on the frame's group, or by earlier encoders.
+	pub fn set_load_op(&mut self, attachment: FramebufferAttachment, load: LoadOp) {
+		self.enc.set_load_op(attachment, load);
+	}
+
+	/// See [`CommandGroupEncoder::set_store_op`].
+	pub fn set_store_op(&mut self, attachment: FramebufferAttachment, store: StoreOp) {
+		self.enc.set_store_op(attachment, store);
+	}
+
+	pub fn debug_marker(&mut self, label: impl Into<String>) {
+		self.enc.debug_marker(label);
+	}
+
+	pub fn draw(&mut self, vertex_shader: impl Into<ShaderArgument>, fragment_shader: impl Into<ShaderArgument>) -> draw::DrawCmdBuilder<'_> {
+		self.enc.draw(vertex_shader, fragment_shader)
+	}
+
+	pub fn draw_fullscreen(&mut self, fragment_shader: impl Into<Option<ShaderHandle>>) -> draw::DrawCmdBuilder<'_> {
+		self.enc.draw_fullscreen(fragment_shader)
+	}
+
+	pub fn compute(&mut self, compute_shader: impl Into<ShaderArgument>) -> compute::ComputeCmdBuilder<'_> {
+		self.enc.compute(compute_shader)
+	}
+}
+
+impl Drop for ThreadGroupEncoder<'_> {
+	fn drop(&mut self) {
+		for _ in 0..self.annotation_depth {
+			self.enc.add(Command::PopDebugGroup);
+		}
+	}
+}
+
+
+impl FrameEncoder {
+	/// Create an encoder that can be sent to another thread. See [`ThreadEncoder`].
+	pub fn thread_encoder(&mut self) -> ThreadEncoder {
+		let submission_index = self.next_thread_submission_index;
+		self.next_thread_submission_index += 1;
+
+		ThreadEncoder {
+			submission_index,
+			groups: Vec::new(),
+			upload_stage: UploadStage::new(),
+		}
+	}
+
+	/// Hand back a finished [`ThreadEncoder`], to be stitched into the frame at execution.
+	pub fn merge(&mut self, encoder: ThreadEncoder) {
+		self.pending_thread_encoders.push(encoder);
+	}
+
+	/// Appends commands from all merged thread encoders to the frame's command groups, in submission order.
+	pub(crate) fn stitch_thread_encoders(&mut self) {
+		if self.pending_thread_encoders.is_empty() {
+			return
+		}
+
+		let mut encoders = std::mem::take(&mut self.pending_thread_encoders);
+		encoders.sort_by_key(|encoder| encoder.submission_index);
+
+		for encoder in encoders {
+			let ThreadEncoder { groups, upload_stage, .. } = encoder;
+
+			let upload_ids = self.upload_stage.append_from(&upload_stage);
+			let remap = |StagedUploadId(index)| upload_ids[index];
+
+			for mut thread_group in groups {
+				// The frame's group has its own shared bindings, so thread group bindings have to be baked in first.
+				thread_group.shared_bindings.remap_staged_uploads(&remap);
+
+				let mut commands = std::mem::take(&mut thread_group.commands);
+
+				for command in commands.iter_mut() {
+					command.remap_staged_uploads(&remap);
+
+					if let Some(bindings) = command.bindings_mut() {
+						bindings.merge_unspecified_from(&thread_group.shared_bindings);
+					}
+				}
+
+				let mut group = self.command_group(thread_group.stage);
+				for command in commands {
+					group.add(command);
+				}
+
+				for (attachment, ops) in thread_group.attachment_ops {
+					group.set_load_op(attachment, ops.load);
+					group.set_store_op(attachment, ops.store);
+				}
+			}
+		}
+	}
+}
@@ -4,196 +4,387 @@ use tracing::instrument;
 use std::collections::VecDeque;
 
 pub const UPLOAD_BUFFER_SIZE: usize = 100<<20;
+pub const PUSH_CONSTANT_BUFFER_SIZE: usize = 1<<20;
 
-pub struct UploadHeap {
-	buffer_name: BufferName,
+/// UBO index reserved for data staged with [`DrawCmdBuilder::push_constants`](crate::command::draw::DrawCmdBuilder::push_constants).
+pub const PUSH_CONSTANTS_UBO_INDEX: u32 = 15;
 
-	buffer_ptr: *mut u8,
-	buffer_cursor: usize,
-	data_pushed_counter: usize,
-	buffer_usage_counter: usize,
+/// Number of partitions per heap buffer. A partition is only reused once the frame that last wrote to it has completed
+/// on the GPU, so waiting should only happen if the GPU falls more than this many frames behind.
+pub const UPLOAD_FRAMES_IN_FLIGHT: usize = 3;
 
-	frame_start_cursor: usize,
-	locked_ranges: VecDeque<LockedRange>,
+/// Number of frames of usage considered when resizing partitions.
+const USAGE_HISTORY_FRAMES: usize = 120;
 
-	resolved_uploads: Vec<BufferRange>,
-}
+const PARTITION_GRANULARITY: usize = 64<<10;
 
-impl UploadHeap {
-	pub fn new(core: &mut Core) -> Self {
-		let create_flags = gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT | gl::MAP_WRITE_BIT;
 
-		let buffer_name = core.create_buffer();
-		core.set_debug_label(buffer_name, "Upload Heap");
-		core.allocate_buffer_storage(buffer_name, UPLOAD_BUFFER_SIZE, create_flags);
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UploadHeapStats {
+	pub partition_size: usize,
 
-		let buffer_ptr = unsafe { core.map_buffer(buffer_name, None) };
+	/// Bytes written last frame, including alignment padding.
+	pub last_frame_usage: usize,
 
-		assert!(!buffer_ptr.is_null(), "Failed to map upload heap");
+	/// Largest frame usage within the usage history.
+	pub peak_usage: usize,
 
-		UploadHeap {
-			buffer_name,
-			buffer_ptr,
-			buffer_cursor: 0,
-			data_pushed_counter: 0,
-			buffer_usage_counter: 0,
+	/// Times a partition wasn't ready to be reused and had to be waited on, since startup.
+	pub total_waits: u32,
+	pub total_wait_time: std::time::Duration,
+
+	/// Times a frame outgrew its partition and had to spill into a temporary buffer, since startup.
+	pub total_overflows: u32,
+}
 
-			frame_start_cursor: 0,
-			locked_ranges: VecDeque::new(),
 
+pub struct UploadHeap {
+	main_heap: PartitionedBuffer,
+
+	// Kept separate so that lots of tiny per-draw uploads don't get interleaved with large uploads.
+	push_constant_heap: PartitionedBuffer,
+
+	resolved_uploads: Vec<(BufferName, BufferRange)>,
+}
+
+impl UploadHeap {
+	pub fn new(core: &mut Core) -> Self {
+		UploadHeap {
+			main_heap: PartitionedBuffer::new(core, "Upload Heap", UPLOAD_BUFFER_SIZE / UPLOAD_FRAMES_IN_FLIGHT),
+			push_constant_heap: PartitionedBuffer::new(core, "Push Constant Heap", PUSH_CONSTANT_BUFFER_SIZE / UPLOAD_FRAMES_IN_FLIGHT),
 			resolved_uploads: Vec::new(),
 		}
 	}
 
-	pub fn reset(&mut self) {
-		if self.buffer_usage_counter > UPLOAD_BUFFER_SIZE {
-			dbg!(self.buffer_usage_counter, UPLOAD_BUFFER_SIZE);
-			dbg!(self.data_pushed_counter);
-			panic!("upload buffer overrun");
-		}
-
-		self.data_pushed_counter = 0;
-		self.buffer_usage_counter = 0;
+	/// Move on to the next partition, resizing partitions if usage has changed significantly.
+	pub fn reset(&mut self, core: &mut Core) {
+		self.main_heap.next_frame(core);
+		self.push_constant_heap.next_frame(core);
 		self.resolved_uploads.clear();
 	}
 
-	pub fn buffer_name(&self) -> BufferName {
-		self.buffer_name
+	pub fn stats(&self) -> UploadHeapStats {
+		self.main_heap.stats
 	}
 
-	fn reserve_space(&mut self, core: &mut Core, size: usize, alignment: usize) -> BufferRange {
-		// Move to next alignment boundary
-		let pre_alignment_cursor = self.buffer_cursor;
-		self.buffer_cursor = (self.buffer_cursor + alignment - 1) & (!alignment + 1);
+	/// Cap the size of main heap partitions, for testing behaviour when uploads don't fit. Frames that upload more
+	/// than this spill into temporary buffers every frame.
+	pub fn set_partition_size_limit(&mut self, limit: Option<usize>) {
+		self.main_heap.partition_size_limit = limit.map(|limit| limit.next_multiple_of(PARTITION_GRANULARITY).max(PARTITION_GRANULARITY));
+	}
 
-		assert!(size < UPLOAD_BUFFER_SIZE, "Tried to upload more than the upload heap can hold: {UPLOAD_BUFFER_SIZE}B");
+	pub fn partition_size_limit(&self) -> Option<usize> {
+		self.main_heap.partition_size_limit
+	}
 
-		let should_invalidate = self.buffer_cursor + size > UPLOAD_BUFFER_SIZE;
-		if should_invalidate {
-			self.buffer_cursor = 0;
-		}
+	pub fn push_constant_stats(&self) -> UploadHeapStats {
+		self.push_constant_heap.stats
+	}
 
-		let offset = self.buffer_cursor;
-		self.buffer_cursor += size;
+	pub fn resolve_allocation(&self, staged_upload: StagedUploadId) -> (BufferName, BufferRange) {
+		self.resolved_uploads.get(staged_upload.0).cloned()
+			.expect("Invalid staged upload id")
+	}
 
-		// Keep track of total buffer usage - including alignment
-		self.buffer_usage_counter += self.buffer_cursor.checked_sub(pre_alignment_cursor)
-			.unwrap_or_else(|| size + UPLOAD_BUFFER_SIZE - pre_alignment_cursor);
+	#[instrument(skip_all, name="UploadHeap::create_end_frame_fence")]
+	pub fn create_end_frame_fence(&mut self, core: &mut Core) {
+		self.main_heap.create_end_frame_fence(core);
+		self.push_constant_heap.create_end_frame_fence(core);
+	}
+}
 
-		let allocation = BufferRange {
-			offset,
 
-			// HACK: this is a measure to avoid binding ranges smaller than the minimum required size - specifically UBOs.
-			// this is needs a bit more thinking about tho, as alignment is not necessarily the correct value to use here
-			size: size.max(alignment),
-		};
 
-		// Check if we need to wait for the earliest range to be used.
-		while let Some(locked_range) = self.locked_ranges.front()
-			&& locked_range.contains_allocation(&allocation)
-		{
-			fn fence_ready(result: u32) -> bool { matches!(result, gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED) }
+struct MappedBuffer {
+	name: BufferName,
+	ptr: *mut u8,
+	size: usize,
+}
+
+impl MappedBuffer {
+	fn new(core: &mut Core, label: &str, size: usize) -> MappedBuffer {
+		let create_flags = gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT | gl::MAP_WRITE_BIT;
 
-			let range = self.locked_ranges.pop_front().unwrap();
+		let name = core.create_buffer();
+		core.set_debug_label(name, label);
+		core.allocate_buffer_storage(name, size, create_flags);
 
-			unsafe {
-				let result = core.gl.ClientWaitSync(range.fence, gl::SYNC_FLUSH_COMMANDS_BIT, 0);
-				if !fence_ready(result) {
-					// TODO(pat.m): would be better to log, or emit a profiler event
-					log::warn!("Waiting for upload heap!");
+		let ptr = unsafe { core.map_buffer(name, None) };
 
-					// Wait for a maximum of 50ms.
-					let max_timeout_ns = 50_000_000;
-					let result = core.gl.ClientWaitSync(range.fence, gl::SYNC_FLUSH_COMMANDS_BIT, max_timeout_ns);
+		assert!(!ptr.is_null(), "Failed to map {label}");
 
-					assert!(fence_ready(result), "Timed out while waiting for upload heap range to become ready");
-				}
+		MappedBuffer { name, ptr, size }
+	}
+}
+
+
+/// Buffers that may still be read by in flight frames, destroyed once all of `fences` have signalled.
+struct RetiredBuffers {
+	fences: Vec<gl::types::GLsync>,
+	buffers: Vec<MappedBuffer>,
+}
+
+
+/// A persistently mapped buffer split into [`UPLOAD_FRAMES_IN_FLIGHT`] partitions, one written per frame.
+/// If a frame outgrows its partition, the rest of the frame spills into a temporary buffer, and partitions are
+/// grown to fit at the start of the next frame.
+struct PartitionedBuffer {
+	label: &'static str,
+
+	buffer: MappedBuffer,
+	partition_size: usize,
+	partition_size_limit: Option<usize>,
+	partition_fences: [Option<gl::types::GLsync>; UPLOAD_FRAMES_IN_FLIGHT],
+
+	partition_index: usize,
+	partition_ready: bool,
+	cursor: usize,
+
+	overflow: Option<(MappedBuffer, usize)>,
+	overflow_this_frame: Vec<MappedBuffer>,
+	retired: Vec<RetiredBuffers>,
+
+	frame_usage: usize,
+	usage_history: VecDeque<usize>,
+	stats: UploadHeapStats,
+}
+
+impl PartitionedBuffer {
+	fn new(core: &mut Core, label: &'static str, partition_size: usize) -> Self {
+		let partition_size = partition_size.next_multiple_of(PARTITION_GRANULARITY);
+
+		PartitionedBuffer {
+			label,
+
+			buffer: MappedBuffer::new(core, label, partition_size * UPLOAD_FRAMES_IN_FLIGHT),
+			partition_size,
+			partition_size_limit: None,
+			partition_fences: [None; UPLOAD_FRAMES_IN_FLIGHT],
+
+			partition_index: 0,
+			partition_ready: false,
+			cursor: 0,
+
+			overflow: None,
+			overflow_this_frame: Vec::new(),
+			retired: Vec::new(),
+
+			frame_usage: 0,
+			usage_history: VecDeque::new(),
+			stats: UploadHeapStats { partition_size, .. UploadHeapStats::default() },
+		}
+	}
+
+	fn reserve_space(&mut self, core: &mut Core, size: usize, alignment: usize) -> (BufferName, usize, BufferRange) {
+		if !self.partition_ready {
+			self.wait_for_partition(core);
+		}
+
+		// HACK: this is a measure to avoid binding ranges smaller than the minimum required size - specifically UBOs.
+		// this is needs a bit more thinking about tho, as alignment is not necessarily the correct value to use here
+		let range_size = size.max(alignment);
+
+		let offset = self.cursor.next_multiple_of(alignment);
+		if offset + range_size <= self.partition_size {
+			self.frame_usage += offset + range_size - self.cursor;
+			self.cursor = offset + range_size;
+
+			let base = self.partition_index * self.partition_size;
+			let allocation = BufferRange { offset: base + offset, size: range_size };
+			return (self.buffer.name, allocation.offset, allocation)
+		}
+
+		self.reserve_overflow_space(core, range_size, alignment)
+	}
+
+	fn reserve_overflow_space(&mut self, core: &mut Core, size: usize, alignment: usize) -> (BufferName, usize, BufferRange) {
+		// Count the whole allocation as usage, so that the next resize accounts for it.
+		self.frame_usage += size + alignment;
 
-				core.gl.DeleteSync(range.fence);
+		if let Some((buffer, cursor)) = &mut self.overflow {
+			let offset = cursor.next_multiple_of(alignment);
+			if offset + size <= buffer.size {
+				*cursor = offset + size;
+				return (buffer.name, offset, BufferRange { offset, size })
 			}
 		}
 
-		allocation
+		log::debug!("{} partition overflowed - spilling into temporary buffer", self.label);
+		self.stats.total_overflows += 1;
+
+		let overflow_size = size.max(self.partition_size).next_multiple_of(PARTITION_GRANULARITY);
+		let buffer = MappedBuffer::new(core, &format!("{} Overflow", self.label), overflow_size);
+		let name = buffer.name;
+
+		if let Some((previous, _)) = self.overflow.replace((buffer, size)) {
+			self.overflow_this_frame.push(previous);
+		}
+
+		(name, 0, BufferRange { offset: 0, size })
 	}
 
-	fn write_to_device<T>(&mut self, core: &mut Core, data: &[T], alignment: usize) -> BufferRange
+	fn write_to_device<T>(&mut self, core: &mut Core, data: &[T], alignment: usize) -> (BufferName, BufferRange)
 		where T: Copy + 'static
 	{
 		let byte_size = data.len() * std::mem::size_of::<T>();
-		let allocation = self.reserve_space(core, byte_size, alignment);
+		let (name, write_offset, allocation) = self.reserve_space(core, byte_size, alignment);
+
+		let buffer_ptr = match &self.overflow {
+			Some((overflow, _)) if overflow.name == name => overflow.ptr,
+			_ => self.buffer.ptr,
+		};
 
 		unsafe {
-			let dest_ptr = self.buffer_ptr.offset(allocation.offset as isize);
+			let dest_ptr = buffer_ptr.add(write_offset);
 			std::ptr::copy(data.as_ptr(), dest_ptr.cast(), data.len());
 		}
 
-		self.data_pushed_counter += byte_size;
-
-		allocation
+		(name, allocation)
 	}
 
-	pub fn resolve_allocation(&self, staged_upload: StagedUploadId) -> BufferRange {
-		self.resolved_uploads.get(staged_upload.0).cloned()
-			.expect("Invalid staged upload id")
+	fn wait_for_partition(&mut self, core: &mut Core) {
+		self.partition_ready = true;
+
+		let Some(fence) = self.partition_fences[self.partition_index].take() else { return };
+
+		fn fence_ready(result: u32) -> bool { matches!(result, gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED) }
+
+		unsafe {
+			let result = core.gl.ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, 0);
+			if !fence_ready(result) {
+				let _span = tracing::info_span!("upload heap wait", label=self.label).entered();
+				let wait_start = std::time::Instant::now();
+
+				// Wait for a maximum of 50ms.
+				let max_timeout_ns = 50_000_000;
+				let result = core.gl.ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, max_timeout_ns);
+
+				assert!(fence_ready(result), "Timed out while waiting for {} partition to become ready", self.label);
+
+				self.stats.total_waits += 1;
+				self.stats.total_wait_time += wait_start.elapsed();
+			}
+
+			core.gl.DeleteSync(fence);
+		}
 	}
 
-	#[instrument(skip_all, name="UploadHeap::create_end_frame_fence")]
-	pub fn create_end_frame_fence(&mut self, core: &mut Core) {
+	fn create_end_frame_fence(&mut self, core: &mut Core) {
 		let fence = unsafe {
 			let _span = tracing::info_span!("glFenceSync").entered();
 			core.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
 		};
 
-		let range_size = self.buffer_cursor.checked_sub(self.frame_start_cursor)
-			.unwrap_or(UPLOAD_BUFFER_SIZE - self.frame_start_cursor + self.buffer_cursor);
+		// Only the partition and any overflow buffers used this frame are protected by this fence.
+		if let Some((overflow, _)) = self.overflow.take() {
+			self.overflow_this_frame.push(overflow);
+		}
 
-		self.locked_ranges.push_back(LockedRange {
-			fence,
-			start: self.frame_start_cursor,
-			size: range_size,
-		});
+		if !self.overflow_this_frame.is_empty() {
+			let overflow_fence = unsafe { core.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+
+			self.retired.push(RetiredBuffers {
+				fences: vec![overflow_fence],
+				buffers: std::mem::take(&mut self.overflow_this_frame),
+			});
+		}
 
-		self.frame_start_cursor = self.buffer_cursor;
+		if let Some(old_fence) = self.partition_fences[self.partition_index].replace(fence) {
+			unsafe { core.gl.DeleteSync(old_fence); }
+		}
 	}
-}
 
+	fn next_frame(&mut self, core: &mut Core) {
+		self.stats.last_frame_usage = self.frame_usage;
+
+		self.usage_history.push_back(self.frame_usage);
+		if self.usage_history.len() > USAGE_HISTORY_FRAMES {
+			self.usage_history.pop_front();
+		}
 
+		self.stats.peak_usage = self.usage_history.iter().copied().max().unwrap_or(0);
 
+		self.frame_usage = 0;
+		self.cursor = 0;
+		self.partition_index = (self.partition_index + 1) % UPLOAD_FRAMES_IN_FLIGHT;
+		self.partition_ready = false;
 
+		self.maybe_resize(core);
+		self.destroy_completed_retired_buffers(core);
+	}
 
+	/// Grow as soon as a frame doesn't fit, but only shrink after a full history of frames using under a quarter.
+	fn maybe_resize(&mut self, core: &mut Core) {
+		let mut target_size = (self.stats.peak_usage + self.stats.peak_usage / 4)
+			.next_multiple_of(PARTITION_GRANULARITY)
+			.max(PARTITION_GRANULARITY);
 
-#[derive(Debug)]
-struct LockedRange {
-	fence: gl::types::GLsync,
+		if let Some(limit) = self.partition_size_limit {
+			target_size = target_size.min(limit);
+		}
 
-	start: usize,
-	size: usize, // NOTE: may wrap
-}
+		let over_limit = self.partition_size_limit.is_some_and(|limit| self.partition_size > limit);
 
-impl LockedRange {
-	fn contains_allocation(&self, allocation: &BufferRange) -> bool {
-		let allocation_end = allocation.offset + allocation.size;
-		let range_end = self.start + self.size;
+		let should_grow = target_size > self.partition_size;
+		let should_shrink = over_limit
+			|| self.usage_history.len() >= USAGE_HISTORY_FRAMES && target_size < self.partition_size / 4;
 
-		if range_end <= UPLOAD_BUFFER_SIZE {
-			allocation.offset < range_end && allocation_end >= self.start
-		} else {
-			allocation.offset >= self.start || allocation_end < (range_end - UPLOAD_BUFFER_SIZE)
+		if !should_grow && !should_shrink {
+			return
 		}
+
+		log::info!("Resizing {} partitions from {}KiB to {}KiB", self.label, self.partition_size >> 10, target_size >> 10);
+
+		let new_buffer = MappedBuffer::new(core, self.label, target_size * UPLOAD_FRAMES_IN_FLIGHT);
+		let old_buffer = std::mem::replace(&mut self.buffer, new_buffer);
+
+		self.retired.push(RetiredBuffers {
+			fences: self.partition_fences.iter_mut().filter_map(Option::take).collect(),
+			buffers: vec![old_buffer],
+		});
+
+		self.partition_size = target_size;
+		self.stats.partition_size = target_size;
+	}
+
+	fn destroy_completed_retired_buffers(&mut self, core: &mut Core) {
+		self.retired.retain_mut(|retired| {
+			retired.fences.retain(|&fence| unsafe {
+				let result = core.gl.ClientWaitSync(fence, 0, 0);
+				let signalled = matches!(result, gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED);
+				if signalled {
+					core.gl.DeleteSync(fence);
+				}
+
+				!signalled
+			});
+
+			if !retired.fences.is_empty() {
+				return true
+			}
+
+			for buffer in retired.buffers.drain(..) {
+				core.destroy_buffer(buffer.name);
+			}
+
+			false
+		});
 	}
 }
 
 
 
+
 #[derive(Copy, Clone, Debug)]
 struct StagedUpload {
 	data: &'static [u8],
 	alignment: usize,
 	index: usize,
+	push_constants: bool,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
-pub struct StagedUploadId(usize);
+pub struct StagedUploadId(pub(crate) usize);
 
 
 pub struct UploadStage {
@@ -233,11 +424,21 @@ impl UploadStage {
 			data: bytes_static,
 			alignment: 1,
 			index,
+			push_constants: false,
 		});
 
 		StagedUploadId(index)
 	}
 
+	/// Like [`Self::stage_data`], but uploaded to a small dedicated ring buffer. Intended for tiny per-draw data.
+	pub fn stage_push_constants<U>(&mut self, data: &U) -> StagedUploadId
+		where U: crate::AsStageableSlice + ?Sized
+	{
+		let upload_id = self.stage_data(data);
+		self.staged_uploads[upload_id.0].push_constants = true;
+		upload_id
+	}
+
 	pub fn stage_data_iter<I, T>(&mut self, iter: I) -> StagedUploadId
 	    where I: IntoIterator<Item = T>
 		    , I::IntoIter: ExactSizeIterator
@@ -259,6 +460,7 @@ impl UploadStage {
 			data: bytes_static,
 			alignment: 1,
 			index,
+			push_constants: false,
 		});
 
 		StagedUploadId(index)
@@ -272,6 +474,29 @@ impl UploadStage {
 		upload.alignment = upload.alignment.max(new_aligment);
 	}
 
+	/// Copy everything staged in `other` into this stage. Returns the new id for each of `other`s uploads, in order.
+	pub fn append_from(&mut self, other: &UploadStage) -> Vec<StagedUploadId> {
+		other.staged_uploads.iter()
+			.map(|upload| {
+				let upload_id = self.stage_data(upload.data);
+
+				let new_upload = &mut self.staged_uploads[upload_id.0];
+				new_upload.alignment = upload.alignment;
+				new_upload.push_constants = upload.push_constants;
+
+				upload_id
+			})
+			.collect()
+	}
+
+	/// Hash the contents of everything staged so far, in staging order. Must be called before [`Self::push_to_heap`].
+	pub(crate) fn hash_staged_data(&self, hasher: &mut impl std::hash::Hasher) {
+		for upload in self.staged_uploads.iter() {
+			hasher.write(upload.data);
+			hasher.write_u8(upload.push_constants as u8);
+		}
+	}
+
 	#[instrument(skip_all, name="gfx UploadStage::push_to_heap")]
 	pub fn push_to_heap(&mut self, core: &mut Core, upload_heap: &mut UploadHeap) {
 		core.push_debug_group("Push Upload Heap");
@@ -279,11 +504,15 @@ impl UploadStage {
 		// Sort descending by alignment for better packing
 		self.staged_uploads.sort_by_key(|upload| !upload.alignment);
 
-		upload_heap.resolved_uploads.resize(self.staged_uploads.len(), Default::default());
+		upload_heap.resolved_uploads.resize(self.staged_uploads.len(), (upload_heap.main_heap.buffer.name, BufferRange::default()));
 
 		for upload in self.staged_uploads.drain(..) {
-			let allocation = upload_heap.write_to_device(core, upload.data, upload.alignment);
-			upload_heap.resolved_uploads[upload.index] = allocation;
+			let heap = match upload.push_constants {
+				true => &mut upload_heap.push_constant_heap,
+				false => &mut upload_heap.main_heap,
+			};
+
+			upload_heap.resolved_uploads[upload.index] = heap.write_to_device(core, upload.data, upload.alignment);
 		}
 
 		core.pop_debug_group();
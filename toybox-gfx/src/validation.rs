@@ -0,0 +1,277 @@
+use crate::{System, FrameStage, Capabilities, BufferArgument};
+use crate::bindings::{BindingDescription, BufferBindTarget, ImageBindTarget};
+use crate::command::{Command, DrawCmd, ComputeCmd, DispatchSize, PrimitiveType};
+use crate::resource_manager::arguments::ImageArgument;
+
+use std::fmt;
+
+
+/// A command that would have failed or misbehaved when dispatched. See [`System::set_validation_enabled`].
+#[derive(Debug, Clone)]
+pub struct ValidationError {
+	pub stage: FrameStage,
+
+	/// Label of the draw if it has one, otherwise the innermost annotation the command was encoded under, if any.
+	pub annotation: Option<String>,
+
+	/// Index of the command within its command group.
+	pub command_index: usize,
+
+	pub kind: ValidationErrorKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum ValidationErrorKind {
+	UnresolvedBufferSource(BufferBindTarget),
+	UnresolvedBufferTarget(BufferBindTarget),
+	MisalignedBufferBinding { target: BufferBindTarget, offset: usize, alignment: usize },
+	UboTooLarge { target: BufferBindTarget, size: usize, max_size: usize },
+
+	UnresolvedImageSource(ImageBindTarget),
+	UnresolvedImageTarget(ImageBindTarget),
+	MissingSampler(ImageBindTarget),
+	ImageUnitOutOfRange(ImageBindTarget),
+
+	MissingFramebuffer,
+
+	UnresolvedIndexBuffer,
+	MisalignedIndexBuffer { offset: usize },
+	ZeroElementsWithIndexBuffer,
+
+	UnresolvedIndirectBuffer,
+
+	PatchesWithoutTessellation,
+	TessellationWithoutPatches,
+	TessControlWithoutEvaluation,
+	PatchVerticesOutOfRange { patch_vertices: u32, max_patch_vertices: usize },
+}
+
+impl fmt::Display for ValidationError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:?}", self.stage)?;
+
+		if let Some(annotation) = &self.annotation {
+			write!(f, " '{annotation}'")?;
+		}
+
+		write!(f, " command {}: {}", self.command_index, self.kind)
+	}
+}
+
+impl fmt::Display for ValidationErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		use ValidationErrorKind::*;
+
+		match self {
+			UnresolvedBufferSource(target) => write!(f, "buffer bound to {target:?} was never resolved to a name"),
+			UnresolvedBufferTarget(target) => write!(f, "buffer bind target {target:?} was never resolved to an index"),
+			MisalignedBufferBinding{target, offset, alignment} =>
+				write!(f, "buffer bound to {target:?} at offset {offset}, which isn't a multiple of the required alignment {alignment}"),
+			UboTooLarge{target, size, max_size} =>
+				write!(f, "buffer bound to {target:?} is {size} bytes, larger than the maximum ubo size {max_size}"),
+
+			UnresolvedImageSource(target) => write!(f, "image bound to {target:?} was never resolved to a name"),
+			UnresolvedImageTarget(target) => write!(f, "image bind target {target:?} was never resolved to a unit"),
+			MissingSampler(target) => write!(f, "image bound to {target:?} is sampled but has no sampler"),
+			ImageUnitOutOfRange(target) => write!(f, "image bind target {target:?} exceeds the number of image units"),
+
+			MissingFramebuffer => write!(f, "no framebuffer bound"),
+
+			UnresolvedIndexBuffer => write!(f, "index buffer was never resolved to a name"),
+			MisalignedIndexBuffer{offset} => write!(f, "index buffer bound at offset {offset}, which isn't a multiple of the index size"),
+			ZeroElementsWithIndexBuffer => write!(f, "indexed draw with zero elements"),
+
+			UnresolvedIndirectBuffer => write!(f, "indirect dispatch buffer was never resolved to a name"),
+
+			PatchesWithoutTessellation => write!(f, "patches drawn without a tessellation evaluation shader"),
+			TessellationWithoutPatches => write!(f, "tessellation shaders used with a primitive type other than patches"),
+			TessControlWithoutEvaluation => write!(f, "tessellation control shader without an evaluation shader"),
+			PatchVerticesOutOfRange{patch_vertices, max_patch_vertices} =>
+				write!(f, "{patch_vertices} vertices per patch, outside of the supported range 1..={max_patch_vertices}"),
+		}
+	}
+}
+
+
+impl System {
+	/// Check every draw and dispatch before execution, logging problems with the stage and annotation of the offending
+	/// command and skipping it, instead of panicking part way through dispatch. Enabled by default for debug contexts.
+	pub fn set_validation_enabled(&mut self, enabled: bool) {
+		self.validation_enabled = enabled;
+
+		if !enabled {
+			self.validation_errors.clear();
+		}
+	}
+
+	pub fn is_validation_enabled(&self) -> bool {
+		self.validation_enabled
+	}
+
+	/// Problems found in the most recently executed frame.
+	pub fn validation_errors(&self) -> &[ValidationError] {
+		&self.validation_errors
+	}
+
+	/// Must be called after all bindings have been merged and resolved, just before dispatch.
+	#[tracing::instrument(skip_all, name="gfxsys validate_commands")]
+	pub(crate) fn validate_commands(&mut self) {
+		self.validation_errors.clear();
+
+		if !self.validation_enabled {
+			return
+		}
+
+		let capabilities = self.core.capabilities();
+
+		for command_group in self.frame_encoder.command_groups.iter_mut() {
+			let mut annotations = Vec::new();
+			let mut invalid_commands = Vec::new();
+
+			for (command_index, command) in command_group.commands.iter().enumerate() {
+				let mut kinds = Vec::new();
+
+				match command {
+					Command::PushDebugGroup{label} => annotations.push(label.clone()),
+					Command::PopDebugGroup => { annotations.pop(); }
+					Command::Draw(cmd) => validate_draw(cmd, capabilities, &mut kinds),
+					Command::Compute(cmd) => validate_compute(cmd, capabilities, &mut kinds),
+					_ => {}
+				}
+
+				if kinds.is_empty() {
+					continue
+				}
+
+				invalid_commands.push(command_index);
+
+				let annotation = match command {
+					Command::Draw(DrawCmd{label: Some(label), ..}) => Some(label.to_string()),
+					_ => annotations.last().cloned(),
+				};
+
+				for kind in kinds {
+					let error = ValidationError {
+						stage: command_group.stage,
+						annotation: annotation.clone(),
+						command_index,
+						kind,
+					};
+
+					log::error!("Skipping invalid command - {error}");
+					self.validation_errors.push(error);
+				}
+			}
+
+			// Remove back to front so indices stay valid.
+			for command_index in invalid_commands.into_iter().rev() {
+				command_group.commands.remove(command_index);
+			}
+		}
+	}
+}
+
+
+fn validate_draw(cmd: &DrawCmd, capabilities: &Capabilities, errors: &mut Vec<ValidationErrorKind>) {
+	validate_bindings(&cmd.bindings, capabilities, errors);
+
+	if let Some(index_buffer) = cmd.index_buffer {
+		match index_buffer {
+			BufferArgument::Name{range, ..} => {
+				// TODO(pat.m): allow non 32b indices
+				let offset = range.map_or(0, |range| range.offset);
+				if offset % 4 != 0 {
+					errors.push(ValidationErrorKind::MisalignedIndexBuffer{offset});
+				}
+			}
+
+			BufferArgument::Staged(_) => errors.push(ValidationErrorKind::UnresolvedIndexBuffer),
+		}
+
+		if cmd.num_elements == 0 {
+			errors.push(ValidationErrorKind::ZeroElementsWithIndexBuffer);
+		}
+	}
+
+	if cmd.has_tess_control_without_evaluation() {
+		errors.push(ValidationErrorKind::TessControlWithoutEvaluation);
+	}
+
+	match (cmd.primitive_type, cmd.has_tessellation()) {
+		(PrimitiveType::Patches, false) => errors.push(ValidationErrorKind::PatchesWithoutTessellation),
+		(PrimitiveType::Patches, true) => {
+			let max_patch_vertices = capabilities.max_patch_vertices;
+			if cmd.patch_vertices == 0 || cmd.patch_vertices as usize > max_patch_vertices {
+				errors.push(ValidationErrorKind::PatchVerticesOutOfRange{patch_vertices: cmd.patch_vertices, max_patch_vertices});
+			}
+		}
+		(_, true) => errors.push(ValidationErrorKind::TessellationWithoutPatches),
+		(_, false) => {}
+	}
+}
+
+fn validate_compute(cmd: &ComputeCmd, capabilities: &Capabilities, errors: &mut Vec<ValidationErrorKind>) {
+	validate_bindings(&cmd.bindings, capabilities, errors);
+
+	if let DispatchSize::Indirect(BufferArgument::Staged(_)) = cmd.dispatch_size {
+		errors.push(ValidationErrorKind::UnresolvedIndirectBuffer);
+	}
+}
+
+fn validate_bindings(bindings: &BindingDescription, capabilities: &Capabilities, errors: &mut Vec<ValidationErrorKind>) {
+	for bind_desc in bindings.buffer_bindings.iter() {
+		let target = bind_desc.target;
+
+		let (alignment, max_size) = match target {
+			BufferBindTarget::UboIndex(_) => (capabilities.ubo_bind_alignment, Some(capabilities.max_ubo_size)),
+			BufferBindTarget::SsboIndex(_) => (capabilities.ssbo_bind_alignment, None),
+			BufferBindTarget::Named(_) => {
+				errors.push(ValidationErrorKind::UnresolvedBufferTarget(target));
+				continue
+			}
+		};
+
+		let BufferArgument::Name{range, ..} = bind_desc.source else {
+			errors.push(ValidationErrorKind::UnresolvedBufferSource(target));
+			continue
+		};
+
+		let Some(range) = range else { continue };
+
+		if alignment > 0 && range.offset % alignment != 0 {
+			errors.push(ValidationErrorKind::MisalignedBufferBinding{target, offset: range.offset, alignment});
+		}
+
+		if let Some(max_size) = max_size && range.size > max_size {
+			errors.push(ValidationErrorKind::UboTooLarge{target, size: range.size, max_size});
+		}
+	}
+
+	for bind_desc in bindings.image_bindings.iter() {
+		let target = bind_desc.target;
+
+		let unit = match target {
+			ImageBindTarget::Sampled(unit) | ImageBindTarget::ReadonlyImage(unit) | ImageBindTarget::ReadWriteImage(unit) => unit,
+			ImageBindTarget::Named(_) => {
+				errors.push(ValidationErrorKind::UnresolvedImageTarget(target));
+				continue
+			}
+		};
+
+		if unit as usize >= capabilities.max_image_units {
+			errors.push(ValidationErrorKind::ImageUnitOutOfRange(target));
+		}
+
+		if !matches!(bind_desc.source, ImageArgument::Name(_)) {
+			errors.push(ValidationErrorKind::UnresolvedImageSource(target));
+		}
+
+		if matches!(target, ImageBindTarget::Sampled(_)) && bind_desc.sampler.is_none() {
+			errors.push(ValidationErrorKind::MissingSampler(target));
+		}
+	}
+
+	if bindings.framebuffer.is_none() {
+		errors.push(ValidationErrorKind::MissingFramebuffer);
+	}
+}
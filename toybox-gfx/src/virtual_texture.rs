@@ -0,0 +1,676 @@
+//! Very large textures where only the pages actually being sampled are kept in memory - e.g., for terrain.
+//!
+//! Shaders sample with `vt_sample` from the [`VIRTUAL_TEXTURE_IMPORT`] import, which also flags the pages they
+//! wanted in a feedback buffer. That is read back a couple of frames later into [`VirtualTexture::page_requests`],
+//! and the app responds by loading page data however it likes and passing it to [`VirtualTexture::provide_page`].
+//! Until then, the finest resident level is sampled instead.
+//!
+//! Pages are committed to a sparse image where GL_ARB_sparse_texture is supported, otherwise they're packed into a
+//! fixed size atlas.
+//!
+//! ```ignore
+//! // Each frame
+//! terrain_texture.update(gfx);
+//!
+//! for page in terrain_texture.page_requests().into_iter().take(4) {
+//!     let data = terrain_pages.load(page, terrain_texture.page_range(page).size);
+//!     terrain_texture.provide_page(gfx, page, &data);
+//! }
+//!
+//! let mut group = gfx.frame_encoder.command_group(FrameStage::Main);
+//! terrain_texture.bind(&mut group, 1, 0);
+//! group.draw(terrain_vs, terrain_fs)...;
+//!
+//! // On shutdown
+//! terrain_texture.destroy(gfx);
+//! ```
+//!
+//! ```glsl
+//! #import virtual_texture
+//! layout(binding=1) uniform TerrainBlock { VirtualTextureInfo u_terrain_vt; };
+//! layout(binding=0) uniform sampler2D u_terrain_image;
+//! layout(binding=1) uniform usampler2D u_terrain_page_table;
+//!
+//! vec4 color = vt_sample(u_terrain_vt, u_terrain_page_table, u_terrain_image, uv);
+//! ```
+
+use crate::prelude::*;
+use crate::{
+	System, FrameStage, CommandGroupEncoder, ImageName, ImageFormat, ImageRange, ComponentFormat,
+	BufferName, BufferRange, SamplerName, SamplerDescription, FilterMode, AddressingMode,
+	glsl::{GlslStruct, ShaderImports},
+	shaders,
+};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::rc::Rc;
+
+
+/// Name of the shader import providing `VirtualTextureInfo`, `vt_sample` and friends.
+pub const VIRTUAL_TEXTURE_IMPORT: &str = "virtual_texture";
+
+/// SSBO index the feedback buffer is bound to. Matches `virtual_texture.glsl`.
+pub const VIRTUAL_TEXTURE_FEEDBACK_BINDING: u32 = 7;
+
+/// How many frames of feedback can be in flight at once. If all slots are still waiting on the GPU,
+/// feedback is skipped for a frame rather than stalling.
+const NUM_FEEDBACK_SLOTS: usize = 3;
+
+/// Pages requested within this many frames won't be evicted.
+const EVICTION_GRACE_FRAMES: u64 = 8;
+
+
+/// Matches `VirtualTextureInfo` in the [`VIRTUAL_TEXTURE_IMPORT`] shader import.
+#[derive(Debug, Copy, Clone, GlslStruct)]
+#[glsl(crate = "crate")]
+#[repr(C)]
+pub struct VirtualTextureInfo {
+	/// xy: size in texels, zw: size in pages
+	pub size: Vec4,
+
+	/// x: num levels, y: page size in texels, z: atlas size in pages or 0 if sparse, w: 1.0 if feedback is enabled
+	pub params: Vec4,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct VirtualTextureSettings {
+	/// Must be a power of two multiple of the page size.
+	pub size: Vec2i,
+	pub format: ImageFormat,
+
+	/// Page size used by the atlas. Sparse images use the page size of the hardware, if it divides `size`.
+	pub page_size: i32,
+
+	/// The most pages that can be resident at once - the size of the atlas, or a memory budget for sparse images.
+	pub max_resident_pages: usize,
+
+	/// Use the atlas even if sparse images are supported - e.g., for testing.
+	pub force_atlas: bool,
+}
+
+impl Default for VirtualTextureSettings {
+	fn default() -> Self {
+		VirtualTextureSettings {
+			size: Vec2i::splat(8192),
+			format: ImageFormat::Srgba8,
+			page_size: 128,
+			max_resident_pages: 256,
+			force_atlas: false,
+		}
+	}
+}
+
+
+/// A page of a [`VirtualTexture`]. `x` and `y` are in pages, within the page grid of `level`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PageId {
+	pub level: u32,
+	pub x: i32,
+	pub y: i32,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VirtualTextureBacking {
+	Sparse,
+	Atlas,
+}
+
+
+#[derive(Debug, Copy, Clone)]
+struct ResidentPage {
+	/// Position in the atlas in pages. Unused for sparse images.
+	slot: Vec2i,
+	last_requested_frame: u64,
+}
+
+
+pub struct VirtualTexture {
+	settings: VirtualTextureSettings,
+	backing: VirtualTextureBacking,
+
+	page_size: i32,
+	num_pages: Vec2i,
+	num_levels: u32,
+
+	image: ImageName,
+	page_table: ImageName,
+	image_sampler: SamplerName,
+	page_table_sampler: SamplerName,
+
+	/// Atlas only.
+	atlas_pages: i32,
+	free_slots: Vec<Vec2i>,
+
+	/// Sparse only. Levels from this one on are committed together, as the mip tail.
+	mip_tail_level: u32,
+	mip_tail_committed: bool,
+
+	resident: HashMap<PageId, ResidentPage>,
+
+	/// Rebuilt whenever new feedback arrives.
+	requests: HashSet<PageId>,
+
+	feedback: Rc<RefCell<Feedback>>,
+	feedback_slot: Option<BufferRange>,
+	frame: u64,
+}
+
+impl VirtualTexture {
+	pub fn new(gfx: &mut System, label: &str, settings: VirtualTextureSettings) -> VirtualTexture {
+		let core = &gfx.core;
+
+		let sparse_page_size = core.sparse_page_size(settings.format)
+			.filter(|_| !settings.force_atlas)
+			.filter(|page_size| page_size.x == page_size.y && is_power_of_two_multiple(settings.size, page_size.x));
+
+		let (backing, page_size) = match sparse_page_size {
+			Some(page_size) => (VirtualTextureBacking::Sparse, page_size.x),
+			None => (VirtualTextureBacking::Atlas, settings.page_size),
+		};
+
+		assert!(is_power_of_two_multiple(settings.size, page_size),
+			"Virtual texture size {:?} must be a power of two multiple of the page size {page_size}", settings.size);
+
+		let num_pages = settings.size / page_size;
+		let num_levels = num_pages.x.max(num_pages.y).ilog2() + 1;
+
+		let mut atlas_pages = 0;
+		let mut free_slots = Vec::new();
+		let mut mip_tail_level = num_levels;
+
+		let image = match backing {
+			VirtualTextureBacking::Sparse => {
+				let image = core.create_sparse_image_2d(settings.format, settings.size, num_levels)
+					.expect("Failed to create sparse image");
+
+				mip_tail_level = core.sparse_image_levels(image).min(num_levels);
+				image
+			}
+
+			VirtualTextureBacking::Atlas => {
+				atlas_pages = (settings.max_resident_pages as f32).sqrt().ceil() as i32;
+				free_slots = (0..atlas_pages).rev()
+					.flat_map(|y| (0..atlas_pages).rev().map(move |x| Vec2i::new(x, y)))
+					.collect();
+
+				core.create_image_2d(settings.format, Vec2i::splat(atlas_pages * page_size))
+			}
+		};
+
+		log::info!("Virtual texture '{label}' using {backing:?} backing, {page_size} texel pages, {num_levels} levels");
+
+		core.set_debug_label(image, label);
+
+		// Entries are slot + 1 for the atlas, or just non-zero for sparse images. Zero means not resident.
+		let page_table = core.create_image_from_info(crate::ImageInfo {
+			image_type: crate::ImageType::Image2D,
+			format: page_table_format(),
+			size: num_pages.extend(1),
+			levels: num_levels,
+			samples: 1,
+		});
+
+		core.set_debug_label(page_table, format!("{label} page table"));
+
+		for level in 0..num_levels {
+			let level_pages = level_pages(num_pages, level);
+			let zeroes = vec![0u16; 2 * (level_pages.x * level_pages.y) as usize];
+
+			unsafe {
+				core.upload_image_level_raw(page_table, level, None, page_table_format(),
+					zeroes.as_ptr().cast(), zeroes.len() * std::mem::size_of::<u16>());
+			}
+		}
+
+		let image_sampler = core.create_sampler_from_description(&match backing {
+			VirtualTextureBacking::Sparse => SamplerDescription::linear()
+				.mip_filter(FilterMode::Linear)
+				.addressing_mode(AddressingMode::Repeat),
+
+			VirtualTextureBacking::Atlas => SamplerDescription::linear(),
+		});
+
+		let page_table_sampler = core.create_sampler_from_description(&SamplerDescription::nearest().mip_filter(FilterMode::Nearest));
+
+		let feedback = Feedback::new(core, level_offset(num_pages, num_levels));
+
+		VirtualTexture {
+			settings,
+			backing,
+
+			page_size,
+			num_pages,
+			num_levels,
+
+			image,
+			page_table,
+			image_sampler,
+			page_table_sampler,
+
+			atlas_pages,
+			free_slots,
+
+			mip_tail_level,
+			mip_tail_committed: false,
+
+			resident: HashMap::new(),
+			requests: HashSet::new(),
+
+			feedback: Rc::new(RefCell::new(feedback)),
+			feedback_slot: None,
+			frame: 0,
+		}
+	}
+
+	pub fn settings(&self) -> &VirtualTextureSettings {
+		&self.settings
+	}
+
+	pub fn backing(&self) -> VirtualTextureBacking {
+		self.backing
+	}
+
+	pub fn page_size(&self) -> i32 {
+		self.page_size
+	}
+
+	pub fn num_levels(&self) -> u32 {
+		self.num_levels
+	}
+
+	pub fn num_resident_pages(&self) -> usize {
+		self.resident.len()
+	}
+
+	pub fn is_resident(&self, page: PageId) -> bool {
+		self.resident.contains_key(&page)
+	}
+
+	/// Texels covered by `page` within its level. Data passed to [`VirtualTexture::provide_page`] must be this size,
+	/// which is smaller than the page size for levels smaller than a single page.
+	pub fn page_range(&self, page: PageId) -> ImageRange {
+		let level_size = Vec2i::new((self.settings.size.x >> page.level).max(1), (self.settings.size.y >> page.level).max(1));
+		let offset = Vec2i::new(page.x, page.y) * self.page_size;
+		let size = Vec2i::new(self.page_size.min(level_size.x - offset.x), self.page_size.min(level_size.y - offset.y));
+		ImageRange::from_2d_range(offset, size)
+	}
+
+	/// Pages that have been sampled recently but aren't resident, coarsest level first.
+	pub fn page_requests(&self) -> Vec<PageId> {
+		let mut requests: Vec<PageId> = self.requests.iter().copied().collect();
+		requests.sort_by(|a, b| b.level.cmp(&a.level).then(a.cmp(b)));
+		requests
+	}
+
+	/// Should be called once per frame before any draws are encoded. Collects feedback from previous frames.
+	pub fn update(&mut self, gfx: &mut System) {
+		self.frame += 1;
+
+		// Requests from older feedback are kept until newer feedback replaces them, so that they don't flicker on frames
+		// where the GPU is running behind.
+		if let Some(requested_pages) = self.feedback.borrow_mut().poll(&gfx.core) {
+			self.requests.clear();
+
+			for index in requested_pages {
+				let page = self.page_from_index(index);
+
+				// Coarser pages are always wanted too, so that there's something sensible to fall back to.
+				for level in page.level..self.num_levels {
+					let shift = level - page.level;
+					let page = PageId { level, x: page.x >> shift, y: page.y >> shift };
+
+					match self.resident.get_mut(&page) {
+						Some(resident) => resident.last_requested_frame = self.frame,
+						None => { self.requests.insert(page); }
+					}
+				}
+			}
+		}
+
+		// The coarsest level is always wanted.
+		let coarsest_level = self.num_levels - 1;
+		let coarsest_pages = level_pages(self.num_pages, coarsest_level);
+		for y in 0..coarsest_pages.y {
+			for x in 0..coarsest_pages.x {
+				let page = PageId { level: coarsest_level, x, y };
+				if !self.resident.contains_key(&page) {
+					self.requests.insert(page);
+				}
+			}
+		}
+
+		self.feedback_slot = self.feedback.borrow_mut().next_slot();
+
+		if let Some(slot) = self.feedback_slot {
+			let feedback = self.feedback.clone();
+			gfx.frame_encoder.command_group(FrameStage::Final)
+				.annotate("Virtual Texture Feedback")
+				.execute(move |core, _| {
+					feedback.borrow_mut().submit(core, slot);
+				});
+		}
+	}
+
+	/// Bind the info ubo, image, page table and feedback buffer for draws in `group`. The page table is bound to
+	/// `image_unit + 1`.
+	pub fn bind(&self, group: &mut CommandGroupEncoder<'_>, ubo_index: u32, image_unit: u32) {
+		let atlas_pages = match self.backing {
+			VirtualTextureBacking::Sparse => 0.0,
+			VirtualTextureBacking::Atlas => self.atlas_pages as f32,
+		};
+
+		let info = VirtualTextureInfo {
+			size: Vec4::new(self.settings.size.x as f32, self.settings.size.y as f32, self.num_pages.x as f32, self.num_pages.y as f32),
+			params: Vec4::new(self.num_levels as f32, self.page_size as f32, atlas_pages, self.feedback_slot.is_some() as u32 as f32),
+		};
+
+		// Something has to be bound even if feedback is disabled this frame.
+		let feedback_buffer = self.feedback.borrow().buffer;
+		let feedback_range = self.feedback_slot.unwrap_or(self.feedback.borrow().slot_range(0));
+
+		group.bind_shared_ubo(ubo_index, &[info]);
+		group.bind_shared_sampled_image(image_unit, self.image, self.image_sampler);
+		group.bind_shared_sampled_image(image_unit + 1, self.page_table, self.page_table_sampler);
+		group.bind_shared_ssbo(VIRTUAL_TEXTURE_FEEDBACK_BINDING, (feedback_buffer, feedback_range));
+	}
+
+	/// Upload the contents of `page`, evicting the least recently requested page if there isn't room.
+	/// `data` must match [`VirtualTextureSettings::format`] and the size of [`VirtualTexture::page_range`].
+	/// Returns false if there was no room, in which case nothing is uploaded.
+	pub fn provide_page(&mut self, gfx: &mut System, page: PageId, data: &impl crate::AsStageableSlice) -> bool {
+		assert!(page.level < self.num_levels, "Virtual texture page {page:?} out of range");
+
+		self.requests.remove(&page);
+
+		if let Some(resident) = self.resident.get(&page) {
+			let slot = resident.slot;
+			let mut group = gfx.frame_encoder.command_group(FrameStage::Start);
+			self.encode_page_upload(&mut group, page, slot, data);
+			return true
+		}
+
+		if self.resident.len() >= self.settings.max_resident_pages && !self.evict_page(gfx) {
+			return false
+		}
+
+		let slot = match self.backing {
+			VirtualTextureBacking::Sparse => Vec2i::zero(),
+			VirtualTextureBacking::Atlas => match self.free_slots.pop() {
+				Some(slot) => slot,
+				None => return false,
+			}
+		};
+
+		let mut group = gfx.frame_encoder.command_group(FrameStage::Start);
+
+		if self.backing == VirtualTextureBacking::Sparse {
+			let image = self.image;
+
+			if page.level >= self.mip_tail_level {
+				if !std::mem::replace(&mut self.mip_tail_committed, true) {
+					let tail_level = self.mip_tail_level;
+					let tail_range = self.level_range(tail_level);
+					group.execute(move |core, _| core.commit_image_pages(image, tail_level, tail_range, true));
+				}
+			} else {
+				let range = self.page_range(page);
+				group.execute(move |core, _| core.commit_image_pages(image, page.level, range, true));
+			}
+		}
+
+		self.encode_page_upload(&mut group, page, slot, data);
+
+		let entry = match self.backing {
+			VirtualTextureBacking::Sparse => [1u16, 1],
+			VirtualTextureBacking::Atlas => [slot.x as u16 + 1, slot.y as u16 + 1],
+		};
+
+		group.upload_image(self.page_table, page.level, page_table_range(page), page_table_format(), &entry);
+
+		self.resident.insert(page, ResidentPage {
+			slot,
+			last_requested_frame: self.frame,
+		});
+
+		true
+	}
+
+	/// Release every resident page - e.g., after the source data has changed.
+	pub fn evict_all(&mut self, gfx: &mut System) {
+		let pages: Vec<PageId> = self.resident.keys().copied().collect();
+		for page in pages {
+			self.evict(gfx, page);
+		}
+	}
+
+	/// Release the image, page table and feedback buffer once the current frame is done with them.
+	pub fn destroy(self, gfx: &mut System) {
+		let VirtualTexture { image, page_table, image_sampler, page_table_sampler, feedback, .. } = self;
+
+		gfx.frame_encoder.command_group(FrameStage::Final)
+			.annotate("Destroy Virtual Texture")
+			.execute(move |core, _| {
+				core.destroy_image(image);
+				core.destroy_image(page_table);
+				core.destroy_sampler(image_sampler);
+				core.destroy_sampler(page_table_sampler);
+				feedback.borrow_mut().destroy(core);
+			});
+	}
+
+	fn encode_page_upload(&self, group: &mut CommandGroupEncoder<'_>, page: PageId, slot: Vec2i, data: &impl crate::AsStageableSlice) {
+		let (level, range) = match self.backing {
+			VirtualTextureBacking::Sparse => (page.level, self.page_range(page)),
+			VirtualTextureBacking::Atlas => (0, ImageRange::from_2d_range(slot * self.page_size, self.page_range(page).size.to_xy())),
+		};
+
+		group.upload_image(self.image, level, range, self.settings.format, data);
+	}
+
+	/// Evict the least recently requested page that hasn't been requested in a while, preferring finer levels.
+	fn evict_page(&mut self, gfx: &mut System) -> bool {
+		let coarsest_level = self.num_levels - 1;
+
+		let candidate = self.resident.iter()
+			.filter(|(page, resident)| page.level < coarsest_level
+				&& resident.last_requested_frame + EVICTION_GRACE_FRAMES < self.frame)
+			.min_by_key(|(page, resident)| (resident.last_requested_frame, std::cmp::Reverse(page.level)))
+			.map(|(page, _)| *page);
+
+		let Some(page) = candidate else {
+			log::trace!("Virtual texture full - no pages can be evicted");
+			return false
+		};
+
+		self.evict(gfx, page);
+		true
+	}
+
+	fn evict(&mut self, gfx: &mut System, page: PageId) {
+		let Some(resident) = self.resident.remove(&page) else { return };
+
+		let mut group = gfx.frame_encoder.command_group(FrameStage::Start);
+		group.upload_image(self.page_table, page.level, page_table_range(page), page_table_format(), &[0u16, 0]);
+
+		match self.backing {
+			VirtualTextureBacking::Atlas => self.free_slots.push(resident.slot),
+
+			// The mip tail is committed as a whole, so stays committed.
+			VirtualTextureBacking::Sparse if page.level < self.mip_tail_level => {
+				let image = self.image;
+				let range = self.page_range(page);
+				group.execute(move |core, _| core.commit_image_pages(image, page.level, range, false));
+			}
+
+			VirtualTextureBacking::Sparse => {}
+		}
+	}
+
+	fn level_range(&self, level: u32) -> ImageRange {
+		let size = Vec2i::new((self.settings.size.x >> level).max(1), (self.settings.size.y >> level).max(1));
+		ImageRange::from_2d_range(Vec2i::zero(), size)
+	}
+
+	fn page_from_index(&self, index: usize) -> PageId {
+		let level = (0..self.num_levels)
+			.rfind(|&level| level_offset(self.num_pages, level) <= index)
+			.unwrap_or(0);
+
+		let pages = level_pages(self.num_pages, level);
+		let index = (index - level_offset(self.num_pages, level)) as i32;
+
+		PageId { level, x: index % pages.x, y: index / pages.x }
+	}
+}
+
+
+fn page_table_format() -> ImageFormat {
+	ImageFormat::RedGreen(ComponentFormat::U16)
+}
+
+fn page_table_range(page: PageId) -> ImageRange {
+	ImageRange::from_2d_range(Vec2i::new(page.x, page.y), Vec2i::splat(1))
+}
+
+fn is_power_of_two_multiple(size: Vec2i, page_size: i32) -> bool {
+	let is_multiple = |size: i32| size > 0 && size % page_size == 0 && (size / page_size).count_ones() == 1;
+	page_size > 0 && is_multiple(size.x) && is_multiple(size.y)
+}
+
+/// Matches `vt_level_pages` in `virtual_texture.glsl`.
+fn level_pages(num_pages: Vec2i, level: u32) -> Vec2i {
+	Vec2i::new((num_pages.x >> level).max(1), (num_pages.y >> level).max(1))
+}
+
+/// Index of the first page of `level` in the feedback bitset.
+fn level_offset(num_pages: Vec2i, level: u32) -> usize {
+	(0..level)
+		.map(|level| level_pages(num_pages, level))
+		.map(|pages| (pages.x * pages.y) as usize)
+		.sum()
+}
+
+
+/// Persistently mapped ring of feedback bitsets, each guarded by a fence. Slots are cleared by the CPU once read.
+struct Feedback {
+	buffer: BufferName,
+	buffer_ptr: *mut u8,
+	num_words: usize,
+	slot_stride: usize,
+
+	next_slot: usize,
+	in_flight: VecDeque<(usize, gl::types::GLsync)>,
+}
+
+impl Feedback {
+	fn new(core: &crate::Core, num_pages: usize) -> Feedback {
+		let num_words = num_pages.div_ceil(32);
+		let alignment = core.capabilities().ssbo_bind_alignment.max(4);
+		let slot_stride = (num_words * 4).div_ceil(alignment) * alignment;
+
+		let flags = gl::MAP_READ_BIT | gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+		let buffer = core.create_buffer();
+		core.set_debug_label(buffer, "Virtual Texture Feedback");
+		core.allocate_buffer_storage(buffer, slot_stride * NUM_FEEDBACK_SLOTS, flags);
+
+		let buffer_ptr = unsafe { core.map_buffer(buffer, None) };
+		assert!(!buffer_ptr.is_null(), "Failed to map virtual texture feedback buffer");
+
+		unsafe {
+			buffer_ptr.write_bytes(0, slot_stride * NUM_FEEDBACK_SLOTS);
+		}
+
+		Feedback {
+			buffer,
+			buffer_ptr,
+			num_words,
+			slot_stride,
+
+			next_slot: 0,
+			in_flight: VecDeque::new(),
+		}
+	}
+
+	fn slot_range(&self, slot: usize) -> BufferRange {
+		BufferRange {
+			offset: slot * self.slot_stride,
+			size: self.num_words * 4,
+		}
+	}
+
+	fn next_slot(&mut self) -> Option<BufferRange> {
+		if self.in_flight.len() >= NUM_FEEDBACK_SLOTS {
+			return None
+		}
+
+		Some(self.slot_range(self.next_slot))
+	}
+
+	fn submit(&mut self, core: &crate::Core, range: BufferRange) {
+		let fence = unsafe {
+			core.gl.MemoryBarrier(gl::CLIENT_MAPPED_BUFFER_BARRIER_BIT);
+			core.gl.FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
+		};
+
+		let slot = range.offset / self.slot_stride;
+		self.in_flight.push_back((slot, fence));
+		self.next_slot = (slot + 1) % NUM_FEEDBACK_SLOTS;
+	}
+
+	/// Indices of pages flagged in any completed slots, without waiting. None if no slots have completed.
+	fn poll(&mut self, core: &crate::Core) -> Option<Vec<usize>> {
+		let mut pages = None;
+
+		while let Some(&(slot, fence)) = self.in_flight.front() {
+			let result = unsafe { core.gl.ClientWaitSync(fence, 0, 0) };
+			if !matches!(result, gl::ALREADY_SIGNALED | gl::CONDITION_SATISFIED) {
+				break
+			}
+
+			unsafe {
+				core.gl.DeleteSync(fence);
+
+				let pages = pages.get_or_insert_with(Vec::new);
+				let words_ptr = self.buffer_ptr.add(slot * self.slot_stride).cast::<u32>();
+				for word_index in 0..self.num_words {
+					let word_ptr = words_ptr.add(word_index);
+					let mut word = word_ptr.read_volatile();
+					word_ptr.write_volatile(0);
+
+					while word != 0 {
+						let bit = word.trailing_zeros() as usize;
+						pages.push(word_index * 32 + bit);
+						word &= word - 1;
+					}
+				}
+			}
+
+			self.in_flight.pop_front();
+		}
+
+		pages
+	}
+
+	fn destroy(&mut self, core: &crate::Core) {
+		for (_, fence) in self.in_flight.drain(..) {
+			unsafe { core.gl.DeleteSync(fence) };
+		}
+
+		unsafe {
+			core.unmap_buffer(self.buffer);
+		}
+
+		self.buffer_ptr = std::ptr::null_mut();
+		core.destroy_buffer(self.buffer);
+	}
+}
+
+
+pub(crate) fn register_shader_imports(imports: &mut ShaderImports) {
+	let source = VirtualTextureInfo::glsl_declarations() + shaders::VIRTUAL_TEXTURE_GLSL_SOURCE;
+	imports.register(VIRTUAL_TEXTURE_IMPORT, source);
+}
@@ -0,0 +1,313 @@
+//! Typed, generational handles shared by all systems - gfx resources, tasks, dialogs, and eventually meshes and
+//! materials - so references between systems are checked the same way everywhere.
+//!
+//! A [`Handle`] is an owning reference - whoever creates one (usually a system like the gfx `ResourceManager`)
+//! is responsible for releasing it. A [`WeakHandle`] is non-owning, and must be upgraded before use, which fails once
+//! the handle has been released. Since slots are reused with a new generation, stale handles never alias new ones.
+//!
+//! Every live handle is tracked in a global registry along with a debug name, which can be inspected with
+//! [`live_handles`] or [`dump_registry`] to track down leaks.
+
+use std::any::TypeId;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+#[cfg(test)]
+mod test;
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+
+pub struct Handle<T> {
+	index: u32,
+	generation: u32,
+	_marker: PhantomData<fn() -> T>,
+}
+
+impl<T: 'static> Handle<T> {
+	/// Allocate a new live handle.
+	pub fn new(debug_name: impl Into<String>) -> Handle<T> {
+		let (index, generation) = with_kind::<T, _>(|kind| kind.allocate(debug_name.into()));
+		Handle::from_parts(index, generation)
+	}
+
+	/// Mark this handle as dead. Returns false if it had already been released.
+	/// Any copies of this handle, and [`WeakHandle`]s to it, are invalidated.
+	pub fn release(self) -> bool {
+		with_kind::<T, _>(|kind| kind.release(self.index, self.generation))
+	}
+
+	pub fn is_alive(self) -> bool {
+		with_kind::<T, _>(|kind| kind.entry(self.index, self.generation).is_some())
+	}
+
+	/// None if this handle has been released.
+	pub fn debug_name(self) -> Option<String> {
+		with_kind::<T, _>(|kind| kind.entry(self.index, self.generation).map(|entry| entry.debug_name.clone()))
+	}
+
+	pub fn set_debug_name(self, debug_name: impl Into<String>) {
+		with_kind::<T, _>(|kind| {
+			if let Some(entry) = kind.entry_mut(self.index, self.generation) {
+				entry.debug_name = debug_name.into();
+			}
+		})
+	}
+
+	pub fn downgrade(self) -> WeakHandle<T> {
+		WeakHandle { handle: self }
+	}
+}
+
+impl<T> Handle<T> {
+	/// Reconstruct a handle from [`Handle::index`] and [`Handle::generation`], e.g., after packing it into another
+	/// id space. The result isn't checked against the registry.
+	pub const fn from_parts(index: u32, generation: u32) -> Handle<T> {
+		Handle { index, generation, _marker: PhantomData }
+	}
+
+	pub fn index(self) -> u32 {
+		self.index
+	}
+
+	pub fn generation(self) -> u32 {
+		self.generation
+	}
+}
+
+// Implemented manually, since derives would require T to implement these too.
+impl<T> Copy for Handle<T> {}
+
+impl<T> Clone for Handle<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> PartialEq for Handle<T> {
+	fn eq(&self, other: &Self) -> bool {
+		(self.index, self.generation) == (other.index, other.generation)
+	}
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> PartialOrd for Handle<T> {
+	fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl<T> Ord for Handle<T> {
+	fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+		(self.index, self.generation).cmp(&(other.index, other.generation))
+	}
+}
+
+impl<T> Hash for Handle<T> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.index.hash(state);
+		self.generation.hash(state);
+	}
+}
+
+impl<T: 'static> fmt::Debug for Handle<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}#{}.{}", short_type_name::<T>(), self.index, self.generation)?;
+
+		match self.debug_name() {
+			Some(name) if !name.is_empty() => write!(f, " '{name}'"),
+			Some(_) => Ok(()),
+			None => write!(f, " (released)"),
+		}
+	}
+}
+
+
+/// Non-owning reference to a [`Handle`], which can't be used until it's upgraded.
+pub struct WeakHandle<T> {
+	handle: Handle<T>,
+}
+
+impl<T: 'static> WeakHandle<T> {
+	/// None if the handle has been released.
+	pub fn upgrade(self) -> Option<Handle<T>> {
+		self.handle.is_alive().then_some(self.handle)
+	}
+
+	pub fn is_alive(self) -> bool {
+		self.handle.is_alive()
+	}
+}
+
+impl<T> Copy for WeakHandle<T> {}
+
+impl<T> Clone for WeakHandle<T> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<T> PartialEq for WeakHandle<T> {
+	fn eq(&self, other: &Self) -> bool {
+		self.handle == other.handle
+	}
+}
+
+impl<T> Eq for WeakHandle<T> {}
+
+impl<T> Hash for WeakHandle<T> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.handle.hash(state);
+	}
+}
+
+impl<T: 'static> fmt::Debug for WeakHandle<T> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "Weak({:?})", self.handle)
+	}
+}
+
+
+/// A live handle, as listed by [`live_handles`].
+#[derive(Debug, Clone)]
+pub struct HandleInfo {
+	/// Name of the type the handle refers to, without its module path.
+	pub kind: &'static str,
+	pub index: u32,
+	pub generation: u32,
+	pub debug_name: String,
+}
+
+/// Every handle that has been created but not released, grouped by kind.
+pub fn live_handles() -> Vec<HandleInfo> {
+	let registry = REGISTRY.lock().unwrap();
+
+	registry.kinds.iter()
+		.flat_map(|kind| {
+			kind.entries.iter().enumerate()
+				.filter(|(_, entry)| entry.alive)
+				.map(|(index, entry)| HandleInfo {
+					kind: kind.name,
+					index: index as u32,
+					generation: entry.generation,
+					debug_name: entry.debug_name.clone(),
+				})
+		})
+		.collect()
+}
+
+/// Number of live handles of each kind.
+pub fn live_handle_counts() -> Vec<(&'static str, usize)> {
+	let registry = REGISTRY.lock().unwrap();
+
+	registry.kinds.iter()
+		.map(|kind| (kind.name, kind.entries.iter().filter(|entry| entry.alive).count()))
+		.collect()
+}
+
+/// Human readable listing of every live handle, for logging.
+pub fn dump_registry() -> String {
+	let mut dump = String::new();
+
+	for HandleInfo{kind, index, generation, debug_name} in live_handles() {
+		dump += &format!("{kind}#{index}.{generation} '{debug_name}'\n");
+	}
+
+	dump
+}
+
+
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry { kinds: Vec::new() });
+
+struct Registry {
+	// Only a handful of kinds exist, so a linear search is fine.
+	kinds: Vec<KindRegistry>,
+}
+
+struct KindRegistry {
+	type_id: TypeId,
+	name: &'static str,
+
+	entries: Vec<Entry>,
+	free_indices: Vec<u32>,
+}
+
+struct Entry {
+	generation: u32,
+	alive: bool,
+	debug_name: String,
+}
+
+impl KindRegistry {
+	fn allocate(&mut self, debug_name: String) -> (u32, u32) {
+		if let Some(index) = self.free_indices.pop() {
+			let entry = &mut self.entries[index as usize];
+			entry.generation = entry.generation.wrapping_add(1);
+			entry.alive = true;
+			entry.debug_name = debug_name;
+			return (index, entry.generation)
+		}
+
+		let index = self.entries.len() as u32;
+		self.entries.push(Entry { generation: 0, alive: true, debug_name });
+		(index, 0)
+	}
+
+	fn release(&mut self, index: u32, generation: u32) -> bool {
+		let Some(entry) = self.entry_mut(index, generation) else {
+			return false
+		};
+
+		entry.alive = false;
+		entry.debug_name = String::new();
+		self.free_indices.push(index);
+		true
+	}
+
+	fn entry(&self, index: u32, generation: u32) -> Option<&Entry> {
+		self.entries.get(index as usize)
+			.filter(|entry| entry.alive && entry.generation == generation)
+	}
+
+	fn entry_mut(&mut self, index: u32, generation: u32) -> Option<&mut Entry> {
+		self.entries.get_mut(index as usize)
+			.filter(|entry| entry.alive && entry.generation == generation)
+	}
+}
+
+fn with_kind<T: 'static, R>(f: impl FnOnce(&mut KindRegistry) -> R) -> R {
+	let mut registry = REGISTRY.lock().unwrap();
+	let type_id = TypeId::of::<T>();
+
+	let kind_index = match registry.kinds.iter().position(|kind| kind.type_id == type_id) {
+		Some(index) => index,
+		None => {
+			registry.kinds.push(KindRegistry {
+				type_id,
+				name: short_type_name::<T>(),
+				entries: Vec::new(),
+				free_indices: Vec::new(),
+			});
+
+			registry.kinds.len() - 1
+		}
+	};
+
+	f(&mut registry.kinds[kind_index])
+}
+
+fn short_type_name<T>() -> &'static str {
+	let name = std::any::type_name::<T>();
+
+	// Strip module paths, but only before any generic arguments.
+	let path_end = name.find('<').unwrap_or(name.len());
+	match name[..path_end].rfind("::") {
+		Some(index) => &name[index + 2..],
+		None => name,
+	}
+}
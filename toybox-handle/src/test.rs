@@ -0,0 +1,59 @@
+use super::*;
+
+
+// Each test uses its own kinds, since the registry is shared between tests running in parallel.
+struct Texture;
+struct Sound;
+struct Mesh;
+struct Material;
+
+
+#[test]
+fn released_handles_are_invalidated() {
+	let handle = Handle::<Texture>::new("grass.png");
+	let copy = handle;
+	let weak = handle.downgrade();
+
+	assert!(handle.is_alive());
+	assert_eq!(handle.debug_name().as_deref(), Some("grass.png"));
+	assert_eq!(weak.upgrade(), Some(handle));
+
+	assert!(handle.release());
+	assert!(!handle.release(), "Releasing twice should fail");
+
+	assert!(!copy.is_alive(), "Copies should be released too");
+	assert_eq!(weak.upgrade(), None, "Weak handles shouldn't upgrade after release");
+	assert_eq!(handle.debug_name(), None);
+}
+
+#[test]
+fn reused_slots_get_new_generations() {
+	let first = Handle::<Sound>::new("first");
+	first.release();
+
+	let second = Handle::<Sound>::new("second");
+	assert_eq!(first.index(), second.index(), "Released slots should be reused");
+	assert_ne!(first, second, "Reused slots shouldn't compare equal to stale handles");
+
+	assert!(!first.is_alive(), "Stale handles shouldn't alias new ones");
+	assert_eq!(first.downgrade().upgrade(), None);
+	assert_eq!(second.debug_name().as_deref(), Some("second"));
+}
+
+#[test]
+fn registry_lists_live_handles_by_kind() {
+	let mesh = Handle::<Mesh>::new("cube");
+	let released_mesh = Handle::<Mesh>::new("sphere");
+	released_mesh.release();
+
+	let material = Handle::<Material>::new("stone");
+	material.set_debug_name("mossy stone");
+
+	let live = live_handles();
+	assert!(live.iter().any(|info| info.kind == "Mesh" && info.index == mesh.index() && info.debug_name == "cube"));
+	assert!(!live.iter().any(|info| info.kind == "Mesh" && info.debug_name == "sphere"), "Released handles shouldn't be listed");
+	assert!(live.iter().any(|info| info.kind == "Material" && info.debug_name == "mossy stone"));
+
+	assert!(dump_registry().contains("Mesh#"));
+	assert_eq!(format!("{mesh:?}"), format!("Mesh#{}.{} 'cube'", mesh.index(), mesh.generation()));
+}
@@ -0,0 +1,74 @@
+use glutin::prelude::*;
+use glutin::api::egl;
+use glutin::config::{ConfigTemplateBuilder, ConfigSurfaceTypes, Api};
+use glutin::context::{GlProfile, ContextApi, Version, ContextAttributesBuilder};
+use glutin::surface::{PbufferSurface, SurfaceAttributesBuilder};
+
+use anyhow::Context;
+use std::num::NonZeroU32;
+
+
+/// An OpenGL context without a window, backed by a fixed size pbuffer. For tests and offline tools.
+/// Only available where EGL device enumeration is - which in practice means linux.
+pub struct HeadlessHost {
+	pub context: egl::context::PossiblyCurrentContext,
+	pub gl: gl::Gl,
+
+	pub surface: egl::surface::Surface<PbufferSurface>,
+	pub size: (u32, u32),
+}
+
+impl HeadlessHost {
+	#[tracing::instrument(name="host HeadlessHost::new")]
+	pub fn new(width: u32, height: u32) -> anyhow::Result<HeadlessHost> {
+		let device = egl::device::Device::query_devices()
+			.context("Querying EGL devices")?
+			.next()
+			.context("No EGL devices available")?;
+
+		let display = unsafe { egl::display::Display::with_device(&device, None)? };
+
+		let config_template = ConfigTemplateBuilder::new()
+			.with_api(Api::OPENGL)
+			.with_surface_type(ConfigSurfaceTypes::PBUFFER)
+			.with_stencil_size(8)
+			.build();
+
+		let config = unsafe { display.find_configs(config_template)? }
+			.find(|config| config.srgb_capable())
+			.context("No suitable headless config")?;
+
+		let context_attributes = ContextAttributesBuilder::new()
+			.with_debug(true)
+			.with_profile(GlProfile::Core)
+			.with_context_api(ContextApi::OpenGl(Some(Version::new(4, 6))))
+			.build(None);
+
+		let context = unsafe { display.create_context(&config, &context_attributes)? };
+
+		let surface_attributes = SurfaceAttributesBuilder::<PbufferSurface>::new()
+			.with_srgb(Some(true))
+			.build(
+				NonZeroU32::new(width).context("Headless surface width must be non-zero")?,
+				NonZeroU32::new(height).context("Headless surface height must be non-zero")?,
+			);
+
+		let surface = unsafe { display.create_pbuffer_surface(&config, &surface_attributes)? };
+		let context = context.make_current(&surface)?;
+
+		let gl = gl::Gl::load_with(|symbol| {
+			let symbol = std::ffi::CString::new(symbol).unwrap();
+			display.get_proc_address(symbol.as_c_str()).cast()
+		});
+
+		log::info!("Headless context created with {config:?}");
+
+		Ok(HeadlessHost {
+			context,
+			gl,
+
+			surface,
+			size: (width, height),
+		})
+	}
+}
@@ -1,6 +1,7 @@
 pub use gl;
 pub use winit;
 pub use glutin;
+pub use raw_window_handle;
 
 use std::rc::Rc;
 
@@ -24,6 +25,26 @@ use raw_window_handle::HasWindowHandle;
 
 use std::num::NonZeroU32;
 
+mod splash;
+pub use splash::{SplashSettings, SplashScreen};
+
+mod log_tap;
+pub use log_tap::{LogEntry, tap_logs};
+
+mod taskbar_progress;
+pub use taskbar_progress::TaskbarProgress;
+
+#[cfg(feature="xr")]
+pub mod xr;
+
+#[cfg(all(unix, not(target_os="macos")))]
+mod headless;
+#[cfg(all(unix, not(target_os="macos")))]
+pub use headless::HeadlessHost;
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod prelude {
 	pub use gl;
 	pub use winit;
@@ -63,17 +84,22 @@ pub fn start<F, H>(settings: Settings<'_>, start_hostee: F) -> anyhow::Result<()
 		.with_stencil_size(8) // TODO(pat.m): don't rely on default backbuffer
 		.with_transparency(settings.transparent);
 
-	let gl_context_attributes = ContextAttributesBuilder::new()
-		.with_debug(true)
-		.with_profile(GlProfile::Core)
-		.with_robustness(Robustness::RobustLoseContextOnReset)
-		.with_context_api(ContextApi::OpenGl(Some(Version::new(4, 6))));
 
+	let splash_settings = settings.splash.map(|splash| OwnedSplashSettings {
+		background_color: splash.background_color,
+		progress_bar_color: splash.progress_bar_color,
+		logo: splash.logo.map(|(width, height, data)| (width, height, data.to_vec())),
+	});
 
 	let bootstrap_state = BootstrapState {
+		app_name: settings.app_name.to_owned(),
+		xr: settings.xr,
+
 		window_attributes,
+		prefer_10bit_color: settings.prefer_10bit_color,
+		splash_settings,
 		gl_config_template,
-		gl_context_attributes,
+		gl_context_mode: settings.gl_context_mode,
 
 		_span,
 	};
@@ -100,25 +126,42 @@ impl<F, H> ApplicationHandler for ApplicationHost<F, H>
 		, H: HostedApp + 'static
 {
 	fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-		let ApplicationHost::Bootstrap(state, start_hostee) = std::mem::take(self) else { return };
+		let ApplicationHost::Bootstrap(mut state, start_hostee) = std::mem::take(self) else { return };
 
 		log::trace!("Bootstrapping ApplicationHost");
 
-		let host = state.bootstrap(event_loop).expect("Failed to bootstrap application");
+		let splash_settings = state.splash_settings.take();
+		let mut host = state.bootstrap(event_loop).expect("Failed to bootstrap application");
 
 		// Enable vsync
 		host.set_vsync(true);
 
+		if let Some(settings) = splash_settings {
+			let settings = SplashSettings {
+				background_color: settings.background_color,
+				progress_bar_color: settings.progress_bar_color,
+				logo: settings.logo.as_ref().map(|(width, height, data)| (*width, *height, data.as_slice())),
+			};
+
+			let splash_screen = host.create_splash_screen(&settings);
+			splash_screen.present(None);
+			host.splash_screen = Some(Rc::new(splash_screen));
+		}
+
 		let mut hosted_app = start_hostee(&host)
 			.expect("Failed to start hosted app");
 
+		host.splash_screen = None;
+
 		mark_tracy_frame();
 
 		// Draw before making the window visisble
 		hosted_app.draw(event_loop);
 
 		host.window.pre_present_notify();
+		hosted_app.begin_swap();
 		host.swap();
+		hosted_app.end_swap();
 
 		mark_tracy_frame();
 
@@ -150,7 +193,9 @@ impl<F, H> ApplicationHandler for ApplicationHost<F, H>
 				hosted_app.draw(event_loop);
 
 				host.window.pre_present_notify();
+				hosted_app.begin_swap();
 				host.swap();
+				hosted_app.end_swap();
 
 				mark_tracy_frame();
 			}
@@ -160,6 +205,13 @@ impl<F, H> ApplicationHandler for ApplicationHost<F, H>
 				hosted_app.window_event(event_loop, event);
 			}
 
+			// Not every platform follows a scale change with a Resized event, so resync the surface just in case.
+			event @ WindowEvent::ScaleFactorChanged{..} => {
+				let PhysicalSize{width, height} = host.window.inner_size();
+				host.resize(width, height);
+				hosted_app.window_event(event_loop, event);
+			}
+
 			event => {
 				hosted_app.window_event(event_loop, event);
 			}
@@ -195,6 +247,10 @@ pub trait HostedApp {
 
 	fn draw(&mut self, _: &ActiveEventLoop) {}
 
+	/// Called immediately before and after each buffer swap.
+	fn begin_swap(&mut self) {}
+	fn end_swap(&mut self) {}
+
 	fn shutdown(&mut self, _: &ActiveEventLoop) {}
 }
 
@@ -205,6 +261,11 @@ pub struct Settings<'title> {
 	pub app_name: &'title str,
 	pub transparent: bool,
 	pub no_decorations: bool,
+	pub prefer_10bit_color: bool,
+	pub reverse_z: bool,
+	pub gl_context_mode: GlContextMode,
+	pub splash: Option<SplashSettings<'title>>,
+	pub xr: bool,
 }
 
 impl<'title> Settings<'title> {
@@ -213,6 +274,11 @@ impl<'title> Settings<'title> {
 			app_name,
 			transparent: false,
 			no_decorations: false,
+			prefer_10bit_color: false,
+			reverse_z: false,
+			gl_context_mode: GlContextMode::default(),
+			splash: None,
+			xr: false,
 		}
 	}
 
@@ -225,16 +291,112 @@ impl<'title> Settings<'title> {
 		self.no_decorations = true;
 		self
 	}
+
+	/// Request a backbuffer with 10 bits per channel if available, to reduce banding.
+	/// See [`Host::color_bits`] for what was actually created.
+	pub fn prefer_10bit_color(mut self) -> Self {
+		self.prefer_10bit_color = true;
+		self
+	}
+
+	/// Use the reverse-Z depth convention for all rendering. Doesn't affect the host itself, but is read by the
+	/// renderer at startup.
+	pub fn reverse_z(mut self) -> Self {
+		self.reverse_z = true;
+		self
+	}
+
+	/// Override what kind of OpenGL context is created. See [`GlContextMode`] for the default.
+	pub fn gl_context_mode(mut self, mode: GlContextMode) -> Self {
+		self.gl_context_mode = mode;
+		self
+	}
+
+	/// Show a minimal loading screen while the hosted app is starting. See [`Host::splash_screen`].
+	pub fn splash(mut self, splash: SplashSettings<'title>) -> Self {
+		self.splash = Some(splash);
+		self
+	}
+
+	/// Experimental - try to start an OpenXR session at startup. Requires the `xr` feature.
+	/// See [`Host::take_xr_session`]. Falls back to desktop rendering if no runtime or headset is available.
+	pub fn xr(mut self) -> Self {
+		self.xr = true;
+		self
+	}
 }
 
 
+/// What kind of OpenGL context to create. Defaults to [`GlContextMode::Debug`] in debug builds and
+/// [`GlContextMode::Release`] otherwise.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GlContextMode {
+	/// Full debug context, with debug output for every error and warning. Slow, but catches misuse early.
+	Debug,
+
+	/// Regular context. Errors are still generated, but the driver may report less through debug output.
+	Release,
+
+	/// KHR_no_error context - the driver skips error checking entirely, which measurably reduces per-call overhead.
+	/// Any errors are undefined behaviour instead, so this should only be used for well tested release builds.
+	/// Falls back to [`GlContextMode::Release`] if the driver doesn't support it.
+	NoError,
+}
 
+impl Default for GlContextMode {
+	fn default() -> Self {
+		match cfg!(debug_assertions) {
+			true => GlContextMode::Debug,
+			false => GlContextMode::Release,
+		}
+	}
+}
+
+impl GlContextMode {
+	/// Parse a mode from config - one of `debug`, `release` or `no_error`.
+	pub fn from_name(name: &str) -> Option<GlContextMode> {
+		match name {
+			"debug" => Some(GlContextMode::Debug),
+			"release" => Some(GlContextMode::Release),
+			"no_error" => Some(GlContextMode::NoError),
+			_ => None,
+		}
+	}
+
+	fn context_attributes(self) -> ContextAttributesBuilder {
+		// No-error contexts can't also be robust or debug contexts.
+		let robustness = match self {
+			GlContextMode::NoError => Robustness::NoError,
+			_ => Robustness::RobustLoseContextOnReset,
+		};
+
+		ContextAttributesBuilder::new()
+			.with_debug(self == GlContextMode::Debug)
+			.with_profile(GlProfile::Core)
+			.with_robustness(robustness)
+			.with_context_api(ContextApi::OpenGl(Some(Version::new(4, 6))))
+	}
+}
+
+
+
+
+struct OwnedSplashSettings {
+	background_color: [f32; 4],
+	progress_bar_color: [f32; 4],
+	logo: Option<(u32, u32, Vec<u8>)>,
+}
 
 struct BootstrapState {
+	app_name: String,
+	xr: bool,
+
 	window_attributes: WindowAttributes,
+	prefer_10bit_color: bool,
+	splash_settings: Option<OwnedSplashSettings>,
 
 	gl_config_template: ConfigTemplateBuilder,
-	gl_context_attributes: ContextAttributesBuilder,
+	gl_context_mode: GlContextMode,
 
 	_span: tracing::span::EnteredSpan,
 }
@@ -264,17 +426,34 @@ impl BootstrapState {
 		// Try to create our window and a config that describes a context we can create
 		let _span = tracing::info_span!("host build display").entered();
 
+		let prefer_10bit_color = self.prefer_10bit_color;
+
+		// OpenXR can only bind to native GL contexts.
+		let api_preference = match self.xr {
+			true => ApiPreference::FallbackEgl,
+			false => ApiPreference::PreferEgl,
+		};
+
 		let (maybe_window, gl_config) = DisplayBuilder::new()
 			.with_window_attributes(Some(self.window_attributes.clone()))
-			.with_preference(ApiPreference::PreferEgl)
+			.with_preference(api_preference)
 			.build(event_loop, self.gl_config_template, |configs| {
-				for config in configs {
-					// We require an sRGB capable backbuffer
-					if !config.srgb_capable() { continue }
-					return config;
+				// We require an sRGB capable backbuffer
+				let mut configs = configs.filter(|config| config.srgb_capable());
+
+				let first_config = configs.next().expect("No suitable config");
+				if !prefer_10bit_color {
+					return first_config;
 				}
 
-				panic!("No suitable config");
+				std::iter::once(first_config.clone())
+					.chain(configs)
+					.find(|config| config_color_bits(config) >= 10)
+					.inspect(|_| log::info!("Found 10 bit backbuffer config"))
+					.unwrap_or_else(|| {
+						log::warn!("10 bit backbuffer requested, but no suitable configs found - falling back to 8 bit");
+						first_config
+					})
 			})
 			.map_err(|e| anyhow::format_err!("Failed to find suitable surface config: {e}"))?;
 
@@ -289,17 +468,28 @@ impl BootstrapState {
 
 		let _span = tracing::info_span!("host create opengl context").entered();
 
-		let gl_context_attributes = self.gl_context_attributes.build(maybe_raw_window_handle);
 		let gl_display = gl_config.display();
+		let mut gl_context_mode = self.gl_context_mode;
 
 		// Create our context
-		let non_current_gl_context = unsafe {
-			gl_display.create_context(&gl_config, &gl_context_attributes)?
+		let gl_context_attributes = gl_context_mode.context_attributes().build(maybe_raw_window_handle);
+		let non_current_gl_context = match unsafe { gl_display.create_context(&gl_config, &gl_context_attributes) } {
+			Ok(context) => context,
+
+			Err(error) if gl_context_mode == GlContextMode::NoError => {
+				log::warn!("Failed to create no-error context, falling back to release context: {error}");
+				gl_context_mode = GlContextMode::Release;
+
+				let gl_context_attributes = gl_context_mode.context_attributes().build(maybe_raw_window_handle);
+				unsafe { gl_display.create_context(&gl_config, &gl_context_attributes)? }
+			}
+
+			Err(error) => return Err(error.into()),
 		};
 
 		_span.exit();
 
-		log::info!("Context created with {gl_context_attributes:?}");
+		log::info!("{gl_context_mode:?} context created");
 
 		// Create our window for real if not already
 		let window = match maybe_window {
@@ -337,43 +527,113 @@ impl BootstrapState {
 			gl_display.get_proc_address(symbol.as_c_str()).cast()
 		}));
 
+		#[cfg(feature="xr")]
+		let xr_session = match self.xr {
+			true => xr::XrSession::new(&self.app_name, &gl_context)
+				.inspect_err(|error| log::error!("Failed to start OpenXR session - falling back to desktop: {error:?}"))
+				.ok(),
+
+			false => None,
+		};
+
+		#[cfg(not(feature="xr"))]
+		if self.xr {
+			log::warn!("XR requested but toybox-host was built without the `xr` feature");
+		}
+
 		Ok(Host {
-			context: gl_context,
+			app_name: self.app_name.clone(),
+			context: Rc::new(gl_context),
 			gl,
 
 			window,
-			surface: gl_surface,
+			surface: Rc::new(gl_surface),
+			splash_screen: None,
+
+			#[cfg(feature="xr")]
+			xr_session: std::cell::RefCell::new(xr_session),
 
 			config: gl_config,
 			window_attributes: self.window_attributes,
+			gl_context_mode,
 		})
 	}
 }
 
 
+#[derive(Clone)]
+pub struct SwapControl {
+	context: Rc<glutin::context::PossiblyCurrentContext>,
+	surface: Rc<glutin::surface::Surface<WindowSurface>>,
+}
+
+impl SwapControl {
+	pub fn set_vsync(&self, enabled: bool) {
+		let interval = match enabled {
+			false => SwapInterval::DontWait,
+			true => SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
+		};
+
+		if let Err(error) = self.surface.set_swap_interval(&self.context, interval) {
+			log::warn!("Failed to set swap interval: {error}");
+		}
+	}
+}
+
+
 pub struct Host {
-	pub context: glutin::context::PossiblyCurrentContext,
+	/// See [`Settings::app_name`].
+	pub app_name: String,
+
+	pub context: Rc<glutin::context::PossiblyCurrentContext>,
 	pub gl: gl::Gl,
 
 	pub config: glutin::config::Config,
 	pub window_attributes: WindowAttributes,
 
+	/// The kind of context that was actually created - which may differ from [`Settings::gl_context_mode`] if no-error
+	/// contexts aren't supported.
+	pub gl_context_mode: GlContextMode,
+
 	pub window: Rc<Window>,
-	pub surface: glutin::surface::Surface<WindowSurface>,
+	pub surface: Rc<glutin::surface::Surface<WindowSurface>>,
+
+	splash_screen: Option<Rc<SplashScreen>>,
+
+	#[cfg(feature="xr")]
+	xr_session: std::cell::RefCell<Option<xr::XrSession>>,
 }
 
 impl Host {
 	pub fn set_vsync(&self, enabled: bool) {
-		let interval = match enabled {
-			false => SwapInterval::DontWait,
-			true => SwapInterval::Wait(NonZeroU32::new(1).unwrap()),
-		};
+		self.swap_control().set_vsync(enabled);
+	}
 
-		if let Err(error) = self.surface.set_swap_interval(&self.context, interval) {
-			log::warn!("Failed to set swap interval: {error}");
+	/// Handle for changing the swap interval after startup.
+	pub fn swap_control(&self) -> SwapControl {
+		SwapControl {
+			context: self.context.clone(),
+			surface: self.surface.clone(),
 		}
 	}
 
+	/// The splash screen configured with [`Settings::splash`], if any. Only available while the hosted app is starting,
+	/// and can be used to report loading progress.
+	pub fn splash_screen(&self) -> Option<Rc<SplashScreen>> {
+		self.splash_screen.clone()
+	}
+
+	/// The OpenXR session started because of [`Settings::xr`], if it succeeded. Can only be taken once.
+	#[cfg(feature="xr")]
+	pub fn take_xr_session(&self) -> Option<xr::XrSession> {
+		self.xr_session.borrow_mut().take()
+	}
+
+	/// Bits per channel of the backbuffer.
+	pub fn color_bits(&self) -> u32 {
+		config_color_bits(&self.config)
+	}
+
 	pub fn swap(&self) {
 		if let Err(error) = self.surface.swap_buffers(&self.context) {
 			// TODO(pat.m): possibly try to recreate surface if lost
@@ -388,6 +648,15 @@ impl Host {
 	}
 }
 
+fn config_color_bits(config: &glutin::config::Config) -> u32 {
+	use glutin::config::ColorBufferType;
+
+	match config.color_buffer_type() {
+		Some(ColorBufferType::Rgb{r_size, g_size, b_size}) => r_size.min(g_size).min(b_size) as u32,
+		_ => 8,
+	}
+}
+
 fn init_logging() {
 	let mut log_builder = env_logger::builder();
 	log_builder.parse_default_env();
@@ -398,7 +667,12 @@ fn init_logging() {
 		log_builder.filter_level(log::LevelFilter::Debug);
 	}
 
-	log_builder.init();
+	let logger = log_builder.build();
+	let max_level = logger.filter();
+
+	log::set_boxed_logger(Box::new(log_tap::TappedLogger { inner: logger }))
+		.expect("Logger already initialized");
+	log::set_max_level(max_level);
 
 	log::info!("Logger initialized");
 }
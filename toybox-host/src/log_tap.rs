@@ -0,0 +1,71 @@
+use std::cell::Cell;
+use std::sync::{Mutex, mpsc};
+
+
+/// A log record forwarded to receivers created with [`tap_logs`].
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+	pub level: log::Level,
+	pub target: String,
+	pub message: String,
+}
+
+
+static TAPS: Mutex<Vec<mpsc::Sender<LogEntry>>> = Mutex::new(Vec::new());
+
+thread_local! {
+	/// Set while forwarding a record to taps, so that logging from inside a tap is dropped instead of deadlocking.
+	static IN_TAP: Cell<bool> = const { Cell::new(false) };
+}
+
+
+/// Receive a copy of every log record that passes the logger's filter, from any thread, from now on.
+/// Taps are removed once their receivers are dropped.
+pub fn tap_logs() -> mpsc::Receiver<LogEntry> {
+	let (tx, rx) = mpsc::channel();
+	TAPS.lock().unwrap().push(tx);
+	rx
+}
+
+
+pub(crate) struct TappedLogger {
+	pub inner: env_logger::Logger,
+}
+
+impl log::Log for TappedLogger {
+	fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+		self.inner.enabled(metadata)
+	}
+
+	fn log(&self, record: &log::Record<'_>) {
+		if !self.inner.matches(record) {
+			return
+		}
+
+		self.inner.log(record);
+
+		if IN_TAP.replace(true) {
+			return
+		}
+
+		let _in_tap_guard = common::defer(|| IN_TAP.set(false));
+
+		let mut taps = TAPS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+		if taps.is_empty() {
+			return
+		}
+
+		let entry = LogEntry {
+			level: record.level(),
+			target: record.target().to_owned(),
+			message: record.args().to_string(),
+		};
+
+		taps.retain(|tap| tap.send(entry.clone()).is_ok());
+	}
+
+	fn flush(&self) {
+		self.inner.flush();
+	}
+}
+
@@ -0,0 +1,170 @@
+use crate::{Host, Surface, GlContext};
+use winit::window::Window;
+
+use std::rc::Rc;
+
+
+/// What to show while the hosted app is starting up.
+#[derive(Debug, Clone)]
+pub struct SplashSettings<'logo> {
+	/// sRGB encoded.
+	pub background_color: [f32; 4],
+	/// sRGB encoded.
+	pub progress_bar_color: [f32; 4],
+
+	/// Size and tightly packed rgba8 texels of an image to draw centered in the window, top row first.
+	pub logo: Option<(u32, u32, &'logo [u8])>,
+}
+
+impl Default for SplashSettings<'_> {
+	fn default() -> Self {
+		SplashSettings {
+			background_color: [0.1, 0.1, 0.1, 1.0],
+			progress_bar_color: [0.8, 0.8, 0.8, 1.0],
+			logo: None,
+		}
+	}
+}
+
+impl<'logo> SplashSettings<'logo> {
+	pub fn logo(self, width: u32, height: u32, rgba8: &'logo [u8]) -> Self {
+		assert_eq!(rgba8.len(), (4 * width * height) as usize, "Splash logo not passed expected amount of data");
+		Self { logo: Some((width, height, rgba8)), .. self }
+	}
+}
+
+
+/// Draws minimal loading frames directly with GL while the hosted app is being constructed, so the window isn't
+/// frozen or invisible. Doesn't rely on any gfx state, and restores any state it touches.
+pub struct SplashScreen {
+	gl: gl::Gl,
+	window: Rc<Window>,
+	surface: Rc<Surface>,
+	context: Rc<GlContext>,
+
+	background_color: [f32; 4],
+	progress_bar_color: [f32; 4],
+
+	logo: Option<Logo>,
+}
+
+struct Logo {
+	width: i32,
+	height: i32,
+	image: u32,
+	framebuffer: u32,
+}
+
+impl Host {
+	pub fn create_splash_screen(&self, settings: &SplashSettings<'_>) -> SplashScreen {
+		let logo = settings.logo.map(|(width, height, data)| unsafe {
+			let gl = &self.gl;
+			let (width, height) = (width as i32, height as i32);
+
+			let mut image = 0;
+			gl.CreateTextures(gl::TEXTURE_2D, 1, &mut image);
+			gl.TextureStorage2D(image, 1, gl::SRGB8_ALPHA8, width, height);
+			gl.TextureSubImage2D(image, 0, 0, 0, width, height, gl::RGBA, gl::UNSIGNED_BYTE, data.as_ptr().cast());
+
+			let mut framebuffer = 0;
+			gl.CreateFramebuffers(1, &mut framebuffer);
+			gl.NamedFramebufferTexture(framebuffer, gl::COLOR_ATTACHMENT0, image, 0);
+
+			Logo { width, height, image, framebuffer }
+		});
+
+		SplashScreen {
+			gl: self.gl.clone(),
+			window: self.window.clone(),
+			surface: self.surface.clone(),
+			context: self.context.clone(),
+
+			background_color: settings.background_color,
+			progress_bar_color: settings.progress_bar_color,
+
+			logo,
+		}
+	}
+}
+
+impl SplashScreen {
+	/// Draw and present a loading frame, making the window visible if it isn't already.
+	/// `progress` should be in [0, 1], or None to hide the progress bar.
+	pub fn present(&self, progress: Option<f32>) {
+		let _span = tracing::info_span!("host present splash").entered();
+
+		let gl = &self.gl;
+		let winit::dpi::PhysicalSize{width, height} = self.window.inner_size().cast::<i32>();
+
+		unsafe {
+			let srgb_enabled = gl.IsEnabled(gl::FRAMEBUFFER_SRGB) != 0;
+			let scissor_enabled = gl.IsEnabled(gl::SCISSOR_TEST) != 0;
+
+			let mut scissor_box = [0i32; 4];
+			gl.GetIntegerv(gl::SCISSOR_BOX, scissor_box.as_mut_ptr());
+
+			// Colors are already sRGB encoded.
+			gl.Disable(gl::FRAMEBUFFER_SRGB);
+			gl.Disable(gl::SCISSOR_TEST);
+
+			gl.ClearNamedFramebufferfv(0, gl::COLOR, 0, self.background_color.as_ptr());
+
+			if let Some(logo) = &self.logo {
+				let x = (width - logo.width) / 2;
+				let y = (height - logo.height) / 2;
+
+				// Flip vertically, since the logo is stored top row first.
+				gl.BlitNamedFramebuffer(logo.framebuffer, 0,
+					0, 0, logo.width, logo.height,
+					x, y + logo.height, x + logo.width, y,
+					gl::COLOR_BUFFER_BIT, gl::LINEAR);
+			}
+
+			if let Some(progress) = progress {
+				let bar_width = width / 3;
+				let bar_height = (height / 100).max(4);
+				let bar_x = (width - bar_width) / 2;
+				let bar_y = height / 6;
+
+				let filled_width = (bar_width as f32 * progress.clamp(0.0, 1.0)) as i32;
+
+				gl.Enable(gl::SCISSOR_TEST);
+				gl.Scissor(bar_x, bar_y, filled_width, bar_height);
+				gl.ClearNamedFramebufferfv(0, gl::COLOR, 0, self.progress_bar_color.as_ptr());
+			}
+
+			// Restore scissor state, since gfx only sets it when it changes.
+			let [x, y, w, h] = scissor_box;
+			gl.Scissor(x, y, w, h);
+
+			if scissor_enabled {
+				gl.Enable(gl::SCISSOR_TEST);
+			} else {
+				gl.Disable(gl::SCISSOR_TEST);
+			}
+
+			if srgb_enabled {
+				gl.Enable(gl::FRAMEBUFFER_SRGB);
+			}
+		}
+
+		self.window.pre_present_notify();
+
+		if let Err(error) = self.surface.swap_buffers(&self.context) {
+			log::warn!("Failed to present splash screen: {error}");
+		}
+
+		self.window.set_visible(true);
+	}
+}
+
+impl Drop for SplashScreen {
+	fn drop(&mut self) {
+		if let Some(logo) = &self.logo {
+			unsafe {
+				self.gl.DeleteFramebuffers(1, &logo.framebuffer);
+				self.gl.DeleteTextures(1, &logo.image);
+			}
+		}
+	}
+}
@@ -0,0 +1,164 @@
+//! Progress shown on the app's taskbar entry - `ITaskbarList3` on windows, and the Unity launcher API on linux, which
+//! KDE and most GNOME docks implement too. The linux implementation requires the `taskbar-progress` feature, and only
+//! connects to the session bus once progress is first set. Does nothing elsewhere.
+
+use winit::window::Window;
+
+
+/// Taskbar progress for one window. Failures are logged once, after which progress is ignored.
+pub struct TaskbarProgress {
+	inner: Option<platform::Inner>,
+	last_progress: Option<f32>,
+}
+
+impl TaskbarProgress {
+	/// `app_id` is the name of the app's .desktop file without the extension, which the Unity launcher API uses to find
+	/// the taskbar entry. Ignored on windows.
+	pub fn new(window: &Window, app_id: &str) -> TaskbarProgress {
+		let inner = platform::Inner::new(window, app_id)
+			.inspect_err(|error| log::warn!("Taskbar progress unavailable: {error:#}"))
+			.ok();
+
+		TaskbarProgress {
+			inner,
+			last_progress: None,
+		}
+	}
+
+	/// `progress` in [0, 1], or None to hide it.
+	pub fn set(&mut self, progress: Option<f32>) {
+		let Some(inner) = &mut self.inner else { return };
+
+		// Small changes are dropped so that progress updated every frame doesn't flood the taskbar.
+		let changed = match (self.last_progress, progress) {
+			(Some(last), Some(progress)) => (last - progress).abs() >= 0.005 || progress >= 1.0,
+			(last, progress) => last.is_some() != progress.is_some(),
+		};
+
+		if !changed {
+			return
+		}
+
+		self.last_progress = progress;
+
+		if let Err(error) = inner.set(progress) {
+			log::warn!("Failed to set taskbar progress - disabling: {error:#}");
+			self.inner = None;
+		}
+	}
+}
+
+
+#[cfg(windows)]
+mod platform {
+	use super::Window;
+	use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+	use windows::Win32::Foundation::HWND;
+	use windows::Win32::System::Com::{CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED};
+	use windows::Win32::UI::Shell::{ITaskbarList3, TaskbarList, TBPF_NOPROGRESS, TBPF_NORMAL};
+
+	const PROGRESS_RANGE: u64 = 1000;
+
+	pub struct Inner {
+		taskbar: ITaskbarList3,
+		hwnd: HWND,
+	}
+
+	impl Inner {
+		pub fn new(window: &Window, _app_id: &str) -> anyhow::Result<Inner> {
+			let RawWindowHandle::Win32(handle) = window.window_handle()?.as_raw() else {
+				anyhow::bail!("Not a win32 window")
+			};
+
+			let hwnd = HWND(handle.hwnd.get() as *mut _);
+
+			unsafe {
+				// winit has usually initialised COM on this thread already, in which case this is a no-op.
+				let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+				let taskbar: ITaskbarList3 = CoCreateInstance(&TaskbarList, None, CLSCTX_INPROC_SERVER)?;
+				taskbar.HrInit()?;
+
+				Ok(Inner { taskbar, hwnd })
+			}
+		}
+
+		pub fn set(&mut self, progress: Option<f32>) -> anyhow::Result<()> {
+			unsafe {
+				match progress {
+					Some(progress) => {
+						self.taskbar.SetProgressState(self.hwnd, TBPF_NORMAL)?;
+						self.taskbar.SetProgressValue(self.hwnd, (progress * PROGRESS_RANGE as f32) as u64, PROGRESS_RANGE)?;
+					}
+
+					None => self.taskbar.SetProgressState(self.hwnd, TBPF_NOPROGRESS)?,
+				}
+			}
+
+			Ok(())
+		}
+	}
+}
+
+
+#[cfg(all(unix, not(target_os="macos"), feature="taskbar-progress"))]
+mod platform {
+	use super::Window;
+	use std::collections::HashMap;
+	use zbus::zvariant::Value;
+
+	const OBJECT_PATH: &str = "/com/canonical/unity/launcherentry/toybox";
+	const INTERFACE: &str = "com.canonical.Unity.LauncherEntry";
+
+	pub struct Inner {
+		// Connected on first use, so that apps that never show progress don't pay for it at startup.
+		connection: Option<zbus::blocking::Connection>,
+		app_uri: String,
+	}
+
+	impl Inner {
+		pub fn new(_window: &Window, app_id: &str) -> anyhow::Result<Inner> {
+			Ok(Inner {
+				connection: None,
+				app_uri: format!("application://{app_id}.desktop"),
+			})
+		}
+
+		pub fn set(&mut self, progress: Option<f32>) -> anyhow::Result<()> {
+			let connection = match self.connection.take() {
+				Some(connection) => connection,
+				None => zbus::blocking::Connection::session()?,
+			};
+
+			let connection = self.connection.insert(connection);
+
+			let properties = HashMap::from([
+				("progress", Value::from(progress.unwrap_or(0.0) as f64)),
+				("progress-visible", Value::from(progress.is_some())),
+			]);
+
+			connection.emit_signal(None::<&str>, OBJECT_PATH, INTERFACE, "Update", &(self.app_uri.as_str(), properties))?;
+			Ok(())
+		}
+	}
+}
+
+
+// TODO(pat.m): dock tile progress on mac
+#[cfg(not(any(windows, all(unix, not(target_os="macos"), feature="taskbar-progress"))))]
+mod platform {
+	use super::Window;
+
+	pub struct Inner;
+
+	impl Inner {
+		pub fn new(_window: &Window, _app_id: &str) -> anyhow::Result<Inner> {
+			Ok(Inner)
+		}
+
+		pub fn set(&mut self, _progress: Option<f32>) -> anyhow::Result<()> {
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,440 @@
+//! Experimental OpenXR session management. Only OpenGL contexts created through WGL are supported for now, so this
+//! only works on Windows.
+//!
+//! The session renders from a single two layer array swapchain - one layer per eye - so that it can be filled with
+//! a single copy from a layered rendertarget.
+
+pub use openxr;
+use openxr as xr;
+
+use glutin::context::{AsRawContext, RawContext};
+
+
+pub const VIEW_CONFIGURATION: xr::ViewConfigurationType = xr::ViewConfigurationType::PRIMARY_STEREO;
+pub const NUM_EYES: usize = 2;
+pub const HANDS: [&str; 2] = ["/user/hand/left", "/user/hand/right"];
+
+
+pub struct XrSession {
+	instance: xr::Instance,
+	session: xr::Session<xr::OpenGL>,
+	frame_waiter: xr::FrameWaiter,
+	frame_stream: xr::FrameStream<xr::OpenGL>,
+	stage: xr::Space,
+	blend_mode: xr::EnvironmentBlendMode,
+
+	swapchain: xr::Swapchain<xr::OpenGL>,
+	swapchain_images: Vec<u32>,
+	eye_size: (u32, u32),
+
+	actions: XrActions,
+	event_buffer: xr::EventDataBuffer,
+
+	running: bool,
+	exit_requested: bool,
+}
+
+/// A frame that has been started with [`XrSession::begin_frame`], and must be finished with [`XrSession::end_frame`].
+pub struct XrFrame {
+	state: xr::FrameState,
+
+	/// Eye poses and fields of view in stage space. Empty if the runtime doesn't want this frame rendered, e.g., while
+	/// the headset is off.
+	pub views: Vec<xr::View>,
+}
+
+impl XrFrame {
+	pub fn should_render(&self) -> bool {
+		self.state.should_render && self.views.len() == NUM_EYES
+	}
+
+	pub fn predicted_display_time(&self) -> xr::Time {
+		self.state.predicted_display_time
+	}
+}
+
+/// State of one controller, in stage space.
+#[derive(Debug, Clone, Default)]
+pub struct XrHandState {
+	pub grip: Option<xr::Posef>,
+	pub aim: Option<xr::Posef>,
+
+	pub trigger: f32,
+	pub squeeze: f32,
+	pub thumbstick: (f32, f32),
+
+	/// A/X
+	pub primary: bool,
+
+	/// B/Y
+	pub secondary: bool,
+
+	pub menu: bool,
+}
+
+
+impl XrSession {
+	/// Must be called with `context` current.
+	pub fn new(app_name: &str, context: &crate::GlContext) -> anyhow::Result<XrSession> {
+		let _span = tracing::info_span!("host xr init").entered();
+
+		let entry = unsafe { xr::Entry::load()? };
+
+		let available_extensions = entry.enumerate_extensions()?;
+		anyhow::ensure!(available_extensions.khr_opengl_enable, "OpenXR runtime doesn't support OpenGL");
+
+		let mut enabled_extensions = xr::ExtensionSet::default();
+		enabled_extensions.khr_opengl_enable = true;
+
+		let instance = entry.create_instance(&xr::ApplicationInfo {
+			application_name: app_name,
+			application_version: 0,
+			engine_name: "toybox",
+			engine_version: 0,
+		}, &enabled_extensions, &[])?;
+
+		let properties = instance.properties()?;
+		log::info!("OpenXR runtime: {} {}", properties.runtime_name, properties.runtime_version);
+
+		let system = instance.system(xr::FormFactor::HEAD_MOUNTED_DISPLAY)?;
+
+		// Required to be queried before session creation.
+		let requirements = instance.graphics_requirements::<xr::OpenGL>(system)?;
+		anyhow::ensure!(requirements.min_api_version_supported <= xr::Version::new(4, 6, 0),
+			"OpenXR runtime requires OpenGL {}", requirements.min_api_version_supported);
+
+		let blend_mode = instance.enumerate_environment_blend_modes(system, VIEW_CONFIGURATION)?
+			.first().copied()
+			.unwrap_or(xr::EnvironmentBlendMode::OPAQUE);
+
+		let view_config_views = instance.enumerate_view_configuration_views(system, VIEW_CONFIGURATION)?;
+		anyhow::ensure!(view_config_views.len() == NUM_EYES, "Expected {NUM_EYES} views, got {}", view_config_views.len());
+
+		let eye_size = (view_config_views[0].recommended_image_rect_width, view_config_views[0].recommended_image_rect_height);
+
+		let session_create_info = session_create_info(context)?;
+		let (session, frame_waiter, frame_stream) = unsafe {
+			instance.create_session::<xr::OpenGL>(system, &session_create_info)?
+		};
+
+		let stage = session.create_reference_space(xr::ReferenceSpaceType::STAGE, xr::Posef::IDENTITY)?;
+
+		// Eyes are copied into the swapchain with glCopyImageSubData, so the format has to match the eye targets exactly.
+		let swapchain_format = gl::SRGB8_ALPHA8;
+		let supported_formats = session.enumerate_swapchain_formats()?;
+		anyhow::ensure!(supported_formats.contains(&swapchain_format),
+			"OpenXR runtime doesn't support SRGB8_ALPHA8 swapchains - supported formats: {supported_formats:#x?}");
+
+		let swapchain = session.create_swapchain(&xr::SwapchainCreateInfo {
+			create_flags: xr::SwapchainCreateFlags::EMPTY,
+			usage_flags: xr::SwapchainUsageFlags::COLOR_ATTACHMENT | xr::SwapchainUsageFlags::TRANSFER_DST,
+			format: swapchain_format,
+			sample_count: 1,
+			width: eye_size.0,
+			height: eye_size.1,
+			face_count: 1,
+			array_size: NUM_EYES as u32,
+			mip_count: 1,
+		})?;
+
+		let swapchain_images = swapchain.enumerate_images()?;
+
+		let actions = XrActions::new(&instance, &session)?;
+
+		log::info!("OpenXR session created with {}x{} eye targets", eye_size.0, eye_size.1);
+
+		Ok(XrSession {
+			instance,
+			session,
+			frame_waiter,
+			frame_stream,
+			stage,
+			blend_mode,
+
+			swapchain,
+			swapchain_images,
+			eye_size,
+
+			actions,
+			event_buffer: xr::EventDataBuffer::new(),
+
+			running: false,
+			exit_requested: false,
+		})
+	}
+
+	/// Recommended size of each eye's rendertarget.
+	pub fn eye_size(&self) -> (u32, u32) {
+		self.eye_size
+	}
+
+	/// Whether the runtime has started the session - i.e., whether frames are being submitted.
+	pub fn is_running(&self) -> bool {
+		self.running
+	}
+
+	/// Whether the runtime wants the app to quit, e.g., from the headset's system menu.
+	pub fn exit_requested(&self) -> bool {
+		self.exit_requested
+	}
+
+	/// Handle session state changes. Should be called once per frame, before [`XrSession::begin_frame`].
+	pub fn poll_events(&mut self) -> anyhow::Result<()> {
+		while let Some(event) = self.instance.poll_event(&mut self.event_buffer)? {
+			match event {
+				xr::Event::SessionStateChanged(change) => {
+					log::info!("OpenXR session state: {:?}", change.state());
+
+					match change.state() {
+						xr::SessionState::READY => {
+							self.session.begin(VIEW_CONFIGURATION)?;
+							self.running = true;
+						}
+
+						xr::SessionState::STOPPING => {
+							self.session.end()?;
+							self.running = false;
+						}
+
+						xr::SessionState::EXITING | xr::SessionState::LOSS_PENDING => {
+							self.running = false;
+							self.exit_requested = true;
+						}
+
+						_ => {}
+					}
+				}
+
+				xr::Event::InstanceLossPending(_) => {
+					self.running = false;
+					self.exit_requested = true;
+				}
+
+				_ => {}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Block until the runtime wants the next frame, then start it. Returns None if the session isn't running.
+	/// Eye views are predicted for when the frame will be displayed, so this should be called as late as possible
+	/// before rendering.
+	pub fn begin_frame(&mut self) -> anyhow::Result<Option<XrFrame>> {
+		if !self.running {
+			return Ok(None)
+		}
+
+		let state = self.frame_waiter.wait()?;
+		self.frame_stream.begin()?;
+
+		let views = match state.should_render {
+			true => self.session.locate_views(VIEW_CONFIGURATION, state.predicted_display_time, &self.stage)?.1,
+			false => Vec::new(),
+		};
+
+		Ok(Some(XrFrame { state, views }))
+	}
+
+	/// Read controller state for the frame. Poses are predicted for the frame's display time.
+	pub fn sync_hands(&self, frame: &XrFrame) -> anyhow::Result<[XrHandState; 2]> {
+		self.actions.sync(&self.session, &self.stage, frame.predicted_display_time())
+	}
+
+	/// Copy the eye images from `source` - a two layer 2D array texture the same size as [`XrSession::eye_size`] -
+	/// to the swapchain, and submit the frame. If the frame shouldn't be rendered, `source` is ignored.
+	pub fn end_frame(&mut self, gl: &gl::Gl, frame: XrFrame, source: Option<u32>) -> anyhow::Result<()> {
+		let XrFrame { state, views } = frame;
+
+		let Some(source) = source.filter(|_| state.should_render && views.len() == NUM_EYES) else {
+			self.frame_stream.end(state.predicted_display_time, self.blend_mode, &[])?;
+			return Ok(())
+		};
+
+		let image_index = self.swapchain.acquire_image()?;
+		self.swapchain.wait_image(xr::Duration::INFINITE)?;
+
+		let (width, height) = self.eye_size;
+
+		unsafe {
+			gl.CopyImageSubData(
+				source, gl::TEXTURE_2D_ARRAY, 0, 0, 0, 0,
+				self.swapchain_images[image_index as usize], gl::TEXTURE_2D_ARRAY, 0, 0, 0, 0,
+				width as i32, height as i32, NUM_EYES as i32);
+		}
+
+		self.swapchain.release_image()?;
+
+		let image_rect = xr::Rect2Di {
+			offset: xr::Offset2Di { x: 0, y: 0 },
+			extent: xr::Extent2Di { width: width as i32, height: height as i32 },
+		};
+
+		let projection_views: Vec<_> = views.iter().enumerate()
+			.map(|(eye, view)| {
+				xr::CompositionLayerProjectionView::new()
+					.pose(view.pose)
+					.fov(view.fov)
+					.sub_image(xr::SwapchainSubImage::new()
+						.swapchain(&self.swapchain)
+						.image_array_index(eye as u32)
+						.image_rect(image_rect))
+			})
+			.collect();
+
+		let layer = xr::CompositionLayerProjection::new()
+			.space(&self.stage)
+			.views(&projection_views);
+
+		self.frame_stream.end(state.predicted_display_time, self.blend_mode, &[&layer])?;
+
+		Ok(())
+	}
+}
+
+
+#[cfg(windows)]
+fn session_create_info(context: &crate::GlContext) -> anyhow::Result<xr::opengl::SessionCreateInfo> {
+	#[link(name = "opengl32")]
+	extern "system" {
+		fn wglGetCurrentDC() -> *mut std::ffi::c_void;
+	}
+
+	let RawContext::Wgl(h_glrc) = context.raw_context() else {
+		anyhow::bail!("OpenXR requires a WGL context");
+	};
+
+	let h_dc = unsafe { wglGetCurrentDC() };
+	anyhow::ensure!(!h_dc.is_null(), "OpenXR requires the GL context to be current");
+
+	Ok(xr::opengl::SessionCreateInfo::Windows {
+		h_dc: h_dc as _,
+		h_glrc: h_glrc as _,
+	})
+}
+
+#[cfg(not(windows))]
+fn session_create_info(context: &crate::GlContext) -> anyhow::Result<xr::opengl::SessionCreateInfo> {
+	// TODO(pat.m): Xlib binding - requires a GLX context, but we prefer EGL.
+	let _ = context.raw_context();
+	anyhow::bail!("OpenXR is only supported with WGL contexts for now")
+}
+
+
+
+struct XrActions {
+	action_set: xr::ActionSet,
+	hands: [xr::Path; 2],
+
+	grip_spaces: [xr::Space; 2],
+	aim_spaces: [xr::Space; 2],
+
+	trigger: xr::Action<f32>,
+	squeeze: xr::Action<f32>,
+	thumbstick: xr::Action<xr::Vector2f>,
+	primary: xr::Action<bool>,
+	secondary: xr::Action<bool>,
+	menu: xr::Action<bool>,
+}
+
+impl XrActions {
+	fn new(instance: &xr::Instance, session: &xr::Session<xr::OpenGL>) -> anyhow::Result<XrActions> {
+		let action_set = instance.create_action_set("toybox", "Toybox", 0)?;
+		let hands = [instance.string_to_path(HANDS[0])?, instance.string_to_path(HANDS[1])?];
+
+		let grip = action_set.create_action::<xr::Posef>("grip_pose", "Grip Pose", &hands)?;
+		let aim = action_set.create_action::<xr::Posef>("aim_pose", "Aim Pose", &hands)?;
+		let trigger = action_set.create_action::<f32>("trigger", "Trigger", &hands)?;
+		let squeeze = action_set.create_action::<f32>("squeeze", "Squeeze", &hands)?;
+		let thumbstick = action_set.create_action::<xr::Vector2f>("thumbstick", "Thumbstick", &hands)?;
+		let primary = action_set.create_action::<bool>("primary", "Primary Button", &hands)?;
+		let secondary = action_set.create_action::<bool>("secondary", "Secondary Button", &hands)?;
+		let menu = action_set.create_action::<bool>("menu", "Menu", &hands)?;
+
+		let path = |path: &str| instance.string_to_path(path);
+
+		// Simple controller is supported by every runtime, so always bind it as a fallback.
+		let mut simple_bindings = Vec::new();
+		for hand in HANDS {
+			simple_bindings.push(xr::Binding::new(&grip, path(&format!("{hand}/input/grip/pose"))?));
+			simple_bindings.push(xr::Binding::new(&aim, path(&format!("{hand}/input/aim/pose"))?));
+			simple_bindings.push(xr::Binding::new(&primary, path(&format!("{hand}/input/select/click"))?));
+			simple_bindings.push(xr::Binding::new(&menu, path(&format!("{hand}/input/menu/click"))?));
+		}
+
+		instance.suggest_interaction_profile_bindings(path("/interaction_profiles/khr/simple_controller")?, &simple_bindings)?;
+
+		let mut touch_bindings = Vec::new();
+		for (hand, [primary_button, secondary_button]) in HANDS.into_iter().zip([["x", "y"], ["a", "b"]]) {
+			touch_bindings.push(xr::Binding::new(&grip, path(&format!("{hand}/input/grip/pose"))?));
+			touch_bindings.push(xr::Binding::new(&aim, path(&format!("{hand}/input/aim/pose"))?));
+			touch_bindings.push(xr::Binding::new(&trigger, path(&format!("{hand}/input/trigger/value"))?));
+			touch_bindings.push(xr::Binding::new(&squeeze, path(&format!("{hand}/input/squeeze/value"))?));
+			touch_bindings.push(xr::Binding::new(&thumbstick, path(&format!("{hand}/input/thumbstick"))?));
+			touch_bindings.push(xr::Binding::new(&primary, path(&format!("{hand}/input/{primary_button}/click"))?));
+			touch_bindings.push(xr::Binding::new(&secondary, path(&format!("{hand}/input/{secondary_button}/click"))?));
+		}
+
+		// Only the left touch controller has a menu button.
+		touch_bindings.push(xr::Binding::new(&menu, path("/user/hand/left/input/menu/click")?));
+
+		instance.suggest_interaction_profile_bindings(path("/interaction_profiles/oculus/touch_controller")?, &touch_bindings)?;
+
+		session.attach_action_sets(&[&action_set])?;
+
+		let grip_spaces = [
+			grip.create_space(session.clone(), hands[0], xr::Posef::IDENTITY)?,
+			grip.create_space(session.clone(), hands[1], xr::Posef::IDENTITY)?,
+		];
+
+		let aim_spaces = [
+			aim.create_space(session.clone(), hands[0], xr::Posef::IDENTITY)?,
+			aim.create_space(session.clone(), hands[1], xr::Posef::IDENTITY)?,
+		];
+
+		Ok(XrActions {
+			action_set,
+			hands,
+			grip_spaces,
+			aim_spaces,
+			trigger,
+			squeeze,
+			thumbstick,
+			primary,
+			secondary,
+			menu,
+		})
+	}
+
+	fn sync(&self, session: &xr::Session<xr::OpenGL>, stage: &xr::Space, time: xr::Time) -> anyhow::Result<[XrHandState; 2]> {
+		session.sync_actions(&[(&self.action_set).into()])?;
+
+		let locate = |space: &xr::Space| -> anyhow::Result<Option<xr::Posef>> {
+			let location = space.locate(stage, time)?;
+			let valid = xr::SpaceLocationFlags::POSITION_VALID | xr::SpaceLocationFlags::ORIENTATION_VALID;
+			Ok(location.location_flags.contains(valid).then_some(location.pose))
+		};
+
+		let mut states: [XrHandState; 2] = Default::default();
+
+		for (index, state) in states.iter_mut().enumerate() {
+			let hand = self.hands[index];
+			let thumbstick = self.thumbstick.state(session, hand)?.current_state;
+
+			*state = XrHandState {
+				grip: locate(&self.grip_spaces[index])?,
+				aim: locate(&self.aim_spaces[index])?,
+
+				trigger: self.trigger.state(session, hand)?.current_state,
+				squeeze: self.squeeze.state(session, hand)?.current_state,
+				thumbstick: (thumbstick.x, thumbstick.y),
+
+				primary: self.primary.state(session, hand)?.current_state,
+				secondary: self.secondary.state(session, hand)?.current_state,
+				menu: self.menu.state(session, hand)?.current_state,
+			};
+		}
+
+		Ok(states)
+	}
+}
@@ -62,34 +62,44 @@ pub fn tracker_ui(ui: &mut egui::Ui, input: &mut System) {
 }
 
 
-#[cfg(feature="gamepad")]
+/// Available without the `gamepad` feature, since gamepad buttons can also be driven by other devices (e.g., XR controllers).
 pub fn gamepad_ui(ui: &mut egui::Ui, input: &mut System) {
-	#[derive(Clone, Default)]
-	struct State {
-		events: Vec<(String, u32)>,
-	}
+	// Events are consumed by System::process, so show the tracked state rather than raw events.
+	#[cfg(feature="gamepad")]
+	connected_gamepads_ui(ui, input);
 
-	let state_id = ui.id().with("state");
-	let mut state: State = ui.data_mut(|map| std::mem::take(map.get_temp_mut_or_default(state_id)));
+	#[cfg(not(feature="gamepad"))]
+	ui.label("Gamepad support not compiled in");
 
-	for (_, timer) in state.events.iter_mut() {
-		*timer = timer.saturating_sub(1);
-	}
-
-	state.events.retain(|(_, timer)| *timer > 0);
+	ui.separator();
 
-	while let Some(gilrs::Event{id, event, time}) = input.gil.next_event() {
-		state.events.push((format!("{:?} New event from {}: {:?}", time, id, event), 20));
-	}
+	ui.horizontal(|ui| {
+		ui.label("Active Buttons: ");
+		for button in input.tracker.active_buttons.iter() {
+			if let Button::Gamepad(button) = button {
+				ui.label(format!("{button:?}"));
+			}
+		}
+	});
 
+	ui.label(format!("Left stick: {:?}", input.tracker.gamepad_stick(GamepadStick::Left)));
+	ui.label(format!("Right stick: {:?}", input.tracker.gamepad_stick(GamepadStick::Right)));
+}
 
-	for (_id, gamepad) in input.gil.gamepads() {
-		ui.label(format!("{} is {:?}", gamepad.name(), gamepad.power_info()));
-	}
-	for (msg, _) in state.events.iter() {
-		ui.label(msg);
+#[cfg(feature="gamepad")]
+fn connected_gamepads_ui(ui: &mut egui::Ui, input: &System) {
+	let Some(gil) = &input.gil else {
+		ui.label("Gamepad support failed to initialise");
+		return
+	};
+
+	let mut any_gamepads = false;
+	for (id, gamepad) in gil.gamepads() {
+		ui.label(format!("{id}: {} is {:?}", gamepad.name(), gamepad.power_info()));
+		any_gamepads = true;
 	}
 
-
-	ui.data_mut(move |map| map.insert_temp(state_id, state));
-}
\ No newline at end of file
+	if !any_gamepads {
+		ui.label("No gamepads connected");
+	}
+}
@@ -0,0 +1,126 @@
+//! Gamepad buttons and sticks, tracked alongside keyboard and mouse input. Only gathered with the `gamepad` feature,
+//! but the types are always available so bindings don't need to be feature gated.
+
+use common::*;
+use crate::Tracker;
+
+
+/// Named by position rather than label, since labels differ between controllers - e.g., `South` is A on an xbox
+/// controller and cross on a playstation controller.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum GamepadButton {
+	South,
+	East,
+	West,
+	North,
+
+	DPadUp,
+	DPadDown,
+	DPadLeft,
+	DPadRight,
+
+	LeftShoulder,
+	RightShoulder,
+	LeftTrigger,
+	RightTrigger,
+
+	LeftStick,
+	RightStick,
+
+	Start,
+	Select,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GamepadStick {
+	Left,
+	Right,
+}
+
+
+/// Sticks report small values at rest, so anything inside this radius is treated as centered.
+pub const STICK_DEADZONE: f32 = 0.15;
+
+/// Rescales so values just outside of the deadzone start from zero, preserving direction.
+pub fn apply_stick_deadzone(value: Vec2) -> Vec2 {
+	let length = value.length();
+	if length <= STICK_DEADZONE {
+		return Vec2::zero()
+	}
+
+	let rescaled = ((length - STICK_DEADZONE) / (1.0 - STICK_DEADZONE)).min(1.0);
+	value * (rescaled / length)
+}
+
+
+#[cfg(feature="gamepad")]
+pub(crate) fn track_gilrs_event(tracker: &mut Tracker, event: &gilrs::EventType) {
+	use gilrs::EventType;
+
+	match *event {
+		EventType::ButtonPressed(button, _) => {
+			if let Some(button) = from_gilrs_button(button) {
+				tracker.track_button(button, true);
+			}
+		}
+
+		EventType::ButtonReleased(button, _) => {
+			if let Some(button) = from_gilrs_button(button) {
+				tracker.track_button(button, false);
+			}
+		}
+
+		EventType::AxisChanged(axis, value, _) => {
+			let (stick, component) = match axis {
+				gilrs::Axis::LeftStickX => (GamepadStick::Left, 0),
+				gilrs::Axis::LeftStickY => (GamepadStick::Left, 1),
+				gilrs::Axis::RightStickX => (GamepadStick::Right, 0),
+				gilrs::Axis::RightStickY => (GamepadStick::Right, 1),
+				_ => return,
+			};
+
+			let mut raw = tracker.raw_gamepad_stick(stick);
+			match component {
+				0 => raw.x = value,
+				_ => raw.y = value,
+			}
+
+			tracker.track_gamepad_stick(stick, raw);
+		}
+
+		EventType::Disconnected => tracker.track_gamepad_disconnected(),
+
+		_ => {}
+	}
+}
+
+#[cfg(feature="gamepad")]
+fn from_gilrs_button(button: gilrs::Button) -> Option<GamepadButton> {
+	use gilrs::Button as B;
+
+	Some(match button {
+		B::South => GamepadButton::South,
+		B::East => GamepadButton::East,
+		B::West => GamepadButton::West,
+		B::North => GamepadButton::North,
+
+		B::DPadUp => GamepadButton::DPadUp,
+		B::DPadDown => GamepadButton::DPadDown,
+		B::DPadLeft => GamepadButton::DPadLeft,
+		B::DPadRight => GamepadButton::DPadRight,
+
+		B::LeftTrigger => GamepadButton::LeftShoulder,
+		B::RightTrigger => GamepadButton::RightShoulder,
+		B::LeftTrigger2 => GamepadButton::LeftTrigger,
+		B::RightTrigger2 => GamepadButton::RightTrigger,
+
+		B::LeftThumb => GamepadButton::LeftStick,
+		B::RightThumb => GamepadButton::RightStick,
+
+		B::Start => GamepadButton::Start,
+		B::Select => GamepadButton::Select,
+
+		_ => return None,
+	})
+}
@@ -9,13 +9,23 @@ use std::rc::Rc;
 pub mod debug;
 pub mod tracker;
 pub mod keys;
+pub mod testing;
+pub mod spaces;
+pub mod gamepad;
+pub mod navigation;
 
 pub mod prelude {}
 
 pub use tracker::*;
+pub use gamepad::{GamepadButton, GamepadStick, apply_stick_deadzone};
+pub use navigation::{UiNavigation, NavigationSettings, NavAction, NavDirection, MenuFocus};
+pub use spaces::{WorldPos, ViewPos, ClipPos, NdcPos, ScreenPos, WindowPos, UiPos, ImageUv};
 pub use winit::event::{MouseButton};
 pub use winit::keyboard::{Key as LogicalKey, NamedKey as LogicalNamedKey, KeyCode as PhysicalKey};
 
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 /// Maps mouse dots to a raw angle in radians. Based on constants used by quake and hl source.
 ///
 /// https://github.com/ValveSoftware/source-sdk-2013/blob/master/sp/src/game/client/in_mouse.cpp#L88
@@ -24,11 +34,15 @@ pub const ANGLE_PER_MOUSE_DOT: f32 = 0.022 * PI / 180.0;
 
 pub struct System {
 	pub tracker: Tracker,
-	// pub gil: gilrs::Gilrs,
+
+	/// None if gilrs failed to initialise, or for headless systems.
+	#[cfg(feature="gamepad")]
+	pub gil: Option<gilrs::Gilrs>,
 
 	pub mouse_sensitivity: f32,
 
-	window: Rc<Window>,
+	/// None for headless systems - see [`System::new_headless`].
+	window: Option<Rc<Window>>,
 
 	wants_capture: bool,
 	occluded: bool,
@@ -37,6 +51,20 @@ pub struct System {
 	is_mouse_captured: bool,
 
 	window_size: Vec2i,
+	pixel_mapping: Option<PixelMapping>,
+	scale_factor: f32,
+}
+
+
+/// Describes a render target that is integer scaled and offset within the window - e.g., for low res rendering.
+/// While set, 'pixels' and 'ndc' refer to this target rather than the window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PixelMapping {
+	/// Offset of the scaled target from the bottom left of the window.
+	pub offset: Vec2i,
+	pub scale: i32,
+	/// Unscaled size of the target.
+	pub size: Vec2i,
 }
 
 /// Input tracker queries. Just convenience functions for the same calls on `self.tracker`
@@ -53,18 +81,46 @@ impl System {
 		self.tracker.button_just_up(button)
 	}
 
+	/// Mouse position in pixels, with the origin at the bottom left.
+	/// If a [`PixelMapping`] is set this is in target pixels, and may lie outside of the target.
 	pub fn mouse_position_pixels(&self) -> Option<Vec2> {
-		self.tracker.physical_mouse_position.map(|Vec2{x, y}| Vec2 {
-			x,
-			y: self.window_size.y as f32 - y - 1.0
-		})
+		self.mouse_screen_pos().map(|ScreenPos(pixels)| pixels)
+	}
+
+	/// Typed version of [`System::mouse_position_pixels`].
+	pub fn mouse_screen_pos(&self) -> Option<ScreenPos> {
+		self.tracker.physical_mouse_position
+			.map(|position| self.window_to_screen(WindowPos(position)))
+	}
+
+	/// Typed version of [`System::mouse_position_ndc`].
+	pub fn mouse_ndc_pos(&self) -> Option<NdcPos> {
+		self.mouse_screen_pos().map(|screen| screen.to_ndc(self.target_size()))
+	}
+
+	/// Mouse position in logical pixels, with the origin at the bottom left. See [`System::pixels_to_logical`].
+	pub fn mouse_position_logical(&self) -> Option<Vec2> {
+		self.mouse_position_pixels().map(|px| self.pixels_to_logical(px))
 	}
 
 	pub fn mouse_position_ndc(&self) -> Option<Vec2> {
-		self.mouse_position_pixels().map(|px| {
-			let flipped_ndc = px / self.window_size.to_vec2() - Vec2::splat(0.5);
-			flipped_ndc * 2.0
-		})
+		self.mouse_position_pixels().map(|px| self.pixels_to_ndc(px))
+	}
+
+	/// Mouse position in desktop coordinates - origin at the top left of the primary monitor, y down.
+	/// Returns None if the mouse isn't over the window, or the platform can't tell us where the window is.
+	pub fn mouse_position_global(&self) -> Option<Vec2> {
+		self.mouse_position_pixels().and_then(|px| self.pixels_to_global(px))
+	}
+
+	/// World space ray under the mouse. `projection_view` must not have reverse-z applied - see [`NdcPos::to_ray`].
+	pub fn mouse_ray(&self, projection_view: &Mat4) -> Option<Ray> {
+		self.mouse_ndc_pos().map(|ndc| ndc.to_ray(projection_view))
+	}
+
+	/// World space ray under the mouse, for a `projection_view` adjusted for reverse-z.
+	pub fn mouse_ray_reverse_z(&self, projection_view: &Mat4) -> Option<Ray> {
+		self.mouse_ndc_pos().map(|ndc| ndc.to_ray_reverse_z(projection_view))
 	}
 
 	/// Gives raw mouse delta - transformed such that moving the mouse forward gives a positive y delta, and moving
@@ -83,6 +139,118 @@ impl System {
 	}
 }
 
+/// Coordinate conversions.
+/// 'pixels' are window relative with the origin at the bottom left, matching [`System::mouse_position_pixels`].
+/// 'ndc' are in the range [-1, 1] with y up, matching [`System::mouse_position_ndc`].
+/// See [`spaces`] for typed versions.
+impl System {
+	pub fn pixels_to_ndc(&self, pixels: Vec2) -> Vec2 {
+		ScreenPos(pixels).to_ndc(self.target_size()).0
+	}
+
+	pub fn ndc_to_pixels(&self, ndc: Vec2) -> Vec2 {
+		NdcPos(ndc).to_screen(self.target_size()).0
+	}
+
+	/// Where `world` appears in target pixels, or None if it's behind the camera. For placing labels and markers.
+	pub fn world_to_screen(&self, world: WorldPos, projection_view: &Mat4) -> Option<ScreenPos> {
+		world.to_screen(projection_view, self.target_size())
+	}
+
+	pub fn pixels_to_global(&self, pixels: Vec2) -> Option<Vec2> {
+		let PhysicalPosition{x, y} = self.window.as_ref()?.inner_position().ok()?.cast::<f32>();
+		let WindowPos(window_pixels) = self.screen_to_window(ScreenPos(pixels));
+		Some(Vec2::new(x, y) + window_pixels)
+	}
+
+	pub fn global_to_pixels(&self, global: Vec2) -> Option<Vec2> {
+		let PhysicalPosition{x, y} = self.window.as_ref()?.inner_position().ok()?.cast::<f32>();
+		let ScreenPos(pixels) = self.window_to_screen(WindowPos(global - Vec2::new(x, y)));
+		Some(pixels)
+	}
+
+	/// Includes the [`PixelMapping`] if set, unlike [`WindowPos::to_screen`].
+	pub fn window_to_screen(&self, window: WindowPos) -> ScreenPos {
+		let ScreenPos(window_pixels) = window.to_screen(self.window_size);
+		ScreenPos(self.window_to_pixels(window_pixels))
+	}
+
+	/// Includes the [`PixelMapping`] if set, unlike [`ScreenPos::to_window`].
+	pub fn screen_to_window(&self, screen: ScreenPos) -> WindowPos {
+		ScreenPos(self.pixels_to_window(screen.0)).to_window(self.window_size)
+	}
+
+	pub fn set_pixel_mapping(&mut self, mapping: impl Into<Option<PixelMapping>>) {
+		self.pixel_mapping = mapping.into();
+	}
+
+	pub fn pixel_mapping(&self) -> Option<PixelMapping> {
+		self.pixel_mapping
+	}
+
+	/// Physical pixels per logical pixel. See [`System::pixels_to_logical`].
+	pub fn scale_factor(&self) -> f32 {
+		self.scale_factor
+	}
+
+	/// Converts to window relative logical pixels, origin at the bottom left - i.e., window pixels divided by
+	/// the scale factor. Unaffected by [`PixelMapping`], so useful for ui laid out in logical units.
+	pub fn pixels_to_logical(&self, pixels: Vec2) -> Vec2 {
+		self.pixels_to_window(pixels) / self.scale_factor
+	}
+
+	pub fn logical_to_pixels(&self, logical: Vec2) -> Vec2 {
+		self.window_to_pixels(logical * self.scale_factor)
+	}
+
+	fn target_size(&self) -> Vec2i {
+		self.pixel_mapping.map_or(self.window_size, |mapping| mapping.size)
+	}
+
+	fn window_to_pixels(&self, window_pixels: Vec2) -> Vec2 {
+		match self.pixel_mapping {
+			Some(PixelMapping{offset, scale, ..}) => (window_pixels - offset.to_vec2()) / scale as f32,
+			None => window_pixels,
+		}
+	}
+
+	fn pixels_to_window(&self, pixels: Vec2) -> Vec2 {
+		match self.pixel_mapping {
+			Some(PixelMapping{offset, scale, ..}) => pixels * scale as f32 + offset.to_vec2(),
+			None => pixels,
+		}
+	}
+
+	/// Unprojects a point in ndc into a world space ray, starting at the near plane. See [`NdcPos::to_ray`].
+	pub fn ndc_to_ray(ndc: Vec2, projection_view: &Mat4) -> Ray {
+		NdcPos(ndc).to_ray(projection_view)
+	}
+}
+
+
+/// A world space ray, as returned by [`System::mouse_ray`].
+#[derive(Debug, Copy, Clone)]
+pub struct Ray {
+	pub origin: Vec3,
+	/// Always normalized.
+	pub direction: Vec3,
+}
+
+impl Ray {
+	pub fn at(&self, distance: f32) -> Vec3 {
+		self.origin + self.direction * distance
+	}
+
+	pub fn origin_pos(&self) -> WorldPos {
+		WorldPos(self.origin)
+	}
+
+	pub fn pos_at(&self, distance: f32) -> WorldPos {
+		WorldPos(self.at(distance))
+	}
+}
+
+
 impl System {
 	#[instrument(skip_all, name="input System::set_capture_mouse")]
 	pub fn set_capture_mouse(&mut self, capture: bool) {
@@ -98,28 +266,39 @@ impl System {
 	fn try_capture_mouse_internal(&mut self, capture: bool) {
 		log::info!("try_capture_mouse_internal({capture})");
 
+		let Some(window) = &self.window else {
+			self.is_mouse_captured = capture;
+			return
+		};
+
 		if capture {
-			if let Err(error) = self.window.set_cursor_grab(CursorGrabMode::Confined)
+			if let Err(error) = window.set_cursor_grab(CursorGrabMode::Confined)
 				.inspect_err(|error| log::warn!("Failed to capture mouse with 'confined' mode - falling back to 'locked' mode. {error}"))
-				.or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Locked))
+				.or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked))
 			{
 				log::error!("Failed to lock mouse: {error}");
 				return;
 			}
 
-			self.window.set_cursor_visible(false);
+			window.set_cursor_visible(false);
 			self.is_mouse_captured = true;
 
 		} else {
-			if let Err(error) = self.window.set_cursor_grab(CursorGrabMode::None) {
+			if let Err(error) = window.set_cursor_grab(CursorGrabMode::None) {
 				log::error!("Failed to release cursor grab: {error}");
 			}
 
-			self.window.set_cursor_visible(true);
+			window.set_cursor_visible(true);
 			self.is_mouse_captured = false;
 		}
 	}
 
+	/// Whether the mouse is actually captured right now, which may differ from what was requested with
+	/// [`System::set_capture_mouse`] if the window isn't focussed or input is occluded.
+	pub fn is_mouse_captured(&self) -> bool {
+		self.is_mouse_captured
+	}
+
 	fn should_capture(&self) -> bool {
 		self.wants_capture && !self.occluded && self.has_focus
 	}
@@ -131,10 +310,28 @@ impl System {
 	#[instrument(skip_all, name="input System::new")]
 	pub fn new(window: Rc<Window>) -> System {
 		let has_focus = window.has_focus();
+		System::new_internal(Some(window), has_focus)
+	}
 
+	/// A system without a window, for feeding synthetic input in tests. Starts focussed, and mouse capture is
+	/// simulated. See [`testing::InputHarness`].
+	pub fn new_headless(window_size: Vec2i) -> System {
+		let mut system = System::new_internal(None, true);
+		system.window_size = window_size;
+		system
+	}
+
+	fn new_internal(window: Option<Rc<Window>>, has_focus: bool) -> System {
 		System {
 			tracker: Tracker::default(),
-			// gil: gilrs::Gilrs::new().unwrap(),
+			// Headless systems only get synthetic input.
+			#[cfg(feature="gamepad")]
+			gil: window.as_ref().and_then(|_| {
+				gilrs::Gilrs::new()
+					.inspect_err(|error| log::error!("Failed to initialise gamepad support: {error}"))
+					.ok()
+			}),
+
 			window,
 
 			wants_capture: false,
@@ -148,6 +345,8 @@ impl System {
 			mouse_sensitivity: 5.0,
 
 			window_size: Vec2i::splat(1),
+			pixel_mapping: None,
+			scale_factor: 1.0,
 		}
 	}
 
@@ -157,7 +356,9 @@ impl System {
 			self.try_capture_mouse_internal(self.should_capture());
 		}
 		
-		self.window.set_cursor_visible(!self.is_mouse_captured);
+		if let Some(window) = &self.window {
+			window.set_cursor_visible(!self.is_mouse_captured);
+		}
 
 		self.tracker.reset();
 	}
@@ -177,6 +378,11 @@ impl System {
 		self.window_size = new_size;
 	}
 
+	/// Not taken from `WindowEvent::ScaleFactorChanged` directly, since the scale factor may be overridden.
+	pub fn on_scale_factor_changed(&mut self, scale_factor: f32) {
+		self.scale_factor = scale_factor;
+	}
+
 	pub fn on_window_event(&mut self, event: &WindowEvent) {
 		use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
 
@@ -214,8 +420,6 @@ impl System {
 				self.try_capture_mouse_internal(self.should_capture());
 			}
 
-			// TODO(pat.m): track dpi
-
 			_ => {}
 		}
 	}
@@ -232,7 +436,15 @@ impl System {
 
 	// Do any processing that needs to happen to the raw input. No new inputs will be recieved this frame.
 	pub fn process(&mut self) {
-
+		#[cfg(feature="gamepad")]
+		if let Some(gil) = &mut self.gil {
+			while let Some(gilrs::Event{event, ..}) = gil.next_event() {
+				// Gamepads are read regardless of focus, so ignore them while in the background like other input.
+				if self.has_focus {
+					gamepad::track_gilrs_event(&mut self.tracker, &event);
+				}
+			}
+		}
 	}
 
 }
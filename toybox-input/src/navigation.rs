@@ -0,0 +1,259 @@
+//! Focus based ui navigation with a gamepad - the dpad or left stick moves focus, and face buttons activate or back out.
+//! [`UiNavigation`] turns raw input into [`NavAction`]s once per frame, which can then drive egui's keyboard focus via
+//! [`UiNavigation::egui_events`], or simple game menus via [`MenuFocus`].
+
+use crate::*;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NavDirection {
+	Up,
+	Down,
+	Left,
+	Right,
+}
+
+impl NavDirection {
+	pub const ALL: [NavDirection; 4] = [NavDirection::Up, NavDirection::Down, NavDirection::Left, NavDirection::Right];
+
+	pub fn dpad_button(self) -> GamepadButton {
+		match self {
+			NavDirection::Up => GamepadButton::DPadUp,
+			NavDirection::Down => GamepadButton::DPadDown,
+			NavDirection::Left => GamepadButton::DPadLeft,
+			NavDirection::Right => GamepadButton::DPadRight,
+		}
+	}
+
+	/// Picks the dominant axis of a y-up stick position.
+	pub fn from_stick(stick: Vec2) -> NavDirection {
+		match (stick.x.abs() > stick.y.abs(), stick.x > 0.0, stick.y > 0.0) {
+			(true, true, _) => NavDirection::Right,
+			(true, false, _) => NavDirection::Left,
+			(false, _, true) => NavDirection::Up,
+			(false, _, false) => NavDirection::Down,
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NavAction {
+	Move(NavDirection),
+	Activate,
+	Back,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct NavigationSettings {
+	/// How far the left stick must be pushed (after deadzone) before it counts as a direction.
+	pub stick_threshold: f32,
+
+	/// Seconds a direction must be held before it starts repeating.
+	pub repeat_delay: f32,
+	/// Seconds between repeats once repeating.
+	pub repeat_interval: f32,
+
+	pub activate_button: GamepadButton,
+	pub back_button: GamepadButton,
+}
+
+impl Default for NavigationSettings {
+	fn default() -> Self {
+		NavigationSettings {
+			stick_threshold: 0.5,
+			repeat_delay: 0.4,
+			repeat_interval: 0.1,
+
+			activate_button: GamepadButton::South,
+			back_button: GamepadButton::East,
+		}
+	}
+}
+
+
+#[derive(Debug, Default)]
+pub struct UiNavigation {
+	pub settings: NavigationSettings,
+
+	/// Whether actions should also be forwarded to egui as key presses, moving focus between widgets.
+	/// Off by default, since otherwise gamepad input meant for the game would also steer any visible egui windows.
+	pub drive_egui: bool,
+
+	/// Held direction, and time until it next repeats.
+	held: Option<(NavDirection, f32)>,
+	actions: Vec<NavAction>,
+}
+
+impl UiNavigation {
+	pub fn new() -> UiNavigation {
+		UiNavigation::default()
+	}
+
+	/// Should be called once per frame after input has been processed.
+	pub fn update(&mut self, tracker: &Tracker, delta_time: f32) {
+		self.actions.clear();
+
+		match (self.held_direction(tracker), &mut self.held) {
+			(None, held) => {
+				*held = None;
+			}
+
+			(Some(direction), Some((held_direction, repeat_timer))) if direction == *held_direction => {
+				*repeat_timer -= delta_time;
+				if *repeat_timer <= 0.0 {
+					*repeat_timer = self.settings.repeat_interval;
+					self.actions.push(NavAction::Move(direction));
+				}
+			}
+
+			(Some(direction), held) => {
+				*held = Some((direction, self.settings.repeat_delay));
+				self.actions.push(NavAction::Move(direction));
+			}
+		}
+
+		if tracker.button_just_down(self.settings.activate_button) {
+			self.actions.push(NavAction::Activate);
+		}
+
+		if tracker.button_just_down(self.settings.back_button) {
+			self.actions.push(NavAction::Back);
+		}
+	}
+
+	/// Actions generated by the last [`UiNavigation::update`], in the order they should be applied.
+	pub fn actions(&self) -> &[NavAction] {
+		&self.actions
+	}
+
+	pub fn moved(&self) -> Option<NavDirection> {
+		self.actions.iter().find_map(|action| match action {
+			NavAction::Move(direction) => Some(*direction),
+			_ => None,
+		})
+	}
+
+	pub fn activated(&self) -> bool {
+		self.actions.contains(&NavAction::Activate)
+	}
+
+	pub fn back(&self) -> bool {
+		self.actions.contains(&NavAction::Back)
+	}
+
+	/// Key events that make egui act on this frame's actions - arrows move focus, enter clicks the focused widget and
+	/// escape drops focus. Since egui can only move focus directionally once something is focused, moves while
+	/// `egui_has_focus` is false become tabs instead, to focus the first (or last) widget.
+	pub fn egui_events(&self, egui_has_focus: bool) -> Vec<egui::Event> {
+		let mut events = Vec::new();
+
+		for action in self.actions.iter() {
+			let (key, modifiers) = match action {
+				NavAction::Move(NavDirection::Up | NavDirection::Left) if !egui_has_focus => (egui::Key::Tab, egui::Modifiers::SHIFT),
+				NavAction::Move(_) if !egui_has_focus => (egui::Key::Tab, egui::Modifiers::NONE),
+
+				NavAction::Move(NavDirection::Up) => (egui::Key::ArrowUp, egui::Modifiers::NONE),
+				NavAction::Move(NavDirection::Down) => (egui::Key::ArrowDown, egui::Modifiers::NONE),
+				NavAction::Move(NavDirection::Left) => (egui::Key::ArrowLeft, egui::Modifiers::NONE),
+				NavAction::Move(NavDirection::Right) => (egui::Key::ArrowRight, egui::Modifiers::NONE),
+
+				NavAction::Activate => (egui::Key::Enter, egui::Modifiers::NONE),
+				NavAction::Back => (egui::Key::Escape, egui::Modifiers::NONE),
+			};
+
+			for pressed in [true, false] {
+				events.push(egui::Event::Key { key, physical_key: None, pressed, repeat: false, modifiers });
+			}
+		}
+
+		events
+	}
+
+	fn held_direction(&self, tracker: &Tracker) -> Option<NavDirection> {
+		// Check just_down too, so a dpad tap that starts and ends within a frame isn't lost.
+		let dpad_direction = NavDirection::ALL.into_iter()
+			.find(|direction| tracker.button_down(direction.dpad_button()) || tracker.button_just_down(direction.dpad_button()));
+
+		if dpad_direction.is_some() {
+			return dpad_direction
+		}
+
+		let stick = tracker.gamepad_stick(GamepadStick::Left);
+		(stick.length() >= self.settings.stick_threshold)
+			.then(|| NavDirection::from_stick(stick))
+	}
+}
+
+
+/// Focus for a simple vertical game menu, driven by [`UiNavigation`].
+///
+/// ```ignore
+/// let activated = self.menu_focus.update(&ctx.ui_navigation, items.len());
+/// for (index, item) in items.iter().enumerate() {
+/// 	draw_item(item, self.menu_focus.is_focused(index));
+/// }
+/// if let Some(index) = activated { ... }
+/// ```
+#[derive(Debug, Clone)]
+pub struct MenuFocus {
+	pub focused: usize,
+
+	/// Whether moving past either end of the menu wraps around to the other.
+	pub wrap: bool,
+}
+
+impl Default for MenuFocus {
+	fn default() -> Self {
+		MenuFocus::new()
+	}
+}
+
+impl MenuFocus {
+	pub fn new() -> MenuFocus {
+		MenuFocus { focused: 0, wrap: true }
+	}
+
+	pub fn with_wrap(self, wrap: bool) -> Self {
+		Self { wrap, .. self }
+	}
+
+	/// Applies this frame's navigation to a menu of `item_count` items, returning the index of the item activated,
+	/// if any. Left and right are ignored, so they remain free for e.g., adjusting sliders.
+	pub fn update(&mut self, navigation: &UiNavigation, item_count: usize) -> Option<usize> {
+		if item_count == 0 {
+			self.focused = 0;
+			return None
+		}
+
+		self.focused = self.focused.min(item_count - 1);
+
+		let mut activated = None;
+
+		for action in navigation.actions() {
+			match action {
+				NavAction::Move(NavDirection::Up) => self.step(-1, item_count),
+				NavAction::Move(NavDirection::Down) => self.step(1, item_count),
+				NavAction::Activate => activated = Some(self.focused),
+				_ => {}
+			}
+		}
+
+		activated
+	}
+
+	pub fn is_focused(&self, index: usize) -> bool {
+		self.focused == index
+	}
+
+	fn step(&mut self, delta: isize, item_count: usize) {
+		let next = self.focused as isize + delta;
+
+		self.focused = if self.wrap {
+			next.rem_euclid(item_count as isize) as usize
+		} else {
+			next.clamp(0, item_count as isize - 1) as usize
+		};
+	}
+}
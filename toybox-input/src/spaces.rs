@@ -0,0 +1,174 @@
+//! Positions tagged with the coordinate space they're in, so that mixing spaces - or forgetting a y flip - is a type
+//! error rather than a bug. Conversions between spaces are explicit, and are the only places flipping happens.
+//!
+//! | Space         | Origin       | Y    | Units                                              |
+//! |---------------|--------------|------|----------------------------------------------------|
+//! | [`WorldPos`]  | world origin | up   | world units                                        |
+//! | [`ViewPos`]   | camera       | up   | world units, looking down -z                       |
+//! | [`ClipPos`]   | -            | up   | homogeneous, before the perspective divide         |
+//! | [`NdcPos`]    | center       | up   | [-1, 1]                                            |
+//! | [`ScreenPos`] | bottom left  | up   | pixels of the render target - matches gl viewports |
+//! | [`WindowPos`] | top left     | down | physical window pixels - matches winit and egui    |
+//! | [`UiPos`]     | top left     | down | egui points                                        |
+//! | [`ImageUv`]   | bottom left  | up   | [0, 1] across an image - matches gl textures       |
+
+use common::*;
+use crate::Ray;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct WorldPos(pub Vec3);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ViewPos(pub Vec3);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ClipPos(pub Vec4);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct NdcPos(pub Vec2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ScreenPos(pub Vec2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct WindowPos(pub Vec2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct UiPos(pub Vec2);
+
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct ImageUv(pub Vec2);
+
+
+impl WorldPos {
+	pub fn to_view(self, view: &Mat4) -> ViewPos {
+		let Vec4{x, y, z, w} = *view * self.0.extend(1.0);
+		ViewPos(Vec3::new(x, y, z) / w)
+	}
+
+	/// `projection_view` should be the same matrix used to transform world space into clip space when rendering.
+	pub fn to_clip(self, projection_view: &Mat4) -> ClipPos {
+		ClipPos(*projection_view * self.0.extend(1.0))
+	}
+
+	/// None if behind the camera. See [`ClipPos::to_ndc`].
+	pub fn to_screen(self, projection_view: &Mat4, target_size: Vec2i) -> Option<ScreenPos> {
+		self.to_clip(projection_view).to_ndc()
+			.map(|ndc| ndc.to_screen(target_size))
+	}
+}
+
+impl ViewPos {
+	pub fn to_world(self, inverse_view: &Mat4) -> WorldPos {
+		let Vec4{x, y, z, w} = *inverse_view * self.0.extend(1.0);
+		WorldPos(Vec3::new(x, y, z) / w)
+	}
+
+	pub fn to_clip(self, projection: &Mat4) -> ClipPos {
+		ClipPos(*projection * self.0.extend(1.0))
+	}
+}
+
+impl ClipPos {
+	/// Perspective divide. None if behind the camera, where the divide would mirror the position.
+	/// Positions outside of the view frustum still convert, but lie outside of [-1, 1].
+	pub fn to_ndc(self) -> Option<NdcPos> {
+		let Vec4{x, y, w, ..} = self.0;
+		(w > 0.0).then(|| NdcPos(Vec2::new(x, y) / w))
+	}
+
+	/// Depth in ndc, before the perspective divide is thrown away by [`ClipPos::to_ndc`].
+	pub fn ndc_depth(self) -> f32 {
+		self.0.z / self.0.w
+	}
+}
+
+impl NdcPos {
+	pub fn to_screen(self, target_size: Vec2i) -> ScreenPos {
+		ScreenPos((self.0 / 2.0 + Vec2::splat(0.5)) * target_size.to_vec2())
+	}
+
+	/// `ui_rect` is the region of the ui the render target is shown in - e.g., `egui::Context::screen_rect`.
+	pub fn to_ui(self, ui_rect: egui::Rect) -> UiPos {
+		UiPos(Vec2::new(
+			ui_rect.left() + (self.0.x * 0.5 + 0.5) * ui_rect.width(),
+			ui_rect.top() + (0.5 - self.0.y * 0.5) * ui_rect.height(),
+		))
+	}
+
+	/// Unprojects into a world space ray, starting at the near plane.
+	/// `projection_view` should transform world space into clip space with the standard -1..1 depth range - i.e.,
+	/// without reverse-z applied. See [`NdcPos::to_ray_reverse_z`] otherwise.
+	pub fn to_ray(self, projection_view: &Mat4) -> Ray {
+		self.unproject_ray(projection_view, -1.0, 1.0)
+	}
+
+	/// Like [`NdcPos::to_ray`], but for a `projection_view` adjusted for reverse-z, which maps near to 1 and far to 0.
+	pub fn to_ray_reverse_z(self, projection_view: &Mat4) -> Ray {
+		self.unproject_ray(projection_view, 1.0, 0.0)
+	}
+
+	fn unproject_ray(self, projection_view: &Mat4, near_depth: f32, far_depth: f32) -> Ray {
+		let inverse = projection_view.inverse();
+
+		let unproject = |z: f32| {
+			let Vec4{x, y, z, w} = inverse * Vec4::new(self.0.x, self.0.y, z, 1.0);
+			Vec3::new(x, y, z) / w
+		};
+
+		let near = unproject(near_depth);
+		let far = unproject(far_depth);
+
+		Ray {
+			origin: near,
+			direction: (far - near).normalize(),
+		}
+	}
+}
+
+impl ScreenPos {
+	pub fn to_ndc(self, target_size: Vec2i) -> NdcPos {
+		NdcPos((self.0 / target_size.to_vec2() - Vec2::splat(0.5)) * 2.0)
+	}
+
+	/// Only meaningful when the render target covers the whole window - see [`crate::PixelMapping`] otherwise.
+	pub fn to_window(self, window_size: Vec2i) -> WindowPos {
+		WindowPos(Vec2::new(self.0.x, window_size.y as f32 - self.0.y - 1.0))
+	}
+}
+
+impl WindowPos {
+	/// Only meaningful when the render target covers the whole window - see [`crate::PixelMapping`] otherwise.
+	pub fn to_screen(self, window_size: Vec2i) -> ScreenPos {
+		ScreenPos(Vec2::new(self.0.x, window_size.y as f32 - self.0.y - 1.0))
+	}
+}
+
+impl UiPos {
+	pub fn to_ndc(self, ui_rect: egui::Rect) -> NdcPos {
+		NdcPos(Vec2::new(
+			(self.0.x - ui_rect.left()) / ui_rect.width() * 2.0 - 1.0,
+			1.0 - (self.0.y - ui_rect.top()) / ui_rect.height() * 2.0,
+		))
+	}
+
+	pub fn to_egui(self) -> egui::Pos2 {
+		egui::pos2(self.0.x, self.0.y)
+	}
+}
+
+impl ImageUv {
+	/// The egui uv that samples this point of a gl image. Both address texels the same way, but egui assumes the first
+	/// row of a texture is the top of the image, where gl images start at the bottom.
+	pub fn to_egui(self) -> egui::Pos2 {
+		egui::pos2(self.0.x, self.0.y)
+	}
+
+	/// egui uvs that show the whole of a gl image the right way up, with its top left at the top left of the widget.
+	pub fn egui_rect() -> egui::Rect {
+		let top_left = ImageUv(Vec2::new(0.0, 1.0));
+		let bottom_right = ImageUv(Vec2::new(1.0, 0.0));
+		egui::Rect::from_min_max(top_left.to_egui(), bottom_right.to_egui())
+	}
+}
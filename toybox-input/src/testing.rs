@@ -0,0 +1,353 @@
+//! Utilities for driving a headless [`System`] with synthetic input, one frame at a time.
+
+use crate::*;
+
+
+/// Owns a headless [`System`] and feeds it synthetic events in the same order the host would.
+///
+/// ```ignore
+/// let mut harness = InputHarness::new();
+/// harness.frame(|input| input.press(MouseButton::Left));
+/// assert!(harness.system.button_just_down(MouseButton::Left));
+/// ```
+pub struct InputHarness {
+	pub system: System,
+}
+
+impl InputHarness {
+	pub fn new() -> InputHarness {
+		InputHarness::with_window_size(Vec2i::new(800, 600))
+	}
+
+	pub fn with_window_size(window_size: Vec2i) -> InputHarness {
+		InputHarness {
+			system: System::new_headless(window_size),
+		}
+	}
+
+	/// Run a single frame, with `f` generating the events that arrive during it.
+	/// Afterwards, `self.system` can be queried as an app would during update.
+	pub fn frame(&mut self, f: impl FnOnce(&mut FrameInput<'_>)) -> &System {
+		self.system.reset_tracker();
+		f(&mut FrameInput { system: &mut self.system });
+		self.system.process();
+		&self.system
+	}
+
+	/// Run a frame with no events.
+	pub fn idle_frame(&mut self) -> &System {
+		self.frame(|_| {})
+	}
+}
+
+impl Default for InputHarness {
+	fn default() -> Self {
+		InputHarness::new()
+	}
+}
+
+
+/// Events arriving during a single frame.
+pub struct FrameInput<'s> {
+	system: &'s mut System,
+}
+
+impl FrameInput<'_> {
+	pub fn window_event(&mut self, event: &WindowEvent) {
+		self.system.on_window_event(event);
+	}
+
+	pub fn device_event(&mut self, event: &DeviceEvent) {
+		self.system.on_device_event(event);
+	}
+
+	/// Mouse buttons go through the same window event path as real input. Keys are tracked directly, since winit
+	/// doesn't allow constructing keyboard events outside of itself.
+	pub fn press(&mut self, button: impl Into<Button>) {
+		self.set_button(button.into(), true);
+	}
+
+	pub fn release(&mut self, button: impl Into<Button>) {
+		self.set_button(button.into(), false);
+	}
+
+	fn set_button(&mut self, button: Button, down: bool) {
+		match button {
+			Button::Mouse(button) => {
+				let state = if down { ElementState::Pressed } else { ElementState::Released };
+				self.window_event(&WindowEvent::MouseInput { device_id: dummy_device_id(), state, button });
+			}
+
+			button => self.system.tracker.track_button(button, down),
+		}
+	}
+
+	/// `position` is in physical window pixels, with the origin at the top left - as winit reports it.
+	pub fn move_cursor_to(&mut self, position: Vec2) {
+		let position = PhysicalPosition::new(position.x as f64, position.y as f64);
+		self.window_event(&WindowEvent::CursorMoved { device_id: dummy_device_id(), position });
+	}
+
+	pub fn cursor_left(&mut self) {
+		self.window_event(&WindowEvent::CursorLeft { device_id: dummy_device_id() });
+	}
+
+	/// Raw mouse motion in dots, y down.
+	pub fn move_mouse(&mut self, delta: Vec2) {
+		self.device_event(&DeviceEvent::MouseMotion { delta: (delta.x as f64, delta.y as f64) });
+	}
+
+	/// x-right, y-up, as gamepads report it.
+	pub fn move_stick(&mut self, stick: GamepadStick, position: Vec2) {
+		self.system.tracker.track_gamepad_stick(stick, position);
+	}
+
+	pub fn disconnect_gamepad(&mut self) {
+		self.system.tracker.track_gamepad_disconnected();
+	}
+
+	pub fn focus(&mut self, focused: bool) {
+		self.window_event(&WindowEvent::Focused(focused));
+	}
+
+	pub fn set_occluded(&mut self, occluded: bool) {
+		self.system.set_occluded(occluded);
+	}
+}
+
+fn dummy_device_id() -> DeviceId {
+	// SAFETY: only used for synthetic events, which never get passed back to winit.
+	unsafe { DeviceId::dummy() }
+}
+
+
+
+#[cfg(test)]
+mod test {
+	use super::*;
+
+
+	#[test]
+	fn just_pressed_only_lasts_one_frame() {
+		let mut harness = InputHarness::new();
+
+		let system = harness.frame(|input| input.press(keys::KeyW));
+		assert!(system.button_just_down(keys::KeyW));
+		assert!(system.button_down(keys::KeyW));
+
+		let system = harness.idle_frame();
+		assert!(!system.button_just_down(keys::KeyW), "Just down state should be cleared on the next frame");
+		assert!(system.button_down(keys::KeyW), "Button should still be held");
+
+		let system = harness.frame(|input| input.release(keys::KeyW));
+		assert!(system.button_just_up(keys::KeyW));
+		assert!(!system.button_down(keys::KeyW));
+	}
+
+	#[test]
+	fn press_and_release_in_same_frame() {
+		let mut harness = InputHarness::new();
+
+		let system = harness.frame(|input| {
+			input.press(MouseButton::Left);
+			input.release(MouseButton::Left);
+		});
+
+		assert!(system.button_just_down(MouseButton::Left), "Short clicks shouldn't be lost");
+		assert!(system.button_just_up(MouseButton::Left), "Short clicks shouldn't be lost");
+		assert!(!system.button_down(MouseButton::Left));
+	}
+
+	#[test]
+	fn focus_loss_releases_held_buttons() {
+		let mut harness = InputHarness::new();
+
+		harness.frame(|input| {
+			input.press(MouseButton::Right);
+			input.press(keys::KeyA);
+		});
+
+		let system = harness.frame(|input| input.focus(false));
+		assert!(!system.button_down(MouseButton::Right));
+		assert!(!system.button_down(keys::KeyA));
+		assert!(system.button_just_up(MouseButton::Right), "Focus loss should look like a release");
+		assert!(system.button_just_up(keys::KeyA), "Focus loss should look like a release");
+	}
+
+	#[test]
+	fn mouse_capture_follows_focus_and_occlusion() {
+		let mut harness = InputHarness::new();
+
+		harness.system.set_capture_mouse(true);
+		assert!(harness.system.is_mouse_captured());
+
+		harness.frame(|input| input.focus(false));
+		assert!(!harness.system.is_mouse_captured(), "Capture should be released on focus loss");
+
+		harness.frame(|input| input.focus(true));
+		assert!(harness.system.is_mouse_captured(), "Capture should be restored on focus gain");
+
+		harness.frame(|input| input.set_occluded(true));
+		assert!(!harness.system.is_mouse_captured(), "Capture should be released while occluded");
+
+		harness.frame(|input| input.set_occluded(false));
+		assert!(harness.system.is_mouse_captured());
+	}
+
+	#[test]
+	fn mouse_position_and_delta() {
+		let mut harness = InputHarness::with_window_size(Vec2i::new(100, 50));
+
+		let system = harness.frame(|input| {
+			input.move_cursor_to(Vec2::new(10.0, 0.0));
+			input.move_mouse(Vec2::new(3.0, 2.0));
+			input.move_mouse(Vec2::new(1.0, 2.0));
+		});
+
+		assert_eq!(system.mouse_position_pixels(), Some(Vec2::new(10.0, 49.0)), "Pixels should have a bottom left origin");
+		assert_eq!(system.mouse_delta_dots(), Some(Vec2::new(4.0, -4.0)), "Deltas should accumulate, with y up");
+
+		let system = harness.idle_frame();
+		assert_eq!(system.mouse_delta_dots(), None, "Deltas should be cleared each frame");
+		assert!(system.mouse_position_pixels().is_some(), "Position should persist between frames");
+
+		let system = harness.frame(|input| input.cursor_left());
+		assert_eq!(system.mouse_position_pixels(), None);
+	}
+
+	#[test]
+	fn coordinate_space_conversions() {
+		let mut harness = InputHarness::with_window_size(Vec2i::new(100, 50));
+
+		let system = harness.frame(|input| input.move_cursor_to(Vec2::new(0.0, 0.0)));
+		assert_eq!(system.mouse_screen_pos(), Some(ScreenPos(Vec2::new(0.0, 49.0))), "Window origin should be top left");
+
+		let screen = ScreenPos(Vec2::new(25.0, 40.0));
+		assert_eq!(system.screen_to_window(screen), WindowPos(Vec2::new(25.0, 9.0)));
+		assert_eq!(system.window_to_screen(system.screen_to_window(screen)), screen, "Window round trip should be lossless");
+
+		let ndc = screen.to_ndc(Vec2i::new(100, 50));
+		assert_eq!(ndc, NdcPos(Vec2::new(-0.5, 0.6)));
+		assert_eq!(ndc.to_screen(Vec2i::new(100, 50)), screen);
+
+		let world = WorldPos(Vec3::new(0.5, -0.5, 0.0));
+		assert_eq!(system.world_to_screen(world, &Mat4::identity()), Some(ScreenPos(Vec2::new(75.0, 12.5))));
+
+		let ui_rect = egui::Rect::from_min_size(egui::pos2(10.0, 20.0), egui::vec2(50.0, 25.0));
+		assert_eq!(ndc.to_ui(ui_rect), UiPos(Vec2::new(22.5, 25.0)), "Ui space should be y down from the rect's top left");
+		assert_eq!(ndc.to_ui(ui_rect).to_ndc(ui_rect), ndc);
+
+		let behind = ClipPos(Vec4::new(0.0, 0.0, 0.0, -1.0));
+		assert_eq!(behind.to_ndc(), None, "Positions behind the camera shouldn't project");
+	}
+
+	#[test]
+	fn gamepad_navigation_repeats_while_held() {
+		let mut harness = InputHarness::new();
+		let mut navigation = UiNavigation::new();
+
+		harness.frame(|input| input.press(GamepadButton::DPadDown));
+		navigation.update(&harness.system.tracker, 0.0);
+		assert_eq!(navigation.actions(), &[NavAction::Move(NavDirection::Down)]);
+
+		harness.idle_frame();
+		navigation.update(&harness.system.tracker, 0.1);
+		assert_eq!(navigation.moved(), None, "Shouldn't repeat before the repeat delay");
+
+		harness.idle_frame();
+		navigation.update(&harness.system.tracker, navigation.settings.repeat_delay);
+		assert_eq!(navigation.moved(), Some(NavDirection::Down), "Should repeat once held past the repeat delay");
+
+		harness.frame(|input| {
+			input.release(GamepadButton::DPadDown);
+			input.press(GamepadButton::South);
+		});
+		navigation.update(&harness.system.tracker, 0.1);
+		assert_eq!(navigation.actions(), &[NavAction::Activate]);
+	}
+
+	#[test]
+	fn gamepad_stick_navigation() {
+		let mut harness = InputHarness::new();
+		let mut navigation = UiNavigation::new();
+
+		harness.frame(|input| input.move_stick(GamepadStick::Left, Vec2::new(0.05, 0.1)));
+		navigation.update(&harness.system.tracker, 0.1);
+		assert_eq!(navigation.moved(), None, "Stick inside deadzone shouldn't navigate");
+
+		harness.frame(|input| input.move_stick(GamepadStick::Left, Vec2::new(-0.9, 0.3)));
+		navigation.update(&harness.system.tracker, 0.1);
+		assert_eq!(navigation.moved(), Some(NavDirection::Left));
+
+		harness.frame(|input| input.move_stick(GamepadStick::Left, Vec2::zero()));
+		navigation.update(&harness.system.tracker, 0.1);
+
+		harness.frame(|input| input.move_stick(GamepadStick::Left, Vec2::new(0.0, 1.0)));
+		navigation.update(&harness.system.tracker, 0.1);
+		assert_eq!(navigation.moved(), Some(NavDirection::Up), "Recentering the stick should allow moving again");
+	}
+
+	#[test]
+	fn gamepad_disconnect_releases_held_input() {
+		let mut harness = InputHarness::new();
+
+		harness.frame(|input| {
+			input.press(GamepadButton::South);
+			input.press(MouseButton::Left);
+			input.move_stick(GamepadStick::Left, Vec2::new(1.0, 0.0));
+		});
+
+		harness.frame(|input| input.disconnect_gamepad());
+		assert!(harness.system.button_just_up(GamepadButton::South));
+		assert!(!harness.system.button_down(GamepadButton::South));
+		assert!(harness.system.button_down(MouseButton::Left), "Only gamepad buttons should be released");
+		assert_eq!(harness.system.tracker.raw_gamepad_stick(GamepadStick::Left), Vec2::zero());
+	}
+
+	#[test]
+	fn menu_focus_wraps_and_activates() {
+		let mut harness = InputHarness::new();
+		let mut navigation = UiNavigation::new();
+		let mut menu = MenuFocus::new();
+
+		harness.frame(|input| input.press(GamepadButton::DPadUp));
+		navigation.update(&harness.system.tracker, 0.0);
+		assert_eq!(menu.update(&navigation, 3), None);
+		assert_eq!(menu.focused, 2, "Moving up from the first item should wrap to the last");
+
+		harness.frame(|input| {
+			input.release(GamepadButton::DPadUp);
+			input.press(GamepadButton::South);
+		});
+		navigation.update(&harness.system.tracker, 0.0);
+		assert_eq!(menu.update(&navigation, 3), Some(2));
+
+		let mut menu = MenuFocus::new().with_wrap(false);
+		harness.frame(|input| input.press(GamepadButton::DPadUp));
+		navigation.update(&harness.system.tracker, 0.0);
+		menu.update(&navigation, 3);
+		assert_eq!(menu.focused, 0, "Shouldn't wrap when wrapping is disabled");
+	}
+
+	#[test]
+	fn reverse_z_rays_match_standard_rays() {
+		let (near, far) = (1.0, 100.0);
+		let projection = Mat4::from_rows([
+			Vec4::new(1.0, 0.0, 0.0, 0.0),
+			Vec4::new(0.0, 1.0, 0.0, 0.0),
+			Vec4::new(0.0, 0.0, (far + near) / (near - far), 2.0 * far * near / (near - far)),
+			Vec4::new(0.0, 0.0, -1.0, 0.0),
+		]);
+
+		let [x, y, z, w] = projection.rows;
+		let reverse_z_projection = Mat4::from_rows([x, y, (w - z) * 0.5, w]);
+
+		let ndc = NdcPos(Vec2::new(0.5, -0.25));
+		let standard = ndc.to_ray(&projection);
+		let reverse_z = ndc.to_ray_reverse_z(&reverse_z_projection);
+
+		assert!((standard.origin - reverse_z.origin).length() < 1e-4, "{standard:?} != {reverse_z:?}");
+		assert!((standard.direction - reverse_z.direction).length() < 1e-4, "{standard:?} != {reverse_z:?}");
+		assert!((standard.origin.z + near).abs() < 1e-4, "Ray should start on the near plane");
+	}
+}
@@ -13,6 +13,10 @@ pub struct Tracker {
 
 	// This is in raw 'dots' per frame - y-down. related to dpi
 	pub mouse_delta: Option<Vec2>,
+
+	// Raw stick positions in [-1, 1], y-up. See [`Tracker::gamepad_stick`] for deadzoned values.
+	pub left_stick: Vec2,
+	pub right_stick: Vec2,
 }
 
 /// Input query API.
@@ -28,6 +32,18 @@ impl Tracker {
 	pub fn button_just_up(&self, button: impl Into<Button>) -> bool {
 		self.up_buttons.contains(&button.into())
 	}
+
+	/// Stick position with [`apply_stick_deadzone`] applied. x-right, y-up.
+	pub fn gamepad_stick(&self, stick: GamepadStick) -> Vec2 {
+		apply_stick_deadzone(self.raw_gamepad_stick(stick))
+	}
+
+	pub fn raw_gamepad_stick(&self, stick: GamepadStick) -> Vec2 {
+		match stick {
+			GamepadStick::Left => self.left_stick,
+			GamepadStick::Right => self.right_stick,
+		}
+	}
 }
 
 /// Input gathering API - called by core.
@@ -68,6 +84,26 @@ impl Tracker {
 		self.mouse_delta = None;
 	}
 
+	pub fn track_gamepad_stick(&mut self, stick: GamepadStick, value: Vec2) {
+		match stick {
+			GamepadStick::Left => self.left_stick = value,
+			GamepadStick::Right => self.right_stick = value,
+		}
+	}
+
+	/// Releases every held gamepad button and recenters the sticks, since no release events will arrive for them.
+	// TODO(pat.m): gamepads aren't tracked separately, so this also releases anything held on other gamepads.
+	pub fn track_gamepad_disconnected(&mut self) {
+		let (released, still_active): (Vec<Button>, Vec<Button>) = self.active_buttons.drain(..)
+			.partition(|button| matches!(button, Button::Gamepad(_)));
+
+		self.active_buttons = still_active;
+		self.up_buttons.extend(released);
+
+		self.left_stick = Vec2::zero();
+		self.right_stick = Vec2::zero();
+	}
+
 	pub fn track_focus_lost(&mut self) {
 		self.up_buttons.append(&mut self.active_buttons);
 	}
@@ -78,11 +114,12 @@ impl Tracker {
 }
 
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum Button {
 	LogicalKey(LogicalKey),
 	PhysicalKey(winit::keyboard::PhysicalKey),
 	Mouse(MouseButton),
+	Gamepad(GamepadButton),
 }
 
 impl From<LogicalKey> for Button {
@@ -113,4 +150,10 @@ impl From<MouseButton> for Button {
 	fn from(o: MouseButton) -> Button {
 		Button::Mouse(o)
 	} 
-}
\ No newline at end of file
+}
+
+impl From<GamepadButton> for Button {
+	fn from(o: GamepadButton) -> Button {
+		Button::Gamepad(o)
+	} 
+}
@@ -0,0 +1,53 @@
+//! Storefront integrations - achievements, rich presence and cloud saves.
+//! Steamworks support is enabled with the `steam` feature. Otherwise, or if the storefront isn't running,
+//! a no-op implementation is used.
+//!
+//! itch.io has no achievements, rich presence or cloud saves to integrate with, so itch.io builds also use the no-op
+//! implementation, and user data stays in the default vfs user data root.
+
+use std::path::PathBuf;
+
+#[cfg(feature="steam")]
+mod steam;
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+
+pub trait PlatformServices {
+	fn name(&self) -> &str;
+
+	/// Called once per frame to pump any platform callbacks.
+	fn update(&mut self) {}
+
+	fn unlock_achievement(&mut self, _id: &str) {}
+	fn clear_achievement(&mut self, _id: &str) {}
+
+	/// Pass `None` to clear `key`.
+	fn set_rich_presence(&mut self, _key: &str, _value: Option<&str>) {}
+
+	/// Where user data should live so that it gets synced by the platform, if anywhere.
+	/// Overrides the vfs user data root. Only steam provides one.
+	fn cloud_save_root(&self, _app_name: &str) -> Option<PathBuf> { None }
+}
+
+
+/// Used when no storefront is available.
+#[derive(Debug, Default)]
+pub struct NoopPlatform;
+
+impl PlatformServices for NoopPlatform {
+	fn name(&self) -> &str { "none" }
+}
+
+
+/// Connect to whichever storefront is enabled and running, falling back to [`NoopPlatform`].
+pub fn init() -> Box<dyn PlatformServices> {
+	#[cfg(feature="steam")]
+	match steam::SteamPlatform::init() {
+		Ok(steam) => return Box::new(steam),
+		Err(error) => log::warn!("Failed to initialise steam - platform services will be unavailable: {error}"),
+	}
+
+	Box::new(NoopPlatform)
+}
@@ -0,0 +1,73 @@
+use crate::PlatformServices;
+use std::path::PathBuf;
+
+
+pub struct SteamPlatform {
+	client: steamworks::Client,
+	single: steamworks::SingleClient,
+
+	stats_dirty: bool,
+}
+
+impl SteamPlatform {
+	/// Expects either to be launched through steam or a `steam_appid.txt` next to the executable.
+	pub fn init() -> anyhow::Result<SteamPlatform> {
+		let (client, single) = steamworks::Client::init()?;
+		log::info!("Connected to steam as '{}'", client.friends().name());
+
+		Ok(SteamPlatform {
+			client,
+			single,
+			stats_dirty: false,
+		})
+	}
+}
+
+impl PlatformServices for SteamPlatform {
+	fn name(&self) -> &str { "steam" }
+
+	fn update(&mut self) {
+		self.single.run_callbacks();
+
+		if std::mem::take(&mut self.stats_dirty) {
+			if let Err(error) = self.client.user_stats().store_stats() {
+				log::error!("Failed to store steam stats: {error:?}");
+			}
+		}
+	}
+
+	fn unlock_achievement(&mut self, id: &str) {
+		match self.client.user_stats().achievement(id).set() {
+			Ok(()) => self.stats_dirty = true,
+			Err(error) => log::error!("Failed to unlock achievement '{id}': {error:?}"),
+		}
+	}
+
+	fn clear_achievement(&mut self, id: &str) {
+		match self.client.user_stats().achievement(id).clear() {
+			Ok(()) => self.stats_dirty = true,
+			Err(error) => log::error!("Failed to clear achievement '{id}': {error:?}"),
+		}
+	}
+
+	fn set_rich_presence(&mut self, key: &str, value: Option<&str>) {
+		if !self.client.friends().set_rich_presence(key, value) {
+			log::warn!("Failed to set rich presence '{key}'");
+		}
+	}
+
+	// Steam Auto-Cloud is configured per app to sync a directory relative to a root - this matches a root of
+	// 'WinAppDataRoaming'/'LinuxXdgDataHome' with a subdirectory of 'toybox/<app name>/steam/{64BitSteamID}'.
+	fn cloud_save_root(&self, app_name: &str) -> Option<PathBuf> {
+		let steam_id = self.client.user().steam_id().raw();
+
+		let mut path = dirs::data_dir()?;
+
+		path.push("toybox");
+		path.push(app_name);
+		path.push("steam");
+		path.push(steam_id.to_string());
+		Some(path)
+	}
+}
+
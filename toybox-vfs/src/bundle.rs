@@ -0,0 +1,372 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use anyhow::Context;
+use tracing::instrument;
+
+
+const MAGIC: &[u8; 8] = b"TOYBUNDL";
+const VERSION: u32 = 1;
+const HEADER_SIZE: usize = 32;
+const DATA_ALIGNMENT: u64 = 16;
+
+
+// Layout:
+//	header: magic, version, generation, index offset, index size
+//	data blobs, each aligned to DATA_ALIGNMENT
+//	index: entry count, then (offset, size, path length, path) per entry
+//
+// Patching appends new blobs and a new index to the end of the file, and then rewrites the header in place to point
+// at the new index. Readers only ever see either the old or the new index, and data referenced by either is never
+// overwritten. The old index and any replaced blobs are left as dead space until the bundle is rebuilt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct Header {
+	generation: u32,
+	index_offset: u64,
+	index_size: u64,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct BundleEntry {
+	offset: u64,
+	size: u64,
+}
+
+
+/// A read only, memory mapped archive of resources. See [`Vfs::mount_bundle`](crate::Vfs::mount_bundle).
+pub struct Bundle {
+	path: PathBuf,
+	file: File,
+	mmap: memmap2::Mmap,
+	header: Header,
+	entries: BTreeMap<String, BundleEntry>,
+}
+
+impl Bundle {
+	#[instrument(name="vfs open bundle")]
+	pub fn open(path: &Path) -> anyhow::Result<Bundle> {
+		let file = File::open(path)
+			.with_context(|| format!("Opening bundle '{}'", path.display()))?;
+
+		let (mmap, header, entries) = map_bundle(&file)
+			.with_context(|| format!("Reading bundle '{}'", path.display()))?;
+
+		Ok(Bundle {
+			path: path.to_owned(),
+			file,
+			mmap,
+			header,
+			entries,
+		})
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	pub fn get(&self, virtual_path: impl AsRef<Path>) -> Option<&[u8]> {
+		let key = bundle_key(virtual_path.as_ref()).ok()?;
+		let entry = self.entries.get(&key)?;
+
+		let start = entry.offset as usize;
+		let end = start + entry.size as usize;
+		self.mmap.get(start..end)
+	}
+
+	pub fn contains(&self, virtual_path: impl AsRef<Path>) -> bool {
+		bundle_key(virtual_path.as_ref())
+			.is_ok_and(|key| self.entries.contains_key(&key))
+	}
+
+	pub fn paths(&self) -> impl Iterator<Item=&str> + '_ {
+		self.entries.keys().map(String::as_str)
+	}
+
+	/// Remaps the bundle if it has been patched since it was opened, and returns which entries changed.
+	pub(crate) fn refresh(&mut self) -> anyhow::Result<Vec<String>> {
+		let header = parse_header(&self.mmap)?;
+		if header == self.header {
+			return Ok(Vec::new())
+		}
+
+		let (mmap, header, entries) = map_bundle(&self.file)?;
+
+		let mut changed: Vec<String> = entries.iter()
+			.filter(|(key, entry)| self.entries.get(*key) != Some(entry))
+			.map(|(key, _)| key.clone())
+			.collect();
+
+		changed.extend(self.entries.keys().filter(|key| !entries.contains_key(*key)).cloned());
+
+		self.mmap = mmap;
+		self.header = header;
+		self.entries = entries;
+
+		log::info!("Bundle '{}' patched (generation {}) - {} entries changed", self.path.display(), header.generation, changed.len());
+
+		Ok(changed)
+	}
+}
+
+
+
+/// Creates new bundles, or patches existing ones in place.
+pub struct BundleWriter {
+	file: File,
+	header: Header,
+	entries: BTreeMap<String, BundleEntry>,
+	end: u64,
+}
+
+impl BundleWriter {
+	/// Create a new empty bundle, replacing anything already at `path`.
+	pub fn create(path: &Path) -> anyhow::Result<BundleWriter> {
+		let mut file = File::options().read(true).write(true).create(true).truncate(true)
+			.open(path)
+			.with_context(|| format!("Creating bundle '{}'", path.display()))?;
+
+		let header = Header {
+			generation: 0,
+			index_offset: 0,
+			index_size: 0,
+		};
+
+		file.write_all(&encode_header(&header))?;
+
+		Ok(BundleWriter {
+			file,
+			header,
+			entries: BTreeMap::new(),
+			end: HEADER_SIZE as u64,
+		})
+	}
+
+	/// Open an existing bundle for patching. Entries that aren't added or removed are left untouched.
+	pub fn patch(path: &Path) -> anyhow::Result<BundleWriter> {
+		let mut file = File::options().read(true).write(true)
+			.open(path)
+			.with_context(|| format!("Opening bundle '{}' for patching", path.display()))?;
+
+		let mut header_bytes = [0u8; HEADER_SIZE];
+		file.read_exact(&mut header_bytes)?;
+		let header = parse_header(&header_bytes)?;
+
+		let end = file.seek(SeekFrom::End(0))?;
+
+		// Offsets come straight from the file, so must be checked before allocating for the index.
+		header.index_offset.checked_add(header.index_size)
+			.filter(|&index_end| index_end <= end)
+			.context("Bundle index out of bounds")?;
+
+		let mut index_bytes = vec![0u8; header.index_size as usize];
+		file.seek(SeekFrom::Start(header.index_offset))?;
+		file.read_exact(&mut index_bytes)?;
+
+		let entries = parse_index(&index_bytes, end)?;
+
+		Ok(BundleWriter {
+			file,
+			header,
+			entries,
+			end,
+		})
+	}
+
+	/// Add or replace the resource at `virtual_path`.
+	pub fn add(&mut self, virtual_path: impl AsRef<Path>, data: &[u8]) -> anyhow::Result<()> {
+		let key = bundle_key(virtual_path.as_ref())?;
+		anyhow::ensure!(key.len() <= u16::MAX as usize, "Bundle path too long '{key}'");
+
+		let offset = self.append(data)?;
+		self.entries.insert(key, BundleEntry { offset, size: data.len() as u64 });
+
+		Ok(())
+	}
+
+	/// Add every file under `root`, keyed by their path relative to it.
+	pub fn add_directory(&mut self, root: &Path) -> anyhow::Result<()> {
+		let mut to_visit = vec![root.to_owned()];
+
+		while let Some(dir) = to_visit.pop() {
+			for dir_entry in dir.read_dir()? {
+				let path = dir_entry?.path();
+
+				if path.is_dir() {
+					to_visit.push(path);
+					continue
+				}
+
+				let data = std::fs::read(&path)
+					.with_context(|| format!("Reading '{}'", path.display()))?;
+
+				self.add(path.strip_prefix(root)?, &data)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn remove(&mut self, virtual_path: impl AsRef<Path>) -> bool {
+		bundle_key(virtual_path.as_ref())
+			.is_ok_and(|key| self.entries.remove(&key).is_some())
+	}
+
+	/// Write the index and point the header at it. Until this is called, readers continue to see the previous
+	/// contents of the bundle.
+	pub fn finish(mut self) -> anyhow::Result<()> {
+		let index = encode_index(&self.entries);
+		let index_offset = self.append(&index)?;
+
+		// Make sure everything the new index references is on disk before publishing it.
+		self.file.sync_data()?;
+
+		self.header = Header {
+			generation: self.header.generation.wrapping_add(1),
+			index_offset,
+			index_size: index.len() as u64,
+		};
+
+		self.file.seek(SeekFrom::Start(0))?;
+		self.file.write_all(&encode_header(&self.header))?;
+		self.file.sync_data()?;
+
+		Ok(())
+	}
+
+	fn append(&mut self, data: &[u8]) -> anyhow::Result<u64> {
+		let offset = self.end.next_multiple_of(DATA_ALIGNMENT);
+		let padding = (offset - self.end) as usize;
+
+		self.file.seek(SeekFrom::Start(self.end))?;
+		self.file.write_all(&[0u8; DATA_ALIGNMENT as usize][..padding])?;
+		self.file.write_all(data)?;
+
+		self.end = offset + data.len() as u64;
+
+		Ok(offset)
+	}
+}
+
+
+
+/// Bundle keys are virtual paths with '/' separators and no root or '.' components.
+pub(crate) fn bundle_key(virtual_path: &Path) -> anyhow::Result<String> {
+	let clean_path = crate::clean_virtual_path(virtual_path.components())?;
+
+	let parts: Vec<_> = clean_path.components()
+		.filter_map(|component| match component {
+			std::path::Component::Normal(text) => text.to_str(),
+			_ => None,
+		})
+		.collect();
+
+	anyhow::ensure!(!parts.is_empty(), "Empty bundle path '{}'", virtual_path.display());
+
+	Ok(parts.join("/"))
+}
+
+fn map_bundle(file: &File) -> anyhow::Result<(memmap2::Mmap, Header, BTreeMap<String, BundleEntry>)> {
+	// SAFETY: Bundles are only ever appended to, or have their header rewritten, so any data referenced by the
+	// index we parse here stays valid for as long as the map. Files being truncated by external processes is UB,
+	// but that's true of most uses of mmap.
+	let mmap = unsafe { memmap2::Mmap::map(file)? };
+
+	let header = parse_header(&mmap)?;
+
+	// Offsets come straight from the file, so may be anything.
+	let index_end = header.index_offset.checked_add(header.index_size)
+		.filter(|&end| end <= mmap.len() as u64)
+		.context("Bundle index out of bounds")?;
+
+	let index_bytes = &mmap[header.index_offset as usize..index_end as usize];
+
+	let entries = parse_index(index_bytes, mmap.len() as u64)?;
+
+	Ok((mmap, header, entries))
+}
+
+fn parse_header(bytes: &[u8]) -> anyhow::Result<Header> {
+	let mut reader = ByteReader(bytes);
+
+	anyhow::ensure!(reader.take(MAGIC.len())? == MAGIC, "Not a bundle");
+
+	let version = reader.u32()?;
+	anyhow::ensure!(version == VERSION, "Unsupported bundle version {version}");
+
+	Ok(Header {
+		generation: reader.u32()?,
+		index_offset: reader.u64()?,
+		index_size: reader.u64()?,
+	})
+}
+
+fn encode_header(header: &Header) -> [u8; HEADER_SIZE] {
+	let mut bytes = [0u8; HEADER_SIZE];
+	bytes[0..8].copy_from_slice(MAGIC);
+	bytes[8..12].copy_from_slice(&VERSION.to_le_bytes());
+	bytes[12..16].copy_from_slice(&header.generation.to_le_bytes());
+	bytes[16..24].copy_from_slice(&header.index_offset.to_le_bytes());
+	bytes[24..32].copy_from_slice(&header.index_size.to_le_bytes());
+	bytes
+}
+
+fn parse_index(bytes: &[u8], file_size: u64) -> anyhow::Result<BTreeMap<String, BundleEntry>> {
+	let mut reader = ByteReader(bytes);
+	let num_entries = reader.u32()?;
+
+	let mut entries = BTreeMap::new();
+
+	for _ in 0..num_entries {
+		let offset = reader.u64()?;
+		let size = reader.u64()?;
+		let path_len = reader.u16()? as usize;
+		let path = std::str::from_utf8(reader.take(path_len)?)?;
+
+		anyhow::ensure!(offset.checked_add(size).is_some_and(|end| end <= file_size),
+			"Bundle entry '{path}' out of bounds");
+
+		entries.insert(path.to_owned(), BundleEntry { offset, size });
+	}
+
+	Ok(entries)
+}
+
+fn encode_index(entries: &BTreeMap<String, BundleEntry>) -> Vec<u8> {
+	let mut bytes = Vec::new();
+	bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+
+	for (path, entry) in entries {
+		bytes.extend_from_slice(&entry.offset.to_le_bytes());
+		bytes.extend_from_slice(&entry.size.to_le_bytes());
+		bytes.extend_from_slice(&(path.len() as u16).to_le_bytes());
+		bytes.extend_from_slice(path.as_bytes());
+	}
+
+	bytes
+}
+
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+	fn take(&mut self, size: usize) -> anyhow::Result<&'a [u8]> {
+		anyhow::ensure!(self.0.len() >= size, "Unexpected end of bundle data");
+
+		let (bytes, rest) = self.0.split_at(size);
+		self.0 = rest;
+		Ok(bytes)
+	}
+
+	fn u16(&mut self) -> anyhow::Result<u16> {
+		Ok(u16::from_le_bytes(self.take(2)?.try_into()?))
+	}
+
+	fn u32(&mut self) -> anyhow::Result<u32> {
+		Ok(u32::from_le_bytes(self.take(4)?.try_into()?))
+	}
+
+	fn u64(&mut self) -> anyhow::Result<u64> {
+		Ok(u64::from_le_bytes(self.take(8)?.try_into()?))
+	}
+}
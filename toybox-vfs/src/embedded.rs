@@ -0,0 +1,47 @@
+use std::path::Path;
+
+use crate::bundle::bundle_key;
+
+
+/// Resources compiled into the executable, e.g., by toybox's `embed-resources` feature.
+/// Mounted with [`Vfs::mount_embedded_resources`](crate::Vfs::mount_embedded_resources).
+#[derive(Debug, Copy, Clone)]
+pub struct EmbeddedResources {
+	entries: &'static [(&'static str, &'static [u8])],
+}
+
+impl EmbeddedResources {
+	/// `entries` must be sorted by path, with paths relative to the resource root and separated by '/' - the same form
+	/// as [`Bundle`](crate::Bundle) paths.
+	pub const fn new(entries: &'static [(&'static str, &'static [u8])]) -> EmbeddedResources {
+		EmbeddedResources { entries }
+	}
+
+	pub fn get(&self, virtual_path: impl AsRef<Path>) -> Option<&'static [u8]> {
+		let key = bundle_key(virtual_path.as_ref()).ok()?;
+
+		self.entries.binary_search_by_key(&key.as_str(), |(path, _)| path)
+			.ok()
+			.map(|index| self.entries[index].1)
+	}
+
+	pub fn contains(&self, virtual_path: impl AsRef<Path>) -> bool {
+		self.get(virtual_path).is_some()
+	}
+
+	pub fn paths(&self) -> impl Iterator<Item=&'static str> + '_ {
+		self.entries.iter().map(|(path, _)| *path)
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	pub fn total_size(&self) -> usize {
+		self.entries.iter().map(|(_, data)| data.len()).sum()
+	}
+}
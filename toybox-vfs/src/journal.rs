@@ -0,0 +1,283 @@
+//! Optional write-ahead journal for editors writing into the resource root.
+//!
+//! While enabled with [`Vfs::enable_journal`], the first write to each resource records its previous contents, so that
+//! everything can be put back with [`Vfs::revert_journaled_changes`]. Writes go to a temporary file that replaces the
+//! resource only once complete, so a crash mid-save can't leave a resource partially written.
+//!
+//! The journal lives in user data and survives crashes - if an editor dies without committing or reverting, the next
+//! [`Vfs::enable_journal`] picks up where it left off, so changes from the crashed session can still be reverted.
+
+use crate::{Vfs, PathKind};
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::Context;
+use tracing::instrument;
+
+
+const JOURNAL_DIRECTORY: &str = "resource_journal";
+const JOURNAL_INDEX: &str = "journal.json";
+
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct JournalEntry {
+	/// Relative to the resource root.
+	pub virtual_path: PathBuf,
+
+	/// File in the journal directory holding the contents from before the first journaled write.
+	/// None if the resource didn't exist yet, in which case reverting deletes it.
+	backup: Option<String>,
+
+	/// Set while a write is in flight, so that an interrupted write can be cleaned up.
+	pending: bool,
+}
+
+impl JournalEntry {
+	/// Whether the resource was created rather than modified.
+	pub fn is_new_file(&self) -> bool {
+		self.backup.is_none()
+	}
+}
+
+
+pub struct Journal {
+	directory: PathBuf,
+	entries: Vec<JournalEntry>,
+	next_backup_id: u32,
+}
+
+impl Journal {
+	pub fn entries(&self) -> &[JournalEntry] {
+		&self.entries
+	}
+
+	#[instrument(skip_all, name="vfs Journal::open")]
+	fn open(directory: PathBuf, resource_root: &Path) -> anyhow::Result<Journal> {
+		std::fs::create_dir_all(&directory)
+			.with_context(|| format!("Creating journal directory '{}'", directory.display()))?;
+
+		let index_path = directory.join(JOURNAL_INDEX);
+		let mut entries: Vec<JournalEntry> = match std::fs::read(&index_path) {
+			Ok(data) => serde_json::from_slice(&data)
+				.with_context(|| format!("Parsing journal '{}'", index_path.display()))?,
+
+			Err(error) if error.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+			Err(error) => return Err(error).with_context(|| format!("Reading journal '{}'", index_path.display())),
+		};
+
+		if !entries.is_empty() {
+			log::warn!("Resource journal has {} uncommitted changes from a previous session", entries.len());
+		}
+
+		// Writes are only ever renamed into place, so an interrupted write leaves the resource untouched - only the
+		// temporary file needs cleaning up.
+		for entry in entries.iter_mut().filter(|entry| entry.pending) {
+			let temp_path = temp_path_for(&resource_root.join(&entry.virtual_path));
+			log::warn!("Cleaning up interrupted write to '{}'", entry.virtual_path.display());
+
+			if let Err(error) = std::fs::remove_file(&temp_path)
+				&& error.kind() != std::io::ErrorKind::NotFound
+			{
+				log::error!("Failed to remove '{}': {error}", temp_path.display());
+			}
+
+			entry.pending = false;
+		}
+
+		let next_backup_id = entries.len() as u32;
+		let journal = Journal { directory, entries, next_backup_id };
+		journal.save_index()?;
+		Ok(journal)
+	}
+
+	#[instrument(skip_all, name="vfs Journal::write")]
+	fn write(&mut self, virtual_path: &Path, path: &Path, data: &[u8]) -> anyhow::Result<()> {
+		let entry_index = match self.entries.iter().position(|entry| entry.virtual_path == virtual_path) {
+			Some(index) => index,
+			None => {
+				let backup = match path.exists() {
+					true => Some(self.backup(path)?),
+					false => None,
+				};
+
+				self.entries.push(JournalEntry {
+					virtual_path: virtual_path.to_owned(),
+					backup,
+					pending: false,
+				});
+
+				self.entries.len() - 1
+			}
+		};
+
+		self.entries[entry_index].pending = true;
+		self.save_index()?;
+
+		write_atomic(path, data)?;
+
+		self.entries[entry_index].pending = false;
+		self.save_index()
+	}
+
+	fn backup(&mut self, path: &Path) -> anyhow::Result<String> {
+		// Ids may collide with backups left over from previous sessions, so skip any that are taken.
+		let backup_name = loop {
+			let name = format!("{}.bak", self.next_backup_id);
+			self.next_backup_id += 1;
+
+			if !self.entries.iter().any(|entry| entry.backup.as_deref() == Some(name.as_str())) {
+				break name
+			}
+		};
+
+		let backup_path = self.directory.join(&backup_name);
+		std::fs::copy(path, &backup_path)
+			.with_context(|| format!("Backing up '{}'", path.display()))?;
+
+		Ok(backup_name)
+	}
+
+	fn save_index(&self) -> anyhow::Result<()> {
+		let data = serde_json::to_vec_pretty(&self.entries)?;
+		write_atomic(&self.directory.join(JOURNAL_INDEX), &data)
+			.context("Saving resource journal")
+	}
+
+	/// Forget all recorded changes and delete backups.
+	fn clear(&mut self) -> anyhow::Result<()> {
+		for backup in self.entries.drain(..).filter_map(|entry| entry.backup) {
+			let _ = std::fs::remove_file(self.directory.join(backup));
+		}
+
+		self.next_backup_id = 0;
+		self.save_index()
+	}
+}
+
+
+impl Vfs {
+	/// Start journaling writes to the resource root. Picks up any journal left behind by a previous session.
+	/// See [`crate::journal`].
+	pub fn enable_journal(&mut self) -> anyhow::Result<()> {
+		if self.journal.is_some() {
+			return Ok(())
+		}
+
+		let directory = self.user_data_root.join(JOURNAL_DIRECTORY);
+		let journal = Journal::open(directory, &self.resource_root)?;
+
+		log::info!("Resource journal enabled");
+		self.journal = Some(Mutex::new(journal));
+		Ok(())
+	}
+
+	pub fn is_journal_enabled(&self) -> bool {
+		self.journal.is_some()
+	}
+
+	/// Resources changed since the journal was last committed or reverted.
+	pub fn journaled_changes(&self) -> Vec<JournalEntry> {
+		match &self.journal {
+			Some(journal) => journal.lock().unwrap().entries().to_vec(),
+			None => Vec::new(),
+		}
+	}
+
+	/// Keep all journaled changes, and start recording again from the current state.
+	#[instrument(skip_all, name="vfs Vfs::commit_journal")]
+	pub fn commit_journal(&self) -> anyhow::Result<()> {
+		let Some(journal) = &self.journal else { return Ok(()) };
+		journal.lock().unwrap().clear()
+	}
+
+	/// Restore every journaled resource to how it was before it was first written, deleting any that were created.
+	/// Returns the number of resources restored. Resources that fail to restore are kept in the journal.
+	#[instrument(skip_all, name="vfs Vfs::revert_journaled_changes")]
+	pub fn revert_journaled_changes(&self) -> anyhow::Result<usize> {
+		let Some(journal) = &self.journal else { return Ok(0) };
+		let mut journal = journal.lock().unwrap();
+
+		let mut failed = Vec::new();
+		let mut num_restored = 0;
+
+		for entry in std::mem::take(&mut journal.entries) {
+			let path = self.resource_root.join(&entry.virtual_path);
+
+			let result = match &entry.backup {
+				Some(backup) => std::fs::read(journal.directory.join(backup))
+					.map_err(anyhow::Error::from)
+					.and_then(|data| write_atomic(&path, &data)),
+
+				None => match std::fs::remove_file(&path) {
+					Err(error) if error.kind() != std::io::ErrorKind::NotFound => Err(error.into()),
+					_ => Ok(()),
+				}
+			};
+
+			match result {
+				Ok(()) => {
+					log::info!("Reverted '{}'", entry.virtual_path.display());
+					num_restored += 1;
+
+					if let Some(backup) = &entry.backup {
+						let _ = std::fs::remove_file(journal.directory.join(backup));
+					}
+				}
+
+				Err(error) => {
+					log::error!("Failed to revert '{}': {error:?}", entry.virtual_path.display());
+					failed.push(entry);
+				}
+			}
+		}
+
+		journal.entries = failed;
+		journal.save_index()?;
+
+		Ok(num_restored)
+	}
+
+	/// Write through the journal if enabled. Returns false if the write should go straight to disk instead.
+	pub(crate) fn try_journaled_write(&self, kind: PathKind, virtual_path: &Path, path: &Path, data: &[u8]) -> anyhow::Result<bool> {
+		if kind != PathKind::Resource {
+			return Ok(false)
+		}
+
+		let Some(journal) = &self.journal else {
+			return Ok(false)
+		};
+
+		let relative_path = path.strip_prefix(&self.resource_root)
+			.with_context(|| format!("Resolving journal path for '{}'", virtual_path.display()))?;
+
+		journal.lock().unwrap().write(relative_path, path, data)?;
+		Ok(true)
+	}
+}
+
+
+fn temp_path_for(path: &Path) -> PathBuf {
+	let mut file_name = path.file_name().unwrap_or_default().to_owned();
+	file_name.push(".journal-tmp");
+	path.with_file_name(file_name)
+}
+
+/// Write to a temporary file and rename it into place, so `path` is either fully replaced or untouched.
+fn write_atomic(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+	use std::io::Write;
+
+	if let Some(parent_path) = path.parent() {
+		std::fs::create_dir_all(parent_path)?;
+	}
+
+	let temp_path = temp_path_for(path);
+
+	let mut file = std::fs::File::create(&temp_path)
+		.with_context(|| format!("Creating '{}'", temp_path.display()))?;
+	file.write_all(data)?;
+	file.sync_all()?;
+	drop(file);
+
+	std::fs::rename(&temp_path, path)
+		.with_context(|| format!("Replacing '{}'", path.display()))
+}
@@ -4,6 +4,29 @@ use std::path::{Path, PathBuf};
 use anyhow::Context;
 use tracing::instrument;
 
+mod watcher;
+
+#[cfg(test)]
+mod test;
+
+pub mod bundle;
+pub use bundle::{Bundle, BundleWriter};
+
+pub mod embedded;
+pub use embedded::EmbeddedResources;
+
+pub mod manifest;
+pub use manifest::{Manifest, ManifestDiff, VerificationReport};
+
+pub mod savegame;
+pub use savegame::SaveSchema;
+
+pub mod journal;
+pub use journal::{Journal, JournalEntry};
+
+/// Version of this crate, as reported by `toybox::build_info`.
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
 pub mod prelude {}
 
 
@@ -16,12 +39,70 @@ pub enum PathKind {
 }
 
 
+/// Artificial delay added to every read, for testing loading screens and streaming on fast machines.
+/// See [`Vfs::set_slow_io_simulation`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SlowIoSimulation {
+	/// Added to every read regardless of size.
+	pub latency: std::time::Duration,
+
+	/// Reads are delayed further as if limited to this throughput. Zero means unlimited.
+	pub bytes_per_second: u64,
+}
+
+impl SlowIoSimulation {
+	pub fn delay_for(&self, byte_size: usize) -> std::time::Duration {
+		let transfer_time = match self.bytes_per_second {
+			0 => std::time::Duration::ZERO,
+			rate => std::time::Duration::from_secs_f64(byte_size as f64 / rate as f64),
+		};
+
+		self.latency + transfer_time
+	}
+}
+
+
+/// Where [`Vfs::user_data_root`] was resolved to, and why.
+#[derive(Copy, Clone, Debug, Hash, PartialEq, Eq)]
+pub enum UserDataLocation {
+	/// The platform data directory, e.g. `%APPDATA%/toybox/<app name>` or `~/.local/share/toybox/<app name>`.
+	Platform,
+
+	/// A `user_data` directory next to the executable.
+	/// Enabled by passing `--portable`, or by placing a `portable.txt` file next to the executable.
+	Portable,
+
+	/// Explicitly set by passing `vfs.user_data_root=<path>`.
+	Override,
+
+	/// A directory synced by a storefront's cloud saves. See [`Vfs::set_platform_user_data_root`].
+	PlatformCloud,
+}
+
+
 pub struct Vfs {
 	// Game data - immutable in release, editable by editors
 	resource_root: Box<Path>,
 
 	// All inter-session data - game saves, user config, etc
 	user_data_root: Box<Path>,
+	user_data_location: UserDataLocation,
+
+	resource_watcher: Option<watcher::ResourceWatcher>,
+	changed_resource_paths: Vec<PathBuf>,
+
+	// Searched in reverse order before the resource root
+	bundles: Vec<Bundle>,
+
+	// Searched after the resource root
+	embedded_resources: Option<EmbeddedResources>,
+
+	verification_report: Option<VerificationReport>,
+
+	slow_io_simulation: Option<SlowIoSimulation>,
+
+	// Records resource writes so they can be reverted - see Vfs::enable_journal
+	journal: Option<std::sync::Mutex<Journal>>,
 }
 
 impl Vfs {
@@ -31,28 +112,216 @@ impl Vfs {
 			.context("Can't find resource directory")?
 			.into_boxed_path();
 
-		let mut user_data_root = dirs::data_dir()
-			.context("Can't find local data directory")?;
-
-		user_data_root.push("toybox");
-		user_data_root.push(app_name);
-
+		let (user_data_root, user_data_location) = find_user_data_root(app_name)?;
 		let user_data_root = user_data_root.into_boxed_path();
 
 		log::info!("Resource Root Path: {}", resource_root.display());
-		log::info!("Data Root Path: {}", user_data_root.display());
+		log::info!("Data Root Path: {} ({user_data_location:?})", user_data_root.display());
+
+		let resource_watcher = watcher::ResourceWatcher::new(&resource_root)
+			.inspect_err(|error| log::warn!("Failed to watch resource directory - resources won't be reloaded on change: {error}"))
+			.ok();
+
+		let mut vfs = Vfs {
+			resource_root,
+			user_data_root,
+			user_data_location,
+
+			resource_watcher,
+			changed_resource_paths: Vec::new(),
+
+			bundles: Vec::new(),
+			embedded_resources: None,
+			verification_report: None,
+			slow_io_simulation: None,
+			journal: None,
+		};
+
+		if std::env::args().skip(1).any(|arg| arg == "--verify-resources") {
+			match vfs.verify_resources() {
+				Ok(report) => vfs.verification_report = Some(report),
+				Err(error) => log::error!("Failed to verify resources: {error:?}"),
+			}
+		}
+
+		Ok(vfs)
+	}
+
+	/// Create a Vfs with explicit roots and no resource watching, e.g., for tests and tools.
+	pub fn with_roots(resource_root: impl Into<PathBuf>, user_data_root: impl Into<PathBuf>) -> Vfs {
+		Vfs {
+			resource_root: resource_root.into().into_boxed_path(),
+			user_data_root: user_data_root.into().into_boxed_path(),
+			user_data_location: UserDataLocation::Override,
+
+			resource_watcher: None,
+			changed_resource_paths: Vec::new(),
+
+			bundles: Vec::new(),
+			embedded_resources: None,
+			verification_report: None,
+			slow_io_simulation: None,
+			journal: None,
+		}
+	}
+
+	/// Like [`Vfs::new`], but with `embedded` mounted, and without requiring a resource directory to exist.
+	/// If one is found, loose files in it take priority over embedded resources - so resources can still be edited
+	/// and reloaded during development.
+	#[instrument(skip_all, name="vfs init")]
+	pub fn with_embedded_resources(app_name: &str, embedded: EmbeddedResources) -> anyhow::Result<Vfs> {
+		let mut vfs = match find_resource_folder() {
+			Ok(_) => Vfs::new(app_name)?,
+
+			Err(error) => {
+				log::info!("No resource directory found, using embedded resources only: {error}");
+
+				let exe_path = std::env::current_exe()?;
+				let exe_dir = exe_path.parent()
+					.ok_or_else(|| anyhow::format_err!("Executable path invalid '{}'", exe_path.display()))?;
+
+				let (user_data_root, user_data_location) = find_user_data_root(app_name)?;
+				log::info!("Data Root Path: {} ({user_data_location:?})", user_data_root.display());
+
+				let mut vfs = Vfs::with_roots(exe_dir.join("resource"), user_data_root);
+				vfs.user_data_location = user_data_location;
+				vfs
+			}
+		};
+
+		vfs.mount_embedded_resources(embedded);
+		Ok(vfs)
+	}
+
+	/// Delay all subsequent reads as if from a slow disk, or stop delaying with `None`.
+	/// Reads block the calling thread for the full delay.
+	pub fn set_slow_io_simulation(&mut self, simulation: Option<SlowIoSimulation>) {
+		if simulation != self.slow_io_simulation {
+			log::info!("Slow IO simulation: {simulation:?}");
+		}
+
+		self.slow_io_simulation = simulation;
+	}
+
+	pub fn slow_io_simulation(&self) -> Option<SlowIoSimulation> {
+		self.slow_io_simulation
+	}
+
+	/// Collects resource files changed on disk since the last call. Should be called once per frame.
+	pub fn update(&mut self) {
+		self.changed_resource_paths.clear();
+
+		if let Some(watcher) = &self.resource_watcher {
+			watcher.drain_into(&mut self.changed_resource_paths);
+		}
+
+		for bundle in self.bundles.iter_mut() {
+			match bundle.refresh() {
+				Ok(changed_keys) => {
+					self.changed_resource_paths.extend(changed_keys.iter().map(|key| self.resource_root.join(key)));
+				}
+
+				Err(error) => log::error!("Failed to refresh bundle '{}': {error}", bundle.path().display()),
+			}
+		}
+	}
 
-		Ok(Vfs { resource_root, user_data_root })
+	/// Mount a bundle created with [`BundleWriter`]. Resources in mounted bundles take priority over loose files
+	/// in the resource root, and bundles mounted later take priority over those mounted earlier.
+	/// Patches made to the bundle while mounted are picked up in [`Vfs::update`].
+	pub fn mount_bundle(&mut self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+		let bundle = Bundle::open(path.as_ref())?;
+		log::info!("Mounted bundle '{}' ({} entries)", bundle.path().display(), bundle.paths().count());
+		self.bundles.push(bundle);
+		Ok(())
+	}
+
+	/// Mount resources compiled into the executable. Unlike bundles, loose files in the resource root take priority
+	/// over embedded resources. Replaces any previously mounted embedded resources.
+	pub fn mount_embedded_resources(&mut self, embedded: EmbeddedResources) {
+		log::info!("Mounted {} embedded resources ({}KiB)", embedded.len(), embedded.total_size() >> 10);
+		self.embedded_resources = Some(embedded);
+	}
+
+	pub fn embedded_resources(&self) -> Option<&EmbeddedResources> {
+		self.embedded_resources.as_ref()
+	}
+
+	/// The result of verifying resources on startup, if `--verify-resources` was passed.
+	pub fn verification_report(&self) -> Option<&VerificationReport> {
+		self.verification_report.as_ref()
+	}
+
+	pub fn bundles(&self) -> &[Bundle] {
+		&self.bundles
+	}
+
+	fn find_in_bundles(&self, kind: PathKind, virtual_path: &Path) -> Option<&[u8]> {
+		if kind != PathKind::Resource {
+			return None
+		}
+
+		self.bundles.iter().rev()
+			.find_map(|bundle| bundle.get(virtual_path))
+	}
+
+	/// Only returns data for paths that don't exist on disk, since loose files take priority.
+	fn find_embedded(&self, kind: PathKind, virtual_path: &Path, resolved_path: &Path) -> Option<&'static [u8]> {
+		if kind != PathKind::Resource || resolved_path.exists() {
+			return None
+		}
+
+		self.embedded_resources.as_ref()?.get(virtual_path)
+	}
+
+	/// Canonical paths of resource files that changed on disk before the last call to [`Vfs::update`].
+	pub fn changed_resource_paths(&self) -> &[PathBuf] {
+		&self.changed_resource_paths
+	}
+
+	/// Whether the resource at `virtual_path` changed on disk before the last call to [`Vfs::update`].
+	pub fn resource_changed(&self, virtual_path: impl AsRef<Path>) -> bool {
+		if self.changed_resource_paths.is_empty() {
+			return false
+		}
+
+		// Changed paths are canonicalized by the watcher.
+		match self.resolve_path(PathKind::Resource, virtual_path) {
+			Ok(path) => self.changed_resource_paths.contains(&watcher::canonicalize_lossy(&path)),
+			Err(_) => false,
+		}
 	}
 
 	pub fn resource_root(&self) -> &Path {
 		&self.resource_root
 	}
 
+	/// Where saves and config actually live. Suitable for displaying to the user.
 	pub fn user_data_root(&self) -> &Path {
 		&self.user_data_root
 	}
 
+	pub fn user_data_location(&self) -> UserDataLocation {
+		self.user_data_location
+	}
+
+	/// Redirect user data to a directory managed by a storefront. Ignored if the user data root was explicitly
+	/// overridden or portable mode is enabled. Should be called before anything is loaded from user data.
+	pub fn set_platform_user_data_root(&mut self, path: impl Into<PathBuf>) {
+		if self.user_data_location != UserDataLocation::Platform {
+			return
+		}
+
+		self.user_data_root = path.into().into_boxed_path();
+		self.user_data_location = UserDataLocation::PlatformCloud;
+
+		log::info!("Data Root Path: {} ({:?})", self.user_data_root.display(), self.user_data_location);
+	}
+
+	pub fn is_portable(&self) -> bool {
+		self.user_data_location == UserDataLocation::Portable
+	}
+
 	fn resolve_root(&self, kind: PathKind) -> &Path {
 		match kind {
 			PathKind::Resource => &self.resource_root,
@@ -70,30 +339,85 @@ impl Vfs {
 		Ok(self.resolve_root(kind).join(clean_path))
 	}
 
+	/// The inverse of [`Vfs::resolve_path`] - map a real path, e.g., from a file dialog, back to a virtual path.
+	/// Returns None if the path isn't within the resource or user data roots.
+	pub fn to_virtual_path(&self, path: impl AsRef<Path>) -> Option<(PathKind, PathBuf)> {
+		let path = path.as_ref();
+		let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+
+		[PathKind::UserData, PathKind::Resource].into_iter()
+			.find_map(|kind| {
+				let root = self.resolve_root(kind);
+				let root = root.canonicalize().unwrap_or_else(|_| root.to_owned());
+
+				let relative = path.strip_prefix(&root).ok()?;
+				Some((kind, relative.to_owned()))
+			})
+	}
+
 	pub fn path_exists(&self, kind: PathKind, virtual_path: impl AsRef<Path>) -> bool {
+		if self.find_in_bundles(kind, virtual_path.as_ref()).is_some() {
+			return true
+		}
+
 		// TODO(pat.m): sketchy as hell for actual FS operations - but we'll leave it for now
-		match self.resolve_path(kind, virtual_path) {
-			Ok(path) => path.exists(),
+		match self.resolve_path(kind, virtual_path.as_ref()) {
+			Ok(path) => path.exists() || self.find_embedded(kind, virtual_path.as_ref(), &path).is_some(),
 			Err(_) => false,
 		}
 	}
 
 	#[instrument(skip_all)]
 	pub fn load_data(&self, kind: PathKind, virtual_path: impl AsRef<Path>) -> anyhow::Result<Vec<u8>> {
-		let path = self.resolve_path(kind, virtual_path)?;
-		std::fs::read(&path).map_err(Into::into)
+		if let Some(data) = self.find_in_bundles(kind, virtual_path.as_ref()) {
+			self.simulate_slow_read(data.len());
+			return Ok(data.to_vec())
+		}
+
+		let path = self.resolve_path(kind, virtual_path.as_ref())?;
+		if let Some(data) = self.find_embedded(kind, virtual_path.as_ref(), &path) {
+			self.simulate_slow_read(data.len());
+			return Ok(data.to_vec())
+		}
+
+		let data = std::fs::read(&path)?;
+		self.simulate_slow_read(data.len());
+		Ok(data)
 	}
 
 	#[instrument(skip_all)]
 	pub fn load_string(&self, kind: PathKind, virtual_path: impl AsRef<Path>) -> anyhow::Result<String> {
-		let path = self.resolve_path(kind, virtual_path)?;
-		std::fs::read_to_string(&path).map_err(Into::into)
+		if let Some(data) = self.find_in_bundles(kind, virtual_path.as_ref()) {
+			self.simulate_slow_read(data.len());
+			return String::from_utf8(data.to_vec()).map_err(Into::into)
+		}
+
+		let path = self.resolve_path(kind, virtual_path.as_ref())?;
+		if let Some(data) = self.find_embedded(kind, virtual_path.as_ref(), &path) {
+			self.simulate_slow_read(data.len());
+			return String::from_utf8(data.to_vec()).map_err(Into::into)
+		}
+
+		let string = std::fs::read_to_string(&path)?;
+		self.simulate_slow_read(string.len());
+		Ok(string)
+	}
+
+	fn simulate_slow_read(&self, byte_size: usize) {
+		if let Some(simulation) = &self.slow_io_simulation {
+			let _span = tracing::info_span!("simulated slow read").entered();
+			std::thread::sleep(simulation.delay_for(byte_size));
+		}
 	}
 
 	#[instrument(skip_all)]
 	pub fn save_data(&self, kind: PathKind, virtual_path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> anyhow::Result<()> {
 		// TODO(pat.m): assert path kind is writable
-		let path = self.resolve_path(kind, virtual_path)?;
+		let path = self.resolve_path(kind, virtual_path.as_ref())?;
+
+		if self.try_journaled_write(kind, virtual_path.as_ref(), &path, data.as_ref())? {
+			return Ok(())
+		}
 
 		if let Some(parent_path) = path.parent() {
 			std::fs::create_dir_all(parent_path)?;
@@ -167,6 +491,42 @@ fn clean_virtual_path(mut components: std::path::Components<'_>) -> anyhow::Resu
 
 
 
+#[instrument]
+fn find_user_data_root(app_name: &str) -> anyhow::Result<(PathBuf, UserDataLocation)> {
+	// NOTE: this can't go through toybox-cfg since the config itself lives in the user data root.
+	let mut portable_requested = false;
+
+	for arg in std::env::args().skip(1) {
+		if arg == "--portable" {
+			portable_requested = true;
+
+		} else if let Some((key, value)) = arg.split_once('=')
+			&& key.trim() == "vfs.user_data_root"
+		{
+			let path = std::path::absolute(value.trim())
+				.with_context(|| format!("Invalid user data root override '{value}'"))?;
+
+			return Ok((path, UserDataLocation::Override))
+		}
+	}
+
+	let exe_path = std::env::current_exe()?;
+	let exe_dir = exe_path.parent()
+		.ok_or_else(|| anyhow::format_err!("Executable path invalid '{}'", exe_path.display()))?;
+
+	if portable_requested || exe_dir.join("portable.txt").exists() {
+		return Ok((exe_dir.join("user_data"), UserDataLocation::Portable))
+	}
+
+	let mut user_data_root = dirs::data_dir()
+		.context("Can't find local data directory")?;
+
+	user_data_root.push("toybox");
+	user_data_root.push(app_name);
+
+	Ok((user_data_root, UserDataLocation::Platform))
+}
+
 #[instrument]
 fn find_resource_folder() -> anyhow::Result<PathBuf> {
 	let mut dirs_scanned = Vec::new();
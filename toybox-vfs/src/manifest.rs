@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use anyhow::Context;
+use tracing::instrument;
+
+use crate::{Vfs, PathKind};
+
+
+/// Default location of the manifest checked by [`Vfs::verify_resources`], relative to the resource root.
+pub const DEFAULT_MANIFEST_PATH: &str = "manifest.json";
+
+
+/// Content hashes of a set of resources, keyed by virtual path with '/' separators.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Manifest {
+	pub entries: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ManifestEntry {
+	pub size: u64,
+
+	/// Hex encoded blake3 hash of the resource contents.
+	pub hash: String,
+}
+
+impl ManifestEntry {
+	pub fn from_data(data: &[u8]) -> ManifestEntry {
+		ManifestEntry {
+			size: data.len() as u64,
+			hash: blake3::hash(data).to_hex().to_string(),
+		}
+	}
+}
+
+impl Manifest {
+	/// Everything that would need to be fetched or reloaded to go from `self` to `other`.
+	pub fn diff(&self, other: &Manifest) -> ManifestDiff {
+		let mut diff = ManifestDiff::default();
+
+		for (path, entry) in &other.entries {
+			match self.entries.get(path) {
+				None => diff.added.push(path.clone()),
+				Some(old_entry) if old_entry != entry => diff.changed.push(path.clone()),
+				_ => {}
+			}
+		}
+
+		diff.removed = self.entries.keys()
+			.filter(|path| !other.entries.contains_key(*path))
+			.cloned()
+			.collect();
+
+		diff
+	}
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ManifestDiff {
+	pub added: Vec<String>,
+	pub removed: Vec<String>,
+	pub changed: Vec<String>,
+}
+
+impl ManifestDiff {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+
+/// Result of checking resources against a [`Manifest`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerificationReport {
+	pub missing: Vec<String>,
+	pub corrupt: Vec<String>,
+	pub num_verified: usize,
+}
+
+impl VerificationReport {
+	pub fn is_ok(&self) -> bool {
+		self.missing.is_empty() && self.corrupt.is_empty()
+	}
+}
+
+
+impl Vfs {
+	/// Hash every resource visible through the vfs - loose files under the resource root as well as anything
+	/// in mounted bundles.
+	#[instrument(skip_all, name="vfs build_resource_manifest")]
+	pub fn build_resource_manifest(&self) -> anyhow::Result<Manifest> {
+		let mut manifest = Manifest::default();
+
+		let mut to_visit = vec![self.resource_root().to_owned()];
+
+		while let Some(dir) = to_visit.pop() {
+			for dir_entry in dir.read_dir()? {
+				let path = dir_entry?.path();
+
+				if path.is_dir() {
+					to_visit.push(path);
+					continue
+				}
+
+				let relative_path = path.strip_prefix(self.resource_root())?;
+
+				// Skip the manifest itself, and anything we can't address through the vfs anyway.
+				let Ok(key) = crate::bundle::bundle_key(relative_path) else { continue };
+				if key == DEFAULT_MANIFEST_PATH {
+					continue
+				}
+
+				let data = std::fs::read(&path)
+					.with_context(|| format!("Reading '{}'", path.display()))?;
+
+				manifest.entries.insert(key, ManifestEntry::from_data(&data));
+			}
+		}
+
+		for bundle in self.bundles() {
+			for key in bundle.paths() {
+				if let Some(data) = bundle.get(key) {
+					manifest.entries.insert(key.to_owned(), ManifestEntry::from_data(data));
+				}
+			}
+		}
+
+		Ok(manifest)
+	}
+
+	/// Check that every resource listed in `manifest` exists and matches its hash.
+	#[instrument(skip_all, name="vfs verify_resources_against")]
+	pub fn verify_resources_against(&self, manifest: &Manifest) -> VerificationReport {
+		let mut report = VerificationReport::default();
+
+		for (path, entry) in &manifest.entries {
+			match self.load_data(PathKind::Resource, path) {
+				Ok(data) if ManifestEntry::from_data(&data) == *entry => report.num_verified += 1,
+				Ok(_) => report.corrupt.push(path.clone()),
+				Err(_) => report.missing.push(path.clone()),
+			}
+		}
+
+		report
+	}
+
+	/// Check resources against the manifest at [`DEFAULT_MANIFEST_PATH`], logging any problems.
+	/// Happens automatically on startup if `--verify-resources` is passed.
+	pub fn verify_resources(&self) -> anyhow::Result<VerificationReport> {
+		let manifest: Manifest = self.load_json_resource(DEFAULT_MANIFEST_PATH)
+			.context("Loading resource manifest")?;
+
+		let report = self.verify_resources_against(&manifest);
+
+		for path in &report.missing {
+			log::error!("Missing resource '{path}'");
+		}
+
+		for path in &report.corrupt {
+			log::error!("Corrupt resource '{path}'");
+		}
+
+		log::info!("Verified {}/{} resources", report.num_verified, manifest.entries.len());
+
+		Ok(report)
+	}
+
+	/// Build a manifest of the current resources and write it to [`DEFAULT_MANIFEST_PATH`].
+	pub fn write_resource_manifest(&self) -> anyhow::Result<Manifest> {
+		let manifest = self.build_resource_manifest()?;
+		self.save_json_resource(Path::new(DEFAULT_MANIFEST_PATH), &manifest)?;
+		Ok(manifest)
+	}
+}
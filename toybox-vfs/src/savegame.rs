@@ -0,0 +1,162 @@
+//! Versioned save data. Saves are stored as json alongside the schema version they were written with, and are
+//! migrated forward on load by a chain of registered migration functions.
+//!
+//! ```ignore
+//! let schema = SaveSchema::new(3)
+//! 	.migration(1, |mut data| { data["inventory"] = json!([]); Ok(data) })
+//! 	.migration(2, |mut data| { data["player"]["hp"] = data["hp"].take(); Ok(data) });
+//!
+//! let save: Option<MySave> = vfs.load_save(&schema, "saves/slot0.json")?;
+//! ```
+
+use crate::{Vfs, PathKind};
+
+use std::path::Path;
+use anyhow::Context;
+use serde_json::Value;
+use tracing::instrument;
+
+
+type MigrationFn = Box<dyn Fn(Value) -> anyhow::Result<Value>>;
+
+
+/// Describes the current version of a save format, and how to get there from older versions.
+pub struct SaveSchema {
+	current_version: u32,
+
+	/// Indexed by the version being migrated from, minus one.
+	migrations: Vec<Option<MigrationFn>>,
+}
+
+impl SaveSchema {
+	/// Versions start at 1.
+	pub fn new(current_version: u32) -> SaveSchema {
+		assert!(current_version > 0, "Save versions start at 1");
+
+		SaveSchema {
+			current_version,
+			migrations: (1..current_version).map(|_| None).collect(),
+		}
+	}
+
+	/// Register a function converting save data from `from_version` to `from_version + 1`.
+	pub fn migration(mut self, from_version: u32, migrate: impl Fn(Value) -> anyhow::Result<Value> + 'static) -> SaveSchema {
+		assert!(from_version > 0 && from_version < self.current_version,
+			"Migration from version {from_version} is outside of schema range 1..{}", self.current_version);
+
+		self.migrations[from_version as usize - 1] = Some(Box::new(migrate));
+		self
+	}
+
+	pub fn current_version(&self) -> u32 {
+		self.current_version
+	}
+
+	/// Apply migrations to bring `data` from `version` up to the current version.
+	pub fn migrate(&self, version: u32, mut data: Value) -> anyhow::Result<Value> {
+		anyhow::ensure!(version > 0, "Invalid save version 0");
+		anyhow::ensure!(version <= self.current_version,
+			"Save version {version} is newer than the current version {}", self.current_version);
+
+		for from_version in version..self.current_version {
+			let migrate = self.migrations[from_version as usize - 1].as_ref()
+				.ok_or_else(|| anyhow::format_err!("No migration registered from save version {from_version}"))?;
+
+			data = migrate(data)
+				.with_context(|| format!("Migrating save from version {from_version} to {}", from_version + 1))?;
+		}
+
+		Ok(data)
+	}
+}
+
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SaveEnvelope {
+	version: u32,
+	data: Value,
+}
+
+
+impl Vfs {
+	/// Write `data` to `virtual_path` in user data, tagged with the current version of `schema`.
+	/// The previous save is only replaced once the new one has been written in full.
+	#[instrument(skip_all)]
+	pub fn store_save<T>(&self, schema: &SaveSchema, virtual_path: impl AsRef<Path>, data: &T) -> anyhow::Result<()>
+		where T: serde::Serialize
+	{
+		let envelope = SaveEnvelope {
+			version: schema.current_version,
+			data: serde_json::to_value(data)?,
+		};
+
+		let encoded = match cfg!(debug_assertions) {
+			true => serde_json::to_vec_pretty(&envelope)?,
+			false => serde_json::to_vec(&envelope)?,
+		};
+
+		let virtual_path = virtual_path.as_ref();
+		let temp_path = virtual_path.with_extension("tmp");
+		self.save_data(PathKind::UserData, &temp_path, encoded)?;
+
+		let path = self.resolve_path(PathKind::UserData, virtual_path)?;
+		let temp_path = self.resolve_path(PathKind::UserData, &temp_path)?;
+		std::fs::rename(&temp_path, &path)
+			.with_context(|| format!("Replacing save '{}'", path.display()))
+	}
+
+	/// Load and migrate a save written by [`Vfs::store_save`]. Returns `None` if the save doesn't exist.
+	///
+	/// Saves that can't be parsed or deserialized are moved aside to `<name>.quarantine-<timestamp>` so that they
+	/// aren't overwritten by a fresh save, and an error is returned. Saves from newer versions, or that can't be
+	/// migrated, are left in place - a newer build may still be able to load them.
+	#[instrument(skip_all)]
+	pub fn load_save<T>(&self, schema: &SaveSchema, virtual_path: impl AsRef<Path>) -> anyhow::Result<Option<T>>
+		where T: for<'a> serde::Deserialize<'a>
+	{
+		let path = self.resolve_path(PathKind::UserData, virtual_path.as_ref())?;
+
+		let data = match self.load_data(PathKind::UserData, virtual_path.as_ref()) {
+			Ok(data) => data,
+			Err(error) if is_not_found(&error) => return Ok(None),
+			Err(error) => return Err(error.context(format!("Reading save '{}'", path.display()))),
+		};
+
+		let envelope = match serde_json::from_slice::<SaveEnvelope>(&data) {
+			Ok(envelope) => envelope,
+			Err(error) => return Err(quarantine_save(&path, error.into())),
+		};
+
+		let data = schema.migrate(envelope.version, envelope.data)
+			.with_context(|| format!("Loading save '{}'", path.display()))?;
+
+		match serde_json::from_value(data) {
+			Ok(save) => Ok(Some(save)),
+			Err(error) => Err(quarantine_save(&path, error.into())),
+		}
+	}
+}
+
+
+fn is_not_found(error: &anyhow::Error) -> bool {
+	error.downcast_ref::<std::io::Error>()
+		.is_some_and(|error| error.kind() == std::io::ErrorKind::NotFound)
+}
+
+/// Moves an unreadable save aside, and returns the error to report.
+fn quarantine_save(path: &Path, error: anyhow::Error) -> anyhow::Error {
+	let timestamp = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_secs());
+
+	let mut file_name = path.file_name().unwrap_or_default().to_owned();
+	file_name.push(format!(".quarantine-{timestamp}"));
+
+	let quarantine_path = path.with_file_name(file_name);
+	if let Err(rename_error) = std::fs::rename(path, &quarantine_path) {
+		return error.context(format!("Loading save '{}' (failed to quarantine: {rename_error})", path.display()))
+	}
+
+	log::error!("Failed to load save '{}', moved to '{}': {error:?}", path.display(), quarantine_path.display());
+	error.context(format!("Loading save '{}'", path.display()))
+}
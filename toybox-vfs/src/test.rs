@@ -0,0 +1,87 @@
+use super::*;
+use serde_json::json;
+
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SaveV3 {
+	name: String,
+	hp: u32,
+	inventory: Vec<String>,
+}
+
+fn test_vfs(name: &str) -> (Vfs, PathBuf) {
+	let root = std::env::temp_dir().join(format!("toybox-vfs-test-{name}-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&root);
+	std::fs::create_dir_all(&root).unwrap();
+
+	(Vfs::with_roots(&root, &root), root)
+}
+
+fn schema_v3() -> SaveSchema {
+	SaveSchema::new(3)
+		.migration(1, |mut data| { data["hp"] = data["health"].take(); Ok(data) })
+		.migration(2, |mut data| { data["inventory"] = json!([]); Ok(data) })
+}
+
+
+#[test]
+fn save_migrates_through_every_version() {
+	let (vfs, root) = test_vfs("migrate");
+
+	let v1 = json!({ "version": 1, "data": { "name": "pat", "health": 7 } });
+	std::fs::write(root.join("save.json"), serde_json::to_vec(&v1).unwrap()).unwrap();
+
+	let save: Option<SaveV3> = vfs.load_save(&schema_v3(), "save.json").unwrap();
+	assert_eq!(save, Some(SaveV3 { name: "pat".into(), hp: 7, inventory: Vec::new() }));
+
+	let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn save_round_trips_at_current_version() {
+	let (vfs, root) = test_vfs("round_trip");
+
+	let save = SaveV3 { name: "pat".into(), hp: 3, inventory: vec!["key".into()] };
+	vfs.store_save(&schema_v3(), "saves/save.json", &save).unwrap();
+
+	assert!(!root.join("saves/save.tmp").exists(), "Temporary save should have been moved into place");
+	assert_eq!(vfs.load_save(&schema_v3(), "saves/save.json").unwrap(), Some(save));
+
+	let missing: Option<SaveV3> = vfs.load_save(&schema_v3(), "saves/missing.json").unwrap();
+	assert!(missing.is_none(), "Missing saves shouldn't be an error");
+
+	let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn corrupt_save_is_quarantined() {
+	let (vfs, root) = test_vfs("quarantine");
+
+	std::fs::write(root.join("save.json"), b"{ not json").unwrap();
+
+	let result: anyhow::Result<Option<SaveV3>> = vfs.load_save(&schema_v3(), "save.json");
+	assert!(result.is_err());
+	assert!(!root.join("save.json").exists(), "Corrupt save should have been moved aside");
+
+	let quarantined = std::fs::read_dir(&root).unwrap()
+		.filter_map(Result::ok)
+		.any(|entry| entry.file_name().to_string_lossy().starts_with("save.json.quarantine-"));
+
+	assert!(quarantined, "Corrupt save should have been quarantined");
+
+	let _ = std::fs::remove_dir_all(&root);
+}
+
+#[test]
+fn newer_save_is_left_in_place() {
+	let (vfs, root) = test_vfs("newer");
+
+	let v4 = json!({ "version": 4, "data": {} });
+	std::fs::write(root.join("save.json"), serde_json::to_vec(&v4).unwrap()).unwrap();
+
+	let result: anyhow::Result<Option<SaveV3>> = vfs.load_save(&schema_v3(), "save.json");
+	assert!(result.is_err());
+	assert!(root.join("save.json").exists(), "Saves from newer versions shouldn't be quarantined");
+
+	let _ = std::fs::remove_dir_all(&root);
+}
@@ -0,0 +1,56 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use notify::Watcher;
+
+
+/// Collects paths of files modified under a directory, to be polled once per frame.
+/// Paths are canonicalized where possible, so that they can be compared against canonicalized lookups regardless of
+/// symlinks or how the root was spelled.
+pub(crate) struct ResourceWatcher {
+	// Never read, but needs to be kept alive.
+	_watcher: notify::RecommendedWatcher,
+	rx: Receiver<PathBuf>,
+}
+
+impl ResourceWatcher {
+	pub fn new(root: &Path) -> anyhow::Result<ResourceWatcher> {
+		let (tx, rx) = mpsc::channel();
+
+		let mut watcher = notify::recommended_watcher(move |result: notify::Result<notify::Event>| {
+			match result {
+				Ok(event) if event.kind.is_modify() || event.kind.is_create() => {
+					for path in event.paths {
+						let _ = tx.send(path);
+					}
+				}
+
+				Ok(_) => {}
+				Err(error) => log::warn!("File watcher error: {error}"),
+			}
+		})?;
+
+		let root = canonicalize_lossy(root);
+		watcher.watch(&root, notify::RecursiveMode::Recursive)?;
+
+		Ok(ResourceWatcher {
+			_watcher: watcher,
+			rx,
+		})
+	}
+
+	pub fn drain_into(&self, paths: &mut Vec<PathBuf>) {
+		for path in self.rx.try_iter() {
+			let path = canonicalize_lossy(&path);
+			if !paths.contains(&path) {
+				paths.push(path);
+			}
+		}
+	}
+}
+
+
+/// Falls back to `path` as-is if it can't be canonicalized - e.g., if it has since been deleted.
+pub(crate) fn canonicalize_lossy(path: &Path) -> PathBuf {
+	path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
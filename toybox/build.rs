@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+
+fn main() {
+	println!("cargo:rerun-if-changed=build.rs");
+
+	emit_build_info();
+
+	if std::env::var_os("CARGO_FEATURE_EMBED_RESOURCES").is_some() {
+		if let Err(error) = write_embedded_resource_index() {
+			panic!("Failed to embed resources: {error}");
+		}
+	}
+}
+
+
+/// Embeds revision and build environment details for `toybox::build_info`.
+fn emit_build_info() {
+	println!("cargo:rerun-if-env-changed=TOYBOX_GIT_HASH");
+	println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+	// Builds from source archives have no repository to ask, so CI can provide the hash instead.
+	let (git_hash, git_dirty) = match std::env::var("TOYBOX_GIT_HASH") {
+		Ok(hash) => (hash, false),
+		Err(_) => git_revision().unwrap_or_else(|| (String::from("unknown"), false)),
+	};
+
+	// Respected for reproducible builds. Note this is when the build script last ran, not when toybox was last compiled.
+	let timestamp = std::env::var("SOURCE_DATE_EPOCH").ok()
+		.and_then(|epoch| epoch.parse().ok())
+		.unwrap_or_else(|| {
+			std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)
+				.map_or(0, |duration| duration.as_secs())
+		});
+
+	let rustc = std::env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+	let rustc_version = command_output(Command::new(rustc).arg("--version"))
+		.unwrap_or_else(|| String::from("unknown"));
+
+	println!("cargo:rustc-env=TOYBOX_GIT_HASH={git_hash}");
+	println!("cargo:rustc-env=TOYBOX_GIT_DIRTY={git_dirty}");
+	println!("cargo:rustc-env=TOYBOX_BUILD_DATE={}", format_utc_timestamp(timestamp));
+	println!("cargo:rustc-env=TOYBOX_BUILD_PROFILE={}", std::env::var("PROFILE").unwrap_or_default());
+	println!("cargo:rustc-env=TOYBOX_BUILD_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+	println!("cargo:rustc-env=TOYBOX_RUSTC_VERSION={rustc_version}");
+}
+
+/// Short hash of HEAD and whether the working tree has uncommitted changes, if building from a git checkout.
+fn git_revision() -> Option<(String, bool)> {
+	let git_dir = PathBuf::from(command_output(Command::new("git").args(["rev-parse", "--git-dir"]))?);
+
+	// Rerun on commit, checkout or staging. Unstaged edits don't trigger a rerun, so the dirty flag can lag behind.
+	println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+	println!("cargo:rerun-if-changed={}", git_dir.join("index").display());
+
+	if let Some(head_ref) = command_output(Command::new("git").args(["symbolic-ref", "-q", "HEAD"])) {
+		let ref_path = git_dir.join(&head_ref);
+		if ref_path.exists() {
+			println!("cargo:rerun-if-changed={}", ref_path.display());
+		}
+	}
+
+	let hash = command_output(Command::new("git").args(["rev-parse", "--short=10", "HEAD"]))?;
+	let dirty = Command::new("git").args(["status", "--porcelain", "--untracked-files=no"]).output().ok()
+		.is_some_and(|output| output.status.success() && !output.stdout.is_empty());
+
+	Some((hash, dirty))
+}
+
+fn command_output(command: &mut Command) -> Option<String> {
+	let output = command.output().ok()?;
+	if !output.status.success() {
+		return None
+	}
+
+	let output = String::from_utf8(output.stdout).ok()?;
+	Some(output.trim().to_owned())
+}
+
+/// `YYYY-MM-DD HH:MM:SS UTC`, without pulling in a date crate.
+fn format_utc_timestamp(timestamp: u64) -> String {
+	let days = (timestamp / 86400) as i64;
+	let seconds = timestamp % 86400;
+
+	// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era/1460 + day_of_era/36524 - day_of_era/146096) / 365;
+	let day_of_year = day_of_era - (365*year_of_era + year_of_era/4 - year_of_era/100);
+	let mp = (5*day_of_year + 2) / 153;
+	let day = day_of_year - (153*mp + 2)/5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+	format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02} UTC", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+
+/// Writes an index of every file in the resource directory to `$OUT_DIR/embedded_resources.rs`, as a sorted slice of
+/// (virtual path, include_bytes!(absolute path)) pairs, for `vfs::EmbeddedResources::new`.
+fn write_embedded_resource_index() -> Result<(), String> {
+	println!("cargo:rerun-if-env-changed=TOYBOX_RESOURCE_DIR");
+
+	// Build scripts run in the manifest directory of this crate rather than the app's, so the app has to say where
+	// its resources are - e.g., in .cargo/config.toml:
+	//   [env]
+	//   TOYBOX_RESOURCE_DIR = { value = "resource", relative = true }
+	let resource_dir = std::env::var_os("TOYBOX_RESOURCE_DIR")
+		.map(PathBuf::from)
+		.ok_or("The embed-resources feature requires TOYBOX_RESOURCE_DIR to be set to the app's resource directory")?;
+
+	let resource_dir = resource_dir.canonicalize()
+		.map_err(|error| format!("Can't find resource directory '{}': {error}", resource_dir.display()))?;
+
+	let mut entries = Vec::new();
+	collect_files(&resource_dir, &resource_dir, &mut entries)?;
+	entries.sort();
+
+	let mut index = String::from("&[\n");
+	for (key, path) in entries {
+		index += &format!("\t({key:?}, include_bytes!({:?})),\n", path.display().to_string());
+	}
+	index += "]\n";
+
+	let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").ok_or("OUT_DIR not set")?);
+	std::fs::write(out_dir.join("embedded_resources.rs"), index)
+		.map_err(|error| format!("Failed to write resource index: {error}"))
+}
+
+fn collect_files(root: &Path, dir: &Path, entries: &mut Vec<(String, PathBuf)>) -> Result<(), String> {
+	// Directories are watched too, so that added or removed files trigger a rebuild.
+	println!("cargo:rerun-if-changed={}", dir.display());
+
+	let children = dir.read_dir()
+		.map_err(|error| format!("Failed to read '{}': {error}", dir.display()))?;
+
+	for child in children {
+		let child = child.map_err(|error| format!("Failed to read '{}': {error}", dir.display()))?;
+		let path = child.path();
+
+		let file_name = child.file_name();
+		if file_name.to_string_lossy().starts_with('.') {
+			continue
+		}
+
+		if path.is_dir() {
+			collect_files(root, &path, entries)?;
+			continue
+		}
+
+		let relative = path.strip_prefix(root).unwrap();
+		let Some(key) = relative.to_str().map(|key| key.replace('\\', "/")) else {
+			println!("cargo:warning=Skipping resource with non-utf8 path '{}'", relative.display());
+			continue
+		};
+
+		println!("cargo:rerun-if-changed={}", path.display());
+		entries.push((key, path));
+	}
+
+	Ok(())
+}
@@ -0,0 +1,242 @@
+use crate::prelude::*;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+
+/// Drives preloading of gfx resources over multiple frames. See [`Assets::preload`].
+pub struct Assets {
+	preloads: Vec<Rc<RefCell<PreloadState>>>,
+
+	/// How many new resource requests are issued each frame, across all preloads.
+	pub requests_per_frame: usize,
+}
+
+impl Default for Assets {
+	fn default() -> Self {
+		Assets {
+			preloads: Vec::new(),
+			requests_per_frame: 4,
+		}
+	}
+}
+
+/// A resource kept alive by a preload. Requesting the same path again will return the same handle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PreloadedAsset {
+	Image(gfx::ImageHandle),
+	Shader(gfx::ShaderHandle),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PreloadProgress {
+	pub loaded: usize,
+	pub failed: usize,
+	pub total: usize,
+
+	/// The asset currently being waited on, if any.
+	pub current: Option<PathBuf>,
+}
+
+impl PreloadProgress {
+	/// In [0, 1]. Failed assets count as complete.
+	pub fn fraction(&self) -> f32 {
+		match self.total {
+			0 => 1.0,
+			total => (self.loaded + self.failed) as f32 / total as f32,
+		}
+	}
+
+	pub fn is_complete(&self) -> bool {
+		self.loaded + self.failed >= self.total
+	}
+}
+
+/// Shared view of a preload started with [`Assets::preload`]. Loading continues even if all handles are dropped.
+#[derive(Clone)]
+pub struct PreloadHandle {
+	state: Rc<RefCell<PreloadState>>,
+}
+
+impl PreloadHandle {
+	pub fn progress(&self) -> PreloadProgress {
+		self.state.borrow().progress.clone()
+	}
+
+	pub fn is_complete(&self) -> bool {
+		self.state.borrow().progress.is_complete()
+	}
+
+	/// Resources that have been requested so far, and the paths they were loaded from.
+	pub fn assets(&self) -> Vec<(PathBuf, PreloadedAsset)> {
+		self.state.borrow().issued.iter()
+			.map(|entry| (entry.path.clone(), entry.asset))
+			.collect()
+	}
+
+	/// Paths that failed to load so far, and why.
+	pub fn failures(&self) -> Vec<(PathBuf, String)> {
+		self.state.borrow().failures.clone()
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PreloadStatus {
+	Pending,
+	Loaded,
+	Failed,
+}
+
+struct IssuedAsset {
+	path: PathBuf,
+	asset: PreloadedAsset,
+	status: PreloadStatus,
+}
+
+struct PreloadState {
+	queued: VecDeque<PathBuf>,
+	issued: Vec<IssuedAsset>,
+	failures: Vec<(PathBuf, String)>,
+	progress: PreloadProgress,
+}
+
+impl PreloadState {
+	fn fail(&mut self, path: PathBuf, error: String) {
+		log::error!("Failed to preload '{}': {error}", path.display());
+		self.failures.push((path, error));
+		self.progress.failed += 1;
+	}
+}
+
+
+impl Assets {
+	/// Start loading images and shaders in the background. Kinds are determined by extension.
+	pub fn preload<P: AsRef<Path>>(&mut self, paths: &[P]) -> PreloadHandle {
+		let queued: VecDeque<PathBuf> = paths.iter().map(|path| path.as_ref().to_owned()).collect();
+
+		let state = Rc::new(RefCell::new(PreloadState {
+			progress: PreloadProgress {
+				total: queued.len(),
+				current: queued.front().cloned(),
+				.. PreloadProgress::default()
+			},
+
+			queued,
+			issued: Vec::new(),
+			failures: Vec::new(),
+		}));
+
+		self.preloads.push(state.clone());
+
+		PreloadHandle { state }
+	}
+
+	pub fn is_idle(&self) -> bool {
+		self.preloads.is_empty()
+	}
+
+	/// Update progress of in flight requests and issue new ones. Requests are processed in [`gfx::System::execute_frame`].
+	/// Failures are recorded against their preload, which carries on with the rest.
+	pub(crate) fn update(&mut self, gfx: &mut gfx::System, vfs: &vfs::Vfs) {
+		let mut budget = self.requests_per_frame;
+
+		for preload in self.preloads.iter() {
+			let mut preload = preload.borrow_mut();
+
+			while budget > 0 {
+				let Some(path) = preload.queued.pop_front() else { break };
+
+				match request_asset(gfx, vfs, &path) {
+					Ok(asset) => preload.issued.push(IssuedAsset { path, asset, status: PreloadStatus::Pending }),
+					Err(error) => preload.fail(path, format!("{error:#}")),
+				}
+
+				budget -= 1;
+			}
+
+			let rm = &gfx.resource_manager;
+			let mut newly_failed = Vec::new();
+
+			for entry in preload.issued.iter_mut().filter(|entry| entry.status == PreloadStatus::Pending) {
+				entry.status = asset_status(rm, entry.asset);
+				if entry.status == PreloadStatus::Failed {
+					newly_failed.push(entry.path.clone());
+				}
+			}
+
+			for path in newly_failed {
+				preload.fail(path, String::from("Failed to load - see log for details"));
+			}
+
+			let PreloadState { queued, issued, progress, .. } = &mut *preload;
+
+			progress.current = issued.iter()
+				.find(|entry| entry.status == PreloadStatus::Pending)
+				.map(|entry| &entry.path)
+				.or(queued.front())
+				.cloned();
+
+			progress.loaded = issued.iter().filter(|entry| entry.status == PreloadStatus::Loaded).count();
+		}
+
+		self.preloads.retain(|preload| !preload.borrow().progress.is_complete());
+	}
+}
+
+fn request_asset(gfx: &mut gfx::System, vfs: &vfs::Vfs, path: &Path) -> anyhow::Result<PreloadedAsset> {
+	// Caught here, since missing resources are otherwise fatal once requested.
+	anyhow::ensure!(vfs.path_exists(vfs::PathKind::Resource, path), "File not found");
+
+	let extension = path.extension()
+		.and_then(std::ffi::OsStr::to_str)
+		.unwrap_or_default();
+
+	let rm = &mut gfx.resource_manager;
+
+	match extension {
+		"glsl" | "spv" => Ok(PreloadedAsset::Shader(rm.request(gfx::LoadShaderRequest::from(path)?))),
+		_ if rm.image_decoders().find(extension).is_some() => Ok(PreloadedAsset::Image(rm.load_image(path))),
+		_ => anyhow::bail!("Unknown asset type"),
+	}
+}
+
+fn asset_status(rm: &gfx::ResourceManager, asset: PreloadedAsset) -> PreloadStatus {
+	let (loaded, failed) = match asset {
+		PreloadedAsset::Image(handle) => (rm.images.get_resource(handle).is_some(), rm.image_failed(handle)),
+		PreloadedAsset::Shader(handle) => (rm.shaders.get_resource(handle).is_some(), rm.shader_failed(handle)),
+	};
+
+	match (loaded, failed) {
+		(true, _) => PreloadStatus::Loaded,
+		(false, true) => PreloadStatus::Failed,
+		(false, false) => PreloadStatus::Pending,
+	}
+}
+
+
+impl crate::Context {
+	/// Block until `handle` has finished loading, reporting progress to the splash screen if there is one.
+	/// Intended for use while the app is being constructed.
+	/// Failed assets don't stop the wait - check [`PreloadHandle::failures`] afterwards.
+	pub fn wait_for_preload(&mut self, handle: &PreloadHandle) -> anyhow::Result<()> {
+		loop {
+			self.assets.update(&mut self.gfx, &self.vfs);
+
+			let progress = handle.progress();
+			self.report_loading_progress(progress.fraction());
+
+			if progress.is_complete() {
+				return Ok(())
+			}
+
+			// Failures are picked up per asset by the next update.
+			let gfx = &mut *self.gfx;
+			if let Err(error) = gfx.resource_manager.process_requests(&mut gfx.core, &self.vfs) {
+				log::error!("Error while preloading: {error:?}");
+			}
+		}
+	}
+}
@@ -0,0 +1,357 @@
+//! Periodic and event-driven autosaves, rotating through a fixed number of slots in user data.
+//!
+//! Apps register each piece of state to save with [`Autosave::register`]. Saves happen every
+//! [`AutosaveSettings::interval`], or soon after an [`AutosaveRequest`] is emitted on the bus - e.g., on reaching a
+//! checkpoint - but never more often than [`AutosaveSettings::min_interval`]. State is snapshotted on the main thread,
+//! then written on a background thread so that large saves don't hitch.
+//!
+//! ```ignore
+//! let world = Rc::new(RefCell::new(World::new()));
+//! ctx.autosave.register("world", {
+//!     let world = world.clone();
+//!     move || Ok(world.borrow().to_snapshot_bytes())
+//! })?;
+//!
+//! // Later
+//! ctx.bus.emit(AutosaveRequest::new("checkpoint reached"));
+//!
+//! // On startup
+//! if let Some(slot) = ctx.autosave.slots(&ctx.vfs).first() {
+//!     let world = World::from_snapshot_bytes(&ctx.autosave.load(&ctx.vfs, slot, "world")?)?;
+//! }
+//! ```
+//!
+//! Each slot is a directory holding one file per registered name, and an `autosave.json` describing when and why it
+//! was saved. Slots are written to a temporary directory and moved into place once complete, so a crash mid-save can
+//! lose the slot being replaced, but never leaves a partially written one.
+
+use crate::prelude::*;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant, SystemTime};
+
+
+const SLOT_INFO_FILE: &str = "autosave.json";
+
+
+#[derive(Debug, Clone)]
+pub struct AutosaveSettings {
+	/// Time between periodic autosaves. None to only save on [`AutosaveRequest`]s.
+	pub interval: Option<Duration>,
+
+	/// Requests arriving sooner than this after the last save are deferred until it has passed.
+	pub min_interval: Duration,
+
+	/// Number of slots to rotate through. The oldest is overwritten once all are used.
+	pub num_slots: usize,
+
+	/// Relative to the user data root.
+	pub directory: PathBuf,
+}
+
+impl Default for AutosaveSettings {
+	fn default() -> Self {
+		AutosaveSettings {
+			interval: Some(Duration::from_secs(5 * 60)),
+			min_interval: Duration::from_secs(30),
+			num_slots: 3,
+			directory: PathBuf::from("autosave"),
+		}
+	}
+}
+
+
+/// Emit on the bus to autosave as soon as the rate limit allows.
+#[derive(Debug, Clone)]
+pub struct AutosaveRequest {
+	pub reason: String,
+}
+
+impl AutosaveRequest {
+	pub fn new(reason: impl Into<String>) -> AutosaveRequest {
+		AutosaveRequest { reason: reason.into() }
+	}
+}
+
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct AutosaveSlot {
+	pub index: usize,
+
+	/// Seconds since the unix epoch.
+	pub timestamp: u64,
+	pub reason: String,
+	pub names: Vec<String>,
+}
+
+
+/// Produces the bytes to save for one piece of registered state.
+pub type AutosaveSource = dyn FnMut() -> anyhow::Result<Vec<u8>>;
+
+
+pub struct Autosave {
+	pub settings: AutosaveSettings,
+	pub enabled: bool,
+
+	sources: BTreeMap<String, Box<AutosaveSource>>,
+	subscription: bus::Subscription<AutosaveRequest>,
+
+	last_save: Instant,
+	pending_reason: Option<String>,
+	next_slot: Option<usize>,
+
+	in_flight: Option<mpsc::Receiver<anyhow::Result<AutosaveSlot>>>,
+	last_result: Option<Result<AutosaveSlot, String>>,
+}
+
+impl Autosave {
+	pub(crate) fn new(bus: &bus::MessageBus) -> Autosave {
+		Autosave {
+			settings: AutosaveSettings::default(),
+			enabled: true,
+
+			sources: BTreeMap::new(),
+			subscription: bus.subscribe(),
+
+			last_save: Instant::now(),
+			pending_reason: None,
+			next_slot: None,
+
+			in_flight: None,
+			last_result: None,
+		}
+	}
+
+	/// `autosave.enabled`, `autosave.interval_minutes` (0 for requests only) and `autosave.slots`.
+	pub(crate) fn apply_config(&mut self, cfg: &cfg::Config) {
+		if let Some(enabled) = cfg.get_bool("autosave.enabled") {
+			self.enabled = enabled;
+		}
+
+		if let Some(minutes) = cfg.get_float("autosave.interval_minutes") {
+			self.settings.interval = (minutes > 0.0).then(|| Duration::from_secs_f64(minutes * 60.0));
+		}
+
+		if let Some(num_slots) = cfg.get_float("autosave.slots") {
+			self.settings.num_slots = (num_slots as usize).max(1);
+		}
+	}
+
+	/// Include `source` in every autosave under `name`, replacing any source already registered with that name.
+	/// Nothing is autosaved until at least one source is registered.
+	/// `name` is used as a file name, so may only contain ascii alphanumerics, spaces, `_`, `-` and `.`.
+	pub fn register(&mut self, name: impl Into<String>, source: impl FnMut() -> anyhow::Result<Vec<u8>> + 'static) -> anyhow::Result<()> {
+		let name = name.into();
+		validate_name(&name)?;
+
+		self.sources.insert(name, Box::new(source));
+		Ok(())
+	}
+
+	pub fn unregister(&mut self, name: &str) {
+		self.sources.remove(name);
+	}
+
+	/// Autosave at the next opportunity, subject to the rate limit.
+	pub fn request(&mut self, reason: impl Into<String>) {
+		self.pending_reason.get_or_insert_with(|| reason.into());
+	}
+
+	/// Whether a save is being written in the background.
+	pub fn is_saving(&self) -> bool {
+		self.in_flight.is_some()
+	}
+
+	/// The most recently finished autosave, or why it failed.
+	pub fn last_result(&self) -> Option<&Result<AutosaveSlot, String>> {
+		self.last_result.as_ref()
+	}
+
+	/// Time until the next periodic autosave, if enabled.
+	pub fn time_until_next(&self) -> Option<Duration> {
+		let interval = self.settings.interval?;
+		Some(interval.saturating_sub(self.last_save.elapsed()))
+	}
+
+	/// Every complete autosave slot, newest first.
+	pub fn slots(&self, vfs: &vfs::Vfs) -> Vec<AutosaveSlot> {
+		let mut slots: Vec<_> = (0..self.settings.num_slots)
+			.filter_map(|index| read_slot_info(vfs, &slot_path(&self.settings.directory, index)).ok())
+			.collect();
+
+		slots.sort_by_key(|slot| std::cmp::Reverse(slot.timestamp));
+		slots
+	}
+
+	/// Load what was saved under `name` in `slot`.
+	pub fn load(&self, vfs: &vfs::Vfs, slot: &AutosaveSlot, name: &str) -> anyhow::Result<Vec<u8>> {
+		validate_name(name)?;
+
+		let path = slot_path(&self.settings.directory, slot.index).join(format!("{name}.bin"));
+		vfs.load_data(vfs::PathKind::UserData, &path)
+			.with_context(|| format!("Loading '{name}' from autosave slot {}", slot.index))
+	}
+
+	/// Block until any in-flight save has been written.
+	pub fn flush(&mut self) {
+		if let Some(rx) = self.in_flight.take() {
+			let result = rx.recv().unwrap_or_else(|_| Err(anyhow::anyhow!("Autosave thread panicked")));
+			self.finish(result);
+		}
+	}
+
+	#[instrument(skip_all, name="toybox Autosave::update")]
+	pub(crate) fn update(&mut self, vfs: &vfs::Vfs, bus: &bus::MessageBus) {
+		let requests: Vec<AutosaveRequest> = bus.poll(&self.subscription).collect();
+		if let Some(request) = requests.into_iter().next() {
+			self.request(request.reason);
+		}
+
+		if let Some(rx) = &self.in_flight {
+			match rx.try_recv() {
+				Ok(result) => {
+					self.in_flight = None;
+					self.finish(result);
+				}
+
+				Err(mpsc::TryRecvError::Empty) => return,
+				Err(mpsc::TryRecvError::Disconnected) => {
+					self.in_flight = None;
+					self.finish(Err(anyhow::anyhow!("Autosave thread panicked")));
+				}
+			}
+		}
+
+		if !self.enabled || self.sources.is_empty() {
+			return
+		}
+
+		let since_last_save = self.last_save.elapsed();
+		if since_last_save < self.settings.min_interval {
+			return
+		}
+
+		let interval_elapsed = self.settings.interval.is_some_and(|interval| since_last_save >= interval);
+		let reason = match self.pending_reason.take() {
+			Some(reason) => reason,
+			None if interval_elapsed => String::from("interval"),
+			None => return,
+		};
+
+		self.start_save(vfs, reason);
+	}
+
+	fn start_save(&mut self, vfs: &vfs::Vfs, reason: String) {
+		self.last_save = Instant::now();
+
+		let num_slots = self.settings.num_slots.max(1);
+		let index = self.next_slot.unwrap_or_else(|| {
+			// Continue after the newest slot from a previous session.
+			(0..num_slots)
+				.filter_map(|index| read_slot_info(vfs, &slot_path(&self.settings.directory, index)).ok())
+				.max_by_key(|slot| slot.timestamp)
+				.map_or(0, |slot| (slot.index + 1) % num_slots)
+		});
+
+		let mut data = Vec::new();
+		for (name, source) in self.sources.iter_mut() {
+			match source() {
+				Ok(bytes) => data.push((name.clone(), bytes)),
+				Err(error) => {
+					self.finish(Err(error.context(format!("Snapshotting '{name}' for autosave"))));
+					return
+				}
+			}
+		}
+
+		// Only move on once the slot is actually going to be written, so failed snapshots don't skip slots.
+		self.next_slot = Some((index + 1) % num_slots);
+
+		let slot = AutosaveSlot {
+			index,
+			timestamp: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs(),
+			reason,
+			names: data.iter().map(|(name, _)| name.clone()).collect(),
+		};
+
+		log::info!("Autosaving to slot {index} ({})", slot.reason);
+
+		// Vfs can't be shared with the save thread, but a Vfs with the same roots writes to the same places.
+		let writer_vfs = vfs::Vfs::with_roots(vfs.resource_root(), vfs.user_data_root());
+		let directory = self.settings.directory.clone();
+		let (tx, rx) = mpsc::channel();
+
+		let spawn_result = std::thread::Builder::new()
+			.name("autosave".into())
+			.spawn(move || {
+				let _ = tx.send(write_slot(&writer_vfs, &directory, slot, data));
+			});
+
+		match spawn_result {
+			Ok(_) => self.in_flight = Some(rx),
+			Err(error) => self.finish(Err(anyhow::Error::from(error).context("Starting autosave thread"))),
+		}
+	}
+
+	fn finish(&mut self, result: anyhow::Result<AutosaveSlot>) {
+		if let Err(error) = &result {
+			log::error!("Autosave failed: {error:?}");
+		}
+
+		self.last_result = Some(result.map_err(|error| format!("{error:?}")));
+	}
+}
+
+
+/// Virtual path of a slot, relative to the user data root.
+fn slot_path(directory: &Path, index: usize) -> PathBuf {
+	directory.join(index.to_string())
+}
+
+fn validate_name(name: &str) -> anyhow::Result<()> {
+	let is_valid = !name.is_empty()
+		&& name.bytes().any(|byte| byte != b'.')
+		&& name.bytes().all(|byte| byte.is_ascii_alphanumeric() || [b' ', b'_', b'-', b'.'].contains(&byte));
+
+	anyhow::ensure!(is_valid, "Invalid autosave name '{name}' - names may only contain ascii alphanumeric characters, \
+		spaces, '_', '-' and '.'");
+
+	Ok(())
+}
+
+fn read_slot_info(vfs: &vfs::Vfs, slot_path: &Path) -> anyhow::Result<AutosaveSlot> {
+	let data = vfs.load_data(vfs::PathKind::UserData, slot_path.join(SLOT_INFO_FILE))?;
+	Ok(serde_json::from_slice(&data)?)
+}
+
+fn write_slot(vfs: &vfs::Vfs, directory: &Path, slot: AutosaveSlot, data: Vec<(String, Vec<u8>)>) -> anyhow::Result<AutosaveSlot> {
+	let final_path = slot_path(directory, slot.index);
+	let temp_path = directory.join(format!("{}.tmp", slot.index));
+
+	let real_temp_path = vfs.resolve_path(vfs::PathKind::UserData, &temp_path)?;
+	if real_temp_path.exists() {
+		std::fs::remove_dir_all(&real_temp_path)?;
+	}
+
+	for (name, bytes) in data {
+		let path = temp_path.join(format!("{name}.bin"));
+		vfs.save_data(vfs::PathKind::UserData, &path, bytes)
+			.with_context(|| format!("Writing '{}'", path.display()))?;
+	}
+
+	// Written last, so that a slot without info is never mistaken for a complete one.
+	vfs.save_data(vfs::PathKind::UserData, temp_path.join(SLOT_INFO_FILE), serde_json::to_vec_pretty(&slot)?)?;
+
+	let real_final_path = vfs.resolve_path(vfs::PathKind::UserData, &final_path)?;
+	if real_final_path.exists() {
+		std::fs::remove_dir_all(&real_final_path)
+			.with_context(|| format!("Removing old autosave '{}'", real_final_path.display()))?;
+	}
+
+	std::fs::rename(&real_temp_path, &real_final_path)
+		.with_context(|| format!("Replacing '{}'", real_final_path.display()))?;
+
+	Ok(slot)
+}
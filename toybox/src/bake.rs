@@ -0,0 +1,368 @@
+//! Converts source assets into the formats the runtime loads fastest, writing them to a folder or a [`vfs::Bundle`].
+//! Bakers reuse the runtime's own format code - e.g., [`ImageBaker`] writes images with [`gfx::encode_ktx2`], which are
+//! then read back by [`gfx::Ktx2Decoder`] - so loaders and bakers can't drift apart.
+//!
+//! Baked files keep their source path, so runtime code loads `textures/foo.png` whether or not it has been baked.
+//! Loaders recognise baked data by its contents rather than its extension.
+//!
+//! With [`BakeSettings::pack_channels`], grayscale material maps sharing a name - `brick_roughness.png`,
+//! `brick_metalness.png` etc. - are packed into a single `brick_packed.ktx2` instead, see [`gfx::channel_packing`].
+//!
+//! A bake tool is just:
+//! ```rust no_run
+//! fn main() -> anyhow::Result<()> {
+//!     toybox::bake::bake_main()
+//! }
+//! ```
+
+use crate::prelude::*;
+
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashSet};
+use std::time::Instant;
+
+
+/// Stem suffix given to packed material maps, e.g., `brick_packed.ktx2`.
+pub const PACKED_MATERIAL_SUFFIX: &str = "_packed";
+
+/// Source formats that should be baked, but that no default baker handles yet - audio (WAV -> OGG) and meshes
+/// (glTF -> mesh blob) have no runtime loaders to share format code with. Unless an app registers a [`Baker`] for
+/// them they're copied through, but listed separately in the [`BakeReport`] and warned about, rather than counted as
+/// ordinary copies.
+pub const UNBAKED_SOURCE_EXTENSIONS: &[&str] = &["wav", "gltf", "glb"];
+
+
+/// Converts one kind of source asset into its baked form.
+pub trait Baker {
+	/// Lowercase source file extensions this baker handles, without the leading '.'.
+	fn source_extensions(&self) -> &[&str];
+
+	fn bake(&self, source_path: &Path, data: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+
+/// Decodes any image the runtime can load and writes it as uncompressed KTX2, so loading skips decompression entirely.
+/// [`gfx::ImageDecoderRegistry`] detects KTX2 data regardless of extension.
+pub struct ImageBaker {
+	decoders: gfx::ImageDecoderRegistry,
+}
+
+impl Default for ImageBaker {
+	fn default() -> Self {
+		ImageBaker { decoders: gfx::ImageDecoderRegistry::default() }
+	}
+}
+
+impl Baker for ImageBaker {
+	fn source_extensions(&self) -> &[&str] {
+		&["png", "jpg", "jpeg", "tga", "qoi"]
+	}
+
+	fn bake(&self, source_path: &Path, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+		let image = self.decoders.decode(source_path, data)?;
+		gfx::encode_ktx2(&image)
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub enum BakeOutput {
+	Folder(PathBuf),
+	Bundle(PathBuf),
+}
+
+
+/// Bakes every file under a source folder. Files no baker handles are copied through unchanged.
+/// See [`UNBAKED_SOURCE_EXTENSIONS`].
+pub struct BakeSettings {
+	pub source: PathBuf,
+	pub output: BakeOutput,
+	pub bakers: Vec<Box<dyn Baker>>,
+
+	/// Pack grayscale material maps according to this convention. See [`BakeSettings::pack_channels`].
+	pub channel_packing: Option<gfx::PackingConvention>,
+}
+
+impl BakeSettings {
+	pub fn new(source: impl Into<PathBuf>, output: BakeOutput) -> BakeSettings {
+		BakeSettings {
+			source: source.into(),
+			output,
+			bakers: vec![Box::new(ImageBaker::default())],
+			channel_packing: None,
+		}
+	}
+
+	/// Images whose stems end in a [`gfx::MaterialChannel::file_suffix`] are grouped by the rest of their path and packed
+	/// into one image per group, rather than baked individually.
+	pub fn pack_channels(self, convention: gfx::PackingConvention) -> Self {
+		Self { channel_packing: Some(convention), .. self }
+	}
+
+	/// Bakers registered later take precedence.
+	pub fn baker(mut self, baker: impl Baker + 'static) -> Self {
+		self.bakers.push(Box::new(baker));
+		self
+	}
+
+	fn find_baker(&self, path: &Path) -> Option<&dyn Baker> {
+		let extension = path.extension()?.to_str()?;
+
+		self.bakers.iter().rev()
+			.find(|baker| baker.source_extensions().iter().any(|ext| ext.eq_ignore_ascii_case(extension)))
+			.map(|baker| &**baker)
+	}
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct BakeReport {
+	pub baked: usize,
+	pub copied: usize,
+
+	/// Source paths with an extension from [`UNBAKED_SOURCE_EXTENSIONS`] that were copied through unbaked.
+	pub unbaked: Vec<PathBuf>,
+
+	/// Source paths relative to the source folder, and why they failed.
+	pub failed: Vec<(PathBuf, String)>,
+}
+
+
+/// Bake everything under `settings.source`. Individual failures are collected in the report rather than stopping
+/// the bake, but failing to read the source or write output is an error.
+#[instrument(skip_all, name="toybox bake")]
+pub fn bake(settings: &BakeSettings) -> anyhow::Result<BakeReport> {
+	let mut writer = match &settings.output {
+		BakeOutput::Folder(path) => {
+			std::fs::create_dir_all(path)
+				.with_context(|| format!("Creating output folder '{}'", path.display()))?;
+			None
+		}
+
+		BakeOutput::Bundle(path) => Some(vfs::BundleWriter::create(path)?),
+	};
+
+	let mut report = BakeReport::default();
+
+	let source_paths = collect_files(&settings.source)?;
+	let mut packed_paths = HashSet::new();
+
+	if let Some(convention) = &settings.channel_packing {
+		for (output_path, sources) in collect_packed_materials(&settings.source, &source_paths, convention)? {
+			packed_paths.extend(sources.iter().map(|(_, path)| path.clone()));
+
+			let start = Instant::now();
+
+			match bake_packed_material(&settings.source, convention, &sources) {
+				Ok(baked) => {
+					log::info!("Packed '{}' in {:?}", output_path.display(), start.elapsed());
+					report.baked += 1;
+					write_output(&mut writer, &settings.output, &output_path, &baked)?;
+				}
+
+				Err(error) => {
+					log::error!("Failed to pack '{}': {error:?}", output_path.display());
+					report.failed.push((output_path, format!("{error:?}")));
+				}
+			}
+		}
+	}
+
+	for source_path in source_paths {
+		let relative_path = source_path.strip_prefix(&settings.source)?;
+		if packed_paths.contains(relative_path) {
+			continue
+		}
+
+		let data = std::fs::read(&source_path)
+			.with_context(|| format!("Reading '{}'", source_path.display()))?;
+
+		let (output_path, output_data) = match settings.find_baker(relative_path) {
+			Some(baker) => {
+				let start = Instant::now();
+
+				match baker.bake(relative_path, &data) {
+					Ok(baked) => {
+						log::info!("Baked '{}' in {:?}", relative_path.display(), start.elapsed());
+						report.baked += 1;
+						(relative_path.to_owned(), baked)
+					}
+
+					Err(error) => {
+						log::error!("Failed to bake '{}': {error:?}", relative_path.display());
+						report.failed.push((relative_path.to_owned(), format!("{error:?}")));
+						continue
+					}
+				}
+			}
+
+			None if is_unbaked_source(relative_path) => {
+				log::warn!("No baker registered for '{}' - copying it unbaked", relative_path.display());
+				report.unbaked.push(relative_path.to_owned());
+				(relative_path.to_owned(), data)
+			}
+
+			None => {
+				report.copied += 1;
+				(relative_path.to_owned(), data)
+			}
+		};
+
+		write_output(&mut writer, &settings.output, &output_path, &output_data)?;
+	}
+
+	if let Some(writer) = writer {
+		writer.finish()?;
+	}
+
+	Ok(report)
+}
+
+
+fn is_unbaked_source(path: &Path) -> bool {
+	path.extension()
+		.and_then(|extension| extension.to_str())
+		.is_some_and(|extension| UNBAKED_SOURCE_EXTENSIONS.iter().any(|unbaked| unbaked.eq_ignore_ascii_case(extension)))
+}
+
+fn write_output(writer: &mut Option<vfs::BundleWriter>, output: &BakeOutput, output_path: &Path, data: &[u8]) -> anyhow::Result<()> {
+	match (writer, output) {
+		(Some(writer), _) => writer.add(output_path, data)?,
+
+		(None, BakeOutput::Folder(root)) => {
+			let path = root.join(output_path);
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+
+			std::fs::write(&path, data)
+				.with_context(|| format!("Writing '{}'", path.display()))?;
+		}
+
+		(None, BakeOutput::Bundle(_)) => unreachable!(),
+	}
+
+	Ok(())
+}
+
+
+/// Groups material maps under `root` by the path they share once their channel suffix is removed.
+/// Returns the output path of each group, relative to `root`, along with its sources.
+fn collect_packed_materials(root: &Path, paths: &[PathBuf], convention: &gfx::PackingConvention)
+	-> anyhow::Result<BTreeMap<PathBuf, Vec<(gfx::MaterialChannel, PathBuf)>>>
+{
+	let mut materials: BTreeMap<_, Vec<_>> = BTreeMap::new();
+
+	for path in paths {
+		let relative_path = path.strip_prefix(root)?;
+		let Some(stem) = relative_path.file_stem().and_then(|stem| stem.to_str()) else { continue };
+
+		let channel = gfx::MaterialChannel::ALL.into_iter()
+			.filter(|&channel| convention.channel_index(channel).is_some())
+			.find(|channel| stem.ends_with(channel.file_suffix()));
+
+		let Some(channel) = channel else { continue };
+
+		let material_stem = &stem[..stem.len() - channel.file_suffix().len()];
+		let output_path = relative_path.with_file_name(format!("{material_stem}{PACKED_MATERIAL_SUFFIX}.ktx2"));
+
+		materials.entry(output_path).or_default().push((channel, relative_path.to_owned()));
+	}
+
+	Ok(materials)
+}
+
+fn bake_packed_material(root: &Path, convention: &gfx::PackingConvention, sources: &[(gfx::MaterialChannel, PathBuf)])
+	-> anyhow::Result<Vec<u8>>
+{
+	let decoders = gfx::ImageDecoderRegistry::default();
+
+	let images = sources.iter()
+		.map(|(channel, path)| {
+			let data = std::fs::read(root.join(path))
+				.with_context(|| format!("Reading '{}'", path.display()))?;
+
+			let image = decoders.decode(path, &data)
+				.with_context(|| format!("Decoding {channel:?} map '{}'", path.display()))?;
+
+			Ok((*channel, image))
+		})
+		.collect::<anyhow::Result<Vec<_>>>()?;
+
+	let images: Vec<_> = images.iter().map(|(channel, image)| (*channel, image)).collect();
+	let packed = gfx::pack_channels(convention, &images)?;
+	gfx::encode_ktx2(&packed)
+}
+
+
+/// Entry point for a bake binary, using the default bakers.
+/// Usage: `<source folder> <output path> [--bundle] [--pack-channels]`
+pub fn bake_main() -> anyhow::Result<()> {
+	let mut args = std::env::args().skip(1);
+	let usage = "Usage: <source folder> <output path> [--bundle] [--pack-channels]";
+
+	let source = args.next().context(usage)?;
+	let output = args.next().context(usage)?;
+
+	let mut bundle = false;
+	let mut pack_channels = false;
+
+	for arg in args {
+		match arg.as_str() {
+			"--bundle" => bundle = true,
+			"--pack-channels" => pack_channels = true,
+			arg => anyhow::bail!("Unexpected argument '{arg}'\n{usage}"),
+		}
+	}
+
+	let output = match bundle {
+		true => BakeOutput::Bundle(output.into()),
+		false => BakeOutput::Folder(output.into()),
+	};
+
+	let mut settings = BakeSettings::new(source, output);
+	if pack_channels {
+		settings = settings.pack_channels(gfx::PackingConvention::default());
+	}
+
+	let report = bake(&settings)?;
+
+	println!("{} baked, {} copied, {} unbaked, {} failed", report.baked, report.copied, report.unbaked.len(), report.failed.len());
+
+	for path in report.unbaked.iter() {
+		println!("  {}: no baker registered, copied unbaked", path.display());
+	}
+
+	for (path, error) in report.failed.iter() {
+		println!("  {}: {error}", path.display());
+	}
+
+	anyhow::ensure!(report.failed.is_empty(), "Some assets failed to bake");
+
+	Ok(())
+}
+
+
+fn collect_files(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+	let mut files = Vec::new();
+	let mut to_visit = vec![root.to_owned()];
+
+	while let Some(dir) = to_visit.pop() {
+		let entries = dir.read_dir()
+			.with_context(|| format!("Reading directory '{}'", dir.display()))?;
+
+		for entry in entries {
+			let path = entry?.path();
+
+			if path.is_dir() {
+				to_visit.push(path);
+			} else {
+				files.push(path);
+			}
+		}
+	}
+
+	// Deterministic order so bundles come out the same every time.
+	files.sort();
+	Ok(files)
+}
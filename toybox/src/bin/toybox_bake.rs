@@ -0,0 +1,3 @@
+fn main() -> anyhow::Result<()> {
+	toybox::bake::bake_main()
+}
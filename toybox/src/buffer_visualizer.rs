@@ -0,0 +1,284 @@
+use crate::prelude::*;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+/// Element layout for [`BufferVisualizer`]. Fields are tightly packed, so std430 padding must be made explicit
+/// with [`BufferField::Padding`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BufferField {
+	Float,
+	Vec2,
+	Vec3,
+	Vec4,
+	Int,
+	Uint,
+
+	/// 4 bytes of ignored data.
+	Padding,
+}
+
+impl BufferField {
+	pub fn size(&self) -> usize {
+		4 * self.components()
+	}
+
+	pub fn components(&self) -> usize {
+		match self {
+			BufferField::Vec2 => 2,
+			BufferField::Vec3 => 3,
+			BufferField::Vec4 => 4,
+			_ => 1,
+		}
+	}
+
+	fn from_str(text: &str) -> Option<BufferField> {
+		match text {
+			"float" => Some(BufferField::Float),
+			"vec2" => Some(BufferField::Vec2),
+			"vec3" => Some(BufferField::Vec3),
+			"vec4" => Some(BufferField::Vec4),
+			"int" => Some(BufferField::Int),
+			"uint" => Some(BufferField::Uint),
+			"pad" => Some(BufferField::Padding),
+			_ => None,
+		}
+	}
+
+	fn format_component(&self, bytes: [u8; 4]) -> String {
+		match self {
+			BufferField::Int => format!("{}", i32::from_le_bytes(bytes)),
+			BufferField::Uint => format!("{}", u32::from_le_bytes(bytes)),
+			BufferField::Padding => String::new(),
+			_ => format!("{:.3}", f32::from_le_bytes(bytes)),
+		}
+	}
+
+	fn component_as_f32(&self, bytes: [u8; 4]) -> f32 {
+		match self {
+			BufferField::Int => i32::from_le_bytes(bytes) as f32,
+			BufferField::Uint => u32::from_le_bytes(bytes) as f32,
+			BufferField::Padding => 0.0,
+			_ => f32::from_le_bytes(bytes),
+		}
+	}
+}
+
+/// Parses whitespace separated field names, e.g., "vec4 vec3 float uint pad".
+pub fn parse_schema(text: &str) -> anyhow::Result<Vec<BufferField>> {
+	let schema = text.split_whitespace()
+		.map(|field| BufferField::from_str(field)
+			.ok_or_else(|| anyhow::format_err!("Unknown field type '{field}'")))
+		.collect::<anyhow::Result<Vec<_>>>()?;
+
+	anyhow::ensure!(!schema.is_empty(), "Empty schema");
+	Ok(schema)
+}
+
+
+
+/// Debug view of SSBO contents, for debugging compute shaders.
+/// Call [`BufferVisualizer::request_readback`] every frame you want the view updated, and [`BufferVisualizer::ui`] to
+/// show the most recently read back data.
+pub struct BufferVisualizer {
+	pub buffer: Option<gfx::BufferName>,
+	pub schema: Vec<BufferField>,
+
+	pub first_element: usize,
+	pub max_elements: usize,
+	pub paused: bool,
+
+	schema_text: String,
+	schema_error: Option<String>,
+	plot_column: Option<usize>,
+
+	pending_readback: Rc<RefCell<Option<Vec<u8>>>>,
+	data: Vec<u8>,
+}
+
+impl BufferVisualizer {
+	pub fn new(buffer: impl Into<Option<gfx::BufferName>>, schema: &str) -> anyhow::Result<BufferVisualizer> {
+		Ok(BufferVisualizer {
+			buffer: buffer.into(),
+			schema: parse_schema(schema)?,
+
+			first_element: 0,
+			max_elements: 256,
+			paused: false,
+
+			schema_text: schema.to_owned(),
+			schema_error: None,
+			plot_column: None,
+
+			pending_readback: Rc::new(RefCell::new(None)),
+			data: Vec::new(),
+		})
+	}
+
+	pub fn stride(&self) -> usize {
+		self.schema.iter().map(BufferField::size).sum()
+	}
+
+	/// Schedule a readback of the visible range at the end of the current frame.
+	pub fn request_readback(&mut self, gfx: &mut gfx::System) {
+		let Some(buffer) = self.buffer else { return };
+		if self.paused {
+			return
+		}
+
+		let stride = self.stride();
+		let offset = self.first_element * stride;
+		let max_size = self.max_elements * stride;
+
+		let pending_readback = self.pending_readback.clone();
+
+		gfx.frame_encoder.command_group(gfx::FrameStage::Final)
+			.annotate("Buffer Visualizer Readback")
+			.execute(move |core, _| {
+				let buffer_size = core.get_buffer_info(buffer).map_or(0, |info| info.size);
+				let size = max_size.min(buffer_size.saturating_sub(offset));
+
+				let data = core.read_buffer_data(buffer, gfx::BufferRange{offset, size});
+				*pending_readback.borrow_mut() = Some(data);
+			});
+	}
+
+	pub fn ui(&mut self, ui: &mut egui::Ui) {
+		if let Some(data) = self.pending_readback.borrow_mut().take() {
+			self.data = data;
+		}
+
+		self.controls_ui(ui);
+
+		ui.separator();
+
+		if let Some(column) = self.plot_column {
+			self.plot_ui(ui, column);
+			ui.separator();
+		}
+
+		self.table_ui(ui);
+	}
+
+	fn controls_ui(&mut self, ui: &mut egui::Ui) {
+		ui.horizontal(|ui| {
+			ui.label("Schema");
+
+			if ui.text_edit_singleline(&mut self.schema_text).changed() {
+				match parse_schema(&self.schema_text) {
+					Ok(schema) => {
+						self.schema = schema;
+						self.schema_error = None;
+						self.plot_column = None;
+					}
+
+					Err(error) => self.schema_error = Some(error.to_string()),
+				}
+			}
+		});
+
+		if let Some(error) = &self.schema_error {
+			ui.colored_label(egui::Color32::LIGHT_RED, error);
+		}
+
+		ui.horizontal(|ui| {
+			ui.add(egui::DragValue::new(&mut self.first_element).prefix("First: "));
+			ui.add(egui::DragValue::new(&mut self.max_elements).clamp_range(1..=4096).prefix("Count: "));
+			ui.checkbox(&mut self.paused, "Paused");
+		});
+
+		ui.label(format!("Stride: {} bytes, {} elements read", self.stride(), self.num_elements()));
+	}
+
+	fn num_elements(&self) -> usize {
+		match self.stride() {
+			0 => 0,
+			stride => self.data.len() / stride,
+		}
+	}
+
+	fn columns(&self) -> impl Iterator<Item=(BufferField, usize)> + '_ {
+		self.schema.iter()
+			.flat_map(|&field| (0..field.components()).map(move |component| (field, component)))
+	}
+
+	fn component_bytes(&self, element: usize, column: usize) -> [u8; 4] {
+		let offset = element * self.stride() + column * 4;
+		self.data[offset..offset+4].try_into().unwrap()
+	}
+
+	fn table_ui(&mut self, ui: &mut egui::Ui) {
+		let columns: Vec<_> = self.columns().collect();
+		let num_elements = self.num_elements();
+
+		egui::ScrollArea::both()
+			.auto_shrink(false)
+			.show_rows(ui, ui.text_style_height(&egui::TextStyle::Body), num_elements, |ui, row_range| {
+				egui::Grid::new("buffer_visualizer_table")
+					.striped(true)
+					.show(ui, |ui| {
+						ui.label("#");
+
+						for (column, (field, component)) in columns.iter().enumerate() {
+							if *field == BufferField::Padding {
+								ui.label("");
+								continue
+							}
+
+							let label = format!("{field:?}.{component}");
+							let selected = self.plot_column == Some(column);
+
+							if ui.selectable_label(selected, label).on_hover_text("Plot").clicked() {
+								self.plot_column = (!selected).then_some(column);
+							}
+						}
+
+						ui.end_row();
+
+						for element in row_range {
+							ui.label(format!("{}", self.first_element + element));
+
+							for (column, (field, _)) in columns.iter().enumerate() {
+								ui.monospace(field.format_component(self.component_bytes(element, column)));
+							}
+
+							ui.end_row();
+						}
+					});
+			});
+	}
+
+	fn plot_ui(&self, ui: &mut egui::Ui, column: usize) {
+		let Some((field, _)) = self.columns().nth(column) else { return };
+
+		let values: Vec<f32> = (0..self.num_elements())
+			.map(|element| field.component_as_f32(self.component_bytes(element, column)))
+			.collect();
+
+		let (min, max) = values.iter()
+			.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &value| (min.min(value), max.max(value)));
+
+		ui.label(format!("min: {min:.3}  max: {max:.3}"));
+
+		let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width(), 100.0), egui::Sense::hover());
+		let rect = response.rect;
+
+		painter.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+
+		if values.len() < 2 || !min.is_finite() || !max.is_finite() {
+			return
+		}
+
+		let range = (max - min).max(f32::EPSILON);
+		let points: Vec<egui::Pos2> = values.iter().enumerate()
+			.map(|(index, &value)| {
+				let x = rect.left() + rect.width() * index as f32 / (values.len() - 1) as f32;
+				let y = rect.bottom() - rect.height() * (value - min) / range;
+				egui::pos2(x, y)
+			})
+			.collect();
+
+		painter.add(egui::Shape::line(points, ui.visuals().widgets.active.fg_stroke));
+	}
+}
@@ -0,0 +1,128 @@
+//! Which engine revision an app was built from, embedded at compile time by `build.rs`.
+//!
+//! Shown in the debug menu and appended to panic messages, so that bug reports identify the exact build. Builds from
+//! outside a git checkout report an `unknown` hash unless `TOYBOX_GIT_HASH` is set in the build environment.
+
+
+/// See [`build_info`].
+#[derive(Debug)]
+pub struct BuildInfo {
+	/// Short hash of the toybox commit built from.
+	pub git_hash: &'static str,
+
+	/// Whether there were uncommitted changes to tracked files at build time.
+	pub git_dirty: bool,
+
+	/// UTC, or from `SOURCE_DATE_EPOCH` if set.
+	pub build_date: &'static str,
+
+	/// Cargo profile, e.g., `debug` or `release`.
+	pub profile: &'static str,
+	pub target: &'static str,
+	pub rustc_version: &'static str,
+
+	/// Cargo features of the `toybox` crate, and whether they were enabled.
+	pub features: &'static [(&'static str, bool)],
+
+	/// Engine crates and their versions, from each crate's own manifest.
+	pub crate_versions: &'static [(&'static str, &'static str)],
+}
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+static BUILD_INFO: BuildInfo = BuildInfo {
+	git_hash: env!("TOYBOX_GIT_HASH"),
+	git_dirty: matches!(env!("TOYBOX_GIT_DIRTY").as_bytes(), b"true"),
+	build_date: env!("TOYBOX_BUILD_DATE"),
+	profile: env!("TOYBOX_BUILD_PROFILE"),
+	target: env!("TOYBOX_BUILD_TARGET"),
+	rustc_version: env!("TOYBOX_RUSTC_VERSION"),
+
+	features: &[
+		("tracy", cfg!(feature="tracy")),
+		("gamepad", cfg!(feature="gamepad")),
+		("steam", cfg!(feature="steam")),
+		("debug-uniforms", cfg!(feature="debug-uniforms")),
+		("dialogs", cfg!(feature="dialogs")),
+		("xr", cfg!(feature="xr")),
+		("taskbar-progress", cfg!(feature="taskbar-progress")),
+		("embed-resources", cfg!(feature="embed-resources")),
+	],
+
+	crate_versions: &[
+		("toybox", VERSION),
+		("toybox-host", toybox_host::VERSION),
+		("toybox-gfx", toybox_gfx::VERSION),
+		("toybox-audio", toybox_audio::VERSION),
+		("toybox-input", toybox_input::VERSION),
+		("toybox-egui", toybox_egui::VERSION),
+		("toybox-cfg", toybox_cfg::VERSION),
+		("toybox-vfs", toybox_vfs::VERSION),
+		("toybox-bus", toybox_bus::VERSION),
+		("toybox-handle", toybox_handle::VERSION),
+		("toybox-platform", toybox_platform::VERSION),
+	],
+};
+
+
+/// Details of the engine build, for bug reports. Available before [`crate::run`] is called.
+pub fn build_info() -> &'static BuildInfo {
+	&BUILD_INFO
+}
+
+
+impl BuildInfo {
+	pub fn version(&self) -> &'static str {
+		VERSION
+	}
+
+	/// e.g., `toybox 0.5.0 (1a2b3c4d5e-dirty, release)`.
+	pub fn summary(&self) -> String {
+		let dirty = if self.git_dirty { "-dirty" } else { "" };
+		format!("toybox {} ({}{dirty}, {})", self.version(), self.git_hash, self.profile)
+	}
+
+	pub fn is_feature_enabled(&self, feature: &str) -> bool {
+		self.features.iter()
+			.any(|&(name, enabled)| name == feature && enabled)
+	}
+}
+
+impl std::fmt::Display for BuildInfo {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "{}", self.summary())?;
+		writeln!(f, "built: {}", self.build_date)?;
+		writeln!(f, "target: {}", self.target)?;
+		writeln!(f, "compiler: {}", self.rustc_version)?;
+
+		let enabled: Vec<_> = self.features.iter()
+			.filter(|&&(_, enabled)| enabled)
+			.map(|&(name, _)| name)
+			.collect();
+
+		if enabled.is_empty() {
+			writeln!(f, "features: none")?;
+		} else {
+			writeln!(f, "features: {}", enabled.join(", "))?;
+		}
+
+		let versions: Vec<_> = self.crate_versions.iter()
+			.map(|&(name, version)| format!("{name} {version}"))
+			.collect();
+
+		write!(f, "crates: {}", versions.join(", "))
+	}
+}
+
+
+/// Append build info to panic messages, so that crash reports say which build crashed.
+/// Chains to whichever hook was installed before.
+pub(crate) fn install_panic_hook() {
+	let previous_hook = std::panic::take_hook();
+
+	std::panic::set_hook(Box::new(move |info| {
+		previous_hook(info);
+		eprintln!("\n{}", build_info());
+		log::error!("Panicked in {}", build_info().summary());
+	}));
+}
@@ -0,0 +1,104 @@
+use crate::prelude::*;
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+
+/// Access to the system clipboard.
+/// Failures are logged rather than returned, since there's rarely anything useful to do about them.
+pub struct Clipboard {
+	inner: Option<arboard::Clipboard>,
+	pending_screenshot: Rc<RefCell<Option<ClipboardImage>>>,
+}
+
+struct ClipboardImage {
+	size: Vec2i,
+	/// Tightly packed rgba8, top row first.
+	data: Vec<u8>,
+}
+
+impl Clipboard {
+	pub(crate) fn new() -> Clipboard {
+		let inner = arboard::Clipboard::new()
+			.inspect_err(|error| log::warn!("Failed to initialise clipboard: {error}"))
+			.ok();
+
+		Clipboard {
+			inner,
+			pending_screenshot: Rc::new(RefCell::new(None)),
+		}
+	}
+
+	pub fn get_text(&mut self) -> Option<String> {
+		let inner = self.inner.as_mut()?;
+
+		inner.get_text()
+			.inspect_err(|error| log::warn!("Failed to get clipboard text: {error}"))
+			.ok()
+	}
+
+	pub fn set_text(&mut self, text: impl Into<String>) {
+		let Some(inner) = &mut self.inner else { return };
+
+		if let Err(error) = inner.set_text(text.into()) {
+			log::warn!("Failed to set clipboard text: {error}");
+		}
+	}
+
+	/// `data` is expected to be tightly packed rgba8 texels, with the top row first.
+	pub fn set_image_rgba8(&mut self, size: Vec2i, data: &[u8]) {
+		let Some(inner) = &mut self.inner else { return };
+
+		let expected_len = 4 * size.x.max(0) as usize * size.y.max(0) as usize;
+		if data.len() != expected_len {
+			log::warn!("Failed to set clipboard image: expected {expected_len} bytes for a {}x{} image, got {}", size.x, size.y, data.len());
+			return
+		}
+
+		let image = arboard::ImageData {
+			width: size.x as usize,
+			height: size.y as usize,
+			bytes: Cow::Borrowed(data),
+		};
+
+		if let Err(error) = inner.set_image(image) {
+			log::warn!("Failed to set clipboard image: {error}");
+		}
+	}
+
+	/// Schedules a readback of the backbuffer at the end of the current frame, which will be copied into the
+	/// clipboard once the frame has been executed.
+	pub fn copy_screenshot(&mut self, gfx: &mut gfx::System) {
+		let pending_screenshot = self.pending_screenshot.clone();
+
+		gfx.frame_encoder.command_group(gfx::FrameStage::Final)
+			.execute(move |core, _| {
+				let size = core.backbuffer_size();
+				let data = read_backbuffer_rgba8(core, size);
+				*pending_screenshot.borrow_mut() = Some(ClipboardImage { size, data });
+			});
+	}
+
+	/// Called after the frame has been executed, to commit any readbacks requested during the frame.
+	pub(crate) fn flush_pending(&mut self) {
+		let Some(ClipboardImage{size, data}) = self.pending_screenshot.borrow_mut().take() else { return };
+		self.set_image_rgba8(size, &data);
+	}
+}
+
+
+/// Read back `size` texels of the backbuffer as tightly packed rgba8, top row first.
+pub(crate) fn read_backbuffer_rgba8(core: &gfx::Core, size: Vec2i) -> Vec<u8> {
+	let mut data = core.read_framebuffer_rgba8(None, size);
+
+	// GL images are bottom row first, but everything else expects the top row first.
+	let row_size = 4 * size.x as usize;
+	let num_rows = size.y as usize;
+	for row in 0..num_rows/2 {
+		let (top, bottom) = data.split_at_mut((num_rows - row - 1) * row_size);
+		top[row * row_size..(row + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+	}
+
+	data
+}
@@ -0,0 +1,641 @@
+//! Drop-down developer console, quake style. Commands are registered with typed arguments, and can be bound to config
+//! keys, debug toggles or arbitrary closures. Log output is captured and shown alongside command output.
+//!
+//! Toggled by the `toggle_console` input binding, backquote by default - see [`crate::settings::InputSettings`].
+//! Escape also closes it while typing.
+//!
+//! ```ignore
+//! ctx.console.register(ConsoleCommand::new("spawn", |ctx, args| {
+//! 	let count = args.first().and_then(ArgValue::as_int).unwrap_or(1);
+//! 	ctx.console.print(format!("Spawning {count} things"));
+//! 	Ok(())
+//! })
+//! 	.optional_arg("count", ArgKind::Int)
+//! 	.help("Spawn some things"));
+//! ```
+
+use crate::prelude::*;
+use crate::Context;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::rc::Rc;
+use std::sync::mpsc;
+
+#[cfg(test)]
+mod test;
+
+
+/// Input binding action that opens and closes the console.
+pub const TOGGLE_CONSOLE_ACTION: &str = "toggle_console";
+
+const MAX_LINES: usize = 1000;
+const MAX_HISTORY: usize = 100;
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ArgKind {
+	Bool,
+	Int,
+	Float,
+	String,
+}
+
+impl ArgKind {
+	pub fn parse(self, token: &str) -> anyhow::Result<ArgValue> {
+		let value = match self {
+			ArgKind::Bool => match token {
+				"1" | "true" | "on" => ArgValue::Bool(true),
+				"0" | "false" | "off" => ArgValue::Bool(false),
+				_ => anyhow::bail!("expected a bool, got '{token}'"),
+			}
+
+			ArgKind::Int => ArgValue::Int(token.parse().with_context(|| format!("expected an int, got '{token}'"))?),
+			ArgKind::Float => ArgValue::Float(token.parse().with_context(|| format!("expected a float, got '{token}'"))?),
+			ArgKind::String => ArgValue::String(token.to_owned()),
+		};
+
+		Ok(value)
+	}
+
+	pub fn name(self) -> &'static str {
+		match self {
+			ArgKind::Bool => "bool",
+			ArgKind::Int => "int",
+			ArgKind::Float => "float",
+			ArgKind::String => "string",
+		}
+	}
+}
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+	Bool(bool),
+	Int(i64),
+	Float(f64),
+	String(String),
+}
+
+impl ArgValue {
+	pub fn as_bool(&self) -> Option<bool> {
+		match *self {
+			ArgValue::Bool(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	pub fn as_int(&self) -> Option<i64> {
+		match *self {
+			ArgValue::Int(value) => Some(value),
+			_ => None,
+		}
+	}
+
+	/// Ints are also accepted.
+	pub fn as_float(&self) -> Option<f64> {
+		match *self {
+			ArgValue::Float(value) => Some(value),
+			ArgValue::Int(value) => Some(value as f64),
+			_ => None,
+		}
+	}
+
+	pub fn as_str(&self) -> Option<&str> {
+		match self {
+			ArgValue::String(value) => Some(value.as_str()),
+			_ => None,
+		}
+	}
+}
+
+impl From<ArgValue> for cfg::Value {
+	fn from(value: ArgValue) -> cfg::Value {
+		match value {
+			ArgValue::Bool(value) => cfg::Value::Boolean(value),
+			ArgValue::Int(value) => cfg::Value::Integer(value),
+			ArgValue::Float(value) => cfg::Value::Float(value),
+			ArgValue::String(value) => cfg::Value::String(value),
+		}
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ArgSpec {
+	pub name: String,
+	pub kind: ArgKind,
+
+	/// Optional arguments can only be followed by other optional arguments.
+	pub optional: bool,
+}
+
+
+/// Called with one value per argument given, which may be fewer than the number of arguments registered if trailing
+/// arguments are optional.
+pub type CommandHandler = dyn FnMut(&mut Context, &[ArgValue]) -> anyhow::Result<()>;
+
+
+pub struct ConsoleCommand {
+	pub name: String,
+	pub help: String,
+	pub args: Vec<ArgSpec>,
+
+	// Shared so the handler can be called while the console itself is borrowed through the Context.
+	handler: Rc<RefCell<CommandHandler>>,
+}
+
+impl ConsoleCommand {
+	pub fn new(name: impl Into<String>, handler: impl FnMut(&mut Context, &[ArgValue]) -> anyhow::Result<()> + 'static) -> ConsoleCommand {
+		ConsoleCommand {
+			name: name.into(),
+			help: String::new(),
+			args: Vec::new(),
+			handler: Rc::new(RefCell::new(handler)),
+		}
+	}
+
+	pub fn help(self, help: impl Into<String>) -> Self {
+		Self { help: help.into(), .. self }
+	}
+
+	/// Panics if an optional argument has already been added, since then it would be ambiguous which arguments were
+	/// given.
+	pub fn arg(mut self, name: impl Into<String>, kind: ArgKind) -> Self {
+		let name = name.into();
+		assert!(self.args.iter().all(|arg| !arg.optional),
+			"Console command '{}': required argument '{name}' can't follow optional arguments", self.name);
+
+		self.args.push(ArgSpec { name, kind, optional: false });
+		self
+	}
+
+	pub fn optional_arg(mut self, name: impl Into<String>, kind: ArgKind) -> Self {
+		self.args.push(ArgSpec { name: name.into(), kind, optional: true });
+		self
+	}
+
+	/// e.g., `spawn <kind: string> [count: int]`
+	pub fn signature(&self) -> String {
+		let mut signature = self.name.clone();
+
+		for ArgSpec{name, kind, optional} in self.args.iter() {
+			match optional {
+				true => signature += &format!(" [{name}: {}]", kind.name()),
+				false => signature += &format!(" <{name}: {}>", kind.name()),
+			}
+		}
+
+		signature
+	}
+
+	fn parse_args(&self, tokens: &[String]) -> anyhow::Result<Vec<ArgValue>> {
+		let required = self.args.iter().filter(|arg| !arg.optional).count();
+
+		if tokens.len() < required || tokens.len() > self.args.len() {
+			anyhow::bail!("usage: {}", self.signature());
+		}
+
+		self.args.iter().zip(tokens)
+			.map(|(spec, token)| spec.kind.parse(token).with_context(|| format!("argument '{}'", spec.name)))
+			.collect()
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ConsoleLine {
+	/// None for command input and output, otherwise the level of a captured log record.
+	pub level: Option<log::Level>,
+	pub text: String,
+}
+
+
+pub struct Console {
+	pub open: bool,
+
+	/// Button that opens and closes the console. Kept in sync with the `toggle_console` binding.
+	pub toggle_button: input::Button,
+
+	commands: BTreeMap<String, ConsoleCommand>,
+	lines: VecDeque<ConsoleLine>,
+	log_rx: mpsc::Receiver<host::LogEntry>,
+
+	history: Vec<String>,
+	/// Index into `history` while browsing it with the arrow keys.
+	history_cursor: Option<usize>,
+
+	input: String,
+	focus_input: bool,
+}
+
+impl Console {
+	pub fn new() -> Console {
+		let mut console = Console {
+			open: false,
+			toggle_button: input::keys::Backquote.into(),
+
+			commands: BTreeMap::new(),
+			lines: VecDeque::new(),
+			log_rx: host::tap_logs(),
+
+			history: Vec::new(),
+			history_cursor: None,
+
+			input: String::new(),
+			focus_input: false,
+		};
+
+		console.register_builtin_commands();
+		console
+	}
+
+	/// Replaces any existing command with the same name.
+	pub fn register(&mut self, command: ConsoleCommand) {
+		if self.commands.contains_key(&command.name) {
+			log::warn!("Replacing console command '{}'", command.name);
+		}
+
+		self.commands.insert(command.name.clone(), command);
+	}
+
+	pub fn unregister(&mut self, name: &str) {
+		self.commands.remove(name);
+	}
+
+	/// Register a command that shows the value of config key `key` when called without arguments, or previews a new
+	/// value when called with one. See [`cfg::Config::preview_value`].
+	pub fn register_config(&mut self, name: impl Into<String>, key: impl Into<String>, kind: ArgKind) {
+		let key = key.into();
+		let help = format!("Show or preview config '{key}'");
+
+		self.register(ConsoleCommand::new(name, move |ctx, args| {
+			match args.first() {
+				Some(value) => ctx.cfg.preview_value(&key, value.clone()),
+				None => match ctx.cfg.get_value(&key) {
+					Some(value) => ctx.console.print(format!("{key} = {value}")),
+					None => ctx.console.print(format!("{key} is not set")),
+				}
+			}
+
+			Ok(())
+		})
+			.optional_arg("value", kind)
+			.help(help));
+	}
+
+	/// Register a command that flips a debug toggle when called without arguments, or sets it when called with one.
+	pub fn register_toggle(&mut self, name: impl Into<String>, help: impl Into<String>,
+		get: impl Fn(&Context) -> bool + 'static, set: impl Fn(&mut Context, bool) + 'static)
+	{
+		let name = name.into();
+		let label = name.clone();
+
+		self.register(ConsoleCommand::new(name, move |ctx, args| {
+			let enabled = args.first()
+				.and_then(ArgValue::as_bool)
+				.unwrap_or_else(|| !get(ctx));
+
+			set(ctx, enabled);
+			ctx.console.print(format!("{label} = {enabled}"));
+			Ok(())
+		})
+			.optional_arg("enabled", ArgKind::Bool)
+			.help(help));
+	}
+
+	pub fn commands(&self) -> impl Iterator<Item=&ConsoleCommand> {
+		self.commands.values()
+	}
+
+	pub fn print(&mut self, text: impl Into<String>) {
+		self.push_line(None, text.into());
+	}
+
+	pub fn clear(&mut self) {
+		self.lines.clear();
+	}
+
+	pub fn lines(&self) -> impl Iterator<Item=&ConsoleLine> {
+		self.lines.iter()
+	}
+
+	/// Previously run commands, oldest first.
+	pub fn history(&self) -> &[String] {
+		&self.history
+	}
+
+	/// Names of commands starting with `prefix`.
+	pub fn completions(&self, prefix: &str) -> Vec<&str> {
+		self.commands.range(prefix.to_owned()..)
+			.map(|(name, _)| name.as_str())
+			.take_while(|name| name.starts_with(prefix))
+			.collect()
+	}
+
+	/// Extends the command name in `input` as far as all completions agree, or adds a space if it's already complete.
+	pub fn complete(&self, input: &str) -> Option<String> {
+		let prefix = input.trim_start();
+		if prefix.contains(char::is_whitespace) {
+			return None
+		}
+
+		let completions = self.completions(prefix);
+		let (first, rest) = completions.split_first()?;
+
+		if rest.is_empty() {
+			return Some(format!("{first} "))
+		}
+
+		let common_prefix = rest.iter()
+			.fold(*first, |common, name| {
+				let len = common.char_indices()
+					.zip(name.chars())
+					.find(|((_, a), b)| a != b)
+					.map_or(common.len().min(name.len()), |((index, _), _)| index);
+
+				&common[..len]
+			});
+
+		Some(common_prefix.to_owned())
+	}
+
+	pub(crate) fn update(&mut self) {
+		// Drain logs even while closed, so they don't build up.
+		while let Ok(entry) = self.log_rx.try_recv() {
+			self.push_line(Some(entry.level), format!("[{}] {}", entry.target, entry.message));
+		}
+	}
+
+	pub(crate) fn toggle(&mut self) {
+		self.open = !self.open;
+		self.focus_input = self.open;
+	}
+
+	fn push_line(&mut self, level: Option<log::Level>, text: String) {
+		if self.lines.len() >= MAX_LINES {
+			self.lines.pop_front();
+		}
+
+		self.lines.push_back(ConsoleLine { level, text });
+	}
+
+	fn push_history(&mut self, line: &str) {
+		self.history_cursor = None;
+
+		if self.history.last().map(String::as_str) == Some(line) {
+			return
+		}
+
+		if self.history.len() >= MAX_HISTORY {
+			self.history.remove(0);
+		}
+
+		self.history.push(line.to_owned());
+	}
+
+	fn browse_history(&mut self, older: bool) {
+		if self.history.is_empty() {
+			return
+		}
+
+		self.history_cursor = match (self.history_cursor, older) {
+			(None, true) => Some(self.history.len() - 1),
+			(None, false) => None,
+			(Some(index), true) => Some(index.saturating_sub(1)),
+			(Some(index), false) => (index + 1 < self.history.len()).then_some(index + 1),
+		};
+
+		self.input = match self.history_cursor {
+			Some(index) => self.history[index].clone(),
+			None => String::new(),
+		};
+	}
+
+	fn register_builtin_commands(&mut self) {
+		self.register(ConsoleCommand::new("help", |ctx, args| {
+			let lines: Vec<String> = match args.first().and_then(ArgValue::as_str) {
+				Some(name) => match ctx.console.commands.get(name) {
+					Some(command) => vec![command.signature(), command.help.clone()],
+					None => anyhow::bail!("unknown command '{name}'"),
+				}
+
+				None => ctx.console.commands()
+					.map(|command| format!("{} - {}", command.signature(), command.help))
+					.collect(),
+			};
+
+			for line in lines {
+				ctx.console.print(line);
+			}
+
+			Ok(())
+		})
+			.optional_arg("command", ArgKind::String)
+			.help("List commands, or show usage for one"));
+
+		self.register(ConsoleCommand::new("clear", |ctx, _| {
+			ctx.console.clear();
+			Ok(())
+		})
+			.help("Clear console output"));
+
+		self.register(ConsoleCommand::new("quit", |ctx, _| {
+			ctx.wants_quit = true;
+			Ok(())
+		})
+			.help("Quit the app"));
+
+		self.register(ConsoleCommand::new("cfg", |ctx, args| {
+			let key = args[0].as_str().unwrap_or_default();
+
+			match args.get(1).and_then(ArgValue::as_str) {
+				Some(value) => ctx.cfg.preview_value_from_str(key, value),
+				None => match ctx.cfg.get_value(key) {
+					Some(value) => ctx.console.print(format!("{key} = {value}")),
+					None => ctx.console.print(format!("{key} is not set")),
+				}
+			}
+
+			Ok(())
+		})
+			.arg("key", ArgKind::String)
+			.optional_arg("value", ArgKind::String)
+			.help("Show or preview any config value. Values are parsed as toml where possible"));
+
+		self.register_toggle("debug_menu", "Show the debug menu",
+			|ctx| ctx.show_debug_menu,
+			|ctx, enabled| ctx.show_debug_menu = enabled);
+
+		self.register_toggle("gfx_validation", "Validate gfx commands before dispatch",
+			|ctx| ctx.gfx.is_validation_enabled(),
+			|ctx, enabled| ctx.gfx.set_validation_enabled(enabled));
+
+		self.register_toggle("determinism_audit", "Hash input, rng and gfx commands every frame",
+			|ctx| ctx.determinism.is_enabled(),
+			|ctx, enabled| ctx.set_determinism_audit(enabled));
+	}
+}
+
+impl Default for Console {
+	fn default() -> Self {
+		Console::new()
+	}
+}
+
+
+impl Context {
+	/// Parse and run a single console command, as if it were typed into the console. Errors are printed to the console.
+	pub fn run_console_command(&mut self, line: &str) {
+		let line = line.trim();
+		if line.is_empty() {
+			return
+		}
+
+		self.console.print(format!("> {line}"));
+		self.console.push_history(line);
+
+		if let Err(error) = self.try_run_console_command(line) {
+			self.console.print(format!("error: {error:#}"));
+		}
+	}
+
+	fn try_run_console_command(&mut self, line: &str) -> anyhow::Result<()> {
+		let tokens = tokenize(line);
+		let (name, arg_tokens) = tokens.split_first()
+			.context("empty command")?;
+
+		let command = self.console.commands.get(name.as_str())
+			.with_context(|| format!("unknown command '{name}' - try 'help'"))?;
+
+		let args = command.parse_args(arg_tokens)?;
+		let handler = Rc::clone(&command.handler);
+
+		let mut handler = handler.try_borrow_mut()
+			.map_err(|_| anyhow::format_err!("'{name}' can't run itself"))?;
+
+		(*handler)(self, &args)
+	}
+}
+
+
+/// Splits on whitespace, except within double quotes.
+fn tokenize(line: &str) -> Vec<String> {
+	let mut tokens = Vec::new();
+	let mut current = None;
+	let mut in_quotes = false;
+
+	for ch in line.chars() {
+		match ch {
+			'"' => {
+				in_quotes = !in_quotes;
+				current.get_or_insert_with(String::new);
+			}
+
+			ch if ch.is_whitespace() && !in_quotes => {
+				tokens.extend(current.take());
+			}
+
+			ch => current.get_or_insert_with(String::new).push(ch),
+		}
+	}
+
+	tokens.extend(current);
+	tokens
+}
+
+
+#[instrument(skip_all, name="toybox console::show")]
+pub(crate) fn show(ctx: &mut Context) {
+	let egui_ctx = ctx.egui.clone();
+	let mut submitted = None;
+
+	egui::TopBottomPanel::top("toybox_console")
+		.resizable(true)
+		.default_height(egui_ctx.screen_rect().height() / 3.0)
+		.show_animated(&egui_ctx, ctx.console.open, |ui| {
+			let console = &mut ctx.console;
+			let input_height = ui.spacing().interact_size.y * 2.0;
+
+			egui::ScrollArea::vertical()
+				.auto_shrink(false)
+				.stick_to_bottom(true)
+				.max_height(ui.available_height() - input_height)
+				.show(ui, |ui| {
+					for ConsoleLine{level, text} in console.lines.iter() {
+						let color = match level {
+							None => ui.visuals().strong_text_color(),
+							Some(log::Level::Error) => ui.visuals().error_fg_color,
+							Some(log::Level::Warn) => ui.visuals().warn_fg_color,
+							Some(_) => ui.visuals().weak_text_color(),
+						};
+
+						ui.label(egui::RichText::new(text).monospace().color(color));
+					}
+				});
+
+			let input_id = egui::Id::new("toybox_console_input");
+
+			// The toggle button doesn't reach the input system while egui has keyboard focus, so escape closes instead.
+			if ui.memory(|memory| memory.has_focus(input_id)) {
+				if ui.input(|input| input.key_pressed(egui::Key::Escape)) {
+					console.open = false;
+				}
+
+				if ui.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::Tab)) {
+					if let Some(completed) = console.complete(&console.input) {
+						console.input = completed;
+						move_cursor_to_end(ui, input_id, &console.input);
+					}
+				}
+
+				if ui.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp)) {
+					console.browse_history(true);
+					move_cursor_to_end(ui, input_id, &console.input);
+				}
+
+				if ui.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown)) {
+					console.browse_history(false);
+					move_cursor_to_end(ui, input_id, &console.input);
+				}
+			}
+
+			let response = ui.add(egui::TextEdit::singleline(&mut console.input)
+				.id(input_id)
+				.font(egui::TextStyle::Monospace)
+				.desired_width(f32::INFINITY)
+				.hint_text("help"));
+
+			if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+				submitted = Some(std::mem::take(&mut console.input));
+				response.request_focus();
+			}
+
+			if std::mem::take(&mut console.focus_input) {
+				response.request_focus();
+			}
+
+			// Hint at possible completions while the command name is still being typed.
+			let prefix = console.input.trim_start();
+			if !prefix.is_empty() && !prefix.contains(char::is_whitespace) {
+				let completions = console.completions(prefix);
+				if !completions.is_empty() {
+					ui.weak(completions.join("  "));
+				}
+			}
+		});
+
+	if let Some(line) = submitted {
+		ctx.run_console_command(&line);
+	}
+}
+
+fn move_cursor_to_end(ui: &egui::Ui, id: egui::Id, text: &str) {
+	use egui::text::{CCursor, CCursorRange};
+
+	if let Some(mut state) = egui::TextEdit::load_state(ui.ctx(), id) {
+		let end = CCursor::new(text.chars().count());
+		state.cursor.set_char_range(Some(CCursorRange::one(end)));
+		state.store(ui.ctx(), id);
+	}
+}
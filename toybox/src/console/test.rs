@@ -0,0 +1,79 @@
+use super::*;
+
+
+fn command(name: &str) -> ConsoleCommand {
+	ConsoleCommand::new(name, |_, _| Ok(()))
+}
+
+fn tokens(strings: &[&str]) -> Vec<String> {
+	strings.iter().map(|s| s.to_string()).collect()
+}
+
+
+#[test]
+fn tokenize_splits_on_whitespace() {
+	assert_eq!(tokenize("spawn  crate\t3 "), tokens(&["spawn", "crate", "3"]));
+	assert_eq!(tokenize("   "), Vec::<String>::new());
+}
+
+#[test]
+fn tokenize_keeps_quoted_whitespace() {
+	assert_eq!(tokenize(r#"say "hello there" world"#), tokens(&["say", "hello there", "world"]));
+	assert_eq!(tokenize(r#"set name "" 1"#), tokens(&["set", "name", "", "1"]));
+	assert_eq!(tokenize(r#"a"b c"d"#), tokens(&["ab cd"]));
+}
+
+#[test]
+fn parse_args_checks_counts() {
+	let spawn = command("spawn")
+		.arg("kind", ArgKind::String)
+		.optional_arg("count", ArgKind::Int);
+
+	assert_eq!(spawn.parse_args(&tokens(&["crate"])).unwrap(), vec![ArgValue::String("crate".into())]);
+	assert_eq!(spawn.parse_args(&tokens(&["crate", "3"])).unwrap(),
+		vec![ArgValue::String("crate".into()), ArgValue::Int(3)]);
+
+	let error = spawn.parse_args(&[]).unwrap_err();
+	assert_eq!(error.to_string(), "usage: spawn <kind: string> [count: int]");
+
+	assert!(spawn.parse_args(&tokens(&["crate", "3", "extra"])).is_err());
+}
+
+#[test]
+fn parse_args_checks_kinds() {
+	let set = command("set")
+		.arg("enabled", ArgKind::Bool)
+		.arg("scale", ArgKind::Float);
+
+	assert_eq!(set.parse_args(&tokens(&["on", "2"])).unwrap(), vec![ArgValue::Bool(true), ArgValue::Float(2.0)]);
+	assert_eq!(set.parse_args(&tokens(&["0", "-0.5"])).unwrap(), vec![ArgValue::Bool(false), ArgValue::Float(-0.5)]);
+
+	let error = set.parse_args(&tokens(&["maybe", "2"])).unwrap_err();
+	assert_eq!(format!("{error:#}"), "argument 'enabled': expected a bool, got 'maybe'");
+
+	assert!(set.parse_args(&tokens(&["true", "big"])).is_err());
+}
+
+#[test]
+fn complete_extends_to_common_prefix() {
+	let mut console = Console::new();
+	console.register(command("spawn"));
+	console.register(command("spawn_many"));
+	console.register(command("speed"));
+
+	assert_eq!(console.completions("spa"), vec!["spawn", "spawn_many"]);
+
+	assert_eq!(console.complete("sp").as_deref(), Some("sp"));
+	assert_eq!(console.complete("spa").as_deref(), Some("spawn"));
+	assert_eq!(console.complete("spawn_").as_deref(), Some("spawn_many "));
+	assert_eq!(console.complete("  spe").as_deref(), Some("speed "));
+}
+
+#[test]
+fn complete_ignores_unknown_commands_and_arguments() {
+	let mut console = Console::new();
+	console.register(command("spawn").arg("kind", ArgKind::String));
+
+	assert_eq!(console.complete("xyz"), None);
+	assert_eq!(console.complete("spawn cr"), None);
+}
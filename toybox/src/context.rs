@@ -1,23 +1,109 @@
 use crate::prelude::*;
+use crate::clipboard::Clipboard;
+use crate::window::Window;
+use crate::determinism::DeterminismAudit;
+use crate::assets::Assets;
+use crate::input_routing::{InputRouter, InputLayer};
+use crate::time::Time;
+use crate::frame_pacing::FramePacing;
+use crate::stage_conditions::StageConditions;
+use crate::sound_metadata::SoundLibrary;
+use crate::sound_events::SoundEvents;
+use crate::palette::PaletteLibrary;
+use crate::profiler::Profiler;
+use crate::tasks::TaskScheduler;
+use crate::autosave::Autosave;
+use crate::ipc::IpcServer;
+use crate::dialogs::Dialogs;
+use crate::console::Console;
+use crate::world_labels::WorldLabels;
 
 pub struct Context {
 	pub gfx: Box<gfx::System>,
 	pub audio: audio::System,
+
+	/// Crossfading music playback, with volume persisted in [`crate::settings::AudioSettings`].
+	/// Its source must be taken with [`audio::MusicPlayer::take_source`] and set as, or mixed into, the audio provider.
+	pub music: audio::MusicPlayer,
+
+	/// Sidecar metadata for sounds, reloaded when changed on disk.
+	pub sounds: SoundLibrary,
+
+	/// Resolves [`crate::SoundEvent`]s emitted on the bus into [`crate::PlaySound`]s, according to a data-driven table.
+	pub sound_events: SoundEvents,
+
+	/// Color palettes loaded from json resources, reloaded when changed on disk.
+	pub palettes: PaletteLibrary,
+
+	/// Timings for app code scopes. See [`Context::profile_scope`].
+	pub profiler: Profiler,
+
+	/// Incremental main thread work, run in a time slice each frame.
+	pub tasks: TaskScheduler,
+
+	/// Periodic and requested saves of registered state, see [`crate::autosave`].
+	pub autosave: Autosave,
+
+	/// Native open/save dialogs, if built with the `dialogs` feature.
+	pub dialogs: Dialogs,
+
+	/// Drop-down developer console. Apps can register their own commands with [`Console::register`].
+	pub console: Console,
+
+	/// Text anchored in the world, drawn over the scene at the end of the frame.
+	pub world_labels: WorldLabels,
+
+	/// Experimental VR output, if requested with [`host::Settings::xr`] and a session could be started.
+	#[cfg(feature="xr")]
+	pub xr: Option<crate::xr::Xr>,
+
+	/// Streams frame events to external tools, if enabled with `ipc.enabled`.
+	pub ipc: Option<IpcServer>,
+
 	pub input: input::System,
 	pub egui: egui::Context,
 	pub cfg: cfg::Config,
 	pub vfs: vfs::Vfs,
 	pub bus: bus::MessageBus,
+	pub platform: Box<dyn platform::PlatformServices>,
+	pub clipboard: Clipboard,
+	pub window: Window,
+	pub determinism: DeterminismAudit,
+	pub assets: Assets,
+	pub time: Time,
+	pub frame_pacing: FramePacing,
 
-	pub(super) egui_integration: egui_backend::Integration,
+	/// Stages and annotated groups that are only dispatched while config conditions hold.
+	pub stage_conditions: StageConditions,
 
-	pub(super) egui_claiming_input_gate: Gate,
+	/// Decides whether egui, game UI or the world should respond to input.
+	pub input_router: InputRouter,
+
+	/// Gamepad driven focus navigation for game menus, and egui if `drive_egui` is set.
+	/// Can be enabled for egui with [`InputSettings::gamepad_ui_navigation`](crate::settings::InputSettings::gamepad_ui_navigation).
+	pub ui_navigation: input::UiNavigation,
+
+	pub(super) egui_integration: egui_backend::Integration,
 
 	// TODO(pat.m): might want to be able to disable this.
 	/// Whether or not to show the built in debug menu.
 	/// Can be toggled by F1.
 	pub show_debug_menu: bool,
 	pub wants_quit: bool,
+
+	/// If set, [`App::update`](crate::App::update) will be called at this fixed interval in seconds,
+	/// possibly multiple times per frame. Otherwise it is called exactly once per frame.
+	/// Values that aren't positive and finite are treated as None.
+	pub fixed_timestep: Option<f32>,
+
+	/// Only set while the app is being constructed.
+	pub(super) splash_screen: Option<std::rc::Rc<host::SplashScreen>>,
+
+	/// When settings were last changed, if they haven't been saved since. See [`crate::settings`].
+	pub(super) settings_changed_at: Option<std::time::Instant>,
+
+	pub(super) fixed_timestep_accumulator: f32,
+	pub(super) fixed_tick: u64,
 }
 
 impl Context {
@@ -25,6 +111,13 @@ impl Context {
 	#[instrument(skip_all, name="toybox prepare_frame")]
 	pub(crate) fn prepare_frame(&mut self) {
 		self.audio.update();
+		self.music.update();
+		self.vfs.update();
+		self.sounds.update(&self.vfs);
+		self.palettes.update(&self.vfs);
+		self.dialogs.update(&self.vfs);
+		self.console.update();
+		self.platform.update();
 		self.input.reset_tracker();
 		self.bus.garbage_collect();
 	}
@@ -32,14 +125,30 @@ impl Context {
 	// Called after events are processed, immediately before control is passed to the app.
 	#[instrument(skip_all, name="toybox start_frame")]
 	pub(crate) fn start_frame(&mut self) {
+		self.time.start_frame(&self.audio);
+		self.frame_pacing.start_frame(self.time.real_delta_time());
+
 		self.gfx.start_frame();
+
+		#[cfg(feature="xr")]
+		self.start_xr_frame();
+
+		self.stage_conditions.apply(&self.cfg, &mut self.gfx.frame_encoder);
+		self.process_ipc_commands();
 		self.input.process();
+		self.determinism.record_input(&self.input);
+		self.update_ui_navigation();
 		self.egui = self.egui_integration.start_frame();
+		self.claim_egui_input();
 
 		if self.input.button_just_down(input::keys::F1) {
 			self.show_debug_menu = !self.show_debug_menu;
 		}
 
+		if self.input.button_just_down(self.console.toggle_button.clone()) {
+			self.console.toggle();
+		}
+
 		if self.input.button_down(input::keys::Control)
 			&& self.input.button_just_down(input::keys::KeyQ)
 		{
@@ -47,30 +156,174 @@ impl Context {
 		}
 	}
 
+	fn update_ui_navigation(&mut self) {
+		self.ui_navigation.update(&self.input.tracker, self.time.real_delta_time());
+
+		if self.ui_navigation.drive_egui {
+			// Still last frame's focus, since egui hasn't started this frame yet.
+			let egui_has_focus = self.egui.memory(|memory| memory.focused().is_some());
+			self.egui_integration.queue_events(self.ui_navigation.egui_events(egui_has_focus));
+		}
+	}
+
+	fn claim_egui_input(&mut self) {
+		if self.egui.wants_pointer_input() {
+			self.input_router.claim_pointer(InputLayer::EGUI);
+		}
+
+		if self.egui.wants_keyboard_input() {
+			self.input_router.claim_keyboard(InputLayer::EGUI);
+		}
+	}
+
+	/// Render the scene at a fixed resolution, integer scaled to fit the window. Ui is still rendered at full resolution.
+	/// Mouse positions reported by [`input::System`] will be relative to the low res target while enabled.
+	pub fn set_low_res_mode(&mut self, size: impl Into<Option<Vec2i>>) {
+		self.gfx.set_low_res_mode(size);
+		self.update_pixel_mapping();
+	}
+
+	fn update_pixel_mapping(&mut self) {
+		let mapping = self.gfx.low_res_viewport()
+			.zip(self.gfx.low_res_size())
+			.map(|(viewport, size)| input::PixelMapping {
+				offset: viewport.min,
+				scale: viewport.size().x / size.x,
+				size,
+			});
+
+		self.input.set_pixel_mapping(mapping);
+	}
+
+	/// Update the loading screen configured with [`host::Settings::splash`]. Only has an effect while the app is
+	/// being constructed. `progress` should be in [0, 1].
+	pub fn report_loading_progress(&self, progress: f32) {
+		if let Some(splash_screen) = &self.splash_screen {
+			splash_screen.present(Some(progress));
+		}
+	}
+
+	/// Number of fixed timestep updates run so far - during [`App::update`](crate::App::update), the tick being
+	/// simulated. Doesn't advance without [`Context::fixed_timestep`]. For keying [`crate::SnapshotRing`]s.
+	pub fn fixed_tick(&self) -> u64 {
+		self.fixed_tick
+	}
+
+	/// Hash input, rng draws and gfx commands every frame so that divergence between runs can be found.
+	/// See [`DeterminismAudit`].
+	pub fn set_determinism_audit(&mut self, enabled: bool) {
+		self.determinism.set_enabled(enabled);
+		self.gfx.set_command_hashing(enabled);
+	}
+
+	/// Run `f` with rendering paused - for blocking native dialogs and the like, which would otherwise cause a huge
+	/// frame time and stuck buttons once they return. See [`Window::pause_rendering`].
+	pub fn run_modal<R>(&mut self, f: impl FnOnce(&Window) -> R) -> R {
+		let result = {
+			let _pause = self.window.pause_rendering();
+			f(&self.window)
+		};
+
+		self.resume_after_pause();
+		result
+	}
+
+	pub(crate) fn resume_after_pause(&mut self) {
+		self.time.skip_elapsed();
+		self.input.tracker.track_focus_lost();
+	}
+
+	/// Time in seconds between the start of the previous frame and the start of this one.
+	/// Unaffected by time scale - see [`Time::delta_time`] for that.
+	pub fn delta_time(&self) -> f32 {
+		self.time.real_delta_time()
+	}
+
+	/// Copy the contents of the backbuffer to the clipboard once this frame has finished rendering.
+	pub fn copy_screenshot_to_clipboard(&mut self) {
+		self.clipboard.copy_screenshot(&mut self.gfx);
+	}
+
 	#[instrument(skip_all, name="toybox notify_resized")]
 	pub(crate) fn notify_resized(&mut self, new_size: Vec2i) {
 		self.gfx.resize(new_size);
 		self.input.on_resize(new_size);
+		self.update_pixel_mapping();
+	}
+
+	/// Physical pixels per logical pixel for the main window. See [`Window::scale_factor`].
+	pub fn scale_factor(&self) -> f32 {
+		self.window.scale_factor()
+	}
+
+	/// Use a fixed scale factor rather than the one the platform reports, for compositors that get it wrong.
+	/// Also settable with the `window.scale_factor` config key. None restores the reported scale factor.
+	pub fn set_scale_factor_override(&mut self, scale_factor: impl Into<Option<f32>>) {
+		let scale_factor = scale_factor.into()
+			.filter(|&scale_factor| {
+				let valid = scale_factor.is_finite() && scale_factor > 0.0;
+				if !valid {
+					log::warn!("Ignoring invalid scale factor override: {scale_factor}");
+				}
+				valid
+			});
+
+		self.window.set_scale_factor_override(scale_factor);
+		self.egui_integration.set_scale_factor_override(scale_factor);
+		self.notify_scale_factor_changed();
+	}
+
+	#[instrument(skip_all, name="toybox notify_scale_factor_changed")]
+	pub(crate) fn notify_scale_factor_changed(&mut self) {
+		let scale_factor = self.window.scale_factor();
+		log::info!("Scale factor changed: {scale_factor} (reported {})", self.window.native_scale_factor());
+
+		self.gfx.set_scale_factor(scale_factor);
+		self.input.on_scale_factor_changed(scale_factor);
+
+		// Resized usually follows, but the new physical size may already be available.
+		self.notify_resized(self.window.size());
 	}
 
 	// Called after app returns control, before the frame ends.
 	#[instrument(skip_all, name="toybox finalize_frame")]
 	pub(crate) fn finalize_frame(&mut self) {
+		self.world_labels.draw(&self.egui);
 		self.egui_integration.end_frame(&mut self.gfx);
 
 		// We want to inform the input system if anything might be interferring with things like
 		// cursor capture state.
-		let claiming_input = self.egui.wants_keyboard_input() || self.egui.wants_pointer_input();
-		match self.egui_claiming_input_gate.update(claiming_input) {
+		self.claim_egui_input();
+		match self.input_router.end_frame() {
 			GateState::RisingEdge => self.input.set_occluded(true),
 			GateState::FallingEdge => self.input.set_occluded(false),
 			_ => {}
 		}
 
+		self.sound_events.update(&self.vfs, &self.bus, &mut self.sounds);
+		self.tasks.run();
+		self.autosave.update(&self.vfs, &self.bus);
+		self.save_changed_settings(false);
+		self.assets.update(&mut self.gfx, &self.vfs);
 		self.gfx.execute_frame(&self.vfs);
+
+		#[cfg(feature="xr")]
+		self.end_xr_frame();
+
+		self.determinism.end_frame(&self.gfx);
+
+		if let Some(ipc) = &mut self.ipc {
+			ipc.end_frame(&self.time, &self.gfx.frame_stats);
+		}
+
+		self.clipboard.flush_pending();
+		self.profiler.end_frame();
 	}
 
-	pub(crate) fn shutdown(&mut self) {}
+	pub(crate) fn shutdown(&mut self) {
+		self.autosave.flush();
+		self.save_changed_settings(true);
+	}
 }
 
 
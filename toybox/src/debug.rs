@@ -1,8 +1,10 @@
 use crate::prelude::*;
+use crate::buffer_visualizer::BufferVisualizer;
+use crate::screenshot_compare::ScreenshotCompare;
 
 // https://www.egui.rs/#demo
 
-#[derive(Default, Copy, Clone)]
+#[derive(Default)]
 pub struct MenuState {
 	egui_settings: bool,
 	egui_style: bool,
@@ -13,29 +15,120 @@ pub struct MenuState {
 
 	input_tracker: bool,
 
-	#[cfg(feature="gamepad")]
+	gfx_frame_stages: bool,
+	gfx_frame_stats: bool,
+	gfx_resources: bool,
+	gfx_dump_frame: bool,
+
+	frame_pacing: bool,
+	profiler: bool,
+	tasks: bool,
+	device_simulation: bool,
+
+	resource_inspector: ResourceInspectorState,
+	buffer_visualizer: Option<BufferVisualizer>,
+
+	screenshot_compare: bool,
+	screenshot_compare_state: ScreenshotCompare,
+
+	audio_stream: bool,
+
 	input_gamepad: bool,
+	input_xr: bool,
+
+	features: bool,
+	palettes: bool,
+	handles: bool,
 }
 
 pub fn show_menu(ctx: &mut super::Context, app: &mut impl super::App, state: &mut MenuState) {
 	use egui::menu;
 
 	let egui_ctx = &ctx.egui.clone();
+	let features = ctx.features();
 
 	egui::TopBottomPanel::top("main_debug_menu")
 		.show_animated(egui_ctx, ctx.show_debug_menu, |ui| {
 			menu::bar(ui, |ui| {
 				ui.menu_button("Toybox", |ui| {
-					show_submenus(ui, state);
+					show_submenus(ui, state, &features);
 
 					ui.separator();
 
+					let build_info = crate::build_info();
+					if ui.button(build_info.summary()).on_hover_text(format!("{build_info}\n\nClick to copy")).clicked() {
+						ctx.clipboard.set_text(build_info.to_string());
+						ui.close_menu();
+					}
+
+					ui.label(format!("User data: {}", ctx.vfs.user_data_root().display()))
+						.on_hover_text(format!("{:?}", ctx.vfs.user_data_location()));
+
+					if ctx.vfs.is_journal_enabled() {
+						let num_changes = ctx.vfs.journaled_changes().len();
+
+						ui.add_enabled_ui(num_changes > 0, |ui| {
+							if ui.button(format!("Revert {num_changes} Resource Changes")).clicked() {
+								if let Err(error) = ctx.vfs.revert_journaled_changes() {
+									log::error!("Failed to revert resource changes: {error:?}");
+								}
+								ui.close_menu();
+							}
+						});
+					}
+
+					ui.toggle_value(&mut state.features, "Features");
+					ui.toggle_value(&mut state.palettes, "Palettes");
+					ui.toggle_value(&mut state.handles, "Handles");
+
+					let mut audit_enabled = ctx.determinism.is_enabled();
+					if ui.checkbox(&mut audit_enabled, "Determinism Audit").changed() {
+						ctx.set_determinism_audit(audit_enabled);
+					}
+
+					let context_kind = match (ctx.gfx.core.has_full_debug_output(), ctx.gfx.core.reports_errors()) {
+						(true, _) => "debug",
+						(false, true) => "release",
+						(false, false) => "no-error",
+					};
+					ui.label(format!("GL context: {context_kind}"));
+
+					let mut capture_safe = ctx.gfx.core.capture_safe_mode();
+					if ui.checkbox(&mut capture_safe, "Capture-safe GL Errors")
+						.on_hover_text(format!("{} errors suppressed around swap", ctx.gfx.core.num_suppressed_swap_errors()))
+						.changed()
+					{
+						ctx.gfx.core.set_capture_safe_mode(capture_safe);
+					}
+
+					let mut validation_enabled = ctx.gfx.is_validation_enabled();
+					if ui.checkbox(&mut validation_enabled, "Validate Commands")
+						.on_hover_text(format!("{} invalid commands skipped last frame", ctx.gfx.validation_errors().len()))
+						.changed()
+					{
+						ctx.gfx.set_validation_enabled(validation_enabled);
+					}
+
+					if ui.button("Copy Screenshot").clicked() {
+						ctx.copy_screenshot_to_clipboard();
+						ui.close_menu();
+					}
+
 					if ui.button("Quit").clicked() {
 						ctx.wants_quit = true;
 					}
 				});
 
 				app.customise_debug_menu(ctx, ui);
+
+				let hitch_count = ctx.frame_pacing.hitch_count();
+				if hitch_count > 0 {
+					ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+						if ui.small_button(format!("Hitches: {hitch_count}")).clicked() {
+							state.frame_pacing = true;
+						}
+					});
+				}
 			})
 		});
 
@@ -75,15 +168,145 @@ pub fn show_menu(ctx: &mut super::Context, app: &mut impl super::App, state: &mu
 			input::debug::tracker_ui(ui, &mut ctx.input);
 		});
 
-	#[cfg(feature="gamepad")]
-	egui::Window::new("Gamepad")
-		.open(&mut state.input_gamepad)
+	egui::Window::new("Frame Stages")
+		.open(&mut state.gfx_frame_stages)
+		.show(egui_ctx, |ui| {
+			frame_stages_ui(ui, &mut ctx.gfx.frame_encoder);
+		});
+
+	egui::Window::new("Frame Stats")
+		.open(&mut state.gfx_frame_stats)
+		.show(egui_ctx, |ui| {
+			frame_stats_ui(ui, &ctx.gfx.frame_stats);
+
+			ui.separator();
+			upload_heap_stats_ui(ui, &ctx.gfx.resource_manager.upload_heap);
+
+			ui.separator();
+			resource_stats_ui(ui, &ctx.gfx.resource_manager.stats());
+		});
+
+	if std::mem::take(&mut state.gfx_dump_frame) {
+		let timestamp = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map_or(0, |duration| duration.as_secs());
+
+		ctx.gfx.dump_next_frame(format!("frame_dumps/frame_{timestamp}.txt"));
+	}
+
+	egui::Window::new("Frame Pacing")
+		.open(&mut state.frame_pacing)
+		.show(egui_ctx, |ui| {
+			frame_pacing_ui(ui, &mut ctx.frame_pacing);
+		});
+
+	egui::Window::new("Profiler")
+		.open(&mut state.profiler)
+		.show(egui_ctx, |ui| {
+			crate::profiler::profiler_ui(ui, &ctx.profiler);
+		});
+
+	egui::Window::new("Tasks")
+		.open(&mut state.tasks)
+		.show(egui_ctx, |ui| {
+			crate::tasks::tasks_ui(ui, &mut ctx.tasks);
+		});
+
+	egui::Window::new("Handles")
+		.open(&mut state.handles)
+		.show(egui_ctx, handles_ui);
+
+	egui::Window::new("Device Simulation")
+		.open(&mut state.device_simulation)
+		.show(egui_ctx, |ui| {
+			crate::device_simulation::device_simulation_ui(ui, ctx);
+		});
+
+	egui::Window::new("Palettes")
+		.open(&mut state.palettes)
+		.show(egui_ctx, |ui| {
+			let mut palettes: Vec<_> = ctx.palettes.iter().collect();
+			palettes.sort_by_key(|(path, _)| *path);
+
+			if palettes.is_empty() {
+				ui.label("No palettes loaded");
+			}
+
+			for (path, palette) in palettes {
+				ui.label(path.display().to_string());
+				crate::palette::palette_ui(ui, palette);
+				ui.separator();
+			}
+		});
+
+	egui::Window::new("Gfx Resources")
+		.open(&mut state.gfx_resources)
+		.show(egui_ctx, |ui| {
+			resource_inspector_ui(ui, &ctx.gfx, &mut state.resource_inspector, &mut state.buffer_visualizer);
+		});
+
+	if let Some(visualizer) = &mut state.buffer_visualizer {
+		let mut open = true;
+
+		egui::Window::new("Buffer Visualizer")
+			.open(&mut open)
+			.show(egui_ctx, |ui| {
+				visualizer.ui(ui);
+			});
+
+		visualizer.request_readback(&mut ctx.gfx);
+
+		if !open {
+			state.buffer_visualizer = None;
+		}
+	}
+
+	egui::Window::new("Screenshot Compare")
+		.open(&mut state.screenshot_compare)
+		.show(egui_ctx, |ui| {
+			state.screenshot_compare_state.ui(ui, &ctx.vfs, &mut ctx.gfx);
+		});
+
+	if state.features {
+		let report = features.to_string();
+
+		egui::Window::new("Features")
+			.open(&mut state.features)
+			.show(egui_ctx, |ui| {
+				if ui.button("Copy").clicked() {
+					ctx.clipboard.set_text(report.clone());
+				}
+
+				egui::ScrollArea::vertical().show(ui, |ui| {
+					ui.monospace(&report);
+				});
+			});
+	}
+
+	egui::Window::new("Audio Stream")
+		.open(&mut state.audio_stream)
 		.show(egui_ctx, |ui| {
-			input::debug::gamepad_ui(ui, &mut ctx.input);
+			audio_stream_ui(ui, &ctx.audio);
 		});
+
+	if features.is_enabled("gamepad") {
+		egui::Window::new("Gamepad")
+			.open(&mut state.input_gamepad)
+			.show(egui_ctx, |ui| {
+				input::debug::gamepad_ui(ui, &mut ctx.input);
+			});
+	}
+
+	if features.is_enabled("xr") {
+		egui::Window::new("XR")
+			.open(&mut state.input_xr)
+			.show(egui_ctx, |ui| {
+				xr_ui(ui, ctx);
+			});
+	}
 }
 
-fn show_submenus(ui: &mut egui::Ui, state: &mut MenuState) {
+fn show_submenus(ui: &mut egui::Ui, state: &mut MenuState, features: &crate::FeatureReport) {
 	ui.menu_button("Egui", |ui| {
 		ui.toggle_value(&mut state.egui_settings, "Settings");
 		ui.toggle_value(&mut state.egui_style, "Style");
@@ -93,8 +316,439 @@ fn show_submenus(ui: &mut egui::Ui, state: &mut MenuState) {
 		ui.toggle_value(&mut state.egui_inspection, "Inspection");
 	});
 
+	ui.menu_button("Gfx", |ui| {
+		ui.toggle_value(&mut state.gfx_frame_stages, "Frame Stages");
+		ui.toggle_value(&mut state.gfx_frame_stats, "Frame Stats");
+		ui.toggle_value(&mut state.gfx_resources, "Resources");
+		ui.toggle_value(&mut state.frame_pacing, "Frame Pacing");
+		ui.toggle_value(&mut state.profiler, "Profiler");
+		ui.toggle_value(&mut state.tasks, "Tasks");
+		ui.toggle_value(&mut state.device_simulation, "Device Simulation");
+		ui.toggle_value(&mut state.screenshot_compare, "Screenshot Compare");
+
+		if ui.button("Dump Frame").clicked() {
+			state.gfx_dump_frame = true;
+			ui.close_menu();
+		}
+	});
+
+	ui.menu_button("Audio", |ui| {
+		ui.toggle_value(&mut state.audio_stream, "Stream");
+	});
+
 	ui.menu_button("Input", |ui| {
 		ui.toggle_value(&mut state.input_tracker, "Tracker");
-		// ui.toggle_value(&mut state.input_gamepad, "Gamepad");
+
+		feature_toggle(ui, features, "gamepad", &mut state.input_gamepad, "Gamepad");
+		feature_toggle(ui, features, "xr", &mut state.input_xr, "XR");
 	});
-}
\ No newline at end of file
+}
+
+/// Toggle for a window that only makes sense with an optional subsystem - disabled if that isn't compiled in.
+fn feature_toggle(ui: &mut egui::Ui, features: &crate::FeatureReport, feature: &str, value: &mut bool, label: &str) {
+	let enabled = features.is_enabled(feature);
+	if !enabled {
+		*value = false;
+	}
+
+	ui.add_enabled_ui(enabled, |ui| ui.toggle_value(value, label))
+		.response
+		.on_disabled_hover_text(format!("Requires the `{feature}` feature"));
+}
+
+#[cfg_attr(not(feature="xr"), allow(unused_variables))]
+fn xr_ui(ui: &mut egui::Ui, ctx: &crate::Context) {
+	#[cfg(feature="xr")]
+	{
+		let Some(xr) = &ctx.xr else {
+			ui.label("No XR session");
+			return
+		};
+
+		ui.label(format!("Rendering: {}", xr.is_rendering()));
+		ui.label(format!("Eye size: {:?}", xr.eye_size()));
+
+		for side in [crate::XrHandSide::Left, crate::XrHandSide::Right] {
+			let hand = xr.hand(side);
+
+			ui.separator();
+			ui.label(format!("{side:?}: {}", if hand.grip.is_some() { "tracked" } else { "not tracked" }));
+			ui.label(format!("Trigger: {:.2}  Squeeze: {:.2}", hand.trigger, hand.squeeze));
+			ui.label(format!("Thumbstick: {:?}", hand.thumbstick));
+		}
+	}
+
+	#[cfg(not(feature="xr"))]
+	ui.label("XR support not compiled in");
+}
+
+fn handles_ui(ui: &mut egui::Ui) {
+	if ui.button("Log Registry").clicked() {
+		log::info!("Live handles:\n{}", handle::dump_registry());
+	}
+
+	egui::Grid::new("handle_counts")
+		.striped(true)
+		.show(ui, |ui| {
+			for (kind, count) in handle::live_handle_counts() {
+				ui.label(kind);
+				ui.label(count.to_string());
+				ui.end_row();
+			}
+		});
+
+	ui.separator();
+
+	egui::ScrollArea::vertical()
+		.show(ui, |ui| {
+			egui::Grid::new("handles")
+				.striped(true)
+				.show(ui, |ui| {
+					for handle::HandleInfo{kind, index, generation, debug_name} in handle::live_handles() {
+						ui.label(format!("{kind}#{index}.{generation}"));
+						ui.label(debug_name);
+						ui.end_row();
+					}
+				});
+		});
+}
+
+fn frame_stages_ui(ui: &mut egui::Ui, frame_encoder: &mut gfx::FrameEncoder) {
+	let mut stages: Vec<_> = frame_encoder.known_stages().collect();
+	stages.sort();
+
+	ui.heading("Stages");
+
+	for stage in stages {
+		let mut enabled = frame_encoder.is_stage_enabled(stage);
+		if ui.checkbox(&mut enabled, format!("{stage:?}")).changed() {
+			frame_encoder.set_stage_enabled(stage, enabled);
+		}
+	}
+
+	ui.separator();
+	ui.heading("Annotated Groups");
+
+	let annotations: Vec<String> = frame_encoder.known_annotations().map(Into::into).collect();
+
+	for label in annotations {
+		let mut enabled = frame_encoder.is_annotation_enabled(&label);
+		if ui.checkbox(&mut enabled, &label).changed() {
+			frame_encoder.set_annotation_enabled(&label, enabled);
+		}
+	}
+}
+
+fn frame_pacing_ui(ui: &mut egui::Ui, frame_pacing: &mut crate::FramePacing) {
+	ui.horizontal(|ui| {
+		ui.label("Max catch up");
+		ui.add(egui::DragValue::new(&mut frame_pacing.max_catch_up).speed(0.01).clamp_range(0.0..=1.0).suffix("s"));
+	});
+
+	ui.horizontal(|ui| {
+		ui.label("Hitch threshold");
+		ui.add(egui::DragValue::new(&mut frame_pacing.hitch_threshold).speed(0.01).clamp_range(0.0..=1.0).suffix("s"));
+	});
+
+	ui.separator();
+
+	ui.label(format!("Last frame: {}", frame_pacing.previous_timings()));
+	ui.label(format!("Hitches: {}", frame_pacing.hitch_count()));
+
+	if let Some(hitch) = frame_pacing.last_hitch() {
+		ui.label(format!("Last hitch: {:.1}ms, mostly {:?}", hitch.frame_time * 1000.0, hitch.timings.dominant_section()));
+		ui.label(format!("{}", hitch.timings));
+
+		if hitch.dropped_time > 0.0 {
+			ui.label(format!("Dropped {:.1}ms of simulation", hitch.dropped_time * 1000.0));
+		}
+	}
+}
+
+fn frame_stats_ui(ui: &mut egui::Ui, frame_stats: &gfx::FrameStats) {
+	let total = frame_stats.total();
+
+	egui::Grid::new("frame_stats")
+		.striped(true)
+		.show(ui, |ui| {
+			ui.label("Stage");
+			ui.label("Draws");
+			ui.label("Dispatches");
+			ui.label("Instances");
+			ui.label("Triangles");
+			ui.end_row();
+
+			let rows = frame_stats.stages.iter()
+				.map(|(stage, stats)| (format!("{stage:?}"), stats))
+				.chain(std::iter::once(("Total".into(), &total)));
+
+			for (label, stats) in rows {
+				ui.label(label);
+				ui.label(stats.draw_calls.to_string());
+				ui.label(stats.compute_dispatches.to_string());
+				ui.label(stats.instances.to_string());
+				ui.label(stats.triangles.to_string());
+				ui.end_row();
+			}
+		});
+}
+
+fn upload_heap_stats_ui(ui: &mut egui::Ui, upload_heap: &gfx::upload_heap::UploadHeap) {
+	egui::Grid::new("upload_heap_stats")
+		.striped(true)
+		.show(ui, |ui| {
+			ui.label("Heap");
+			ui.label("Partition");
+			ui.label("Last Frame");
+			ui.label("Peak");
+			ui.label("Waits");
+			ui.label("Wait Time");
+			ui.label("Overflows");
+			ui.end_row();
+
+			let rows = [("Upload", upload_heap.stats()), ("Push Constants", upload_heap.push_constant_stats())];
+
+			for (label, stats) in rows {
+				ui.label(label);
+				ui.label(format!("{}KiB", stats.partition_size >> 10));
+				ui.label(format!("{}KiB", stats.last_frame_usage >> 10));
+				ui.label(format!("{}KiB", stats.peak_usage >> 10));
+				ui.label(stats.total_waits.to_string());
+				ui.label(format!("{:.1?}", stats.total_wait_time));
+				ui.label(stats.total_overflows.to_string());
+				ui.end_row();
+			}
+		});
+}
+
+fn resource_stats_ui(ui: &mut egui::Ui, stats: &gfx::ResourceManagerStats) {
+	egui::Grid::new("resource_stats")
+		.striped(true)
+		.show(ui, |ui| {
+			ui.label("Resource");
+			ui.label("Count");
+			ui.label("Size");
+			ui.end_row();
+
+			for (label, category) in stats.categories() {
+				ui.label(label);
+				ui.label(category.count.to_string());
+				ui.label(format!("{}KiB", category.bytes >> 10));
+				ui.end_row();
+			}
+		});
+
+	ui.label(format!("Pending requests: {}", stats.pending_requests.total()))
+		.on_hover_ui(|ui| {
+			egui::Grid::new("resource_request_queues")
+				.show(ui, |ui| {
+					for (label, depth) in stats.pending_requests.queues() {
+						ui.label(label);
+						ui.label(depth.to_string());
+						ui.end_row();
+					}
+				});
+		});
+}
+
+#[derive(Copy, Clone, Default)]
+struct ResourceInspectorState {
+	selected_image: Option<gfx::ImageName>,
+	display_options: egui_backend::ImageDisplayOptions,
+}
+
+fn resource_inspector_ui(ui: &mut egui::Ui, gfx: &gfx::System, state: &mut ResourceInspectorState,
+	buffer_visualizer: &mut Option<BufferVisualizer>)
+{
+	let rm = &gfx.resource_manager;
+
+	let image_label = |name: gfx::ImageName| {
+		rm.images.iter()
+			.find(|image| image.name == name)
+			.map_or_else(|| format!("{name:?}"), |image| image.label.clone())
+	};
+
+	ui.collapsing("Images", |ui| {
+		egui::ScrollArea::vertical()
+			.id_source("images")
+			.max_height(200.0)
+			.show(ui, |ui| {
+				for image in rm.images.iter() {
+					let gfx::ImageInfo{format, size, ..} = image.image_info;
+					let text = format!("{} - {}x{}x{} {format:?}", image.label, size.x, size.y, size.z);
+					ui.selectable_value(&mut state.selected_image, Some(image.name), text);
+				}
+			});
+	});
+
+	ui.collapsing("Image Decodes", |ui| {
+		ui.label(format!("{} pending", rm.num_pending_image_decodes()));
+
+		for timing in rm.image_decode_timings().rev() {
+			let gfx::ImageDecodeTiming{path, size, decode_time} = timing;
+			ui.label(format!("{} - {}x{} {:.1}ms", path.display(), size.x, size.y, decode_time.as_secs_f32() * 1000.0));
+		}
+	});
+
+	ui.collapsing("Framebuffers", |ui| {
+		for (desc, name) in rm.framebuffers() {
+			ui.label(format!("{name:?}"));
+			ui.indent(name, |ui| {
+				for attachment in desc.attachments.iter().flatten() {
+					let Some(image_name) = rm.images.get_name(*attachment) else { continue };
+					ui.selectable_value(&mut state.selected_image, Some(image_name), image_label(image_name));
+				}
+			});
+		}
+	});
+
+	ui.collapsing("Named Buffers", |ui| {
+		for (label, buffer) in rm.named_buffers.iter() {
+			ui.horizontal(|ui| {
+				ui.label(format!("{label} - {:?} {} bytes", buffer.name, buffer.size));
+
+				if ui.small_button("Visualize").clicked() {
+					*buffer_visualizer = BufferVisualizer::new(buffer.name, "vec4").ok();
+				}
+			});
+		}
+	});
+
+	ui.separator();
+
+	let Some(selected) = state.selected_image else { return };
+	let Some(image) = rm.images.iter().find(|image| image.name == selected) else {
+		state.selected_image = None;
+		return
+	};
+
+	let image_info = image.image_info.clone();
+	ui.label(format!("{}: {image_info:?}", image.label));
+
+	if image_info.image_type != gfx::ImageType::Image2D || image_info.samples > 1 || image_info.format.is_compressed() {
+		ui.label("Preview not supported for this image type");
+		return
+	}
+
+	let options = &mut state.display_options;
+
+	if image_info.format.is_depth() || image_info.format.is_depth_stencil() {
+		use gfx::DepthVisualization as DV;
+
+		let mode = &mut options.depth;
+
+		ui.horizontal(|ui| {
+			if ui.selectable_label(matches!(mode, DV::Raw), "Raw").clicked() {
+				*mode = DV::Raw;
+			}
+			if ui.selectable_label(matches!(mode, DV::Remap{..}), "Remap").clicked() {
+				*mode = DV::Remap { min: 0.9, max: 1.0 };
+			}
+			if ui.selectable_label(matches!(mode, DV::Linearize{..}), "Linearize").clicked() {
+				*mode = DV::Linearize { near: 0.1, far: 100.0 };
+			}
+		});
+
+		match mode {
+			DV::Raw => {}
+			DV::Remap{min, max} => {
+				ui.add(egui::Slider::new(min, 0.0..=1.0).text("Min"));
+				ui.add(egui::Slider::new(max, 0.0..=1.0).text("Max"));
+			}
+			DV::Linearize{near, far} => {
+				ui.add(egui::DragValue::new(near).prefix("Near: ").speed(0.01).clamp_range(0.001..=f32::MAX));
+				ui.add(egui::DragValue::new(far).prefix("Far: ").speed(1.0).clamp_range(0.001..=f32::MAX));
+			}
+		}
+	} else {
+		use egui_backend::ImageChannel as IC;
+
+		ui.horizontal(|ui| {
+			ui.selectable_value(&mut options.channel, None, "RGBA");
+			ui.selectable_value(&mut options.channel, Some(IC::Red), "R");
+			ui.selectable_value(&mut options.channel, Some(IC::Green), "G");
+			ui.selectable_value(&mut options.channel, Some(IC::Blue), "B");
+			ui.selectable_value(&mut options.channel, Some(IC::Alpha), "A");
+		});
+
+		ui.horizontal(|ui| {
+			ui.add_enabled(!image_info.format.is_srgb(), egui::Checkbox::new(&mut options.srgb_encoded, "sRGB encoded"));
+			ui.checkbox(&mut options.ignore_alpha, "Ignore alpha");
+		});
+	}
+
+	egui_backend::show_image_name_with(ui, selected, *options);
+}
+
+fn audio_stream_ui(ui: &mut egui::Ui, audio: &audio::System) {
+	ui.label(format!("Status: {:?}", audio.stream_status()));
+	ui.label(format!("Provider: {}", if audio.has_provider() { "set" } else { "none" }));
+
+	if let Some(config) = audio.current_configuration() {
+		ui.label(format!("Sample rate: {}Hz", config.sample_rate));
+		match config.channel_layout {
+			Some(layout) if config.device_channels != config.channels =>
+				ui.label(format!("Channels: {} mapped to {} device channels", layout.name(), config.device_channels)),
+			Some(layout) => ui.label(format!("Channels: {}", layout.name())),
+			None => ui.label(format!("Channels: {}", config.channels)),
+		};
+
+		match config.frames_per_buffer {
+			Some(frames) => ui.label(format!("Requested buffer: {frames} frames")),
+			None => ui.label("Requested buffer: default"),
+		};
+	}
+
+	if let Some(frames) = audio.last_buffer_frames() {
+		ui.label(format!("Last buffer: {frames} frames"));
+	}
+
+	if let Some(latency) = audio.achieved_latency() {
+		ui.label(format!("Output latency: {:.1}ms", latency.as_secs_f64() * 1000.0));
+	}
+
+	let analysis = audio.output_analysis();
+	let level_bar = |ui: &mut egui::Ui, label: &str, db: f32| {
+		let fraction = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+		ui.add(egui::ProgressBar::new(fraction).text(format!("{label} {db:.1}dB")));
+	};
+
+	level_bar(ui, "Peak", analysis.peak_db());
+	level_bar(ui, "RMS", analysis.rms_db());
+
+	ui.separator();
+
+	let nodes = audio.node_graph().nodes();
+	let mut depths = std::collections::HashMap::new();
+
+	egui::Grid::new("audio_nodes")
+		.striped(true)
+		.show(ui, |ui| {
+			for node in nodes.iter() {
+				// Parents always come first, so their depth is already known.
+				let depth = node.parent().map_or(0, |parent| depths.get(&parent).map_or(0, |depth| depth + 1));
+				depths.insert(node.id(), depth);
+
+				ui.label(format!("{}{}", "    ".repeat(depth), node.name()));
+
+				let db = node.analysis().peak_db();
+				let fraction = ((db + 60.0) / 60.0).clamp(0.0, 1.0);
+				ui.add(egui::ProgressBar::new(fraction).desired_width(120.0).text(format!("{db:.1}dB")));
+
+				let mut muted = node.is_muted();
+				if ui.checkbox(&mut muted, "Mute").changed() {
+					node.set_muted(muted);
+				}
+
+				// Master has no siblings to solo over.
+				if node.parent().is_some() {
+					let mut soloed = node.is_soloed();
+					if ui.checkbox(&mut soloed, "Solo").changed() {
+						node.set_soloed(soloed);
+					}
+				}
+
+				ui.end_row();
+			}
+		});
+}
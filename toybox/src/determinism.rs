@@ -0,0 +1,189 @@
+use crate::prelude::*;
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use gfx::{FnvHasher, fnv1a};
+
+
+const MAX_HISTORY: usize = 600;
+
+
+/// Opt-in mode that hashes per-frame input state, rng draws and submitted gfx commands, and folds them into a
+/// rolling digest that is logged every frame.
+/// Comparing logs from two runs (or two machines) shows the first frame at which they diverged.
+/// Enable with [`Context::set_determinism_audit`](crate::Context::set_determinism_audit).
+#[derive(Debug, Default)]
+pub struct DeterminismAudit {
+	enabled: bool,
+
+	frame: u64,
+	digest: u64,
+
+	input_hash: u64,
+	rng_hash: Rc<Cell<u64>>,
+	user_hash: FnvHasher,
+
+	history: VecDeque<FrameDigest>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameDigest {
+	pub frame: u64,
+	pub input: u64,
+	pub rng: u64,
+	pub user: u64,
+	pub commands: Option<u64>,
+
+	/// Rolling digest of this and every previous frame since the audit was enabled.
+	pub digest: u64,
+}
+
+impl DeterminismAudit {
+	pub fn is_enabled(&self) -> bool {
+		self.enabled
+	}
+
+	pub(crate) fn set_enabled(&mut self, enabled: bool) {
+		if self.enabled == enabled {
+			return
+		}
+
+		*self = DeterminismAudit {
+			enabled,
+			rng_hash: self.rng_hash.clone(),
+			.. DeterminismAudit::default()
+		};
+
+		self.rng_hash.set(FnvHasher::default().finish());
+	}
+
+	/// Digests of the most recent frames, oldest first.
+	pub fn history(&self) -> impl Iterator<Item=&FrameDigest> + '_ {
+		self.history.iter()
+	}
+
+	pub fn last_frame(&self) -> Option<&FrameDigest> {
+		self.history.back()
+	}
+
+	/// Mix arbitrary simulation state into this frame's digest. Does nothing while disabled.
+	pub fn record(&mut self, value: impl Hash) {
+		if self.enabled {
+			value.hash(&mut self.user_hash);
+		}
+	}
+
+	/// Wrap an rng so that every value drawn from it is mixed into the digest while auditing is enabled.
+	pub fn wrap_rng<R: RngCore>(&self, rng: R) -> AuditedRng<R> {
+		AuditedRng {
+			inner: rng,
+			hash: self.rng_hash.clone(),
+		}
+	}
+
+	pub(crate) fn record_input(&mut self, input: &input::System) {
+		if !self.enabled {
+			return
+		}
+
+		let tracker = &input.tracker;
+
+		let mut hasher = FnvHasher::default();
+		tracker.active_buttons.hash(&mut hasher);
+		tracker.down_buttons.hash(&mut hasher);
+		tracker.up_buttons.hash(&mut hasher);
+
+		hash_vec2(&mut hasher, tracker.physical_mouse_position);
+		hash_vec2(&mut hasher, tracker.mouse_delta);
+
+		self.input_hash = hasher.finish();
+	}
+
+	pub(crate) fn end_frame(&mut self, gfx: &gfx::System) {
+		if !self.enabled {
+			return
+		}
+
+		let rng = self.rng_hash.replace(FnvHasher::default().finish());
+		let user = std::mem::take(&mut self.user_hash).finish();
+		let commands = gfx.last_command_hash();
+
+		let mut hasher = FnvHasher::default();
+		(self.digest, self.frame, self.input_hash, rng, user, commands).hash(&mut hasher);
+		self.digest = hasher.finish();
+
+		let frame_digest = FrameDigest {
+			frame: self.frame,
+			input: self.input_hash,
+			rng,
+			user,
+			commands,
+			digest: self.digest,
+		};
+
+		log::info!(target: "determinism", "frame {}: {:016x} (input {:016x}, rng {:016x}, user {:016x}, commands {:016x})",
+			frame_digest.frame, frame_digest.digest, frame_digest.input, frame_digest.rng, frame_digest.user,
+			frame_digest.commands.unwrap_or(0));
+
+		if self.history.len() >= MAX_HISTORY {
+			self.history.pop_front();
+		}
+
+		self.history.push_back(frame_digest);
+
+		self.frame += 1;
+		self.input_hash = 0;
+	}
+}
+
+fn hash_vec2(hasher: &mut impl Hasher, v: Option<Vec2>) {
+	v.map(|Vec2{x, y}| (x.to_bits(), y.to_bits())).hash(hasher);
+}
+
+
+
+/// Rng wrapper created by [`DeterminismAudit::wrap_rng`].
+#[derive(Debug, Clone)]
+pub struct AuditedRng<R> {
+	inner: R,
+	hash: Rc<Cell<u64>>,
+}
+
+impl<R> AuditedRng<R> {
+	pub fn into_inner(self) -> R {
+		self.inner
+	}
+
+	fn mix(&self, bytes: &[u8]) {
+		self.hash.set(fnv1a(self.hash.get(), bytes));
+	}
+}
+
+impl<R: RngCore> RngCore for AuditedRng<R> {
+	fn next_u32(&mut self) -> u32 {
+		let value = self.inner.next_u32();
+		self.mix(&value.to_le_bytes());
+		value
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		let value = self.inner.next_u64();
+		self.mix(&value.to_le_bytes());
+		value
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.inner.fill_bytes(dest);
+		self.mix(dest);
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+		self.inner.try_fill_bytes(dest)?;
+		self.mix(dest);
+		Ok(())
+	}
+}
+
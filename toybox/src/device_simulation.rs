@@ -0,0 +1,109 @@
+//! Debug options that make a fast dev machine behave like a slow or memory constrained one, for testing loading
+//! screens, streaming and out of memory handling.
+//!
+//! Configured in the `debug.device_simulation` config section, e.g. `debug.device_simulation.read_latency_ms=50` on the
+//! command line, or from the debug menu. All limits are off when zero.
+
+use crate::prelude::*;
+use crate::Context;
+
+
+pub const DEVICE_SIMULATION_SECTION: &str = "debug.device_simulation";
+
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct DeviceSimulationSettings {
+	/// Added to every vfs read.
+	pub read_latency_ms: u32,
+
+	/// Vfs reads are further delayed as if limited to this throughput.
+	pub read_kib_per_second: u32,
+
+	/// Image and buffer creation fails once estimated gpu memory usage would exceed this.
+	/// See [`gfx::Core::check_memory_budget`].
+	pub memory_budget_mib: u32,
+
+	/// Caps the size of each upload heap partition, forcing frames that upload more to spill.
+	pub upload_heap_budget_kib: u32,
+}
+
+impl cfg::ConfigSection for DeviceSimulationSettings {}
+
+impl DeviceSimulationSettings {
+	pub fn is_active(&self) -> bool {
+		*self != DeviceSimulationSettings::default()
+	}
+
+	pub fn apply(&self, vfs: &mut vfs::Vfs, gfx: &mut gfx::System) {
+		let slow_io = (self.read_latency_ms > 0 || self.read_kib_per_second > 0)
+			.then(|| vfs::SlowIoSimulation {
+				latency: std::time::Duration::from_millis(self.read_latency_ms as u64),
+				bytes_per_second: self.read_kib_per_second as u64 * 1024,
+			});
+
+		vfs.set_slow_io_simulation(slow_io);
+
+		let memory_budget = (self.memory_budget_mib > 0).then(|| (self.memory_budget_mib as usize) << 20);
+		gfx.core.set_simulated_memory_budget(memory_budget);
+
+		let upload_heap_budget = (self.upload_heap_budget_kib > 0).then(|| (self.upload_heap_budget_kib as usize) << 10);
+		gfx.resource_manager.upload_heap.set_partition_size_limit(upload_heap_budget);
+	}
+}
+
+
+impl Context {
+	pub fn device_simulation_settings(&self) -> DeviceSimulationSettings {
+		self.cfg.bind_or_default(DEVICE_SIMULATION_SECTION)
+	}
+
+	/// Apply new simulation settings for this session only - they aren't saved.
+	pub fn set_device_simulation_settings(&mut self, settings: &DeviceSimulationSettings) {
+		settings.apply(&mut self.vfs, &mut self.gfx);
+
+		if let Err(error) = self.cfg.preview(DEVICE_SIMULATION_SECTION, settings) {
+			log::error!("Failed to store device simulation settings: {error}");
+		}
+	}
+
+	pub(crate) fn apply_startup_device_simulation(&mut self) {
+		let settings = self.device_simulation_settings();
+		if settings.is_active() {
+			log::warn!("Device simulation active: {settings:?}");
+		}
+
+		settings.apply(&mut self.vfs, &mut self.gfx);
+	}
+}
+
+
+
+pub(crate) fn device_simulation_ui(ui: &mut egui::Ui, ctx: &mut Context) {
+	let mut settings = ctx.device_simulation_settings();
+	let mut changed = false;
+
+	ui.label("Slow IO");
+	changed |= ui.add(egui::Slider::new(&mut settings.read_latency_ms, 0..=1000).text("Read latency (ms)")).changed();
+	changed |= ui.add(egui::Slider::new(&mut settings.read_kib_per_second, 0..=100_000).logarithmic(true)
+		.text("Read throughput (KiB/s)")).changed();
+
+	ui.separator();
+
+	ui.label("Low Memory");
+	changed |= ui.add(egui::Slider::new(&mut settings.memory_budget_mib, 0..=8192).logarithmic(true)
+		.text("Gpu memory budget (MiB)")).changed();
+	changed |= ui.add(egui::Slider::new(&mut settings.upload_heap_budget_kib, 0..=100_000).logarithmic(true)
+		.text("Upload heap partition (KiB)")).changed();
+
+	ui.label(format!("Estimated gpu memory usage: {}MiB", ctx.gfx.core.estimated_memory_usage() >> 20));
+
+	if ui.button("Reset").clicked() {
+		settings = DeviceSimulationSettings::default();
+		changed = true;
+	}
+
+	if changed {
+		ctx.set_device_simulation_settings(&settings);
+	}
+}
@@ -0,0 +1,313 @@
+//! Native open/save dialogs that don't block the event loop. Requires the `dialogs` feature - without it every
+//! request immediately resolves as [`DialogResult::Unavailable`].
+//!
+//! ```rust ignore
+//! // On click
+//! self.open_dialog = Some(ctx.dialogs.open_file(FileDialog::new().filter("Levels", &["json"])));
+//!
+//! // Each frame
+//! if let Some(result) = self.open_dialog.and_then(|handle| ctx.dialogs.take_result(handle)) {
+//!     self.open_dialog = None;
+//!
+//!     if let Some((kind, path)) = result.file().and_then(|file| file.vfs_path.as_ref()) {
+//!         self.level_data = ctx.vfs.load_data(*kind, path)?;
+//!     }
+//! }
+//! ```
+
+use crate::prelude::*;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+
+#[derive(Debug, Clone)]
+pub struct FileFilter {
+	pub name: String,
+
+	/// Without the leading '.'.
+	pub extensions: Vec<String>,
+}
+
+
+/// Options for a dialog.
+#[derive(Debug, Clone, Default)]
+pub struct FileDialog {
+	pub title: Option<String>,
+	pub filters: Vec<FileFilter>,
+
+	/// Directory the dialog starts in.
+	pub directory: Option<PathBuf>,
+
+	/// Default file name for save dialogs.
+	pub file_name: Option<String>,
+}
+
+impl FileDialog {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn title(self, title: impl Into<String>) -> Self {
+		Self { title: Some(title.into()), .. self }
+	}
+
+	pub fn filter(mut self, name: impl Into<String>, extensions: &[&str]) -> Self {
+		self.filters.push(FileFilter {
+			name: name.into(),
+			extensions: extensions.iter().map(|extension| extension.trim_start_matches('.').to_owned()).collect(),
+		});
+
+		self
+	}
+
+	pub fn directory(self, path: impl Into<PathBuf>) -> Self {
+		Self { directory: Some(path.into()), .. self }
+	}
+
+	/// Start in a virtual directory, e.g., `(PathKind::UserData, "levels")`.
+	pub fn vfs_directory(self, vfs: &vfs::Vfs, kind: vfs::PathKind, virtual_path: impl AsRef<std::path::Path>) -> Self {
+		match vfs.resolve_path(kind, virtual_path) {
+			Ok(path) => self.directory(path),
+			Err(error) => {
+				log::warn!("Ignoring dialog directory: {error}");
+				self
+			}
+		}
+	}
+
+	pub fn file_name(self, file_name: impl Into<String>) -> Self {
+		Self { file_name: Some(file_name.into()), .. self }
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct PickedFile {
+	/// The real path as chosen by the user.
+	pub path: PathBuf,
+
+	/// `path` relative to the resource or user data roots, if it's within either.
+	/// See [`vfs::Vfs::to_virtual_path`].
+	pub vfs_path: Option<(vfs::PathKind, PathBuf)>,
+}
+
+
+#[derive(Debug, Clone)]
+pub enum DialogResult {
+	Picked(Vec<PickedFile>),
+	Cancelled,
+
+	/// Built without the `dialogs` feature, or the platform has no native dialogs.
+	Unavailable,
+}
+
+impl DialogResult {
+	/// The first picked file, if any.
+	pub fn file(&self) -> Option<&PickedFile> {
+		self.files().first()
+	}
+
+	pub fn files(&self) -> &[PickedFile] {
+		match self {
+			DialogResult::Picked(files) => files,
+			_ => &[],
+		}
+	}
+}
+
+
+/// Released once its result is taken.
+pub type DialogHandle = handle::Handle<DialogResult>;
+
+
+#[derive(Debug, Copy, Clone)]
+enum DialogKind {
+	OpenFile,
+	OpenFiles,
+	SaveFile,
+	PickFolder,
+}
+
+
+/// Runs native dialogs parented to the main window. Results are collected at the start of each frame and kept until
+/// taken with [`Dialogs::take_result`].
+pub struct Dialogs {
+	#[cfg_attr(not(feature="dialogs"), allow(dead_code))]
+	window: Rc<host::winit::window::Window>,
+
+	#[cfg(feature="dialogs")]
+	pending: Vec<(DialogHandle, backend::PendingDialog)>,
+
+	finished: HashMap<DialogHandle, DialogResult>,
+}
+
+impl Dialogs {
+	pub(crate) fn new(window: Rc<host::winit::window::Window>) -> Dialogs {
+		Dialogs {
+			window,
+
+			#[cfg(feature="dialogs")]
+			pending: Vec::new(),
+
+			finished: HashMap::new(),
+		}
+	}
+
+	pub fn is_available(&self) -> bool {
+		cfg!(feature="dialogs")
+	}
+
+	pub fn open_file(&mut self, dialog: FileDialog) -> DialogHandle {
+		self.start(DialogKind::OpenFile, dialog)
+	}
+
+	pub fn open_files(&mut self, dialog: FileDialog) -> DialogHandle {
+		self.start(DialogKind::OpenFiles, dialog)
+	}
+
+	pub fn save_file(&mut self, dialog: FileDialog) -> DialogHandle {
+		self.start(DialogKind::SaveFile, dialog)
+	}
+
+	pub fn pick_folder(&mut self, dialog: FileDialog) -> DialogHandle {
+		self.start(DialogKind::PickFolder, dialog)
+	}
+
+	/// Whether the dialog is still open.
+	pub fn is_pending(&self, handle: DialogHandle) -> bool {
+		#[cfg(feature="dialogs")]
+		return self.pending.iter().any(|(pending, _)| *pending == handle);
+
+		#[cfg(not(feature="dialogs"))]
+		return { let _ = handle; false };
+	}
+
+	/// The result of a closed dialog. Returns None while the dialog is open, or if the result was already taken.
+	pub fn take_result(&mut self, handle: DialogHandle) -> Option<DialogResult> {
+		let result = self.finished.remove(&handle)?;
+		handle.release();
+		Some(result)
+	}
+
+	fn start(&mut self, kind: DialogKind, dialog: FileDialog) -> DialogHandle {
+		let handle = DialogHandle::new(format!("{kind:?} dialog"));
+
+		log::info!("Opening {kind:?} dialog");
+
+		#[cfg(feature="dialogs")]
+		self.pending.push((handle, backend::PendingDialog::start(&self.window, kind, dialog)));
+
+		#[cfg(not(feature="dialogs"))]
+		{
+			let _ = dialog;
+			log::warn!("Native dialogs unavailable - toybox was built without the `dialogs` feature");
+			self.finished.insert(handle, DialogResult::Unavailable);
+		}
+
+		handle
+	}
+
+	/// Collect results from dialogs that have closed since last frame.
+	pub(crate) fn update(&mut self, vfs: &vfs::Vfs) {
+		#[cfg(feature="dialogs")]
+		self.pending.retain_mut(|(handle, dialog)| {
+			let Some(paths) = dialog.poll() else { return true };
+
+			let result = match paths {
+				Some(paths) => DialogResult::Picked(paths.into_iter()
+					.map(|path| PickedFile {
+						vfs_path: vfs.to_virtual_path(&path),
+						path,
+					})
+					.collect()),
+
+				None => DialogResult::Cancelled,
+			};
+
+			self.finished.insert(*handle, result);
+			false
+		});
+
+		#[cfg(not(feature="dialogs"))]
+		let _ = vfs;
+	}
+}
+
+
+#[cfg(feature="dialogs")]
+mod backend {
+	use super::*;
+
+	use std::future::Future;
+	use std::pin::Pin;
+	use std::task::{Context, Poll, Waker};
+
+	type DialogFuture = Pin<Box<dyn Future<Output=Option<Vec<PathBuf>>>>>;
+
+	pub struct PendingDialog {
+		future: DialogFuture,
+	}
+
+	impl PendingDialog {
+		pub fn start(window: &host::winit::window::Window, kind: DialogKind, dialog: FileDialog) -> PendingDialog {
+			let mut builder = rfd::AsyncFileDialog::new()
+				.set_parent(window);
+
+			if let Some(title) = &dialog.title {
+				builder = builder.set_title(title);
+			}
+
+			for FileFilter{name, extensions} in dialog.filters.iter() {
+				builder = builder.add_filter(name, extensions.as_slice());
+			}
+
+			if let Some(directory) = &dialog.directory {
+				builder = builder.set_directory(directory);
+			}
+
+			if let Some(file_name) = &dialog.file_name {
+				builder = builder.set_file_name(file_name);
+			}
+
+			let to_path = |handle: rfd::FileHandle| handle.path().to_owned();
+
+			let future: DialogFuture = match kind {
+				DialogKind::OpenFile => {
+					let future = builder.pick_file();
+					Box::pin(async move { future.await.map(|handle| vec![to_path(handle)]) })
+				}
+
+				DialogKind::OpenFiles => {
+					let future = builder.pick_files();
+					Box::pin(async move { future.await.map(|handles| handles.into_iter().map(to_path).collect()) })
+				}
+
+				DialogKind::SaveFile => {
+					let future = builder.save_file();
+					Box::pin(async move { future.await.map(|handle| vec![to_path(handle)]) })
+				}
+
+				DialogKind::PickFolder => {
+					let future = builder.pick_folder();
+					Box::pin(async move { future.await.map(|handle| vec![to_path(handle)]) })
+				}
+			};
+
+			PendingDialog { future }
+		}
+
+		/// Returns Some once the dialog has closed.
+		// rfd completes its futures from its own threads or the platform event loop, so polling once a frame
+		// with a noop waker is enough - no executor needed.
+		pub fn poll(&mut self) -> Option<Option<Vec<PathBuf>>> {
+			let mut context = Context::from_waker(Waker::noop());
+
+			match self.future.as_mut().poll(&mut context) {
+				Poll::Ready(paths) => Some(paths),
+				Poll::Pending => None,
+			}
+		}
+	}
+}
@@ -0,0 +1,59 @@
+use crate::prelude::*;
+use crate::Context;
+
+
+/// Everything optional about this build and the machine it's running on, in one place.
+/// Its `Display` impl is intended for bug reports.
+#[derive(Debug, Clone)]
+pub struct FeatureReport {
+	pub build: &'static crate::BuildInfo,
+
+	/// Optional subsystems and whether they were compiled in.
+	pub compiled_features: Vec<(&'static str, bool)>,
+
+	/// See [`platform::PlatformServices::name`].
+	pub platform: String,
+
+	pub gpu_capabilities: gfx::Capabilities,
+	pub reverse_z: bool,
+	pub backbuffer_color_bits: u32,
+}
+
+impl FeatureReport {
+	pub fn is_enabled(&self, feature: &str) -> bool {
+		self.compiled_features.iter()
+			.any(|&(name, enabled)| name == feature && enabled)
+	}
+}
+
+/// Optional subsystems, as named by the `toybox` crate's cargo features.
+pub fn compiled_features() -> Vec<(&'static str, bool)> {
+	crate::build_info().features.to_vec()
+}
+
+impl std::fmt::Display for FeatureReport {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		// Build info already lists the enabled features.
+		writeln!(f, "{}", self.build)?;
+		writeln!(f)?;
+
+		writeln!(f, "platform: {}", self.platform)?;
+		writeln!(f, "reverse z: {}", self.reverse_z)?;
+		writeln!(f, "backbuffer color bits: {}", self.backbuffer_color_bits)?;
+		write!(f, "{:#?}", self.gpu_capabilities)
+	}
+}
+
+
+impl Context {
+	pub fn features(&self) -> FeatureReport {
+		FeatureReport {
+			build: crate::build_info(),
+			compiled_features: compiled_features(),
+			platform: self.platform.name().to_owned(),
+			gpu_capabilities: self.gfx.core.capabilities().clone(),
+			reverse_z: self.gfx.core.is_reverse_z(),
+			backbuffer_color_bits: self.gfx.core.backbuffer_color_bits(),
+		}
+	}
+}
@@ -0,0 +1,188 @@
+use crate::prelude::*;
+
+use std::time::Instant;
+
+
+/// Where time went during a frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrameSection {
+	/// Between the end of the previous frame and the start of this one. Mostly swapping buffers, so waiting on the
+	/// gpu and vsync, but also event processing - which is where window drags and resizes block on some platforms.
+	Wait,
+
+	/// [`App::update`](crate::App::update).
+	Update,
+
+	/// [`App::present`](crate::App::present).
+	Present,
+
+	/// Painting egui and submitting the frame to the gpu.
+	Submit,
+}
+
+impl FrameSection {
+	pub const ALL: [FrameSection; 4] = [FrameSection::Wait, FrameSection::Update, FrameSection::Present, FrameSection::Submit];
+}
+
+
+/// Duration of each [`FrameSection`] in seconds.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameTimings {
+	durations: [f32; 4],
+}
+
+impl FrameTimings {
+	pub fn get(&self, section: FrameSection) -> f32 {
+		self.durations[section as usize]
+	}
+
+	/// The section that took the longest.
+	pub fn dominant_section(&self) -> FrameSection {
+		FrameSection::ALL.into_iter()
+			.max_by(|a, b| self.get(*a).total_cmp(&self.get(*b)))
+			.unwrap()
+	}
+}
+
+impl std::fmt::Display for FrameTimings {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		for (index, section) in FrameSection::ALL.into_iter().enumerate() {
+			if index > 0 {
+				write!(f, ", ")?;
+			}
+
+			write!(f, "{section:?} {:.1}ms", self.get(section) * 1000.0)?;
+		}
+
+		Ok(())
+	}
+}
+
+
+#[derive(Copy, Clone, Debug)]
+pub struct HitchReport {
+	/// Real time in seconds between the start of the hitching frame and the one before it.
+	pub frame_time: f32,
+
+	/// Breakdown of the hitching frame.
+	pub timings: FrameTimings,
+
+	/// Simulated time in seconds that was discarded by [`FramePacing::max_catch_up`].
+	pub dropped_time: f32,
+}
+
+
+/// Protects the simulation from long frames, and keeps track of when they happen and why.
+///
+/// Without clamping, a long stall (e.g., dragging the window) would require many fixed timestep updates to catch up,
+/// which makes the next frame long too - and so on.
+#[derive(Debug)]
+pub struct FramePacing {
+	/// Maximum simulated time in seconds that can be fed to updates in a single frame. Anything beyond this is dropped,
+	/// so the game slows down instead of spiralling.
+	pub max_catch_up: f32,
+
+	/// Frames longer than this in seconds are counted and logged as hitches.
+	pub hitch_threshold: f32,
+
+	hitch_count: u32,
+	last_hitch: Option<HitchReport>,
+
+	/// Whether `last_hitch` is the current frame.
+	hitch_this_frame: bool,
+	dropped_time: f32,
+
+	current: FrameTimings,
+	previous: FrameTimings,
+	frame_end: Option<Instant>,
+}
+
+impl FramePacing {
+	pub(crate) fn new() -> FramePacing {
+		FramePacing {
+			max_catch_up: 0.25,
+			hitch_threshold: 0.1,
+
+			hitch_count: 0,
+			last_hitch: None,
+
+			hitch_this_frame: false,
+			dropped_time: 0.0,
+
+			current: FrameTimings::default(),
+			previous: FrameTimings::default(),
+			frame_end: None,
+		}
+	}
+
+	pub fn hitch_count(&self) -> u32 {
+		self.hitch_count
+	}
+
+	pub fn last_hitch(&self) -> Option<&HitchReport> {
+		self.last_hitch.as_ref()
+	}
+
+	/// Simulated time in seconds that was discarded by [`FramePacing::max_catch_up`] this frame.
+	pub fn dropped_time(&self) -> f32 {
+		self.dropped_time
+	}
+
+	/// Timings of the last complete frame.
+	pub fn previous_timings(&self) -> &FrameTimings {
+		&self.previous
+	}
+
+	pub(crate) fn start_frame(&mut self, real_delta_time: f32) {
+		self.hitch_this_frame = false;
+		self.dropped_time = 0.0;
+
+		// The very first frame includes startup, so isn't worth reporting.
+		let Some(frame_end) = self.frame_end.take() else { return };
+
+		self.current.durations[FrameSection::Wait as usize] = frame_end.elapsed().as_secs_f32();
+		self.previous = std::mem::take(&mut self.current);
+
+		if real_delta_time > self.hitch_threshold {
+			self.hitch_count += 1;
+			self.last_hitch = Some(HitchReport {
+				frame_time: real_delta_time,
+				timings: self.previous,
+				dropped_time: 0.0,
+			});
+
+			self.hitch_this_frame = true;
+
+			log::warn!("Hitch: {:.1}ms, mostly {:?} ({})", real_delta_time * 1000.0, self.previous.dominant_section(), self.previous);
+		}
+	}
+
+	pub(crate) fn record_section(&mut self, section: FrameSection, start: Instant) {
+		self.current.durations[section as usize] = start.elapsed().as_secs_f32();
+
+		if section == FrameSection::Submit {
+			self.frame_end = Some(Instant::now());
+		}
+	}
+
+	/// Clamp simulated time for this frame to [`FramePacing::max_catch_up`].
+	pub(crate) fn clamp_catch_up(&mut self, delta_time: f32) -> f32 {
+		let clamped = delta_time.min(self.max_catch_up);
+		let dropped_time = delta_time - clamped;
+
+		self.dropped_time = dropped_time;
+
+		if dropped_time > 0.0 {
+			log::warn!("Dropped {:.1}ms of simulation time", dropped_time * 1000.0);
+
+			// Only if the last hitch was this frame - otherwise it belongs to some earlier frame.
+			if self.hitch_this_frame {
+				if let Some(hitch) = &mut self.last_hitch {
+					hitch.dropped_time = dropped_time;
+				}
+			}
+		}
+
+		clamped
+	}
+}
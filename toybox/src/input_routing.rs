@@ -0,0 +1,142 @@
+use crate::prelude::*;
+
+
+/// Decides which of several overlapping consumers of input gets to respond to it. Layers are registered with a
+/// priority, and each frame layers that are under the pointer (or have keyboard focus) claim input.
+/// A layer should only respond to input if no higher priority layer has claimed it.
+///
+/// egui is always the highest priority layer and world picking always the lowest, with game UI in between.
+///
+/// ```ignore
+/// let hud = ctx.input_router.register("hud", 0);
+///
+/// // In update
+/// if hud_rect.contains(mouse_pos) {
+/// 	ctx.input_router.claim_pointer(hud);
+/// }
+///
+/// if ctx.input_router.pointer_available(InputLayer::WORLD) {
+/// 	// pick
+/// }
+/// ```
+///
+/// Claims from the previous frame are also respected, so the order layers are updated within a frame doesn't
+/// matter - at the cost of a frame of latency when the pointer leaves a layer.
+#[derive(Debug)]
+pub struct InputRouter {
+	layers: Vec<LayerInfo>,
+
+	claims: Vec<Claim>,
+	previous_claims: Vec<Claim>,
+
+	occluding_gate: Gate,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InputLayer(u32);
+
+impl InputLayer {
+	pub const EGUI: InputLayer = InputLayer(0);
+	pub const WORLD: InputLayer = InputLayer(1);
+}
+
+#[derive(Debug)]
+struct LayerInfo {
+	name: String,
+	priority: i32,
+
+	/// Whether claiming input should release mouse capture. See [`input::System::set_occluded`].
+	occludes_capture: bool,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Claim {
+	pointer: bool,
+	keyboard: bool,
+}
+
+impl InputRouter {
+	pub(crate) fn new() -> InputRouter {
+		let mut router = InputRouter {
+			layers: Vec::new(),
+			claims: Vec::new(),
+			previous_claims: Vec::new(),
+			occluding_gate: Gate::new(),
+		};
+
+		router.register_internal("egui", i32::MAX, true);
+		router.register_internal("world", i32::MIN, false);
+		router
+	}
+
+	/// Higher priorities get first claim on input. Priorities must be strictly between `i32::MIN` and `i32::MAX`.
+	pub fn register(&mut self, name: impl Into<String>, priority: i32) -> InputLayer {
+		assert!(priority != i32::MIN && priority != i32::MAX, "Input layer priority is reserved for builtin layers");
+		self.register_internal(name.into(), priority, false)
+	}
+
+	/// Like [`InputRouter::register`], but claiming input also releases mouse capture, like egui does.
+	pub fn register_occluding(&mut self, name: impl Into<String>, priority: i32) -> InputLayer {
+		assert!(priority != i32::MIN && priority != i32::MAX, "Input layer priority is reserved for builtin layers");
+		self.register_internal(name.into(), priority, true)
+	}
+
+	fn register_internal(&mut self, name: impl Into<String>, priority: i32, occludes_capture: bool) -> InputLayer {
+		let layer = InputLayer(self.layers.len() as u32);
+		self.layers.push(LayerInfo { name: name.into(), priority, occludes_capture });
+		self.claims.push(Claim::default());
+		self.previous_claims.push(Claim::default());
+		layer
+	}
+
+	pub fn name(&self, layer: InputLayer) -> &str {
+		&self.layers[layer.0 as usize].name
+	}
+
+	/// Claim pointer input for this frame.
+	pub fn claim_pointer(&mut self, layer: InputLayer) {
+		self.claims[layer.0 as usize].pointer = true;
+	}
+
+	/// Claim keyboard input for this frame.
+	pub fn claim_keyboard(&mut self, layer: InputLayer) {
+		self.claims[layer.0 as usize].keyboard = true;
+	}
+
+	/// Whether `layer` should respond to the pointer, i.e., no higher priority layer has claimed it.
+	pub fn pointer_available(&self, layer: InputLayer) -> bool {
+		self.highest_claim(|claim| claim.pointer)
+			.map_or(true, |owner| self.layers[owner].priority <= self.layers[layer.0 as usize].priority)
+	}
+
+	/// Whether `layer` should respond to the keyboard, i.e., no higher priority layer has claimed it.
+	pub fn keyboard_available(&self, layer: InputLayer) -> bool {
+		self.highest_claim(|claim| claim.keyboard)
+			.map_or(true, |owner| self.layers[owner].priority <= self.layers[layer.0 as usize].priority)
+	}
+
+	/// The highest priority layer that has claimed the pointer, if any.
+	pub fn pointer_owner(&self) -> Option<InputLayer> {
+		self.highest_claim(|claim| claim.pointer)
+			.map(|index| InputLayer(index as u32))
+	}
+
+	fn highest_claim(&self, filter: impl Fn(&Claim) -> bool) -> Option<usize> {
+		self.claims.iter().zip(&self.previous_claims)
+			.enumerate()
+			.filter(|(_, (current, previous))| filter(current) || filter(previous))
+			.max_by_key(|(index, _)| self.layers[*index].priority)
+			.map(|(index, _)| index)
+	}
+
+	/// Returns whether any occluding layer claimed input this frame, and begins the next frame.
+	pub(crate) fn end_frame(&mut self) -> GateState {
+		let occluding = self.claims.iter().zip(&self.layers)
+			.any(|(claim, layer)| layer.occludes_capture && (claim.pointer || claim.keyboard));
+
+		std::mem::swap(&mut self.claims, &mut self.previous_claims);
+		self.claims.fill(Claim::default());
+
+		self.occluding_gate.update(occluding)
+	}
+}
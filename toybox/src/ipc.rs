@@ -0,0 +1,267 @@
+//! Optional local server that streams frame stats and log output to external tools, and accepts simple commands back -
+//! for dashboards, editors or tuning scripts that want to watch or poke a running game.
+//!
+//! Enabled by setting `ipc.enabled = true` in config, or passing it on the command line. Listens on a unix socket in
+//! the temp directory named after the app, or on loopback TCP where unix sockets aren't available.
+//!
+//! The protocol is newline delimited json in both directions. See [`IpcEvent`] and [`IpcCommand`].
+
+use crate::prelude::*;
+use crate::{Context, Time};
+
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum IpcEvent {
+	Frame {
+		frame: u64,
+		delta_time: f32,
+		draw_calls: u32,
+		compute_dispatches: u32,
+		triangles: u64,
+	},
+
+	Log {
+		level: String,
+		target: String,
+		message: String,
+	},
+}
+
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum IpcCommand {
+	/// `stage` is the debug name of a [`gfx::FrameStage`] that has been used at least once, e.g. `"Main"` or `"Ui(0)"`.
+	SetStageEnabled { stage: String, enabled: bool },
+
+	/// Previews a config value - see [`cfg::Config::preview_value_from_str`].
+	SetConfig { key: String, value: String },
+}
+
+
+type ClientWriter = Box<dyn Write + Send>;
+
+
+/// Clients are only ever written to from the writer thread, so that slow clients can't stall the frame.
+enum WriterMessage {
+	Connect(ClientWriter),
+	Send(Vec<u8>),
+}
+
+
+pub struct IpcServer {
+	address: String,
+	num_clients: Arc<AtomicUsize>,
+	writer_tx: mpsc::Sender<WriterMessage>,
+	command_rx: mpsc::Receiver<IpcCommand>,
+	log_rx: mpsc::Receiver<host::LogEntry>,
+	frame: u64,
+}
+
+impl IpcServer {
+	#[instrument(skip_all, name="toybox IpcServer::start")]
+	pub fn start(app_name: &str) -> anyhow::Result<IpcServer> {
+		let num_clients = Arc::new(AtomicUsize::new(0));
+		let (command_tx, command_rx) = mpsc::channel();
+		let (writer_tx, writer_rx) = mpsc::channel();
+
+		spawn_writer(writer_rx, Arc::clone(&num_clients))?;
+
+		let address = listen(app_name, writer_tx.clone(), command_tx)?;
+		log::info!("IPC server listening on {address}");
+
+		Ok(IpcServer {
+			address,
+			num_clients,
+			writer_tx,
+			command_rx,
+			log_rx: host::tap_logs(),
+			frame: 0,
+		})
+	}
+
+	pub fn address(&self) -> &str {
+		&self.address
+	}
+
+	pub fn num_clients(&self) -> usize {
+		self.num_clients.load(Ordering::Relaxed)
+	}
+
+	/// Queue `event` to be sent to all connected clients from the writer thread. Clients that can't keep up are
+	/// disconnected.
+	pub fn send(&self, event: &IpcEvent) {
+		if self.num_clients() == 0 {
+			return
+		}
+
+		let Ok(mut line) = serde_json::to_vec(event) else { return };
+		line.push(b'\n');
+
+		let _ = self.writer_tx.send(WriterMessage::Send(line));
+	}
+
+	pub(crate) fn end_frame(&mut self, time: &Time, frame_stats: &gfx::FrameStats) {
+		// Drain logs even without clients so they don't build up.
+		let logs: Vec<_> = self.log_rx.try_iter().collect();
+
+		if self.num_clients() > 0 {
+			for entry in logs {
+				self.send(&IpcEvent::Log {
+					level: entry.level.to_string(),
+					target: entry.target,
+					message: entry.message,
+				});
+			}
+
+			let total = frame_stats.total();
+
+			self.send(&IpcEvent::Frame {
+				frame: self.frame,
+				delta_time: time.real_delta_time(),
+				draw_calls: total.draw_calls,
+				compute_dispatches: total.compute_dispatches,
+				triangles: total.triangles,
+			});
+		}
+
+		self.frame += 1;
+	}
+
+	fn take_commands(&self) -> Vec<IpcCommand> {
+		self.command_rx.try_iter().collect()
+	}
+}
+
+
+impl Context {
+	pub(crate) fn process_ipc_commands(&mut self) {
+		let Some(ipc) = &self.ipc else { return };
+
+		for command in ipc.take_commands() {
+			log::info!("IPC command: {command:?}");
+
+			match command {
+				IpcCommand::SetStageEnabled { stage, enabled } => {
+					let known_stage = self.gfx.frame_encoder.known_stages()
+						.find(|known| format!("{known:?}") == stage);
+
+					match known_stage {
+						Some(stage) => self.gfx.frame_encoder.set_stage_enabled(stage, enabled),
+						None => log::warn!("IPC: unknown stage '{stage}'"),
+					}
+				}
+
+				IpcCommand::SetConfig { key, value } => {
+					self.cfg.preview_value_from_str(&key, &value);
+				}
+			}
+		}
+	}
+}
+
+
+fn spawn_writer(writer_rx: mpsc::Receiver<WriterMessage>, num_clients: Arc<AtomicUsize>) -> anyhow::Result<()> {
+	std::thread::Builder::new()
+		.name("ipc writer".into())
+		.spawn(move || {
+			let mut clients: Vec<ClientWriter> = Vec::new();
+
+			for message in writer_rx {
+				match message {
+					WriterMessage::Connect(client) => clients.push(client),
+					WriterMessage::Send(line) => clients.retain_mut(|client| client.write_all(&line).is_ok()),
+				}
+
+				num_clients.store(clients.len(), Ordering::Relaxed);
+			}
+		})?;
+
+	Ok(())
+}
+
+
+fn spawn_client_reader(reader: impl std::io::Read + Send + 'static, command_tx: mpsc::Sender<IpcCommand>) {
+	std::thread::spawn(move || {
+		for line in BufReader::new(reader).lines() {
+			let Ok(line) = line else { return };
+			if line.trim().is_empty() {
+				continue
+			}
+
+			match serde_json::from_str(&line) {
+				Ok(command) => if command_tx.send(command).is_err() { return },
+				Err(error) => log::warn!("IPC: bad command '{line}': {error}"),
+			}
+		}
+	});
+}
+
+
+#[cfg(unix)]
+fn listen(app_name: &str, writer_tx: mpsc::Sender<WriterMessage>, command_tx: mpsc::Sender<IpcCommand>) -> anyhow::Result<String> {
+	use std::os::unix::net::{UnixListener, UnixStream};
+
+	let path = std::env::temp_dir().join(format!("toybox-{app_name}.sock"));
+
+	// Sockets are left behind if the previous run crashed, but may also belong to another running instance - only
+	// remove it if nothing is listening.
+	match UnixStream::connect(&path) {
+		Ok(_) => anyhow::bail!("IPC socket '{}' is in use by another instance", path.display()),
+		Err(error) if error.kind() == std::io::ErrorKind::ConnectionRefused => {
+			let _ = std::fs::remove_file(&path);
+		}
+		Err(_) => {}
+	}
+
+	let listener = UnixListener::bind(&path)
+		.with_context(|| format!("Binding IPC socket '{}'", path.display()))?;
+
+	std::thread::Builder::new()
+		.name("ipc listener".into())
+		.spawn(move || {
+			for stream in listener.incoming() {
+				let Ok(stream) = stream else { continue };
+				let Ok(reader) = stream.try_clone() else { continue };
+
+				let _ = stream.set_write_timeout(Some(std::time::Duration::from_millis(5)));
+
+				spawn_client_reader(reader, command_tx.clone());
+				let _ = writer_tx.send(WriterMessage::Connect(Box::new(stream)));
+			}
+		})?;
+
+	Ok(path.display().to_string())
+}
+
+// TODO(pat.m): named pipes on windows
+#[cfg(not(unix))]
+fn listen(_app_name: &str, writer_tx: mpsc::Sender<WriterMessage>, command_tx: mpsc::Sender<IpcCommand>) -> anyhow::Result<String> {
+	use std::net::TcpListener;
+
+	let listener = TcpListener::bind("127.0.0.1:0")
+		.context("Binding IPC listener")?;
+
+	let address = listener.local_addr()?.to_string();
+
+	std::thread::Builder::new()
+		.name("ipc listener".into())
+		.spawn(move || {
+			for stream in listener.incoming() {
+				let Ok(stream) = stream else { continue };
+				let Ok(reader) = stream.try_clone() else { continue };
+
+				let _ = stream.set_write_timeout(Some(std::time::Duration::from_millis(5)));
+
+				spawn_client_reader(reader, command_tx.clone());
+				let _ = writer_tx.send(WriterMessage::Connect(Box::new(stream)));
+			}
+		})?;
+
+	Ok(address)
+}
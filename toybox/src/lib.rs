@@ -7,15 +7,116 @@ pub use crate::prelude::*;
 pub mod context;
 pub use context::Context;
 
+pub mod clipboard;
+pub use clipboard::Clipboard;
+
+pub mod window;
+pub use window::{Window, RenderingPause, WindowEventHookId};
+
+pub mod determinism;
+pub use determinism::DeterminismAudit;
+
+pub mod snapshot;
+pub use snapshot::{Snapshot, SnapshotRing};
+
+pub mod settings;
+
+pub mod buffer_visualizer;
+pub mod screenshot_compare;
+
+pub mod assets;
+pub use assets::{Assets, PreloadHandle};
+
+pub mod input_routing;
+pub use input_routing::{InputRouter, InputLayer};
+
+pub mod time;
+pub use time::Time;
+
+pub mod frame_pacing;
+pub use frame_pacing::{FramePacing, FrameSection};
+
+pub mod features;
+pub use features::FeatureReport;
+
+pub mod build_info;
+pub use build_info::{build_info, BuildInfo};
+
+pub mod stage_conditions;
+pub use stage_conditions::{StageConditions, ConditionTarget, ConfigCondition};
+
+pub mod sound_metadata;
+pub use sound_metadata::{SoundLibrary, SoundMetadata, SoundInstance};
+
+pub mod sound_events;
+pub use sound_events::{SoundEvents, SoundEvent, PlaySound, SoundEventTable, SpatialState};
+
+pub mod autosave;
+pub use autosave::{Autosave, AutosaveSettings, AutosaveRequest, AutosaveSlot};
+
+pub mod tasks;
+pub use tasks::{TaskScheduler, TaskHandle, TaskPriority, TaskStep, TaskContext};
+
+pub mod profiler;
+pub use profiler::{Profiler, ProfileScope};
+
+pub mod palette;
+pub use palette::{Palette, PaletteLibrary};
+
+mod resource_cache;
+
+pub mod tilemap;
+pub use tilemap::{Tilemap, TilemapLayer, TilemapView};
+
+pub mod world_labels;
+pub use world_labels::{WorldLabels, WorldLabel, LabelOrientation};
+
+pub mod bake;
+
+pub mod ipc;
+pub use ipc::IpcServer;
+
+pub mod console;
+pub use console::{Console, ConsoleCommand, ArgKind, ArgValue};
+
+pub mod dialogs;
+pub use dialogs::{Dialogs, FileDialog, DialogHandle, DialogResult, PickedFile};
+
+#[cfg(feature="xr")]
+pub mod xr;
+#[cfg(feature="xr")]
+pub use xr::{Xr, XrPose, XrHand, XrHandSide};
+
+pub mod device_simulation;
+pub use device_simulation::DeviceSimulationSettings;
+
 mod debug;
 
 
+/// Per frame, the order of operations is:
+/// - window and device events are processed and fed to [`input::System`]
+/// - egui begins its frame, and the debug menu is shown
+/// - [`App::update`] is called, once or once per fixed tick (see [`Context::fixed_timestep`])
+/// - [`App::present`] is called
+/// - egui is painted, and the frame is submitted to the gpu
 pub trait App {
 	fn customise_debug_menu(&mut self, _: &mut Context, _: &mut egui::Ui) {}
+
+	/// Gameplay and simulation should be mutated here. Input and egui are ready to be queried.
+	/// `dt` is the scaled time in seconds since the last update (see [`Time`]), or the fixed timestep if one is configured.
+	/// With a fixed timestep this may be called zero or more times per frame, and won't be called at all while paused.
+	fn update(&mut self, _: &mut Context, _dt: f32) {}
+
+	/// Rendering should happen here. Called exactly once per frame, after all updates.
 	fn present(&mut self, _: &mut Context);
 }
 
 
+#[cfg(feature="embed-resources")]
+static EMBEDDED_RESOURCES: vfs::EmbeddedResources = vfs::EmbeddedResources::new(
+	include!(concat!(env!("OUT_DIR"), "/embedded_resources.rs")));
+
+
 pub fn run<F, A>(app_name: &str, start_app: F) -> anyhow::Result<()>
 	where A: App + 'static
 		, F: FnOnce(&mut Context) -> anyhow::Result<A>
@@ -24,19 +125,60 @@ pub fn run<F, A>(app_name: &str, start_app: F) -> anyhow::Result<()>
 }
 
 
-pub fn run_with_settings<F, A>(settings: host::Settings<'_>, start_app: F) -> anyhow::Result<()>
+pub fn run_with_settings<F, A>(mut settings: host::Settings<'_>, start_app: F) -> anyhow::Result<()>
 	where A: App + 'static
 		, F: FnOnce(&mut Context) -> anyhow::Result<A>
 {
 	host::init_environment();
+	build_info::install_panic_hook();
+
+	log::info!("{}", build_info().summary());
 
 	let _span = tracing::info_span!("toybox early start").entered();
 
-	let vfs = vfs::Vfs::new(settings.app_name)
-		.context("Initialising Vfs")?;
+	let reverse_z = settings.reverse_z;
+	let platform = platform::init();
+
+	#[cfg(feature="embed-resources")]
+	let vfs = vfs::Vfs::with_embedded_resources(settings.app_name, EMBEDDED_RESOURCES);
+	#[cfg(not(feature="embed-resources"))]
+	let vfs = vfs::Vfs::new(settings.app_name);
+
+	let mut vfs = vfs.context("Initialising Vfs")?;
+
+	if let Some(path) = platform.cloud_save_root(settings.app_name) {
+		vfs.set_platform_user_data_root(path);
+	}
 
 	let cfg = cfg::Config::from_vfs(&vfs)?;
-	let audio = audio::System::init();
+
+	// For editors writing into the resource root - see vfs::journal.
+	if cfg.get_bool("vfs.journal") == Some(true) {
+		vfs.enable_journal().context("Enabling resource journal")?;
+	}
+
+	if let Some(name) = cfg.get_string("gfx.context_mode") {
+		match host::GlContextMode::from_name(name) {
+			Some(mode) => settings.gl_context_mode = mode,
+			None => log::warn!("Unknown gfx.context_mode '{name}' - expected debug, release or no_error"),
+		}
+	}
+
+	let mut stream_settings = audio::StreamSettings::default();
+	let audio_settings: settings::AudioSettings = cfg.bind_or_default(settings::AUDIO_SECTION);
+	if let Some(layout) = audio_settings.channel_layout() {
+		stream_settings = stream_settings.channel_layout(layout);
+	}
+
+	let audio = audio::System::init(stream_settings);
+
+	let ipc = match cfg.get_bool("ipc.enabled") {
+		Some(true) => IpcServer::start(settings.app_name)
+			.inspect_err(|error| log::error!("Failed to start IPC server: {error:?}"))
+			.ok(),
+
+		_ => None,
+	};
 
 	_span.exit();
 
@@ -47,7 +189,20 @@ pub fn run_with_settings<F, A>(settings: host::Settings<'_>, start_app: F) -> an
 		let backbuffer_size = Vec2i::new(width, height);
 
 		let mut gfx = tracing::info_span!("init gfx").in_scope(|| {
-			let core = gfx::Core::new(host.gl.clone());
+			let mut core = gfx::Core::new(host.gl.clone());
+			core.set_backbuffer_color_bits(host.color_bits());
+			core.set_reverse_z(reverse_z);
+			core.set_capture_safe_mode(cfg.get_bool("gfx.capture_safe").unwrap_or(true));
+
+			if cfg.get_bool("gfx.panic_on_errors") == Some(false) {
+				core.set_debug_policy(gfx::DebugPolicy::log_only());
+			}
+
+			// Known driver spam, by message id.
+			if let Some(ids) = cfg.get_value("gfx.ignored_debug_ids").and_then(|value| value.as_array()) {
+				let ids: Vec<u32> = ids.iter().filter_map(|id| id.as_integer()).map(|id| id as u32).collect();
+				core.modify_debug_policy(|policy| policy.ignore_ids("gfx.ignored_debug_ids", ids));
+			}
 			gfx::System::new(core)
 		})?;
 
@@ -59,31 +214,87 @@ pub fn run_with_settings<F, A>(settings: host::Settings<'_>, start_app: F) -> an
 		let egui = egui::Context::default();
 		let egui_integration = egui_backend::Integration::new(egui.clone(), host.window.clone(), &mut gfx)?;
 
+		let sound_events = SoundEvents::new(&bus);
+		let autosave = Autosave::new(&bus);
+
 		let mut context = context::Context {
 			gfx,
 			audio,
+			music: audio::MusicPlayer::new(),
+			sounds: SoundLibrary::default(),
+			sound_events,
+			palettes: PaletteLibrary::default(),
+			profiler: Profiler::default(),
+			tasks: TaskScheduler::default(),
+			autosave,
+			dialogs: Dialogs::new(host.window.clone()),
+			console: Console::new(),
+			world_labels: WorldLabels::default(),
+
+			#[cfg(feature="xr")]
+			xr: None,
+
+			ipc,
 			input,
 			egui,
 			cfg,
 			vfs,
 			bus,
+			platform,
+			clipboard: Clipboard::new(),
+			window: Window::new(host),
+			determinism: DeterminismAudit::default(),
+			assets: Assets::default(),
+			input_router: InputRouter::new(),
+			ui_navigation: input::UiNavigation::new(),
+			time: Time::new(),
+			frame_pacing: FramePacing::new(),
+			stage_conditions: StageConditions::default(),
 
 			egui_integration,
-			egui_claiming_input_gate: Gate::new(),
 
 			show_debug_menu: false,
 			wants_quit: false,
+
+			fixed_timestep: None,
+			splash_screen: host.splash_screen(),
+			settings_changed_at: None,
+			fixed_timestep_accumulator: 0.0,
+			fixed_tick: 0,
 		};
 
+		context.apply_startup_settings();
+
+		let scale_factor_override = context.cfg.get_float("window.scale_factor").map(|scale_factor| scale_factor as f32);
+		context.set_scale_factor_override(scale_factor_override);
+
+		#[cfg(feature="xr")]
+		if let Some(session) = host.take_xr_session() {
+			// The XR runtime paces frames, so the desktop window can't also wait on vsync.
+			context.window.set_vsync(false);
+			context.xr = Some(xr::Xr::new(session, &mut context.gfx));
+		}
+
+		context.apply_startup_device_simulation();
+
+		if let Some(path) = context.cfg.get_string("sound_events.table") {
+			context.sound_events.set_table_path(path);
+		}
+		context.autosave.apply_config(&context.cfg);
+		context.profiler.apply_settings(&context.cfg.bind_or_default(profiler::PROFILER_SECTION));
+
 		// Required since we now call this at the end of frames rather than the beginning.
 		context.prepare_frame();
 
 		let app = tracing::info_span!("app start").in_scope(|| start_app(&mut context))?;
+		context.splash_screen = None;
 
 		Ok(Box::new(HostedApp {
 			context,
 			debug_menu_state: debug::MenuState::default(),
 			app,
+
+			rendering_paused: false,
 		}))
 	})
 }
@@ -96,11 +307,17 @@ struct HostedApp<A: App> {
 	context: context::Context,
 	debug_menu_state: debug::MenuState,
 	app: A,
+
+	rendering_paused: bool,
 }
 
 
 impl<A: App> host::HostedApp for HostedApp<A> {
 	fn window_event(&mut self, _: &host::ActiveEventLoop, event: host::WindowEvent) {
+		if self.context.window.run_event_hooks(&event) {
+			return
+		}
+
 		if self.context.egui_integration.on_event(&event) {
 			self.context.input.tracker.track_focus_lost();
 			return
@@ -117,6 +334,10 @@ impl<A: App> host::HostedApp for HostedApp<A> {
 				// self.app.resize(new_size);
 			}
 
+			host::WindowEvent::ScaleFactorChanged{..} => {
+				self.context.notify_scale_factor_changed();
+			}
+
 			event => {
 				self.context.input.on_window_event(&event);
 			}
@@ -129,15 +350,37 @@ impl<A: App> host::HostedApp for HostedApp<A> {
 
 	#[instrument(skip_all, name="toybox draw")]
 	fn draw(&mut self, event_loop: &host::ActiveEventLoop) {
+		if self.context.window.is_rendering_paused() {
+			// Nothing is swapped while paused, so there's no vsync to keep this from spinning.
+			self.rendering_paused = true;
+			std::thread::sleep(std::time::Duration::from_millis(10));
+			return
+		}
+
+		if std::mem::take(&mut self.rendering_paused) {
+			self.context.resume_after_pause();
+		}
+
 		self.context.start_frame();
 
 		debug::show_menu(&mut self.context, &mut self.app, &mut self.debug_menu_state);
+		console::show(&mut self.context);
 
+		let section_start = std::time::Instant::now();
+		tracing::info_span!("app update").in_scope(|| {
+			self.run_updates();
+		});
+		self.context.frame_pacing.record_section(FrameSection::Update, section_start);
+
+		let section_start = std::time::Instant::now();
 		tracing::info_span!("app present").in_scope(|| {
 			self.app.present(&mut self.context);
 		});
+		self.context.frame_pacing.record_section(FrameSection::Present, section_start);
 
+		let section_start = std::time::Instant::now();
 		self.context.finalize_frame();
+		self.context.frame_pacing.record_section(FrameSection::Submit, section_start);
 
 		if self.context.wants_quit {
 			event_loop.exit();
@@ -146,7 +389,52 @@ impl<A: App> host::HostedApp for HostedApp<A> {
 		self.context.prepare_frame();
 	}
 
+	// See gfx::Core::set_capture_safe_mode.
+	fn begin_swap(&mut self) {
+		self.context.gfx.core.set_in_swap_region(true);
+	}
+
+	fn end_swap(&mut self) {
+		self.context.gfx.core.set_in_swap_region(false);
+	}
+
 	fn shutdown(&mut self, _: &host::ActiveEventLoop) {
 		self.context.shutdown();
 	}
+}
+
+/// Upper limit on [`App::update`] calls per frame with a fixed timestep.
+const MAX_FIXED_TICKS_PER_FRAME: u32 = 16;
+
+impl<A: App> HostedApp<A> {
+	fn run_updates(&mut self) {
+		let delta_time = self.context.frame_pacing.clamp_catch_up(self.context.time.delta_time());
+
+		// Zero, negative or NaN timesteps would never drain the accumulator.
+		let fixed_timestep = self.context.fixed_timestep
+			.filter(|&timestep| timestep.is_finite() && timestep > 0.0);
+
+		let Some(fixed_timestep) = fixed_timestep else {
+			self.context.fixed_timestep_accumulator = 0.0;
+			self.app.update(&mut self.context, delta_time);
+			return
+		};
+
+		self.context.fixed_timestep_accumulator += delta_time;
+
+		let mut num_ticks = 0;
+		while self.context.fixed_timestep_accumulator >= fixed_timestep {
+			// A tiny timestep could still mean millions of ticks in one frame - drop whatever is left over rather than
+			// falling further and further behind.
+			if num_ticks >= MAX_FIXED_TICKS_PER_FRAME {
+				self.context.fixed_timestep_accumulator %= fixed_timestep;
+				break
+			}
+
+			self.context.fixed_timestep_accumulator -= fixed_timestep;
+			self.app.update(&mut self.context, fixed_timestep);
+			self.context.fixed_tick += 1;
+			num_ticks += 1;
+		}
+	}
 }
\ No newline at end of file
@@ -0,0 +1,119 @@
+//! Named color palettes loaded from json resources, so colors can be shared between gameplay and UI code and tweaked
+//! without recompiling. Colors are written as sRGB hex, as they appear in image editors, and converted to linear on load:
+//! ```json
+//! {
+//!     "colors": {
+//!         "sky": "#87ceeb",
+//!         "grass": "#3a7d2c",
+//!         "shadow": "#00000080"
+//!     }
+//! }
+//! ```
+
+use crate::prelude::*;
+use crate::resource_cache::ReloadingCache;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+	/// Linear colors, sorted by name.
+	pub colors: BTreeMap<String, Color>,
+
+	/// Missing names that have already been warned about, so that lookups every frame don't spam the log.
+	warned_missing: RefCell<HashSet<String>>,
+}
+
+impl PartialEq for Palette {
+	fn eq(&self, other: &Palette) -> bool {
+		self.colors == other.colors
+	}
+}
+
+#[derive(serde::Deserialize)]
+struct PaletteFile {
+	colors: BTreeMap<String, String>,
+}
+
+impl Palette {
+	pub fn load(vfs: &vfs::Vfs, path: impl AsRef<Path>) -> anyhow::Result<Palette> {
+		let path = path.as_ref();
+
+		let file: PaletteFile = vfs.load_json_resource(path)
+			.with_context(|| format!("Loading palette '{}'", path.display()))?;
+
+		let colors = file.colors.into_iter()
+			.map(|(name, hex)| {
+				let color = Color::from_hex(&hex)
+					.with_context(|| format!("Parsing color '{name}' in palette '{}'", path.display()))?;
+
+				Ok((name, color))
+			})
+			.collect::<anyhow::Result<_>>()?;
+
+		Ok(Palette { colors, ..Palette::default() })
+	}
+
+	pub fn get(&self, name: &str) -> Option<Color> {
+		self.colors.get(name).copied()
+	}
+
+	/// Like [`Palette::get`], but missing colors are magenta so they stand out. Each missing name is warned about once.
+	pub fn color(&self, name: &str) -> Color {
+		self.get(name).unwrap_or_else(|| {
+			if self.warned_missing.borrow_mut().insert(name.to_owned()) {
+				log::warn!("Missing palette color '{name}'");
+			}
+
+			Color::light_magenta()
+		})
+	}
+}
+
+
+/// Caches [`Palette`]s by resource path, reloading them when they change on disk.
+pub struct PaletteLibrary {
+	palettes: ReloadingCache<Palette>,
+}
+
+impl Default for PaletteLibrary {
+	fn default() -> PaletteLibrary {
+		PaletteLibrary { palettes: ReloadingCache::new("palette") }
+	}
+}
+
+impl PaletteLibrary {
+	/// The palette at `path`, loading it if it hasn't been already.
+	/// Palettes that fail to load are logged and treated as empty.
+	pub fn get(&mut self, vfs: &vfs::Vfs, path: impl AsRef<Path>) -> &Palette {
+		let path = path.as_ref();
+		self.palettes.get_or_load(path, || Palette::load(vfs, path))
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item=(&Path, &Palette)> + '_ {
+		self.palettes.iter()
+	}
+
+	/// Drop any palettes that changed on disk, so they're reloaded on next use.
+	pub(crate) fn update(&mut self, vfs: &vfs::Vfs) {
+		self.palettes.drop_changed(vfs, Path::to_owned);
+	}
+}
+
+
+
+/// Swatches for every color in `palette`, with names and hex values on hover.
+pub fn palette_ui(ui: &mut egui::Ui, palette: &Palette) {
+	ui.horizontal_wrapped(|ui| {
+		for (name, color) in palette.colors.iter() {
+			let (rect, response) = ui.allocate_exact_size(egui::vec2(24.0, 24.0), egui::Sense::hover());
+			let [r, g, b, a] = color.to_srgb8();
+			ui.painter().rect_filled(rect, 2.0, egui::Color32::from_rgba_unmultiplied(r, g, b, a));
+
+			response.on_hover_text(format!("{name}\n{}", color.to_hex()));
+		}
+	});
+}
@@ -11,6 +11,8 @@ pub use toybox_input as input;
 pub use toybox_egui as egui_backend;
 pub use toybox_vfs as vfs;
 pub use toybox_bus as bus;
+pub use toybox_handle as handle;
+pub use toybox_platform as platform;
 
 pub use host::prelude::*;
 pub use gfx::prelude::*;
@@ -0,0 +1,281 @@
+//! Lightweight hierarchical timing for app code. [`Context::profile_scope`] guards show up both as tracing spans - so
+//! in Tracy when the `tracy` feature is enabled - and in the debug menu's Profiler window.
+//!
+//! Top level scopes can be given per-frame budgets, either with [`Profiler::set_budget`] or in config:
+//! ```toml
+//! [profiler.budgets_ms]
+//! physics = 2.0
+//! ai = 1.5
+//! ```
+//! Scopes that go over budget are highlighted in the Profiler window and logged, at most once a second per scope.
+
+use crate::prelude::*;
+use crate::Context;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+
+pub const PROFILER_SECTION: &str = "profiler";
+
+const BUDGET_WARNING_INTERVAL: Duration = Duration::from_secs(1);
+
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct ProfilerSettings {
+	/// Per-frame budgets in milliseconds for top level scopes, by name.
+	pub budgets_ms: BTreeMap<String, f32>,
+}
+
+impl cfg::ConfigSection for ProfilerSettings {
+	fn validate(&self, errors: &mut Vec<String>) {
+		for (name, budget) in self.budgets_ms.iter() {
+			cfg::check_range(errors, &format!("budgets_ms.{name}"), *budget, 0.0..);
+		}
+	}
+}
+
+
+/// Total time spent in all instances of a scope with the same name and parent, within one frame.
+#[derive(Debug, Clone)]
+pub struct ScopeTiming {
+	pub name: &'static str,
+	pub depth: usize,
+	pub parent: Option<usize>,
+	pub calls: u32,
+	pub duration: Duration,
+}
+
+
+/// See [`Context::profile_scope`].
+pub struct ProfileScope {
+	state: Rc<RefCell<ProfilerState>>,
+	index: usize,
+	start: Instant,
+	_span: tracing::span::EnteredSpan,
+}
+
+impl Drop for ProfileScope {
+	fn drop(&mut self) {
+		let elapsed = self.start.elapsed();
+		self.state.borrow_mut().exit_scope(self.index, elapsed);
+	}
+}
+
+
+#[derive(Clone, Default)]
+pub struct Profiler {
+	state: Rc<RefCell<ProfilerState>>,
+}
+
+#[derive(Default)]
+struct ProfilerState {
+	current: Vec<ScopeTiming>,
+	previous: Vec<ScopeTiming>,
+	open_scopes: Vec<usize>,
+
+	budgets: HashMap<&'static str, Duration>,
+	config_budgets: BTreeMap<String, Duration>,
+
+	last_budget_warning: HashMap<&'static str, Instant>,
+	budget_overruns: u32,
+}
+
+impl Profiler {
+	/// Start timing a scope named `name`, nested within any scopes already open. Timing stops when the guard is dropped.
+	pub fn scope(&self, name: &'static str) -> ProfileScope {
+		let span = tracing::info_span!("profile_scope", name).entered();
+		let index = self.state.borrow_mut().enter_scope(name);
+
+		ProfileScope {
+			state: Rc::clone(&self.state),
+			index,
+			start: Instant::now(),
+			_span: span,
+		}
+	}
+
+	/// Warn when the top level scope `name` takes longer than `budget` in total in a frame.
+	/// Overrides any budget set in config.
+	pub fn set_budget(&self, name: &'static str, budget: impl Into<Option<Duration>>) {
+		let mut state = self.state.borrow_mut();
+
+		match budget.into() {
+			Some(budget) => { state.budgets.insert(name, budget); }
+			None => { state.budgets.remove(name); }
+		}
+	}
+
+	pub fn budget(&self, name: &str) -> Option<Duration> {
+		self.state.borrow().budget(name)
+	}
+
+	/// Scopes recorded during the last complete frame, in the order they were first entered.
+	pub fn previous_frame(&self) -> Vec<ScopeTiming> {
+		self.state.borrow().previous.clone()
+	}
+
+	/// Number of times any scope has gone over budget, since startup.
+	pub fn budget_overruns(&self) -> u32 {
+		self.state.borrow().budget_overruns
+	}
+
+	pub(crate) fn apply_settings(&self, settings: &ProfilerSettings) {
+		self.state.borrow_mut().config_budgets = settings.budgets_ms.iter()
+			.map(|(name, budget_ms)| (name.clone(), Duration::from_secs_f32(budget_ms / 1000.0)))
+			.collect();
+	}
+
+	pub(crate) fn end_frame(&self) {
+		let mut state = self.state.borrow_mut();
+
+		if !state.open_scopes.is_empty() {
+			log::warn!("{} profile scopes still open at end of frame - scopes shouldn't outlive a frame", state.open_scopes.len());
+			state.open_scopes.clear();
+		}
+
+		state.check_budgets();
+
+		let current = std::mem::take(&mut state.current);
+		state.previous = current;
+	}
+}
+
+impl ProfilerState {
+	fn enter_scope(&mut self, name: &'static str) -> usize {
+		let parent = self.open_scopes.last().copied();
+
+		// Merge with earlier calls to the same scope in the same parent, so loops don't flood the hierarchy.
+		let existing = self.current.iter()
+			.position(|timing| timing.parent == parent && timing.name == name);
+
+		let index = existing.unwrap_or_else(|| {
+			self.current.push(ScopeTiming {
+				name,
+				depth: self.open_scopes.len(),
+				parent,
+				calls: 0,
+				duration: Duration::ZERO,
+			});
+
+			self.current.len() - 1
+		});
+
+		self.open_scopes.push(index);
+		index
+	}
+
+	fn exit_scope(&mut self, index: usize, elapsed: Duration) {
+		// Scopes left open across end_frame no longer refer to anything.
+		let Some(position) = self.open_scopes.iter().rposition(|&open| open == index) else { return };
+		self.open_scopes.truncate(position);
+
+		if let Some(timing) = self.current.get_mut(index) {
+			timing.calls += 1;
+			timing.duration += elapsed;
+		}
+	}
+
+	fn budget(&self, name: &str) -> Option<Duration> {
+		self.budgets.get(name).copied()
+			.or_else(|| self.config_budgets.get(name).copied())
+	}
+
+	fn check_budgets(&mut self) {
+		let now = Instant::now();
+
+		let overruns: Vec<_> = self.current.iter()
+			.filter(|timing| timing.parent.is_none())
+			.filter_map(|timing| {
+				let budget = self.budget(timing.name)?;
+				(timing.duration > budget).then_some((timing.name, timing.duration, budget))
+			})
+			.collect();
+
+		for (name, duration, budget) in overruns {
+			self.budget_overruns += 1;
+
+			let warned_recently = self.last_budget_warning.get(name)
+				.is_some_and(|last| now.duration_since(*last) < BUDGET_WARNING_INTERVAL);
+
+			if !warned_recently {
+				log::warn!("'{name}' over budget: {:.2}ms of {:.2}ms", duration.as_secs_f32() * 1000.0, budget.as_secs_f32() * 1000.0);
+				self.last_budget_warning.insert(name, now);
+			}
+		}
+	}
+}
+
+
+impl Context {
+	/// Time a scope of app code. See [`Profiler::scope`].
+	/// ```rust ignore
+	/// let _scope = ctx.profile_scope("physics");
+	/// ```
+	pub fn profile_scope(&self, name: &'static str) -> ProfileScope {
+		self.profiler.scope(name)
+	}
+}
+
+
+
+pub(crate) fn profiler_ui(ui: &mut egui::Ui, profiler: &Profiler) {
+	ui.label(format!("Budget overruns: {}", profiler.budget_overruns()));
+	ui.separator();
+
+	let timings = profiler.previous_frame();
+	if timings.is_empty() {
+		ui.label("No scopes recorded - see Context::profile_scope");
+		return
+	}
+
+	// Children are only ever recorded after their parents, so a depth first ordering just needs siblings grouped.
+	let mut ordered = Vec::with_capacity(timings.len());
+	let mut stack: Vec<usize> = timings.iter().enumerate().rev()
+		.filter(|(_, timing)| timing.parent.is_none())
+		.map(|(index, _)| index)
+		.collect();
+
+	while let Some(index) = stack.pop() {
+		ordered.push(index);
+		stack.extend(timings.iter().enumerate().rev()
+			.filter(|(_, timing)| timing.parent == Some(index))
+			.map(|(child, _)| child));
+	}
+
+	egui::Grid::new("profiler_scopes")
+		.striped(true)
+		.show(ui, |ui| {
+			ui.label("Scope");
+			ui.label("Time");
+			ui.label("Calls");
+			ui.label("Budget");
+			ui.end_row();
+
+			for index in ordered {
+				let timing = &timings[index];
+				let budget = timing.parent.is_none().then(|| profiler.budget(timing.name)).flatten();
+				let over_budget = budget.is_some_and(|budget| timing.duration > budget);
+
+				let time_text = egui::RichText::new(format!("{:.2}ms", timing.duration.as_secs_f32() * 1000.0));
+				let time_text = match over_budget {
+					true => time_text.color(egui::Color32::LIGHT_RED),
+					false => time_text,
+				};
+
+				ui.label(format!("{}{}", "  ".repeat(timing.depth), timing.name));
+				ui.label(time_text);
+				ui.label(timing.calls.to_string());
+
+				match budget {
+					Some(budget) => ui.label(format!("{:.2}ms", budget.as_secs_f32() * 1000.0)),
+					None => ui.label("-"),
+				};
+
+				ui.end_row();
+			}
+		});
+}
@@ -0,0 +1,57 @@
+//! Data loaded from resources and cached by path, dropped when the resource changes on disk so that it's reloaded on
+//! next use. Shared by [`crate::SoundLibrary`] and [`crate::PaletteLibrary`].
+
+use crate::prelude::*;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+
+pub(crate) struct ReloadingCache<T> {
+	entries: HashMap<PathBuf, T>,
+
+	/// What's cached, for logging - e.g., "palette".
+	kind: &'static str,
+}
+
+impl<T: Default> ReloadingCache<T> {
+	pub fn new(kind: &'static str) -> ReloadingCache<T> {
+		ReloadingCache {
+			entries: HashMap::new(),
+			kind,
+		}
+	}
+
+	/// The entry for `path`, loading it with `load` if it isn't cached. Failures are logged and cached as the default.
+	pub fn get_or_load(&mut self, path: &Path, load: impl FnOnce() -> anyhow::Result<T>) -> &mut T {
+		self.entries.entry(path.to_owned())
+			.or_insert_with(|| {
+				load().unwrap_or_else(|error| {
+					log::error!("{error:?}");
+					T::default()
+				})
+			})
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item=(&Path, &T)> + '_ {
+		self.entries.iter().map(|(path, entry)| (path.as_path(), entry))
+	}
+
+	/// Drop entries whose resource changed on disk. `resource_path` maps a cache key to the resource it was loaded from.
+	pub fn drop_changed(&mut self, vfs: &vfs::Vfs, resource_path: impl Fn(&Path) -> PathBuf) {
+		if vfs.changed_resource_paths().is_empty() {
+			return
+		}
+
+		let kind = self.kind;
+
+		self.entries.retain(|path, _| {
+			let changed = vfs.resource_changed(resource_path(path));
+			if changed {
+				log::info!("Reloading {kind} '{}'", path.display());
+			}
+
+			!changed
+		});
+	}
+}
@@ -0,0 +1,349 @@
+use crate::prelude::*;
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use image::ImageEncoder;
+
+
+/// Tightly packed rgba8 image, top row first.
+#[derive(Debug, Clone)]
+pub struct Screenshot {
+	pub size: Vec2i,
+	pub data: Vec<u8>,
+}
+
+impl Screenshot {
+	/// Absolute paths are read directly from disk, so goldens can be loaded from outside of the vfs.
+	/// Anything else is relative to the user data root.
+	pub fn load(vfs: &vfs::Vfs, path: impl AsRef<Path>) -> anyhow::Result<Screenshot> {
+		let path = path.as_ref();
+
+		let data = match path.is_absolute() {
+			true => std::fs::read(path)?,
+			false => vfs.load_data(vfs::PathKind::UserData, path)?,
+		};
+
+		let image = image::load_from_memory(&data)?.into_rgba8();
+
+		Ok(Screenshot {
+			size: Vec2i::new(image.width() as i32, image.height() as i32),
+			data: image.into_raw(),
+		})
+	}
+
+	/// Saves as png, relative to the user data root.
+	pub fn save(&self, vfs: &vfs::Vfs, path: impl AsRef<Path>) -> anyhow::Result<()> {
+		let mut encoded = Vec::new();
+
+		image::codecs::png::PngEncoder::new(&mut encoded)
+			.write_image(&self.data, self.size.x as u32, self.size.y as u32, image::ColorType::Rgba8)?;
+
+		vfs.save_data(vfs::PathKind::UserData, path, encoded)
+	}
+
+	pub fn texel(&self, pos: Vec2i) -> Option<[u8; 4]> {
+		if pos.x < 0 || pos.y < 0 || pos.x >= self.size.x || pos.y >= self.size.y {
+			return None
+		}
+
+		let offset = 4 * (pos.y * self.size.x + pos.x) as usize;
+		self.data[offset..offset+4].try_into().ok()
+	}
+
+	fn to_color_image(&self) -> egui::ColorImage {
+		egui::ColorImage::from_rgba_unmultiplied([self.size.x as usize, self.size.y as usize], &self.data)
+	}
+}
+
+
+/// Result of comparing two [`Screenshot`]s of the same size.
+#[derive(Debug, Clone)]
+pub struct ScreenshotDiff {
+	pub size: Vec2i,
+
+	/// Largest per channel difference for each texel, top row first.
+	pub differences: Vec<u8>,
+
+	pub max_difference: u8,
+
+	/// Number of texels with a difference greater than the threshold passed to [`ScreenshotDiff::new`].
+	pub num_differing_texels: usize,
+}
+
+impl ScreenshotDiff {
+	/// Returns None if the screenshots aren't the same size.
+	pub fn new(a: &Screenshot, b: &Screenshot, threshold: u8) -> Option<ScreenshotDiff> {
+		if a.size != b.size {
+			return None
+		}
+
+		let differences: Vec<u8> = a.data.chunks_exact(4).zip(b.data.chunks_exact(4))
+			.map(|(a, b)| a.iter().zip(b).map(|(&a, &b)| a.abs_diff(b)).max().unwrap_or(0))
+			.collect();
+
+		Some(ScreenshotDiff {
+			size: a.size,
+			max_difference: differences.iter().copied().max().unwrap_or(0),
+			num_differing_texels: differences.iter().filter(|&&difference| difference > threshold).count(),
+			differences,
+		})
+	}
+
+	pub fn difference(&self, pos: Vec2i) -> Option<u8> {
+		if pos.x < 0 || pos.y < 0 || pos.x >= self.size.x || pos.y >= self.size.y {
+			return None
+		}
+
+		self.differences.get((pos.y * self.size.x + pos.x) as usize).copied()
+	}
+
+	/// Black where texels match, ramping through red to yellow as the difference grows.
+	/// Differences at or below `threshold` are shown dimmed.
+	fn to_heatmap(&self, threshold: u8) -> egui::ColorImage {
+		let pixels = self.differences.iter()
+			.map(|&difference| {
+				if difference == 0 {
+					egui::Color32::BLACK
+				} else if difference <= threshold {
+					egui::Color32::from_gray(40)
+				} else {
+					// Scale so that even tiny differences stand out.
+					let intensity = (difference as f32 / 255.0).sqrt();
+					let red = (intensity * 2.0).min(1.0);
+					let green = (intensity * 2.0 - 1.0).max(0.0);
+					egui::Color32::from_rgb((red * 255.0) as u8, (green * 255.0) as u8, 0)
+				}
+			})
+			.collect();
+
+		egui::ColorImage {
+			size: [self.size.x as usize, self.size.y as usize],
+			pixels,
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum CompareView {
+	A,
+	B,
+	Diff,
+}
+
+#[derive(Default)]
+struct Slot {
+	path: String,
+	screenshot: Option<Screenshot>,
+	error: Option<String>,
+}
+
+
+/// Debug panel for comparing two screenshots - e.g., a golden image against the current frame - with a per texel
+/// difference heatmap. Screenshots can be loaded from disk or captured from the backbuffer.
+pub struct ScreenshotCompare {
+	slots: [Slot; 2],
+	view: CompareView,
+	threshold: u8,
+	zoom: f32,
+
+	diff: Option<ScreenshotDiff>,
+	texture: Option<egui::TextureHandle>,
+	texture_dirty: bool,
+
+	pending_capture: Rc<RefCell<Option<(usize, Screenshot)>>>,
+}
+
+impl Default for ScreenshotCompare {
+	fn default() -> Self {
+		ScreenshotCompare {
+			slots: Default::default(),
+			view: CompareView::Diff,
+			threshold: 0,
+			zoom: 1.0,
+
+			diff: None,
+			texture: None,
+			texture_dirty: false,
+
+			pending_capture: Rc::new(RefCell::new(None)),
+		}
+	}
+}
+
+impl ScreenshotCompare {
+	pub fn set_screenshot(&mut self, slot: usize, screenshot: Screenshot) {
+		self.slots[slot].screenshot = Some(screenshot);
+		self.slots[slot].error = None;
+		self.update_diff();
+	}
+
+	pub fn diff(&self) -> Option<&ScreenshotDiff> {
+		self.diff.as_ref()
+	}
+
+	/// Schedules a readback of the backbuffer at the end of the current frame, to be shown in `slot`.
+	pub fn capture(&mut self, gfx: &mut gfx::System, slot: usize) {
+		let pending_capture = self.pending_capture.clone();
+
+		gfx.frame_encoder.command_group(gfx::FrameStage::Final)
+			.annotate("Screenshot Compare Capture")
+			.execute(move |core, _| {
+				let size = core.backbuffer_size();
+				let data = crate::clipboard::read_backbuffer_rgba8(core, size);
+				*pending_capture.borrow_mut() = Some((slot, Screenshot { size, data }));
+			});
+	}
+
+	fn update_diff(&mut self) {
+		self.diff = match &self.slots {
+			[Slot{screenshot: Some(a), ..}, Slot{screenshot: Some(b), ..}] => ScreenshotDiff::new(a, b, self.threshold),
+			_ => None,
+		};
+
+		self.texture_dirty = true;
+	}
+
+	pub fn ui(&mut self, ui: &mut egui::Ui, vfs: &vfs::Vfs, gfx: &mut gfx::System) {
+		let pending_capture = self.pending_capture.borrow_mut().take();
+		if let Some((slot, screenshot)) = pending_capture {
+			self.set_screenshot(slot, screenshot);
+		}
+
+		for slot in 0..2 {
+			self.slot_ui(ui, vfs, gfx, slot);
+		}
+
+		ui.separator();
+
+		ui.horizontal(|ui| {
+			let prev_view = self.view;
+			ui.selectable_value(&mut self.view, CompareView::A, "A");
+			ui.selectable_value(&mut self.view, CompareView::B, "B");
+			ui.selectable_value(&mut self.view, CompareView::Diff, "Diff");
+			self.texture_dirty |= prev_view != self.view;
+
+			ui.separator();
+
+			if ui.add(egui::Slider::new(&mut self.threshold, 0..=255).text("Threshold")).changed() {
+				self.update_diff();
+			}
+		});
+
+		ui.add(egui::Slider::new(&mut self.zoom, 0.125..=16.0).logarithmic(true).text("Zoom"));
+
+		if let Some(diff) = &self.diff {
+			let total_texels = (diff.size.x * diff.size.y).max(1) as usize;
+			let percentage = 100.0 * diff.num_differing_texels as f32 / total_texels as f32;
+			ui.label(format!("{} differing texels ({percentage:.3}%), max difference {}",
+				diff.num_differing_texels, diff.max_difference));
+
+		} else if let [Slot{screenshot: Some(a), ..}, Slot{screenshot: Some(b), ..}] = &self.slots {
+			ui.colored_label(egui::Color32::LIGHT_RED,
+				format!("Size mismatch: {}x{} vs {}x{}", a.size.x, a.size.y, b.size.x, b.size.y));
+		}
+
+		ui.separator();
+
+		self.image_ui(ui);
+	}
+
+	fn slot_ui(&mut self, ui: &mut egui::Ui, vfs: &vfs::Vfs, gfx: &mut gfx::System, slot: usize) {
+		let label = ["A", "B"][slot];
+
+		ui.horizontal(|ui| {
+			ui.label(label);
+			ui.text_edit_singleline(&mut self.slots[slot].path)
+				.on_hover_text("Relative to user data, or absolute");
+
+			if ui.button("Load").clicked() {
+				match Screenshot::load(vfs, &self.slots[slot].path) {
+					Ok(screenshot) => self.set_screenshot(slot, screenshot),
+					Err(error) => self.slots[slot].error = Some(error.to_string()),
+				}
+			}
+
+			if ui.button("Capture").clicked() {
+				self.capture(gfx, slot);
+			}
+
+			let can_save = self.slots[slot].screenshot.is_some() && !self.slots[slot].path.is_empty();
+			if ui.add_enabled(can_save, egui::Button::new("Save")).clicked() {
+				if let Some(screenshot) = &self.slots[slot].screenshot {
+					if let Err(error) = screenshot.save(vfs, &self.slots[slot].path) {
+						self.slots[slot].error = Some(error.to_string());
+					}
+				}
+			}
+
+			if let Some(screenshot) = &self.slots[slot].screenshot {
+				ui.label(format!("{}x{}", screenshot.size.x, screenshot.size.y));
+			}
+		});
+
+		if let Some(error) = &self.slots[slot].error {
+			ui.colored_label(egui::Color32::LIGHT_RED, error);
+		}
+	}
+
+	fn displayed_size(&self) -> Option<Vec2i> {
+		match self.view {
+			CompareView::A => self.slots[0].screenshot.as_ref().map(|screenshot| screenshot.size),
+			CompareView::B => self.slots[1].screenshot.as_ref().map(|screenshot| screenshot.size),
+			CompareView::Diff => self.diff.as_ref().map(|diff| diff.size),
+		}
+	}
+
+	fn image_ui(&mut self, ui: &mut egui::Ui) {
+		if std::mem::take(&mut self.texture_dirty) {
+			let image = match self.view {
+				CompareView::A => self.slots[0].screenshot.as_ref().map(Screenshot::to_color_image),
+				CompareView::B => self.slots[1].screenshot.as_ref().map(Screenshot::to_color_image),
+				CompareView::Diff => self.diff.as_ref().map(|diff| diff.to_heatmap(self.threshold)),
+			};
+
+			// Nearest filtering so individual texels are visible when zoomed in.
+			self.texture = image.map(|image| ui.ctx().load_texture("screenshot compare", image, egui::TextureOptions::NEAREST));
+		}
+
+		let (Some(texture), Some(size)) = (&self.texture, self.displayed_size()) else {
+			ui.label("Nothing to show");
+			return
+		};
+
+		let zoom = self.zoom;
+		let display_size = egui::vec2(size.x as f32, size.y as f32) * zoom;
+
+		let response = egui::ScrollArea::both()
+			.auto_shrink(false)
+			.show(ui, |ui| {
+				ui.add(egui::Image::new(egui::load::SizedTexture::new(texture.id(), display_size))
+					.sense(egui::Sense::hover()))
+			})
+			.inner;
+
+		let Some(hover_pos) = response.hover_pos() else { return };
+
+		let local_pos = (hover_pos - response.rect.min) / zoom;
+		let texel = Vec2i::new(local_pos.x as i32, local_pos.y as i32);
+
+		let format_texel = |screenshot: &Option<Screenshot>| match screenshot.as_ref().and_then(|screenshot| screenshot.texel(texel)) {
+			Some([r, g, b, a]) => format!("{r:3} {g:3} {b:3} {a:3}"),
+			None => "-".to_owned(),
+		};
+
+		let mut text = format!("{}, {}\nA: {}\nB: {}", texel.x, texel.y,
+			format_texel(&self.slots[0].screenshot),
+			format_texel(&self.slots[1].screenshot));
+
+		if let Some(difference) = self.diff.as_ref().and_then(|diff| diff.difference(texel)) {
+			text += &format!("\nDiff: {difference}");
+		}
+
+		response.on_hover_ui_at_pointer(|ui| {
+			ui.monospace(text);
+		});
+	}
+}
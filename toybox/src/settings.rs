@@ -0,0 +1,563 @@
+//! Typed engine settings persisted through [`cfg::Config`], and egui widgets for assembling options menus from them.
+//! Graphics and audio settings are applied automatically on startup.
+//!
+//! Graphics quality is chosen with a single `graphics.quality` key - one of `low`, `medium`, `high` or `custom`, where
+//! `custom` uses the values in `graphics.custom_quality`. Sampler anisotropy and render scale are applied directly,
+//! and a [`QualitySettingsChanged`] message is emitted on [`Context::bus`] when the quality changes so that apps can
+//! resize their shadow maps and toggle postprocessing to match.
+//!
+//! Changes are applied immediately, but only written to disk once they've stopped changing for
+//! [`SETTINGS_SAVE_DELAY`], so that dragging a slider doesn't rewrite the config every frame.
+
+use crate::prelude::*;
+use crate::Context;
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod test;
+
+
+pub const GRAPHICS_SECTION: &str = "graphics";
+pub const AUDIO_SECTION: &str = "audio";
+pub const INPUT_SECTION: &str = "input";
+
+/// How long settings must go unchanged before they're saved.
+pub const SETTINGS_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GraphicsSettings {
+	pub vsync: bool,
+	pub fullscreen: bool,
+
+	/// Window size in physical pixels. [0, 0] leaves the window at whatever size the platform chooses.
+	pub resolution: [i32; 2],
+
+	#[serde(default)]
+	pub quality: QualityTier,
+
+	/// Only used if `quality` is [`QualityTier::Custom`].
+	#[serde(default)]
+	pub custom_quality: QualitySettings,
+}
+
+impl Default for GraphicsSettings {
+	fn default() -> Self {
+		GraphicsSettings {
+			vsync: true,
+			fullscreen: false,
+			resolution: [0, 0],
+			quality: QualityTier::default(),
+			custom_quality: QualitySettings::default(),
+		}
+	}
+}
+
+impl cfg::ConfigSection for GraphicsSettings {
+	fn validate(&self, errors: &mut Vec<String>) {
+		let [width, height] = self.resolution;
+		cfg::check_range(errors, "resolution.x", width, 0..=16384);
+		cfg::check_range(errors, "resolution.y", height, 0..=16384);
+
+		cfg::check_range(errors, "custom_quality.anisotropy", self.custom_quality.anisotropy, 1.0..=16.0);
+		cfg::check_range(errors, "custom_quality.shadow_resolution", self.custom_quality.shadow_resolution, 64..=8192);
+		cfg::check_range(errors, "custom_quality.render_scale", self.custom_quality.render_scale, 0.25..=2.0);
+	}
+}
+
+impl GraphicsSettings {
+	/// Apply everything, e.g., on startup. Use [`Self::apply_changes`] when settings are being edited.
+	pub fn apply(&self, window: &mut Window, gfx: &mut gfx::System) {
+		window.set_vsync(self.vsync);
+		window.set_fullscreen(self.fullscreen);
+		self.request_resolution(window);
+
+		self.quality_settings().apply(gfx);
+	}
+
+	/// Like [`Self::apply`], but only touches what differs from `previous`. Changing window state can be slow and
+	/// visibly disruptive, so shouldn't happen just because e.g., the quality tier changed.
+	pub fn apply_changes(&self, previous: &GraphicsSettings, window: &mut Window, gfx: &mut gfx::System) {
+		if self.vsync != previous.vsync {
+			window.set_vsync(self.vsync);
+		}
+
+		if self.fullscreen != previous.fullscreen {
+			window.set_fullscreen(self.fullscreen);
+		}
+
+		// Leaving fullscreen should restore the chosen resolution too.
+		if self.resolution != previous.resolution || self.fullscreen != previous.fullscreen {
+			self.request_resolution(window);
+		}
+
+		let quality = self.quality_settings();
+		if quality != previous.quality_settings() {
+			quality.apply(gfx);
+		}
+	}
+
+	fn request_resolution(&self, window: &mut Window) {
+		let [width, height] = self.resolution;
+		if width > 0 && height > 0 && !self.fullscreen {
+			window.request_size(Vec2i::new(width, height));
+		}
+	}
+
+	/// The settings for the selected tier.
+	pub fn quality_settings(&self) -> QualitySettings {
+		self.quality.preset()
+			.unwrap_or_else(|| self.custom_quality.clone())
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QualityTier {
+	Low,
+	Medium,
+	#[default]
+	High,
+	Custom,
+}
+
+impl QualityTier {
+	pub const ALL: [QualityTier; 4] = [QualityTier::Low, QualityTier::Medium, QualityTier::High, QualityTier::Custom];
+
+	pub fn label(&self) -> &'static str {
+		match self {
+			QualityTier::Low => "Low",
+			QualityTier::Medium => "Medium",
+			QualityTier::High => "High",
+			QualityTier::Custom => "Custom",
+		}
+	}
+
+	/// None for [`QualityTier::Custom`].
+	pub fn preset(&self) -> Option<QualitySettings> {
+		let settings = match self {
+			QualityTier::Low => QualitySettings {
+				anisotropy: 1.0,
+				shadow_resolution: 512,
+				render_scale: 0.75,
+				auto_exposure: false,
+				color_grading: true,
+				dithering: false,
+			},
+
+			QualityTier::Medium => QualitySettings {
+				anisotropy: 4.0,
+				shadow_resolution: 1024,
+				render_scale: 1.0,
+				auto_exposure: true,
+				color_grading: true,
+				dithering: false,
+			},
+
+			QualityTier::High => QualitySettings {
+				anisotropy: 16.0,
+				shadow_resolution: 2048,
+				render_scale: 1.0,
+				auto_exposure: true,
+				color_grading: true,
+				dithering: true,
+			},
+
+			QualityTier::Custom => return None,
+		};
+
+		Some(settings)
+	}
+}
+
+
+/// What a [`QualityTier`] maps to. Anisotropy and render scale are applied to [`gfx::System`] directly, everything
+/// else is up to the app - see [`QualitySettingsChanged`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct QualitySettings {
+	/// Applied to the linear common samplers. 1.0 disables anisotropic filtering.
+	pub anisotropy: f32,
+
+	/// For [`gfx::shadows::CascadedShadowMap::set_resolution`] and friends.
+	pub shadow_resolution: i32,
+
+	/// See [`gfx::System::set_render_scale`].
+	pub render_scale: f32,
+
+	pub auto_exposure: bool,
+	pub color_grading: bool,
+	pub dithering: bool,
+}
+
+impl Default for QualitySettings {
+	fn default() -> Self {
+		QualityTier::High.preset().unwrap()
+	}
+}
+
+impl QualitySettings {
+	pub fn apply(&self, gfx: &mut gfx::System) {
+		gfx.resource_manager.set_common_sampler_anisotropy(&gfx.core, self.anisotropy);
+		gfx.set_render_scale(self.render_scale);
+	}
+}
+
+
+/// Emitted on [`Context::bus`] when the effective [`QualitySettings`] change.
+#[derive(Debug, Clone)]
+pub struct QualitySettingsChanged(pub QualitySettings);
+
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+	pub master_volume: f32,
+	pub muted: bool,
+
+	/// Applied to [`Context::music`], on top of master volume.
+	pub music_volume: f32,
+
+	/// Speaker layout to mix for, as parsed by [`audio::ChannelLayout::from_name`]. Stereo if unset.
+	/// Only read on startup.
+	pub channel_layout: Option<String>,
+}
+
+impl Default for AudioSettings {
+	fn default() -> Self {
+		AudioSettings {
+			master_volume: 1.0,
+			muted: false,
+			music_volume: 1.0,
+			channel_layout: None,
+		}
+	}
+}
+
+impl cfg::ConfigSection for AudioSettings {
+	fn validate(&self, errors: &mut Vec<String>) {
+		cfg::check_range(errors, "master_volume", self.master_volume, 0.0..=1.0);
+		cfg::check_range(errors, "music_volume", self.music_volume, 0.0..=1.0);
+
+		if let Some(name) = &self.channel_layout {
+			if audio::ChannelLayout::from_name(name).is_none() {
+				errors.push(format!("Unknown channel_layout '{name}' - expected mono, stereo, quad, 5.1 or 7.1"));
+			}
+		}
+	}
+}
+
+impl AudioSettings {
+	pub fn apply(&self, audio: &audio::System, music: &audio::MusicPlayer) {
+		audio.set_master_volume(self.master_volume);
+		audio.set_muted(self.muted);
+		music.set_volume(self.music_volume);
+	}
+
+	pub fn channel_layout(&self) -> Option<audio::ChannelLayout> {
+		self.channel_layout.as_deref().and_then(audio::ChannelLayout::from_name)
+	}
+}
+
+
+/// User overrides for named actions. Actions without an override should use the app's default binding.
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct InputSettings {
+	pub bindings: BTreeMap<String, input::Button>,
+
+	/// Whether gamepads can navigate egui, see [`input::UiNavigation::drive_egui`].
+	#[serde(default)]
+	pub gamepad_ui_navigation: bool,
+}
+
+impl cfg::ConfigSection for InputSettings {}
+
+impl InputSettings {
+	pub fn binding(&self, action: &str, default: impl Into<input::Button>) -> input::Button {
+		self.bindings.get(action).cloned()
+			.unwrap_or_else(|| default.into())
+	}
+
+	pub fn apply(&self, ctx: &mut Context) {
+		ctx.console.toggle_button = self.binding(crate::console::TOGGLE_CONSOLE_ACTION, input::keys::Backquote);
+		ctx.ui_navigation.drive_egui = self.gamepad_ui_navigation;
+	}
+}
+
+
+
+impl Context {
+	pub fn graphics_settings(&self) -> GraphicsSettings {
+		self.cfg.bind_or_default(GRAPHICS_SECTION)
+	}
+
+	pub fn audio_settings(&self) -> AudioSettings {
+		self.cfg.bind_or_default(AUDIO_SECTION)
+	}
+
+	pub fn input_settings(&self) -> InputSettings {
+		self.cfg.bind_or_default(INPUT_SECTION)
+	}
+
+	pub fn quality_settings(&self) -> QualitySettings {
+		self.graphics_settings().quality_settings()
+	}
+
+	/// Store, apply and persist new graphics settings. Emits [`QualitySettingsChanged`] if the quality changed.
+	pub fn set_graphics_settings(&mut self, settings: &GraphicsSettings) {
+		let previous = self.graphics_settings();
+
+		settings.apply_changes(&previous, &mut self.window, &mut self.gfx);
+		self.store_settings(GRAPHICS_SECTION, settings);
+
+		let quality = settings.quality_settings();
+		if quality != previous.quality_settings() {
+			self.bus.emit(QualitySettingsChanged(quality));
+		}
+	}
+
+	/// Store, apply and persist new audio settings.
+	pub fn set_audio_settings(&mut self, settings: &AudioSettings) {
+		settings.apply(&self.audio, &self.music);
+		self.store_settings(AUDIO_SECTION, settings);
+	}
+
+	/// Store and persist new input settings.
+	pub fn set_input_settings(&mut self, settings: &InputSettings) {
+		settings.apply(self);
+		self.store_settings(INPUT_SECTION, settings);
+	}
+
+	pub(crate) fn apply_startup_settings(&mut self) {
+		self.graphics_settings().apply(&mut self.window, &mut self.gfx);
+		self.audio_settings().apply(&self.audio, &self.music);
+		self.input_settings().apply(self);
+	}
+
+	fn store_settings(&mut self, section: &str, settings: &impl cfg::ConfigSection) {
+		match self.cfg.store(section, settings) {
+			Ok(_) => self.settings_changed_at = Some(Instant::now()),
+			Err(error) => log::error!("Failed to store '{section}' settings: {error}"),
+		}
+	}
+
+	/// Save settings once they've settled, or immediately if `force` is set - e.g., on shutdown.
+	pub(crate) fn save_changed_settings(&mut self, force: bool) {
+		let Some(changed_at) = self.settings_changed_at else { return };
+
+		if !force && changed_at.elapsed() < SETTINGS_SAVE_DELAY {
+			return
+		}
+
+		self.settings_changed_at = None;
+
+		if let Err(error) = self.cfg.save(&self.vfs) {
+			log::error!("Failed to save settings: {error}");
+		}
+	}
+}
+
+
+
+/// Vsync, fullscreen, resolution and quality. Changes are applied immediately.
+pub fn graphics_settings_ui(ui: &mut egui::Ui, ctx: &mut Context) -> egui::Response {
+	let mut settings = ctx.graphics_settings();
+
+	let mut response = ui.checkbox(&mut settings.vsync, "VSync");
+	response |= ui.checkbox(&mut settings.fullscreen, "Fullscreen");
+
+	ui.add_enabled_ui(!settings.fullscreen, |ui| {
+		response |= resolution_picker(ui, &ctx.window, &mut settings.resolution);
+	});
+
+	let previous_tier = settings.quality;
+	response |= quality_picker(ui, &mut settings.quality);
+
+	// Start customising from wherever the previous tier was.
+	if settings.quality == QualityTier::Custom && previous_tier != QualityTier::Custom {
+		settings.custom_quality = previous_tier.preset().unwrap_or_default();
+	}
+
+	if settings.quality == QualityTier::Custom {
+		ui.indent("custom_quality", |ui| {
+			response |= custom_quality_ui(ui, &mut settings.custom_quality);
+		});
+	}
+
+	if response.changed() {
+		ctx.set_graphics_settings(&settings);
+	}
+
+	response
+}
+
+/// Master volume, music volume and mute. Changes are applied immediately.
+pub fn audio_settings_ui(ui: &mut egui::Ui, ctx: &mut Context) -> egui::Response {
+	let mut settings = ctx.audio_settings();
+
+	let mut response = volume_slider(ui, "Master Volume", &mut settings.master_volume);
+	response |= volume_slider(ui, "Music Volume", &mut settings.music_volume);
+	response |= ui.checkbox(&mut settings.muted, "Mute");
+
+	if response.changed() {
+		ctx.set_audio_settings(&settings);
+	}
+
+	response
+}
+
+/// A rebind button for each of `actions`, given as (action name, default binding). Changes are applied immediately.
+pub fn input_settings_ui(ui: &mut egui::Ui, ctx: &mut Context, actions: &[(&str, input::Button)]) -> egui::Response {
+	let mut settings = ctx.input_settings();
+	let mut response = ui.allocate_response(egui::Vec2::ZERO, egui::Sense::hover());
+
+	egui::Grid::new("input_settings")
+		.striped(true)
+		.show(ui, |ui| {
+			for (action, default) in actions {
+				let mut binding = settings.binding(action, default.clone());
+
+				ui.label(*action);
+				let button_response = rebind_button(ui, &ctx.input, &mut binding);
+
+				if button_response.changed() {
+					settings.bindings.insert(action.to_string(), binding);
+				}
+
+				if ui.add_enabled(settings.bindings.contains_key(*action), egui::Button::new("Reset")).clicked() {
+					settings.bindings.remove(*action);
+					response.mark_changed();
+				}
+
+				response |= button_response;
+				ui.end_row();
+			}
+		});
+
+	if response.changed() {
+		ctx.set_input_settings(&settings);
+	}
+
+	response
+}
+
+
+
+/// Combo box of resolutions supported by the current monitor.
+pub fn resolution_picker(ui: &mut egui::Ui, window: &Window, resolution: &mut [i32; 2]) -> egui::Response {
+	let label = |[width, height]: [i32; 2]| match width > 0 && height > 0 {
+		true => format!("{width}x{height}"),
+		false => "Default".to_owned(),
+	};
+
+	let mut changed = false;
+
+	let mut response = egui::ComboBox::from_label("Resolution")
+		.selected_text(label(*resolution))
+		.show_ui(ui, |ui| {
+			changed |= ui.selectable_value(resolution, [0, 0], label([0, 0])).changed();
+
+			for size in window.available_resolutions() {
+				changed |= ui.selectable_value(resolution, [size.x, size.y], label([size.x, size.y])).changed();
+			}
+		})
+		.response;
+
+	if changed {
+		response.mark_changed();
+	}
+
+	response
+}
+
+pub fn quality_picker(ui: &mut egui::Ui, tier: &mut QualityTier) -> egui::Response {
+	let mut changed = false;
+
+	let mut response = egui::ComboBox::from_label("Quality")
+		.selected_text(tier.label())
+		.show_ui(ui, |ui| {
+			for option in QualityTier::ALL {
+				changed |= ui.selectable_value(tier, option, option.label()).changed();
+			}
+		})
+		.response;
+
+	if changed {
+		response.mark_changed();
+	}
+
+	response
+}
+
+pub fn custom_quality_ui(ui: &mut egui::Ui, quality: &mut QualitySettings) -> egui::Response {
+	let mut response = ui.add(egui::Slider::new(&mut quality.anisotropy, 1.0..=16.0)
+		.text("Anisotropic Filtering")
+		.logarithmic(true)
+		.custom_formatter(|value, _| format!("{value:.0}x")));
+
+	response |= ui.add(egui::Slider::new(&mut quality.shadow_resolution, 256..=4096)
+		.text("Shadow Resolution")
+		.logarithmic(true));
+
+	response |= ui.add(egui::Slider::new(&mut quality.render_scale, 0.25..=2.0)
+		.text("Render Scale")
+		.custom_formatter(|value, _| format!("{:.0}%", value * 100.0)));
+
+	response |= ui.checkbox(&mut quality.auto_exposure, "Auto Exposure");
+	response |= ui.checkbox(&mut quality.color_grading, "Color Grading");
+	response |= ui.checkbox(&mut quality.dithering, "Dithering");
+
+	response
+}
+
+pub fn volume_slider(ui: &mut egui::Ui, label: &str, volume: &mut f32) -> egui::Response {
+	ui.add(egui::Slider::new(volume, 0.0..=1.0)
+		.text(label)
+		.custom_formatter(|value, _| format!("{:.0}%", value * 100.0)))
+}
+
+/// Shows the current binding. Once clicked, the next button pressed becomes the new binding, or Escape cancels.
+pub fn rebind_button(ui: &mut egui::Ui, input: &input::System, button: &mut input::Button) -> egui::Response {
+	let id = ui.next_auto_id();
+	let listening = ui.data(|data| data.get_temp::<bool>(id).unwrap_or(false));
+
+	let text = match listening {
+		true => "Press a button...".to_owned(),
+		false => button_label(button),
+	};
+
+	let mut response = ui.add(egui::Button::new(text).selected(listening));
+
+	if response.clicked() && !listening {
+		ui.data_mut(|data| data.insert_temp(id, true));
+		return response
+	}
+
+	if !listening {
+		return response
+	}
+
+	let Some(pressed) = input.tracker.down_buttons.first() else {
+		return response
+	};
+
+	if *pressed != input::Button::from(input::keys::Escape) {
+		*button = pressed.clone();
+		response.mark_changed();
+	}
+
+	ui.data_mut(|data| data.remove::<bool>(id));
+	response
+}
+
+fn button_label(button: &input::Button) -> String {
+	match button {
+		input::Button::LogicalKey(key) => format!("{key:?}"),
+		input::Button::PhysicalKey(key) => format!("{key:?}"),
+		input::Button::Mouse(button) => format!("Mouse {button:?}"),
+		input::Button::Gamepad(button) => format!("Gamepad {button:?}"),
+	}
+}
@@ -0,0 +1,44 @@
+use super::*;
+
+
+#[test]
+fn audio_settings_bind_with_channel_layout() {
+	let mut config = cfg::Config::default();
+	config.preview_value("audio.master_volume", 0.5);
+	config.preview_value("audio.channel_layout", "5.1");
+
+	let settings: AudioSettings = config.bind(AUDIO_SECTION).unwrap();
+	assert_eq!(settings.master_volume, 0.5);
+	assert_eq!(settings.channel_layout(), Some(audio::ChannelLayout::Surround51));
+}
+
+#[test]
+fn audio_settings_reject_unknown_channel_layout() {
+	let mut config = cfg::Config::default();
+	config.preview_value("audio.channel_layout", "13.2");
+
+	assert!(config.bind::<AudioSettings>(AUDIO_SECTION).is_err());
+}
+
+#[test]
+fn audio_settings_store_keeps_channel_layout() {
+	let mut config = cfg::Config::default();
+	let settings = AudioSettings {
+		channel_layout: Some("quad".into()),
+		.. AudioSettings::default()
+	};
+
+	config.store(AUDIO_SECTION, &settings).unwrap();
+	assert_eq!(config.get_string("audio.channel_layout"), Some("quad"));
+	assert_eq!(config.bind::<AudioSettings>(AUDIO_SECTION).unwrap(), settings);
+}
+
+#[test]
+fn input_settings_bind_with_gamepad_ui_navigation() {
+	let mut config = cfg::Config::default();
+	config.preview_value("input.gamepad_ui_navigation", true);
+
+	let settings: InputSettings = config.bind(INPUT_SECTION).unwrap();
+	assert!(settings.gamepad_ui_navigation);
+	assert!(settings.bindings.is_empty());
+}
@@ -0,0 +1,503 @@
+//! Compact save/restore of simulation state, for rollback netcode experiments and instant replays.
+//!
+//! State implements [`Snapshot`] to write itself into a bit-packed [`BitWriter`]. A [`SnapshotRing`] keeps the
+//! most recent snapshots keyed by fixed timestep tick (see [`Context::fixed_tick`](crate::Context::fixed_tick)),
+//! and [`encode_delta`]/[`apply_delta`] produce small diffs between two snapshots for sending over the wire.
+//!
+//! ```ignore
+//! fn update(&mut self, ctx: &mut Context, dt: f32) {
+//!     // A late remote input arrived for an earlier tick - rewind and resimulate up to now.
+//!     if let Some((tick, input)) = self.late_input.take() {
+//!         if let Some(world) = self.history.rollback(tick) {
+//!             self.world = world;
+//!             self.inputs.insert(tick, input);
+//!
+//!             for tick in tick..ctx.fixed_tick() {
+//!                 self.world.step(&self.inputs[&tick], dt);
+//!                 self.history.push(tick + 1, &self.world);
+//!             }
+//!         }
+//!     }
+//!
+//!     self.world.step(&self.inputs[&ctx.fixed_tick()], dt);
+//!     self.history.push(ctx.fixed_tick() + 1, &self.world);
+//! }
+//! ```
+
+use crate::prelude::*;
+
+use std::collections::VecDeque;
+
+#[cfg(test)]
+mod test;
+
+
+/// State that can be written to and restored from a bit-packed snapshot. Reads must mirror writes exactly.
+pub trait Snapshot: Sized {
+	fn write_snapshot(&self, writer: &mut BitWriter);
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self>;
+
+	fn to_snapshot_bytes(&self) -> Vec<u8> {
+		let mut writer = BitWriter::new();
+		self.write_snapshot(&mut writer);
+		writer.finish()
+	}
+
+	fn from_snapshot_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+		Self::read_snapshot(&mut BitReader::new(bytes))
+	}
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct BitWriter {
+	bytes: Vec<u8>,
+	bit_position: usize,
+}
+
+impl BitWriter {
+	pub fn new() -> Self {
+		BitWriter::default()
+	}
+
+	/// Write the low `num_bits` bits of `value`, least significant first.
+	pub fn write_bits(&mut self, value: u64, num_bits: u32) {
+		assert!(num_bits <= 64);
+
+		for bit in 0..num_bits {
+			let byte_index = self.bit_position / 8;
+			if byte_index >= self.bytes.len() {
+				self.bytes.push(0);
+			}
+
+			if (value >> bit) & 1 != 0 {
+				self.bytes[byte_index] |= 1 << (self.bit_position % 8);
+			}
+
+			self.bit_position += 1;
+		}
+	}
+
+	pub fn write_bool(&mut self, value: bool) {
+		self.write_bits(value as u64, 1);
+	}
+
+	/// Write `value` in as few bits as needed to represent `0..=max`.
+	pub fn write_ranged(&mut self, value: u64, max: u64) {
+		debug_assert!(value <= max, "{value} out of range 0..={max}");
+		self.write_bits(value, bits_for_max(max));
+	}
+
+	/// Small values take fewer bits - 7 bits per group, plus a continuation bit.
+	pub fn write_varint(&mut self, mut value: u64) {
+		loop {
+			self.write_bits(value & 0x7f, 7);
+			value >>= 7;
+
+			self.write_bool(value != 0);
+			if value == 0 {
+				break
+			}
+		}
+	}
+
+	/// Write `value` quantized to `bits` bits across `min..=max`. Lossy.
+	pub fn write_quantized(&mut self, value: f32, min: f32, max: f32, bits: u32) {
+		assert!(bits > 0 && bits < 64);
+
+		let max_step = ((1u64 << bits) - 1) as f32;
+		let t = ((value - min) / (max - min)).clamp(0.0, 1.0);
+		self.write_bits((t * max_step).round() as u64, bits);
+	}
+
+	pub fn bit_len(&self) -> usize {
+		self.bit_position
+	}
+
+	pub fn finish(self) -> Vec<u8> {
+		self.bytes
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct BitReader<'a> {
+	bytes: &'a [u8],
+	bit_position: usize,
+}
+
+impl<'a> BitReader<'a> {
+	pub fn new(bytes: &'a [u8]) -> Self {
+		BitReader {
+			bytes,
+			bit_position: 0,
+		}
+	}
+
+	pub fn read_bits(&mut self, num_bits: u32) -> anyhow::Result<u64> {
+		assert!(num_bits <= 64);
+
+		if self.bit_position + num_bits as usize > self.bytes.len() * 8 {
+			anyhow::bail!("Snapshot truncated: reading {num_bits} bits at bit {} of {}", self.bit_position, self.bytes.len() * 8);
+		}
+
+		let mut value = 0;
+
+		for bit in 0..num_bits {
+			let byte = self.bytes[self.bit_position / 8];
+			if (byte >> (self.bit_position % 8)) & 1 != 0 {
+				value |= 1 << bit;
+			}
+
+			self.bit_position += 1;
+		}
+
+		Ok(value)
+	}
+
+	pub fn read_bool(&mut self) -> anyhow::Result<bool> {
+		Ok(self.read_bits(1)? != 0)
+	}
+
+	pub fn read_ranged(&mut self, max: u64) -> anyhow::Result<u64> {
+		let value = self.read_bits(bits_for_max(max))?;
+		if value > max {
+			anyhow::bail!("Snapshot value {value} out of range 0..={max}");
+		}
+
+		Ok(value)
+	}
+
+	pub fn read_varint(&mut self) -> anyhow::Result<u64> {
+		let mut value = 0;
+		let mut shift = 0;
+
+		loop {
+			if shift >= 64 {
+				anyhow::bail!("Snapshot varint too long");
+			}
+
+			value |= self.read_bits(7)? << shift;
+			shift += 7;
+
+			if !self.read_bool()? {
+				return Ok(value)
+			}
+		}
+	}
+
+	pub fn read_quantized(&mut self, min: f32, max: f32, bits: u32) -> anyhow::Result<f32> {
+		assert!(bits > 0 && bits < 64);
+
+		let max_step = ((1u64 << bits) - 1) as f32;
+		let t = self.read_bits(bits)? as f32 / max_step;
+		Ok(min + t * (max - min))
+	}
+
+	pub fn bits_remaining(&self) -> usize {
+		(self.bytes.len() * 8).saturating_sub(self.bit_position)
+	}
+}
+
+fn bits_for_max(max: u64) -> u32 {
+	64 - max.leading_zeros()
+}
+
+
+
+macro_rules! impl_snapshot_for_int {
+	($($ty:ty),*) => {
+		$(
+			impl Snapshot for $ty {
+				fn write_snapshot(&self, writer: &mut BitWriter) {
+					writer.write_bits(*self as u64, <$ty>::BITS);
+				}
+
+				fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+					Ok(reader.read_bits(<$ty>::BITS)? as $ty)
+				}
+			}
+		)*
+	}
+}
+
+impl_snapshot_for_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Snapshot for usize {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		writer.write_varint(*self as u64);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok(reader.read_varint()? as usize)
+	}
+}
+
+impl Snapshot for bool {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		writer.write_bool(*self);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		reader.read_bool()
+	}
+}
+
+impl Snapshot for f32 {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		writer.write_bits(self.to_bits() as u64, 32);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok(f32::from_bits(reader.read_bits(32)? as u32))
+	}
+}
+
+impl Snapshot for f64 {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		writer.write_bits(self.to_bits(), 64);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok(f64::from_bits(reader.read_bits(64)?))
+	}
+}
+
+impl Snapshot for Vec2 {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		self.x.write_snapshot(writer);
+		self.y.write_snapshot(writer);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok(Vec2::new(f32::read_snapshot(reader)?, f32::read_snapshot(reader)?))
+	}
+}
+
+impl Snapshot for Vec3 {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		self.x.write_snapshot(writer);
+		self.y.write_snapshot(writer);
+		self.z.write_snapshot(writer);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok(Vec3::new(f32::read_snapshot(reader)?, f32::read_snapshot(reader)?, f32::read_snapshot(reader)?))
+	}
+}
+
+impl Snapshot for Vec2i {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		self.x.write_snapshot(writer);
+		self.y.write_snapshot(writer);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok(Vec2i::new(i32::read_snapshot(reader)?, i32::read_snapshot(reader)?))
+	}
+}
+
+impl<T: Snapshot> Snapshot for Option<T> {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		writer.write_bool(self.is_some());
+
+		if let Some(value) = self {
+			value.write_snapshot(writer);
+		}
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		match reader.read_bool()? {
+			true => Ok(Some(T::read_snapshot(reader)?)),
+			false => Ok(None),
+		}
+	}
+}
+
+impl<T: Snapshot> Snapshot for Vec<T> {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		writer.write_varint(self.len() as u64);
+
+		for value in self.iter() {
+			value.write_snapshot(writer);
+		}
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		let len = reader.read_varint()? as usize;
+
+		// Every element takes at least one bit, so anything longer must be garbage.
+		if len > reader.bits_remaining() {
+			anyhow::bail!("Snapshot vec length {len} exceeds remaining data");
+		}
+
+		(0..len).map(|_| T::read_snapshot(reader)).collect()
+	}
+}
+
+impl<T: Snapshot, const N: usize> Snapshot for [T; N] {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		for value in self.iter() {
+			value.write_snapshot(writer);
+		}
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		let values = (0..N).map(|_| T::read_snapshot(reader)).collect::<anyhow::Result<Vec<T>>>()?;
+		Ok(values.try_into().unwrap_or_else(|_| unreachable!()))
+	}
+}
+
+impl<A: Snapshot, B: Snapshot> Snapshot for (A, B) {
+	fn write_snapshot(&self, writer: &mut BitWriter) {
+		self.0.write_snapshot(writer);
+		self.1.write_snapshot(writer);
+	}
+
+	fn read_snapshot(reader: &mut BitReader<'_>) -> anyhow::Result<Self> {
+		Ok((A::read_snapshot(reader)?, B::read_snapshot(reader)?))
+	}
+}
+
+
+
+/// Encode the difference between two snapshots. Each byte costs a single bit if unchanged, so deltas between
+/// similar snapshots are much smaller than either.
+pub fn encode_delta(baseline: &[u8], current: &[u8]) -> Vec<u8> {
+	let mut writer = BitWriter::new();
+	writer.write_varint(current.len() as u64);
+
+	for (index, &byte) in current.iter().enumerate() {
+		let diff = byte ^ baseline.get(index).copied().unwrap_or(0);
+		writer.write_bool(diff != 0);
+
+		if diff != 0 {
+			writer.write_bits(diff as u64, 8);
+		}
+	}
+
+	writer.finish()
+}
+
+/// Reconstruct a snapshot from the baseline passed to [`encode_delta`] and its result.
+pub fn apply_delta(baseline: &[u8], delta: &[u8]) -> anyhow::Result<Vec<u8>> {
+	let mut reader = BitReader::new(delta);
+	let len = reader.read_varint()? as usize;
+
+	if len > reader.bits_remaining() {
+		anyhow::bail!("Snapshot delta length {len} exceeds remaining data");
+	}
+
+	(0..len)
+		.map(|index| {
+			let base = baseline.get(index).copied().unwrap_or(0);
+			match reader.read_bool()? {
+				true => Ok(base ^ reader.read_bits(8)? as u8),
+				false => Ok(base),
+			}
+		})
+		.collect()
+}
+
+
+
+/// Fixed capacity history of encoded snapshots, keyed by tick. Ticks must be pushed in increasing order.
+#[derive(Debug, Clone)]
+pub struct SnapshotRing {
+	capacity: usize,
+	entries: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl SnapshotRing {
+	pub fn new(capacity: usize) -> Self {
+		assert!(capacity > 0, "SnapshotRing must have non-zero capacity");
+
+		SnapshotRing {
+			capacity,
+			entries: VecDeque::with_capacity(capacity),
+		}
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+
+	/// Snapshot `state` as of `tick`, replacing any existing snapshots at or after it.
+	pub fn push(&mut self, tick: u64, state: &impl Snapshot) {
+		self.push_bytes(tick, state.to_snapshot_bytes());
+	}
+
+	pub fn push_bytes(&mut self, tick: u64, bytes: Vec<u8>) {
+		self.truncate_from(tick);
+
+		if self.entries.len() >= self.capacity {
+			self.entries.pop_front();
+		}
+
+		self.entries.push_back((tick, bytes));
+	}
+
+	pub fn oldest_tick(&self) -> Option<u64> {
+		self.entries.front().map(|(tick, _)| *tick)
+	}
+
+	pub fn latest_tick(&self) -> Option<u64> {
+		self.entries.back().map(|(tick, _)| *tick)
+	}
+
+	pub fn contains(&self, tick: u64) -> bool {
+		self.bytes(tick).is_some()
+	}
+
+	pub fn bytes(&self, tick: u64) -> Option<&[u8]> {
+		let index = self.entries.binary_search_by_key(&tick, |(tick, _)| *tick).ok()?;
+		Some(&self.entries[index].1)
+	}
+
+	/// Decode the snapshot for `tick`. None if it has fallen out of the ring or was never pushed.
+	pub fn get<T: Snapshot>(&self, tick: u64) -> Option<T> {
+		let bytes = self.bytes(tick)?;
+
+		T::from_snapshot_bytes(bytes)
+			.inspect_err(|error| log::error!("Failed to decode snapshot for tick {tick}: {error}"))
+			.ok()
+	}
+
+	/// Restore the snapshot for `tick` and discard everything after it, ready for resimulation.
+	pub fn rollback<T: Snapshot>(&mut self, tick: u64) -> Option<T> {
+		let state = self.get(tick)?;
+		self.truncate_from(tick + 1);
+		Some(state)
+	}
+
+	/// Remove snapshots at or after `tick`.
+	pub fn truncate_from(&mut self, tick: u64) {
+		while self.entries.back().is_some_and(|(latest, _)| *latest >= tick) {
+			self.entries.pop_back();
+		}
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+	}
+
+	/// Delta from the snapshot at `baseline_tick` to the one at `tick`, for sending to a peer that has acked the baseline.
+	pub fn delta(&self, baseline_tick: u64, tick: u64) -> Option<Vec<u8>> {
+		Some(encode_delta(self.bytes(baseline_tick)?, self.bytes(tick)?))
+	}
+
+	/// Snapshots from `start_tick` onwards, oldest first - e.g., for instant replays.
+	pub fn iter_from(&self, start_tick: u64) -> impl Iterator<Item=(u64, &[u8])> + '_ {
+		self.entries.iter()
+			.filter(move |(tick, _)| *tick >= start_tick)
+			.map(|(tick, bytes)| (*tick, bytes.as_slice()))
+	}
+}
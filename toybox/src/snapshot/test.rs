@@ -0,0 +1,164 @@
+use super::*;
+
+
+#[test]
+fn bits_round_trip() {
+	let mut writer = BitWriter::new();
+	writer.write_bits(0b101, 3);
+	writer.write_bool(true);
+	writer.write_bits(u64::MAX, 64);
+	writer.write_bits(0x1234, 13);
+
+	assert_eq!(writer.bit_len(), 3 + 1 + 64 + 13);
+
+	let bytes = writer.finish();
+	assert_eq!(bytes.len(), 11);
+
+	let mut reader = BitReader::new(&bytes);
+	assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+	assert!(reader.read_bool().unwrap());
+	assert_eq!(reader.read_bits(64).unwrap(), u64::MAX);
+	assert_eq!(reader.read_bits(13).unwrap(), 0x1234 & 0x1fff);
+
+	// Only padding left in the final byte.
+	assert_eq!(reader.bits_remaining(), 7);
+}
+
+#[test]
+fn varint_round_trip() {
+	let values = [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX];
+
+	let mut writer = BitWriter::new();
+	for value in values {
+		writer.write_varint(value);
+	}
+
+	let bytes = writer.finish();
+	let mut reader = BitReader::new(&bytes);
+
+	for value in values {
+		assert_eq!(reader.read_varint().unwrap(), value);
+	}
+}
+
+#[test]
+fn varint_small_values_are_small() {
+	let mut writer = BitWriter::new();
+	writer.write_varint(5);
+	assert_eq!(writer.bit_len(), 8);
+
+	writer.write_varint(200);
+	assert_eq!(writer.bit_len(), 8 + 16);
+}
+
+#[test]
+fn ranged_round_trip() {
+	let mut writer = BitWriter::new();
+	writer.write_ranged(0, 0);
+	writer.write_ranged(5, 5);
+	writer.write_ranged(1000, 1023);
+
+	// No bits are needed to represent 0..=0.
+	assert_eq!(writer.bit_len(), 3 + 10);
+
+	let bytes = writer.finish();
+	let mut reader = BitReader::new(&bytes);
+	assert_eq!(reader.read_ranged(0).unwrap(), 0);
+	assert_eq!(reader.read_ranged(5).unwrap(), 5);
+	assert_eq!(reader.read_ranged(1023).unwrap(), 1000);
+}
+
+#[test]
+fn ranged_out_of_range_is_an_error() {
+	let mut writer = BitWriter::new();
+	writer.write_bits(7, 3);
+
+	let bytes = writer.finish();
+	assert!(BitReader::new(&bytes).read_ranged(5).is_err());
+}
+
+#[test]
+fn quantized_round_trip() {
+	let (min, max, bits) = (-10.0, 10.0, 12);
+	let step = (max - min) / ((1 << bits) - 1) as f32;
+
+	let mut writer = BitWriter::new();
+	for value in [-10.0, -3.3, 0.0, 7.25, 10.0, 50.0] {
+		writer.write_quantized(value, min, max, bits);
+	}
+
+	let bytes = writer.finish();
+	let mut reader = BitReader::new(&bytes);
+
+	for expected in [-10.0, -3.3, 0.0, 7.25, 10.0] {
+		let value = reader.read_quantized(min, max, bits).unwrap();
+		assert!((value - expected).abs() <= step / 2.0 + f32::EPSILON, "{value} should be within half a step of {expected}");
+	}
+
+	// Out of range values are clamped.
+	assert_eq!(reader.read_quantized(min, max, bits).unwrap(), max);
+}
+
+#[test]
+fn truncated_input_is_an_error() {
+	let mut writer = BitWriter::new();
+	writer.write_bits(0xabcd, 16);
+	writer.write_varint(u64::MAX);
+
+	let bytes = writer.finish();
+	let truncated = &bytes[..bytes.len() - 1];
+
+	let mut reader = BitReader::new(truncated);
+	assert_eq!(reader.read_bits(16).unwrap(), 0xabcd);
+	assert!(reader.read_varint().is_err());
+
+	assert!(BitReader::new(&[]).read_bool().is_err());
+	assert!(BitReader::new(&[0xff]).read_bits(9).is_err());
+}
+
+#[test]
+fn snapshot_impls_round_trip() {
+	type State = (Vec<(i32, Option<f32>)>, ([u8; 3], (usize, bool)));
+
+	let state: State = (
+		vec![(-5, Some(1.5)), (i32::MAX, None)],
+		([1, 2, 3], (123_456, true)),
+	);
+
+	let bytes = state.to_snapshot_bytes();
+	assert_eq!(State::from_snapshot_bytes(&bytes).unwrap(), state);
+
+	assert!(State::from_snapshot_bytes(&bytes[..bytes.len() / 2]).is_err());
+}
+
+#[test]
+fn delta_round_trip() {
+	let baseline: Vec<u8> = (0..64).collect();
+
+	let mut current = baseline.clone();
+	current[3] = 200;
+	current[40] = 0;
+
+	let delta = encode_delta(&baseline, &current);
+	assert!(delta.len() < current.len(), "Delta between similar snapshots should be smaller than either");
+	assert_eq!(apply_delta(&baseline, &delta).unwrap(), current);
+
+	// Growing and shrinking relative to the baseline.
+	let longer: Vec<u8> = (0..80).rev().collect();
+	assert_eq!(apply_delta(&baseline, &encode_delta(&baseline, &longer)).unwrap(), longer);
+
+	let shorter = &baseline[..10];
+	assert_eq!(apply_delta(&baseline, &encode_delta(&baseline, shorter)).unwrap(), shorter);
+
+	assert_eq!(apply_delta(&baseline, &encode_delta(&baseline, &[])).unwrap(), Vec::<u8>::new());
+}
+
+#[test]
+fn truncated_delta_is_an_error() {
+	let baseline = vec![0u8; 32];
+	let current = vec![0xffu8; 32];
+
+	let delta = encode_delta(&baseline, &current);
+	assert!(apply_delta(&baseline, &delta[..delta.len() / 2]).is_err());
+	assert!(apply_delta(&baseline, &[]).is_err());
+}
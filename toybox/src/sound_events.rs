@@ -0,0 +1,393 @@
+//! Data-driven mapping from gameplay [`SoundEvent`]s to sounds, so gameplay code never refers to sound assets directly.
+//!
+//! Gameplay emits events on the bus:
+//! ```ignore
+//! ctx.bus.emit(SoundEvent::new("footstep").param("surface", "stone").at(position));
+//! ```
+//! Which are matched against a table loaded from a json resource, set with [`SoundEvents::set_table_path`].
+//! Each event has a list of rules, and the first rule whose `when` params all match is used - so more specific rules
+//! should come first. All fields except `sounds` are optional:
+//! ```json
+//! {
+//!     "footstep": [
+//!         {
+//!             "when": { "surface": "stone" },
+//!             "sounds": ["sounds/step_stone_1.wav", "sounds/step_stone_2.wav"],
+//!             "volume": 0.8,
+//!             "spatial": { "min_distance": 1.0, "max_distance": 30.0, "rolloff": "inverse", "doppler_strength": 1.0 }
+//!         },
+//!         { "sounds": ["sounds/step.wav"] }
+//!     ]
+//! }
+//! ```
+//! Matched events are emitted back on the bus as [`PlaySound`]s, with [`SoundMetadata`](crate::SoundMetadata) sidecars
+//! already applied, for the app's mixer to pick up.
+
+use crate::prelude::*;
+use crate::sound_metadata::{SoundLibrary, SoundInstance};
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+
+/// Something happened that might make a sound. What, if anything, is played is decided by the [`SoundEventTable`].
+#[derive(Debug, Clone)]
+pub struct SoundEvent {
+	pub name: String,
+	pub params: Vec<(String, String)>,
+
+	/// World space position, for spatialized sounds. Events without a position are never attenuated.
+	pub position: Option<Vec3>,
+
+	/// World space velocity in units per second, for doppler. Assumed stationary if missing.
+	pub velocity: Option<Vec3>,
+}
+
+impl SoundEvent {
+	pub fn new(name: impl Into<String>) -> SoundEvent {
+		SoundEvent {
+			name: name.into(),
+			params: Vec::new(),
+			position: None,
+			velocity: None,
+		}
+	}
+
+	pub fn param(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+		self.params.push((key.into(), value.into()));
+		self
+	}
+
+	pub fn at(self, position: Vec3) -> Self {
+		Self { position: Some(position), .. self }
+	}
+
+	pub fn moving(self, velocity: Vec3) -> Self {
+		Self { velocity: Some(velocity), .. self }
+	}
+
+	pub fn param_value(&self, key: &str) -> Option<&str> {
+		self.params.iter()
+			.find(|(param_key, _)| param_key == key)
+			.map(|(_, value)| value.as_str())
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all="snake_case")]
+pub enum Rolloff {
+	/// Falls off linearly from full volume at `min_distance` to silence at `max_distance`.
+	#[default]
+	Linear,
+
+	/// Falls off as `min_distance / distance`, cut off at `max_distance`.
+	Inverse,
+}
+
+/// Speed of sound in air in m/s, assuming world units are meters.
+pub const SPEED_OF_SOUND: f32 = 343.0;
+
+/// Doppler pitch shifts are clamped to within this factor, so near-sonic speeds don't produce extreme shifts.
+pub const MAX_DOPPLER_SHIFT: f32 = 2.0;
+
+
+/// Position and velocity of an emitter or listener, in world space. Velocity is in units per second.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct SpatialState {
+	pub position: Vec3,
+	pub velocity: Vec3,
+}
+
+impl SpatialState {
+	pub fn new(position: Vec3, velocity: Vec3) -> SpatialState {
+		SpatialState { position, velocity }
+	}
+
+	pub fn stationary(position: Vec3) -> SpatialState {
+		SpatialState { position, velocity: Vec3::zero() }
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Spatialization {
+	pub min_distance: f32,
+	pub max_distance: f32,
+	pub rolloff: Rolloff,
+
+	/// Scales relative velocities before calculating doppler. Zero disables doppler, 1 is physically based, and
+	/// values in between are usually more pleasant for fast vehicles and projectiles.
+	pub doppler_strength: f32,
+}
+
+impl Default for Spatialization {
+	fn default() -> Self {
+		Spatialization {
+			min_distance: 1.0,
+			max_distance: 50.0,
+			rolloff: Rolloff::Linear,
+			doppler_strength: 0.0,
+		}
+	}
+}
+
+impl Spatialization {
+	/// Gain in [0, 1] for a sound `distance` away from the listener.
+	pub fn attenuation(&self, distance: f32) -> f32 {
+		if distance <= self.min_distance {
+			return 1.0
+		}
+
+		if distance >= self.max_distance {
+			return 0.0
+		}
+
+		match self.rolloff {
+			Rolloff::Linear => 1.0 - (distance - self.min_distance) / (self.max_distance - self.min_distance),
+			Rolloff::Inverse => self.min_distance / distance,
+		}
+	}
+
+	/// Pitch (and playback rate) multiplier due to doppler - above 1 when the emitter and listener are approaching
+	/// each other. Should be reapplied as they move, e.g., via an [`audio::RateControl`].
+	pub fn doppler_factor(&self, emitter: SpatialState, listener: SpatialState) -> f32 {
+		let to_listener = listener.position - emitter.position;
+		let distance = to_listener.length();
+
+		if self.doppler_strength <= 0.0 || distance <= f32::EPSILON {
+			return 1.0
+		}
+
+		let direction = to_listener / distance;
+
+		// Speeds along the line between them, positive when approaching. Kept below the speed of sound so the
+		// factor stays finite.
+		let max_speed = SPEED_OF_SOUND * 0.9;
+		let emitter_speed = (emitter.velocity.dot(direction) * self.doppler_strength).clamp(-max_speed, max_speed);
+		let listener_speed = (-listener.velocity.dot(direction) * self.doppler_strength).clamp(-max_speed, max_speed);
+
+		let factor = (SPEED_OF_SOUND + listener_speed) / (SPEED_OF_SOUND - emitter_speed);
+		factor.clamp(1.0 / MAX_DOPPLER_SHIFT, MAX_DOPPLER_SHIFT)
+	}
+}
+
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SoundEventRule {
+	/// Params the event must have for this rule to apply. Empty matches any event.
+	pub when: HashMap<String, String>,
+
+	/// Resource paths picked between at random, never picking the same sound twice in a row.
+	pub sounds: Vec<PathBuf>,
+
+	/// Linear gain, applied on top of the gain from the sound's sidecar.
+	pub volume: f32,
+
+	/// Unspatialized if missing.
+	pub spatial: Option<Spatialization>,
+}
+
+impl Default for SoundEventRule {
+	fn default() -> Self {
+		SoundEventRule {
+			when: HashMap::new(),
+			sounds: Vec::new(),
+			volume: 1.0,
+			spatial: None,
+		}
+	}
+}
+
+impl SoundEventRule {
+	pub fn matches(&self, event: &SoundEvent) -> bool {
+		self.when.iter()
+			.all(|(key, value)| event.param_value(key) == Some(value.as_str()))
+	}
+}
+
+
+#[derive(Debug, Clone, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct SoundEventTable {
+	pub events: HashMap<String, Vec<SoundEventRule>>,
+}
+
+impl SoundEventTable {
+	pub fn load(vfs: &vfs::Vfs, path: impl AsRef<Path>) -> anyhow::Result<SoundEventTable> {
+		let path = path.as_ref();
+
+		let table: SoundEventTable = vfs.load_json_resource(path)
+			.with_context(|| format!("Loading sound event table '{}'", path.display()))?;
+
+		table.validate()
+			.with_context(|| format!("Validating sound event table '{}'", path.display()))?;
+
+		Ok(table)
+	}
+
+	pub fn validate(&self) -> anyhow::Result<()> {
+		for (name, rules) in self.events.iter() {
+			for (index, rule) in rules.iter().enumerate() {
+				anyhow::ensure!(!rule.sounds.is_empty(), "'{name}' rule {index} has no sounds");
+				anyhow::ensure!(rule.volume >= 0.0, "'{name}' rule {index} volume must not be negative, got {}", rule.volume);
+
+				if let Some(Spatialization{min_distance, max_distance, doppler_strength, ..}) = rule.spatial {
+					anyhow::ensure!(min_distance > 0.0 && min_distance < max_distance,
+						"'{name}' rule {index} distances must be positive and ordered, got {min_distance}..{max_distance}");
+					anyhow::ensure!(doppler_strength >= 0.0,
+						"'{name}' rule {index} doppler_strength must not be negative, got {doppler_strength}");
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Index and rule of the first rule for `event` that matches its params.
+	pub fn resolve(&self, event: &SoundEvent) -> Option<(usize, &SoundEventRule)> {
+		self.events.get(&event.name)?
+			.iter()
+			.enumerate()
+			.find(|(_, rule)| rule.matches(event))
+	}
+}
+
+
+/// Emitted on the bus for each [`SoundEvent`] that matched a rule.
+#[derive(Debug, Clone)]
+pub struct PlaySound {
+	/// Name of the event that triggered this sound.
+	pub event: String,
+
+	/// Gain includes both the sidecar gain and the rule volume.
+	pub instance: SoundInstance,
+
+	pub position: Option<Vec3>,
+	pub velocity: Option<Vec3>,
+	pub spatial: Option<Spatialization>,
+}
+
+impl PlaySound {
+	/// Gain after attenuation, for a listener at `listener_position`.
+	pub fn gain_at(&self, listener_position: Vec3) -> f32 {
+		match (self.position, self.spatial) {
+			(Some(position), Some(spatial)) => self.instance.gain * spatial.attenuation((position - listener_position).length()),
+			_ => self.instance.gain,
+		}
+	}
+
+	/// Pitch including doppler, for `listener`. Only accounts for motion at the time of the event - sounds that play
+	/// for a while as their emitter moves should update [`Spatialization::doppler_factor`] themselves.
+	pub fn pitch_at(&self, listener: SpatialState) -> f32 {
+		match (self.position, self.spatial) {
+			(Some(position), Some(spatial)) => {
+				let emitter = SpatialState::new(position, self.velocity.unwrap_or_else(Vec3::zero));
+				self.instance.pitch * spatial.doppler_factor(emitter, listener)
+			}
+
+			_ => self.instance.pitch,
+		}
+	}
+}
+
+
+/// Turns [`SoundEvent`]s emitted on the bus into [`PlaySound`]s, according to a [`SoundEventTable`] that is reloaded
+/// when it changes on disk. Events are processed at the end of each frame, so [`PlaySound`]s can be polled from the
+/// start of the next.
+pub struct SoundEvents {
+	table_path: Option<PathBuf>,
+	table: Option<SoundEventTable>,
+
+	subscription: bus::Subscription<SoundEvent>,
+
+	/// Last sound picked for each (event, rule), to avoid repeats.
+	last_picked: HashMap<(String, usize), usize>,
+}
+
+impl SoundEvents {
+	pub(crate) fn new(bus: &bus::MessageBus) -> SoundEvents {
+		SoundEvents {
+			table_path: None,
+			table: None,
+			subscription: bus.subscribe(),
+			last_picked: HashMap::new(),
+		}
+	}
+
+	/// Resource path of the table to match events against. The table is loaded on next use.
+	/// Also settable with the `sound_events.table` config key.
+	pub fn set_table_path(&mut self, path: impl Into<PathBuf>) {
+		self.table_path = Some(path.into());
+		self.table = None;
+		self.last_picked.clear();
+	}
+
+	/// The current table, loading it if it hasn't been already. Tables that fail to load are logged and treated as empty.
+	pub fn table(&mut self, vfs: &vfs::Vfs) -> Option<&SoundEventTable> {
+		let path = self.table_path.as_ref()?;
+
+		let table = self.table.get_or_insert_with(|| {
+			SoundEventTable::load(vfs, path)
+				.unwrap_or_else(|error| {
+					log::error!("{error:?}");
+					SoundEventTable::default()
+				})
+		});
+
+		Some(table)
+	}
+
+	/// Resolve `event` immediately, rather than waiting for it to come through the bus.
+	pub fn resolve(&mut self, vfs: &vfs::Vfs, sounds: &mut SoundLibrary, event: &SoundEvent) -> Option<PlaySound> {
+		let rng = &mut rand::thread_rng();
+
+		let (rule_index, rule) = self.table(vfs)?.resolve(event)?;
+		let rule = rule.clone();
+
+		let last_picked = self.last_picked.entry((event.name.clone(), rule_index)).or_insert(usize::MAX);
+		let picked = match rule.sounds.len() {
+			1 => 0,
+			num_sounds => {
+				// Pick from all but the last picked sound, skipping over it.
+				let picked = rng.gen_range(0..num_sounds - (*last_picked < num_sounds) as usize);
+				picked + (picked >= *last_picked) as usize
+			}
+		};
+
+		*last_picked = picked;
+
+		let mut instance = sounds.next_instance(vfs, &rule.sounds[picked], rng);
+		instance.gain *= rule.volume;
+
+		Some(PlaySound {
+			event: event.name.clone(),
+			instance,
+			position: event.position,
+			velocity: event.velocity,
+			spatial: rule.spatial,
+		})
+	}
+
+	#[instrument(skip_all, name="toybox SoundEvents::update")]
+	pub(crate) fn update(&mut self, vfs: &vfs::Vfs, bus: &bus::MessageBus, sounds: &mut SoundLibrary) {
+		if let Some(path) = &self.table_path {
+			if self.table.is_some() && vfs.resource_changed(path) {
+				log::info!("Reloading sound event table '{}'", path.display());
+				self.table = None;
+				self.last_picked.clear();
+			}
+		}
+
+		let events: Vec<SoundEvent> = bus.poll(&self.subscription).collect();
+
+		for event in events {
+			match self.resolve(vfs, sounds, &event) {
+				Some(play_sound) => bus.emit(play_sound),
+				None => log::trace!("No sound for event '{}' {:?}", event.name, event.params),
+			}
+		}
+	}
+}
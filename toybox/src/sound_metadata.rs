@@ -0,0 +1,159 @@
+//! Per-sound tuning loaded from `.json` sidecars next to sound resources, so that sounds can be tweaked without code changes.
+//! The sidecar for `sounds/step.wav` is `sounds/step.wav.json`, and all fields are optional:
+//! ```json
+//! {
+//!     "gain": 0.8,
+//!     "pitch_range": [0.95, 1.05],
+//!     "loop_points": { "start": 1200, "end": 48000 },
+//!     "variations": ["sounds/step_1.wav", "sounds/step_2.wav"]
+//! }
+//! ```
+
+use crate::prelude::*;
+use crate::resource_cache::ReloadingCache;
+
+use std::path::{Path, PathBuf};
+
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct SoundMetadata {
+	/// Linear gain applied on top of whatever the sound is played at.
+	pub gain: f32,
+
+	/// Playback rate is picked uniformly from this range each time the sound is played.
+	pub pitch_range: [f32; 2],
+
+	pub loop_points: Option<LoopPoints>,
+
+	/// Resource paths played in turn instead of the sound itself. Empty means the sound always plays itself.
+	pub variations: Vec<PathBuf>,
+}
+
+impl Default for SoundMetadata {
+	fn default() -> Self {
+		SoundMetadata {
+			gain: 1.0,
+			pitch_range: [1.0, 1.0],
+			loop_points: None,
+			variations: Vec::new(),
+		}
+	}
+}
+
+/// In frames from the start of the sound. Playback loops back to `start` on reaching `end`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LoopPoints {
+	pub start: usize,
+	pub end: usize,
+}
+
+impl SoundMetadata {
+	pub fn sidecar_path(sound_path: impl AsRef<Path>) -> PathBuf {
+		let mut path = sound_path.as_ref().as_os_str().to_owned();
+		path.push(".json");
+		path.into()
+	}
+
+	/// Loads the sidecar for `sound_path` if it exists, or returns defaults if it doesn't.
+	pub fn load_for(vfs: &vfs::Vfs, sound_path: impl AsRef<Path>) -> anyhow::Result<SoundMetadata> {
+		let sidecar_path = Self::sidecar_path(sound_path);
+
+		if !vfs.path_exists(vfs::PathKind::Resource, &sidecar_path) {
+			return Ok(SoundMetadata::default())
+		}
+
+		let metadata: SoundMetadata = vfs.load_json_resource(&sidecar_path)
+			.with_context(|| format!("Loading sound metadata '{}'", sidecar_path.display()))?;
+
+		metadata.validate()
+			.with_context(|| format!("Validating sound metadata '{}'", sidecar_path.display()))?;
+
+		Ok(metadata)
+	}
+
+	pub fn validate(&self) -> anyhow::Result<()> {
+		let [min_pitch, max_pitch] = self.pitch_range;
+
+		anyhow::ensure!(self.gain >= 0.0, "gain must not be negative, got {}", self.gain);
+		anyhow::ensure!(min_pitch > 0.0 && min_pitch <= max_pitch, "pitch_range must be positive and ordered, got {:?}", self.pitch_range);
+
+		if let Some(LoopPoints{start, end}) = self.loop_points {
+			anyhow::ensure!(start < end, "loop_points start must come before end, got {start}..{end}");
+		}
+
+		Ok(())
+	}
+}
+
+
+/// Everything needed to play one instance of a sound, after variations and pitch randomisation are resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundInstance {
+	pub path: PathBuf,
+	pub gain: f32,
+	pub pitch: f32,
+	pub loop_points: Option<LoopPoints>,
+}
+
+
+/// Caches [`SoundMetadata`] by sound path, reloading sidecars when they change on disk, and tracks round-robin state
+/// for sounds with variations.
+pub struct SoundLibrary {
+	entries: ReloadingCache<SoundEntry>,
+}
+
+impl Default for SoundLibrary {
+	fn default() -> SoundLibrary {
+		SoundLibrary { entries: ReloadingCache::new("sound metadata for") }
+	}
+}
+
+#[derive(Default)]
+struct SoundEntry {
+	metadata: SoundMetadata,
+	next_variation: usize,
+}
+
+impl SoundLibrary {
+	/// Metadata for `sound_path`, loading its sidecar if it hasn't been already.
+	/// Sidecars that fail to load are logged and treated as missing.
+	pub fn metadata(&mut self, vfs: &vfs::Vfs, sound_path: impl AsRef<Path>) -> &SoundMetadata {
+		&self.entry(vfs, sound_path.as_ref()).metadata
+	}
+
+	/// Resolve the next play of `sound_path` - picking the next variation in turn, and a random pitch within range.
+	/// Variations use their own loop points, but the gain and pitch of `sound_path`.
+	pub fn next_instance(&mut self, vfs: &vfs::Vfs, sound_path: impl AsRef<Path>, rng: &mut impl Rng) -> SoundInstance {
+		let sound_path = sound_path.as_ref();
+		let entry = self.entry(vfs, sound_path);
+
+		let SoundMetadata { gain, pitch_range: [min_pitch, max_pitch], loop_points, ref variations } = entry.metadata;
+
+		let pitch = match min_pitch < max_pitch {
+			true => rng.gen_range(min_pitch..=max_pitch),
+			false => min_pitch,
+		};
+
+		let Some(variation) = variations.get(entry.next_variation % variations.len().max(1)).cloned() else {
+			return SoundInstance { path: sound_path.to_owned(), gain, pitch, loop_points }
+		};
+
+		entry.next_variation = (entry.next_variation + 1) % variations.len();
+
+		let loop_points = self.metadata(vfs, &variation).loop_points;
+		SoundInstance { path: variation, gain, pitch, loop_points }
+	}
+
+	/// Drop cached metadata for any sidecars that changed on disk, so they're reloaded on next use.
+	pub(crate) fn update(&mut self, vfs: &vfs::Vfs) {
+		self.entries.drop_changed(vfs, |sound_path| SoundMetadata::sidecar_path(sound_path));
+	}
+
+	fn entry(&mut self, vfs: &vfs::Vfs, sound_path: &Path) -> &mut SoundEntry {
+		self.entries.get_or_load(sound_path, || {
+			let metadata = SoundMetadata::load_for(vfs, sound_path)?;
+			Ok(SoundEntry { metadata, next_variation: 0 })
+		})
+	}
+}
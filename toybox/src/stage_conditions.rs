@@ -0,0 +1,117 @@
+use crate::prelude::*;
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConditionTarget {
+	Stage(gfx::FrameStage),
+
+	/// Any group created with [`gfx::CommandGroupEncoder::annotate`] with this label.
+	Annotation(String),
+}
+
+
+/// A condition on a single config key, parsed from one of:
+/// - `key` - met if `key` is `true`
+/// - `key=value` - met if `key` equals `value`
+/// - `key!=value` - met if `key` doesn't equal `value`
+///
+/// Missing keys never equal anything, so `key` and `key=value` conditions are unmet if `key` isn't set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigCondition {
+	key: String,
+	expected: String,
+	negated: bool,
+}
+
+impl ConfigCondition {
+	pub fn parse(condition: &str) -> ConfigCondition {
+		let (key, expected, negated) = if let Some((key, value)) = condition.split_once("!=") {
+			(key, value, true)
+		} else if let Some((key, value)) = condition.split_once('=') {
+			(key, value, false)
+		} else {
+			(condition, "true", false)
+		};
+
+		ConfigCondition {
+			key: key.trim().to_owned(),
+			expected: expected.trim().to_owned(),
+			negated,
+		}
+	}
+
+	pub fn is_met(&self, cfg: &cfg::Config) -> bool {
+		let Some(value) = cfg.get_value(&self.key) else {
+			return self.negated
+		};
+
+		let equal = if let Some(value) = value.as_bool() {
+			self.expected.parse::<bool>().ok() == Some(value)
+		} else if let Some(value) = value.as_integer() {
+			self.expected.parse::<i64>().ok() == Some(value)
+		} else if let Some(value) = value.as_float() {
+			self.expected.parse::<f64>().ok() == Some(value)
+		} else if let Some(value) = value.as_str() {
+			self.expected == value
+		} else {
+			false
+		};
+
+		equal != self.negated
+	}
+}
+
+
+/// Stages and annotated groups that should only be dispatched while some condition holds, evaluated every frame.
+/// Lets config toggles like `debug.bloom` turn whole passes off without branching at every submission site.
+/// Keys in sections bound by [`crate::settings`] (`graphics`, `audio`, `input`) must be fields of those settings,
+/// so free-form toggles belong in another section.
+///
+/// ```ignore
+/// ctx.stage_conditions.annotation_when_config("Bloom", "debug.bloom");
+/// ```
+#[derive(Default)]
+pub struct StageConditions {
+	conditions: Vec<(ConditionTarget, Box<dyn Fn(&cfg::Config) -> bool>)>,
+}
+
+impl StageConditions {
+	/// Only dispatch `target` while `predicate` returns true. Replaces any existing condition for `target`.
+	pub fn set(&mut self, target: ConditionTarget, predicate: impl Fn(&cfg::Config) -> bool + 'static) {
+		self.remove(&target);
+		self.conditions.push((target, Box::new(predicate)));
+	}
+
+	/// See [`ConfigCondition`] for the format of `condition`.
+	pub fn set_config(&mut self, target: ConditionTarget, condition: &str) {
+		let condition = ConfigCondition::parse(condition);
+		self.set(target, move |cfg| condition.is_met(cfg));
+	}
+
+	pub fn stage_when_config(&mut self, stage: gfx::FrameStage, condition: &str) {
+		self.set_config(ConditionTarget::Stage(stage), condition);
+	}
+
+	pub fn annotation_when_config(&mut self, label: &str, condition: &str) {
+		self.set_config(ConditionTarget::Annotation(label.to_owned()), condition);
+	}
+
+	/// `target` will be dispatched unconditionally from the next frame.
+	pub fn remove(&mut self, target: &ConditionTarget) {
+		self.conditions.retain(|(existing, _)| existing != target);
+	}
+
+	/// Evaluate all conditions and apply them to `frame_encoder`, including resetting removed conditions.
+	pub(crate) fn apply(&self, cfg: &cfg::Config, frame_encoder: &mut gfx::FrameEncoder) {
+		frame_encoder.clear_conditions();
+
+		for (target, predicate) in self.conditions.iter() {
+			let met = predicate(cfg);
+
+			match target {
+				ConditionTarget::Stage(stage) => frame_encoder.set_stage_condition(*stage, met),
+				ConditionTarget::Annotation(label) => frame_encoder.set_annotation_condition(label, met),
+			}
+		}
+	}
+}
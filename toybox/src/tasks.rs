@@ -0,0 +1,272 @@
+//! Cooperative scheduling for long running main thread work - world generation, baking, asset post-processing -
+//! split into small steps and run within a fixed time slice each frame, so it never freezes the app.
+//!
+//! ```rust ignore
+//! let mut remaining = chunks_to_generate;
+//! let handle = ctx.tasks.spawn("worldgen", TaskPriority::NORMAL, move |task| {
+//!     while let Some(chunk) = remaining.pop() {
+//!         generate(chunk);
+//!         task.set_progress(1.0 - remaining.len() as f32 / total as f32);
+//!
+//!         if task.should_yield() {
+//!             return TaskStep::Pending
+//!         }
+//!     }
+//!
+//!     TaskStep::Done
+//! });
+//! ```
+
+use crate::prelude::*;
+
+use std::time::{Duration, Instant};
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TaskPriority(pub i32);
+
+impl TaskPriority {
+	pub const LOW: TaskPriority = TaskPriority(-100);
+	pub const NORMAL: TaskPriority = TaskPriority(0);
+	pub const HIGH: TaskPriority = TaskPriority(100);
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TaskStep {
+	/// Run again next time this task is scheduled.
+	Pending,
+	Done,
+}
+
+
+/// Released once the task finishes or is cancelled.
+pub type TaskHandle = handle::Handle<TaskInfo>;
+
+
+/// Passed to each step of a task.
+pub struct TaskContext {
+	slice_end: Instant,
+	progress: Option<f32>,
+}
+
+impl TaskContext {
+	/// Whether the frame's time slice has been used up. Tasks that loop internally should check this and return
+	/// [`TaskStep::Pending`] when it's true.
+	pub fn should_yield(&self) -> bool {
+		Instant::now() >= self.slice_end
+	}
+
+	/// Report progress in [0, 1], for loading bars and the debug menu.
+	pub fn set_progress(&mut self, progress: f32) {
+		self.progress = Some(progress.clamp(0.0, 1.0));
+	}
+}
+
+
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+	pub handle: TaskHandle,
+	pub name: String,
+	pub priority: TaskPriority,
+	pub progress: Option<f32>,
+	pub steps: u32,
+
+	/// Total time spent running steps of this task.
+	pub run_time: Duration,
+}
+
+
+struct Task {
+	info: TaskInfo,
+	step: Box<dyn FnMut(&mut TaskContext) -> TaskStep>,
+
+	// Used to round robin between tasks of the same priority.
+	last_run_frame: u64,
+}
+
+
+/// Runs tasks on the main thread in priority order, for at most [`TaskScheduler::time_slice`] per frame.
+/// Tasks of equal priority take turns. At least one step is always run per frame while tasks are pending, so a slice
+/// smaller than a single step still makes progress.
+pub struct TaskScheduler {
+	/// Time budget for all tasks per frame.
+	pub time_slice: Duration,
+
+	tasks: Vec<Task>,
+	frame: u64,
+}
+
+impl Default for TaskScheduler {
+	fn default() -> Self {
+		TaskScheduler {
+			time_slice: Duration::from_millis(4),
+
+			tasks: Vec::new(),
+			frame: 0,
+		}
+	}
+}
+
+impl TaskScheduler {
+	pub fn spawn(&mut self, name: impl Into<String>, priority: TaskPriority, step: impl FnMut(&mut TaskContext) -> TaskStep + 'static) -> TaskHandle {
+		let name = name.into();
+		let handle = TaskHandle::new(name.clone());
+
+		self.tasks.push(Task {
+			info: TaskInfo {
+				handle,
+				name,
+				priority,
+				progress: None,
+				steps: 0,
+				run_time: Duration::ZERO,
+			},
+
+			step: Box::new(step),
+			last_run_frame: 0,
+		});
+
+		handle
+	}
+
+	/// Stop a task before it completes. Its closure is dropped immediately. Returns false if the task has already
+	/// finished or been cancelled.
+	pub fn cancel(&mut self, handle: TaskHandle) -> bool {
+		let num_tasks = self.tasks.len();
+		self.tasks.retain(|task| task.info.handle != handle);
+
+		let cancelled = self.tasks.len() != num_tasks;
+		if cancelled {
+			handle.release();
+		}
+
+		cancelled
+	}
+
+	pub fn set_priority(&mut self, handle: TaskHandle, priority: TaskPriority) {
+		if let Some(task) = self.tasks.iter_mut().find(|task| task.info.handle == handle) {
+			task.info.priority = priority;
+		}
+	}
+
+	/// Whether the task is still pending. Finished and cancelled tasks aren't.
+	pub fn is_running(&self, handle: TaskHandle) -> bool {
+		self.tasks.iter().any(|task| task.info.handle == handle)
+	}
+
+	pub fn info(&self, handle: TaskHandle) -> Option<&TaskInfo> {
+		self.tasks.iter()
+			.find(|task| task.info.handle == handle)
+			.map(|task| &task.info)
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item=&TaskInfo> + '_ {
+		self.tasks.iter().map(|task| &task.info)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.tasks.is_empty()
+	}
+
+	#[instrument(skip_all, name="toybox TaskScheduler::run")]
+	pub(crate) fn run(&mut self) {
+		self.frame += 1;
+
+		if self.tasks.is_empty() {
+			return
+		}
+
+		let slice_start = Instant::now();
+		let slice_end = slice_start + self.time_slice;
+
+		loop {
+			// Highest priority first, then whichever task of that priority has waited longest.
+			let Some(index) = self.tasks.iter().enumerate()
+				.max_by_key(|(_, task)| (task.info.priority, std::cmp::Reverse(task.last_run_frame)))
+				.map(|(index, _)| index)
+			else {
+				break
+			};
+
+			let task = &mut self.tasks[index];
+			let mut context = TaskContext { slice_end, progress: task.info.progress };
+
+			let step_start = Instant::now();
+			let result = {
+				let _span = tracing::info_span!("task step", name=%task.info.name).entered();
+				(task.step)(&mut context)
+			};
+
+			task.info.steps += 1;
+			task.info.run_time += step_start.elapsed();
+			task.info.progress = context.progress;
+			task.last_run_frame = self.frame;
+
+			if result == TaskStep::Done {
+				log::debug!("Task '{}' finished after {} steps, {:.1}ms", task.info.name, task.info.steps,
+					task.info.run_time.as_secs_f32() * 1000.0);
+				self.tasks.remove(index).info.handle.release();
+			}
+
+			if Instant::now() >= slice_end || self.tasks.is_empty() {
+				break
+			}
+		}
+	}
+}
+
+
+
+pub(crate) fn tasks_ui(ui: &mut egui::Ui, tasks: &mut TaskScheduler) {
+	let mut slice_ms = tasks.time_slice.as_secs_f32() * 1000.0;
+
+	ui.horizontal(|ui| {
+		ui.label("Time slice");
+		if ui.add(egui::DragValue::new(&mut slice_ms).speed(0.1).clamp_range(0.0..=100.0).suffix("ms")).changed() {
+			tasks.time_slice = Duration::from_secs_f32(slice_ms / 1000.0);
+		}
+	});
+
+	ui.separator();
+
+	if tasks.is_empty() {
+		ui.label("No pending tasks");
+		return
+	}
+
+	let mut to_cancel = None;
+
+	egui::Grid::new("tasks")
+		.striped(true)
+		.show(ui, |ui| {
+			ui.label("Task");
+			ui.label("Priority");
+			ui.label("Progress");
+			ui.label("Run Time");
+			ui.label("");
+			ui.end_row();
+
+			for info in tasks.iter() {
+				ui.label(&info.name);
+				ui.label(info.priority.0.to_string());
+
+				match info.progress {
+					Some(progress) => ui.add(egui::ProgressBar::new(progress).show_percentage()),
+					None => ui.label(format!("{} steps", info.steps)),
+				};
+
+				ui.label(format!("{:.1}ms", info.run_time.as_secs_f32() * 1000.0));
+
+				if ui.button("Cancel").clicked() {
+					to_cancel = Some(info.handle);
+				}
+
+				ui.end_row();
+			}
+		});
+
+	if let Some(handle) = to_cancel {
+		tasks.cancel(handle);
+	}
+}
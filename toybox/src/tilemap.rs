@@ -0,0 +1,570 @@
+//! Tile maps made in [Tiled](https://www.mapeditor.org/), exported as JSON (`.tmj`/`.json`). Tilesets can be embedded
+//! or external (`.tsj`/`.json`), but must use a single image. Only tile layers are drawn - object and image layers
+//! are ignored.
+//!
+//! Layers are split into [`TILEMAP_CHUNK_SIZE`]² tile chunks when loaded, and only chunks overlapping the view are
+//! drawn. Visible chunks of the same layer and tileset are merged into a single batch, which is kept on the gpu and
+//! only rebuilt when the set of visible chunks or the layer's opacity changes.
+//!
+//! Map space is in pixels, with y up, and the top left corner of the map at the origin - so the map lies in negative y.
+
+use crate::prelude::*;
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+
+pub const TILEMAP_CHUNK_SIZE: u32 = 16;
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+const GID_MASK: u32 = !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY | 0x1000_0000);
+
+
+#[derive(serde::Deserialize)]
+struct TiledMap {
+	width: u32,
+	height: u32,
+	tilewidth: u32,
+	tileheight: u32,
+
+	#[serde(default)]
+	infinite: bool,
+
+	layers: Vec<TiledLayer>,
+	tilesets: Vec<TiledTilesetRef>,
+}
+
+#[derive(serde::Deserialize)]
+struct TiledLayer {
+	#[serde(rename="type")]
+	ty: String,
+
+	#[serde(default)]
+	name: String,
+
+	#[serde(default)]
+	data: Vec<u32>,
+
+	#[serde(default = "default_true")]
+	visible: bool,
+
+	#[serde(default = "default_one")]
+	opacity: f32,
+
+	#[serde(default)]
+	offsetx: f32,
+
+	#[serde(default)]
+	offsety: f32,
+
+	#[serde(default = "default_one")]
+	parallaxx: f32,
+
+	#[serde(default = "default_one")]
+	parallaxy: f32,
+
+	/// Group layers.
+	#[serde(default)]
+	layers: Vec<TiledLayer>,
+}
+
+#[derive(serde::Deserialize)]
+struct TiledTilesetRef {
+	firstgid: u32,
+
+	/// Set for external tilesets, relative to the map.
+	source: Option<PathBuf>,
+
+	#[serde(flatten)]
+	tileset: Option<TiledTileset>,
+}
+
+#[derive(serde::Deserialize)]
+struct TiledTileset {
+	image: PathBuf,
+	imagewidth: u32,
+	imageheight: u32,
+	tilewidth: u32,
+	tileheight: u32,
+	columns: u32,
+	tilecount: u32,
+
+	#[serde(default)]
+	margin: u32,
+
+	#[serde(default)]
+	spacing: u32,
+}
+
+fn default_true() -> bool { true }
+fn default_one() -> f32 { 1.0 }
+
+
+
+#[derive(Debug, Clone)]
+pub struct Tileset {
+	pub first_gid: u32,
+	pub image: gfx::ImageHandle,
+	pub image_size: Vec2i,
+	pub tile_size: Vec2i,
+	pub columns: u32,
+	pub tile_count: u32,
+	pub margin: u32,
+	pub spacing: u32,
+}
+
+impl Tileset {
+	fn contains(&self, gid: u32) -> bool {
+		(self.first_gid..self.first_gid + self.tile_count).contains(&gid)
+	}
+
+	/// UV rect of a tile, as (min, max). Image rows are stored top to bottom, matching Tiled.
+	fn tile_uvs(&self, local_id: u32) -> (Vec2, Vec2) {
+		let column = local_id % self.columns;
+		let row = local_id / self.columns;
+
+		let min = Vec2i::new(
+			(self.margin + column * (self.tile_size.x as u32 + self.spacing)) as i32,
+			(self.margin + row * (self.tile_size.y as u32 + self.spacing)) as i32,
+		);
+
+		let image_size = self.image_size.to_vec2();
+		let min_uv = min.to_vec2() / image_size;
+		let max_uv = (min + self.tile_size).to_vec2() / image_size;
+
+		(min_uv, max_uv)
+	}
+}
+
+
+/// Tiles of one layer within one chunk, that use the same tileset.
+#[derive(Debug, Clone)]
+struct ChunkBatch {
+	tileset: usize,
+	vertices: Vec<gfx::StandardVertex>,
+}
+
+#[derive(Debug, Clone)]
+struct Chunk {
+	/// Bounds in map space. Includes offsets set in Tiled, but not [`TilemapLayer::offset`] or parallax.
+	min: Vec2,
+	max: Vec2,
+	batches: Vec<ChunkBatch>,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct TilemapLayer {
+	pub name: String,
+	pub visible: bool,
+	pub opacity: f32,
+
+	/// Extra offset applied when drawing, in pixels, y up. Offsets set in Tiled are already applied.
+	pub offset: Vec2,
+
+	/// How far the layer moves relative to the camera - 1.0 moves with the world, 0.0 is fixed to the screen.
+	pub parallax: Vec2,
+
+	chunks: Vec<Chunk>,
+	mesh: RefCell<LayerMesh>,
+}
+
+
+/// Merged vertices of the chunks of a layer that were visible when it was last drawn.
+#[derive(Debug, Default)]
+struct LayerMesh {
+	visible_chunks: Vec<usize>,
+	opacity: f32,
+
+	/// (tileset index, vertex buffer, number of vertices)
+	batches: Vec<(usize, gfx::BufferName, u32)>,
+}
+
+// Clones rebuild their own mesh rather than sharing buffers.
+impl Clone for LayerMesh {
+	fn clone(&self) -> Self {
+		LayerMesh::default()
+	}
+}
+
+impl LayerMesh {
+	fn is_stale(&self, visible_chunks: &[usize], opacity: f32) -> bool {
+		self.visible_chunks != visible_chunks || self.opacity != opacity
+	}
+
+	fn rebuild(&mut self, core: &gfx::Core, group: &mut gfx::CommandGroupEncoder<'_>, chunks: &[Chunk],
+		visible_chunks: Vec<usize>, opacity: f32, num_tilesets: usize)
+	{
+		// Merge visible chunks per tileset, so each layer is at most one draw per tileset.
+		let mut batches: Vec<Vec<gfx::StandardVertex>> = vec![Vec::new(); num_tilesets];
+		for &chunk_index in visible_chunks.iter() {
+			for batch in chunks[chunk_index].batches.iter() {
+				batches[batch.tileset].extend_from_slice(&batch.vertices);
+			}
+		}
+
+		// Vertices are built opaque white, so opacity can change without rebuilding chunks.
+		if opacity < 1.0 {
+			let alpha = (opacity.clamp(0.0, 1.0) * 65535.0).round() as u16;
+
+			for vertex in batches.iter_mut().flatten() {
+				vertex.color_packed[3] = alpha;
+			}
+		}
+
+		self.destroy(group);
+
+		for (tileset, vertices) in batches.into_iter().enumerate() {
+			if vertices.is_empty() {
+				continue
+			}
+
+			let buffer = core.create_buffer();
+			core.upload_immutable_buffer_immediate(buffer, &vertices);
+			self.batches.push((tileset, buffer, vertices.len() as u32));
+		}
+
+		self.visible_chunks = visible_chunks;
+		self.opacity = opacity;
+	}
+
+	fn destroy(&mut self, group: &mut gfx::CommandGroupEncoder<'_>) {
+		if self.batches.is_empty() {
+			return
+		}
+
+		// Draws recorded earlier in the group may still be using the old buffers.
+		let buffers: Vec<_> = self.batches.drain(..).map(|(_, buffer, _)| buffer).collect();
+		group.execute(move |core, _| {
+			for buffer in buffers {
+				core.destroy_buffer(buffer);
+			}
+		});
+
+		self.visible_chunks.clear();
+	}
+}
+
+
+/// Where and how much of the map to draw.
+#[derive(Debug, Copy, Clone)]
+pub struct TilemapView {
+	/// Map space position at the center of the view, for layers with a parallax of 1.
+	pub center: Vec2,
+
+	/// Size of the visible region in map space pixels.
+	pub size: Vec2,
+}
+
+impl TilemapView {
+	/// View the whole backbuffer at one texel per pixel, times `zoom`.
+	pub fn pixel_perfect(center: Vec2, backbuffer_size: Vec2i, zoom: f32) -> TilemapView {
+		TilemapView {
+			center,
+			size: backbuffer_size.to_vec2() / zoom,
+		}
+	}
+
+	fn layer_center(&self, layer: &TilemapLayer) -> Vec2 {
+		// Parallax layers scroll by a fraction of the camera movement, so from the layer's point of view the camera
+		// is only `parallax` as far along.
+		Vec2::new(self.center.x * layer.parallax.x, self.center.y * layer.parallax.y) - layer.offset
+	}
+}
+
+
+pub struct Tilemap {
+	pub size_in_tiles: Vec2i,
+	pub tile_size: Vec2i,
+	pub tilesets: Vec<Tileset>,
+	pub layers: Vec<TilemapLayer>,
+}
+
+impl Tilemap {
+	#[instrument(skip_all, name="toybox Tilemap::load")]
+	pub fn load(vfs: &vfs::Vfs, gfx: &mut gfx::System, path: impl AsRef<Path>) -> anyhow::Result<Tilemap> {
+		let path = path.as_ref();
+
+		anyhow::ensure!(path.extension().is_none_or(|extension| extension != "tmx"),
+			"Loading tilemap '{}': TMX maps aren't supported - export from Tiled as JSON instead", path.display());
+
+		let map: TiledMap = vfs.load_json_resource(path)
+			.with_context(|| format!("Loading tilemap '{}'", path.display()))?;
+
+		anyhow::ensure!(!map.infinite, "Loading tilemap '{}': infinite maps aren't supported", path.display());
+
+		let map_dir = path.parent().unwrap_or(Path::new(""));
+
+		let tilesets = map.tilesets.into_iter()
+			.map(|tileset_ref| load_tileset(vfs, gfx, map_dir, tileset_ref))
+			.collect::<anyhow::Result<Vec<_>>>()
+			.with_context(|| format!("Loading tilesets for tilemap '{}'", path.display()))?;
+
+		let size_in_tiles = Vec2i::new(map.width as i32, map.height as i32);
+		let tile_size = Vec2i::new(map.tilewidth as i32, map.tileheight as i32);
+
+		let mut layers = Vec::new();
+		flatten_layers(map.layers, LayerInheritance::default(), &mut |layer, inherited| {
+			layers.push(build_layer(layer, inherited, size_in_tiles, tile_size, &tilesets));
+		});
+
+		log::info!("Loaded tilemap '{}': {}x{} tiles, {} layers, {} tilesets", path.display(),
+			size_in_tiles.x, size_in_tiles.y, layers.len(), tilesets.len());
+
+		Ok(Tilemap {
+			size_in_tiles,
+			tile_size,
+			tilesets,
+			layers,
+		})
+	}
+
+	pub fn layer(&self, name: &str) -> Option<&TilemapLayer> {
+		self.layers.iter().find(|layer| layer.name == name)
+	}
+
+	pub fn layer_mut(&mut self, name: &str) -> Option<&mut TilemapLayer> {
+		self.layers.iter_mut().find(|layer| layer.name == name)
+	}
+
+	/// Draw all visible layers in order, back to front, into `group`. Uses alpha blending and no depth testing.
+	pub fn draw(&self, core: &gfx::Core, group: &mut gfx::CommandGroupEncoder<'_>, view: &TilemapView) {
+		for layer in self.layers.iter().filter(|layer| layer.visible && layer.opacity > 0.0) {
+			self.draw_layer(core, group, view, layer);
+		}
+	}
+
+	pub fn draw_layer(&self, core: &gfx::Core, group: &mut gfx::CommandGroupEncoder<'_>, view: &TilemapView, layer: &TilemapLayer) {
+		let center = view.layer_center(layer);
+		let half_size = view.size / 2.0;
+
+		let view_min = center - half_size;
+		let view_max = center + half_size;
+
+		let projection_view = Mat4::ortho(view_min.x, view_max.x, view_min.y, view_max.y, -1.0, 1.0);
+
+		let visible_chunks: Vec<usize> = layer.chunks.iter().enumerate()
+			.filter(|(_, chunk)| chunk.max.x > view_min.x && chunk.min.x < view_max.x
+				&& chunk.max.y > view_min.y && chunk.min.y < view_max.y)
+			.map(|(chunk_index, _)| chunk_index)
+			.collect();
+
+		let mut mesh = layer.mesh.borrow_mut();
+		if mesh.is_stale(&visible_chunks, layer.opacity) {
+			mesh.rebuild(core, group, &layer.chunks, visible_chunks, layer.opacity, self.tilesets.len());
+		}
+
+		for &(tileset_index, buffer, num_vertices) in mesh.batches.iter() {
+			let tileset = &self.tilesets[tileset_index];
+
+			group.draw(gfx::CommonShader::StandardVertex, gfx::CommonShader::FlatTexturedFragment)
+				.ubo(0, &[projection_view])
+				.ssbo(0, buffer)
+				.sampled_image(0, tileset.image, gfx::CommonSampler::Nearest)
+				.elements(num_vertices)
+				.blend_mode(gfx::BlendMode::ALPHA)
+				.depth_test(false)
+				.depth_write(false);
+		}
+	}
+
+	/// Free the gpu buffers of cached layer meshes, once commands already recorded into `group` have run.
+	pub fn destroy(self, group: &mut gfx::CommandGroupEncoder<'_>) {
+		for layer in self.layers {
+			layer.mesh.into_inner().destroy(group);
+		}
+	}
+}
+
+
+fn load_tileset(vfs: &vfs::Vfs, gfx: &mut gfx::System, map_dir: &Path, tileset_ref: TiledTilesetRef) -> anyhow::Result<Tileset> {
+	let (tileset, tileset_dir) = match tileset_ref.source {
+		Some(source) => {
+			let source_path = map_dir.join(&source);
+
+			anyhow::ensure!(source.extension().is_none_or(|extension| extension != "tsx"),
+				"TSX tileset '{}' isn't supported - export from Tiled as JSON instead", source_path.display());
+
+			let tileset: TiledTileset = vfs.load_json_resource(&source_path)
+				.with_context(|| format!("Loading tileset '{}'", source_path.display()))?;
+
+			(tileset, source_path.parent().map(Path::to_owned).unwrap_or_default())
+		}
+
+		None => {
+			let tileset = tileset_ref.tileset
+				.context("Embedded tileset is missing its image - only single image tilesets are supported")?;
+
+			(tileset, map_dir.to_owned())
+		}
+	};
+
+	let image_path = tileset_dir.join(&tileset.image);
+	let image = gfx.resource_manager.request(gfx::LoadImageRequest::from(image_path));
+
+	Ok(Tileset {
+		first_gid: tileset_ref.firstgid,
+		image,
+		image_size: Vec2i::new(tileset.imagewidth as i32, tileset.imageheight as i32),
+		tile_size: Vec2i::new(tileset.tilewidth as i32, tileset.tileheight as i32),
+		columns: tileset.columns.max(1),
+		tile_count: tileset.tilecount,
+		margin: tileset.margin,
+		spacing: tileset.spacing,
+	})
+}
+
+
+/// Properties of group layers that apply to all of their children.
+#[derive(Copy, Clone)]
+struct LayerInheritance {
+	visible: bool,
+	opacity: f32,
+	offset: Vec2,
+	parallax: Vec2,
+}
+
+impl Default for LayerInheritance {
+	fn default() -> Self {
+		LayerInheritance {
+			visible: true,
+			opacity: 1.0,
+			offset: Vec2::zero(),
+			parallax: Vec2::splat(1.0),
+		}
+	}
+}
+
+fn flatten_layers(layers: Vec<TiledLayer>, inherited: LayerInheritance, visit: &mut impl FnMut(TiledLayer, LayerInheritance)) {
+	for mut layer in layers {
+		let combined = LayerInheritance {
+			visible: inherited.visible && layer.visible,
+			opacity: inherited.opacity * layer.opacity,
+			offset: inherited.offset + Vec2::new(layer.offsetx, -layer.offsety),
+			parallax: Vec2::new(inherited.parallax.x * layer.parallaxx, inherited.parallax.y * layer.parallaxy),
+		};
+
+		match layer.ty.as_str() {
+			"tilelayer" => visit(layer, combined),
+			"group" => flatten_layers(std::mem::take(&mut layer.layers), combined, visit),
+			_ => {}
+		}
+	}
+}
+
+fn build_layer(layer: TiledLayer, inherited: LayerInheritance, size_in_tiles: Vec2i, tile_size: Vec2i, tilesets: &[Tileset]) -> TilemapLayer {
+	let chunks_x = (size_in_tiles.x as u32).div_ceil(TILEMAP_CHUNK_SIZE);
+	let chunks_y = (size_in_tiles.y as u32).div_ceil(TILEMAP_CHUNK_SIZE);
+
+	let tile_size_f = tile_size.to_vec2();
+
+	let mut chunks = Vec::new();
+
+	for chunk_y in 0..chunks_y {
+		for chunk_x in 0..chunks_x {
+			let mut batches: Vec<ChunkBatch> = Vec::new();
+
+			let tiles_x = chunk_x * TILEMAP_CHUNK_SIZE .. ((chunk_x + 1) * TILEMAP_CHUNK_SIZE).min(size_in_tiles.x as u32);
+			let tiles_y = chunk_y * TILEMAP_CHUNK_SIZE .. ((chunk_y + 1) * TILEMAP_CHUNK_SIZE).min(size_in_tiles.y as u32);
+
+			for tile_y in tiles_y.clone() {
+				for tile_x in tiles_x.clone() {
+					let index = (tile_y * size_in_tiles.x as u32 + tile_x) as usize;
+					let Some(&raw_gid) = layer.data.get(index) else { continue };
+
+					let gid = raw_gid & GID_MASK;
+					if gid == 0 {
+						continue
+					}
+
+					let Some(tileset_index) = tilesets.iter().rposition(|tileset| tileset.contains(gid)) else {
+						log::warn!("Tile gid {gid} in layer '{}' doesn't belong to any tileset", layer.name);
+						continue
+					};
+
+					let tileset = &tilesets[tileset_index];
+					let (uv_min, uv_max) = tileset.tile_uvs(gid - tileset.first_gid);
+
+					// Tiles larger than the map grid extend up and to the right from the bottom left of their cell.
+					let bottom_left = Vec2::new(tile_x as f32 * tile_size_f.x, -((tile_y + 1) as f32) * tile_size_f.y);
+					let quad_size = tileset.tile_size.to_vec2();
+
+					let batch = match batches.iter_mut().position(|batch| batch.tileset == tileset_index) {
+						Some(position) => &mut batches[position],
+						None => {
+							batches.push(ChunkBatch { tileset: tileset_index, vertices: Vec::new() });
+							batches.last_mut().unwrap()
+						}
+					};
+
+					push_tile_quad(&mut batch.vertices, bottom_left + inherited.offset, quad_size, uv_min, uv_max, raw_gid);
+				}
+			}
+
+			if batches.is_empty() {
+				continue
+			}
+
+			// Oversized tiles can extend past the chunk, so pad bounds by the largest tileset tile.
+			let max_tile_size = tilesets.iter()
+				.fold(tile_size_f, |size, tileset| Vec2::new(size.x.max(tileset.tile_size.x as f32), size.y.max(tileset.tile_size.y as f32)));
+
+			let min = Vec2::new(tiles_x.start as f32 * tile_size_f.x, -(tiles_y.end as f32) * tile_size_f.y);
+			let max = Vec2::new(tiles_x.end as f32 * tile_size_f.x, -(tiles_y.start as f32) * tile_size_f.y);
+
+			chunks.push(Chunk {
+				min: min + inherited.offset,
+				max: max + inherited.offset + max_tile_size - tile_size_f,
+				batches,
+			});
+		}
+	}
+
+	TilemapLayer {
+		name: layer.name,
+		visible: inherited.visible,
+		opacity: inherited.opacity,
+		offset: Vec2::zero(),
+		parallax: inherited.parallax,
+		chunks,
+		mesh: RefCell::default(),
+	}
+}
+
+fn push_tile_quad(vertices: &mut Vec<gfx::StandardVertex>, bottom_left: Vec2, size: Vec2, uv_min: Vec2, uv_max: Vec2, raw_gid: u32) {
+	// Corners in order: bottom left, bottom right, top right, top left. Image v increases downwards.
+	let mut uvs = [
+		Vec2::new(uv_min.x, uv_max.y),
+		Vec2::new(uv_max.x, uv_max.y),
+		Vec2::new(uv_max.x, uv_min.y),
+		Vec2::new(uv_min.x, uv_min.y),
+	];
+
+	if raw_gid & FLIPPED_DIAGONALLY != 0 {
+		// Swap x and y - i.e., mirror across the top left to bottom right diagonal.
+		uvs.swap(0, 2);
+	}
+
+	if raw_gid & FLIPPED_HORIZONTALLY != 0 {
+		uvs.swap(0, 1);
+		uvs.swap(2, 3);
+	}
+
+	if raw_gid & FLIPPED_VERTICALLY != 0 {
+		uvs.swap(0, 3);
+		uvs.swap(1, 2);
+	}
+
+	let positions = [
+		bottom_left,
+		bottom_left + Vec2::new(size.x, 0.0),
+		bottom_left + size,
+		bottom_left + Vec2::new(0.0, size.y),
+	];
+
+	for index in [0, 1, 2, 0, 2, 3] {
+		vertices.push(gfx::StandardVertex::new(positions[index].extend(0.0), uvs[index], Color::white()));
+	}
+}
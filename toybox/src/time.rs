@@ -0,0 +1,116 @@
+use crate::prelude::*;
+
+use std::time::Instant;
+
+
+/// Frame timing, and scaling of game time for slow motion and pausing.
+///
+/// Scaled time drives [`App::update`](crate::App::update) and the fixed timestep accumulator, and optionally
+/// audio playback. Anything else that advances over time should use [`Time::delta_time`] to respect it too,
+/// and [`Time::real_delta_time`] for things that shouldn't slow down, like menus.
+#[derive(Debug)]
+pub struct Time {
+	scale: f32,
+	paused: bool,
+
+	/// Whether audio playback should follow the time scale. Pausing always pauses audio if this is set.
+	pub scale_audio: bool,
+
+	/// If set, audio slows down without changing pitch - it's up to the [`audio::Provider`] to implement this.
+	/// Otherwise audio is resampled, like slowing down a tape.
+	pub preserve_audio_pitch: bool,
+
+	real_delta_time: f32,
+	elapsed: f64,
+	real_elapsed: f64,
+
+	last_frame_instant: Instant,
+}
+
+impl Time {
+	pub(crate) fn new() -> Time {
+		Time {
+			scale: 1.0,
+			paused: false,
+
+			scale_audio: true,
+			preserve_audio_pitch: false,
+
+			real_delta_time: 0.0,
+			elapsed: 0.0,
+			real_elapsed: 0.0,
+
+			last_frame_instant: Instant::now(),
+		}
+	}
+
+	/// Multiplier applied to game time. Clamped to be non-negative.
+	pub fn set_scale(&mut self, scale: f32) {
+		self.scale = scale.max(0.0);
+	}
+
+	pub fn scale(&self) -> f32 {
+		self.scale
+	}
+
+	pub fn set_paused(&mut self, paused: bool) {
+		self.paused = paused;
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	pub fn toggle_paused(&mut self) {
+		self.paused = !self.paused;
+	}
+
+	/// The scale actually applied this frame - zero while paused.
+	pub fn effective_scale(&self) -> f32 {
+		match self.paused {
+			true => 0.0,
+			false => self.scale,
+		}
+	}
+
+	/// Scaled time in seconds since the last frame.
+	pub fn delta_time(&self) -> f32 {
+		self.real_delta_time * self.effective_scale()
+	}
+
+	/// Unscaled time in seconds since the last frame.
+	pub fn real_delta_time(&self) -> f32 {
+		self.real_delta_time
+	}
+
+	/// Scaled time in seconds since startup.
+	pub fn elapsed(&self) -> f64 {
+		self.elapsed
+	}
+
+	/// Unscaled time in seconds since startup.
+	pub fn real_elapsed(&self) -> f64 {
+		self.real_elapsed
+	}
+
+	/// Don't count time since the last frame, e.g., after rendering was paused.
+	pub(crate) fn skip_elapsed(&mut self) {
+		self.last_frame_instant = Instant::now();
+	}
+
+	pub(crate) fn start_frame(&mut self, audio: &audio::System) {
+		let now = Instant::now();
+		self.real_delta_time = (now - self.last_frame_instant).as_secs_f32();
+		self.last_frame_instant = now;
+
+		self.elapsed += self.delta_time() as f64;
+		self.real_elapsed += self.real_delta_time as f64;
+
+		let audio_scale = match self.scale_audio {
+			true => self.effective_scale(),
+			false => 1.0,
+		};
+
+		audio.set_time_scale(audio_scale, self.preserve_audio_pitch);
+	}
+}
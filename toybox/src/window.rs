@@ -0,0 +1,289 @@
+use crate::prelude::*;
+
+use host::winit;
+use host::raw_window_handle::{self, HasWindowHandle, HasDisplayHandle, WindowHandle, DisplayHandle, HandleError};
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+pub use winit::window::ResizeDirection;
+
+
+/// Access to the OS window.
+/// Mostly useful for borderless windows (see [`host::Settings::no_decorations`]), which need to implement their own
+/// title bars and resize handles.
+pub struct Window {
+	inner: Rc<winit::window::Window>,
+	swap_control: host::SwapControl,
+
+	progress: Option<f32>,
+	taskbar_progress: host::TaskbarProgress,
+	vsync: bool,
+	scale_factor_override: Option<f32>,
+
+	rendering_pauses: Rc<Cell<u32>>,
+
+	event_hooks: Vec<(WindowEventHookId, Box<dyn FnMut(&winit::event::WindowEvent) -> bool>)>,
+	next_event_hook_id: u32,
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WindowEventHookId(u32);
+
+
+/// Keeps the engine from running frames until dropped. See [`Window::pause_rendering`].
+#[must_use]
+pub struct RenderingPause {
+	pauses: Rc<Cell<u32>>,
+}
+
+impl Drop for RenderingPause {
+	fn drop(&mut self) {
+		self.pauses.set(self.pauses.get() - 1);
+	}
+}
+
+/// What dragging from some point on a borderless window should do.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DragRegion {
+	Move,
+	Resize(ResizeDirection),
+}
+
+impl Window {
+	pub(crate) fn new(host: &host::Host) -> Window {
+		Window {
+			inner: host.window.clone(),
+			swap_control: host.swap_control(),
+
+			progress: None,
+			taskbar_progress: host::TaskbarProgress::new(&host.window, &host.app_name),
+
+			// Host enables vsync on startup.
+			vsync: true,
+			scale_factor_override: None,
+
+			rendering_pauses: Rc::new(Cell::new(0)),
+
+			event_hooks: Vec::new(),
+			next_event_hook_id: 0,
+		}
+	}
+
+	/// Stop running frames - no updates, rendering or swaps - until the returned guard is dropped, e.g., while a native
+	/// modal dialog or an external overlay owns the window. Events are still processed.
+	/// Time spent paused doesn't count towards [`crate::Time`], and held buttons are released on resume.
+	/// See also [`crate::Context::run_modal`].
+	pub fn pause_rendering(&self) -> RenderingPause {
+		self.rendering_pauses.set(self.rendering_pauses.get() + 1);
+		RenderingPause { pauses: Rc::clone(&self.rendering_pauses) }
+	}
+
+	pub fn is_rendering_paused(&self) -> bool {
+		self.rendering_pauses.get() > 0
+	}
+
+	/// Let an external library see window events before anything else does. If `hook` returns true, the event is
+	/// consumed and egui, input and the app won't see it.
+	pub fn add_event_hook(&mut self, hook: impl FnMut(&winit::event::WindowEvent) -> bool + 'static) -> WindowEventHookId {
+		let id = WindowEventHookId(self.next_event_hook_id);
+		self.next_event_hook_id += 1;
+
+		self.event_hooks.push((id, Box::new(hook)));
+		id
+	}
+
+	pub fn remove_event_hook(&mut self, id: WindowEventHookId) {
+		self.event_hooks.retain(|(hook_id, _)| *hook_id != id);
+	}
+
+	/// Returns true if any hook consumed the event.
+	pub(crate) fn run_event_hooks(&mut self, event: &winit::event::WindowEvent) -> bool {
+		self.event_hooks.iter_mut()
+			.any(|(_, hook)| hook(event))
+	}
+
+	/// The underlying winit window, for anything not wrapped here.
+	pub fn winit_window(&self) -> &winit::window::Window {
+		&self.inner
+	}
+
+	pub fn size(&self) -> Vec2i {
+		let winit::dpi::PhysicalSize{width, height} = self.inner.inner_size().cast::<i32>();
+		Vec2i::new(width, height)
+	}
+
+	/// Physical pixels per logical pixel, respecting [`crate::Context::set_scale_factor_override`].
+	pub fn scale_factor(&self) -> f32 {
+		self.scale_factor_override.unwrap_or_else(|| self.native_scale_factor())
+	}
+
+	/// Scale factor as reported by the platform. Fractional on Wayland compositors supporting fractional-scale-v1,
+	/// and on X11 it comes from `Xft.dpi` or `WINIT_X11_SCALE_FACTOR`.
+	pub fn native_scale_factor(&self) -> f32 {
+		self.inner.scale_factor() as f32
+	}
+
+	pub fn scale_factor_override(&self) -> Option<f32> {
+		self.scale_factor_override
+	}
+
+	pub(crate) fn set_scale_factor_override(&mut self, scale_factor: Option<f32>) {
+		self.scale_factor_override = scale_factor;
+	}
+
+	pub fn is_decorated(&self) -> bool {
+		self.inner.is_decorated()
+	}
+
+	pub fn set_vsync(&mut self, enabled: bool) {
+		if self.vsync != enabled {
+			self.swap_control.set_vsync(enabled);
+			self.vsync = enabled;
+		}
+	}
+
+	pub fn is_vsync_enabled(&self) -> bool {
+		self.vsync
+	}
+
+	/// Switch between borderless fullscreen on the current monitor and windowed.
+	pub fn set_fullscreen(&self, fullscreen: bool) {
+		if fullscreen == self.is_fullscreen() {
+			return
+		}
+
+		self.inner.set_fullscreen(fullscreen.then_some(winit::window::Fullscreen::Borderless(None)));
+	}
+
+	pub fn is_fullscreen(&self) -> bool {
+		self.inner.fullscreen().is_some()
+	}
+
+	/// Request a new size for the window in physical pixels. May be ignored or adjusted by the platform.
+	pub fn request_size(&self, size: Vec2i) {
+		let _ = self.inner.request_inner_size(winit::dpi::PhysicalSize::new(size.x as u32, size.y as u32));
+	}
+
+	/// Sizes supported by the monitor the window is currently on, largest first.
+	pub fn available_resolutions(&self) -> Vec<Vec2i> {
+		let Some(monitor) = self.inner.current_monitor() else {
+			return Vec::new()
+		};
+
+		let mut resolutions: Vec<_> = monitor.video_modes()
+			.map(|mode| {
+				let winit::dpi::PhysicalSize{width, height} = mode.size().cast::<i32>();
+				Vec2i::new(width, height)
+			})
+			.collect();
+
+		resolutions.sort_by_key(|size| std::cmp::Reverse((size.x, size.y)));
+		resolutions.dedup();
+		resolutions
+	}
+
+	/// Show progress of some long running task on the window's taskbar entry, or None to clear it.
+	/// `progress` is clamped to [0, 1]. See [`host::TaskbarProgress`] for platform support.
+	pub fn set_progress(&mut self, progress: impl Into<Option<f32>>) {
+		let progress = progress.into().map(|progress| progress.clamp(0.0, 1.0));
+		if progress == self.progress {
+			return
+		}
+
+		self.progress = progress;
+		self.taskbar_progress.set(progress);
+	}
+
+	pub fn progress(&self) -> Option<f32> {
+		self.progress
+	}
+
+	/// Flash the taskbar entry or bounce the dock icon, if the window isn't already focussed.
+	/// Cleared automatically once the window gains focus.
+	pub fn request_user_attention(&self) {
+		self.inner.request_user_attention(Some(winit::window::UserAttentionType::Informational));
+	}
+
+	pub fn cancel_user_attention_request(&self) {
+		self.inner.request_user_attention(None);
+	}
+
+	/// Start moving the window with the mouse. Should be called while the left mouse button is held.
+	pub fn start_move(&self) {
+		if let Err(error) = self.inner.drag_window() {
+			log::warn!("Failed to start window move: {error}");
+		}
+	}
+
+	/// Start resizing the window with the mouse. Should be called while the left mouse button is held.
+	pub fn start_resize(&self, direction: ResizeDirection) {
+		if let Err(error) = self.inner.drag_resize_window(direction) {
+			log::warn!("Failed to start window resize: {error}");
+		}
+	}
+
+	pub fn start_drag(&self, region: DragRegion) {
+		match region {
+			DragRegion::Move => self.start_move(),
+			DragRegion::Resize(direction) => self.start_resize(direction),
+		}
+	}
+
+	/// Classifies `position` for a window with a custom title bar `title_bar_height` pixels tall, and resize handles
+	/// `border_size` pixels wide around its edges. `position` should come from [`input::System::mouse_screen_pos`]
+	/// without a pixel mapping.
+	/// Resize handles take priority over the title bar. Returns None if `position` isn't in either.
+	pub fn drag_region_at(&self, position: input::ScreenPos, title_bar_height: f32, border_size: f32) -> Option<DragRegion> {
+		let input::ScreenPos(position) = position;
+		let size = self.size().to_vec2();
+
+		let left = position.x < border_size;
+		let right = position.x >= size.x - border_size;
+		let bottom = position.y < border_size;
+		let top = position.y >= size.y - border_size;
+
+		let direction = match (left, right, top, bottom) {
+			(true, _, true, _) => Some(ResizeDirection::NorthWest),
+			(_, true, true, _) => Some(ResizeDirection::NorthEast),
+			(true, _, _, true) => Some(ResizeDirection::SouthWest),
+			(_, true, _, true) => Some(ResizeDirection::SouthEast),
+			(true, ..) => Some(ResizeDirection::West),
+			(_, true, ..) => Some(ResizeDirection::East),
+			(_, _, true, _) => Some(ResizeDirection::North),
+			(.., true) => Some(ResizeDirection::South),
+			_ => None,
+		};
+
+		if let Some(direction) = direction {
+			return Some(DragRegion::Resize(direction))
+		}
+
+		(position.y >= size.y - title_bar_height).then_some(DragRegion::Move)
+	}
+}
+
+
+/// Raw handles for integrating native libraries - file dialogs, capture SDKs, XR loaders and the like.
+impl HasWindowHandle for Window {
+	fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+		self.inner.window_handle()
+	}
+}
+
+impl HasDisplayHandle for Window {
+	fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+		self.inner.display_handle()
+	}
+}
+
+impl Window {
+	pub fn raw_window_handle(&self) -> Result<raw_window_handle::RawWindowHandle, HandleError> {
+		self.window_handle().map(|handle| handle.as_raw())
+	}
+
+	pub fn raw_display_handle(&self) -> Result<raw_window_handle::RawDisplayHandle, HandleError> {
+		self.display_handle().map(|handle| handle.as_raw())
+	}
+}
@@ -0,0 +1,246 @@
+//! Text anchored in the world - nameplates, waypoint markers, editor annotations.
+//!
+//! Labels are immediate mode - add them every frame with [`WorldLabels::add`], after setting the camera with
+//! [`WorldLabels::set_camera`]. They're laid out with egui's fonts and drawn into a background egui layer at the end of
+//! the frame, so they share a font atlas with the rest of the ui, and always draw over the scene but under any windows.
+//! Positions assume the scene covers the whole window.
+
+use crate::prelude::*;
+
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LabelOrientation {
+	/// Always faces the camera.
+	Billboard,
+
+	/// Lies in the plane spanned by `right` and `up`, which should be normalized and perpendicular.
+	/// Text reads backwards from behind.
+	InPlane { right: Vec3, up: Vec3 },
+}
+
+
+#[derive(Debug, Clone)]
+pub struct WorldLabel {
+	pub text: String,
+	pub position: input::WorldPos,
+	pub orientation: LabelOrientation,
+
+	/// Height of a line of text in world units.
+	pub height: f32,
+
+	/// Billboards only - fixed height in ui points regardless of distance, overriding `height`. For markers that
+	/// should stay readable far away.
+	pub screen_height: Option<f32>,
+
+	/// Which part of the text sits on `position`.
+	pub anchor: egui::Align2,
+
+	pub color: Color,
+
+	/// Distances between which the label fades out, from fully visible to hidden.
+	pub fade_distance: Option<(f32, f32)>,
+
+	/// Hide the label if the scene blocks the line of sight to `position`. See [`WorldLabels::set_occlusion_test`].
+	pub occlusion_test: bool,
+}
+
+impl WorldLabel {
+	pub fn billboard(position: input::WorldPos, text: impl Into<String>) -> WorldLabel {
+		WorldLabel {
+			text: text.into(),
+			position,
+			orientation: LabelOrientation::Billboard,
+
+			height: 0.25,
+			screen_height: None,
+			anchor: egui::Align2::CENTER_BOTTOM,
+
+			color: Color::white(),
+			fade_distance: None,
+			occlusion_test: false,
+		}
+	}
+
+	pub fn in_plane(position: input::WorldPos, right: Vec3, up: Vec3, text: impl Into<String>) -> WorldLabel {
+		WorldLabel {
+			orientation: LabelOrientation::InPlane { right, up },
+			anchor: egui::Align2::CENTER_CENTER,
+			.. WorldLabel::billboard(position, text)
+		}
+	}
+
+	pub fn height(self, height: f32) -> Self {
+		Self { height, .. self }
+	}
+
+	pub fn screen_height(self, screen_height: f32) -> Self {
+		Self { screen_height: Some(screen_height), .. self }
+	}
+
+	pub fn anchor(self, anchor: egui::Align2) -> Self {
+		Self { anchor, .. self }
+	}
+
+	pub fn color(self, color: impl Into<Color>) -> Self {
+		Self { color: color.into(), .. self }
+	}
+
+	pub fn fade(self, start: f32, end: f32) -> Self {
+		Self { fade_distance: Some((start, end)), .. self }
+	}
+
+	pub fn occlusion_test(self) -> Self {
+		Self { occlusion_test: true, .. self }
+	}
+
+	fn opacity(&self, distance: f32) -> f32 {
+		match self.fade_distance {
+			Some((start, end)) if end > start => 1.0 - ((distance - start) / (end - start)).clamp(0.0, 1.0),
+			Some((_, end)) => if distance < end { 1.0 } else { 0.0 },
+			None => 1.0,
+		}
+	}
+}
+
+
+/// Returns true if something blocks the line between the camera and a label, given as (eye, label position).
+pub type OcclusionTest = dyn Fn(input::WorldPos, input::WorldPos) -> bool;
+
+
+/// Font size labels are laid out at before being scaled into the world - high enough that glyphs stay sharp up close.
+const LAYOUT_FONT_SIZE: f32 = 32.0;
+
+
+#[derive(Default)]
+pub struct WorldLabels {
+	labels: Vec<WorldLabel>,
+	camera: Option<(Mat4, Mat4)>,
+	occlusion_test: Option<Box<OcclusionTest>>,
+}
+
+impl WorldLabels {
+	/// `projection` should be a standard OpenGL projection - not adjusted for reverse-z.
+	pub fn set_camera(&mut self, view: Mat4, projection: Mat4) {
+		self.camera = Some((view, projection));
+	}
+
+	/// E.g., a raycast against scene collision. Only used for labels with [`WorldLabel::occlusion_test`] set.
+	pub fn set_occlusion_test(&mut self, test: impl Fn(input::WorldPos, input::WorldPos) -> bool + 'static) {
+		self.occlusion_test = Some(Box::new(test));
+	}
+
+	/// Show `label` this frame.
+	pub fn add(&mut self, label: WorldLabel) {
+		self.labels.push(label);
+	}
+
+	#[instrument(skip_all, name="toybox WorldLabels::draw")]
+	pub(crate) fn draw(&mut self, egui: &egui::Context) {
+		let mut labels = std::mem::take(&mut self.labels);
+
+		let Some((view, projection)) = self.camera else {
+			if !labels.is_empty() {
+				log::warn!("World labels added without a camera - call WorldLabels::set_camera first");
+			}
+			return
+		};
+
+		let inverse_view = view.inverse();
+		let transform = |vector: Vec4| {
+			let Vec4{x, y, z, ..} = inverse_view * vector;
+			Vec3::new(x, y, z)
+		};
+
+		let eye = input::ViewPos(Vec3::zero()).to_world(&inverse_view);
+		let camera_right = transform(Vec4::new(1.0, 0.0, 0.0, 0.0)).normalize();
+		let camera_up = transform(Vec4::new(0.0, 1.0, 0.0, 0.0)).normalize();
+
+		let projection_view = projection * view;
+		let screen_rect = egui.screen_rect();
+
+		let to_egui = |position: input::WorldPos| {
+			let ndc = position.to_clip(&projection_view).to_ndc()?;
+			Some(ndc.to_ui(screen_rect).to_egui())
+		};
+		let distance_to_eye = |position: input::WorldPos| (position.0 - eye.0).length();
+
+		// Back to front, so nearer labels draw over farther ones.
+		labels.sort_by(|a, b| distance_to_eye(b.position).total_cmp(&distance_to_eye(a.position)));
+
+		let painter = egui.layer_painter(egui::LayerId::new(egui::Order::Background, egui::Id::new("toybox_world_labels")));
+		let [atlas_width, atlas_height] = egui.fonts(|fonts| fonts.font_image_size());
+		let uv_scale = egui::vec2(1.0 / atlas_width as f32, 1.0 / atlas_height as f32);
+
+		for label in labels {
+			let opacity = label.opacity(distance_to_eye(label.position));
+			if opacity <= 0.0 {
+				continue
+			}
+
+			if label.occlusion_test && self.occlusion_test.as_ref().is_some_and(|test| test(eye, label.position)) {
+				continue
+			}
+
+			let [r, g, b, a] = label.color.to_srgb8();
+			let color = egui::Color32::from_rgba_unmultiplied(r, g, b, a).gamma_multiply(opacity);
+
+			let (right, up) = match label.orientation {
+				LabelOrientation::Billboard => {
+					if let Some(screen_height) = label.screen_height {
+						let Some(position) = to_egui(label.position) else { continue };
+
+						let galley = painter.layout_no_wrap(label.text, egui::FontId::proportional(screen_height), color);
+						let rect = label.anchor.anchor_size(position, galley.size());
+						painter.galley(rect.min, galley, color);
+						continue
+					}
+
+					(camera_right, camera_up)
+				}
+
+				LabelOrientation::InPlane { right, up } => (right, up),
+			};
+
+			let galley = painter.layout_no_wrap(label.text, egui::FontId::proportional(LAYOUT_FONT_SIZE), color);
+
+			// Galley space is in points with y down - map it onto the label plane so that the anchor lands on position.
+			let row_height = galley.rows.first().map_or(LAYOUT_FONT_SIZE, |row| row.rect.height());
+			let scale = label.height / row_height.max(1.0);
+			let anchor = label.anchor.pos_in_rect(&egui::Rect::from_min_size(egui::Pos2::ZERO, galley.size()));
+			let to_world = |point: egui::Pos2| input::WorldPos(label.position.0
+				+ right * ((point.x - anchor.x) * scale)
+				+ up * ((anchor.y - point.y) * scale));
+
+			let mut mesh = egui::Mesh::default();
+			let vertices = galley.rows.iter()
+				.flat_map(|row| row.visuals.mesh.vertices.iter());
+
+			for vertex in vertices {
+				// Skip the whole label if any part of it is behind the camera.
+				let Some(pos) = to_egui(to_world(vertex.pos)) else {
+					mesh.clear();
+					break
+				};
+
+				mesh.vertices.push(egui::epaint::Vertex {
+					pos,
+					uv: (vertex.uv.to_vec2() * uv_scale).to_pos2(),
+					color,
+				});
+			}
+
+			if mesh.vertices.is_empty() {
+				continue
+			}
+
+			let mut index_offset = 0;
+			for row in galley.rows.iter() {
+				let row_mesh = &row.visuals.mesh;
+				mesh.indices.extend(row_mesh.indices.iter().map(|index| index + index_offset));
+				index_offset += row_mesh.vertices.len() as u32;
+			}
+
+			painter.add(egui::Shape::mesh(mesh));
+		}
+	}
+}
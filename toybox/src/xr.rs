@@ -0,0 +1,386 @@
+//! Experimental VR output through OpenXR. Enabled with the `xr` feature and [`host::Settings::xr`].
+//!
+//! Both eyes are rendered in a single pass into a two layer array rendertarget using [`gfx::MultiView`], which is
+//! copied to the runtime's swapchain at the end of the frame. The desktop window keeps rendering as normal.
+//!
+//! Controllers are routed into the gamepad buttons and sticks of [`input::Tracker`], so gamepad bindings work
+//! unchanged in VR - see [`XrHand::gamepad_buttons`] for the mapping. Poses and analog values are available
+//! from [`Xr::hand`].
+//!
+//! ```rust ignore
+//! if let Some(mut group) = ctx.xr.as_ref().and_then(|xr| xr.begin_eyes(&mut ctx.gfx)) {
+//!     let xr = ctx.xr.as_ref().unwrap();
+//!     group.draw(xr.multi_view().vertex_shader(), fragment_shader)
+//!         .elements(...)
+//!         .instances(count * xr.multi_view().num_views() as u32);
+//! }
+//! ```
+
+use crate::prelude::*;
+use crate::Context;
+
+use host::xr::{XrSession, XrFrame, XrHandState, openxr};
+
+
+/// Eyes are encoded in `FrameStage::BeforeMain(XR_EYE_STAGE)`.
+pub const XR_EYE_STAGE: i8 = -64;
+
+
+/// A position and orientation in stage space - the tracked play area, with y up and the floor at 0.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct XrPose {
+	pub position: Vec3,
+
+	/// Quaternion, as xyzw.
+	pub orientation: Vec4,
+}
+
+impl XrPose {
+	fn from_xr(pose: openxr::Posef) -> XrPose {
+		let openxr::Posef { position: p, orientation: q } = pose;
+
+		XrPose {
+			position: Vec3::new(p.x, p.y, p.z),
+			orientation: Vec4::new(q.x, q.y, q.z, q.w),
+		}
+	}
+
+	pub fn to_mat4(&self) -> Mat4 {
+		let Vec4{x, y, z, w} = self.orientation;
+		let p = self.position;
+
+		Mat4::from_rows([
+			Vec4::new(1.0 - 2.0*(y*y + z*z), 2.0*(x*y - z*w), 2.0*(x*z + y*w), p.x),
+			Vec4::new(2.0*(x*y + z*w), 1.0 - 2.0*(x*x + z*z), 2.0*(y*z - x*w), p.y),
+			Vec4::new(2.0*(x*z - y*w), 2.0*(y*z + x*w), 1.0 - 2.0*(x*x + y*y), p.z),
+			Vec4::new(0.0, 0.0, 0.0, 1.0),
+		])
+	}
+
+	/// Direction the pose is pointing in - -Z, following OpenXR conventions.
+	pub fn forward(&self) -> Vec3 {
+		let Vec4{x, y, z, w} = self.orientation;
+		-Vec3::new(2.0*(x*z + y*w), 2.0*(y*z - x*w), 1.0 - 2.0*(x*x + y*y))
+	}
+}
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct XrEye {
+	pub pose: XrPose,
+
+	/// Standard OpenGL projection - not adjusted for reverse-z.
+	pub projection: Mat4,
+}
+
+
+/// Controller state for one hand. Poses are in stage space, see [`Xr::stage_to_world`].
+#[derive(Debug, Clone, Default)]
+pub struct XrHand {
+	/// Where the controller is held.
+	pub grip: Option<XrPose>,
+
+	/// Where the controller points, for pointing and aiming.
+	pub aim: Option<XrPose>,
+
+	pub trigger: f32,
+	pub squeeze: f32,
+	pub thumbstick: Vec2,
+
+	/// A/X
+	pub primary: bool,
+
+	/// B/Y
+	pub secondary: bool,
+
+	pub menu: bool,
+}
+
+/// Analog triggers and squeezes count as pressed past this.
+const XR_BUTTON_THRESHOLD: f32 = 0.5;
+
+impl XrHand {
+	/// The gamepad buttons this hand is routed to, and whether each is pressed. Face buttons follow their position on
+	/// a gamepad - A/B on the right hand are South/East, X/Y on the left hand are West/North.
+	pub fn gamepad_buttons(&self, side: XrHandSide) -> [(input::GamepadButton, bool); 5] {
+		use input::GamepadButton as B;
+
+		let trigger = self.trigger > XR_BUTTON_THRESHOLD;
+		let squeeze = self.squeeze > XR_BUTTON_THRESHOLD;
+
+		match side {
+			XrHandSide::Left => [
+				(B::West, self.primary),
+				(B::North, self.secondary),
+				(B::LeftTrigger, trigger),
+				(B::LeftShoulder, squeeze),
+				(B::Start, self.menu),
+			],
+
+			XrHandSide::Right => [
+				(B::South, self.primary),
+				(B::East, self.secondary),
+				(B::RightTrigger, trigger),
+				(B::RightShoulder, squeeze),
+				(B::Select, self.menu),
+			],
+		}
+	}
+
+	pub fn gamepad_stick(side: XrHandSide) -> input::GamepadStick {
+		match side {
+			XrHandSide::Left => input::GamepadStick::Left,
+			XrHandSide::Right => input::GamepadStick::Right,
+		}
+	}
+
+	fn from_xr(state: &XrHandState) -> XrHand {
+		XrHand {
+			grip: state.grip.map(XrPose::from_xr),
+			aim: state.aim.map(XrPose::from_xr),
+			trigger: state.trigger,
+			squeeze: state.squeeze,
+			thumbstick: Vec2::new(state.thumbstick.0, state.thumbstick.1),
+			primary: state.primary,
+			secondary: state.secondary,
+			menu: state.menu,
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum XrHandSide {
+	Left,
+	Right,
+}
+
+
+pub struct Xr {
+	/// Places the play area in the world, e.g., to move the player around.
+	pub stage_to_world: Mat4,
+
+	pub near: f32,
+	pub far: f32,
+
+	/// Color the eye targets are cleared to at the start of each [`Xr::begin_eyes`].
+	pub clear_color: Option<Color>,
+
+	session: XrSession,
+	frame: Option<XrFrame>,
+
+	eyes: Vec<XrEye>,
+	hands: [XrHand; 2],
+
+	multi_view: gfx::MultiView,
+	eye_size: Vec2i,
+	color_image: gfx::ImageHandle,
+	depth_image: gfx::ImageHandle,
+}
+
+impl Xr {
+	pub(crate) fn new(session: XrSession, gfx: &mut gfx::System) -> Xr {
+		let (width, height) = session.eye_size();
+		let eye_size = Vec2i::new(width as i32, height as i32);
+		let num_eyes = host::xr::NUM_EYES as u32;
+
+		let rm = &mut gfx.resource_manager;
+		let color_image = rm.request(gfx::CreateImageRequest::fixed_2d_array("xr eyes color", eye_size, num_eyes, gfx::ImageFormat::Srgba8));
+		let depth_image = rm.request(gfx::CreateImageRequest::fixed_2d_array("xr eyes depth", eye_size, num_eyes, gfx::ImageFormat::Depth)
+			.clear_policy(gfx::ImageClearPolicy::DefaultAtFrameStart));
+
+		Xr {
+			stage_to_world: Mat4::identity(),
+			near: 0.05,
+			far: 1000.0,
+			clear_color: Some(Color::black()),
+
+			session,
+			frame: None,
+
+			eyes: Vec::new(),
+			hands: Default::default(),
+
+			multi_view: gfx::MultiView::with_layers(gfx, host::xr::NUM_EYES),
+			eye_size,
+			color_image,
+			depth_image,
+		}
+	}
+
+	/// Whether eyes should be rendered this frame. False while the headset isn't being worn, or the session
+	/// hasn't started yet.
+	pub fn is_rendering(&self) -> bool {
+		self.frame.as_ref().is_some_and(XrFrame::should_render)
+	}
+
+	pub fn eye_size(&self) -> Vec2i {
+		self.eye_size
+	}
+
+	/// Eye poses and projections for this frame. Empty if [`Xr::is_rendering`] is false.
+	pub fn eyes(&self) -> &[XrEye] {
+		&self.eyes
+	}
+
+	pub fn hand(&self, side: XrHandSide) -> &XrHand {
+		match side {
+			XrHandSide::Left => &self.hands[0],
+			XrHandSide::Right => &self.hands[1],
+		}
+	}
+
+	/// World space transform for a stage space pose, e.g., for drawing controllers.
+	pub fn pose_to_world(&self, pose: &XrPose) -> Mat4 {
+		self.stage_to_world * pose.to_mat4()
+	}
+
+	/// Set up with both eyes' projection views, routed to layers of the eye targets.
+	pub fn multi_view(&self) -> &gfx::MultiView {
+		&self.multi_view
+	}
+
+	/// Layered color target containing both eyes.
+	pub fn eye_image(&self) -> gfx::ImageHandle {
+		self.color_image
+	}
+
+	pub fn eye_depth_image(&self) -> gfx::ImageHandle {
+		self.depth_image
+	}
+
+	/// Start encoding commands for both eyes, with the eye targets bound and [`gfx::multi_view::MultiViewUniforms`]
+	/// bound to UBO 0. Returns None if eyes shouldn't be rendered this frame.
+	/// Should only be called once per frame, since it clears the targets.
+	pub fn begin_eyes<'g>(&self, gfx: &'g mut gfx::System) -> Option<gfx::AnnotatedCommandGroupEncoder<'g>> {
+		if !self.is_rendering() {
+			return None
+		}
+
+		let mut group = gfx.frame_encoder.command_group(gfx::FrameStage::BeforeMain(XR_EYE_STAGE))
+			.annotate("xr eyes");
+
+		if let Some(clear_color) = self.clear_color {
+			let color_image = self.color_image;
+			group.execute(move |core, rm| {
+				if let Some(image_name) = rm.images.get_name(color_image) {
+					core.clear_image_with_color(image_name, clear_color);
+				}
+			});
+		}
+
+		group.bind_rendertargets(gfx::FramebufferDescription::from(&[self.color_image, self.depth_image]));
+		self.multi_view.bind(&mut group);
+
+		Some(group)
+	}
+
+	#[instrument(skip_all, name="toybox Xr::start_frame")]
+	fn start_frame(&mut self, core: &gfx::Core) -> anyhow::Result<()> {
+		self.session.poll_events()?;
+
+		self.eyes.clear();
+		self.frame = self.session.begin_frame()?;
+
+		let Some(frame) = &self.frame else {
+			self.hands = Default::default();
+			return Ok(())
+		};
+
+		self.hands = self.session.sync_hands(frame)?
+			.each_ref()
+			.map(XrHand::from_xr);
+
+		if !frame.should_render() {
+			return Ok(())
+		}
+
+		for (index, view) in frame.views.iter().enumerate() {
+			let eye = XrEye {
+				pose: XrPose::from_xr(view.pose),
+				projection: fov_projection(view.fov, self.near, self.far),
+			};
+
+			let world_to_eye = (self.stage_to_world * eye.pose.to_mat4()).inverse();
+			self.multi_view.set_projection_view(index, core.apply_depth_convention(eye.projection) * world_to_eye);
+
+			self.eyes.push(eye);
+		}
+
+		Ok(())
+	}
+
+	#[instrument(skip_all, name="toybox Xr::end_frame")]
+	fn end_frame(&mut self, gfx: &gfx::System) -> anyhow::Result<()> {
+		let Some(frame) = self.frame.take() else { return Ok(()) };
+
+		let source = gfx.resource_manager.images.get_name(self.color_image)
+			.map(|name| name.as_raw());
+
+		self.session.end_frame(&gfx.core.gl, frame, source)
+	}
+}
+
+
+/// Asymmetric OpenGL projection for an OpenXR field of view.
+fn fov_projection(fov: openxr::Fovf, near: f32, far: f32) -> Mat4 {
+	let left = fov.angle_left.tan();
+	let right = fov.angle_right.tan();
+	let up = fov.angle_up.tan();
+	let down = fov.angle_down.tan();
+
+	let width = right - left;
+	let height = up - down;
+
+	Mat4::from_rows([
+		Vec4::new(2.0 / width, 0.0, (right + left) / width, 0.0),
+		Vec4::new(0.0, 2.0 / height, (up + down) / height, 0.0),
+		Vec4::new(0.0, 0.0, (far + near) / (near - far), 2.0 * far * near / (near - far)),
+		Vec4::new(0.0, 0.0, -1.0, 0.0),
+	])
+}
+
+
+impl Context {
+	pub(crate) fn start_xr_frame(&mut self) {
+		let Some(xr) = &mut self.xr else { return };
+
+		let previous_hands = xr.hands.clone();
+
+		if let Err(error) = xr.start_frame(&self.gfx.core) {
+			log::error!("XR frame failed - disabling XR: {error:?}");
+			self.xr = None;
+			return
+		}
+
+		// Only changes are tracked, so that controllers don't release buttons or recenter sticks held on a gamepad.
+		for (side, index) in [(XrHandSide::Left, 0), (XrHandSide::Right, 1)] {
+			let (previous, current) = (&previous_hands[index], &xr.hands[index]);
+
+			let buttons = previous.gamepad_buttons(side).into_iter().zip(current.gamepad_buttons(side));
+			for ((_, was_down), (button, down)) in buttons {
+				if was_down != down {
+					self.input.tracker.track_button(button, down);
+				}
+			}
+
+			if previous.thumbstick != current.thumbstick {
+				self.input.tracker.track_gamepad_stick(XrHand::gamepad_stick(side), current.thumbstick);
+			}
+		}
+
+		if xr.session.exit_requested() {
+			log::info!("XR runtime requested exit");
+			self.wants_quit = true;
+		}
+	}
+
+	pub(crate) fn end_xr_frame(&mut self) {
+		let Some(xr) = &mut self.xr else { return };
+
+		if let Err(error) = xr.end_frame(&self.gfx) {
+			log::error!("XR frame failed - disabling XR: {error:?}");
+			self.xr = None;
+		}
+	}
+}